@@ -0,0 +1,97 @@
+//! Benchmarks of the paths every update or price tick goes through, run with
+//! `cargo bench` before and after a performance motivated refactoring
+use chrono::{Duration, Local};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use currency_bot::bot_modules::convert::Conversion;
+use currency_bot::bot_modules::{
+    AnalyticsModule, Args, BotCommandsModule, ChartModule, ConvertModule, EchoModule,
+    ParsedCommand, PriceModule, StartModule,
+};
+use currency_bot::domain::{get_all_currency_pairs, CurrencyPair};
+use currency_bot::price_service::history::{PriceHistory, Resolution, RetentionPolicy};
+use currency_bot::ModuleRegistry;
+
+fn registry() -> ModuleRegistry {
+    let mut registry = ModuleRegistry::new();
+    registry.register(Box::new(StartModule));
+    registry.register(Box::new(PriceModule));
+    registry.register(Box::new(ConvertModule));
+    registry.register(Box::new(ChartModule));
+    registry.register(Box::new(AnalyticsModule));
+    registry.register(Box::new(BotCommandsModule::new(EchoModule)));
+    registry
+}
+
+fn command_routing(c: &mut Criterion) {
+    let registry = registry();
+    let texts = ["/start", "/price USD/RUB", "/echo hello", "/unknown 1 2"];
+    c.bench_function("command_routing", |b| {
+        b.iter(|| {
+            for text in texts {
+                let command = ParsedCommand::parse(black_box(text)).unwrap();
+                black_box(registry.find_module(command.name));
+            }
+        })
+    });
+}
+
+fn pair_parsing(c: &mut Criterion) {
+    c.bench_function("pair_argument", |b| {
+        b.iter(|| {
+            for pair in ["USD/RUB", "USDTe/RUB", "USDCe/RUB", "EUR/RUB"] {
+                black_box(Args::new(black_box(pair)).pair().ok());
+            }
+        })
+    });
+    c.bench_function("conversion_question", |b| {
+        b.iter(|| {
+            black_box(Conversion::parse(black_box(
+                "сколько стоит 1500,5 usdt в рублях",
+            )));
+        })
+    });
+}
+
+/// A week of minute samples for every pair
+fn week_of_history() -> PriceHistory {
+    let history = PriceHistory::new(RetentionPolicy::default());
+    let start = Local::now() - Duration::days(7);
+    for minute in 0..7 * 24 * 60 {
+        let time = start + Duration::minutes(minute);
+        for pair in get_all_currency_pairs() {
+            history.record(&pair, 90.0 + (minute % 120) as f64 / 10.0, time);
+        }
+    }
+    history
+}
+
+fn history_aggregation(c: &mut Criterion) {
+    let history = week_of_history();
+    let pair = CurrencyPair::USD2RUB;
+    c.bench_function("history_ohlc_day", |b| {
+        b.iter(|| black_box(history.ohlc(&pair, Local::now() - Duration::days(1))))
+    });
+    c.bench_function("history_range_week", |b| {
+        b.iter(|| black_box(history.range(&pair, Local::now() - Duration::days(7)).len()))
+    });
+    c.bench_function("history_compact", |b| {
+        b.iter_batched(
+            || {
+                let history = PriceHistory::new(RetentionPolicy {
+                    raw: Duration::days(1),
+                    ..RetentionPolicy::default()
+                });
+                history.import(week_of_history().export());
+                history
+            },
+            |history| {
+                history.compact(Local::now());
+                black_box(history.candles(&pair, Resolution::Hour).len())
+            },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(benches, command_routing, pair_parsing, history_aggregation);
+criterion_main!(benches);
@@ -0,0 +1,56 @@
+//! Benchmark of finding a symbol's quote in NewLine's city data, which grows
+//! with every city and exchange direction the API returns
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use currency_bot::price_service::providers::newline_provider::{
+    NewLineCityData, NewLineExchange, NewLineToData,
+};
+use currency_bot::price_service::providers::{NewLineConfig, NewLineProvider};
+
+const CURRENCIES: [&str; 6] = ["CASHRUB", "CASHUSD", "USDTERC", "USDTTRC", "BTC", "ETH"];
+
+/// Every direction between the currencies in `cities` cities, the preferred
+/// one last
+fn city_data(cities: usize) -> Vec<NewLineCityData> {
+    (0..cities)
+        .map(|city| NewLineCityData {
+            city_code: if city + 1 == cities {
+                "msk".to_string()
+            } else {
+                format!("city{}", city)
+            },
+            data: CURRENCIES
+                .iter()
+                .map(|from| NewLineExchange {
+                    from_: from.to_string(),
+                    to_data: CURRENCIES
+                        .iter()
+                        .filter(|to| *to != from)
+                        .map(|to| NewLineToData {
+                            course_from: 1.0,
+                            course_to: 92.5,
+                            to: to.to_string(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+fn symbol_lookup(c: &mut Criterion) {
+    let provider = NewLineProvider::new(
+        NewLineConfig {
+            base_url: "http://localhost".to_string(),
+            cookie: String::new(),
+            preferred_city: "msk".to_string(),
+        },
+        reqwest::Client::new(),
+    );
+    let data = city_data(50);
+    c.bench_function("newline_symbol_lookup", |b| {
+        b.iter(|| black_box(provider.find_price_in_city_data(&data, black_box("ETH_TO_CASHUSD"))))
+    });
+}
+
+criterion_group!(benches, symbol_lookup);
+criterion_main!(benches);
@@ -0,0 +1,562 @@
+// HTTP API module
+// Serves the quotes the bot has seen as JSON, for dashboards and scripts,
+// streams fresh ones over a WebSocket, serves the calendar feeds of the chats
+// and answers the health checks of the process
+
+use crate::bot_modules::TelegramBot;
+use crate::calendar::CalendarFeeds;
+use crate::domain::{get_all_currency_pairs, CurrencyPair};
+use crate::price_service::history::{PriceChange, PricePoint};
+use crate::price_service::service::{ProviderStats, PROVIDER_DOWN_AFTER_ERRORS};
+use crate::price_service::PriceService;
+use crate::startup::check_store;
+use chrono::Local;
+use futures::{SinkExt, StreamExt};
+use hyper::header::{
+    CONNECTION, CONTENT_TYPE, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION,
+    UPGRADE,
+};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::upgrade::Upgraded;
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::prelude::*;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::WebSocketStream;
+use tokio_util::sync::CancellationToken;
+
+/// How long `/readyz` waits for the Telegram API
+const TELEGRAM_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What the API serves from and checks for readiness
+pub struct ApiState {
+    pub price_service: Arc<PriceService>,
+    pub bot: TelegramBot,
+    /// Subscriptions state file (`SUBSCRIBERS_FILE`), if persistence is on
+    pub store: Option<PathBuf>,
+    pub calendar: CalendarFeeds,
+}
+
+/// Latest known quote of a pair
+#[derive(Debug, Serialize)]
+struct Quote {
+    pair: String,
+    #[serde(flatten)]
+    point: PricePoint,
+    /// Against the quote recorded before it
+    change: Option<PriceChange>,
+}
+
+/// Pair as written in URLs, `USD/RUB` becomes `USD-RUB`
+fn pair_slug(pair: &CurrencyPair) -> String {
+    pair.to_string().replace('/', "-")
+}
+
+fn parse_pair_slug(slug: &str) -> Option<CurrencyPair> {
+    get_all_currency_pairs()
+        .into_iter()
+        .find(|pair| pair_slug(pair).eq_ignore_ascii_case(slug))
+}
+
+fn quote(price_service: &PriceService, pair: &CurrencyPair) -> Option<Quote> {
+    let point = price_service.history().latest(pair)?;
+    Some(Quote {
+        pair: pair.to_string(),
+        point,
+        change: price_service.last_change(pair),
+    })
+}
+
+fn error(status: StatusCode, message: &str) -> (StatusCode, Value) {
+    (status, json!({ "error": message }))
+}
+
+/// Answer a request from the price history only, so that polling the API
+/// never reaches the providers
+fn route(price_service: &PriceService, method: &Method, path: &str) -> (StatusCode, Value) {
+    if method != Method::GET {
+        return error(StatusCode::METHOD_NOT_ALLOWED, "Only GET is supported");
+    }
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["api", "price", slug] => {
+            let Some(pair) = parse_pair_slug(slug) else {
+                return error(StatusCode::NOT_FOUND, "Unknown currency pair");
+            };
+            match quote(price_service, &pair) {
+                Some(quote) => (StatusCode::OK, json!(quote)),
+                None => error(StatusCode::NOT_FOUND, "No quote fetched yet"),
+            }
+        }
+        ["api", "analytics", slug] => {
+            let Some(pair) = parse_pair_slug(slug) else {
+                return error(StatusCode::NOT_FOUND, "Unknown currency pair");
+            };
+            let indicators = price_service.history().analytics().indicators(&pair);
+            match indicators.is_empty() {
+                true => error(StatusCode::NOT_FOUND, "No quote fetched yet"),
+                false => (
+                    StatusCode::OK,
+                    json!({
+                        "pair": pair.to_string(),
+                        "indicators": indicators,
+                        "watermarks": price_service.history().analytics().watermarks(&pair),
+                    }),
+                ),
+            }
+        }
+        ["api", "rates"] => {
+            let rates: Vec<Quote> = get_all_currency_pairs()
+                .iter()
+                .filter_map(|pair| quote(price_service, pair))
+                .collect();
+            (StatusCode::OK, json!({ "rates": rates }))
+        }
+        ["api", "providers"] => {
+            let providers: Vec<Value> = price_service
+                .provider_stats()
+                .into_iter()
+                .map(|(name, stats)| json!({ "name": name, "requests": stats.requests, "errors": stats.errors, "schema_drift": stats.schema_drift }))
+                .collect();
+            (StatusCode::OK, json!({ "providers": providers }))
+        }
+        _ => error(StatusCode::NOT_FOUND, "Not found"),
+    }
+}
+
+/// Ready when at least one provider used so far is not failing, or when
+/// none has been asked yet so a fresh start isn't restarted before polling
+fn check_providers(stats: &[(String, ProviderStats)]) -> Result<(), String> {
+    let failing: Vec<&str> = stats
+        .iter()
+        .filter(|(_, stats)| stats.consecutive_errors >= PROVIDER_DOWN_AFTER_ERRORS)
+        .map(|(name, _)| name.as_str())
+        .collect();
+    if !stats.is_empty() && failing.len() == stats.len() {
+        return Err(format!("All providers are failing: {}", failing.join(", ")));
+    }
+    Ok(())
+}
+
+async fn check_telegram(bot: &TelegramBot) -> Result<(), String> {
+    match tokio::time::timeout(TELEGRAM_CHECK_TIMEOUT, bot.get_me()).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("Timed out".to_string()),
+    }
+}
+
+fn check_json(result: &Result<(), String>) -> Value {
+    match result {
+        Ok(()) => json!({ "ok": true }),
+        Err(e) => json!({ "ok": false, "error": e }),
+    }
+}
+
+/// `/readyz`: Telegram is reachable, a provider is healthy and the state file
+/// can be written
+async fn readiness(state: &ApiState) -> (StatusCode, Value) {
+    let telegram = check_telegram(&state.bot).await;
+    let providers = check_providers(&state.price_service.provider_stats());
+    let store = state.store.as_deref().map(check_store).unwrap_or(Ok(()));
+    let ready = telegram.is_ok() && providers.is_ok() && store.is_ok();
+    let status = match ready {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    let body = json!({
+        "ready": ready,
+        "checks": {
+            "telegram": check_json(&telegram),
+            "providers": check_json(&providers),
+            "store": check_json(&store),
+        },
+    });
+    (status, body)
+}
+
+/// `Sec-WebSocket-Accept` answering a WebSocket handshake request
+fn websocket_accept(request: &Request<Body>) -> Result<String, &'static str> {
+    let headers = request.headers();
+    let lists = |name, token: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| {
+                value
+                    .split(',')
+                    .any(|item| item.trim().eq_ignore_ascii_case(token))
+            })
+    };
+    if !lists(CONNECTION, "upgrade") || !lists(UPGRADE, "websocket") {
+        return Err("Expected a WebSocket upgrade");
+    }
+    if !lists(SEC_WEBSOCKET_VERSION, "13") {
+        return Err("Unsupported WebSocket version");
+    }
+    let key = headers
+        .get(SEC_WEBSOCKET_KEY)
+        .ok_or("Missing Sec-WebSocket-Key")?;
+    Ok(derive_accept_key(key.as_bytes()))
+}
+
+/// Send the latest quote of every pair, then every fresh quote as the bot
+/// records it, until the client disconnects or the server stops
+async fn stream_quotes(
+    mut socket: WebSocketStream<Upgraded>,
+    price_service: &PriceService,
+    shutdown: CancellationToken,
+) {
+    // Subscribed first so no quote recorded meanwhile is missed
+    let mut updates = price_service.subscribe();
+    let latest: Vec<Quote> = get_all_currency_pairs()
+        .iter()
+        .filter_map(|pair| quote(price_service, pair))
+        .collect();
+    for latest in latest {
+        if socket
+            .send(WsMessage::Text(json!(latest).to_string()))
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => {
+                if let Err(e) = socket.close(None).await {
+                    tracing::debug!("Failed to close WebSocket: {}", e);
+                }
+                return;
+            }
+            update = updates.recv() => match update {
+                Ok(pair) => {
+                    let Some(fresh) = quote(price_service, &pair) else {
+                        continue;
+                    };
+                    if socket.send(WsMessage::Text(json!(fresh).to_string())).await.is_err() {
+                        return;
+                    }
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::debug!("WebSocket client is too slow, skipped {} quotes", missed)
+                }
+                Err(RecvError::Closed) => return,
+            },
+            // Pings are answered by tungstenite, anything else the client sends is ignored
+            message = socket.next() => match message {
+                Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+/// `/ws`: switch the connection to the WebSocket protocol and stream quotes
+fn websocket(
+    state: &ApiState,
+    mut request: Request<Body>,
+    shutdown: CancellationToken,
+) -> Response<Body> {
+    let accept = match websocket_accept(&request) {
+        Ok(accept) => accept,
+        Err(message) => return json_response(error(StatusCode::BAD_REQUEST, message)),
+    };
+    let price_service = Arc::clone(&state.price_service);
+    tokio::spawn(async move {
+        match hyper::upgrade::on(&mut request).await {
+            Ok(upgraded) => {
+                let socket = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+                stream_quotes(socket, &price_service, shutdown).await;
+            }
+            Err(e) => tracing::warn!("WebSocket upgrade failed: {}", e),
+        }
+    });
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(CONNECTION, "Upgrade")
+        .header(UPGRADE, "websocket")
+        .header(SEC_WEBSOCKET_ACCEPT, accept)
+        .body(Body::empty())
+        .expect("static response parts are valid")
+}
+
+fn json_response((status, body): (StatusCode, Value)) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .expect("static response parts are valid")
+}
+
+/// `/calendar/<token>.ics`: iCalendar feed of the messages a chat gets on
+/// schedule, the token being the one `/calendar` gave the chat
+fn calendar(state: &ApiState, path: &str) -> Response<Body> {
+    let feed = path
+        .strip_prefix("/calendar/")
+        .and_then(|name| name.strip_suffix(".ics"))
+        .and_then(|token| state.calendar.render(token, Local::now()));
+    match feed {
+        Some(feed) => Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "text/calendar; charset=utf-8")
+            .body(Body::from(feed))
+            .expect("static response parts are valid"),
+        None => json_response(error(StatusCode::NOT_FOUND, "Unknown calendar")),
+    }
+}
+
+async fn handle(
+    state: &ApiState,
+    request: Request<Body>,
+    shutdown: &CancellationToken,
+) -> Response<Body> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/ws") => return websocket(state, request, shutdown.clone()),
+        (&Method::GET, "/healthz") => (StatusCode::OK, json!({ "status": "ok" })),
+        (&Method::GET, "/readyz") => readiness(state).await,
+        (&Method::GET, path) if path.starts_with("/calendar/") => return calendar(state, path),
+        (method, path) => route(&state.price_service, method, path),
+    };
+    json_response(response)
+}
+
+/// Serve the API on `addr` until `shutdown` is cancelled, which also closes
+/// the WebSocket connections
+pub async fn serve(
+    addr: SocketAddr,
+    state: Arc<ApiState>,
+    shutdown: CancellationToken,
+) -> Result<(), hyper::Error> {
+    let sockets_shutdown = shutdown.clone();
+    let make_service = make_service_fn(move |_| {
+        let state = Arc::clone(&state);
+        let shutdown = sockets_shutdown.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let state = Arc::clone(&state);
+                let shutdown = shutdown.clone();
+                async move { Ok::<_, Infallible>(handle(&state, request, &shutdown).await) }
+            }))
+        }
+    });
+    let server = Server::try_bind(&addr)?.serve(make_service);
+    tracing::info!("HTTP API listening on {}", server.local_addr());
+    server
+        .with_graceful_shutdown(async move { shutdown.cancelled().await })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot_modules::scheduler::{Job, JobSchedule, Scheduler};
+    use crate::bot_modules::subscribers::SubscriberManager;
+    use crate::price_service::providers::fixed::FixedProvider;
+    use teloxide::adaptors::throttle::Limits;
+    use tokio::net::TcpStream;
+
+    #[test]
+    fn test_parse_pair_slug() {
+        assert_eq!(parse_pair_slug("USD-RUB"), Some(CurrencyPair::USD2RUB));
+        assert_eq!(parse_pair_slug("usdte-rub"), Some(CurrencyPair::USDTe2RUB));
+        assert_eq!(parse_pair_slug("USD/RUB"), None);
+        assert_eq!(parse_pair_slug("EUR-RUB"), None);
+    }
+
+    #[test]
+    fn test_route_serves_history() {
+        let price_service = PriceService::new();
+        let (status, _) = route(&price_service, &Method::GET, "/api/price/USD-RUB");
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let history = price_service.history();
+        history.record(&CurrencyPair::USD2RUB, 90.0, Local::now());
+        history.record(&CurrencyPair::USD2RUB, 91.0, Local::now());
+        let (status, body) = route(&price_service, &Method::GET, "/api/price/USD-RUB");
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["pair"], "USD/RUB");
+        assert_eq!(body["price"], 91.0);
+        assert_eq!(body["change"]["delta"], 1.0);
+
+        let (status, body) = route(&price_service, &Method::GET, "/api/rates/");
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["rates"].as_array().unwrap().len(), 1);
+
+        let (status, body) = route(&price_service, &Method::GET, "/api/analytics/usd-rub");
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["indicators"][0]["window_minutes"], 60);
+        assert_eq!(body["indicators"][0]["sma"], 90.5);
+        assert_eq!(body["watermarks"]["rolling"]["max"], 91.0);
+        let (status, _) = route(&price_service, &Method::GET, "/api/analytics/USDTe-RUB");
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, body) = route(&price_service, &Method::GET, "/api/providers");
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["providers"], json!([]));
+    }
+
+    #[test]
+    fn test_check_providers() {
+        let stats = |consecutive_errors| ProviderStats {
+            requests: 10,
+            errors: consecutive_errors,
+            consecutive_errors,
+            consecutive_auth_failures: 0,
+            schema_drift: 0,
+        };
+        assert!(check_providers(&[]).is_ok());
+        assert!(check_providers(&[("NewLine".to_string(), stats(2))]).is_ok());
+        assert!(check_providers(&[("NewLine".to_string(), stats(3))]).is_err());
+        assert!(check_providers(&[
+            ("NewLine".to_string(), stats(5)),
+            ("Backup".to_string(), stats(0)),
+        ])
+        .is_ok());
+    }
+
+    #[test]
+    fn test_route_rejects_unknown_requests() {
+        let price_service = PriceService::new();
+        let cases = [
+            (Method::GET, "/api/price/EUR-RUB", StatusCode::NOT_FOUND),
+            (Method::GET, "/api/unknown", StatusCode::NOT_FOUND),
+            (Method::POST, "/api/rates", StatusCode::METHOD_NOT_ALLOWED),
+        ];
+        for (method, path, expected) in cases {
+            let (status, body) = route(&price_service, &method, path);
+            assert_eq!(status, expected, "{} {}", method, path);
+            assert!(body["error"].is_string());
+        }
+    }
+
+    #[test]
+    fn test_websocket_accept() {
+        let request = |upgrade: &str, version: &str| {
+            Request::get("/ws")
+                .header(CONNECTION, "keep-alive, Upgrade")
+                .header(UPGRADE, upgrade)
+                .header(SEC_WEBSOCKET_VERSION, version)
+                .header(SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+                .body(Body::empty())
+                .unwrap()
+        };
+        // The example handshake of RFC 6455
+        assert_eq!(
+            websocket_accept(&request("websocket", "13")).as_deref(),
+            Ok("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+        );
+        assert!(websocket_accept(&request("h2c", "13")).is_err());
+        assert!(websocket_accept(&request("websocket", "8")).is_err());
+        let plain = Request::get("/ws").body(Body::empty()).unwrap();
+        assert!(websocket_accept(&plain).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_calendar_serves_the_feed_of_a_token() {
+        let manager = Arc::new(SubscriberManager::new("Test message".to_string()));
+        let token = manager.get_calendar_token(ChatId(42));
+        let mut scheduler = Scheduler::new(CancellationToken::new());
+        let broadcast = scheduler.register(Job::new(
+            "broadcast",
+            JobSchedule::from_minutes(10),
+            || async {},
+        ));
+        let state = ApiState {
+            price_service: Arc::new(PriceService::new()),
+            bot: Bot::new("123:abc").throttle(Limits::default()),
+            store: None,
+            calendar: CalendarFeeds {
+                bots: vec![(manager, broadcast)],
+                summary: None,
+                weekly_report: None,
+            },
+        };
+        let response = calendar(&state, &format!("/calendar/{}.ics", token));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[CONTENT_TYPE],
+            "text/calendar; charset=utf-8"
+        );
+        for path in [
+            "/calendar/unknown.ics".to_string(),
+            format!("/calendar/{}", token),
+        ] {
+            assert_eq!(calendar(&state, &path).status(), StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_websocket_streams_quotes() {
+        let mut price_service = PriceService::new();
+        price_service.add_provider(Arc::new(FixedProvider::new(92.0)));
+        let price_service = Arc::new(price_service);
+        price_service
+            .history()
+            .record(&CurrencyPair::USD2RUB, 90.0, Local::now());
+        let state = Arc::new(ApiState {
+            price_service: Arc::clone(&price_service),
+            bot: Bot::new("123:abc").throttle(Limits::default()),
+            store: None,
+            calendar: CalendarFeeds {
+                bots: Vec::new(),
+                summary: None,
+                weekly_report: None,
+            },
+        });
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let shutdown = CancellationToken::new();
+        let server = tokio::spawn(serve(addr, state, shutdown.clone()));
+
+        let mut stream = None;
+        for _ in 0..50 {
+            match TcpStream::connect(addr).await {
+                Ok(connected) => {
+                    stream = Some(connected);
+                    break;
+                }
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        }
+        let url = format!("ws://{}/ws", addr);
+        let (mut socket, _) = tokio_tungstenite::client_async(url, stream.unwrap())
+            .await
+            .unwrap();
+        let mut next_quote = async || {
+            let message = socket.next().await.unwrap().unwrap();
+            serde_json::from_str::<Value>(message.to_text().unwrap()).unwrap()
+        };
+
+        let latest = next_quote().await;
+        assert_eq!(latest["pair"], "USD/RUB");
+        assert_eq!(latest["price"], 90.0);
+
+        price_service
+            .get_price(&CurrencyPair::USDTe2RUB)
+            .await
+            .unwrap();
+        let fresh = next_quote().await;
+        assert_eq!(fresh["pair"], "USDTe/RUB");
+        assert_eq!(fresh["price"], 92.0);
+
+        shutdown.cancel();
+        assert!(matches!(
+            socket.next().await,
+            Some(Ok(WsMessage::Close(_))) | None
+        ));
+        server.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,1160 @@
+// Bot runner
+// Wires the configured services, jobs and modules together and dispatches updates
+
+use chrono::{Local, Utc};
+use futures::future::join_all;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use teloxide::adaptors::throttle::Limits;
+use teloxide::dispatching::{DefaultKey, Dispatcher};
+use teloxide::prelude::*;
+use teloxide::RequestError;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+use crate::bot_modules::alerts::check_alerts;
+use crate::bot_modules::audit::AuditLog;
+use crate::bot_modules::channels::ChannelPublisher;
+use crate::bot_modules::chart::register_chart_font;
+use crate::bot_modules::scheduler::{Job, JobHandle, JobSchedule, Scheduler};
+use crate::bot_modules::subscribers::fetch_quotes;
+use crate::bot_modules::summary::{publish_daily_summary, send_daily_summary};
+use crate::bot_modules::weekly_report::send_weekly_report;
+#[cfg(feature = "newline")]
+use crate::bot_modules::NewLineModule;
+use crate::bot_modules::{
+    module_key, AccessControl, AdminModule, AlertModule, AnalyticsModule, BotCommandsModule,
+    ChartModule, ConvertModule, DuplicateFilter, EchoModule, FeedbackModule, FeedbackReplyModule,
+    HelpModule, InlineQuotes, LoggingMiddleware, MeModule, Module, ModuleContext, ModuleRegistry,
+    OutboundQueue, PriceModule, Priority, RateLimitMiddleware, RouteModule, SettingsModule,
+    StartModule, SubscriberManager, SubscriberModule, TelegramBot, WeekModule,
+};
+use crate::config::{self, BotConfig, Config, TelegramConfig, MAIN_BOT_ID};
+use crate::domain::{get_all_currency_pairs, get_reference_pairs, CurrencyPair};
+use crate::i18n::Language;
+use crate::price_service::history::PriceHistory;
+use crate::price_service::provider::PriceProvider;
+#[cfg(any(feature = "newline", feature = "kraken", feature = "webhooks"))]
+use crate::price_service::providers::http::shared_client;
+use crate::price_service::providers::{FaultInjectingProvider, StreetRate};
+#[cfg(feature = "kraken")]
+use crate::price_service::providers::{KrakenConfig, KrakenProvider};
+#[cfg(feature = "newline")]
+use crate::price_service::providers::{NewLineConfig, NewLineProvider};
+use crate::price_service::PriceService;
+use crate::reporting::{
+    credential_reminder, deliver_reports, rate_limit_warning, watchdog_notice, ErrorReporter,
+};
+use crate::snapshot::{self, RuntimeSnapshot, RUNTIME_SNAPSHOT_VERSION};
+use crate::startup::{self, StartupError};
+#[cfg(feature = "webhooks")]
+use crate::webhooks::AlertWebhooks;
+#[cfg(feature = "http-api")]
+use crate::{api, calendar};
+
+/// Admins and allowed and blocked chats from the `[telegram]` settings
+fn access_control(config: &TelegramConfig) -> AccessControl {
+    let admin_ids: HashSet<UserId> = config.admin_user_ids.iter().copied().map(UserId).collect();
+    let mut access = AccessControl::new(admin_ids);
+    if let Some(ids) = &config.allowed_ids {
+        access = access.with_whitelist(ids.iter().copied().collect());
+    }
+    access.with_blacklist(config.blocked_ids.iter().copied().collect())
+}
+
+/// Private chats of the admins of `config`, where their notifications go
+fn admin_chats(config: &TelegramConfig) -> Vec<ChatId> {
+    config
+        .admin_user_ids
+        .iter()
+        .map(|id| ChatId(*id as i64))
+        .collect()
+}
+
+/// Price service with the providers configured in `config`, failing when
+/// the `[http_client]` they share can't be built
+pub fn price_service(config: &Config) -> Result<PriceService, StartupError> {
+    #[cfg(any(feature = "newline", feature = "kraken"))]
+    let client = shared_client(&config.http_client).map_err(|e| StartupError(vec![e]))?;
+    // Built in with their cargo feature, asked in this order
+    let mut providers: Vec<Arc<dyn PriceProvider>> = vec![
+        #[cfg(feature = "newline")]
+        Arc::new(
+            NewLineProvider::new(
+                NewLineConfig {
+                    base_url: config.newline.base_url.clone(),
+                    cookie: config.newline.cookie.clone().unwrap_or_default(),
+                    preferred_city: config.newline.preferred_city.clone(),
+                },
+                client.clone(),
+            )
+            .with_conditional_requests(config.prices.conditional_requests),
+        ),
+        #[cfg(feature = "kraken")]
+        Arc::new(
+            KrakenProvider::new(
+                KrakenConfig {
+                    base_url: config.kraken.base_url.clone(),
+                },
+                client.clone(),
+            )
+            .with_conditional_requests(config.prices.conditional_requests),
+        ),
+    ];
+    let faults = config.fault_injection.faults();
+    if faults.is_active() {
+        tracing::warn!("Injecting faults into the price providers: {:?}", faults);
+        providers = providers
+            .into_iter()
+            .map(|provider| {
+                Arc::new(FaultInjectingProvider::new(provider, faults)) as Arc<dyn PriceProvider>
+            })
+            .collect();
+    }
+    let mut price_service = PriceService::new();
+    if config.street_rate.enabled {
+        price_service.set_street_rate(StreetRate::new(config.street_rate.trim_percent));
+    }
+    price_service.set_history(
+        PriceHistory::new(config.history.retention())
+            .with_analytics_windows(config.analytics.windows.clone()),
+    );
+    for provider in providers {
+        price_service.add_provider(provider);
+    }
+    #[cfg(feature = "newline")]
+    price_service.set_provider_enabled(NewLineProvider::NAME, config.newline.enabled);
+    #[cfg(feature = "newline")]
+    if let Some(limit) = config.newline.rate_limit_per_hour {
+        price_service.set_rate_limit(NewLineProvider::NAME, limit);
+    }
+    #[cfg(not(feature = "newline"))]
+    if config.newline.cookie.is_some() {
+        tracing::warn!("NewLine is configured, but the bot is built without the newline feature");
+    }
+    #[cfg(feature = "kraken")]
+    price_service.set_provider_enabled(KrakenProvider::NAME, config.kraken.enabled);
+    #[cfg(feature = "kraken")]
+    if let Some(limit) = config.kraken.rate_limit_per_hour {
+        price_service.set_rate_limit(KrakenProvider::NAME, limit);
+    }
+    #[cfg(not(feature = "kraken"))]
+    if config.kraken != Default::default() {
+        tracing::warn!("Kraken is configured, but the bot is built without the kraken feature");
+    }
+    Ok(price_service)
+}
+
+/// Message every admin of the main bot through its outbound queue
+async fn notify_admins(bot: &TelegramBot, outbound: &OutboundQueue, admins: &[ChatId], text: &str) {
+    for admin in admins {
+        let sent = outbound
+            .send(Priority::Broadcast, *admin, || {
+                bot.send_message(*admin, text).send()
+            })
+            .await;
+        if let Err(e) = sent {
+            tracing::warn!("Failed to notify admin {}: {}", admin, e);
+        }
+    }
+}
+
+/// Job reminding the admins of the credentials about to expire
+const CREDENTIAL_EXPIRY_JOB: &str = "credential_expiry";
+
+/// Every morning at 10:00 local time
+const CREDENTIAL_EXPIRY_SCHEDULE: &str = "0 0 10 * * *";
+
+/// Job warning the admins of providers close to their rate limit
+const RATE_LIMIT_JOB: &str = "provider_rate_limits";
+
+/// Minutes between the checks of the providers' request volume
+const RATE_LIMIT_CHECK_MINUTES: u64 = 5;
+
+/// Job sampling the prices, it samples right away after a restart rather
+/// than resuming its countdown
+const PRICE_POLLER_JOB: &str = "price_poller";
+
+/// How often the watchdog checks that the jobs are still making progress
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Span grouping everything logged while handling one update of the bot
+/// `bot_id`, the module that handles it is recorded by the registry
+fn update_span(bot_id: &str, update: &Update) -> tracing::Span {
+    let span = tracing::info_span!(
+        "update",
+        bot = bot_id,
+        update_id = update.id.0,
+        chat_id = tracing::field::Empty,
+        module = tracing::field::Empty,
+    );
+    if let Some(chat) = update.chat() {
+        span.record("chat_id", chat.id.0);
+    }
+    span
+}
+
+/// Populate the command menu shown by Telegram clients with the commands
+/// of the switched on modules: the default language for everyone, the others
+/// for users with that client language
+async fn set_command_menu(bot: &TelegramBot, bot_id: &str, registry: &ModuleRegistry) {
+    for language in Language::ALL {
+        let mut request = bot.set_my_commands(registry.bot_commands(language));
+        if language != Language::default() {
+            request = request.language_code(language.code());
+        }
+        if let Err(e) = request.await {
+            tracing::warn!(
+                "Failed to register {} commands of bot {} with Telegram: {}",
+                language,
+                bot_id,
+                e
+            );
+        }
+    }
+}
+
+/// Whether the module `name` is in a bot's `modules` list, compared
+/// case-insensitively and without the `Module` suffix some names have.
+/// Every module is served without a list.
+fn module_enabled(modules: Option<&[String]>, name: &str) -> bool {
+    modules.is_none_or(|modules| {
+        modules
+            .iter()
+            .any(|module| module_key(module) == module_key(name))
+    })
+}
+
+/// Bot API server of `TELOXIDE_API_URL`, `None` for Telegram's own
+fn telegram_api_url() -> Result<Option<url::Url>, StartupError> {
+    let Ok(api_url) = std::env::var("TELOXIDE_API_URL") else {
+        return Ok(None);
+    };
+    url::Url::parse(api_url.trim_end_matches('/'))
+        .map(Some)
+        .map_err(|e| StartupError(vec![format!("TELOXIDE_API_URL {}: {}", api_url, e)]))
+}
+
+/// A bot served by the process: its own subscriptions and broadcast job on
+/// top of the shared price service and scheduler
+struct BotInstance {
+    config: BotConfig,
+    bot: TelegramBot,
+    manager: Arc<SubscriberManager>,
+    broadcast_handle: Arc<JobHandle>,
+    audit: AuditLog,
+}
+
+impl BotInstance {
+    /// Restore the bot's subscriptions and register its broadcast job. The
+    /// bot talks to `api_url` (Telegram's without one) through the
+    /// `TELOXIDE_PROXY`, like `Bot::from_env` does.
+    fn new(
+        config: BotConfig,
+        api_url: Option<&url::Url>,
+        settings: &Config,
+        price_service: &Arc<PriceService>,
+        scheduler: &mut Scheduler,
+    ) -> Self {
+        let subscriptions = &settings.subscriptions;
+        let mut manager = SubscriberManager::new(subscriptions.message_text.clone())
+            .with_jitter_window(Duration::from_secs(subscriptions.jitter_seconds))
+            .with_pinned_messages(subscriptions.pin_messages)
+            .with_sparkline_window(settings.display.sparkline_window())
+            .with_footer(settings.display.footer.clone())
+            .with_price_formats(settings.display.price_formats())
+            .with_message_format(settings.telegram.parse_mode)
+            .with_outbound(OutboundQueue::new(settings.telegram.messages_per_second));
+        if let Some(path) = &config.subscriptions_file {
+            manager = manager.with_state_file(path.clone());
+        }
+        let manager = Arc::new(manager);
+        if let Some(path) = &config.subscriptions_file {
+            match manager.load_from_file(path) {
+                Ok(count) => tracing::info!(
+                    "Restored {} subscriptions of bot {} from {}",
+                    count,
+                    config.id,
+                    path.display()
+                ),
+                Err(e) => tracing::error!(
+                    "Failed to restore subscriptions of bot {}: {}",
+                    config.id,
+                    e
+                ),
+            }
+        }
+
+        // Queue outbound sends to stay within Telegram's flood limits; requests
+        // failing with RetryAfter are retried after the freeze period
+        let bot = Bot::with_client(config.token.clone(), teloxide::net::client_from_env());
+        let bot = match api_url {
+            Some(api_url) => bot.set_api_url(api_url.clone()),
+            None => bot,
+        };
+        let bot = bot.throttle(Limits::default());
+
+        let broadcast_manager = Arc::clone(&manager);
+        let broadcast_prices = Arc::clone(price_service);
+        let broadcast_bot = bot.clone();
+        let countdown_manager = Arc::clone(&manager);
+        let job_name = match config.id.as_str() {
+            MAIN_BOT_ID => "subscriber_broadcast".to_string(),
+            id => format!("subscriber_broadcast_{}", id),
+        };
+        let mut broadcast_job =
+            Job::new(&job_name, subscriptions.broadcast_schedule(), move || {
+                let manager = Arc::clone(&broadcast_manager);
+                let price_service = Arc::clone(&broadcast_prices);
+                let bot = broadcast_bot.clone();
+                async move {
+                    manager
+                        .broadcast_periodic_message(&bot, &price_service)
+                        .await
+                }
+            })
+            .missed_ticks(subscriptions.missed_ticks)
+            .on_next_run(move |next_run| countdown_manager.set_next_send_time(next_run));
+        // Resume the countdown saved before a restart instead of sending right away
+        if let Some(next_send_time) = manager.get_next_send_time() {
+            broadcast_job = broadcast_job.first_run_at(next_send_time);
+        }
+        let broadcast_handle = scheduler.register(broadcast_job);
+
+        Self {
+            config,
+            bot,
+            manager,
+            broadcast_handle,
+            audit: AuditLog::new(
+                settings.audit.max_entries_per_chat,
+                settings.audit.retention(),
+            ),
+        }
+    }
+
+    /// Registry with the modules enabled for the bot and `extra_modules`,
+    /// with the command menu registered with Telegram
+    async fn registry(
+        &self,
+        settings: &Config,
+        price_service: &Arc<PriceService>,
+        error_reporter: ErrorReporter,
+        extra_modules: Vec<Box<dyn Module>>,
+    ) -> Arc<ModuleRegistry> {
+        let manager = &self.manager;
+        // Languages and the message format come from the manager, so the
+        // chats' choices survive restarts
+        let context = ModuleContext::new(
+            Arc::new(settings.clone()),
+            Arc::clone(price_service),
+            Arc::clone(manager),
+        )
+        .with_audit(self.audit.clone());
+
+        let mut modules: Vec<Box<dyn Module>> = vec![
+            Box::new(StartModule),
+            Box::new(BotCommandsModule::new(EchoModule)),
+            Box::new(PriceModule),
+            #[cfg(feature = "newline")]
+            Box::new(NewLineModule),
+            Box::new(ConvertModule),
+            Box::new(ChartModule),
+            Box::new(AnalyticsModule),
+            Box::new(WeekModule),
+            Box::new(RouteModule),
+            Box::new(AlertModule),
+            Box::new(SubscriberModule),
+            Box::new(SettingsModule::new(Arc::clone(&self.broadcast_handle))),
+            Box::new(MeModule),
+        ];
+        // /feedback is only offered when there is a chat to deliver it to
+        if let Some(feedback_chat) = settings.telegram.feedback_chat_id.map(ChatId) {
+            modules.push(Box::new(FeedbackModule::new(feedback_chat)));
+            modules.push(Box::new(FeedbackReplyModule));
+        }
+        modules.push(Box::new(AdminModule::new(Arc::clone(
+            &self.broadcast_handle,
+        ))));
+        modules.extend(extra_modules);
+
+        let enabled = self.config.modules.as_deref();
+        for name in enabled.unwrap_or_default() {
+            if !modules
+                .iter()
+                .any(|module| module_enabled(Some(std::slice::from_ref(name)), module.name()))
+            {
+                tracing::warn!("Bot {} has unknown module '{}'", self.config.id, name);
+            }
+        }
+        let mut registry = ModuleRegistry::new();
+        for module in modules {
+            if module_enabled(enabled, module.name()) {
+                registry.register(module);
+            }
+        }
+        // Registered last so the help lists every other module
+        let help_sections = Language::ALL
+            .into_iter()
+            .map(|language| (language, registry.help_sections(language)))
+            .collect();
+        registry.register(Box::new(HelpModule::new(help_sections)));
+        registry.set_module_switches(manager.module_switches().clone());
+        registry.set_access_control(access_control(&settings.telegram));
+        registry.set_error_reporter(error_reporter);
+        registry.add_middleware(Box::new(LoggingMiddleware::new()));
+        registry.add_middleware(Box::new(context.activity.clone()));
+        // Before the rate limit, so a double-tap doesn't count twice
+        let duplicate_window = settings.telegram.duplicate_window_seconds;
+        if duplicate_window > 0 {
+            registry.add_middleware(Box::new(DuplicateFilter::new(Duration::from_secs(
+                duplicate_window,
+            ))));
+        }
+        let commands_per_minute = settings.telegram.commands_per_minute;
+        if commands_per_minute > 0 {
+            registry.add_middleware(Box::new(RateLimitMiddleware::new(
+                commands_per_minute,
+                Duration::from_secs(60),
+                context.languages.clone(),
+                context.outbound.clone(),
+            )));
+        }
+        // After the rate limit, so only commands that reached a module are recorded
+        if settings.audit.max_entries_per_chat > 0 {
+            registry.add_middleware(Box::new(self.audit.clone()));
+        }
+        registry.set_context(context);
+        match self.bot.get_me().await {
+            Ok(me) => registry.set_bot_username(me.username().to_string()),
+            Err(e) => tracing::warn!(
+                "Failed to get info of bot {}, @mentions won't be checked: {}",
+                self.config.id,
+                e
+            ),
+        }
+
+        let registry = Arc::new(registry);
+        set_command_menu(&self.bot, &self.config.id, &registry).await;
+        // Modules switched on or off by the admins leave or rejoin the menu
+        let bot = self.bot.clone();
+        let bot_id = self.config.id.clone();
+        let switches = manager.module_switches().clone();
+        let menu_registry = Arc::clone(&registry);
+        tokio::spawn(async move {
+            loop {
+                switches.changed().await;
+                set_command_menu(&bot, &bot_id, &menu_registry).await;
+            }
+        });
+        registry
+    }
+
+    /// Dispatcher routing the bot's updates to `registry`
+    fn dispatcher(
+        &self,
+        registry: Arc<ModuleRegistry>,
+        inline_quotes: Arc<InlineQuotes>,
+    ) -> Dispatcher<TelegramBot, RequestError, DefaultKey> {
+        let bot_id: Arc<str> = Arc::from(self.config.id.as_str());
+        let message_id = Arc::clone(&bot_id);
+        let edited_id = Arc::clone(&bot_id);
+        let callback_id = Arc::clone(&bot_id);
+        let inline_id = bot_id;
+        let handler = dptree::entry()
+            .branch(Update::filter_message().endpoint(
+                move |bot: TelegramBot,
+                      update: Update,
+                      msg: Message,
+                      registry: Arc<ModuleRegistry>| {
+                    async move {
+                        if let Err(e) = registry.handle_message(bot, msg).await {
+                            tracing::error!("Error handling message: {}", e);
+                        }
+                        respond(())
+                    }
+                    .instrument(update_span(&message_id, &update))
+                },
+            ))
+            .branch(Update::filter_edited_message().endpoint(
+                move |bot: TelegramBot,
+                      update: Update,
+                      msg: Message,
+                      registry: Arc<ModuleRegistry>| {
+                    async move {
+                        if let Err(e) = registry.handle_edited_message(bot, msg).await {
+                            tracing::error!("Error handling edited message: {}", e);
+                        }
+                        respond(())
+                    }
+                    .instrument(update_span(&edited_id, &update))
+                },
+            ))
+            .branch(Update::filter_callback_query().endpoint(
+                move |bot: TelegramBot,
+                      update: Update,
+                      query: CallbackQuery,
+                      registry: Arc<ModuleRegistry>| {
+                    async move {
+                        if let Err(e) = registry.handle_callback_query(bot, query).await {
+                            tracing::error!("Error handling callback query: {}", e);
+                        }
+                        respond(())
+                    }
+                    .instrument(update_span(&callback_id, &update))
+                },
+            ))
+            .branch(Update::filter_inline_query().endpoint(
+                move |bot: TelegramBot,
+                      update: Update,
+                      query: InlineQuery,
+                      registry: Arc<ModuleRegistry>,
+                      inline_quotes: Arc<InlineQuotes>| {
+                    async move {
+                        // Inline queries have no chat, the user's private chat stands in
+                        let user = query.from.id;
+                        if !registry.is_allowed(ChatId(user.0 as i64), Some(user)) {
+                            tracing::debug!("Ignoring inline query from a user not allowed");
+                            return respond(());
+                        }
+                        if let Err(e) = inline_quotes.handle(bot, query).await {
+                            tracing::error!("Error handling inline query: {}", e);
+                        }
+                        respond(())
+                    }
+                    .instrument(update_span(&inline_id, &update))
+                },
+            ));
+
+        Dispatcher::builder(self.bot.clone(), handler)
+            .dependencies(dptree::deps![registry, inline_quotes])
+            .default_handler(|_| async {})
+            .build()
+    }
+}
+
+/// Run the bot with the built-in modules until Ctrl-C or SIGTERM
+pub async fn run_bot(config: Config) -> Result<(), StartupError> {
+    run_bot_with_modules(config, Vec::new()).await
+}
+
+/// Run the bot with `modules` registered after the built-in ones, so the
+/// built-in commands take precedence, and listed in /help. The modules are
+/// added to the `[telegram]` bot, the `[[bots]]` get the built-in ones only.
+/// Returns after Ctrl-C or SIGTERM, once the jobs are stopped and the
+/// subscriptions and the [runtime snapshot](crate::snapshot) saved, or right away when the [`startup::check`] fails.
+/// Edits of the config file are applied while running, see [`config::watch`].
+pub async fn run_bot_with_modules(
+    config: Config,
+    modules: Vec<Box<dyn Module>>,
+) -> Result<(), StartupError> {
+    tracing::info!("Starting currency bot...");
+
+    let missed_ticks = config.subscriptions.missed_ticks;
+    let message_format = config.telegram.parse_mode;
+
+    // Errors nobody would notice in the logs go to the operator's chat
+    let error_report_chat = config.reporting.chat_id.map(ChatId);
+    let error_report_cooldown = config.reporting.cooldown();
+    let sentry_dsn = config.reporting.sentry_dsn.clone();
+    #[cfg(feature = "sentry")]
+    let _sentry = sentry_dsn.as_deref().map(sentry::init);
+    let (error_reporter, error_reports) = if error_report_chat.is_some() || sentry_dsn.is_some() {
+        let (reporter, reports) = ErrorReporter::new(error_report_cooldown);
+        (reporter, Some(reports))
+    } else {
+        (ErrorReporter::default(), None)
+    };
+
+    let mut price_service = price_service(&config)?;
+    price_service.set_error_reporter(error_reporter.clone());
+    let price_service = Arc::new(price_service);
+
+    // Everything the config can't tell is checked before anything is started
+    startup::check(&config, &price_service).await?;
+
+    if let Err(e) = register_chart_font(&config.chart.font_path) {
+        tracing::warn!("Charts will not render: {}", e);
+    }
+
+    let shutdown = CancellationToken::new();
+    let mut scheduler = Scheduler::new(shutdown.clone());
+    scheduler.set_error_reporter(error_reporter.clone());
+
+    // Runtime state of the last graceful shutdown, so a short redeploy goes unnoticed
+    let mut restored = config.snapshot.file.as_deref().and_then(|path| {
+        RuntimeSnapshot::load(path)
+            .inspect_err(|e| tracing::error!("Failed to restore the runtime snapshot: {}", e))
+            .ok()
+            .flatten()
+    });
+    if let Some(restored) = restored.as_mut() {
+        tracing::info!("Restoring runtime state saved at {}", restored.saved_at);
+        price_service
+            .history()
+            .import(std::mem::take(&mut restored.prices));
+        price_service
+            .history()
+            .import_candles(std::mem::take(&mut restored.candles));
+    }
+
+    let api_url = telegram_api_url()?;
+    let instances: Vec<BotInstance> = config
+        .bots()
+        .into_iter()
+        .map(|bot| {
+            BotInstance::new(
+                bot,
+                api_url.as_ref(),
+                &config,
+                &price_service,
+                &mut scheduler,
+            )
+        })
+        .collect();
+    for instance in &instances {
+        let state = restored
+            .as_mut()
+            .and_then(|restored| restored.bots.remove(&instance.config.id));
+        if let Some(state) = state {
+            instance.manager.import_delivery_state(state);
+        }
+        let audit = restored
+            .as_mut()
+            .and_then(|restored| restored.audit.remove(&instance.config.id));
+        if let Some(entries) = audit {
+            instance.audit.import(entries);
+        }
+        // Finish the broadcasts the last run was stopped in the middle of
+        if !instance.manager.pending_broadcasts().is_empty() {
+            let manager = Arc::clone(&instance.manager);
+            let bot = instance.bot.clone();
+            tokio::spawn(async move { manager.resume_broadcasts(&bot).await });
+        }
+    }
+    if instances.len() > 1 {
+        tracing::info!("Running {} bots", instances.len());
+    }
+    // Reports, channel posts and the HTTP API use the `[telegram]` bot
+    let main = &instances[0];
+    if let Some(reports) = error_reports {
+        tokio::spawn(deliver_reports(
+            reports,
+            main.bot.clone(),
+            main.manager.outbound().clone(),
+            error_report_chat,
+            main.manager.languages().clone(),
+        ));
+    }
+
+    let channels = config.channels.list.clone();
+    let publisher = (!channels.is_empty()).then(|| {
+        tracing::info!("Publishing rates to {} channels", channels.len());
+        let publisher = ChannelPublisher::new(channels, message_format)
+            .with_outbound(main.manager.outbound().clone())
+            .with_footer(config.display.footer.clone())
+            .with_price_formats(config.display.price_formats());
+        if let Some(restored) = restored.as_mut() {
+            publisher.import_posts(std::mem::take(&mut restored.channel_posts));
+        }
+        Arc::new(publisher)
+    });
+    let channel_handle = publisher.as_ref().map(|publisher| {
+        let publisher = Arc::clone(publisher);
+        let channel_prices = Arc::clone(&price_service);
+        let channel_bot = main.bot.clone();
+        scheduler.register(
+            Job::new("channel_publish", config.channel_schedule(), move || {
+                let publisher = Arc::clone(&publisher);
+                let price_service = Arc::clone(&channel_prices);
+                let bot = channel_bot.clone();
+                async move { publisher.publish(&bot, &price_service).await }
+            })
+            .missed_ticks(missed_ticks),
+        )
+    });
+
+    // Sample every pair regularly so charts have data between broadcasts,
+    // and check the price alerts of every bot against the fresh quotes
+    let poller_prices = Arc::clone(&price_service);
+    let bot_managers: Vec<(TelegramBot, Arc<SubscriberManager>)> = instances
+        .iter()
+        .map(|instance| (instance.bot.clone(), Arc::clone(&instance.manager)))
+        .collect();
+    let bot_managers = Arc::new(bot_managers);
+    let poller_bots = Arc::clone(&bot_managers);
+    #[cfg(feature = "webhooks")]
+    let poller_webhooks = match config.alert_webhooks.callbacks().is_empty() {
+        true => None,
+        false => {
+            let client = shared_client(&config.http_client).map_err(|e| StartupError(vec![e]))?;
+            AlertWebhooks::new(&config.alert_webhooks, client).map(Arc::new)
+        }
+    };
+    #[cfg(not(feature = "webhooks"))]
+    if !config.alert_webhooks.urls.is_empty() {
+        tracing::warn!(
+            "Alert webhooks are configured, but the bot is built without the webhooks feature"
+        );
+    }
+    let poller_handle = scheduler.register(Job::new(
+        PRICE_POLLER_JOB,
+        JobSchedule::from_minutes(config.prices.poll_interval_minutes),
+        move || {
+            let price_service = Arc::clone(&poller_prices);
+            let bots = Arc::clone(&poller_bots);
+            #[cfg(feature = "webhooks")]
+            let webhooks = poller_webhooks.clone();
+            async move {
+                // Reference pairs too, for the indexes computed from their history
+                let pairs: Vec<CurrencyPair> = get_all_currency_pairs()
+                    .into_iter()
+                    .chain(get_reference_pairs())
+                    .filter(|pair| price_service.supports(pair))
+                    .collect();
+                let quotes = fetch_quotes(&price_service, &pairs).await;
+                for (bot, manager) in bots.iter() {
+                    let fired = check_alerts(manager, price_service.history(), bot, &quotes).await;
+                    if fired.is_empty() {
+                        continue;
+                    }
+                    tracing::info!("{} price alerts fired", fired.len());
+                    // Slow callbacks don't hold up the alerts of the other bots
+                    #[cfg(feature = "webhooks")]
+                    if let Some(webhooks) = webhooks.clone() {
+                        tokio::spawn(async move { webhooks.deliver(&fired).await });
+                    }
+                }
+            }
+        },
+    ));
+
+    // Fold old samples into hourly and daily candles so the history stays bounded
+    let compaction_prices = Arc::clone(&price_service);
+    scheduler.register(Job::new(
+        "history_compaction",
+        JobSchedule::from_minutes(config.history.compaction_interval_minutes),
+        move || {
+            let price_service = Arc::clone(&compaction_prices);
+            async move {
+                let compacted = price_service.history().compact(Local::now());
+                if compacted > 0 {
+                    tracing::info!("Compacted {} price samples into candles", compacted);
+                }
+            }
+        },
+    ));
+
+    // Open, high, low and close of the day at the configured time
+    if let Some(schedule) = config.summary.schedule() {
+        let summary_prices = Arc::clone(&price_service);
+        let summary_bots = Arc::clone(&bot_managers);
+        let strength_index = config.summary.strength_index;
+        let summary_channels = publisher
+            .as_ref()
+            .filter(|_| config.summary.channels)
+            .map(|publisher| (main.bot.clone(), Arc::clone(publisher)));
+        scheduler.register(Job::new("daily_summary", schedule, move || {
+            let price_service = Arc::clone(&summary_prices);
+            let bots = Arc::clone(&summary_bots);
+            let channels = summary_channels.clone();
+            async move {
+                let history = price_service.history();
+                for (bot, manager) in bots.iter() {
+                    send_daily_summary(bot, manager, history, strength_index).await;
+                }
+                if let Some((bot, publisher)) = channels {
+                    publish_daily_summary(&bot, &publisher, history, strength_index).await;
+                }
+            }
+        }));
+    }
+
+    // Replaced by config reloads, the jobs read it on every run
+    let admins = Arc::new(RwLock::new(admin_chats(&config.telegram)));
+    // CSV of the week's hourly rates, the admins get it from the main bot
+    if let Some(schedule) = config.weekly_report.schedule.clone() {
+        let report_prices = Arc::clone(&price_service);
+        let report_bots = Arc::clone(&bot_managers);
+        let report_admins = Arc::clone(&admins);
+        scheduler.register(Job::new("weekly_report", schedule, move || {
+            let price_service = Arc::clone(&report_prices);
+            let bots = Arc::clone(&report_bots);
+            let admins = report_admins.read().unwrap().clone();
+            async move {
+                let history = price_service.history();
+                for (index, (bot, manager)) in bots.iter().enumerate() {
+                    let admins: &[ChatId] = if index == 0 { &admins } else { &[] };
+                    send_weekly_report(bot, manager, admins, history).await;
+                }
+            }
+        }));
+    }
+
+    // Daily reminders of the credentials about to expire, from the main bot
+    let expiries = config.credential_expiries();
+    if !expiries.is_empty() {
+        let reminder_bot = main.bot.clone();
+        let reminder_outbound = main.manager.outbound().clone();
+        let reminder_admins = Arc::clone(&admins);
+        let reminder_languages = main.manager.languages().clone();
+        let remind_days = config.reporting.credential_reminder_days;
+        scheduler.register(Job::new(
+            CREDENTIAL_EXPIRY_JOB,
+            JobSchedule::parse(CREDENTIAL_EXPIRY_SCHEDULE).expect("valid reminder schedule"),
+            move || {
+                let bot = reminder_bot.clone();
+                let outbound = reminder_outbound.clone();
+                let admins = reminder_admins.read().unwrap().clone();
+                let languages = reminder_languages.clone();
+                let expiries = expiries.clone();
+                async move {
+                    let today = Local::now().date_naive();
+                    for (credential, expires) in expiries {
+                        // Each admin in the language of their chat with the bot
+                        for admin in &admins {
+                            let language = languages.get(*admin);
+                            if let Some(text) = credential_reminder(
+                                language,
+                                credential,
+                                expires,
+                                today,
+                                remind_days,
+                            ) {
+                                notify_admins(&bot, &outbound, &[*admin], &text).await;
+                            }
+                        }
+                    }
+                }
+            },
+        ));
+    }
+
+    // Warn the admins once per provider nearing its rate limit, again only
+    // after it dropped below the threshold
+    if !price_service.request_volume().is_empty() {
+        let limit_prices = Arc::clone(&price_service);
+        let limit_bot = main.bot.clone();
+        let limit_outbound = main.manager.outbound().clone();
+        let limit_admins = Arc::clone(&admins);
+        let limit_languages = main.manager.languages().clone();
+        let limit_poller = Arc::clone(&poller_handle);
+        let warned: Arc<Mutex<HashSet<String>>> = Arc::default();
+        scheduler.register(Job::new(
+            RATE_LIMIT_JOB,
+            JobSchedule::from_minutes(RATE_LIMIT_CHECK_MINUTES),
+            move || {
+                let price_service = Arc::clone(&limit_prices);
+                let bot = limit_bot.clone();
+                let outbound = limit_outbound.clone();
+                let admins = limit_admins.read().unwrap().clone();
+                let languages = limit_languages.clone();
+                let warned = Arc::clone(&warned);
+                // The interval the poller runs on now, rescheduled by reloads
+                let poll_interval = match limit_poller.schedule() {
+                    JobSchedule::Interval(interval) => Some(interval.as_secs() / 60),
+                    JobSchedule::Cron(_) => None,
+                };
+                async move {
+                    for (provider, volume) in price_service.request_volume() {
+                        // Each admin in the language of their chat with the bot
+                        let warnings: Vec<(ChatId, String)> = admins
+                            .iter()
+                            .filter_map(|admin| {
+                                let language = languages.get(*admin);
+                                rate_limit_warning(language, &provider, volume, poll_interval)
+                                    .map(|text| (*admin, text))
+                            })
+                            .collect();
+                        if warnings.is_empty() {
+                            warned.lock().unwrap().remove(&provider);
+                            continue;
+                        }
+                        if warned.lock().unwrap().insert(provider) {
+                            for (admin, text) in &warnings {
+                                notify_admins(&bot, &outbound, &[*admin], text).await;
+                            }
+                        }
+                    }
+                }
+            },
+        ));
+    }
+
+    // Registered last, it watches every job registered before it
+    let watchdog = scheduler.watchdog();
+    let watchdog_bot = main.bot.clone();
+    let watchdog_outbound = main.manager.outbound().clone();
+    let watchdog_admins = Arc::clone(&admins);
+    let watchdog_languages = main.manager.languages().clone();
+    scheduler.register(Job::new(
+        "watchdog",
+        JobSchedule::Interval(WATCHDOG_INTERVAL),
+        move || {
+            let restarted = watchdog.check(Instant::now());
+            let bot = watchdog_bot.clone();
+            let outbound = watchdog_outbound.clone();
+            let admins = watchdog_admins.read().unwrap().clone();
+            let languages = watchdog_languages.clone();
+            async move {
+                for (job, health) in restarted {
+                    // Each admin in the language of their chat with the bot
+                    for admin in &admins {
+                        let language = languages.get(*admin);
+                        if let Some(text) = watchdog_notice(language, &job, &health) {
+                            notify_admins(&bot, &outbound, &[*admin], &text).await;
+                        }
+                    }
+                }
+            }
+        },
+    ));
+
+    let mut extra_modules = Some(modules);
+    let mut registries = Vec::new();
+    for instance in &instances {
+        let modules = extra_modules.take().unwrap_or_default();
+        let registry = instance
+            .registry(&config, &price_service, error_reporter.clone(), modules)
+            .await;
+        registries.push(registry);
+    }
+
+    // Countdowns of the subscriptions file take precedence
+    for (name, next_run) in restored
+        .map(|restored| restored.next_runs)
+        .unwrap_or_default()
+    {
+        if name != PRICE_POLLER_JOB {
+            scheduler.resume(&name, snapshot::instant(next_run));
+        }
+    }
+    scheduler.start_all();
+    tracing::info!("Scheduler started in background");
+
+    // Apply edits of the config file that are safe to change at runtime
+    let (config_sender, mut config_receiver) = watch::channel(config.clone());
+    let _config_watcher = config::watch(|name| std::env::var(name).ok(), config_sender)
+        .inspect_err(|e| tracing::warn!("Config changes apply after a restart: {}", e))
+        .ok();
+    let reload_bots: Vec<(Arc<SubscriberManager>, Arc<JobHandle>, Arc<ModuleRegistry>)> = instances
+        .iter()
+        .zip(&registries)
+        .map(|(instance, registry)| {
+            (
+                Arc::clone(&instance.manager),
+                Arc::clone(&instance.broadcast_handle),
+                Arc::clone(registry),
+            )
+        })
+        .collect();
+    #[cfg(any(feature = "newline", feature = "kraken"))]
+    let reload_prices = Arc::clone(&price_service);
+    let reload_admins = Arc::clone(&admins);
+    tokio::spawn(async move {
+        let mut current = config_receiver.borrow().clone();
+        while config_receiver.changed().await.is_ok() {
+            let new = config_receiver.borrow_and_update().clone();
+            tracing::info!("Configuration file changed, applying");
+            for section in current.restart_required(&new) {
+                tracing::warn!("Changes to [{}] apply after a restart", section);
+            }
+            let schedule = new.subscriptions.broadcast_schedule();
+            for (manager, broadcast_handle, registry) in &reload_bots {
+                if new.subscriptions.message_text != current.subscriptions.message_text {
+                    manager.set_periodic_message_text(new.subscriptions.message_text.clone());
+                }
+                if schedule != current.subscriptions.broadcast_schedule() {
+                    broadcast_handle.reschedule(schedule.clone()).await;
+                }
+                registry.set_access_control(access_control(&new.telegram));
+            }
+            *reload_admins.write().unwrap() = admin_chats(&new.telegram);
+            let schedule = new.channel_schedule();
+            if let Some(handle) = channel_handle.as_ref() {
+                if schedule != current.channel_schedule() {
+                    handle.reschedule(schedule).await;
+                }
+            }
+            if new.prices.poll_interval_minutes != current.prices.poll_interval_minutes {
+                poller_handle
+                    .reschedule(JobSchedule::from_minutes(new.prices.poll_interval_minutes))
+                    .await;
+            }
+            #[cfg(feature = "newline")]
+            reload_prices.set_provider_enabled(NewLineProvider::NAME, new.newline.enabled);
+            #[cfg(feature = "kraken")]
+            reload_prices.set_provider_enabled(KrakenProvider::NAME, new.kraken.enabled);
+            current = new;
+        }
+    });
+
+    #[cfg(feature = "http-api")]
+    if let Some(addr) = config.http.addr {
+        let api_state = Arc::new(api::ApiState {
+            price_service: Arc::clone(&price_service),
+            bot: main.bot.clone(),
+            store: main.config.subscriptions_file.clone(),
+            calendar: calendar::CalendarFeeds {
+                bots: instances
+                    .iter()
+                    .map(|instance| {
+                        (
+                            Arc::clone(&instance.manager),
+                            Arc::clone(&instance.broadcast_handle),
+                        )
+                    })
+                    .collect(),
+                summary: config.summary.schedule(),
+                weekly_report: config.weekly_report.schedule.clone(),
+            },
+        });
+        let api_shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            if let Err(e) = api::serve(addr, api_state, api_shutdown).await {
+                tracing::error!("HTTP API stopped: {}", e);
+            }
+        });
+    }
+    #[cfg(not(feature = "http-api"))]
+    if config.http.addr.is_some() {
+        tracing::warn!(
+            "HTTP API address is set, but the bot is built without the http-api feature"
+        );
+    }
+
+    let mut dispatchers: Vec<_> = instances
+        .iter()
+        .zip(registries)
+        .map(|(instance, registry)| {
+            let inline_quotes = Arc::new(InlineQuotes::new(
+                Arc::clone(&price_service),
+                instance.manager.languages().clone(),
+                config.display.price_formats(),
+            ));
+            instance.dispatcher(registry, inline_quotes)
+        })
+        .collect();
+
+    let dispatcher_shutdowns: Vec<_> = dispatchers
+        .iter()
+        .map(|dispatcher| dispatcher.shutdown_token())
+        .collect();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        tracing::info!("Shutdown signal received, stopping...");
+        // Stop accepting updates and wait for the handlers in flight
+        for dispatcher_shutdown in dispatcher_shutdowns {
+            match dispatcher_shutdown.shutdown() {
+                Ok(finished) => finished.await,
+                Err(e) => tracing::warn!("Dispatcher is not running: {}", e),
+            }
+        }
+    });
+
+    join_all(
+        dispatchers
+            .iter_mut()
+            .map(|dispatcher| dispatcher.dispatch()),
+    )
+    .await;
+
+    shutdown.cancel();
+    scheduler.stop_all().await;
+
+    for instance in &instances {
+        if let Some(path) = &instance.config.subscriptions_file {
+            if let Err(e) = instance.manager.save_to_file(path) {
+                tracing::error!("Failed to save subscriptions to {}: {}", path.display(), e);
+            }
+        }
+    }
+    if let Some(path) = &config.snapshot.file {
+        let snapshot = RuntimeSnapshot {
+            version: RUNTIME_SNAPSHOT_VERSION,
+            saved_at: Utc::now(),
+            prices: price_service.history().export(),
+            candles: price_service.history().export_candles(),
+            bots: instances
+                .iter()
+                .map(|instance| {
+                    (
+                        instance.config.id.clone(),
+                        instance.manager.export_delivery_state(),
+                    )
+                })
+                .collect(),
+            channel_posts: publisher
+                .map(|publisher| publisher.export_posts())
+                .unwrap_or_default(),
+            audit: instances
+                .iter()
+                .map(|instance| (instance.config.id.clone(), instance.audit.export()))
+                .collect(),
+            next_runs: scheduler
+                .next_runs()
+                .into_iter()
+                .map(|(name, next_run)| (name, snapshot::wall_clock(next_run)))
+                .collect(),
+        };
+        match snapshot.save(path) {
+            Ok(()) => tracing::info!("Saved the runtime snapshot to {}", path.display()),
+            Err(e) => tracing::error!(
+                "Failed to save the runtime snapshot to {}: {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+
+    tracing::info!("Currency bot stopped");
+    Ok(())
+}
+
+/// Resolve on Ctrl-C or SIGTERM (sent by `docker stop` and systemd)
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_enabled() {
+        assert!(module_enabled(None, "Admin"));
+        let modules = ["price".to_string(), "Admin".to_string()];
+        assert!(module_enabled(Some(&modules), "PriceModule"));
+        assert!(module_enabled(Some(&modules), "Admin"));
+        assert!(!module_enabled(Some(&modules), "Subscriber"));
+        assert!(!module_enabled(Some(&[]), "Help"));
+    }
+}
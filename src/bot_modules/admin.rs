@@ -0,0 +1,880 @@
+use super::outbound::Priority;
+use super::scheduler::JobHandle;
+use super::subscribers::SubscriptionsSnapshot;
+use super::{module_key, reply, topic_of, Module, ModuleContext, ParsedCommand, Role, TelegramBot};
+use crate::domain::CurrencyPair;
+use crate::i18n::{tr, tr_args, Language};
+use crate::price_service::history::parse_period;
+use async_trait::async_trait;
+use chrono::Local;
+use std::error::Error;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use teloxide::net::Download;
+use teloxide::prelude::*;
+use teloxide::types::{Document, InputFile};
+
+/// Dead letters listed in one reply
+const MAX_DEAD_LETTERS_SHOWN: usize = 50;
+
+/// Chats listed by /users
+const MAX_USERS_SHOWN: usize = 20;
+
+/// Latest commands listed by /audit
+const MAX_AUDIT_ENTRIES_SHOWN: usize = 30;
+
+/// Audience of `/broadcast`: the subscribers matching every filter given
+#[derive(Debug, Default, PartialEq)]
+struct BroadcastFilter {
+    /// Pair the chat's periodic message shows
+    pair: Option<CurrencyPair>,
+    /// Window the chat's latest command in the audit log falls within
+    active_within: Option<chrono::Duration>,
+    language: Option<Language>,
+}
+
+impl BroadcastFilter {
+    /// Split the leading `--pair`, `--active-within` and `--lang` options off
+    /// the arguments, returning the filter and the text to send.
+    /// `--active-within` is at most `max`, the retention of the audit log,
+    /// errors are written in `language`.
+    fn parse(
+        language: Language,
+        args: &str,
+        max: chrono::Duration,
+    ) -> Result<(Self, &str), String> {
+        let mut filter = Self::default();
+        let mut rest = args.trim_start();
+        while let Some(option) = rest.strip_prefix("--") {
+            let (name, after) = option
+                .split_once(char::is_whitespace)
+                .unwrap_or((option, ""));
+            let after = after.trim_start();
+            let (value, after) = after.split_once(char::is_whitespace).unwrap_or((after, ""));
+            if value.is_empty() {
+                return Err(tr_args(
+                    language,
+                    "admin.filter_missing",
+                    &[("filter", &name)],
+                ));
+            }
+            match name {
+                "pair" => {
+                    let pair = CurrencyPair::from_str(value)
+                        .map_err(|_| tr_args(language, "unknown_pair", &[("pair", &value)]))?;
+                    filter.pair = Some(pair);
+                }
+                "active-within" => {
+                    let period = parse_period(value, max).ok_or_else(|| {
+                        tr_args(language, "args.bad_period", &[("value", &value)])
+                    })?;
+                    filter.active_within = Some(period);
+                }
+                "lang" => {
+                    let code = Language::from_code(value).ok_or_else(|| {
+                        tr_args(language, "language.unknown", &[("language", &value)])
+                    })?;
+                    filter.language = Some(code);
+                }
+                _ => {
+                    return Err(tr_args(
+                        language,
+                        "admin.filter_unknown",
+                        &[("filter", &name)],
+                    ))
+                }
+            }
+            rest = after.trim_start();
+        }
+        Ok((filter, rest))
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn matches(&self, ctx: &ModuleContext, chat_id: ChatId) -> bool {
+        let pair = self
+            .pair
+            .as_ref()
+            .is_none_or(|pair| ctx.manager.get_pairs(chat_id).contains(pair));
+        let active = self.active_within.is_none_or(|period| {
+            ctx.audit
+                .last_seen(chat_id)
+                .is_some_and(|time| time >= Local::now() - period)
+        });
+        let language = self
+            .language
+            .is_none_or(|language| ctx.languages.get(chat_id) == language);
+        pair && active && language
+    }
+
+    /// Subscribers the broadcast goes to
+    fn audience(&self, ctx: &ModuleContext) -> Vec<ChatId> {
+        ctx.manager
+            .get_subscribers()
+            .into_iter()
+            .filter(|chat_id| self.matches(ctx, *chat_id))
+            .collect()
+    }
+}
+
+/// Admin module with operational commands, restricted to admins by the registry
+pub struct AdminModule {
+    broadcast_job: Arc<JobHandle>,
+    started_at: Instant,
+}
+
+impl AdminModule {
+    /// Create a new AdminModule instance
+    pub fn new(broadcast_job: Arc<JobHandle>) -> Self {
+        Self {
+            broadcast_job,
+            started_at: Instant::now(),
+        }
+    }
+
+    fn format_uptime(language: Language, uptime: Duration) -> String {
+        let minutes = uptime.as_secs() / 60;
+        tr_args(
+            language,
+            "admin.uptime",
+            &[
+                ("days", &(minutes / (24 * 60))),
+                ("hours", &(minutes / 60 % 24)),
+                ("minutes", &(minutes % 60)),
+            ],
+        )
+    }
+
+    fn format_stats(&self, ctx: &ModuleContext, language: Language) -> String {
+        let uptime = Self::format_uptime(language, self.started_at.elapsed());
+        let count = |key, count: u64| tr_args(language, key, &[("count", &count)]);
+        let mut lines = vec![
+            tr_args(language, "admin.stats_uptime", &[("uptime", &uptime)]),
+            count(
+                "admin.stats_subscribers",
+                ctx.manager.subscriber_count() as u64,
+            ),
+            count("admin.stats_periodic", ctx.manager.total_message_count()),
+            count("admin.stats_active", ctx.activity.chat_count() as u64),
+            count(
+                "admin.stats_dead_letters",
+                ctx.manager.get_dead_letters().len() as u64,
+            ),
+        ];
+        let (sent, failed) = ctx.outbound.counts();
+        lines.push(tr_args(
+            language,
+            "admin.stats_outbound",
+            &[("sent", &sent), ("failed", &failed)],
+        ));
+        let providers = ctx.price_service.provider_stats();
+        if !providers.is_empty() {
+            lines.push(tr(language, "admin.stats_providers").to_string());
+        }
+        for (name, stats) in providers {
+            let error_rate = stats.errors as f64 * 100.0 / stats.requests.max(1) as f64;
+            let mut line = tr_args(
+                language,
+                "admin.stats_provider",
+                &[
+                    ("provider", &name),
+                    ("requests", &stats.requests),
+                    ("errors", &stats.errors),
+                    ("rate", &format!("{:.1}", error_rate)),
+                ],
+            );
+            if stats.schema_drift > 0 {
+                line.push_str(&tr_args(
+                    language,
+                    "admin.stats_schema_drift",
+                    &[("count", &stats.schema_drift)],
+                ));
+            }
+            lines.push(line);
+        }
+        tr_args(language, "admin.stats", &[("lines", &lines.join("\n"))])
+    }
+
+    fn format_users(ctx: &ModuleContext, language: Language) -> String {
+        let chats = ctx.activity.recent(MAX_USERS_SHOWN);
+        if chats.is_empty() {
+            return tr(language, "admin.users_none").to_string();
+        }
+        let lines: Vec<String> = chats
+            .iter()
+            .map(|chat| {
+                let title = chat
+                    .title
+                    .as_ref()
+                    .map(|title| format!(" ({})", title))
+                    .unwrap_or_default();
+                tr_args(
+                    language,
+                    "admin.users_line",
+                    &[
+                        ("chat", &chat.chat_id),
+                        ("title", &title),
+                        ("time", &chat.last_seen.format("%d.%m.%Y %H:%M")),
+                        ("commands", &chat.commands),
+                    ],
+                )
+            })
+            .collect();
+        tr_args(language, "admin.users", &[("chats", &lines.join("\n"))])
+    }
+
+    fn format_audit(ctx: &ModuleContext, language: Language, chat_id: &str) -> String {
+        let Ok(chat_id) = chat_id.parse().map(ChatId) else {
+            return tr(language, "admin.audit_usage").to_string();
+        };
+        let entries = ctx.audit.entries(chat_id);
+        if entries.is_empty() {
+            return tr_args(language, "admin.audit_none", &[("chat", &chat_id)]);
+        }
+
+        let skipped = entries.len().saturating_sub(MAX_AUDIT_ENTRIES_SHOWN);
+        let mut lines: Vec<String> = Vec::new();
+        if skipped > 0 {
+            lines.push(tr_args(
+                language,
+                "admin.audit_earlier",
+                &[("count", &skipped)],
+            ));
+        }
+        lines.extend(entries[skipped..].iter().map(|entry| {
+            let user = entry
+                .user_id
+                .map(|user| tr_args(language, "admin.audit_user", &[("user", &user)]))
+                .unwrap_or_default();
+            let outcome = match &entry.error {
+                Some(error) => format!(" ❌ {}", error),
+                None => String::new(),
+            };
+            tr_args(
+                language,
+                "admin.audit_line",
+                &[
+                    ("time", &entry.time.format("%d.%m.%Y %H:%M:%S")),
+                    ("command", &entry.command),
+                    ("module", &entry.module),
+                    ("latency", &entry.latency_ms),
+                    ("user", &user),
+                    ("error", &outcome),
+                ],
+            )
+        }));
+        tr_args(
+            language,
+            "admin.audit",
+            &[("chat", &chat_id), ("entries", &lines.join("\n"))],
+        )
+    }
+
+    async fn broadcast(
+        ctx: &ModuleContext,
+        bot: &TelegramBot,
+        msg: &Message,
+        args: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let language = ctx.languages.get(msg.chat.id);
+        let retention = ctx.config.audit.retention();
+        let (filter, text) = match BroadcastFilter::parse(language, args, retention) {
+            Ok((_, "")) => {
+                let usage = tr(language, "admin.broadcast_usage");
+                ctx.send_reply(bot, msg, reply(bot, msg, usage)).await?;
+                return Ok(());
+            }
+            Ok(parsed) => parsed,
+            Err(error) => {
+                ctx.send_reply(bot, msg, reply(bot, msg, error)).await?;
+                return Ok(());
+            }
+        };
+        let audience = filter.audience(ctx);
+        if audience.is_empty() && !filter.is_empty() {
+            ctx.send_reply(bot, msg, reply(bot, msg, tr(language, "admin.no_audience")))
+                .await?;
+            return Ok(());
+        }
+        let recipients = audience.len();
+        let (delivered, failed) = ctx.manager.broadcast_text(bot, audience, text).await;
+        tracing::info!(
+            "Admin broadcast to {} chats ({:?}) delivered to {}, {} failed",
+            recipients,
+            filter,
+            delivered,
+            failed
+        );
+        let done = tr_args(
+            language,
+            "admin.broadcast_done",
+            &[("delivered", &delivered), ("failed", &failed)],
+        );
+        ctx.send_reply(bot, msg, reply(bot, msg, done)).await?;
+        Ok(())
+    }
+
+    /// `/admin set_newline_cookie <cookie>`: swap the rotated cookie into the
+    /// running provider and check it. The message with the cookie is deleted
+    /// first so it doesn't stay in the chat history; a restart goes back to
+    /// the cookie of the config.
+    #[cfg(feature = "newline")]
+    async fn set_newline_cookie(
+        ctx: &ModuleContext,
+        bot: &TelegramBot,
+        msg: &Message,
+        cookie: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        use crate::price_service::providers::NewLineProvider;
+
+        let language = ctx.languages.get(msg.chat.id);
+        if cookie.is_empty() {
+            let usage = tr(language, "admin.cookie_usage");
+            ctx.send_reply(bot, msg, reply(bot, msg, usage)).await?;
+            return Ok(());
+        }
+        if let Err(e) = bot.delete_message(msg.chat.id, msg.id).await {
+            tracing::warn!(
+                "Failed to delete the message with the NewLine cookie: {}",
+                e
+            );
+        }
+        let price_service = &ctx.price_service;
+        if !price_service.set_credential(NewLineProvider::NAME, cookie) {
+            ctx.send_reply(
+                bot,
+                msg,
+                reply(bot, msg, tr(language, "admin.cookie_no_provider")),
+            )
+            .await?;
+            return Ok(());
+        }
+        let response = match price_service.check_provider(NewLineProvider::NAME).await {
+            Some(Ok(())) => tr(language, "admin.cookie_checked").to_string(),
+            Some(Err(e)) => tr_args(language, "admin.cookie_check_failed", &[("error", &e)]),
+            None => tr(language, "admin.cookie_replaced").to_string(),
+        };
+        ctx.send_reply(bot, msg, reply(bot, msg, response)).await?;
+        Ok(())
+    }
+
+    async fn export_subs(
+        ctx: &ModuleContext,
+        bot: &TelegramBot,
+        msg: &Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let language = ctx.languages.get(msg.chat.id);
+        let snapshot = ctx.manager.export_subscriptions();
+        let count = snapshot.subscriptions.len();
+        let json = serde_json::to_vec_pretty(&snapshot)?;
+
+        let mut request = bot
+            .send_document(
+                msg.chat.id,
+                InputFile::memory(json).file_name("subscriptions.json"),
+            )
+            .caption(tr_args(
+                language,
+                "admin.export_caption",
+                &[("count", &count)],
+            ));
+        if let Some(thread_id) = topic_of(msg) {
+            request = request.message_thread_id(thread_id);
+        }
+        ctx.outbound
+            .send(Priority::Reply, msg.chat.id, || request.send_ref())
+            .await?;
+        Ok(())
+    }
+
+    async fn import_subs(
+        ctx: &ModuleContext,
+        bot: &TelegramBot,
+        msg: &Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let language = ctx.languages.get(msg.chat.id);
+        let Some(document) = Self::attached_document(msg) else {
+            let help = tr(language, "admin.import_help");
+            ctx.send_reply(bot, msg, reply(bot, msg, help)).await?;
+            return Ok(());
+        };
+
+        let file = bot.get_file(document.file.id.clone()).await?;
+        let mut contents = Vec::new();
+        bot.download_file(&file.path, &mut contents).await?;
+
+        let response = match SubscriptionsSnapshot::parse(&contents) {
+            Ok(snapshot) => match ctx.manager.import_subscriptions(snapshot) {
+                Ok(count) => {
+                    ctx.manager.save_state();
+                    tr_args(language, "admin.import_done", &[("count", &count)])
+                }
+                Err(e) => tr_args(language, "admin.import_failed", &[("error", &e)]),
+            },
+            Err(e) => tr_args(language, "admin.import_invalid", &[("error", &e)]),
+        };
+        ctx.send_reply(bot, msg, reply(bot, msg, response)).await?;
+        Ok(())
+    }
+
+    async fn control_scheduler(
+        &self,
+        ctx: &ModuleContext,
+        language: Language,
+        action: &str,
+    ) -> &'static str {
+        let key = match action {
+            "pause" if self.broadcast_job.is_running() => {
+                self.broadcast_job.stop().await;
+                ctx.manager.clear_next_send_time();
+                "admin.scheduler_paused"
+            }
+            "pause" => "admin.scheduler_already_paused",
+            "resume" if self.broadcast_job.is_running() => "admin.scheduler_already_running",
+            "resume" => {
+                self.broadcast_job.start();
+                "admin.scheduler_resumed"
+            }
+            _ if self.broadcast_job.is_running() => "admin.scheduler_running_state",
+            _ => "admin.scheduler_paused_state",
+        };
+        tr(language, key)
+    }
+
+    /// `/admin module [enable|disable <name>]`, the change is saved right away
+    fn switch_module(
+        &self,
+        ctx: &ModuleContext,
+        language: Language,
+        action: &str,
+        name: &str,
+    ) -> String {
+        let switches = ctx.manager.module_switches();
+        let enabled = match action {
+            "enable" => true,
+            "disable" => false,
+            _ => {
+                let modules: Vec<String> = switches
+                    .known()
+                    .into_iter()
+                    .map(|module| {
+                        let state = match switches.is_enabled(&module) {
+                            true => tr(language, "settings.on"),
+                            false => tr(language, "settings.off"),
+                        };
+                        format!("{} — {}", module, state)
+                    })
+                    .collect();
+                return tr_args(
+                    language,
+                    "admin.modules",
+                    &[("modules", &modules.join("\n"))],
+                );
+            }
+        };
+        // Switching off the admin module would lock the admins out
+        if module_key(name) == module_key(self.name()) {
+            return tr(language, "admin.module_locked").to_string();
+        }
+        if !switches.set_enabled(name, enabled) {
+            return tr_args(language, "admin.module_unknown", &[("module", &name)]);
+        }
+        ctx.manager.save_state();
+        let key = match enabled {
+            true => "admin.module_enabled",
+            false => "admin.module_disabled",
+        };
+        tr_args(language, key, &[("module", &module_key(name))])
+    }
+
+    fn format_dead_letters(ctx: &ModuleContext, language: Language) -> String {
+        let dead_letters = ctx.manager.get_dead_letters();
+        if dead_letters.is_empty() {
+            return tr(language, "admin.dead_letters_none").to_string();
+        }
+
+        let mut lines: Vec<String> = dead_letters
+            .iter()
+            .take(MAX_DEAD_LETTERS_SHOWN)
+            .map(|letter| {
+                tr_args(
+                    language,
+                    "admin.dead_letters_line",
+                    &[
+                        ("chat", &letter.chat_id),
+                        ("attempts", &letter.attempts),
+                        ("time", &letter.failed_at.format("%d.%m.%Y %H:%M")),
+                        ("error", &letter.error),
+                    ],
+                )
+            })
+            .collect();
+        if dead_letters.len() > MAX_DEAD_LETTERS_SHOWN {
+            lines.push(tr_args(
+                language,
+                "admin.dead_letters_more",
+                &[("count", &(dead_letters.len() - MAX_DEAD_LETTERS_SHOWN))],
+            ));
+        }
+        tr_args(
+            language,
+            "admin.dead_letters",
+            &[("letters", &lines.join("\n"))],
+        )
+    }
+
+    /// Document sent with the command as a caption, or the one it replies to
+    fn attached_document(msg: &Message) -> Option<&Document> {
+        msg.document()
+            .or_else(|| msg.reply_to_message().and_then(|reply| reply.document()))
+    }
+}
+
+#[async_trait]
+impl Module for AdminModule {
+    fn name(&self) -> &str {
+        "Admin"
+    }
+
+    fn commands(&self) -> Vec<&str> {
+        vec!["/admin", "/audit", "/broadcast", "/stats", "/users"]
+    }
+
+    fn required_role(&self) -> Role {
+        Role::Admin
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let Some(command) = ParsedCommand::from_message(&msg) else {
+            return Ok(());
+        };
+        let language = ctx.languages.get(msg.chat.id);
+        match command.name {
+            "/broadcast" => return Self::broadcast(ctx, &bot, &msg, command.args).await,
+            "/stats" => {
+                let stats = self.format_stats(ctx, language);
+                ctx.send_reply(&bot, &msg, reply(&bot, &msg, stats)).await?;
+                return Ok(());
+            }
+            "/users" => {
+                let users = Self::format_users(ctx, language);
+                ctx.send_reply(&bot, &msg, reply(&bot, &msg, users)).await?;
+                return Ok(());
+            }
+            "/audit" => {
+                let audit = Self::format_audit(ctx, language, command.args.trim());
+                ctx.send_reply(&bot, &msg, reply(&bot, &msg, audit)).await?;
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let mut args = command.arg_list().into_iter();
+        let subcommand = args.next().unwrap_or("");
+        let argument = args.next().unwrap_or("");
+
+        match subcommand {
+            // Cookies may contain spaces, the whole rest of the command is the value
+            #[cfg(feature = "newline")]
+            "set_newline_cookie" => {
+                let cookie = command.args.trim().trim_start_matches(subcommand).trim();
+                Self::set_newline_cookie(ctx, &bot, &msg, cookie).await?;
+            }
+            "export_subs" => Self::export_subs(ctx, &bot, &msg).await?,
+            "import_subs" => Self::import_subs(ctx, &bot, &msg).await?,
+            "dead_letters" => {
+                let response = if argument == "clear" {
+                    ctx.manager.clear_dead_letters();
+                    tr(language, "admin.dead_letters_cleared").to_string()
+                } else {
+                    Self::format_dead_letters(ctx, language)
+                };
+                ctx.send_reply(&bot, &msg, reply(&bot, &msg, response))
+                    .await?;
+            }
+            "broadcast_now" => {
+                let sending = tr(language, "admin.broadcast_now");
+                ctx.send_reply(&bot, &msg, reply(&bot, &msg, sending))
+                    .await?;
+                self.broadcast_job.run_now().await;
+                let done = tr_args(
+                    language,
+                    "admin.broadcast_now_done",
+                    &[("count", &ctx.manager.subscriber_count())],
+                );
+                ctx.send_reply(&bot, &msg, reply(&bot, &msg, done)).await?;
+            }
+            "scheduler" => {
+                let response = self.control_scheduler(ctx, language, argument).await;
+                ctx.send_reply(&bot, &msg, reply(&bot, &msg, response))
+                    .await?;
+            }
+            "module" => {
+                let module = args.next().unwrap_or("");
+                let response = self.switch_module(ctx, language, argument, module);
+                ctx.send_reply(&bot, &msg, reply(&bot, &msg, response))
+                    .await?;
+            }
+            _ => {
+                let usage = tr(language, "admin.usage");
+                ctx.send_reply(&bot, &msg, reply(&bot, &msg, usage)).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot_modules::audit::AuditEntry;
+    use crate::bot_modules::scheduler::{Job, JobSchedule, Scheduler};
+    use crate::bot_modules::{BotCommandsModule, EchoModule, ModuleRegistry};
+    use tokio_util::sync::CancellationToken;
+
+    fn test_module() -> AdminModule {
+        let mut scheduler = Scheduler::new(CancellationToken::new());
+        let broadcast_job = scheduler.register(Job::new(
+            "broadcast",
+            JobSchedule::from_minutes(10),
+            || async {},
+        ));
+        AdminModule::new(broadcast_job)
+    }
+
+    #[test]
+    fn test_module_name() {
+        let module = test_module();
+        assert_eq!(module.name(), "Admin");
+    }
+
+    #[test]
+    fn test_module_commands() {
+        let module = test_module();
+        assert_eq!(
+            module.commands(),
+            vec!["/admin", "/audit", "/broadcast", "/stats", "/users"]
+        );
+        assert_eq!(module.required_role(), Role::Admin);
+    }
+
+    #[test]
+    fn test_format_stats_and_users() {
+        let module = test_module();
+        let ctx = ModuleContext::default();
+        assert_eq!(
+            AdminModule::format_uptime(Language::Ru, Duration::from_secs(26 * 3600 + 5 * 60)),
+            "1д 2ч 5м"
+        );
+        let stats = module.format_stats(&ctx, Language::Ru);
+        assert!(stats.contains("Подписчиков: 0"));
+        assert!(stats.contains("Исходящих сообщений: 0, ошибок: 0"));
+        assert!(!stats.contains("Провайдеры"));
+        assert_eq!(
+            AdminModule::format_users(&ctx, Language::Ru),
+            "Активных чатов пока нет."
+        );
+
+        let stats = module.format_stats(&ctx, Language::En);
+        assert!(stats.starts_with("📊 Statistics\n"));
+        assert!(stats.contains("Outgoing messages: 0, failed: 0"));
+        assert_eq!(
+            AdminModule::format_users(&ctx, Language::En),
+            "No active chats yet."
+        );
+    }
+
+    #[test]
+    fn test_format_audit() {
+        let ctx = ModuleContext::default();
+        assert_eq!(
+            AdminModule::format_audit(&ctx, Language::Ru, "me"),
+            "Использование: /audit <chat_id>"
+        );
+        assert_eq!(
+            AdminModule::format_audit(&ctx, Language::Ru, "-100"),
+            "Команд чата -100 в журнале нет."
+        );
+
+        let time = chrono::Local::now();
+        ctx.audit.import(vec![AuditEntry {
+            chat_id: ChatId(-100),
+            user_id: Some(UserId(7)),
+            time,
+            command: "/price".to_string(),
+            module: "PriceModule".to_string(),
+            latency_ms: 120,
+            error: Some("Network error".to_string()),
+        }]);
+        assert_eq!(
+            AdminModule::format_audit(&ctx, Language::Ru, "-100"),
+            format!(
+                "Журнал команд чата -100:\n{} /price (PriceModule) — 120 мс, пользователь 7 ❌ Network error",
+                time.format("%d.%m.%Y %H:%M:%S")
+            )
+        );
+    }
+
+    #[test]
+    fn test_broadcast_filter() {
+        let retention = chrono::Duration::days(30);
+        assert_eq!(
+            BroadcastFilter::parse(Language::Ru, "  Hello\nworld", retention),
+            Ok((BroadcastFilter::default(), "Hello\nworld"))
+        );
+        let (filter, text) = BroadcastFilter::parse(
+            Language::Ru,
+            "--pair USD/RUB --active-within 7d --lang en Maintenance at 3am",
+            retention,
+        )
+        .unwrap();
+        assert_eq!(
+            filter,
+            BroadcastFilter {
+                pair: Some(CurrencyPair::USD2RUB),
+                active_within: Some(chrono::Duration::days(7)),
+                language: Some(Language::En),
+            }
+        );
+        assert_eq!(text, "Maintenance at 3am");
+        assert_eq!(
+            BroadcastFilter::parse(Language::Ru, "--lang en", retention)
+                .unwrap()
+                .1,
+            ""
+        );
+        assert_eq!(
+            BroadcastFilter::parse(Language::Ru, "--pair EUR/RUB hi", retention),
+            Err("❌ Неизвестная пара: EUR/RUB".to_string())
+        );
+        assert_eq!(
+            BroadcastFilter::parse(Language::Ru, "--active-within week hi", retention),
+            Err("❌ Неверный период: week, например 30m, 2h или 7d".to_string())
+        );
+        assert_eq!(
+            BroadcastFilter::parse(Language::Ru, "--active-within 60d hi", retention),
+            Err("❌ Неверный период: 60d, например 30m, 2h или 7d".to_string())
+        );
+        assert_eq!(
+            BroadcastFilter::parse(Language::Ru, "--active-within", retention),
+            Err("❌ Не указано значение --active-within".to_string())
+        );
+        assert_eq!(
+            BroadcastFilter::parse(Language::Ru, "--city Moscow hi", retention),
+            Err("❌ Неизвестный фильтр: --city".to_string())
+        );
+        assert_eq!(
+            BroadcastFilter::parse(Language::En, "--lang de hi", retention),
+            Err("❌ Unknown language: de".to_string())
+        );
+
+        let ctx = ModuleContext::default();
+        for chat_id in [ChatId(1), ChatId(2), ChatId(3)] {
+            ctx.manager.subscribe(chat_id);
+        }
+        ctx.manager
+            .set_pairs(ChatId(1), vec![CurrencyPair::USD2RUB]);
+        ctx.manager
+            .set_pairs(ChatId(2), vec![CurrencyPair::USDTe2RUB]);
+        ctx.languages.set(ChatId(2), Language::En);
+        let command = |chat_id: i64, days_ago: i64| AuditEntry {
+            chat_id: ChatId(chat_id),
+            user_id: None,
+            time: Local::now() - chrono::Duration::days(days_ago),
+            command: "/price".to_string(),
+            module: "PriceModule".to_string(),
+            latency_ms: 10,
+            error: None,
+        };
+        ctx.audit.import(vec![command(1, 2), command(2, 10)]);
+
+        let audience = |args: &str| {
+            let mut chats = BroadcastFilter::parse(Language::Ru, args, retention)
+                .unwrap()
+                .0
+                .audience(&ctx);
+            chats.sort_by_key(|chat_id| chat_id.0);
+            chats
+        };
+        assert_eq!(audience("hi"), [ChatId(1), ChatId(2), ChatId(3)]);
+        // Chats that never picked pairs get all of them
+        assert_eq!(audience("--pair USD/RUB hi"), [ChatId(1), ChatId(3)]);
+        assert_eq!(audience("--active-within 7d hi"), [ChatId(1)]);
+        assert_eq!(audience("--active-within 14d hi"), [ChatId(1), ChatId(2)]);
+        assert_eq!(audience("--lang en hi"), [ChatId(2)]);
+        assert!(audience("--lang en --active-within 7d hi").is_empty());
+    }
+
+    #[test]
+    fn test_switch_module() {
+        let module = test_module();
+        let ctx = ModuleContext::default();
+        let mut registry = ModuleRegistry::new();
+        registry.register(Box::new(BotCommandsModule::new(EchoModule)));
+        registry.register(Box::new(test_module()));
+        let switches = ctx.manager.module_switches();
+        registry.set_module_switches(switches.clone());
+
+        assert_eq!(
+            module.switch_module(&ctx, Language::Ru, "disable", "Echo"),
+            "✅ Модуль echo выключен"
+        );
+        assert!(!switches.is_enabled("EchoModule"));
+        assert!(module
+            .switch_module(&ctx, Language::Ru, "", "")
+            .contains("echo — выкл"));
+        assert_eq!(
+            module.switch_module(&ctx, Language::Ru, "disable", "admin"),
+            "❌ Модуль admin нельзя выключить"
+        );
+        assert_eq!(
+            module.switch_module(&ctx, Language::Ru, "disable", "weather"),
+            "❌ Неизвестный модуль: weather"
+        );
+        assert_eq!(
+            ctx.manager.export_subscriptions().disabled_modules,
+            ["echo"]
+        );
+
+        assert_eq!(
+            module.switch_module(&ctx, Language::Ru, "enable", "echo"),
+            "✅ Модуль echo включён"
+        );
+        assert!(switches.is_enabled("Echo"));
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_pause_resume() {
+        let module = test_module();
+        let ctx = ModuleContext::default();
+        module.broadcast_job.start();
+        ctx.manager
+            .set_next_send_time(std::time::Instant::now() + std::time::Duration::from_secs(60));
+
+        assert!(module
+            .control_scheduler(&ctx, Language::Ru, "pause")
+            .await
+            .contains("приостановлена"));
+        assert!(!module.broadcast_job.is_running());
+        assert_eq!(ctx.manager.get_time_until_next(), None);
+        assert!(module
+            .control_scheduler(&ctx, Language::Ru, "pause")
+            .await
+            .contains("уже"));
+
+        assert!(module
+            .control_scheduler(&ctx, Language::Ru, "resume")
+            .await
+            .contains("возобновлена"));
+        assert!(module.broadcast_job.is_running());
+        module.broadcast_job.stop().await;
+    }
+}
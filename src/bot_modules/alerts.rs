@@ -0,0 +1,972 @@
+use super::format::{PriceFormat, PriceFormats};
+use super::subscribers::{Quotes, SubscriberManager};
+use super::{reply, Module, ModuleContext, ParsedCommand, TelegramBot};
+use crate::domain::CurrencyPair;
+use crate::i18n::{tr, tr_args, Language};
+use crate::price_service::history::{format_period, parse_period, PriceHistory};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::str::FromStr;
+use std::sync::Mutex;
+use teloxide::prelude::*;
+
+/// Alert rules a single chat may keep
+pub const MAX_ALERTS_PER_CHAT: usize = 20;
+
+/// Condition a fresh quote is checked against
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AlertCondition {
+    Above {
+        price: f64,
+    },
+    Below {
+        price: f64,
+    },
+    /// The price moved by at least `percent` in either direction within
+    /// the last `window_minutes`
+    Moves {
+        percent: f64,
+        window_minutes: i64,
+    },
+}
+
+/// Whether `price` is at least `percent` off a sample of `pair` recorded
+/// within the last `window_minutes`
+fn moved_by(
+    pair: &CurrencyPair,
+    price: f64,
+    history: &PriceHistory,
+    percent: f64,
+    window_minutes: i64,
+) -> bool {
+    let since = Local::now() - Duration::minutes(window_minutes);
+    history
+        .range(pair, since)
+        .iter()
+        .filter(|point| point.price > 0.0)
+        .any(|point| ((price - point.price) / point.price).abs() * 100.0 >= percent)
+}
+
+impl AlertCondition {
+    fn is_met(&self, pair: &CurrencyPair, price: f64, history: &PriceHistory) -> bool {
+        match self {
+            AlertCondition::Above { price: threshold } => price > *threshold,
+            AlertCondition::Below { price: threshold } => price < *threshold,
+            AlertCondition::Moves {
+                percent,
+                window_minutes,
+            } => moved_by(pair, price, history, *percent, *window_minutes),
+        }
+    }
+
+    /// Whether the value moved back at least `rearm_percent` of the
+    /// threshold past it, the hysteresis keeping a price hovering around the
+    /// threshold from re-arming the rule. Without hysteresis the condition
+    /// just has to stop holding.
+    fn is_cleared(
+        &self,
+        pair: &CurrencyPair,
+        price: f64,
+        history: &PriceHistory,
+        rearm_percent: f64,
+    ) -> bool {
+        let margin = rearm_percent / 100.0;
+        match self {
+            AlertCondition::Above { price: threshold } => price <= threshold * (1.0 - margin),
+            AlertCondition::Below { price: threshold } => price >= threshold * (1.0 + margin),
+            AlertCondition::Moves {
+                percent,
+                window_minutes,
+            } => !moved_by(
+                pair,
+                price,
+                history,
+                percent * (1.0 - margin),
+                *window_minutes,
+            ),
+        }
+    }
+
+    /// The condition with its price written in `price_format`
+    pub fn describe(&self, language: Language, price_format: &PriceFormat) -> String {
+        match self {
+            AlertCondition::Above { price } => format!("> {}", price_format.format(*price)),
+            AlertCondition::Below { price } => format!("< {}", price_format.format(*price)),
+            AlertCondition::Moves {
+                percent,
+                window_minutes,
+            } => tr_args(
+                language,
+                "alerts.moves",
+                &[
+                    ("percent", &format!("{:.2}", percent)),
+                    ("period", &format_period(Duration::minutes(*window_minutes))),
+                ],
+            ),
+        }
+    }
+}
+
+/// Where a repeating rule is between notifications, stored with the rule
+/// so a restart neither repeats nor loses one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertState {
+    /// Fires on the next quote meeting the condition
+    #[default]
+    Armed,
+    /// Fired, re-arms once the condition stops holding
+    Triggered,
+    /// Fired, stays silent until `until` and is armed again afterwards
+    Cooldown { until: DateTime<Utc> },
+}
+
+/// Price alert created by a chat
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub pair: CurrencyPair,
+    pub condition: AlertCondition,
+    /// Repeating rules stay after firing, one-shot rules are removed
+    pub repeat: bool,
+    #[serde(default)]
+    pub state: AlertState,
+    /// Minimum time between two notifications of a repeating rule
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cooldown_minutes: Option<i64>,
+    /// How far back past the threshold, in percent of it, the value has to
+    /// move before a repeating rule re-arms
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rearm_percent: Option<f64>,
+    /// When the rule last fired, the start of its cooldown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fired_at: Option<DateTime<Utc>>,
+}
+
+impl AlertRule {
+    /// Armed rule without cooldown and hysteresis
+    pub fn new(pair: CurrencyPair, condition: AlertCondition, repeat: bool) -> Self {
+        Self {
+            pair,
+            condition,
+            repeat,
+            state: AlertState::Armed,
+            cooldown_minutes: None,
+            rearm_percent: None,
+            fired_at: None,
+        }
+    }
+
+    pub fn describe(&self, language: Language, price_formats: &PriceFormats) -> String {
+        let condition = self
+            .condition
+            .describe(language, price_formats.get(&self.pair));
+        let mut text = format!("{} {}", self.pair, condition);
+        let mut options = Vec::new();
+        if let Some(minutes) = self.cooldown_minutes {
+            let period = format_period(Duration::minutes(minutes));
+            options.push(tr_args(language, "alerts.cooldown", &[("period", &period)]));
+        }
+        if let Some(percent) = self.rearm_percent {
+            let percent = format!("{:.2}", percent);
+            options.push(tr_args(language, "alerts.rearm", &[("percent", &percent)]));
+        }
+        if !options.is_empty() {
+            text.push_str(&tr_args(
+                language,
+                "alerts.repeating_with",
+                &[("options", &options.join(", "))],
+            ));
+        } else if self.repeat {
+            text.push_str(tr(language, "alerts.repeating"));
+        }
+        text
+    }
+
+    /// State a fired rule re-arms into: in cooldown while it lasts
+    fn rearmed_state(&self, now: DateTime<Utc>) -> AlertState {
+        let until = self
+            .fired_at
+            .zip(self.cooldown_minutes)
+            .map(|(fired_at, minutes)| fired_at + Duration::minutes(minutes));
+        match until {
+            Some(until) if until > now => AlertState::Cooldown { until },
+            _ => AlertState::Armed,
+        }
+    }
+}
+
+/// Alert rules of one chat inside a subscriptions snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRecord {
+    pub chat_id: ChatId,
+    pub rules: Vec<AlertRule>,
+    /// Notifications the chat got so far, see `/me`
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub triggered: u64,
+}
+
+fn is_zero(count: &u64) -> bool {
+    *count == 0
+}
+
+/// Alert rule whose condition was met by a fresh quote
+#[derive(Debug, Clone)]
+pub struct TriggeredAlert {
+    pub chat_id: ChatId,
+    pub rule: AlertRule,
+    pub price: f64,
+}
+
+/// Keeps alert rules per chat and evaluates them against fresh quotes
+#[derive(Debug, Default)]
+pub struct AlertEngine {
+    rules: Mutex<HashMap<ChatId, Vec<AlertRule>>>,
+    /// Rules fired per chat, kept when the rules are gone
+    triggered: Mutex<HashMap<ChatId, u64>>,
+}
+
+impl AlertEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule, returning its 1-based number in the chat's list, or
+    /// `None` when the chat already has [`MAX_ALERTS_PER_CHAT`] rules
+    pub fn add(&self, chat_id: ChatId, rule: AlertRule) -> Option<usize> {
+        let mut rules = self.rules.lock().unwrap();
+        let chat_rules = rules.entry(chat_id).or_default();
+        if chat_rules.len() >= MAX_ALERTS_PER_CHAT {
+            return None;
+        }
+        chat_rules.push(rule);
+        Some(chat_rules.len())
+    }
+
+    pub fn list(&self, chat_id: ChatId) -> Vec<AlertRule> {
+        let rules = self.rules.lock().unwrap();
+        rules.get(&chat_id).cloned().unwrap_or_default()
+    }
+
+    /// Delete the chat's rule with the 1-based `number`
+    pub fn delete(&self, chat_id: ChatId, number: usize) -> Option<AlertRule> {
+        let mut rules = self.rules.lock().unwrap();
+        let chat_rules = rules.get_mut(&chat_id)?;
+        if number == 0 || number > chat_rules.len() {
+            return None;
+        }
+        let rule = chat_rules.remove(number - 1);
+        if chat_rules.is_empty() {
+            rules.remove(&chat_id);
+        }
+        Some(rule)
+    }
+
+    /// Number of times the chat's rules fired
+    pub fn triggered_count(&self, chat_id: ChatId) -> u64 {
+        let triggered = self.triggered.lock().unwrap();
+        triggered.get(&chat_id).copied().unwrap_or_default()
+    }
+
+    /// Move the rules of `from` to `to`, a group upgraded to a supergroup
+    pub fn migrate(&self, from: ChatId, to: ChatId) {
+        let mut rules = self.rules.lock().unwrap();
+        if let Some(chat_rules) = rules.remove(&from) {
+            rules.insert(to, chat_rules);
+        }
+        let mut triggered = self.triggered.lock().unwrap();
+        if let Some(count) = triggered.remove(&from) {
+            triggered.insert(to, count);
+        }
+    }
+
+    /// Check every rule with a quote in `quotes`, dropping fired one-shot
+    /// rules and updating the state of repeating ones. Percent-change rules
+    /// compare the quote with the recorded `history`.
+    pub fn evaluate(&self, quotes: &Quotes, history: &PriceHistory) -> Vec<TriggeredAlert> {
+        let mut triggered = Vec::new();
+        let mut rules = self.rules.lock().unwrap();
+        let now = Utc::now();
+        for (chat_id, chat_rules) in rules.iter_mut() {
+            chat_rules.retain_mut(|rule| {
+                let Some(price) = quotes.get(&rule.pair).copied() else {
+                    return true;
+                };
+                match rule.state {
+                    AlertState::Cooldown { until } if until > now => return true,
+                    AlertState::Cooldown { .. } | AlertState::Armed => {}
+                    AlertState::Triggered => {
+                        let rearm_percent = rule.rearm_percent.unwrap_or_default();
+                        if rule
+                            .condition
+                            .is_cleared(&rule.pair, price, history, rearm_percent)
+                        {
+                            rule.state = rule.rearmed_state(now);
+                        }
+                        return true;
+                    }
+                }
+                if !rule.condition.is_met(&rule.pair, price, history) {
+                    rule.state = AlertState::Armed;
+                    return true;
+                }
+                rule.state = AlertState::Triggered;
+                rule.fired_at = Some(now);
+                triggered.push(TriggeredAlert {
+                    chat_id: *chat_id,
+                    rule: rule.clone(),
+                    price,
+                });
+                rule.repeat
+            });
+        }
+        rules.retain(|_, chat_rules| !chat_rules.is_empty());
+        let mut counts = self.triggered.lock().unwrap();
+        for alert in &triggered {
+            *counts.entry(alert.chat_id).or_default() += 1;
+        }
+        triggered
+    }
+
+    /// Collect all rules for a subscriptions snapshot
+    pub fn export(&self) -> Vec<AlertRecord> {
+        let rules = self.rules.lock().unwrap();
+        let triggered = self.triggered.lock().unwrap();
+        let chat_ids: std::collections::BTreeSet<ChatId> =
+            rules.keys().chain(triggered.keys()).copied().collect();
+        chat_ids
+            .into_iter()
+            .map(|chat_id| AlertRecord {
+                chat_id,
+                rules: rules.get(&chat_id).cloned().unwrap_or_default(),
+                triggered: triggered.get(&chat_id).copied().unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    /// Replace the rules of every chat present in `records`
+    pub fn import(&self, records: Vec<AlertRecord>) {
+        let mut rules = self.rules.lock().unwrap();
+        let mut triggered = self.triggered.lock().unwrap();
+        for record in records {
+            if record.triggered > 0 {
+                triggered.insert(record.chat_id, record.triggered);
+            }
+            if record.rules.is_empty() {
+                rules.remove(&record.chat_id);
+            } else {
+                rules.insert(record.chat_id, record.rules);
+            }
+        }
+    }
+}
+
+/// Evaluate the alert rules of `manager` against `quotes` and notify the
+/// chats whose rules fired in their language. The state file is updated
+/// right away so a crash doesn't repeat the notifications. Returns the
+/// alerts that fired.
+pub async fn check_alerts(
+    manager: &SubscriberManager,
+    history: &PriceHistory,
+    bot: &TelegramBot,
+    quotes: &Quotes,
+) -> Vec<TriggeredAlert> {
+    let fired = manager.alerts().evaluate(quotes, history);
+    if !fired.is_empty() {
+        manager.save_state();
+    }
+    for alert in &fired {
+        let language = manager.languages().get(alert.chat_id);
+        let price_format = manager.price_formats().get(&alert.rule.pair);
+        let mut text = tr_args(
+            language,
+            "alerts.fired",
+            &[
+                ("pair", &alert.rule.pair),
+                (
+                    "condition",
+                    &alert.rule.condition.describe(language, price_format),
+                ),
+                ("price", &price_format.format(alert.price)),
+            ],
+        );
+        if !alert.rule.repeat {
+            text.push('\n');
+            text.push_str(tr(language, "alerts.fired_removed"));
+        }
+        // Into the forum topic the chat subscribed from, in the chat's markup
+        let text = manager.message_format().escape(&text);
+        if let Err(e) = manager.send_to_chat(bot, alert.chat_id, text).await {
+            tracing::warn!("Failed to send price alert to {}: {}", alert.chat_id, e);
+        }
+    }
+    fired
+}
+
+/// Alert module managing the chat's price alerts
+pub struct AlertModule;
+
+impl AlertModule {
+    /// Parse a positive percentage like `2%` or `1,5`
+    fn parse_percent(text: &str) -> Option<f64> {
+        text.trim_end_matches('%')
+            .replace(',', ".")
+            .parse()
+            .ok()
+            .filter(|percent: &f64| percent.is_finite() && *percent > 0.0)
+    }
+
+    /// Apply the options following a rule: `repeat`, `cooldown <period>`
+    /// and `rearm <percent>%`, the last two for repeating rules only. The
+    /// cooldown is at most `max`.
+    fn parse_options(
+        language: Language,
+        mut rule: AlertRule,
+        options: &[&str],
+        max: Duration,
+    ) -> Result<AlertRule, String> {
+        let mut options = options.iter();
+        while let Some(option) = options.next() {
+            match *option {
+                "repeat" => rule.repeat = true,
+                "cooldown" => {
+                    let period = options
+                        .next()
+                        .and_then(|period| parse_period(period, max))
+                        .ok_or(tr(language, "alerts.bad_period"))?;
+                    rule.cooldown_minutes = Some(period.num_minutes());
+                }
+                "rearm" => {
+                    let percent = options
+                        .next()
+                        .and_then(|percent| Self::parse_percent(percent))
+                        .filter(|percent| *percent < 100.0)
+                        .ok_or(tr(language, "alerts.bad_rearm"))?;
+                    rule.rearm_percent = Some(percent);
+                }
+                _ => return Err(tr(language, "alerts.help").to_string()),
+            }
+        }
+        if !rule.repeat && (rule.cooldown_minutes.is_some() || rule.rearm_percent.is_some()) {
+            return Err(tr(language, "alerts.options_need_repeat").to_string());
+        }
+        Ok(rule)
+    }
+
+    /// Parse `<pair> >|< <price> [options]`, the operator may be attached to
+    /// the price (`>100`), or `<pair> moves <percent>% in <window> [options]`.
+    /// Periods are at most `max`, the raw retention the moves are looked up in.
+    fn parse_rule(language: Language, args: &[&str], max: Duration) -> Result<AlertRule, String> {
+        let usage = || tr(language, "alerts.help").to_string();
+        let (pair, rest) = args.split_first().ok_or_else(usage)?;
+        let pair = CurrencyPair::from_str(pair)
+            .map_err(|_| tr_args(language, "unknown_pair", &[("pair", pair)]))?;
+
+        let (condition, options) = match rest {
+            ["moves", percent, "in", window, options @ ..] => {
+                let percent =
+                    Self::parse_percent(percent).ok_or(tr(language, "alerts.bad_percent"))?;
+                let window = parse_period(window, max).ok_or(tr(language, "alerts.bad_period"))?;
+                let condition = AlertCondition::Moves {
+                    percent,
+                    window_minutes: window.num_minutes(),
+                };
+                (condition, options)
+            }
+            ["moves", ..] => return Err(usage()),
+            _ => {
+                let (operator, price, options) = match rest {
+                    [operator @ (">" | "<"), price, options @ ..] => (*operator, *price, options),
+                    [">" | "<"] => return Err(tr(language, "alerts.bad_price").to_string()),
+                    [attached, options @ ..]
+                        if attached.starts_with('>') || attached.starts_with('<') =>
+                    {
+                        let (operator, price) = attached.split_at(1);
+                        (operator, price, options)
+                    }
+                    _ => return Err(usage()),
+                };
+                let price: f64 = price
+                    .replace(',', ".")
+                    .parse()
+                    .ok()
+                    .filter(|price: &f64| price.is_finite() && *price > 0.0)
+                    .ok_or(tr(language, "alerts.bad_price"))?;
+                let condition = match operator {
+                    ">" => AlertCondition::Above { price },
+                    _ => AlertCondition::Below { price },
+                };
+                (condition, options)
+            }
+        };
+        Self::parse_options(
+            language,
+            AlertRule::new(pair, condition, false),
+            options,
+            max,
+        )
+    }
+
+    fn format_alerts(manager: &SubscriberManager, language: Language, chat_id: ChatId) -> String {
+        let rules = manager.alerts().list(chat_id);
+        if rules.is_empty() {
+            return tr(language, "alerts.none").to_string();
+        }
+        let lines = rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| {
+                let rule = rule.describe(language, manager.price_formats());
+                format!("{}. {}", i + 1, rule)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        tr_args(language, "alerts.list", &[("alerts", &lines)])
+    }
+
+    /// Reply to `/alert`, the periods of the rules at most `max`
+    fn handle_alert(
+        manager: &SubscriberManager,
+        language: Language,
+        chat_id: ChatId,
+        args: &[&str],
+        max: Duration,
+    ) -> String {
+        match args {
+            [] => tr(language, "alerts.help").to_string(),
+            ["delete", number] => match number
+                .parse()
+                .ok()
+                .and_then(|number| manager.alerts().delete(chat_id, number))
+            {
+                Some(rule) => {
+                    manager.save_state();
+                    tr_args(
+                        language,
+                        "alerts.deleted",
+                        &[("rule", &rule.describe(language, manager.price_formats()))],
+                    )
+                }
+                None => tr_args(language, "alerts.not_found", &[("number", number)]),
+            },
+            _ => match Self::parse_rule(language, args, max) {
+                Ok(rule) => {
+                    let description = rule.describe(language, manager.price_formats());
+                    match manager.alerts().add(chat_id, rule) {
+                        Some(number) => {
+                            manager.save_state();
+                            tr_args(
+                                language,
+                                "alerts.created",
+                                &[("number", &number), ("rule", &description)],
+                            )
+                        }
+                        None => tr_args(language, "alerts.limit", &[("max", &MAX_ALERTS_PER_CHAT)]),
+                    }
+                }
+                Err(e) => e,
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl Module for AlertModule {
+    fn name(&self) -> &str {
+        "Alerts"
+    }
+
+    fn commands(&self) -> Vec<&str> {
+        vec!["/alerts", "/alert"]
+    }
+
+    fn command_descriptions(&self, language: Language) -> Vec<(&str, &str)> {
+        vec![
+            ("/alert", tr(language, "alerts.menu.alert")),
+            ("/alerts", tr(language, "alerts.menu.alerts")),
+        ]
+    }
+
+    fn description(&self, language: Language) -> Option<&str> {
+        Some(tr(language, "alerts.section"))
+    }
+
+    fn usage(&self, language: Language) -> Vec<String> {
+        [
+            "alerts.usage.threshold",
+            "alerts.usage.moves",
+            "alerts.usage.list",
+        ]
+        .into_iter()
+        .map(|key| tr(language, key).to_string())
+        .collect()
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let Some(command) = ParsedCommand::from_message(&msg) else {
+            return Ok(());
+        };
+        let args = command.arg_list();
+        let language = ctx.languages.get(msg.chat.id);
+
+        let response = match command.name {
+            "/alerts" => Self::format_alerts(&ctx.manager, language, msg.chat.id),
+            "/alert" => {
+                let max = ctx.price_service.history().retention().raw;
+                Self::handle_alert(&ctx.manager, language, msg.chat.id, &args, max)
+            }
+            _ => return Ok(()),
+        };
+        ctx.send_reply(&bot, &msg, reply(&bot, &msg, response))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Raw retention of the history in the tests
+    fn retention() -> Duration {
+        Duration::days(30)
+    }
+
+    fn rule(condition: AlertCondition, repeat: bool) -> AlertRule {
+        AlertRule::new(CurrencyPair::USD2RUB, condition, repeat)
+    }
+
+    #[test]
+    fn test_module_commands() {
+        assert_eq!(AlertModule.name(), "Alerts");
+        assert_eq!(AlertModule.commands(), vec!["/alerts", "/alert"]);
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(
+            AlertModule::parse_rule(Language::Ru, &["USD/RUB", ">", "100"], retention()),
+            Ok(rule(AlertCondition::Above { price: 100.0 }, false))
+        );
+        assert_eq!(
+            AlertModule::parse_rule(Language::Ru, &["USD/RUB", "<90,5", "repeat"], retention()),
+            Ok(rule(AlertCondition::Below { price: 90.5 }, true))
+        );
+        assert!(
+            AlertModule::parse_rule(Language::Ru, &["EUR/RUB", ">", "100"], retention()).is_err()
+        );
+        assert!(
+            AlertModule::parse_rule(Language::Ru, &["USD/RUB", "=", "100"], retention()).is_err()
+        );
+        assert!(
+            AlertModule::parse_rule(Language::Ru, &["USD/RUB", ">", "abc"], retention()).is_err()
+        );
+        assert!(AlertModule::parse_rule(
+            Language::Ru,
+            &["USD/RUB", ">", "100", "often"],
+            retention()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_one_shot_alert_fires_once() {
+        let engine = AlertEngine::new();
+        let history = PriceHistory::default();
+        let chat_id = ChatId(1);
+        engine
+            .add(chat_id, rule(AlertCondition::Above { price: 100.0 }, false))
+            .unwrap();
+
+        let below = Quotes::from([(CurrencyPair::USD2RUB, 99.0)]);
+        let above = Quotes::from([(CurrencyPair::USD2RUB, 101.0)]);
+        assert!(engine.evaluate(&below, &history).is_empty());
+
+        let fired = engine.evaluate(&above, &history);
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].chat_id, chat_id);
+        assert_eq!(fired[0].price, 101.0);
+        assert!(engine.list(chat_id).is_empty());
+
+        // The count outlives the rule, also across a snapshot
+        assert_eq!(engine.triggered_count(chat_id), 1);
+        let restored = AlertEngine::new();
+        restored.import(engine.export());
+        assert_eq!(restored.triggered_count(chat_id), 1);
+        assert!(restored.list(chat_id).is_empty());
+    }
+
+    #[test]
+    fn test_repeating_alert_rearms_after_crossing_back() {
+        let engine = AlertEngine::new();
+        let history = PriceHistory::default();
+        let chat_id = ChatId(1);
+        engine
+            .add(chat_id, rule(AlertCondition::Below { price: 90.0 }, true))
+            .unwrap();
+
+        let low = Quotes::from([(CurrencyPair::USD2RUB, 89.0)]);
+        let high = Quotes::from([(CurrencyPair::USD2RUB, 91.0)]);
+        assert_eq!(engine.evaluate(&low, &history).len(), 1);
+        // Still below the threshold, no repeated notification
+        assert!(engine.evaluate(&low, &history).is_empty());
+        assert!(engine.evaluate(&high, &history).is_empty());
+        assert_eq!(engine.evaluate(&low, &history).len(), 1);
+        assert_eq!(engine.list(chat_id)[0].state, AlertState::Triggered);
+    }
+
+    #[test]
+    fn test_parse_cooldown_and_rearm() {
+        let parsed = AlertModule::parse_rule(
+            Language::En,
+            &[
+                "USD/RUB", ">", "100", "repeat", "cooldown", "1h", "rearm", "0,5%",
+            ],
+            retention(),
+        )
+        .unwrap();
+        assert_eq!(parsed.cooldown_minutes, Some(60));
+        assert_eq!(parsed.rearm_percent, Some(0.5));
+        assert_eq!(
+            parsed.describe(Language::En, &PriceFormats::default()),
+            "USD/RUB > 100.00 (repeating, at most every 1h, re-arms 0.50% back)"
+        );
+        let moves = AlertModule::parse_rule(
+            Language::En,
+            &[
+                "USD/RUB", "moves", "2%", "in", "1h", "repeat", "rearm", "25",
+            ],
+            retention(),
+        )
+        .unwrap();
+        assert_eq!(moves.rearm_percent, Some(25.0));
+
+        let args: [&[&str]; 4] = [
+            &["USD/RUB", ">100", "cooldown", "1h"],
+            &["USD/RUB", ">100", "repeat", "cooldown"],
+            &["USD/RUB", ">100", "repeat", "rearm", "100%"],
+            &["USD/RUB", ">"],
+        ];
+        for args in args {
+            assert!(
+                AlertModule::parse_rule(Language::En, args, retention()).is_err(),
+                "{:?}",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn test_hysteresis_ignores_price_hovering_around_threshold() {
+        let engine = AlertEngine::new();
+        let history = PriceHistory::default();
+        let chat_id = ChatId(1);
+        let hovering = AlertRule {
+            rearm_percent: Some(1.0),
+            ..rule(AlertCondition::Above { price: 100.0 }, true)
+        };
+        engine.add(chat_id, hovering).unwrap();
+
+        let quote = |price| Quotes::from([(CurrencyPair::USD2RUB, price)]);
+        assert_eq!(engine.evaluate(&quote(100.5), &history).len(), 1);
+        // Back under the threshold, but by less than 1%
+        assert!(engine.evaluate(&quote(99.5), &history).is_empty());
+        assert!(engine.evaluate(&quote(100.5), &history).is_empty());
+        assert!(engine.evaluate(&quote(98.9), &history).is_empty());
+        assert_eq!(engine.evaluate(&quote(100.5), &history).len(), 1);
+    }
+
+    #[test]
+    fn test_cooldown_delays_rearming() {
+        let engine = AlertEngine::new();
+        let history = PriceHistory::default();
+        let chat_id = ChatId(1);
+        let cooling = AlertRule {
+            cooldown_minutes: Some(60),
+            ..rule(AlertCondition::Below { price: 90.0 }, true)
+        };
+        engine.add(chat_id, cooling).unwrap();
+
+        let quote = |price| Quotes::from([(CurrencyPair::USD2RUB, price)]);
+        assert_eq!(engine.evaluate(&quote(89.0), &history).len(), 1);
+        assert!(engine.evaluate(&quote(91.0), &history).is_empty());
+        assert!(matches!(
+            engine.list(chat_id)[0].state,
+            AlertState::Cooldown { .. }
+        ));
+        assert!(engine.evaluate(&quote(89.0), &history).is_empty());
+
+        // Fired long enough ago, the rule re-arms right away
+        let mut rules = engine.list(chat_id);
+        rules[0].state = AlertState::Triggered;
+        rules[0].fired_at = Some(Utc::now() - Duration::hours(2));
+        engine.import(vec![AlertRecord {
+            chat_id,
+            rules,
+            triggered: 0,
+        }]);
+        assert!(engine.evaluate(&quote(91.0), &history).is_empty());
+        assert_eq!(engine.evaluate(&quote(89.0), &history).len(), 1);
+    }
+
+    #[test]
+    fn test_alert_in_cooldown_stays_silent() {
+        let engine = AlertEngine::new();
+        let history = PriceHistory::default();
+        let chat_id = ChatId(1);
+        let cooling = |until| AlertRule {
+            state: AlertState::Cooldown { until },
+            ..rule(AlertCondition::Above { price: 100.0 }, true)
+        };
+        engine
+            .add(chat_id, cooling(Utc::now() + Duration::hours(1)))
+            .unwrap();
+        engine
+            .add(chat_id, cooling(Utc::now() - Duration::minutes(1)))
+            .unwrap();
+
+        // Only the rule whose cooldown is over fires
+        let above = Quotes::from([(CurrencyPair::USD2RUB, 101.0)]);
+        let fired = engine.evaluate(&above, &history);
+        assert_eq!(fired.len(), 1);
+        let states: Vec<AlertState> = engine.list(chat_id).iter().map(|rule| rule.state).collect();
+        assert!(matches!(states[0], AlertState::Cooldown { .. }));
+        assert_eq!(states[1], AlertState::Triggered);
+    }
+
+    #[test]
+    fn test_parse_moves_rule() {
+        assert_eq!(
+            AlertModule::parse_rule(
+                Language::Ru,
+                &["USDTe/RUB", "moves", "2%", "in", "1h"],
+                retention()
+            ),
+            Ok(AlertRule::new(
+                CurrencyPair::USDTe2RUB,
+                AlertCondition::Moves {
+                    percent: 2.0,
+                    window_minutes: 60,
+                },
+                false
+            ))
+        );
+        let repeating = AlertModule::parse_rule(
+            Language::Ru,
+            &["USD/RUB", "moves", "1,5", "in", "30m", "repeat"],
+            retention(),
+        )
+        .unwrap();
+        assert!(repeating.repeat);
+        assert_eq!(
+            repeating.describe(Language::Ru, &PriceFormats::default()),
+            "USD/RUB ±1.50% за 30m (повторяющееся)"
+        );
+        assert!(
+            AlertModule::parse_rule(Language::Ru, &["USD/RUB", "moves", "2%"], retention())
+                .is_err()
+        );
+        assert!(AlertModule::parse_rule(
+            Language::Ru,
+            &["USD/RUB", "moves", "2%", "in", "1y"],
+            retention()
+        )
+        .is_err());
+        // The moves are looked up in the raw samples only
+        assert!(AlertModule::parse_rule(
+            Language::Ru,
+            &["USD/RUB", "moves", "2%", "in", "7d"],
+            Duration::days(3)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_moves_alert_uses_history_window() {
+        let engine = AlertEngine::new();
+        let history = PriceHistory::default();
+        let chat_id = ChatId(1);
+        let now = Local::now();
+        history.record(&CurrencyPair::USD2RUB, 100.0, now - Duration::hours(3));
+        history.record(&CurrencyPair::USD2RUB, 95.0, now - Duration::minutes(30));
+        engine
+            .add(
+                chat_id,
+                rule(
+                    AlertCondition::Moves {
+                        percent: 2.0,
+                        window_minutes: 60,
+                    },
+                    false,
+                ),
+            )
+            .unwrap();
+
+        // 96.0 is 4% off the price 3 hours ago, but only ~1% within the hour
+        let small_move = Quotes::from([(CurrencyPair::USD2RUB, 96.0)]);
+        assert!(engine.evaluate(&small_move, &history).is_empty());
+
+        let big_move = Quotes::from([(CurrencyPair::USD2RUB, 97.5)]);
+        assert_eq!(engine.evaluate(&big_move, &history).len(), 1);
+    }
+
+    #[test]
+    fn test_alert_changes_are_saved_right_away() {
+        let path =
+            std::env::temp_dir().join(format!("currency-bot-alerts-{}.json", std::process::id()));
+        let manager =
+            SubscriberManager::new("Test message".to_string()).with_state_file(path.clone());
+        let chat_id = ChatId(1);
+        AlertModule::handle_alert(
+            &manager,
+            Language::Ru,
+            chat_id,
+            &["USD/RUB", ">", "100"],
+            retention(),
+        );
+
+        let restored = SubscriberManager::new("Test message".to_string());
+        restored.load_from_file(&path).unwrap();
+        assert_eq!(restored.alerts().list(chat_id).len(), 1);
+
+        AlertModule::handle_alert(
+            &manager,
+            Language::Ru,
+            chat_id,
+            &["delete", "1"],
+            retention(),
+        );
+        let restored = SubscriberManager::new("Test message".to_string());
+        restored.load_from_file(&path).unwrap();
+        assert!(restored.alerts().list(chat_id).is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_delete_and_limit() {
+        let engine = AlertEngine::new();
+        let chat_id = ChatId(1);
+        for i in 0..MAX_ALERTS_PER_CHAT {
+            let price = 100.0 + i as f64;
+            engine
+                .add(chat_id, rule(AlertCondition::Above { price }, false))
+                .unwrap();
+        }
+        assert!(engine
+            .add(chat_id, rule(AlertCondition::Above { price: 1.0 }, false))
+            .is_none());
+
+        let deleted = engine.delete(chat_id, 2).unwrap();
+        assert_eq!(deleted.condition, AlertCondition::Above { price: 101.0 });
+        assert!(engine.delete(chat_id, 0).is_none());
+        assert!(engine.delete(chat_id, MAX_ALERTS_PER_CHAT).is_none());
+    }
+}
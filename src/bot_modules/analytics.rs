@@ -0,0 +1,137 @@
+use super::format::MessageFormat;
+use super::{Module, ModuleContext, ParsedCommand, TelegramBot};
+use crate::domain::{get_all_currency_pairs, CurrencyPair};
+use crate::i18n::{tr, Language};
+use crate::price_service::analytics::Indicators;
+use crate::price_service::history::format_period;
+use async_trait::async_trait;
+use chrono::Duration;
+use std::error::Error;
+use std::str::FromStr;
+use teloxide::prelude::*;
+
+/// `/ta <pair>` shows the moving averages and the realized volatility of a
+/// pair over the configured windows, see [`crate::price_service::analytics`]
+pub struct AnalyticsModule;
+
+impl AnalyticsModule {
+    /// Indicators of `pair` one window per line
+    fn render(
+        language: Language,
+        format: MessageFormat,
+        pair: &CurrencyPair,
+        indicators: &[Indicators],
+    ) -> String {
+        let value = |value: f64| format.code(&format!("{:.2}", value));
+        let lines: Vec<String> = indicators
+            .iter()
+            .map(|indicators| {
+                let window = format_period(Duration::minutes(indicators.window_minutes));
+                let volatility = match indicators.volatility {
+                    Some(volatility) => format.code(&format!("{:.2}%", volatility)),
+                    None => format.escape(tr(language, "ta.no_volatility")),
+                };
+                format.render(
+                    tr(language, "ta.line"),
+                    &[
+                        ("window", format.bold(&window)),
+                        ("sma", value(indicators.sma)),
+                        ("ema", value(indicators.ema)),
+                        ("volatility", volatility),
+                        ("samples", format.escape(&indicators.samples.to_string())),
+                    ],
+                )
+            })
+            .collect();
+        let title = format.render(
+            tr(language, "ta.title"),
+            &[("pair", format.bold(&pair.to_string()))],
+        );
+        format!("{}\n\n{}", title, lines.join("\n"))
+    }
+
+    fn respond(ctx: &ModuleContext, language: Language, args: &[&str]) -> String {
+        let format = ctx.format;
+        let pairs = get_all_currency_pairs()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let [pair] = args else {
+            return format.render(tr(language, "ta.help"), &[("pairs", format.escape(&pairs))]);
+        };
+        let Ok(pair) = CurrencyPair::from_str(pair) else {
+            return format.render(
+                tr(language, "unknown_pair"),
+                &[("pair", format.escape(pair))],
+            );
+        };
+        let indicators = ctx.price_service.history().analytics().indicators(&pair);
+        if indicators.is_empty() {
+            return format.render(
+                tr(language, "ta.no_data"),
+                &[("pair", format.escape(&pair.to_string()))],
+            );
+        }
+        Self::render(language, format, &pair, &indicators)
+    }
+}
+
+#[async_trait]
+impl Module for AnalyticsModule {
+    fn name(&self) -> &str {
+        "Analytics"
+    }
+
+    fn commands(&self) -> Vec<&str> {
+        vec!["/ta"]
+    }
+
+    fn command_descriptions(&self, language: Language) -> Vec<(&str, &str)> {
+        vec![("/ta", tr(language, "ta.menu"))]
+    }
+
+    fn usage(&self, language: Language) -> Vec<String> {
+        vec![tr(language, "ta.usage").to_string()]
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let Some(command) = ParsedCommand::from_message(&msg) else {
+            return Ok(());
+        };
+        let language = ctx.languages.get(msg.chat.id);
+        let response = Self::respond(ctx, language, &command.arg_list());
+        ctx.send_reply(&bot, &msg, ctx.format.reply(&bot, &msg, response))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+
+    #[test]
+    fn test_ta_command() {
+        let ctx = ModuleContext::default();
+        let respond = |args: &[&str]| AnalyticsModule::respond(&ctx, Language::En, args);
+        assert!(respond(&[]).starts_with("Usage: /ta"));
+        assert_eq!(respond(&["EUR/RUB"]), "❌ Unknown pair: EUR/RUB");
+        assert_eq!(respond(&["USD/RUB"]), "No prices of USD/RUB recorded yet.");
+
+        let history = ctx.price_service.history();
+        let now = Local::now();
+        history.record(&CurrencyPair::USD2RUB, 90.0, now - Duration::minutes(10));
+        history.record(&CurrencyPair::USD2RUB, 92.0, now);
+        let response = respond(&["USD/RUB"]);
+        assert!(response
+            .starts_with("📈 USD/RUB: moving averages and volatility\n\n1h: SMA 91.00, EMA "));
+        assert_eq!(response.lines().count(), 5);
+    }
+}
@@ -0,0 +1,201 @@
+use super::middleware::{HandlerResult, Middleware};
+use super::{ParsedCommand, TelegramBot};
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use teloxide::prelude::*;
+use teloxide::types::MessageId;
+
+/// One command handled by a module
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub chat_id: ChatId,
+    pub user_id: Option<UserId>,
+    pub time: DateTime<Local>,
+    /// Command without the arguments, e.g. `/price`
+    pub command: String,
+    pub module: String,
+    pub latency_ms: u64,
+    /// Error of the handler, `None` when it succeeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+struct AuditState {
+    entries: HashMap<ChatId, VecDeque<AuditEntry>>,
+    /// Start of the commands in flight by chat and message
+    started: HashMap<(ChatId, MessageId), Instant>,
+}
+
+/// Middleware recording every handled command per chat, kept across
+/// restarts in the runtime snapshot and shown by `/audit <chat_id>`.
+/// Each chat keeps at most `max_entries` commands, none older than
+/// `retention`.
+#[derive(Clone)]
+pub struct AuditLog {
+    max_entries: usize,
+    retention: chrono::Duration,
+    state: Arc<Mutex<AuditState>>,
+}
+
+impl AuditLog {
+    pub fn new(max_entries: usize, retention: chrono::Duration) -> Self {
+        Self {
+            max_entries,
+            retention,
+            state: Arc::new(Mutex::new(AuditState::default())),
+        }
+    }
+
+    fn record(&self, entry: AuditEntry) {
+        let cutoff = entry.time - self.retention;
+        let mut state = self.state.lock().unwrap();
+        let entries = state.entries.entry(entry.chat_id).or_default();
+        entries.push_back(entry);
+        while entries.len() > self.max_entries
+            || entries.front().is_some_and(|entry| entry.time < cutoff)
+        {
+            entries.pop_front();
+        }
+    }
+
+    /// Commands of `chat_id` within the retention, oldest first
+    pub fn entries(&self, chat_id: ChatId) -> Vec<AuditEntry> {
+        let cutoff = Local::now() - self.retention;
+        let state = self.state.lock().unwrap();
+        state
+            .entries
+            .get(&chat_id)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter(|entry| entry.time >= cutoff)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Time of the latest command of `chat_id` within the retention
+    pub fn last_seen(&self, chat_id: ChatId) -> Option<DateTime<Local>> {
+        let cutoff = Local::now() - self.retention;
+        let state = self.state.lock().unwrap();
+        let entries = state.entries.get(&chat_id)?;
+        entries
+            .back()
+            .map(|entry| entry.time)
+            .filter(|time| *time >= cutoff)
+    }
+
+    /// Entries of every chat for the runtime snapshot
+    pub fn export(&self) -> Vec<AuditEntry> {
+        let cutoff = Local::now() - self.retention;
+        let state = self.state.lock().unwrap();
+        state
+            .entries
+            .values()
+            .flatten()
+            .filter(|entry| entry.time >= cutoff)
+            .cloned()
+            .collect()
+    }
+
+    /// Restore entries written by [`Self::export`], applying the current limits
+    pub fn import(&self, mut entries: Vec<AuditEntry>) {
+        entries.sort_by_key(|entry| entry.time);
+        for entry in entries {
+            self.record(entry);
+        }
+    }
+}
+
+#[async_trait]
+impl Middleware for AuditLog {
+    fn name(&self) -> &str {
+        "Audit"
+    }
+
+    async fn before(
+        &self,
+        _bot: &TelegramBot,
+        msg: &Message,
+        _module: &str,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        self.state
+            .lock()
+            .unwrap()
+            .started
+            .insert((msg.chat.id, msg.id), Instant::now());
+        Ok(true)
+    }
+
+    async fn after(&self, msg: &Message, module: &str, result: &HandlerResult) {
+        let started = self
+            .state
+            .lock()
+            .unwrap()
+            .started
+            .remove(&(msg.chat.id, msg.id));
+        // Plain text answered by a module, e.g. conversions, is not a command
+        let Some(command) = ParsedCommand::from_message(msg) else {
+            return;
+        };
+        let latency = started.map(|started| started.elapsed()).unwrap_or_default();
+        self.record(AuditEntry {
+            chat_id: msg.chat.id,
+            user_id: msg.from.as_ref().map(|user| user.id),
+            time: Local::now(),
+            command: command.name.to_string(),
+            module: module.to_string(),
+            latency_ms: latency.as_millis() as u64,
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn entry(chat_id: i64, command: &str, time: DateTime<Local>) -> AuditEntry {
+        AuditEntry {
+            chat_id: ChatId(chat_id),
+            user_id: Some(UserId(7)),
+            time,
+            command: command.to_string(),
+            module: "PriceModule".to_string(),
+            latency_ms: 12,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_retention_limits() {
+        let log = AuditLog::new(2, Duration::days(30));
+        let now = Local::now();
+        log.record(entry(1, "/old", now - Duration::days(31)));
+        log.record(entry(1, "/first", now - Duration::minutes(3)));
+        log.record(entry(2, "/other", now - Duration::minutes(2)));
+        assert_eq!(log.entries(ChatId(1)).len(), 1);
+
+        log.record(entry(1, "/second", now - Duration::minutes(1)));
+        log.record(entry(1, "/third", now));
+        let commands: Vec<_> = log
+            .entries(ChatId(1))
+            .into_iter()
+            .map(|entry| entry.command)
+            .collect();
+        assert_eq!(commands, ["/second", "/third"]);
+
+        let restored = AuditLog::new(1, Duration::days(30));
+        restored.import(log.export());
+        assert_eq!(restored.entries(ChatId(1)), [entry(1, "/third", now)]);
+        assert_eq!(restored.entries(ChatId(2)).len(), 1);
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::HashSet;
+use teloxide::types::{ChatId, UserId};
+
+/// Role a user needs to run the commands of a module
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    User,
+    Admin,
+}
+
+/// Parse a comma-separated list of Telegram IDs, e.g. `123,-100500`
+pub fn parse_id_list(ids: &str) -> Result<Vec<i64>, String> {
+    ids.split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| id.parse().map_err(|_| format!("Invalid ID '{}'", id)))
+        .collect()
+}
+
+/// Who may use the bot and who is an admin. Lists hold user IDs and chat IDs
+/// (negative for groups), so a whole group can be allowed or blocked.
+#[derive(Debug, Default)]
+pub struct AccessControl {
+    admin_ids: HashSet<UserId>,
+    /// Users and chats allowed to use the bot, everyone when not set
+    whitelist: Option<HashSet<i64>>,
+    blacklist: HashSet<i64>,
+}
+
+impl AccessControl {
+    pub fn new(admin_ids: HashSet<UserId>) -> Self {
+        Self {
+            admin_ids,
+            ..Self::default()
+        }
+    }
+
+    /// Only serve the listed users and chats (private deployments)
+    pub fn with_whitelist(mut self, ids: HashSet<i64>) -> Self {
+        self.whitelist = Some(ids);
+        self
+    }
+
+    /// Ignore the listed users and chats
+    pub fn with_blacklist(mut self, ids: HashSet<i64>) -> Self {
+        self.blacklist = ids;
+        self
+    }
+
+    pub fn role(&self, user: Option<UserId>) -> Role {
+        match user {
+            Some(user) if self.admin_ids.contains(&user) => Role::Admin,
+            _ => Role::User,
+        }
+    }
+
+    /// Whether the bot serves `user` in `chat_id`. Admins are always served.
+    pub fn is_allowed(&self, chat_id: ChatId, user: Option<UserId>) -> bool {
+        if self.role(user) == Role::Admin {
+            return true;
+        }
+        let user_id = user.map(|user| user.0 as i64);
+        let listed = |ids: &HashSet<i64>| {
+            ids.contains(&chat_id.0) || user_id.is_some_and(|id| ids.contains(&id))
+        };
+        if listed(&self.blacklist) {
+            return false;
+        }
+        self.whitelist.as_ref().is_none_or(listed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_id_list() {
+        assert_eq!(parse_id_list("123, -100500,"), Ok(vec![123, -100500]));
+        assert_eq!(parse_id_list(""), Ok(vec![]));
+        assert!(parse_id_list("123,abc").is_err());
+    }
+
+    #[test]
+    fn test_roles() {
+        let access = AccessControl::new(HashSet::from([UserId(1)]));
+        assert_eq!(access.role(Some(UserId(1))), Role::Admin);
+        assert_eq!(access.role(Some(UserId(2))), Role::User);
+        assert_eq!(access.role(None), Role::User);
+        assert!(Role::Admin > Role::User);
+    }
+
+    #[test]
+    fn test_whitelist_and_blacklist() {
+        let open = AccessControl::default().with_blacklist(HashSet::from([2, -300]));
+        assert!(open.is_allowed(ChatId(1), Some(UserId(1))));
+        assert!(!open.is_allowed(ChatId(2), Some(UserId(2))));
+        assert!(!open.is_allowed(ChatId(-300), Some(UserId(1))));
+
+        let private = AccessControl::new(HashSet::from([UserId(9)]))
+            .with_whitelist(HashSet::from([1, -100]))
+            .with_blacklist(HashSet::from([3]));
+        assert!(private.is_allowed(ChatId(1), Some(UserId(1))));
+        // Any member of a whitelisted group
+        assert!(private.is_allowed(ChatId(-100), Some(UserId(2))));
+        // A whitelisted user in another group
+        assert!(private.is_allowed(ChatId(-200), Some(UserId(1))));
+        assert!(!private.is_allowed(ChatId(-100), Some(UserId(3))));
+        assert!(!private.is_allowed(ChatId(2), Some(UserId(2))));
+        assert!(!private.is_allowed(ChatId(-200), None));
+        // Admins are never locked out
+        assert!(private.is_allowed(ChatId(9), Some(UserId(9))));
+    }
+}
@@ -0,0 +1,226 @@
+use super::{reply, ArgError, Module, ModuleContext, ParsedCommand, Role, TelegramBot};
+use crate::i18n::{lookup, Language};
+use async_trait::async_trait;
+use std::error::Error;
+use std::num::{ParseFloatError, ParseIntError};
+use teloxide::prelude::*;
+use teloxide::utils::command::{BotCommands, ParseError};
+
+/// Module declaring its commands as an enum deriving teloxide's
+/// [`BotCommands`], registered wrapped in a [`BotCommandsModule`]. The derive
+/// parses the arguments and its descriptions fill the command menu and /help;
+/// a description that is an i18n key (`#[command(description = "echo.menu")]`)
+/// is translated. Hidden commands have no menu entry and are not routed.
+#[async_trait]
+pub trait CommandsModule: Send + Sync {
+    type Command: BotCommands + Send;
+
+    fn name(&self) -> &str;
+
+    /// Heading of the module's section in /help
+    fn description(&self, _language: Language) -> Option<&str> {
+        None
+    }
+
+    /// Help lines of the module's commands, by default the derived commands
+    /// with their descriptions
+    fn usage(&self, language: Language) -> Vec<String> {
+        Self::Command::bot_commands()
+            .into_iter()
+            .map(|command| {
+                let description = translate(language, &command.description);
+                format!("{} - {}", command.command, description)
+            })
+            .collect()
+    }
+
+    /// Role required to run the module's commands
+    fn required_role(&self) -> Role {
+        Role::User
+    }
+
+    async fn handle_command(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+        command: Self::Command,
+    ) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+/// [`Module`] routing the derived commands of a [`CommandsModule`]. Commands
+/// whose arguments don't parse are answered with the error and the module's
+/// help lines.
+pub struct BotCommandsModule<M> {
+    module: M,
+    /// Command with its prefix and untranslated description
+    commands: Vec<(String, String)>,
+}
+
+impl<M: CommandsModule> BotCommandsModule<M> {
+    pub fn new(module: M) -> Self {
+        Self {
+            module,
+            commands: M::Command::bot_commands()
+                .into_iter()
+                .map(|command| (command.command, command.description))
+                .collect(),
+        }
+    }
+}
+
+/// Translation of a description given as an i18n key, other texts as they are
+fn translate(language: Language, description: &str) -> &str {
+    lookup(language, description).unwrap_or(description)
+}
+
+/// Error of the derived parser without its English messages, see
+/// [`ArgError::message`]
+fn arg_error(error: ParseError) -> ArgError {
+    match error {
+        ParseError::TooFewArguments { .. } => ArgError::Missing,
+        // The message is "Excess argument: <argument>"
+        ParseError::TooManyArguments { message, .. } => {
+            let argument = message.rsplit(": ").next().unwrap_or_default();
+            ArgError::Unexpected(argument.to_string())
+        }
+        ParseError::IncorrectFormat(e) if e.is::<ParseFloatError>() || e.is::<ParseIntError>() => {
+            ArgError::NotANumber
+        }
+        ParseError::IncorrectFormat(_)
+        | ParseError::Custom(_)
+        | ParseError::UnknownCommand(_)
+        | ParseError::WrongBotName(_) => ArgError::Invalid,
+    }
+}
+
+#[async_trait]
+impl<M: CommandsModule> Module for BotCommandsModule<M> {
+    fn name(&self) -> &str {
+        self.module.name()
+    }
+
+    fn commands(&self) -> Vec<&str> {
+        self.commands
+            .iter()
+            .map(|(command, _)| command.as_str())
+            .collect()
+    }
+
+    fn command_descriptions(&self, language: Language) -> Vec<(&str, &str)> {
+        self.commands
+            .iter()
+            .map(|(command, description)| (command.as_str(), translate(language, description)))
+            .collect()
+    }
+
+    fn description(&self, language: Language) -> Option<&str> {
+        self.module.description(language)
+    }
+
+    fn usage(&self, language: Language) -> Vec<String> {
+        self.module.usage(language)
+    }
+
+    fn required_role(&self) -> Role {
+        self.module.required_role()
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let Some(command) = ParsedCommand::from_message(&msg) else {
+            return Ok(());
+        };
+        // The registry already dropped commands addressed to other bots
+        let text = format!("{} {}", command.name, command.args);
+        match M::Command::parse(text.trim_end(), "") {
+            Ok(command) => self.module.handle_command(ctx, bot, msg, command).await,
+            Err(e) => {
+                let language = ctx.languages.get(msg.chat.id);
+                let mut text = arg_error(e).message(language);
+                for line in self.usage(language) {
+                    text.push('\n');
+                    text.push_str(&line);
+                }
+                ctx.send_reply(&bot, &msg, reply(&bot, &msg, text)).await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(BotCommands, Debug, PartialEq)]
+    #[command(rename_rule = "lowercase")]
+    enum TestCommand {
+        #[command(description = "echo.menu")]
+        Echo(String),
+        #[command(description = "Set a level", parse_with = "split")]
+        Level { pair: String, price: f64 },
+    }
+
+    struct TestModule;
+
+    #[async_trait]
+    impl CommandsModule for TestModule {
+        type Command = TestCommand;
+
+        fn name(&self) -> &str {
+            "Test"
+        }
+
+        async fn handle_command(
+            &self,
+            _ctx: &ModuleContext,
+            _bot: TelegramBot,
+            _msg: Message,
+            _command: TestCommand,
+        ) -> Result<(), Box<dyn Error + Send + Sync>> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_derived_commands() {
+        let module = BotCommandsModule::new(TestModule);
+        assert_eq!(module.commands(), vec!["/echo", "/level"]);
+        assert_eq!(
+            module.command_descriptions(Language::En),
+            vec![("/echo", "Send an echo reply"), ("/level", "Set a level")]
+        );
+        assert_eq!(
+            module.usage(Language::Ru),
+            ["/echo - Отправить эхо-ответ", "/level - Set a level"]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let parse = |text| TestCommand::parse(text, "").map_err(arg_error);
+        assert_eq!(parse("/level USD/RUB 95,5"), Err(ArgError::NotANumber));
+        assert_eq!(
+            parse("/level USD/RUB 95.5"),
+            Ok(TestCommand::Level {
+                pair: "USD/RUB".to_string(),
+                price: 95.5
+            })
+        );
+        assert_eq!(parse("/level USD/RUB"), Err(ArgError::Missing));
+        assert_eq!(
+            parse("/level USD/RUB 95 1"),
+            Err(ArgError::Unexpected("1".to_string()))
+        );
+        // The replies are translated
+        assert_eq!(
+            ArgError::NotANumber.message(Language::Ru),
+            "❌ Аргумент должен быть числом"
+        );
+    }
+}
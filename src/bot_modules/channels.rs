@@ -0,0 +1,358 @@
+use super::format::{MessageFormat, PriceFormats};
+use super::outbound::{OutboundQueue, Priority};
+use super::subscribers::{
+    day_ago_quotes, fetch_quotes, is_edit_target_lost, render_footer, render_quotes, Quotes,
+    Sparklines, Trends, DEFAULT_PRICE_LINE,
+};
+use super::TelegramBot;
+use crate::domain::get_all_currency_pairs;
+use crate::price_service::PriceService;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use teloxide::prelude::*;
+use teloxide::requests::HasPayload;
+use teloxide::types::{MessageId, Recipient};
+use teloxide::RequestError;
+
+/// Channel the bot publishes rate updates to, with its own template
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelConfig {
+    pub chat: Recipient,
+    /// Rendered for every pair like a periodic message template, the default
+    /// price line when not set
+    pub template: Option<String>,
+}
+
+/// Parse `CHANNELS`: entries separated by `;`, each a channel ID or
+/// `@username` optionally followed by `|template`, e.g.
+/// `-1001234567890|💰 {pair}: {price} {delta};@rates_channel`
+pub fn parse_channels(spec: &str) -> Result<Vec<ChannelConfig>, String> {
+    spec.split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (chat, template) = match entry.split_once('|') {
+                Some((chat, template)) => (chat.trim(), Some(template.trim().to_string())),
+                None => (entry, None),
+            };
+            let chat = if chat.starts_with('@') && chat.len() > 1 {
+                Recipient::ChannelUsername(chat.to_string())
+            } else {
+                chat.parse()
+                    .map(|id| Recipient::Id(ChatId(id)))
+                    .map_err(|_| format!("Invalid channel '{}'", chat))?
+            };
+            Ok(ChannelConfig {
+                chat,
+                template: template.filter(|template| !template.is_empty()),
+            })
+        })
+        .collect()
+}
+
+/// Last post of a channel, edited by the next publication
+#[derive(Debug)]
+struct ChannelPost {
+    message_id: MessageId,
+    quotes: Quotes,
+}
+
+/// Last post of a channel in the [runtime snapshot](crate::snapshot)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelPostRecord {
+    pub chat: Recipient,
+    pub message_id: i32,
+    pub quotes: Quotes,
+}
+
+/// Publishes rate updates to the configured channels on its own schedule,
+/// independent of the chat subscriptions. Every channel keeps one post that
+/// is edited in place instead of flooding the channel.
+pub struct ChannelPublisher {
+    channels: Vec<ChannelConfig>,
+    format: MessageFormat,
+    outbound: OutboundQueue,
+    posts: Mutex<HashMap<Recipient, ChannelPost>>,
+    /// Template of the footer of the posts (`display.footer`)
+    footer: Option<String>,
+    price_formats: PriceFormats,
+}
+
+impl ChannelPublisher {
+    pub fn new(channels: Vec<ChannelConfig>, format: MessageFormat) -> Self {
+        Self {
+            channels,
+            format,
+            outbound: OutboundQueue::default(),
+            posts: Mutex::new(HashMap::new()),
+            footer: None,
+            price_formats: PriceFormats::default(),
+        }
+    }
+
+    /// End the rate posts with a footer rendered from `template`, e.g. the
+    /// attribution a channel republishing rates must show
+    pub fn with_footer(mut self, template: Option<String>) -> Self {
+        self.footer = template;
+        self
+    }
+
+    /// Write the prices of the posts in `formats`
+    pub fn with_price_formats(mut self, formats: PriceFormats) -> Self {
+        self.price_formats = formats;
+        self
+    }
+
+    pub fn price_formats(&self) -> &PriceFormats {
+        &self.price_formats
+    }
+
+    /// Send the posts through the bot's queue instead of one of their own
+    pub fn with_outbound(mut self, outbound: OutboundQueue) -> Self {
+        self.outbound = outbound;
+        self
+    }
+
+    /// Last post of every channel
+    pub fn export_posts(&self) -> Vec<ChannelPostRecord> {
+        let posts = self.posts.lock().unwrap();
+        posts
+            .iter()
+            .map(|(chat, post)| ChannelPostRecord {
+                chat: chat.clone(),
+                message_id: post.message_id.0,
+                quotes: post.quotes.clone(),
+            })
+            .collect()
+    }
+
+    /// Restore the posts of an [`Self::export_posts`] so the next publication
+    /// edits them, channels no longer configured are left out
+    pub fn import_posts(&self, records: Vec<ChannelPostRecord>) {
+        let mut posts = self.posts.lock().unwrap();
+        for record in records {
+            if self
+                .channels
+                .iter()
+                .any(|channel| channel.chat == record.chat)
+            {
+                posts.insert(
+                    record.chat,
+                    ChannelPost {
+                        message_id: MessageId(record.message_id),
+                        quotes: record.quotes,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Markup of the channel posts
+    pub fn format(&self) -> MessageFormat {
+        self.format
+    }
+
+    /// Send `text` to every channel as a new post, leaving the edited rate
+    /// posts alone
+    pub async fn post(&self, bot: &TelegramBot, text: &str) {
+        for channel in &self.channels {
+            let post = self
+                .outbound
+                .send(Priority::Broadcast, channel.chat.clone(), || {
+                    self.format
+                        .send_message(bot, channel.chat.clone(), text.to_string())
+                        .send()
+                })
+                .await;
+            if let Err(e) = post {
+                tracing::error!("Failed to post to channel {}: {}", channel.chat, e);
+            }
+        }
+    }
+
+    /// Text of the channel's next post, with deltas against its last post
+    /// and changes against the `day_ago` quotes
+    fn render(
+        &self,
+        channel: &ChannelConfig,
+        quotes: &Quotes,
+        day_ago: &Quotes,
+        footer: Option<&str>,
+    ) -> String {
+        let posts = self.posts.lock().unwrap();
+        let trends = Trends {
+            previous: posts.get(&channel.chat).map(|post| &post.quotes),
+            day_ago,
+            sparklines: &Sparklines::new(),
+        };
+        let text = render_quotes(
+            channel.template.as_deref().unwrap_or(DEFAULT_PRICE_LINE),
+            get_all_currency_pairs(),
+            quotes,
+            trends,
+            self.format,
+            &self.price_formats,
+        );
+        match footer {
+            Some(footer) => format!("{}\n\n{}", text, footer),
+            None => text,
+        }
+    }
+
+    fn remember_post(&self, channel: &ChannelConfig, message_id: MessageId, quotes: &Quotes) {
+        let mut posts = self.posts.lock().unwrap();
+        posts.insert(
+            channel.chat.clone(),
+            ChannelPost {
+                message_id,
+                quotes: quotes.clone(),
+            },
+        );
+    }
+
+    /// Publish the current rates to every channel
+    pub async fn publish(&self, bot: &TelegramBot, price_service: &PriceService) {
+        let pairs = get_all_currency_pairs();
+        let quotes = fetch_quotes(price_service, &pairs).await;
+        let footer = self
+            .footer
+            .as_deref()
+            .map(|template| render_footer(template, price_service, &pairs, self.format, None));
+        let day_ago = day_ago_quotes(price_service.history(), &pairs);
+        for channel in &self.channels {
+            let text = self.render(channel, &quotes, &day_ago, footer.as_deref());
+            if let Err(e) = self.publish_to(bot, channel, &text, &quotes).await {
+                tracing::error!("Failed to publish rates to channel {}: {}", channel.chat, e);
+            }
+        }
+    }
+
+    /// Edit the channel's last post, or send a new one when there is none or
+    /// it can no longer be edited
+    async fn publish_to(
+        &self,
+        bot: &TelegramBot,
+        channel: &ChannelConfig,
+        text: &str,
+        quotes: &Quotes,
+    ) -> Result<(), RequestError> {
+        let last_post = self
+            .posts
+            .lock()
+            .unwrap()
+            .get(&channel.chat)
+            .map(|post| post.message_id);
+
+        if let Some(message_id) = last_post {
+            let edit = self
+                .outbound
+                .send(Priority::Broadcast, channel.chat.clone(), || {
+                    let mut request = bot.edit_message_text(channel.chat.clone(), message_id, text);
+                    request.payload_mut().parse_mode = self.format.parse_mode();
+                    request.send()
+                })
+                .await;
+            match edit {
+                Ok(_) => {
+                    self.remember_post(channel, message_id, quotes);
+                    return Ok(());
+                }
+                Err(e) if is_edit_target_lost(&e) => {
+                    tracing::info!(
+                        "Post in channel {} can't be edited ({}), sending a new one",
+                        channel.chat,
+                        e
+                    );
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let message = self
+            .outbound
+            .send(Priority::Broadcast, channel.chat.clone(), || {
+                self.format
+                    .send_message(bot, channel.chat.clone(), text.to_string())
+                    .send()
+            })
+            .await?;
+        self.remember_post(channel, message.id, quotes);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::CurrencyPair;
+
+    #[test]
+    fn test_parse_channels() {
+        let channels =
+            parse_channels("-1001234567890|💰 {pair}: {price}; @rates_channel ;").unwrap();
+        assert_eq!(
+            channels,
+            vec![
+                ChannelConfig {
+                    chat: Recipient::Id(ChatId(-1001234567890)),
+                    template: Some("💰 {pair}: {price}".to_string()),
+                },
+                ChannelConfig {
+                    chat: Recipient::ChannelUsername("@rates_channel".to_string()),
+                    template: None,
+                },
+            ]
+        );
+        assert!(parse_channels("").unwrap().is_empty());
+        assert!(parse_channels("rates_channel").is_err());
+        assert!(parse_channels("@").is_err());
+    }
+
+    #[test]
+    fn test_render_uses_channel_template_and_last_post() {
+        let channel = ChannelConfig {
+            chat: Recipient::Id(ChatId(-100)),
+            template: Some("{pair} {price} {delta}".to_string()),
+        };
+        let publisher = ChannelPublisher::new(vec![channel.clone()], MessageFormat::Plain);
+        let first = Quotes::from([(CurrencyPair::USD2RUB, 90.0)]);
+        assert!(publisher
+            .render(&channel, &first, &Quotes::new(), None)
+            .ends_with("USD/RUB 90.00"));
+
+        publisher.remember_post(&channel, MessageId(1), &first);
+        let second = Quotes::from([(CurrencyPair::USD2RUB, 91.0)]);
+        assert!(publisher
+            .render(&channel, &second, &Quotes::new(), None)
+            .ends_with("USD/RUB 91.00 ▲ +1.00 (+1.11%)"));
+        assert!(publisher
+            .render(&channel, &second, &Quotes::new(), Some("Source: NewLine"))
+            .ends_with("(+1.11%)\n\nSource: NewLine"));
+    }
+
+    #[test]
+    fn test_import_posts_of_configured_channels() {
+        let channel = ChannelConfig {
+            chat: Recipient::Id(ChatId(-100)),
+            template: None,
+        };
+        let removed = ChannelConfig {
+            chat: Recipient::ChannelUsername("@old_channel".to_string()),
+            template: None,
+        };
+        let source =
+            ChannelPublisher::new(vec![channel.clone(), removed.clone()], MessageFormat::Plain);
+        let quotes = Quotes::from([(CurrencyPair::USD2RUB, 90.0)]);
+        source.remember_post(&channel, MessageId(7), &quotes);
+        source.remember_post(&removed, MessageId(8), &quotes);
+        let json = serde_json::to_string(&source.export_posts()).unwrap();
+
+        let target = ChannelPublisher::new(vec![channel], MessageFormat::Plain);
+        target.import_posts(serde_json::from_str(&json).unwrap());
+        let posts = target.export_posts();
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].chat, Recipient::Id(ChatId(-100)));
+        assert_eq!(posts[0].message_id, 7);
+    }
+}
@@ -0,0 +1,370 @@
+use super::outbound::{OutboundQueue, Priority};
+use super::{reply, topic_of, ArgError, Args, Module, ModuleContext, ParsedCommand, TelegramBot};
+use crate::domain::{get_all_currency_pairs, CurrencyPair};
+use crate::i18n::{tr, tr_args, Language};
+use crate::price_service::history::{parse_period, PriceHistory, PricePoint};
+use async_trait::async_trait;
+use chrono::{Duration, Local};
+use plotters::prelude::*;
+use plotters::style::{register_font, FontStyle};
+use std::error::Error;
+use std::path::Path;
+use teloxide::prelude::*;
+use teloxide::types::{InputFile, ThreadId};
+use teloxide::RequestError;
+
+/// Font used for chart captions, labels and annotations
+pub const DEFAULT_CHART_FONT_PATH: &str = "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf";
+
+/// Period charted when none is given
+pub const DEFAULT_CHART_PERIOD: &str = "1d";
+
+const CHART_FONT_FAMILY: &str = "sans-serif";
+const CHART_WIDTH: u32 = 800;
+const CHART_HEIGHT: u32 = 450;
+
+/// Make the font at `path` available to chart rendering. Plotters keeps
+/// fonts for the lifetime of the process, so the bytes are leaked once.
+pub fn register_chart_font(path: &Path) -> Result<(), String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("Failed to read font {}: {}", path.display(), e))?;
+    register_font(
+        CHART_FONT_FAMILY,
+        FontStyle::Normal,
+        Box::leak(bytes.into_boxed_slice()),
+    )
+    .map_err(|_| format!("Invalid font file {}", path.display()))
+}
+
+/// Chart module rendering the recorded price history of a pair
+pub struct ChartModule;
+
+impl ChartModule {
+    /// Usage of `/chart` with the pairs and the longest period, `max`
+    fn help(language: Language, max: Duration) -> String {
+        let pairs = get_all_currency_pairs()
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        tr_args(
+            language,
+            "chart.help",
+            &[("max", &max.num_days()), ("pairs", &pairs)],
+        )
+    }
+
+    /// Parse `<pair> [period]` into the pair, the period of at most `max` and
+    /// its label
+    fn parse_args(args: &str, max: Duration) -> Result<(CurrencyPair, Duration, String), ArgError> {
+        let mut args = Args::new(args);
+        let pair = args.pair()?;
+        let label = args.optional(Args::word)?.unwrap_or(DEFAULT_CHART_PERIOD);
+        let period =
+            parse_period(label, max).ok_or_else(|| ArgError::BadPeriod(label.to_string()))?;
+        args.finish()?;
+        Ok((pair, period, label.to_string()))
+    }
+
+    /// Render a line chart of `points` as PNG with min/max/current annotations
+    fn render(
+        language: Language,
+        pair: &CurrencyPair,
+        period: &str,
+        points: &[PricePoint],
+    ) -> Result<Vec<u8>, String> {
+        let (Some(first), Some(last)) = (points.first(), points.last()) else {
+            return Err("No price points to draw".to_string());
+        };
+        let by_price = |a: &&PricePoint, b: &&PricePoint| a.price.total_cmp(&b.price);
+        let min = points.iter().min_by(by_price).copied().unwrap_or(*first);
+        let max = points.iter().max_by(by_price).copied().unwrap_or(*first);
+        // Keep a flat line off the chart border
+        let padding = ((max.price - min.price) * 0.1).max(0.01);
+
+        let mut buffer = vec![0u8; (CHART_WIDTH * CHART_HEIGHT * 3) as usize];
+        {
+            let root = BitMapBackend::with_buffer(&mut buffer, (CHART_WIDTH, CHART_HEIGHT))
+                .into_drawing_area();
+            root.fill(&WHITE).map_err(|e| e.to_string())?;
+
+            let mut chart = ChartBuilder::on(&root)
+                .caption(
+                    format!("{} — {}", pair, period),
+                    (CHART_FONT_FAMILY, 24).into_font(),
+                )
+                .margin(16)
+                // Room for the annotation of the latest point
+                .margin_right(110)
+                .x_label_area_size(32)
+                .y_label_area_size(64)
+                .build_cartesian_2d(
+                    first.time..last.time,
+                    (min.price - padding)..(max.price + padding),
+                )
+                .map_err(|e| e.to_string())?;
+
+            chart
+                .configure_mesh()
+                .x_labels(6)
+                .x_label_formatter(&|time| time.format("%d.%m %H:%M").to_string())
+                .y_label_formatter(&|price| format!("{:.2}", price))
+                .label_style((CHART_FONT_FAMILY, 14))
+                .draw()
+                .map_err(|e| e.to_string())?;
+
+            chart
+                .draw_series(LineSeries::new(
+                    points.iter().map(|point| (point.time, point.price)),
+                    BLUE.stroke_width(2),
+                ))
+                .map_err(|e| e.to_string())?;
+
+            let annotations = [
+                (tr(language, "chart.min"), min, RED),
+                (tr(language, "chart.max"), max, GREEN),
+                (tr(language, "chart.now"), *last, BLUE),
+            ];
+            for (label, point, color) in annotations {
+                let position = (point.time, point.price);
+                chart
+                    .draw_series([EmptyElement::at(position)
+                        + Circle::new((0, 0), 4, color.filled())
+                        + Text::new(
+                            format!("{} {:.2}", label, point.price),
+                            (6, -18),
+                            (CHART_FONT_FAMILY, 14).into_font().color(&color),
+                        )])
+                    .map_err(|e| e.to_string())?;
+            }
+
+            root.present().map_err(|e| e.to_string())?;
+        }
+
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, CHART_WIDTH, CHART_HEIGHT);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        writer
+            .write_image_data(&buffer)
+            .map_err(|e| e.to_string())?;
+        writer.finish().map_err(|e| e.to_string())?;
+        Ok(png)
+    }
+}
+
+/// Answer to a chart request: the chart or why there is none
+pub enum Chart {
+    Photo { png: Vec<u8>, caption: String },
+    Text(String),
+}
+
+impl Chart {
+    /// Chart of `pair` over the period `label` (e.g. `7d`)
+    pub fn draw(
+        history: &PriceHistory,
+        language: Language,
+        pair: &CurrencyPair,
+        label: &str,
+    ) -> Self {
+        let period =
+            parse_period(label, history.retention().raw).unwrap_or_else(|| Duration::days(1));
+        let points = history.range(pair, Local::now() - period);
+        if points.len() < 2 {
+            return Self::Text(tr_args(
+                language,
+                "chart.not_enough_data",
+                &[("pair", pair), ("period", &label)],
+            ));
+        }
+
+        match ChartModule::render(language, pair, label, &points) {
+            Ok(png) => {
+                let last = points.last().map(|point| point.price).unwrap_or_default();
+                let caption = tr_args(
+                    language,
+                    "chart.caption",
+                    &[
+                        ("pair", pair),
+                        ("period", &label),
+                        ("price", &format!("{:.2}", last)),
+                    ],
+                );
+                Self::Photo { png, caption }
+            }
+            Err(e) => {
+                tracing::error!("Failed to render chart for {}: {}", pair, e);
+                Self::Text(tr(language, "chart.render_failed").to_string())
+            }
+        }
+    }
+
+    /// Request sending the photo to `chat_id`, into the forum topic `thread_id`
+    fn send_photo(
+        bot: &TelegramBot,
+        chat_id: ChatId,
+        thread_id: Option<ThreadId>,
+        png: Vec<u8>,
+        caption: String,
+    ) -> <TelegramBot as Requester>::SendPhoto {
+        let request = bot
+            .send_photo(chat_id, InputFile::memory(png).file_name("chart.png"))
+            .caption(caption);
+        match thread_id {
+            Some(thread_id) => request.message_thread_id(thread_id),
+            None => request,
+        }
+    }
+
+    /// Send the chart to `chat_id` through `outbound`, as the quick actions
+    /// of `/price` do
+    pub async fn send(
+        self,
+        bot: &TelegramBot,
+        outbound: &OutboundQueue,
+        chat_id: ChatId,
+        thread_id: Option<ThreadId>,
+    ) -> Result<(), RequestError> {
+        match self {
+            Self::Photo { png, caption } => {
+                let request = Self::send_photo(bot, chat_id, thread_id, png, caption);
+                outbound
+                    .send(Priority::Reply, chat_id, || request.send_ref())
+                    .await?;
+            }
+            Self::Text(text) => {
+                let request = bot.send_message(chat_id, text);
+                let request = match thread_id {
+                    Some(thread_id) => request.message_thread_id(thread_id),
+                    None => request,
+                };
+                outbound.reply(chat_id, request).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Module for ChartModule {
+    fn name(&self) -> &str {
+        "Chart"
+    }
+
+    fn commands(&self) -> Vec<&str> {
+        vec!["/chart"]
+    }
+
+    fn command_descriptions(&self, language: Language) -> Vec<(&str, &str)> {
+        vec![("/chart", tr(language, "chart.menu"))]
+    }
+
+    fn usage(&self, language: Language) -> Vec<String> {
+        vec![tr(language, "chart.usage").to_string()]
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let Some(command) = ParsedCommand::from_message(&msg) else {
+            return Ok(());
+        };
+        let language = ctx.languages.get(msg.chat.id);
+        let max = ctx.price_service.history().retention().raw;
+        let (pair, label) = match Self::parse_args(command.args, max) {
+            Ok((pair, _, label)) => (pair, label),
+            Err(ArgError::Missing) => {
+                ctx.send_reply(&bot, &msg, reply(&bot, &msg, Self::help(language, max)))
+                    .await?;
+                return Ok(());
+            }
+            Err(error) => {
+                let response = format!(
+                    "{}\n\n{}",
+                    error.message(language),
+                    Self::help(language, max)
+                );
+                ctx.send_reply(&bot, &msg, reply(&bot, &msg, response))
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        // Replies through the context, so an edited command updates its chart
+        match Chart::draw(ctx.price_service.history(), language, &pair, &label) {
+            Chart::Photo { png, caption } => {
+                let request = Chart::send_photo(&bot, msg.chat.id, topic_of(&msg), png, caption);
+                ctx.send_photo_reply(&bot, &msg, request).await?;
+            }
+            Chart::Text(text) => ctx.send_reply(&bot, &msg, reply(&bot, &msg, text)).await?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_commands() {
+        assert_eq!(ChartModule.name(), "Chart");
+        assert_eq!(ChartModule.commands(), vec!["/chart"]);
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let max = Duration::days(30);
+        let (pair, period, label) = ChartModule::parse_args("USD/RUB 7d", max).unwrap();
+        assert_eq!(pair, CurrencyPair::USD2RUB);
+        assert_eq!(period, Duration::days(7));
+        assert_eq!(label, "7d");
+
+        let (_, period, _) = ChartModule::parse_args("USDTe/RUB", max).unwrap();
+        assert_eq!(period, Duration::days(1));
+
+        assert_eq!(ChartModule::parse_args("", max), Err(ArgError::Missing));
+        assert_eq!(
+            ChartModule::parse_args("EUR/RUB 1d", max),
+            Err(ArgError::UnknownPair("EUR/RUB".to_string()))
+        );
+        assert_eq!(
+            ChartModule::parse_args("USD/RUB 7w", max),
+            Err(ArgError::BadPeriod("7w".to_string()))
+        );
+        assert_eq!(
+            ChartModule::parse_args("USD/RUB 7d", Duration::days(3)),
+            Err(ArgError::BadPeriod("7d".to_string()))
+        );
+        assert_eq!(
+            ChartModule::parse_args("USD/RUB 7d now", max),
+            Err(ArgError::Unexpected("now".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_render_png() {
+        // Needs a font on disk; rendering itself doesn't depend on Telegram
+        if register_chart_font(Path::new(DEFAULT_CHART_FONT_PATH)).is_err() {
+            return;
+        }
+        let now = Local::now();
+        let points: Vec<PricePoint> = (0..24)
+            .map(|hour| PricePoint {
+                time: now - Duration::hours(24 - hour),
+                price: 90.0 + (hour as f64).sin(),
+            })
+            .collect();
+
+        let png = ChartModule::render(Language::En, &CurrencyPair::USD2RUB, "1d", &points).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+    }
+}
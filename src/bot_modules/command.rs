@@ -0,0 +1,261 @@
+use crate::domain::CurrencyPair;
+use crate::i18n::{tr, tr_args, Language};
+use crate::price_service::history::parse_period;
+use chrono::Duration;
+use std::str::{FromStr, SplitWhitespace};
+use teloxide::types::Message;
+
+/// A bot command split into its name, optional `@botname` mention and the
+/// rest of the message, e.g. `/price@MyBot USD/RUB` -> (`/price`, `MyBot`, `USD/RUB`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParsedCommand<'a> {
+    pub name: &'a str,
+    pub mention: Option<&'a str>,
+    pub args: &'a str,
+}
+
+impl<'a> ParsedCommand<'a> {
+    /// Parse a message text, `None` if it doesn't start with a command
+    pub fn parse(text: &'a str) -> Option<Self> {
+        let text = text.trim_start();
+        if !text.starts_with('/') {
+            return None;
+        }
+        let (token, args) = match text.split_once(char::is_whitespace) {
+            Some((token, rest)) => (token, rest.trim()),
+            None => (text, ""),
+        };
+        let (name, mention) = match token.split_once('@') {
+            Some((name, mention)) => (name, Some(mention)),
+            None => (token, None),
+        };
+        (name.len() > 1).then_some(Self {
+            name,
+            mention,
+            args,
+        })
+    }
+
+    /// Parse the command of a message. Documents carry their command in the
+    /// caption (e.g. /admin import_subs).
+    pub fn from_message(msg: &'a Message) -> Option<Self> {
+        msg.text().or_else(|| msg.caption()).and_then(Self::parse)
+    }
+
+    /// Whitespace separated arguments
+    pub fn arg_list(&self) -> Vec<&'a str> {
+        self.args.split_whitespace().collect()
+    }
+
+    /// Whether the command is meant for the bot named `username`: commands
+    /// without a mention are addressed to every bot in the chat
+    pub fn is_addressed_to(&self, username: &str) -> bool {
+        self.mention
+            .is_none_or(|mention| mention.eq_ignore_ascii_case(username))
+    }
+}
+
+/// Why the arguments of a command couldn't be read, answered with
+/// [`ArgError::message`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgError {
+    /// Fewer arguments than the command takes
+    Missing,
+    UnknownPair(String),
+    /// Not a currency of any pair, e.g. `BTC`
+    UnknownCurrency(String),
+    /// Not a positive number, `,` is accepted as the decimal separator
+    BadAmount(String),
+    /// Not a period like `30m`, `2h` or `7d` within the history retention
+    BadPeriod(String),
+    /// More arguments than the command takes
+    Unexpected(String),
+    /// An argument a [`BotCommands`](teloxide::utils::command::BotCommands)
+    /// enum reads as a number isn't one
+    NotANumber,
+    /// Arguments a [`BotCommands`](teloxide::utils::command::BotCommands)
+    /// enum couldn't parse otherwise; the parser's explanation is English, so
+    /// it isn't shown
+    Invalid,
+}
+
+impl ArgError {
+    /// Reply explaining the error in `language`
+    pub fn message(&self, language: Language) -> String {
+        match self {
+            ArgError::Missing => tr(language, "args.missing").to_string(),
+            ArgError::UnknownPair(pair) => tr_args(language, "unknown_pair", &[("pair", pair)]),
+            ArgError::UnknownCurrency(value) => {
+                tr_args(language, "args.unknown_currency", &[("value", value)])
+            }
+            ArgError::BadAmount(value) => tr_args(language, "args.bad_amount", &[("value", value)]),
+            ArgError::BadPeriod(value) => tr_args(language, "args.bad_period", &[("value", value)]),
+            ArgError::Unexpected(value) => {
+                tr_args(language, "args.unexpected", &[("value", value)])
+            }
+            ArgError::NotANumber => tr(language, "args.not_a_number").to_string(),
+            ArgError::Invalid => tr(language, "args.invalid").to_string(),
+        }
+    }
+}
+
+/// Whitespace separated arguments of a command read one at a time, e.g.
+/// `/chart USD/RUB 7d` with [`Args::pair`] and then [`Args::period`];
+/// [`Args::finish`] rejects whatever is left
+#[derive(Debug, Clone)]
+pub struct Args<'a> {
+    tokens: SplitWhitespace<'a>,
+}
+
+impl<'a> Args<'a> {
+    pub fn new(args: &'a str) -> Self {
+        Self {
+            tokens: args.split_whitespace(),
+        }
+    }
+
+    /// Whether every argument has been read
+    pub fn is_empty(&self) -> bool {
+        self.tokens.clone().next().is_none()
+    }
+
+    /// Next argument as it was written
+    pub fn word(&mut self) -> Result<&'a str, ArgError> {
+        self.tokens.next().ok_or(ArgError::Missing)
+    }
+
+    pub fn pair(&mut self) -> Result<CurrencyPair, ArgError> {
+        let word = self.word()?;
+        CurrencyPair::from_str(word).map_err(|_| ArgError::UnknownPair(word.to_string()))
+    }
+
+    /// Positive amount like `100` or `1,5`
+    pub fn amount(&mut self) -> Result<f64, ArgError> {
+        let word = self.word()?;
+        word.replace(',', ".")
+            .parse()
+            .ok()
+            .filter(|amount: &f64| amount.is_finite() && *amount > 0.0)
+            .ok_or_else(|| ArgError::BadAmount(word.to_string()))
+    }
+
+    /// Period like `30m`, `2h` or `7d` of at most `max`, see [`parse_period`]
+    pub fn period(&mut self, max: Duration) -> Result<Duration, ArgError> {
+        let word = self.word()?;
+        parse_period(word, max).ok_or_else(|| ArgError::BadPeriod(word.to_string()))
+    }
+
+    /// Read the next argument with `read` if there is one left
+    pub fn optional<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, ArgError>,
+    ) -> Result<Option<T>, ArgError> {
+        match self.is_empty() {
+            true => Ok(None),
+            false => read(self).map(Some),
+        }
+    }
+
+    /// Fail on the first argument that wasn't read
+    pub fn finish(mut self) -> Result<(), ArgError> {
+        match self.tokens.next() {
+            Some(word) => Err(ArgError::Unexpected(word.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parse space- or comma-separated pairs without repeating one, returning
+/// the first invalid token on error
+pub fn parse_pairs(args: &str) -> Result<Vec<CurrencyPair>, &str> {
+    let mut pairs = Vec::new();
+    for token in args
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|t| !t.is_empty())
+    {
+        let pair = CurrencyPair::from_str(token).map_err(|_| token)?;
+        if !pairs.contains(&pair) {
+            pairs.push(pair);
+        }
+    }
+    Ok(pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pairs() {
+        assert_eq!(
+            parse_pairs("USD/RUB, USDTe/RUB USD/RUB"),
+            Ok(vec![CurrencyPair::USD2RUB, CurrencyPair::USDTe2RUB])
+        );
+        assert_eq!(parse_pairs("USD/RUB EUR/RUB"), Err("EUR/RUB"));
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            ParsedCommand::parse("/subscribe"),
+            Some(ParsedCommand {
+                name: "/subscribe",
+                mention: None,
+                args: "",
+            })
+        );
+        assert_eq!(
+            ParsedCommand::parse("/price@CurrencyBot  USD/RUB "),
+            Some(ParsedCommand {
+                name: "/price",
+                mention: Some("CurrencyBot"),
+                args: "USD/RUB",
+            })
+        );
+        let command = ParsedCommand::parse("/template 💰 {pair}\n{price}").unwrap();
+        assert_eq!(command.name, "/template");
+        assert_eq!(command.args, "💰 {pair}\n{price}");
+        assert_eq!(command.arg_list(), vec!["💰", "{pair}", "{price}"]);
+
+        assert!(ParsedCommand::parse("hello").is_none());
+        assert!(ParsedCommand::parse("/").is_none());
+        assert!(ParsedCommand::parse("").is_none());
+    }
+
+    #[test]
+    fn test_typed_args() {
+        let max = Duration::days(30);
+        let mut args = Args::new(" USD/RUB  1,5 2h ");
+        assert_eq!(args.pair(), Ok(CurrencyPair::USD2RUB));
+        assert_eq!(args.amount(), Ok(1.5));
+        assert_eq!(
+            args.optional(|args| args.period(max)),
+            Ok(Some(Duration::hours(2)))
+        );
+        assert_eq!(args.optional(|args| args.period(max)), Ok(None));
+        assert_eq!(args.pair(), Err(ArgError::Missing));
+        assert_eq!(args.finish(), Ok(()));
+
+        let mut args = Args::new("EUR/RUB -5 2w extra");
+        assert_eq!(
+            args.pair(),
+            Err(ArgError::UnknownPair("EUR/RUB".to_string()))
+        );
+        assert_eq!(args.amount(), Err(ArgError::BadAmount("-5".to_string())));
+        assert_eq!(args.period(max), Err(ArgError::BadPeriod("2w".to_string())));
+        assert_eq!(
+            args.finish().unwrap_err().message(Language::En),
+            "❌ Unexpected argument: extra"
+        );
+    }
+
+    #[test]
+    fn test_is_addressed_to() {
+        let command = ParsedCommand::parse("/status@currencybot").unwrap();
+        assert!(command.is_addressed_to("CurrencyBot"));
+        assert!(!command.is_addressed_to("OtherBot"));
+        assert!(ParsedCommand::parse("/status")
+            .unwrap()
+            .is_addressed_to("OtherBot"));
+    }
+}
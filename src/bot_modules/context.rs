@@ -0,0 +1,201 @@
+use super::audit::AuditLog;
+use super::format::MessageFormat;
+use super::middleware::ActivityTracker;
+use super::outbound::{OutboundQueue, Priority};
+use super::profile::UserProfile;
+use super::replies::ReplyLog;
+use super::SubscriberManager;
+use super::TelegramBot;
+use crate::config::Config;
+use crate::i18n::Languages;
+use crate::price_service::PriceService;
+use std::ops::Deref;
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::requests::HasPayload;
+use teloxide::types::{InputMedia, InputMediaPhoto, MessageId, ReplyMarkup};
+use teloxide::{ApiError, RequestError};
+
+/// Services shared by the modules of a bot, handed to every
+/// [`super::Module`] call by the registry as a [`ModuleContext`]. Modules
+/// only keep their own settings, so a new service is added here rather than
+/// to every constructor.
+#[derive(Clone)]
+pub struct Services {
+    pub price_service: Arc<PriceService>,
+    /// Subscriptions, alerts and chat settings of the bot
+    pub manager: Arc<SubscriberManager>,
+    /// Settings as loaded at startup
+    pub config: Arc<Config>,
+    /// Reply language of every chat, the ones stored by `manager`
+    pub languages: Languages,
+    /// Markup of quotes and other formatted replies (`PARSE_MODE`)
+    pub format: MessageFormat,
+    /// Queue of the messages the bot sends, the one of `manager`
+    pub outbound: OutboundQueue,
+    /// Chats that used the bot recently, see `/users`
+    pub activity: ActivityTracker,
+    /// Commands handled per chat, see `/audit`
+    pub audit: AuditLog,
+    /// Replies sent to recent commands, edited when a command is
+    pub replies: ReplyLog,
+}
+
+/// [`Services`] of the bot, reached through `Deref` like `ctx.manager`, and
+/// the profile of the chat an update came from. The services are shared by
+/// the contexts of all updates, so creating one per update is cheap.
+#[derive(Clone)]
+pub struct ModuleContext {
+    services: Arc<Services>,
+    /// Profile of the chat of the update being handled, loaded by the
+    /// registry with [`Self::for_chat`], the default one outside of updates
+    pub profile: UserProfile,
+}
+
+impl Deref for ModuleContext {
+    type Target = Services;
+
+    fn deref(&self) -> &Services {
+        &self.services
+    }
+}
+
+impl ModuleContext {
+    pub fn new(
+        config: Arc<Config>,
+        price_service: Arc<PriceService>,
+        manager: Arc<SubscriberManager>,
+    ) -> Self {
+        let audit = &config.audit;
+        let services = Services {
+            languages: manager.languages().clone(),
+            format: manager.message_format(),
+            outbound: manager.outbound().clone(),
+            activity: ActivityTracker::new(),
+            audit: AuditLog::new(audit.max_entries_per_chat, audit.retention()),
+            replies: ReplyLog::new(),
+            price_service,
+            manager,
+            config,
+        };
+        Self {
+            services: Arc::new(services),
+            profile: UserProfile::default(),
+        }
+    }
+
+    /// Context of an update from `chat_id`, with the chat's stored profile
+    pub fn for_chat(&self, chat_id: ChatId) -> Self {
+        Self {
+            services: Arc::clone(&self.services),
+            profile: self.manager.profile(chat_id),
+        }
+    }
+
+    /// Send `request`, the reply to `msg` built with [`super::reply`] or
+    /// [`MessageFormat::reply`], through [`Self::outbound`]. The reply to an
+    /// edited command is edited into the earlier one when there is one.
+    pub async fn send_reply(
+        &self,
+        bot: &TelegramBot,
+        msg: &Message,
+        request: <TelegramBot as Requester>::SendMessage,
+    ) -> Result<(), RequestError> {
+        if let Some(earlier) = self.earlier_reply(msg) {
+            let payload = request.payload_ref();
+            let markup = match &payload.reply_markup {
+                Some(ReplyMarkup::InlineKeyboard(keyboard)) => Some(keyboard.clone()),
+                _ => None,
+            };
+            let edit = self
+                .outbound
+                .send(Priority::Reply, msg.chat.id, || {
+                    let mut edit = bot.edit_message_text(msg.chat.id, earlier, &payload.text);
+                    edit.payload_mut().parse_mode = payload.parse_mode;
+                    edit.payload_mut().entities = payload.entities.clone();
+                    edit.payload_mut().reply_markup = markup.clone();
+                    edit.send()
+                })
+                .await;
+            if edited(msg, earlier, edit) {
+                return Ok(());
+            }
+        }
+        let reply = self.outbound.reply(msg.chat.id, request).await?;
+        self.replies.remember(msg, &reply);
+        Ok(())
+    }
+
+    /// [`Self::send_reply`] for a photo, e.g. a chart
+    pub async fn send_photo_reply(
+        &self,
+        bot: &TelegramBot,
+        msg: &Message,
+        request: <TelegramBot as Requester>::SendPhoto,
+    ) -> Result<(), RequestError> {
+        if let Some(earlier) = self.earlier_reply(msg) {
+            let payload = request.payload_ref();
+            let mut photo = InputMediaPhoto::new(payload.photo.clone());
+            photo.caption = payload.caption.clone();
+            let edit = self
+                .outbound
+                .send(Priority::Reply, msg.chat.id, || {
+                    bot.edit_message_media(msg.chat.id, earlier, InputMedia::Photo(photo.clone()))
+                        .send()
+                })
+                .await;
+            if edited(msg, earlier, edit) {
+                return Ok(());
+            }
+        }
+        let reply = self
+            .outbound
+            .send(Priority::Reply, msg.chat.id, || request.send_ref())
+            .await?;
+        self.replies.remember(msg, &reply);
+        Ok(())
+    }
+
+    /// Reply to update when `msg` is an edited command answered before
+    fn earlier_reply(&self, msg: &Message) -> Option<MessageId> {
+        msg.edit_date()?;
+        self.replies.get(msg.chat.id, msg.id)
+    }
+
+    /// Record the commands into `audit` instead of a log of its own
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        Arc::make_mut(&mut self.services).audit = audit;
+        self
+    }
+}
+
+/// Whether the earlier reply to `msg` was updated by `edit`; an answer that
+/// didn't change counts, other failures get a new reply, e.g. a chart
+/// answered with text or a reply deleted since
+fn edited<T>(msg: &Message, earlier: MessageId, edit: Result<T, RequestError>) -> bool {
+    match edit {
+        Ok(_) | Err(RequestError::Api(ApiError::MessageNotModified)) => true,
+        Err(e) => {
+            tracing::debug!(
+                "Failed to edit reply {} in {}, sending a new one: {}",
+                earlier,
+                msg.chat.id,
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Context with the default settings and empty services
+impl Default for ModuleContext {
+    fn default() -> Self {
+        let config = Config::default();
+        let manager = SubscriberManager::new(config.subscriptions.message_text.clone());
+        Self::new(
+            Arc::new(config),
+            Arc::new(PriceService::new()),
+            Arc::new(manager),
+        )
+    }
+}
@@ -0,0 +1,259 @@
+use super::price::format_price_error;
+use super::{ModuleContext, TelegramBot};
+use crate::domain::CurrencyPair;
+use crate::i18n::{tr, Language};
+use async_trait::async_trait;
+use std::error::Error;
+use teloxide::prelude::*;
+
+/// Currency mentioned in a conversion question
+#[derive(Debug, Clone, PartialEq)]
+enum Currency {
+    Rub,
+    Asset(CurrencyPair),
+}
+
+impl Currency {
+    /// Recognize a lowercase word like `usd`, `доллара`, `рублей` or `$`
+    fn from_word(word: &str) -> Option<Self> {
+        match word {
+            "usd" | "$" | "бакс" | "баксов" | "бакса" => {
+                Some(Currency::Asset(CurrencyPair::USD2RUB))
+            }
+            "usdt" | "usdte" | "tether" => Some(Currency::Asset(CurrencyPair::USDTe2RUB)),
+            "usdc" | "usdce" => Some(Currency::Asset(CurrencyPair::USDCe2RUB)),
+            "rub" | "rur" | "₽" => Some(Currency::Rub),
+            _ if word.starts_with("долл") => Some(Currency::Asset(CurrencyPair::USD2RUB)),
+            _ if word.starts_with("руб") => Some(Currency::Rub),
+            _ => None,
+        }
+    }
+}
+
+/// Amount to convert between a pair's currency and rubles, parsed from a
+/// question like "100 usd в рубли" or "сколько стоит 50 usdt"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conversion {
+    /// `None` when the question names none
+    pub amount: Option<f64>,
+    pub pair: CurrencyPair,
+    /// Rubles are converted into the pair's currency instead
+    pub from_rub: bool,
+}
+
+impl Conversion {
+    /// Parse a plain text question, the first mentioned currency is the one
+    /// converted
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut amount = None;
+        let mut currencies = Vec::new();
+        for token in tokenize(&text.to_lowercase()) {
+            if token.starts_with(|c: char| c.is_ascii_digit()) {
+                amount = amount.or_else(|| token.replace(',', ".").parse::<f64>().ok());
+            } else if let Some(currency) = Currency::from_word(&token) {
+                if !currencies.contains(&currency) {
+                    currencies.push(currency);
+                }
+            }
+        }
+
+        if amount.is_some_and(|amount| !amount.is_finite() || amount <= 0.0) {
+            return None;
+        }
+        let (pair, from_rub) = match currencies.as_slice() {
+            [Currency::Asset(pair)] | [Currency::Asset(pair), Currency::Rub] => (pair, false),
+            [Currency::Rub, Currency::Asset(pair)] => (pair, true),
+            _ => return None,
+        };
+        Some(Self {
+            amount,
+            pair: pair.clone(),
+            from_rub,
+        })
+    }
+}
+
+/// Split text into numbers, words and currency signs, so that "100usd" and
+/// "$100" are understood as well
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for c in text.chars() {
+        let continues = current.chars().last().is_some_and(|last| {
+            if last.is_ascii_digit() || last == '.' || last == ',' {
+                c.is_ascii_digit() || c == '.' || c == ','
+            } else {
+                last.is_alphabetic() && c.is_alphabetic()
+            }
+        });
+        if !continues && !current.is_empty() {
+            tokens.push(std::mem::take(&mut current));
+        }
+        if c.is_alphanumeric() || c == '.' || c == ',' || c == '$' || c == '₽' {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Answers conversion questions written as plain text, tried by the
+/// registry when a message is not a command
+pub struct ConvertModule;
+
+impl ConvertModule {
+    /// Reply with the converted amount, the chat's default amount or 1 when
+    /// the question names none
+    async fn convert(ctx: &ModuleContext, language: Language, conversion: &Conversion) -> String {
+        let format = ctx.format;
+        let amount = conversion
+            .amount
+            .or(ctx.profile.default_amount)
+            .unwrap_or(1.0);
+        let price_data = match ctx.price_service.get_price(&conversion.pair).await {
+            Ok(price_data) => price_data,
+            Err(e) => return format_price_error(language, format, &e),
+        };
+        let price = ctx
+            .config
+            .display
+            .price_format(&conversion.pair)
+            .format(price_data.price);
+        let pair = conversion.pair.to_string();
+        let currency = pair.split('/').next().unwrap_or(&pair).to_string();
+        let (from, to, result) = if conversion.from_rub {
+            let result = amount / price_data.price;
+            ("RUB".to_string(), currency, result)
+        } else {
+            let result = amount * price_data.price;
+            (currency, "RUB".to_string(), result)
+        };
+        format.render(
+            tr(language, "convert.result"),
+            &[
+                ("amount", format.code(&format!("{:.2}", amount))),
+                ("from", format.escape(&from)),
+                ("result", format.code(&format!("{:.2}", result))),
+                ("to", format.escape(&to)),
+                ("pair", format.bold(&pair)),
+                ("price", format.code(&price)),
+            ],
+        )
+    }
+}
+
+#[async_trait]
+impl super::Module for ConvertModule {
+    fn name(&self) -> &str {
+        "ConvertModule"
+    }
+
+    fn commands(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    fn usage(&self, language: Language) -> Vec<String> {
+        vec![tr(language, "convert.usage").to_string()]
+    }
+
+    fn accepts_text(&self, text: &str) -> bool {
+        Conversion::parse(text).is_some()
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if let Some(conversion) = msg.text().and_then(Conversion::parse) {
+            let language = ctx.languages.get(msg.chat.id);
+            let response = Self::convert(ctx, language, &conversion).await;
+            ctx.send_reply(&bot, &msg, ctx.format.reply(&bot, &msg, response))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conversion(amount: Option<f64>, pair: CurrencyPair, from_rub: bool) -> Option<Conversion> {
+        Some(Conversion {
+            amount,
+            pair,
+            from_rub,
+        })
+    }
+
+    #[test]
+    fn test_parse_questions() {
+        assert_eq!(
+            Conversion::parse("100 usd в рубли"),
+            conversion(Some(100.0), CurrencyPair::USD2RUB, false)
+        );
+        assert_eq!(
+            Conversion::parse("сколько стоит 50 usdt?"),
+            conversion(Some(50.0), CurrencyPair::USDTe2RUB, false)
+        );
+        assert_eq!(
+            Conversion::parse("1500,5 рублей в доллары"),
+            conversion(Some(1500.5), CurrencyPair::USD2RUB, true)
+        );
+        assert_eq!(
+            Conversion::parse("$20"),
+            conversion(Some(20.0), CurrencyPair::USD2RUB, false)
+        );
+        assert_eq!(
+            Conversion::parse("курс доллара"),
+            conversion(None, CurrencyPair::USD2RUB, false)
+        );
+        assert_eq!(
+            Conversion::parse("10USDC"),
+            conversion(Some(10.0), CurrencyPair::USDCe2RUB, false)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_convert_uses_default_amount() {
+        use crate::price_service::providers::fixed::FixedProvider;
+        use crate::price_service::PriceService;
+        use std::sync::Arc;
+
+        let mut service = PriceService::new();
+        service.add_provider(Arc::new(FixedProvider::new(90.0)));
+        let defaults = ModuleContext::default();
+        let mut ctx = ModuleContext::new(
+            defaults.config.clone(),
+            Arc::new(service),
+            defaults.manager.clone(),
+        );
+        let question = Conversion::parse("курс доллара").unwrap();
+        let response = ConvertModule::convert(&ctx, Language::En, &question).await;
+        assert!(response.contains("90.00"), "{}", response);
+
+        ctx.profile.default_amount = Some(10.0);
+        let response = ConvertModule::convert(&ctx, Language::En, &question).await;
+        assert!(response.contains("900.00"), "{}", response);
+        // An amount in the question wins
+        let question = Conversion::parse("2 usd").unwrap();
+        let response = ConvertModule::convert(&ctx, Language::En, &question).await;
+        assert!(response.contains("180.00"), "{}", response);
+    }
+
+    #[test]
+    fn test_parse_rejects_other_texts() {
+        assert_eq!(Conversion::parse("привет"), None);
+        assert_eq!(Conversion::parse("100 рублей"), None);
+        assert_eq!(Conversion::parse("0 usd"), None);
+        assert_eq!(Conversion::parse("usd в usdt"), None);
+    }
+}
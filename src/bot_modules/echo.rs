@@ -1,37 +1,45 @@
-use super::Module;
+use super::{reply, CommandsModule, ModuleContext, TelegramBot};
+use crate::i18n::{tr, Language};
 use async_trait::async_trait;
 use std::error::Error;
 use teloxide::prelude::*;
+use teloxide::utils::command::BotCommands;
 
-pub struct EchoModule;
-
-impl EchoModule {
-    pub fn new() -> Self {
-        Self
-    }
+#[derive(BotCommands, Debug, Clone, PartialEq)]
+#[command(rename_rule = "lowercase")]
+pub enum EchoCommand {
+    #[command(description = "echo.menu")]
+    Echo(String),
 }
 
+pub struct EchoModule;
+
 #[async_trait]
-impl Module for EchoModule {
+impl CommandsModule for EchoModule {
+    type Command = EchoCommand;
+
     fn name(&self) -> &str {
         "Echo"
     }
 
-    fn commands(&self) -> Vec<&str> {
-        vec!["/echo"]
+    fn usage(&self, language: Language) -> Vec<String> {
+        vec![tr(language, "echo.usage").to_string()]
     }
 
-    async fn handle(&self, bot: Bot, msg: Message) -> Result<(), Box<dyn Error + Send + Sync>> {
-        if let Some(text) = msg.text() {
-            if text.starts_with("/echo") {
-                let echo_text = text.trim_start_matches("/echo").trim();
-                if echo_text.is_empty() {
-                    bot.send_message(msg.chat.id, "Использование: /echo <текст>")
-                        .await?;
-                } else {
-                    bot.send_message(msg.chat.id, echo_text).await?;
-                }
-            }
+    async fn handle_command(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+        command: EchoCommand,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let EchoCommand::Echo(text) = command;
+        if text.is_empty() {
+            let language = ctx.languages.get(msg.chat.id);
+            ctx.send_reply(&bot, &msg, reply(&bot, &msg, tr(language, "echo.help")))
+                .await?;
+        } else {
+            ctx.send_reply(&bot, &msg, reply(&bot, &msg, text)).await?;
         }
         Ok(())
     }
@@ -40,16 +48,29 @@ impl Module for EchoModule {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bot_modules::{BotCommandsModule, Module};
 
     #[test]
     fn test_module_name() {
-        let module = EchoModule::new();
+        let module = BotCommandsModule::new(EchoModule);
         assert_eq!(module.name(), "Echo");
     }
 
     #[test]
     fn test_module_commands() {
-        let module = EchoModule::new();
+        let module = BotCommandsModule::new(EchoModule);
         assert_eq!(module.commands(), vec!["/echo"]);
+        assert_eq!(
+            module.command_descriptions(Language::En),
+            vec![("/echo", "Send an echo reply")]
+        );
+        assert_eq!(
+            module.usage(Language::En),
+            ["/echo <text> - Send an echo reply"]
+        );
+        assert_eq!(
+            EchoCommand::parse("/echo hello  world", "").ok(),
+            Some(EchoCommand::Echo("hello  world".to_string()))
+        );
     }
 }
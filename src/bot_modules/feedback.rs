@@ -0,0 +1,225 @@
+use super::outbound::Priority;
+use super::{reply, Module, ModuleContext, ParsedCommand, Role, TelegramBot};
+use crate::i18n::{tr, tr_args, Language};
+use async_trait::async_trait;
+use std::error::Error;
+use teloxide::prelude::*;
+
+/// Message forwarded to the feedback chat in its `language`, with enough
+/// about the sender to answer with /reply
+fn format_feedback(language: Language, msg: &Message, recipient: ChatId, text: &str) -> String {
+    let sender = match msg.from.as_ref() {
+        Some(user) => match &user.username {
+            Some(username) => format!("{} (@{})", user.full_name(), username),
+            None => user.full_name(),
+        },
+        None => msg.chat.title().unwrap_or("—").to_string(),
+    };
+    tr_args(
+        language,
+        "feedback.forwarded",
+        &[
+            ("sender", &sender),
+            ("user", &recipient),
+            ("chat", &msg.chat.id),
+            ("text", &text),
+        ],
+    )
+}
+
+/// Split `/reply` arguments into the recipient and the answer
+fn parse_reply(args: &str) -> Option<(ChatId, &str)> {
+    let (recipient, text) = args.split_once(char::is_whitespace)?;
+    let recipient = recipient.parse().ok()?;
+    let text = text.trim();
+    (!text.is_empty()).then_some((ChatId(recipient), text))
+}
+
+/// `/feedback <text>` forwards a message from a user to the operator's chat
+pub struct FeedbackModule {
+    feedback_chat: ChatId,
+}
+
+impl FeedbackModule {
+    /// Create a module forwarding feedback to `feedback_chat`
+    pub fn new(feedback_chat: ChatId) -> Self {
+        Self { feedback_chat }
+    }
+}
+
+#[async_trait]
+impl Module for FeedbackModule {
+    fn name(&self) -> &str {
+        "Feedback"
+    }
+
+    fn commands(&self) -> Vec<&str> {
+        vec!["/feedback"]
+    }
+
+    fn command_descriptions(&self, language: Language) -> Vec<(&str, &str)> {
+        vec![("/feedback", tr(language, "feedback.menu"))]
+    }
+
+    fn usage(&self, language: Language) -> Vec<String> {
+        vec![tr(language, "feedback.usage").to_string()]
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let Some(command) = ParsedCommand::from_message(&msg) else {
+            return Ok(());
+        };
+        let language = ctx.languages.get(msg.chat.id);
+        if command.args.is_empty() {
+            ctx.send_reply(&bot, &msg, reply(&bot, &msg, tr(language, "feedback.help")))
+                .await?;
+            return Ok(());
+        }
+
+        // Answers go to the user in private, the group itself for anonymous admins
+        let recipient = msg
+            .from
+            .as_ref()
+            .map(|user| ChatId(user.id.0 as i64))
+            .unwrap_or(msg.chat.id);
+        let feedback = format_feedback(
+            ctx.languages.get(self.feedback_chat),
+            &msg,
+            recipient,
+            command.args,
+        );
+        let forward = ctx
+            .outbound
+            .send(Priority::Broadcast, self.feedback_chat, || {
+                bot.send_message(self.feedback_chat, &feedback).send()
+            })
+            .await;
+        let response = match forward {
+            Ok(_) => {
+                tracing::info!("Forwarded feedback from {}", recipient);
+                tr(language, "feedback.sent")
+            }
+            Err(e) => {
+                tracing::error!("Failed to forward feedback from {}: {}", recipient, e);
+                tr(language, "feedback.failed")
+            }
+        };
+        ctx.send_reply(&bot, &msg, reply(&bot, &msg, response))
+            .await?;
+        Ok(())
+    }
+}
+
+/// Admin `/reply <user_id> <text>` answering feedback in the user's language
+pub struct FeedbackReplyModule;
+
+#[async_trait]
+impl Module for FeedbackReplyModule {
+    fn name(&self) -> &str {
+        "FeedbackReply"
+    }
+
+    fn commands(&self) -> Vec<&str> {
+        vec!["/reply"]
+    }
+
+    fn required_role(&self) -> Role {
+        Role::Admin
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let Some(command) = ParsedCommand::from_message(&msg) else {
+            return Ok(());
+        };
+        let language = ctx.languages.get(msg.chat.id);
+        let Some((recipient, text)) = parse_reply(command.args) else {
+            let usage = tr(language, "feedback.reply_usage");
+            ctx.send_reply(&bot, &msg, reply(&bot, &msg, usage)).await?;
+            return Ok(());
+        };
+
+        let answer = tr_args(
+            ctx.languages.get(recipient),
+            "feedback.reply",
+            &[("text", &text)],
+        );
+        let sent = ctx
+            .outbound
+            .send(Priority::Reply, recipient, || {
+                bot.send_message(recipient, &answer).send()
+            })
+            .await;
+        let response = match sent {
+            Ok(_) => tr_args(language, "feedback.reply_sent", &[("chat", &recipient)]),
+            Err(e) => tr_args(
+                language,
+                "feedback.reply_failed",
+                &[("chat", &recipient), ("error", &e)],
+            ),
+        };
+        ctx.send_reply(&bot, &msg, reply(&bot, &msg, response))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_commands() {
+        let feedback = FeedbackModule::new(ChatId(-100));
+        assert_eq!(feedback.commands(), vec!["/feedback"]);
+        assert_eq!(feedback.required_role(), Role::User);
+        assert_eq!(FeedbackReplyModule.commands(), vec!["/reply"]);
+        assert_eq!(FeedbackReplyModule.required_role(), Role::Admin);
+    }
+
+    #[test]
+    fn test_format_feedback() {
+        let msg: Message = serde_json::from_value(serde_json::json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": {"id": 42, "type": "private", "first_name": "Ivan"},
+            "from": {
+                "id": 42,
+                "is_bot": false,
+                "first_name": "Ivan",
+                "last_name": "Petrov",
+                "username": "ivan"
+            },
+            "text": "/feedback Не приходит рассылка",
+        }))
+        .unwrap();
+        let feedback = format_feedback(Language::Ru, &msg, ChatId(42), "Не приходит рассылка");
+        assert!(feedback.starts_with("📝 Отзыв от Ivan Petrov (@ivan)"));
+        assert!(feedback.contains("Не приходит рассылка"));
+        assert!(feedback.ends_with("/reply 42 <текст>"));
+        let feedback = format_feedback(Language::En, &msg, ChatId(42), "No messages");
+        assert!(feedback.starts_with("📝 Feedback from Ivan Petrov (@ivan)"));
+        assert!(feedback.ends_with("/reply 42 <text>"));
+    }
+
+    #[test]
+    fn test_parse_reply() {
+        assert_eq!(
+            parse_reply("42 Спасибо, исправили"),
+            Some((ChatId(42), "Спасибо, исправили"))
+        );
+        assert_eq!(parse_reply("-100500 ok"), Some((ChatId(-100500), "ok")));
+        assert_eq!(parse_reply("42"), None);
+        assert_eq!(parse_reply("42   "), None);
+        assert_eq!(parse_reply("ivan hello"), None);
+    }
+}
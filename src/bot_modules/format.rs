@@ -0,0 +1,319 @@
+use super::{topic_of, TelegramBot};
+use crate::domain::CurrencyPair;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use teloxide::payloads::SendMessageSetters;
+use teloxide::prelude::*;
+use teloxide::types::{ParseMode, Recipient};
+
+/// Characters MarkdownV2 requires to be escaped outside of entities
+const MARKDOWN_V2_SPECIAL: &str = "_*[]()~`>#+-=|{}.!\\";
+
+/// Markup of the bot's formatted replies (quotes and periodic messages),
+/// configured with `PARSE_MODE`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Plain text without any markup
+    #[default]
+    Plain,
+    Html,
+    MarkdownV2,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.trim().to_lowercase().as_str() {
+            "plain" | "" => Ok(MessageFormat::Plain),
+            "html" => Ok(MessageFormat::Html),
+            "markdownv2" | "markdown" => Ok(MessageFormat::MarkdownV2),
+            _ => Err(format!("'{}' is not plain, html or markdownv2", s)),
+        }
+    }
+}
+
+impl MessageFormat {
+    /// Parse mode to send formatted text with, `None` for plain text
+    pub fn parse_mode(self) -> Option<ParseMode> {
+        match self {
+            MessageFormat::Plain => None,
+            MessageFormat::Html => Some(ParseMode::Html),
+            MessageFormat::MarkdownV2 => Some(ParseMode::MarkdownV2),
+        }
+    }
+
+    /// Escape literal `text` so it is shown as is
+    pub fn escape(self, text: &str) -> String {
+        match self {
+            MessageFormat::Plain => text.to_string(),
+            MessageFormat::Html => text
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;"),
+            MessageFormat::MarkdownV2 => {
+                let mut escaped = String::with_capacity(text.len());
+                for c in text.chars() {
+                    if MARKDOWN_V2_SPECIAL.contains(c) {
+                        escaped.push('\\');
+                    }
+                    escaped.push(c);
+                }
+                escaped
+            }
+        }
+    }
+
+    /// Bold `text`, e.g. a pair name
+    pub fn bold(self, text: &str) -> String {
+        match self {
+            MessageFormat::Plain => text.to_string(),
+            MessageFormat::Html => format!("<b>{}</b>", self.escape(text)),
+            MessageFormat::MarkdownV2 => format!("*{}*", self.escape(text)),
+        }
+    }
+
+    /// Monospace `text`, e.g. a price, so numbers line up
+    pub fn code(self, text: &str) -> String {
+        match self {
+            MessageFormat::Plain => text.to_string(),
+            MessageFormat::Html => format!("<code>{}</code>", self.escape(text)),
+            MessageFormat::MarkdownV2 => {
+                format!("`{}`", text.replace('\\', "\\\\").replace('`', "\\`"))
+            }
+        }
+    }
+
+    /// Replace `{name}` placeholders of `template` with the already
+    /// formatted `args`, escaping the rest of the template. Unknown
+    /// placeholders are kept as literal text.
+    pub fn render(self, template: &str, args: &[(&str, String)]) -> String {
+        let mut result = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            result.push_str(&self.escape(&rest[..start]));
+            let value = rest[start..].find('}').and_then(|end| {
+                let name = &rest[start + 1..start + end];
+                let value = args.iter().find(|(arg, _)| *arg == name)?;
+                Some((end, &value.1))
+            });
+            match value {
+                Some((end, value)) => {
+                    result.push_str(value);
+                    rest = &rest[start + end + 1..];
+                }
+                None => {
+                    result.push_str(&self.escape("{"));
+                    rest = &rest[start + 1..];
+                }
+            }
+        }
+        result.push_str(&self.escape(rest));
+        result
+    }
+
+    /// Reply to `msg` with `text` rendered in this format, in the forum
+    /// topic of `msg`
+    pub fn reply(
+        self,
+        bot: &TelegramBot,
+        msg: &Message,
+        text: String,
+    ) -> <TelegramBot as Requester>::SendMessage {
+        let request = self.send_message(bot, msg.chat.id, text);
+        match topic_of(msg) {
+            Some(thread_id) => request.message_thread_id(thread_id),
+            None => request,
+        }
+    }
+
+    /// Send request for `text` rendered in this format
+    pub fn send_message(
+        self,
+        bot: &TelegramBot,
+        chat_id: impl Into<Recipient>,
+        text: String,
+    ) -> <TelegramBot as Requester>::SendMessage {
+        let request = bot.send_message(chat_id, text);
+        match self.parse_mode() {
+            Some(mode) => request.parse_mode(mode),
+            None => request,
+        }
+    }
+}
+
+/// Rounding of a price to its displayed decimals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    /// To the nearest, halves away from zero
+    #[default]
+    HalfUp,
+    /// Towards zero
+    Down,
+    /// Away from zero
+    Up,
+}
+
+/// How the prices of a pair are written, configured in `[display]`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PriceFormat {
+    pub decimals: u8,
+    /// Put between groups of three digits, e.g. `" "` for `1 234.50`
+    pub thousands_separator: String,
+    pub rounding: Rounding,
+}
+
+impl Default for PriceFormat {
+    fn default() -> Self {
+        Self {
+            decimals: 2,
+            thousands_separator: String::new(),
+            rounding: Rounding::HalfUp,
+        }
+    }
+}
+
+/// Formats of the prices of every pair, from `[display]`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PriceFormats {
+    /// Format of the pairs without one of their own
+    pub default: PriceFormat,
+    /// Formats by pair name, e.g. `USDT/USD`
+    pub pairs: BTreeMap<String, PriceFormat>,
+}
+
+impl PriceFormats {
+    /// Format of the prices of `pair`
+    pub fn get(&self, pair: &CurrencyPair) -> &PriceFormat {
+        self.pairs.get(&pair.to_string()).unwrap_or(&self.default)
+    }
+}
+
+impl PriceFormat {
+    /// `value` rounded to `decimals` the way it's written
+    pub fn round(&self, value: f64) -> f64 {
+        let scale = 10f64.powi(i32::from(self.decimals));
+        let mut scaled = value * scale;
+        // 92.35 is 9234.999... scaled, it mustn't be rounded down to 92.34
+        if (scaled - scaled.round()).abs() < 1e-6 {
+            scaled = scaled.round();
+        }
+        let rounded = match self.rounding {
+            Rounding::HalfUp => scaled.round(),
+            Rounding::Down => scaled.trunc(),
+            Rounding::Up => scaled.abs().ceil().copysign(scaled),
+        };
+        rounded / scale
+    }
+
+    /// `value` rounded to `decimals` with the thousands separated, still to
+    /// be escaped for the message format
+    pub fn format(&self, value: f64) -> String {
+        let text = format!("{:.*}", usize::from(self.decimals), self.round(value));
+        if self.thousands_separator.is_empty() {
+            return text;
+        }
+        let (sign, digits) = match text.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", text.as_str()),
+        };
+        let (integer, fraction) = match digits.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (digits, None),
+        };
+        let mut grouped = String::from(sign);
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                grouped.push_str(&self.thousands_separator);
+            }
+            grouped.push(digit);
+        }
+        if let Some(fraction) = fraction {
+            grouped.push('.');
+            grouped.push_str(fraction);
+        }
+        grouped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(
+            MessageFormat::from_str("HTML").ok(),
+            Some(MessageFormat::Html)
+        );
+        assert_eq!(
+            MessageFormat::from_str("MarkdownV2").ok(),
+            Some(MessageFormat::MarkdownV2)
+        );
+        assert_eq!(
+            MessageFormat::from_str("plain").ok(),
+            Some(MessageFormat::Plain)
+        );
+        assert_eq!(MessageFormat::from_str("bbcode").ok(), None);
+    }
+
+    #[test]
+    fn test_markdown_v2_escaping() {
+        let format = MessageFormat::MarkdownV2;
+        assert_eq!(format.escape("1.5 (+2%)!"), "1\\.5 \\(\\+2%\\)\\!");
+        assert_eq!(format.bold("USD/RUB"), "*USD/RUB*");
+        assert_eq!(format.bold("USDTe_RUB"), "*USDTe\\_RUB*");
+        assert_eq!(format.code("92.46"), "`92.46`");
+    }
+
+    #[test]
+    fn test_html_escaping() {
+        let format = MessageFormat::Html;
+        assert_eq!(format.escape("a < b & c"), "a &lt; b &amp; c");
+        assert_eq!(format.bold("USD/RUB"), "<b>USD/RUB</b>");
+        assert_eq!(format.code("92.46"), "<code>92.46</code>");
+    }
+
+    #[test]
+    fn test_render() {
+        let format = MessageFormat::MarkdownV2;
+        assert_eq!(
+            format.render(
+                "Цена {pair}: {price}. {unknown}",
+                &[
+                    ("pair", format.bold("USD/RUB")),
+                    ("price", format.code("92.46"))
+                ]
+            ),
+            "Цена *USD/RUB*: `92.46`\\. \\{unknown\\}"
+        );
+        assert_eq!(
+            MessageFormat::Plain.render("{a} {b", &[("a", "1".to_string())]),
+            "1 {b"
+        );
+    }
+
+    #[test]
+    fn test_price_format() {
+        let format = |decimals, separator: &str, rounding| PriceFormat {
+            decimals,
+            thousands_separator: separator.to_string(),
+            rounding,
+        };
+        assert_eq!(PriceFormat::default().format(92.456), "92.46");
+        assert_eq!(format(2, "", Rounding::Down).format(92.35), "92.35");
+        assert_eq!(format(2, "", Rounding::Down).format(92.359), "92.35");
+        assert_eq!(format(2, "", Rounding::Up).format(92.351), "92.36");
+        assert_eq!(format(4, "", Rounding::HalfUp).format(0.99987), "0.9999");
+        assert_eq!(
+            format(0, " ", Rounding::HalfUp).format(6_543_210.5),
+            "6 543 211"
+        );
+        assert_eq!(format(2, ",", Rounding::Down).format(-1234.5), "-1,234.50");
+        assert_eq!(format(2, ",", Rounding::HalfUp).format(123.0), "123.00");
+        assert_eq!(format(4, ",", Rounding::Down).round(1.08349), 1.0834);
+    }
+}
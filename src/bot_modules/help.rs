@@ -1,30 +1,39 @@
-use super::Module;
+use super::{reply, Module, ModuleContext, ModuleSwitches, TelegramBot};
+use crate::i18n::{tr, Language};
 use async_trait::async_trait;
+use std::collections::HashMap;
 use std::error::Error;
 use teloxide::prelude::*;
 
-pub struct HelpModule;
+/// Help module listing the commands of the modules registered before it
+/// that are switched on
+pub struct HelpModule {
+    sections: HashMap<Language, Vec<(String, String)>>,
+}
 
 impl HelpModule {
-    pub fn new() -> Self {
-        Self
+    /// Create a help module for the sections of
+    /// [`super::ModuleRegistry::help_sections`] in every language
+    pub fn new(sections: HashMap<Language, Vec<(String, String)>>) -> Self {
+        Self { sections }
     }
 
-    fn get_help_text() -> String {
-        "
-Доступные команды:
-
-/start - Начать работу с ботом
-/echo <текст> - Отправить эхо-ответ
-/subscribe - Подписаться на периодические сообщения
-/unsubscribe - Отписаться от периодических сообщений
-/status - Проверить статус подписки
-/help - Показать эту справку
-
-Используйте /help для получения информации о доступных командах.
-"
-        .trim()
-        .to_string()
+    fn get_help_text(&self, language: Language, switches: &ModuleSwitches) -> String {
+        let mut text = format!("{}\n\n", tr(language, "help.title"));
+        let sections: Vec<&str> = self
+            .sections
+            .get(&language)
+            .into_iter()
+            .flatten()
+            .filter(|(module, _)| switches.is_enabled(module))
+            .map(|(_, section)| section.as_str())
+            .collect();
+        if !sections.is_empty() {
+            text.push_str(&sections.join("\n\n"));
+            text.push_str("\n\n");
+        }
+        text.push_str(&self.usage(language).join("\n"));
+        text
     }
 }
 
@@ -38,8 +47,23 @@ impl Module for HelpModule {
         vec!["/help"]
     }
 
-    async fn handle(&self, bot: Bot, msg: Message) -> Result<(), Box<dyn Error + Send + Sync>> {
-        bot.send_message(msg.chat.id, Self::get_help_text()).await?;
+    fn command_descriptions(&self, language: Language) -> Vec<(&str, &str)> {
+        vec![("/help", tr(language, "help.menu"))]
+    }
+
+    fn usage(&self, language: Language) -> Vec<String> {
+        vec![tr(language, "help.usage").to_string()]
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let language = ctx.languages.get(msg.chat.id);
+        let text = self.get_help_text(language, ctx.manager.module_switches());
+        ctx.send_reply(&bot, &msg, reply(&bot, &msg, text)).await?;
         Ok(())
     }
 }
@@ -47,27 +71,74 @@ impl Module for HelpModule {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bot_modules::scheduler::{Job, JobSchedule, Scheduler};
+    use crate::bot_modules::{
+        AlertModule, BotCommandsModule, ChartModule, EchoModule, ModuleRegistry, NewLineModule,
+        PriceModule, SettingsModule, StartModule, SubscriberModule,
+    };
+    use tokio_util::sync::CancellationToken;
 
     #[test]
     fn test_module_name() {
-        let module = HelpModule::new();
+        let module = HelpModule::new(HashMap::new());
         assert_eq!(module.name(), "Help");
     }
 
     #[test]
     fn test_module_commands() {
-        let module = HelpModule::new();
+        let module = HelpModule::new(HashMap::new());
         assert_eq!(module.commands(), vec!["/help"]);
     }
 
     #[test]
     fn test_help_text() {
-        let help_text = HelpModule::get_help_text();
+        let mut registry = ModuleRegistry::new();
+        registry.register(Box::new(StartModule));
+        registry.register(Box::new(BotCommandsModule::new(EchoModule)));
+        registry.register(Box::new(PriceModule));
+        registry.register(Box::new(NewLineModule));
+        registry.register(Box::new(ChartModule));
+        registry.register(Box::new(AlertModule));
+        registry.register(Box::new(SubscriberModule));
+        let broadcast_job = Scheduler::new(CancellationToken::new()).register(Job::new(
+            "broadcast",
+            JobSchedule::from_minutes(10),
+            || async {},
+        ));
+        registry.register(Box::new(SettingsModule::new(broadcast_job)));
+
+        let sections = Language::ALL
+            .into_iter()
+            .map(|language| (language, registry.help_sections(language)))
+            .collect();
+        let help = HelpModule::new(sections);
+        let switches = ModuleSwitches::new();
+        registry.set_module_switches(switches.clone());
+        let help_text = help.get_help_text(Language::Ru, &switches);
         assert!(help_text.contains("/start"));
         assert!(help_text.contains("/echo"));
+        assert!(help_text.contains("/price"));
+        assert!(help_text.contains("/newLine"));
+        assert!(help_text.contains("/chart"));
+        assert!(help_text.contains("/alert"));
         assert!(help_text.contains("/subscribe"));
         assert!(help_text.contains("/unsubscribe"));
         assert!(help_text.contains("/status"));
+        assert!(help_text.contains("/template"));
+        assert!(help_text.contains("/pairs"));
+        assert!(help_text.contains("/settings"));
+        assert!(help_text.contains("/language"));
         assert!(help_text.contains("/help"));
+        assert!(help_text.contains("Рассылка:"));
+
+        let help_text = help.get_help_text(Language::En, &switches);
+        assert!(help_text.starts_with("Available commands:"));
+        assert!(help_text.contains("/subscribe - Subscribe to periodic messages"));
+
+        // A switched off module is left out until it's switched on again
+        switches.set_enabled("chart", false);
+        let help_text = help.get_help_text(Language::En, &switches);
+        assert!(!help_text.contains("/chart"));
+        assert!(help_text.contains("/price"));
     }
 }
@@ -0,0 +1,148 @@
+use super::format::PriceFormats;
+use super::TelegramBot;
+use crate::domain::{get_all_currency_pairs, CurrencyPair};
+use crate::i18n::{tr_args, Languages};
+use crate::price_service::history::PricePoint;
+use crate::price_service::PriceService;
+use chrono::{Duration, Local};
+use std::error::Error;
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::types::{
+    InlineQueryResult, InlineQueryResultArticle, InputMessageContent, InputMessageContentText,
+};
+
+/// Cached quotes older than this are fetched again before answering
+const MAX_QUOTE_AGE_MINUTES: i64 = 10;
+
+/// Seconds Telegram may cache an inline answer on its side
+const INLINE_CACHE_TIME: u32 = 60;
+
+/// Answers inline queries (`@bot usd` in any chat) with cards of current rates
+pub struct InlineQuotes {
+    price_service: Arc<PriceService>,
+    languages: Languages,
+    price_formats: PriceFormats,
+}
+
+impl InlineQuotes {
+    pub fn new(
+        price_service: Arc<PriceService>,
+        languages: Languages,
+        price_formats: PriceFormats,
+    ) -> Self {
+        Self {
+            price_service,
+            languages,
+            price_formats,
+        }
+    }
+
+    /// Pairs whose name contains the query, all pairs for an empty query
+    fn matching_pairs(query: &str) -> Vec<CurrencyPair> {
+        let query = query.trim().to_lowercase();
+        get_all_currency_pairs()
+            .into_iter()
+            .filter(|pair| pair.to_string().to_lowercase().contains(&query))
+            .collect()
+    }
+
+    /// Latest quote of `pair` from the poller's history, fetched live when
+    /// missing or stale
+    async fn quote(&self, pair: &CurrencyPair) -> Option<PricePoint> {
+        let cached = self.price_service.history().latest(pair);
+        let max_age = Duration::minutes(MAX_QUOTE_AGE_MINUTES);
+        if let Some(point) = cached.filter(|point| Local::now() - point.time <= max_age) {
+            return Some(point);
+        }
+
+        match self.price_service.get_price(pair).await {
+            Ok(price_data) => Some(PricePoint {
+                time: Local::now(),
+                price: price_data.price,
+            }),
+            Err(e) => {
+                tracing::warn!("Failed to fetch {} for inline query: {}", pair, e);
+                cached
+            }
+        }
+    }
+
+    pub async fn handle(
+        &self,
+        bot: TelegramBot,
+        query: InlineQuery,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // Inline queries have no chat, use the language of the user's private chat
+        let language = self.languages.get(ChatId(query.from.id.0 as i64));
+        let mut results = Vec::new();
+        for pair in Self::matching_pairs(&query.query) {
+            let Some(point) = self.quote(&pair).await else {
+                continue;
+            };
+            let price = self.price_formats.get(&pair).format(point.price);
+            let text = format!("💰 {}: {}", pair, price);
+            let article = InlineQueryResultArticle::new(
+                pair.to_string(),
+                format!("{}: {}", pair, price),
+                InputMessageContent::Text(InputMessageContentText::new(text)),
+            )
+            .description(tr_args(
+                language,
+                "inline.rate_at",
+                &[("time", &point.time.format("%d.%m.%Y %H:%M"))],
+            ));
+            results.push(InlineQueryResult::Article(article));
+        }
+
+        bot.answer_inline_query(query.id, results)
+            .cache_time(INLINE_CACHE_TIME)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matching_pairs() {
+        assert_eq!(InlineQuotes::matching_pairs(""), get_all_currency_pairs());
+        assert_eq!(
+            InlineQuotes::matching_pairs("usd"),
+            get_all_currency_pairs()
+        );
+        assert_eq!(
+            InlineQuotes::matching_pairs(" usdte "),
+            vec![CurrencyPair::USDTe2RUB]
+        );
+        assert_eq!(
+            InlineQuotes::matching_pairs("USD/RUB"),
+            vec![CurrencyPair::USD2RUB]
+        );
+        assert!(InlineQuotes::matching_pairs("eur").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_quote_uses_fresh_history() {
+        let price_service = Arc::new(PriceService::new());
+        let time = Local::now() - Duration::minutes(5);
+        price_service
+            .history()
+            .record(&CurrencyPair::USD2RUB, 91.5, time);
+        let inline = InlineQuotes::new(
+            Arc::clone(&price_service),
+            Languages::new(),
+            PriceFormats::default(),
+        );
+
+        // No providers are configured, so only the cache can answer
+        // The card shows when the cached quote was recorded
+        assert_eq!(
+            inline.quote(&CurrencyPair::USD2RUB).await,
+            Some(PricePoint { time, price: 91.5 })
+        );
+        assert_eq!(inline.quote(&CurrencyPair::USDTe2RUB).await, None);
+    }
+}
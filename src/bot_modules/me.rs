@@ -0,0 +1,175 @@
+use super::{reply, Module, ModuleContext, TelegramBot};
+use crate::i18n::{tr, tr_args, Language};
+use async_trait::async_trait;
+use chrono::{Local, Utc};
+use std::collections::HashMap;
+use std::error::Error;
+use teloxide::prelude::*;
+
+/// `/me` shows how the chat uses the bot, from the audit log and the
+/// counters of the subscriptions and alerts, also the first thing to look
+/// at when a chat reports it never gets messages
+pub struct MeModule;
+
+impl MeModule {
+    fn subscription(ctx: &ModuleContext, language: Language, chat_id: ChatId) -> String {
+        if !ctx.manager.is_subscribed(chat_id) {
+            return tr(language, "me.not_subscribed").to_string();
+        }
+        match ctx.manager.get_subscribed_at(chat_id) {
+            Some(time) => tr_args(
+                language,
+                "me.subscribed_since",
+                &[
+                    (
+                        "date",
+                        &time.with_timezone(&Local).format("%d.%m.%Y").to_string(),
+                    ),
+                    ("days", &(Utc::now() - time).num_days()),
+                ],
+            ),
+            None => tr(language, "me.subscribed").to_string(),
+        }
+    }
+
+    fn render(ctx: &ModuleContext, language: Language, chat_id: ChatId) -> String {
+        let commands = ctx.audit.entries(chat_id);
+        let alerts = ctx.manager.alerts();
+        let mut text = tr_args(
+            language,
+            "me.title",
+            &[
+                ("days", &ctx.config.audit.retention_days),
+                ("commands", &commands.len()),
+                ("messages", &ctx.manager.get_message_count(chat_id)),
+                ("subscription", &Self::subscription(ctx, language, chat_id)),
+                ("alerts", &alerts.list(chat_id).len()),
+                ("triggered", &alerts.triggered_count(chat_id)),
+            ],
+        );
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for entry in &commands {
+            *counts.entry(&entry.command).or_default() += 1;
+        }
+        // Ties go to the alphabetically first command, so the reply is stable
+        let favorite = counts
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(a.0)));
+        if let Some((command, count)) = favorite {
+            text.push('\n');
+            text.push_str(&tr_args(
+                language,
+                "me.favorite",
+                &[("command", &command), ("count", &count)],
+            ));
+        }
+
+        // Why the periodic messages might not arrive
+        if ctx.manager.is_subscribed(chat_id) {
+            if let Some(quiet_hours) = ctx.manager.get_quiet_hours(chat_id) {
+                text.push_str("\n\n");
+                text.push_str(&tr_args(language, "me.quiet", &[("quiet", &quiet_hours)]));
+            }
+        }
+        let dead_letter = ctx
+            .manager
+            .get_dead_letters()
+            .into_iter()
+            .find(|letter| letter.chat_id == chat_id);
+        if let Some(letter) = dead_letter {
+            text.push_str("\n\n");
+            text.push_str(&tr_args(
+                language,
+                "me.delivery_failed",
+                &[
+                    ("time", &letter.failed_at.format("%d.%m.%Y %H:%M")),
+                    ("error", &letter.error),
+                ],
+            ));
+        }
+        text
+    }
+}
+
+#[async_trait]
+impl Module for MeModule {
+    fn name(&self) -> &str {
+        "Me"
+    }
+
+    fn commands(&self) -> Vec<&str> {
+        vec!["/me"]
+    }
+
+    fn command_descriptions(&self, language: Language) -> Vec<(&str, &str)> {
+        vec![("/me", tr(language, "me.menu"))]
+    }
+
+    fn usage(&self, language: Language) -> Vec<String> {
+        vec![tr(language, "me.usage").to_string()]
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let language = ctx.languages.get(msg.chat.id);
+        ctx.send_reply(
+            &bot,
+            &msg,
+            reply(&bot, &msg, Self::render(ctx, language, msg.chat.id)),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot_modules::subscribers::QuietHours;
+    use crate::bot_modules::testing::{MessageBuilder, MockBot};
+    use crate::bot_modules::ModuleRegistry;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_me_aggregates_chat_stats() {
+        let ctx = ModuleContext::default();
+        let chat_id = ChatId(42);
+        let render = || MeModule::render(&ctx, Language::En, chat_id);
+        assert_eq!(
+            render(),
+            "👤 Chat stats\n\nCommands in the last 30 days: 0\nMessages from the bot: 0\nSubscription: none, see /subscribe\nAlerts: 0 active, 0 triggered"
+        );
+
+        // Commands go through the audit log like in the running bot
+        let mock = MockBot::start().await;
+        let mut registry = ModuleRegistry::new();
+        registry.add_middleware(Box::new(ctx.audit.clone()));
+        registry.register(Box::new(MeModule));
+        registry.set_context(ctx.clone());
+        for _ in 0..2 {
+            let msg = MessageBuilder::new("/me").build();
+            registry.handle_message(mock.bot(), msg).await.unwrap();
+        }
+
+        ctx.manager.subscribe(chat_id);
+        ctx.manager.increment_message_counter(chat_id);
+        ctx.manager
+            .set_quiet_hours(chat_id, QuietHours::from_str("22-8").ok());
+        let text = render();
+        assert!(
+            text.contains("Commands in the last 30 days: 2\n"),
+            "{}",
+            text
+        );
+        assert!(text.contains("Messages from the bot: 1\n"));
+        assert!(text.contains("Subscription: since "));
+        assert!(text.contains("(0 days)"));
+        assert!(text.contains("Most used: /me (2)"));
+        assert!(text.ends_with("🌙 No periodic messages during the quiet hours 22:00–08:00"));
+    }
+}
@@ -0,0 +1,458 @@
+use super::outbound::OutboundQueue;
+use super::{reply, TelegramBot};
+use crate::i18n::{tr_args, Languages};
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use teloxide::prelude::*;
+use teloxide::types::{MessageId, UserId};
+
+/// Result of a module handler as seen by [`Middleware::after`]
+pub type HandlerResult = Result<(), Box<dyn Error + Send + Sync>>;
+
+/// Interceptor around module handlers registered with
+/// [`super::ModuleRegistry::add_middleware`]. `before` hooks run in
+/// registration order, `after` hooks in reverse order.
+#[async_trait]
+pub trait Middleware: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Called before `module` handles `msg`. Returning `false` skips the
+    /// module and the remaining middlewares, the middleware is then
+    /// responsible for telling the user why.
+    async fn before(
+        &self,
+        _bot: &TelegramBot,
+        _msg: &Message,
+        _module: &str,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        Ok(true)
+    }
+
+    /// Called after `module` handled `msg`, also when the handler failed
+    async fn after(&self, _msg: &Message, _module: &str, _result: &HandlerResult) {}
+}
+
+/// Logs every handled command with its module, duration and outcome
+pub struct LoggingMiddleware {
+    /// Start of the commands in flight by chat and message
+    started: Mutex<HashMap<(ChatId, MessageId), Instant>>,
+}
+
+impl LoggingMiddleware {
+    pub fn new() -> Self {
+        Self {
+            started: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for LoggingMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Middleware for LoggingMiddleware {
+    fn name(&self) -> &str {
+        "Logging"
+    }
+
+    async fn before(
+        &self,
+        _bot: &TelegramBot,
+        msg: &Message,
+        _module: &str,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        self.started
+            .lock()
+            .unwrap()
+            .insert((msg.chat.id, msg.id), Instant::now());
+        Ok(true)
+    }
+
+    async fn after(&self, msg: &Message, module: &str, result: &HandlerResult) {
+        let started = self.started.lock().unwrap().remove(&(msg.chat.id, msg.id));
+        let elapsed = started.map(|started| started.elapsed()).unwrap_or_default();
+        let user = msg.from.as_ref().map(|user| user.id.0).unwrap_or_default();
+        match result {
+            Ok(()) => tracing::info!(
+                "Module '{}' handled message from user {} in chat {} in {:?}",
+                module,
+                user,
+                msg.chat.id,
+                elapsed
+            ),
+            Err(e) => tracing::warn!(
+                "Module '{}' failed on message from user {} in chat {} after {:?}: {}",
+                module,
+                user,
+                msg.chat.id,
+                elapsed,
+                e
+            ),
+        }
+    }
+}
+
+/// Last command seen in a chat
+#[derive(Debug, Clone)]
+pub struct ChatActivity {
+    pub chat_id: ChatId,
+    /// Group title or `@username` of a private chat
+    pub title: Option<String>,
+    pub last_seen: DateTime<Local>,
+    pub commands: u64,
+}
+
+/// Records which chats use the bot and when they last sent a command
+#[derive(Clone, Default)]
+pub struct ActivityTracker {
+    chats: Arc<Mutex<HashMap<ChatId, ChatActivity>>>,
+}
+
+impl ActivityTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record(&self, msg: &Message, now: DateTime<Local>) {
+        let title = msg
+            .chat
+            .title()
+            .map(str::to_string)
+            .or_else(|| msg.chat.username().map(|name| format!("@{}", name)));
+        let mut chats = self.chats.lock().unwrap();
+        let activity = chats.entry(msg.chat.id).or_insert_with(|| ChatActivity {
+            chat_id: msg.chat.id,
+            title: None,
+            last_seen: now,
+            commands: 0,
+        });
+        activity.title = title;
+        activity.last_seen = now;
+        activity.commands += 1;
+    }
+
+    /// Up to `limit` chats, most recently active first
+    pub fn recent(&self, limit: usize) -> Vec<ChatActivity> {
+        let mut chats: Vec<ChatActivity> = self.chats.lock().unwrap().values().cloned().collect();
+        chats.sort_by_key(|chat| std::cmp::Reverse(chat.last_seen));
+        chats.truncate(limit);
+        chats
+    }
+
+    /// Chats that sent at least one command since startup
+    pub fn chat_count(&self) -> usize {
+        self.chats.lock().unwrap().len()
+    }
+}
+
+#[async_trait]
+impl Middleware for ActivityTracker {
+    fn name(&self) -> &str {
+        "Activity"
+    }
+
+    async fn before(
+        &self,
+        _bot: &TelegramBot,
+        msg: &Message,
+        _module: &str,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        self.record(msg, Local::now());
+        Ok(true)
+    }
+}
+
+/// A user in a chat, `None` for anonymous admins and channels posting as the
+/// chat itself
+type Sender = (ChatId, Option<UserId>);
+
+/// Skips a command a sender repeats in a chat within `window`, e.g. a
+/// double-tap or an update Telegram redelivers, so prices aren't fetched and
+/// answered twice. Nothing is cached: the repeat gets no reply of its own,
+/// the reply to the first one answers both.
+pub struct DuplicateFilter {
+    window: Duration,
+    /// First time each sender sent a command text within the window
+    seen: Mutex<HashMap<(Sender, String), Instant>>,
+}
+
+impl DuplicateFilter {
+    /// Number of commands tracked before expired ones are dropped
+    const CLEANUP_THRESHOLD: usize = 1000;
+
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether `text` from `sender` repeats a command they sent within the
+    /// window before `now`, recording it otherwise
+    fn is_duplicate(&self, sender: Sender, text: &str, now: Instant) -> bool {
+        let key = (
+            sender,
+            text.split_whitespace().collect::<Vec<_>>().join(" "),
+        );
+        let mut seen = self.seen.lock().unwrap();
+        if seen.len() >= Self::CLEANUP_THRESHOLD {
+            seen.retain(|_, first| now.duration_since(*first) < self.window);
+        }
+        match seen.get(&key) {
+            Some(first) if now.duration_since(*first) < self.window => true,
+            _ => {
+                seen.insert(key, now);
+                false
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Middleware for DuplicateFilter {
+    fn name(&self) -> &str {
+        "Duplicates"
+    }
+
+    async fn before(
+        &self,
+        _bot: &TelegramBot,
+        msg: &Message,
+        module: &str,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let Some(text) = msg.text().or_else(|| msg.caption()) else {
+            return Ok(true);
+        };
+        let sender = (msg.chat.id, msg.from.as_ref().map(|user| user.id));
+        if !self.is_duplicate(sender, text, Instant::now()) {
+            return Ok(true);
+        }
+        tracing::debug!(
+            "Skipped duplicate command for module '{}' in chat {}",
+            module,
+            msg.chat.id
+        );
+        Ok(false)
+    }
+}
+
+/// Commands seen from one sender within the current window
+#[derive(Default)]
+struct RateWindow {
+    hits: VecDeque<Instant>,
+    notified: bool,
+}
+
+/// Rejection of a command over the limit
+#[derive(Debug, PartialEq)]
+struct Limited {
+    retry_after: Duration,
+    /// Only the first rejection of a window is answered, so the cooldown
+    /// notices can't be used for spam either
+    notify: bool,
+}
+
+/// Limits every sender (a user, or the chat for anonymous admins and
+/// channels) to a number of commands per sliding window
+pub struct RateLimitMiddleware {
+    max_commands: usize,
+    window: Duration,
+    windows: Mutex<HashMap<Sender, RateWindow>>,
+    languages: Languages,
+    outbound: OutboundQueue,
+}
+
+impl RateLimitMiddleware {
+    /// Number of senders tracked before expired windows are dropped
+    const CLEANUP_THRESHOLD: usize = 1000;
+
+    pub fn new(
+        max_commands: usize,
+        window: Duration,
+        languages: Languages,
+        outbound: OutboundQueue,
+    ) -> Self {
+        Self {
+            max_commands,
+            window,
+            windows: Mutex::new(HashMap::new()),
+            languages,
+            outbound,
+        }
+    }
+
+    /// Record a command of `sender` at `now` unless it is over the limit
+    fn acquire(&self, sender: Sender, now: Instant) -> Result<(), Limited> {
+        let mut windows = self.windows.lock().unwrap();
+        if windows.len() >= Self::CLEANUP_THRESHOLD {
+            windows.retain(|_, window| {
+                window
+                    .hits
+                    .back()
+                    .is_some_and(|last| now.duration_since(*last) < self.window)
+            });
+        }
+
+        let window = windows.entry(sender).or_default();
+        while window
+            .hits
+            .front()
+            .is_some_and(|first| now.duration_since(*first) >= self.window)
+        {
+            window.hits.pop_front();
+        }
+
+        match window.hits.front() {
+            Some(first) if window.hits.len() >= self.max_commands => {
+                let retry_after = self.window - now.duration_since(*first);
+                let notify = !window.notified;
+                window.notified = true;
+                Err(Limited {
+                    retry_after,
+                    notify,
+                })
+            }
+            _ => {
+                window.hits.push_back(now);
+                window.notified = false;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Middleware for RateLimitMiddleware {
+    fn name(&self) -> &str {
+        "RateLimit"
+    }
+
+    async fn before(
+        &self,
+        bot: &TelegramBot,
+        msg: &Message,
+        module: &str,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let sender = (msg.chat.id, msg.from.as_ref().map(|user| user.id));
+        let Err(limited) = self.acquire(sender, Instant::now()) else {
+            return Ok(true);
+        };
+
+        tracing::warn!(
+            "Rate limited command for module '{}' in chat {}",
+            module,
+            msg.chat.id
+        );
+        if limited.notify {
+            let seconds = limited.retry_after.as_secs().max(1);
+            let language = self.languages.get(msg.chat.id);
+            let text = tr_args(language, "rate_limited", &[("seconds", &seconds)]);
+            self.outbound
+                .reply(msg.chat.id, reply(bot, msg, text))
+                .await?;
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat_message(chat_id: i64, title: &str) -> Message {
+        serde_json::from_value(serde_json::json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": {"id": chat_id, "type": "supergroup", "title": title},
+            "text": "/price USD/RUB",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_activity_tracker_recent() {
+        let tracker = ActivityTracker::new();
+        let now = Local::now();
+        tracker.record(&chat_message(-1, "First"), now - chrono::Duration::hours(2));
+        tracker.record(
+            &chat_message(-2, "Second"),
+            now - chrono::Duration::hours(1),
+        );
+        tracker.record(&chat_message(-1, "Renamed"), now);
+
+        let recent = tracker.recent(10);
+        assert_eq!(tracker.chat_count(), 2);
+        assert_eq!(recent[0].chat_id, ChatId(-1));
+        assert_eq!(recent[0].title.as_deref(), Some("Renamed"));
+        assert_eq!(recent[0].commands, 2);
+        assert_eq!(recent[1].chat_id, ChatId(-2));
+        assert_eq!(tracker.recent(1).len(), 1);
+    }
+
+    #[test]
+    fn test_rate_limit_window() {
+        let limiter = RateLimitMiddleware::new(
+            2,
+            Duration::from_secs(60),
+            Languages::new(),
+            OutboundQueue::default(),
+        );
+        let user = (ChatId(1), Some(UserId(1)));
+        let other = (ChatId(1), Some(UserId(2)));
+        let start = Instant::now();
+
+        assert_eq!(limiter.acquire(user, start), Ok(()));
+        assert_eq!(
+            limiter.acquire(user, start + Duration::from_secs(10)),
+            Ok(())
+        );
+        assert_eq!(
+            limiter.acquire(user, start + Duration::from_secs(20)),
+            Err(Limited {
+                retry_after: Duration::from_secs(40),
+                notify: true,
+            })
+        );
+        // Later rejections of the same window are silent
+        assert_eq!(
+            limiter.acquire(user, start + Duration::from_secs(30)),
+            Err(Limited {
+                retry_after: Duration::from_secs(30),
+                notify: false,
+            })
+        );
+        // Other senders have their own window
+        assert_eq!(
+            limiter.acquire(other, start + Duration::from_secs(30)),
+            Ok(())
+        );
+        // The first command leaves the window
+        assert_eq!(
+            limiter.acquire(user, start + Duration::from_secs(60)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_duplicate_filter() {
+        let filter = DuplicateFilter::new(Duration::from_secs(3));
+        let sender = (ChatId(1), Some(UserId(10)));
+        let start = Instant::now();
+
+        assert!(!filter.is_duplicate(sender, "/price USD/RUB", start));
+        assert!(filter.is_duplicate(sender, "/price  USD/RUB", start + Duration::from_secs(1)));
+        assert!(!filter.is_duplicate(sender, "/price EUR/RUB", start + Duration::from_secs(1)));
+        assert!(!filter.is_duplicate((ChatId(2), Some(UserId(10))), "/price USD/RUB", start));
+        // Another member of the group asking the same gets an answer too
+        assert!(!filter.is_duplicate((ChatId(1), Some(UserId(11))), "/price USD/RUB", start));
+        // The window starts at the first command, not at the repeats
+        assert!(!filter.is_duplicate(sender, "/price USD/RUB", start + Duration::from_secs(3)));
+    }
+}
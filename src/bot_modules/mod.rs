@@ -1,6 +1,36 @@
+use crate::i18n::{tr, tr_args, Language};
+use crate::reporting::ErrorReporter;
 use async_trait::async_trait;
+use std::collections::BTreeSet;
 use std::error::Error;
+use std::sync::{Arc, RwLock};
+use teloxide::adaptors::Throttle;
 use teloxide::prelude::*;
+use teloxide::types::{BotCommand, ThreadId};
+use tokio::sync::Notify;
+
+/// Bot used for all outbound Telegram calls: sends are queued by the
+/// [`Throttle`] adaptor to respect global, per-chat and per-group flood limits
+pub type TelegramBot = Throttle<Bot>;
+
+/// Forum topic `msg` was posted in, `None` outside of forum supergroups
+pub fn topic_of(msg: &Message) -> Option<ThreadId> {
+    msg.thread_id.filter(|_| msg.is_topic_message)
+}
+
+/// Send `text` to the chat of `msg`, into the forum topic it came from, so
+/// replies don't land in the General topic
+pub fn reply(
+    bot: &TelegramBot,
+    msg: &Message,
+    text: impl Into<String>,
+) -> <TelegramBot as Requester>::SendMessage {
+    let request = bot.send_message(msg.chat.id, text);
+    match topic_of(msg) {
+        Some(thread_id) => request.message_thread_id(thread_id),
+        None => request,
+    }
+}
 
 #[async_trait]
 pub trait Module: Send + Sync {
@@ -8,59 +38,858 @@ pub trait Module: Send + Sync {
 
     fn commands(&self) -> Vec<&str>;
 
-    async fn handle(&self, bot: Bot, msg: Message) -> Result<(), Box<dyn Error + Send + Sync>>;
+    /// Commands with descriptions shown in Telegram's command menu. Commands
+    /// not listed here still work but are not advertised.
+    fn command_descriptions(&self, _language: Language) -> Vec<(&str, &str)> {
+        Vec::new()
+    }
+
+    /// Heading of the module's section in /help
+    fn description(&self, _language: Language) -> Option<&str> {
+        None
+    }
+
+    /// Help lines of the module's commands, by default built from
+    /// [`Self::command_descriptions`]
+    fn usage(&self, language: Language) -> Vec<String> {
+        self.command_descriptions(language)
+            .into_iter()
+            .map(|(command, description)| format!("{} - {}", command, description))
+            .collect()
+    }
+
+    /// Role required to run the module's commands and use its buttons
+    fn required_role(&self) -> Role {
+        Role::User
+    }
+
+    /// Whether the module answers the plain text `text`, tried when a
+    /// message is not a command
+    fn accepts_text(&self, _text: &str) -> bool {
+        false
+    }
+
+    /// Whether the module's commands only answer without changing anything,
+    /// so an edited command is run again and its reply updated
+    fn read_only(&self) -> bool {
+        false
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+    /// Prefix of the inline keyboard callback data owned by the module, e.g.
+    /// `alerts:` for buttons carrying `alerts:delete:3`
+    fn callback_prefix(&self) -> Option<&str> {
+        None
+    }
+
+    /// Handle a callback query whose data starts with [`Self::callback_prefix`].
+    /// The module is responsible for answering the query.
+    async fn handle_callback(
+        &self,
+        _ctx: &ModuleContext,
+        bot: TelegramBot,
+        query: CallbackQuery,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        tracing::debug!(
+            "Module '{}' ignored callback query {}",
+            self.name(),
+            query.id
+        );
+        bot.answer_callback_query(query.id).await?;
+        Ok(())
+    }
+}
+
+/// Typos farther from every command than this get no suggestion
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Levenshtein distance between `a` and `b` in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Module name as configured and switched, case-insensitive and without the
+/// `Module` suffix some names have (`PriceModule` is `price`)
+pub fn module_key(name: &str) -> String {
+    name.trim_end_matches("Module").to_lowercase()
+}
+
+/// Modules switched off at runtime with `/admin module disable <name>`,
+/// shared by the registry and the admin module and kept in the state file
+#[derive(Debug, Clone, Default)]
+pub struct ModuleSwitches {
+    disabled: Arc<RwLock<BTreeSet<String>>>,
+    /// Keys of the registered modules, see [`ModuleRegistry::set_module_switches`]
+    known: Arc<RwLock<BTreeSet<String>>>,
+    /// Woken on every switch, so the command menu can follow
+    changed: Arc<Notify>,
+}
+
+impl ModuleSwitches {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        !self.disabled.read().unwrap().contains(&module_key(name))
+    }
+
+    /// Switch the module `name` on or off, `false` when no such module is registered
+    pub fn set_enabled(&self, name: &str, enabled: bool) -> bool {
+        let key = module_key(name);
+        if !self.known.read().unwrap().contains(&key) {
+            return false;
+        }
+        let mut disabled = self.disabled.write().unwrap();
+        if enabled {
+            disabled.remove(&key);
+        } else {
+            disabled.insert(key);
+        }
+        self.changed.notify_one();
+        true
+    }
+
+    /// Wait for the next [`ModuleSwitches::set_enabled`], returning right
+    /// away when one happened since the last wait
+    pub async fn changed(&self) {
+        self.changed.notified().await;
+    }
+
+    /// Keys of the registered modules, sorted
+    pub fn known(&self) -> Vec<String> {
+        self.known.read().unwrap().iter().cloned().collect()
+    }
+
+    /// Keys of the disabled modules, sorted
+    pub fn export(&self) -> Vec<String> {
+        self.disabled.read().unwrap().iter().cloned().collect()
+    }
+
+    pub fn import(&self, disabled: Vec<String>) {
+        let mut current = self.disabled.write().unwrap();
+        current.extend(disabled.iter().map(|name| module_key(name)));
+    }
 }
 
 pub struct ModuleRegistry {
     modules: Vec<Box<dyn Module>>,
+    middlewares: Vec<Box<dyn Middleware>>,
+    access: RwLock<AccessControl>,
+    switches: ModuleSwitches,
+    context: ModuleContext,
+    bot_username: Option<String>,
+    reporter: ErrorReporter,
 }
 
 impl ModuleRegistry {
     pub fn new() -> Self {
         Self {
             modules: Vec::new(),
+            middlewares: Vec::new(),
+            access: RwLock::new(AccessControl::default()),
+            switches: ModuleSwitches::new(),
+            context: ModuleContext::default(),
+            bot_username: None,
+            reporter: ErrorReporter::default(),
         }
     }
 
+    pub fn add_middleware(&mut self, middleware: Box<dyn Middleware>) {
+        tracing::info!("Registered middleware: {}", middleware.name());
+        self.middlewares.push(middleware);
+    }
+
+    /// Replace the admins and the allowed and blocked lists, also while
+    /// the bot is running
+    pub fn set_access_control(&self, access: AccessControl) {
+        *self.access.write().unwrap() = access;
+    }
+
+    /// Whether the bot serves `user` in `chat_id`, for updates answered
+    /// outside of the registry like inline queries
+    pub fn is_allowed(&self, chat_id: ChatId, user: Option<UserId>) -> bool {
+        self.access.read().unwrap().is_allowed(chat_id, user)
+    }
+
+    /// Where errors returned by modules are reported
+    pub fn set_error_reporter(&mut self, reporter: ErrorReporter) {
+        self.reporter = reporter;
+    }
+
+    /// Services handed to the modules, its chat languages are also used for
+    /// the registry's own replies
+    pub fn set_context(&mut self, context: ModuleContext) {
+        self.context = context;
+    }
+
+    /// Switches deciding which modules answer, offering every module
+    /// registered so far for switching
+    pub fn set_module_switches(&mut self, switches: ModuleSwitches) {
+        *switches.known.write().unwrap() = self
+            .modules
+            .iter()
+            .map(|module| module_key(module.name()))
+            .collect();
+        self.switches = switches;
+    }
+
+    /// Username of the bot, commands mentioning another bot (`/price@OtherBot`)
+    /// are ignored once it is known
+    pub fn set_bot_username(&mut self, username: String) {
+        self.bot_username = Some(username);
+    }
+
     pub fn register(&mut self, module: Box<dyn Module>) {
-        log::info!("Registered module: {}", module.name());
+        tracing::info!("Registered module: {}", module.name());
         self.modules.push(module);
     }
 
+    /// Module owning `command`, matched by the exact command name
+    pub fn find_module(&self, command: &str) -> Option<&dyn Module> {
+        self.modules
+            .iter()
+            .find(|module| module.commands().contains(&command))
+            .map(|module| module.as_ref())
+    }
+
+    /// Module answering the plain `text` among the ones `role` may run
+    fn find_text_module(&self, text: &str, role: Role) -> Option<&dyn Module> {
+        self.modules
+            .iter()
+            .filter(|module| module.required_role() <= role)
+            .filter(|module| self.switches.is_enabled(module.name()))
+            .find(|module| module.accepts_text(text))
+            .map(|module| module.as_ref())
+    }
+
     pub async fn handle_message(
         &self,
-        bot: Bot,
+        bot: TelegramBot,
         msg: Message,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
-        if let Some(text) = msg.text() {
-            for module in &self.modules {
-                for cmd in module.commands() {
-                    if text.starts_with(cmd) {
-                        log::debug!("Module '{}' handling message", module.name());
-                        return module.handle(bot, msg).await;
-                    }
-                }
+        let user = msg.from.as_ref().map(|user| user.id);
+        if !self.access.read().unwrap().is_allowed(msg.chat.id, user) {
+            tracing::debug!(
+                "Ignoring message from a user not allowed in {}",
+                msg.chat.id
+            );
+            return Ok(());
+        }
+
+        let command = ParsedCommand::from_message(&msg);
+        if let (Some(command), Some(username)) = (command, &self.bot_username) {
+            if !command.is_addressed_to(username) {
+                return Ok(());
+            }
+        }
+
+        let language = self.context.languages.get(msg.chat.id);
+        if let Some(module) = command.and_then(|command| self.find_module(command.name)) {
+            // Switched off modules stay silent, e.g. /echo abused for spam
+            if !self.switches.is_enabled(module.name()) {
+                tracing::debug!("Ignoring command of disabled module '{}'", module.name());
+                return Ok(());
+            }
+            if self.access.read().unwrap().role(user) < module.required_role() {
+                tracing::warn!(
+                    "Rejected command of module '{}' from non-admin in chat {}",
+                    module.name(),
+                    msg.chat.id
+                );
+                self.context
+                    .send_reply(&bot, &msg, reply(&bot, &msg, tr(language, "admin_only")))
+                    .await?;
+                return Ok(());
             }
+            return self.run_module(module, bot, msg).await;
+        }
+        if command.is_none() {
+            let role = self.access.read().unwrap().role(user);
+            if let Some(module) = msg
+                .text()
+                .and_then(|text| self.find_text_module(text, role))
+            {
+                return self.run_module(module, bot, msg).await;
+            }
+        }
 
-            log::debug!("No module found for command: {}", text);
-            bot.send_message(msg.chat.id, "Неизвестная команда. Используйте /help")
+        // Plain documents and photos without a command are not answered
+        if let Some(text) = msg.text() {
+            tracing::debug!("No module found for command: {}", text);
+            let suggestion = command.and_then(|command| {
+                self.suggest_command(command.name, self.access.read().unwrap().role(user))
+            });
+            let response = match suggestion {
+                Some(suggestion) => tr_args(
+                    language,
+                    "unknown_command_suggestion",
+                    &[("command", &suggestion)],
+                ),
+                None => tr(language, "unknown_command").to_string(),
+            };
+            self.context
+                .send_reply(&bot, &msg, reply(&bot, &msg, response))
                 .await?;
         }
         Ok(())
     }
+
+    /// Handle an edited message of a [`Module::read_only`] module like a new
+    /// one, editing the earlier reply, so fixing `/price USD/RUV` updates the
+    /// quote. Other edits are ignored: running `/subscribe` or `/alert` again
+    /// would repeat what the command changed.
+    pub async fn handle_edited_message(
+        &self,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let module = match ParsedCommand::from_message(&msg) {
+            Some(command) => self.find_module(command.name),
+            None => {
+                let user = msg.from.as_ref().map(|user| user.id);
+                let role = self.access.read().unwrap().role(user);
+                msg.text()
+                    .and_then(|text| self.find_text_module(text, role))
+            }
+        };
+        let read_only = module.is_some_and(|module| module.read_only());
+        if !read_only {
+            tracing::debug!("Ignoring edited message {} in {}", msg.id, msg.chat.id);
+            return Ok(());
+        }
+        self.handle_message(bot, msg).await
+    }
+
+    /// Registered command closest to a mistyped `command`, among the ones
+    /// `role` may run
+    fn suggest_command(&self, command: &str, role: Role) -> Option<&str> {
+        let command = command.to_lowercase();
+        self.modules
+            .iter()
+            .filter(|module| module.required_role() <= role)
+            .filter(|module| self.switches.is_enabled(module.name()))
+            .flat_map(|module| module.commands())
+            .map(|candidate| {
+                (
+                    edit_distance(&command, &candidate.to_lowercase()),
+                    candidate,
+                )
+            })
+            .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate)
+    }
+
+    /// Help sections in `language` of the registered modules available to
+    /// every user, each with the name of its module
+    pub fn help_sections(&self, language: Language) -> Vec<(String, String)> {
+        self.modules
+            .iter()
+            .filter(|module| module.required_role() == Role::User)
+            .filter_map(|module| {
+                let usage = module.usage(language);
+                if usage.is_empty() {
+                    return None;
+                }
+                let mut section: Vec<String> = module
+                    .description(language)
+                    .map(str::to_string)
+                    .into_iter()
+                    .collect();
+                section.extend(usage);
+                Some((module.name().to_string(), section.join("\n")))
+            })
+            .collect()
+    }
+
+    /// Run `module` wrapped in the middleware chain
+    async fn run_module(
+        &self,
+        module: &dyn Module,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let name = module.name();
+        tracing::Span::current().record("module", name);
+        // Only middlewares that let the message through get their `after` called
+        let mut entered = 0;
+        let mut result = Ok(());
+        for middleware in &self.middlewares {
+            match middleware.before(&bot, &msg, name).await {
+                Ok(true) => entered += 1,
+                Ok(false) => {
+                    tracing::debug!(
+                        "Middleware '{}' stopped message for module '{}'",
+                        middleware.name(),
+                        name
+                    );
+                    break;
+                }
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        if entered == self.middlewares.len() {
+            tracing::debug!("Module '{}' handling message", name);
+            let context = self.context.for_chat(msg.chat.id);
+            result = module.handle(&context, bot, msg.clone()).await;
+        }
+        if let Err(e) = &result {
+            self.reporter.report(&format!("module {}", name), e);
+        }
+        for middleware in self.middlewares[..entered].iter().rev() {
+            middleware.after(&msg, name, &result).await;
+        }
+        result
+    }
+
+    /// Described commands of the switched on modules in `language` for
+    /// `set_my_commands`. Telegram only accepts lowercase commands of latin
+    /// letters, digits and underscores.
+    pub fn bot_commands(&self, language: Language) -> Vec<BotCommand> {
+        let mut commands = Vec::new();
+        for module in &self.modules {
+            if !self.switches.is_enabled(module.name()) {
+                continue;
+            }
+            for (command, description) in module.command_descriptions(language) {
+                let name = command.trim_start_matches('/');
+                let valid = !name.is_empty()
+                    && name.len() <= 32
+                    && name
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+                if valid {
+                    commands.push(BotCommand::new(name, description));
+                } else {
+                    tracing::warn!("Command {} can't be registered with Telegram", command);
+                }
+            }
+        }
+        commands
+    }
+
+    /// Module owning callback `data`, matched by [`Module::callback_prefix`]
+    fn find_callback_module(&self, data: &str) -> Option<&dyn Module> {
+        self.modules
+            .iter()
+            .filter(|module| self.switches.is_enabled(module.name()))
+            .find(|module| {
+                module
+                    .callback_prefix()
+                    .is_some_and(|prefix| data.starts_with(prefix))
+            })
+            .map(|module| module.as_ref())
+    }
+
+    pub async fn handle_callback_query(
+        &self,
+        bot: TelegramBot,
+        query: CallbackQuery,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let data = query.data.as_deref().unwrap_or("");
+        let user = Some(query.from.id);
+        let allowed = query.message.as_ref().is_none_or(|message| {
+            self.access
+                .read()
+                .unwrap()
+                .is_allowed(message.chat().id, user)
+        });
+        match self.find_callback_module(data) {
+            Some(module)
+                if allowed && self.access.read().unwrap().role(user) >= module.required_role() =>
+            {
+                tracing::Span::current().record("module", module.name());
+                tracing::debug!("Module '{}' handling callback query", module.name());
+                let chat_id = query
+                    .message
+                    .as_ref()
+                    .map_or(ChatId(query.from.id.0 as i64), |message| message.chat().id);
+                let context = self.context.for_chat(chat_id);
+                module.handle_callback(&context, bot, query).await
+            }
+            _ => {
+                tracing::debug!("No module found or allowed for callback data: {}", data);
+                // Always answer, otherwise the client keeps showing a spinner
+                bot.answer_callback_query(query.id).await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Default for ModuleRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
+pub mod admin;
+pub mod alerts;
+pub mod analytics;
+pub mod audit;
+pub mod auth;
+pub mod bot_commands;
+pub mod channels;
+pub mod chart;
+pub mod command;
+pub mod context;
+pub mod convert;
 pub mod echo;
+pub mod feedback;
+pub mod format;
 pub mod help;
+pub mod inline;
+pub mod me;
+pub mod middleware;
 pub mod newline;
+pub mod outbound;
 pub mod price;
+pub mod profile;
+pub mod replies;
+pub mod route;
 pub mod scheduler;
+pub mod settings;
 pub mod start;
 pub mod subscribers;
+pub mod summary;
+pub mod template;
+#[cfg(test)]
+pub mod testing;
+pub mod week;
+pub mod weekly_report;
 
+pub use self::admin::AdminModule;
+pub use self::alerts::AlertModule;
+pub use self::analytics::AnalyticsModule;
+pub use self::auth::{AccessControl, Role};
+pub use self::bot_commands::{BotCommandsModule, CommandsModule};
+pub use self::chart::ChartModule;
+pub use self::command::{ArgError, Args, ParsedCommand};
+pub use self::context::ModuleContext;
+pub use self::convert::ConvertModule;
 pub use self::echo::EchoModule;
+pub use self::feedback::{FeedbackModule, FeedbackReplyModule};
+pub use self::format::MessageFormat;
 pub use self::help::HelpModule;
+pub use self::inline::InlineQuotes;
+pub use self::me::MeModule;
+pub use self::middleware::{DuplicateFilter, LoggingMiddleware, Middleware, RateLimitMiddleware};
 pub use self::newline::NewLineModule;
+pub use self::outbound::{OutboundQueue, Priority};
 pub use self::price::PriceModule;
+pub use self::profile::{Preferences, UserProfile};
+pub use self::route::RouteModule;
+pub use self::settings::SettingsModule;
 pub use self::start::StartModule;
 pub use self::subscribers::{SubscriberManager, SubscriberModule};
+pub use self::week::WeekModule;
+
+#[cfg(test)]
+mod tests {
+    use super::middleware::HandlerResult;
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use teloxide::requests::RequesterExt;
+
+    struct CallbackModule;
+
+    #[async_trait]
+    impl Module for CallbackModule {
+        fn name(&self) -> &str {
+            "Callback"
+        }
+
+        fn commands(&self) -> Vec<&str> {
+            vec!["/callback"]
+        }
+
+        async fn handle(
+            &self,
+            _ctx: &ModuleContext,
+            _bot: TelegramBot,
+            _msg: Message,
+        ) -> Result<(), Box<dyn Error + Send + Sync>> {
+            Ok(())
+        }
+
+        fn callback_prefix(&self) -> Option<&str> {
+            Some("cb:")
+        }
+    }
+
+    #[test]
+    fn test_bot_commands_only_include_described_commands() {
+        let mut registry = ModuleRegistry::new();
+        registry.register(Box::new(HelpModule::new(HashMap::new())));
+        registry.register(Box::new(CallbackModule));
+
+        let commands = registry.bot_commands(Language::Ru);
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].command, "help");
+        assert!(!commands[0].description.is_empty());
+
+        let switches = ModuleSwitches::new();
+        registry.set_module_switches(switches.clone());
+        switches.set_enabled("help", false);
+        assert!(registry.bot_commands(Language::Ru).is_empty());
+    }
+
+    #[test]
+    fn test_command_routing_is_exact() {
+        let mut registry = ModuleRegistry::new();
+        registry.register(Box::new(HelpModule::new(HashMap::new())));
+        registry.register(Box::new(CallbackModule));
+
+        assert_eq!(
+            registry.find_module("/callback").unwrap().name(),
+            "Callback"
+        );
+        assert_eq!(registry.find_module("/help").unwrap().name(), "Help");
+        assert!(registry.find_module("/callbacks").is_none());
+        assert!(registry.find_module("/call").is_none());
+    }
+
+    /// Records its hooks and lets messages through unless `stop` is set
+    struct RecordingMiddleware {
+        label: &'static str,
+        stop: bool,
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Middleware for RecordingMiddleware {
+        fn name(&self) -> &str {
+            self.label
+        }
+
+        async fn before(
+            &self,
+            _bot: &TelegramBot,
+            _msg: &Message,
+            module: &str,
+        ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+            let event = format!("{} before {}", self.label, module);
+            self.events.lock().unwrap().push(event);
+            Ok(!self.stop)
+        }
+
+        async fn after(&self, _msg: &Message, module: &str, result: &HandlerResult) {
+            let event = format!("{} after {} ok={}", self.label, module, result.is_ok());
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    fn command_message(text: &str) -> Message {
+        serde_json::from_value(serde_json::json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": {"id": 42, "type": "private", "first_name": "Test"},
+            "text": text,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_topic_of_ignores_reply_threads() {
+        let topic_message = |is_topic_message: bool| -> Message {
+            serde_json::from_value(serde_json::json!({
+                "message_id": 10,
+                "message_thread_id": 7,
+                "is_topic_message": is_topic_message,
+                "date": 0,
+                "chat": {"id": -100, "type": "supergroup", "title": "Test", "is_forum": true},
+                "text": "/price",
+            }))
+            .unwrap()
+        };
+        assert_eq!(
+            topic_of(&topic_message(true)),
+            Some(ThreadId(teloxide::types::MessageId(7)))
+        );
+        // Replies in regular groups carry a thread ID too
+        assert_eq!(topic_of(&topic_message(false)), None);
+        assert_eq!(topic_of(&command_message("/price")), None);
+    }
+
+    fn registry_with_middlewares(stop_second: bool) -> (ModuleRegistry, Arc<Mutex<Vec<String>>>) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut registry = ModuleRegistry::new();
+        registry.register(Box::new(CallbackModule));
+        for (label, stop) in [("first", false), ("second", stop_second)] {
+            registry.add_middleware(Box::new(RecordingMiddleware {
+                label,
+                stop,
+                events: Arc::clone(&events),
+            }));
+        }
+        (registry, events)
+    }
+
+    #[tokio::test]
+    async fn test_middlewares_wrap_module() {
+        let bot = Bot::new("token").throttle(Default::default());
+        let (registry, events) = registry_with_middlewares(false);
+
+        registry
+            .handle_message(bot, command_message("/callback"))
+            .await
+            .unwrap();
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                "first before Callback",
+                "second before Callback",
+                "second after Callback ok=true",
+                "first after Callback ok=true",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_middleware_stops_chain() {
+        let bot = Bot::new("token").throttle(Default::default());
+        let (registry, events) = registry_with_middlewares(true);
+
+        registry
+            .handle_message(bot, command_message("/callback@AnyBot arg"))
+            .await
+            .unwrap();
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                "first before Callback",
+                "second before Callback",
+                "first after Callback ok=true",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_edited_commands_are_ignored_unless_read_only() {
+        let bot = Bot::new("token").throttle(Default::default());
+        let (registry, events) = registry_with_middlewares(false);
+
+        registry
+            .handle_edited_message(bot.clone(), command_message("just text"))
+            .await
+            .unwrap();
+        registry
+            .handle_edited_message(bot, command_message("/callback"))
+            .await
+            .unwrap();
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_disabled_module_is_ignored() {
+        let bot = Bot::new("token").throttle(Default::default());
+        let (mut registry, events) = registry_with_middlewares(false);
+        let switches = ModuleSwitches::new();
+        registry.set_module_switches(switches.clone());
+        assert!(switches.set_enabled("callback", false));
+        assert!(!switches.set_enabled("unknown", false));
+
+        registry
+            .handle_message(bot.clone(), command_message("/callback"))
+            .await
+            .unwrap();
+        assert!(events.lock().unwrap().is_empty());
+        assert_eq!(registry.suggest_command("/calback", Role::User), None);
+
+        switches.set_enabled("Callback", true);
+        registry
+            .handle_message(bot, command_message("/callback"))
+            .await
+            .unwrap();
+        assert_eq!(events.lock().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_not_allowed_chat_is_ignored() {
+        let bot = Bot::new("token").throttle(Default::default());
+        let (registry, events) = registry_with_middlewares(false);
+        registry.set_access_control(
+            AccessControl::default().with_whitelist(std::collections::HashSet::from([1])),
+        );
+
+        registry
+            .handle_message(bot, command_message("/callback"))
+            .await
+            .unwrap();
+        assert!(events.lock().unwrap().is_empty());
+        assert!(registry.is_allowed(ChatId(1), None));
+        assert!(!registry.is_allowed(ChatId(2), Some(UserId(2))));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("/price", "/price"), 0);
+        assert_eq!(edit_distance("/prce", "/price"), 1);
+        assert_eq!(edit_distance("/pirce", "/price"), 2);
+        assert_eq!(edit_distance("", "/help"), 5);
+        assert_eq!(edit_distance("/курс", "/курсы"), 1);
+    }
+
+    #[test]
+    fn test_suggest_command() {
+        let mut registry = ModuleRegistry::new();
+        registry.register(Box::new(HelpModule::new(HashMap::new())));
+        registry.register(Box::new(CallbackModule));
+        registry.register(Box::new(NewLineModule));
+
+        assert_eq!(registry.suggest_command("/hlep", Role::User), Some("/help"));
+        assert_eq!(
+            registry.suggest_command("/callbak", Role::User),
+            Some("/callback")
+        );
+        assert_eq!(
+            registry.suggest_command("/newline", Role::User),
+            Some("/newLine")
+        );
+        assert_eq!(registry.suggest_command("/weather", Role::User), None);
+    }
+
+    #[test]
+    fn test_plain_text_routing() {
+        let mut registry = ModuleRegistry::new();
+        registry.register(Box::new(HelpModule::new(HashMap::new())));
+        registry.register(Box::new(ConvertModule));
+
+        let module = registry.find_text_module("100 usd в рубли", Role::User);
+        assert_eq!(module.unwrap().name(), "ConvertModule");
+        assert!(registry.find_text_module("привет", Role::User).is_none());
+    }
+
+    #[test]
+    fn test_callback_routing_by_prefix() {
+        let mut registry = ModuleRegistry::new();
+        registry.register(Box::new(HelpModule::new(HashMap::new())));
+        registry.register(Box::new(CallbackModule));
+
+        let module = registry.find_callback_module("cb:delete:1").unwrap();
+        assert_eq!(module.name(), "Callback");
+        assert!(registry.find_callback_module("other:1").is_none());
+        assert!(registry.find_callback_module("").is_none());
+    }
+}
@@ -1,20 +1,13 @@
-use crate::{domain::CurrencyPair, domain::PriceProviderError, price_service::PriceService};
+use super::price::{append_footer, format_price_error, format_quote};
+use super::{ModuleContext, TelegramBot};
+use crate::domain::CurrencyPair;
+use crate::i18n::{tr, Language};
 use async_trait::async_trait;
 use std::error::Error;
-use std::sync::Arc;
 use teloxide::prelude::*;
 
 /// NewLine module for handling NewLine-specific commands
-pub struct NewLineModule {
-    price_service: Arc<PriceService>,
-}
-
-impl NewLineModule {
-    /// Create a new NewLineModule instance
-    pub fn new(price_service: Arc<PriceService>) -> Self {
-        Self { price_service }
-    }
-}
+pub struct NewLineModule;
 
 #[async_trait]
 impl super::Module for NewLineModule {
@@ -26,27 +19,37 @@ impl super::Module for NewLineModule {
         vec!["/newLine"]
     }
 
-    async fn handle(&self, bot: Bot, msg: Message) -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn usage(&self, language: Language) -> Vec<String> {
+        vec![tr(language, "price.newline_usage").to_string()]
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
         let pair = CurrencyPair::USDTe2RUB;
+        let language = ctx.languages.get(msg.chat.id);
 
-        match self.price_service.get_price(&pair).await {
+        let response = match ctx.price_service.get_price(&pair).await {
             Ok(price_data) => {
-                let response = format!(
-                    "💰 {} Price\n\nCurrency Pair: {}\nPrice: {:.2}",
-                    pair, price_data.pair, price_data.price
+                let change = ctx.price_service.last_change(&pair);
+                let price_format = ctx.config.display.price_format(&pair);
+                let quote = format_quote(
+                    language,
+                    ctx.format,
+                    &price_format,
+                    &pair,
+                    &price_data,
+                    change,
                 );
-                bot.send_message(msg.chat.id, response).await?;
-            }
-            Err(e) => {
-                let error_msg = match e {
-                    PriceProviderError::Network(msg) => format!("🌐 Network error: {}", msg),
-                    PriceProviderError::Api(msg) => format!("🔌 API error: {}", msg),
-                    PriceProviderError::Parsing(msg) => format!("📜 Parsing error: {}", msg),
-                    PriceProviderError::Provider(msg) => format!("❌ Provider error: {}", msg),
-                };
-                bot.send_message(msg.chat.id, error_msg).await?;
+                append_footer(ctx, quote, std::slice::from_ref(&pair))
             }
-        }
+            Err(e) => format_price_error(language, ctx.format, &e),
+        };
+        ctx.send_reply(&bot, &msg, ctx.format.reply(&bot, &msg, response))
+            .await?;
 
         Ok(())
     }
@@ -0,0 +1,248 @@
+use super::TelegramBot;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::Recipient;
+use teloxide::RequestError;
+use tokio::time::Instant;
+
+/// Telegram delivers at most about one message per second to the same chat
+const CHAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Chats remembered before the ones past their interval are dropped
+const CLEANUP_THRESHOLD: usize = 1000;
+
+/// Attempts for a request Telegram answers with a RetryAfter (429) error
+const MAX_RETRY_AFTER_ATTEMPTS: u32 = 3;
+
+/// Repeat `request` while Telegram answers with a RetryAfter (429) error
+pub async fn with_retry_after<T, F, Fut>(mut request: F) -> Result<T, RequestError>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, RequestError>>,
+{
+    let mut attempt = 1;
+    loop {
+        match request().await {
+            Err(RequestError::RetryAfter(after)) if attempt < MAX_RETRY_AFTER_ATTEMPTS => {
+                tracing::warn!("Flood limit hit, retrying in {:?}", after.duration());
+                tokio::time::sleep(after.duration()).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Order in which queued messages get the global send slots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Answer to a user, sent as soon as its chat allows
+    Reply,
+    /// Periodic messages, broadcasts, alerts, reports and channel posts,
+    /// paced to the global rate behind any reply
+    Broadcast,
+}
+
+#[derive(Debug)]
+struct Pacing {
+    /// Earliest start of the next broadcast send
+    next_slot: Instant,
+    /// Reserved start of the last send per chat
+    chats: HashMap<Recipient, Instant>,
+}
+
+impl Pacing {
+    /// Reserve the start of a send to `chat` requested at `now`. Every send
+    /// takes a global slot, replies only wait for their chat and so push the
+    /// queued broadcasts back.
+    fn reserve(
+        &mut self,
+        priority: Priority,
+        chat: Recipient,
+        now: Instant,
+        interval: Duration,
+    ) -> Instant {
+        if self.chats.len() > CLEANUP_THRESHOLD {
+            self.chats.retain(|_, last| *last + CHAT_INTERVAL > now);
+        }
+        let chat_ready = self
+            .chats
+            .get(&chat)
+            .map_or(now, |last| (*last + CHAT_INTERVAL).max(now));
+        let start = match priority {
+            Priority::Reply => chat_ready,
+            Priority::Broadcast => chat_ready.max(self.next_slot),
+        };
+        self.next_slot = self.next_slot.max(now) + interval;
+        self.chats.insert(chat, start);
+        start
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    interval: Duration,
+    pacing: Mutex<Pacing>,
+    sent: AtomicU64,
+    failed: AtomicU64,
+}
+
+/// Single path of the messages a bot sends: replies, periodic messages,
+/// broadcasts, alerts, reports and channel posts. Sends are paced to
+/// `messages_per_second` overall and one per second per chat, replies go
+/// before broadcasts, flood limits are retried and failures counted in one
+/// place. Clones share the queue.
+#[derive(Debug, Clone)]
+pub struct OutboundQueue {
+    inner: Arc<Inner>,
+}
+
+impl OutboundQueue {
+    pub fn new(messages_per_second: u32) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                interval: Duration::from_secs(1) / messages_per_second.max(1),
+                pacing: Mutex::new(Pacing {
+                    next_slot: Instant::now(),
+                    chats: HashMap::new(),
+                }),
+                sent: AtomicU64::new(0),
+                failed: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Wait for a send slot to `chat`, then run `request` until it is not
+    /// flood limited anymore
+    pub async fn send<T, F, Fut>(
+        &self,
+        priority: Priority,
+        chat: impl Into<Recipient>,
+        request: F,
+    ) -> Result<T, RequestError>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T, RequestError>>,
+    {
+        let chat = chat.into();
+        let start = self.inner.pacing.lock().unwrap().reserve(
+            priority,
+            chat.clone(),
+            Instant::now(),
+            self.inner.interval,
+        );
+        tokio::time::sleep_until(start).await;
+
+        let result = with_retry_after(request).await;
+        match &result {
+            Ok(_) => self.inner.sent.fetch_add(1, Ordering::Relaxed),
+            Err(e) => {
+                tracing::debug!("Outbound {:?} message to {} failed: {}", priority, chat, e);
+                self.inner.failed.fetch_add(1, Ordering::Relaxed)
+            }
+        };
+        result
+    }
+
+    /// Send `request`, an answer to a user in `chat`, ahead of the queued
+    /// broadcasts
+    pub async fn reply(
+        &self,
+        chat: ChatId,
+        request: <TelegramBot as Requester>::SendMessage,
+    ) -> Result<Message, RequestError> {
+        self.send(Priority::Reply, chat, || request.send_ref())
+            .await
+    }
+
+    /// Messages delivered and failed since the start, for `/stats`
+    pub fn counts(&self) -> (u64, u64) {
+        (
+            self.inner.sent.load(Ordering::Relaxed),
+            self.inner.failed.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Telegram's recommended global limit of ~30 messages per second, with headroom
+impl Default for OutboundQueue {
+    fn default() -> Self {
+        Self::new(25)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use teloxide::types::{ChatId, Seconds};
+
+    #[test]
+    fn test_pacing_priorities() {
+        let now = Instant::now();
+        let interval = Duration::from_millis(100);
+        let mut pacing = Pacing {
+            next_slot: now,
+            chats: HashMap::new(),
+        };
+        let chat = |id| Recipient::Id(ChatId(id));
+
+        // Broadcasts to different chats are spread by the global interval
+        assert_eq!(
+            pacing.reserve(Priority::Broadcast, chat(1), now, interval),
+            now
+        );
+        assert_eq!(
+            pacing.reserve(Priority::Broadcast, chat(2), now, interval),
+            now + interval
+        );
+        // A reply doesn't queue behind them, but the next broadcast does
+        assert_eq!(pacing.reserve(Priority::Reply, chat(3), now, interval), now);
+        assert_eq!(
+            pacing.reserve(Priority::Broadcast, chat(4), now, interval),
+            now + interval * 3
+        );
+        // The same chat waits for its own interval
+        assert_eq!(
+            pacing.reserve(Priority::Reply, chat(1), now, interval),
+            now + CHAT_INTERVAL
+        );
+        assert_eq!(
+            pacing.reserve(Priority::Broadcast, chat(1), now, interval),
+            now + CHAT_INTERVAL * 2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_counts_results() {
+        let queue = OutboundQueue::new(1000);
+        let mut calls = 0;
+        let result = queue
+            .send(Priority::Broadcast, ChatId(1), || {
+                calls += 1;
+                let attempt = calls;
+                async move {
+                    if attempt < 2 {
+                        Err(RequestError::RetryAfter(Seconds::from_seconds(0)))
+                    } else {
+                        Ok(attempt)
+                    }
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), 2);
+
+        let mut calls = 0;
+        let result: Result<(), _> = queue
+            .send(Priority::Reply, ChatId(2), || {
+                calls += 1;
+                async { Err(RequestError::RetryAfter(Seconds::from_seconds(0))) }
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls, MAX_RETRY_AFTER_ATTEMPTS);
+        assert_eq!(queue.counts(), (1, 1));
+    }
+}
@@ -1,21 +1,336 @@
-use crate::{domain::CurrencyPair, domain::PriceProviderError, price_service::PriceService};
+use super::alerts::{AlertCondition, AlertRule, MAX_ALERTS_PER_CHAT};
+use super::chart::{Chart, DEFAULT_CHART_PERIOD};
+use super::command::parse_pairs;
+use super::format::{MessageFormat, PriceFormat};
+use super::outbound::Priority;
+use super::subscribers::{
+    day_ago_quotes, render_footer, render_quotes, Quotes, Sparklines, Trends, DEFAULT_PRICE_LINE,
+};
+use super::{topic_of, ModuleContext, ParsedCommand, TelegramBot};
+use crate::domain::{get_all_currency_pairs, CurrencyPair, PriceData, PriceProviderError};
+use crate::i18n::{tr, tr_args, Language};
+use crate::price_service::analytics::{PriceRange, Watermarks};
+use crate::price_service::history::{format_period, PriceChange, PriceHistory};
 use async_trait::async_trait;
 use std::error::Error;
-use std::sync::Arc;
+use std::str::FromStr;
 use teloxide::prelude::*;
+use teloxide::requests::HasPayload;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+use teloxide::{ApiError, RequestError};
 
-/// Price module for handling price-related commands
-pub struct PriceModule {
-    price_service: Arc<PriceService>,
+const CALLBACK_PREFIX: &str = "price:";
+
+/// Buttons under a `/price` reply, so the usual follow-ups don't need typing
+#[derive(Debug, Clone, PartialEq)]
+enum QuickAction {
+    /// Fetch the price again and update the reply, or show it in place of
+    /// the pair picker
+    Refresh(CurrencyPair),
+    /// One-shot alert once the price crosses the level shown in the reply
+    Alert(CurrencyPair, f64),
+    /// Chart of the pair over [`DEFAULT_CHART_PERIOD`]
+    Chart(CurrencyPair),
+}
+
+impl QuickAction {
+    fn parse(data: &str) -> Option<Self> {
+        let (action, args) = data.strip_prefix(CALLBACK_PREFIX)?.split_once(':')?;
+        match action {
+            "refresh" => Some(QuickAction::Refresh(CurrencyPair::from_str(args).ok()?)),
+            "chart" => Some(QuickAction::Chart(CurrencyPair::from_str(args).ok()?)),
+            "alert" => {
+                let (pair, level) = args.rsplit_once(':')?;
+                let level = level.parse().ok().filter(|level: &f64| *level > 0.0)?;
+                Some(QuickAction::Alert(
+                    CurrencyPair::from_str(pair).ok()?,
+                    level,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Callback data of the button, well within Telegram's 64 bytes
+    fn data(&self) -> String {
+        let action = match self {
+            QuickAction::Refresh(pair) => format!("refresh:{}", pair),
+            QuickAction::Alert(pair, level) => format!("alert:{}:{}", pair, level),
+            QuickAction::Chart(pair) => format!("chart:{}", pair),
+        };
+        format!("{}{}", CALLBACK_PREFIX, action)
+    }
+
+    fn button(self, language: Language) -> InlineKeyboardButton {
+        let key = match self {
+            QuickAction::Refresh(_) => "price.action.refresh",
+            QuickAction::Alert(..) => "price.action.alert",
+            QuickAction::Chart(_) => "price.action.chart",
+        };
+        InlineKeyboardButton::callback(tr(language, key), self.data())
+    }
+}
+
+/// Quick actions of a quote of `pair` at `price`, the alert is set at the
+/// price as the quote shows it
+fn quick_actions(
+    language: Language,
+    price_format: &PriceFormat,
+    pair: &CurrencyPair,
+    price: f64,
+) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![[
+        QuickAction::Refresh(pair.clone()),
+        QuickAction::Alert(pair.clone(), price_format.round(price)),
+        QuickAction::Chart(pair.clone()),
+    ]
+    .map(|action| action.button(language))])
+}
+
+/// Buttons of every pair for a bare `/price` of a chat without a default
+/// pair, two in a row
+fn pair_picker() -> InlineKeyboardMarkup {
+    let buttons: Vec<InlineKeyboardButton> = get_all_currency_pairs()
+        .into_iter()
+        .map(|pair| {
+            InlineKeyboardButton::callback(pair.to_string(), QuickAction::Refresh(pair).data())
+        })
+        .collect();
+    InlineKeyboardMarkup::new(buttons.chunks(2).map(<[_]>::to_vec))
+}
+
+/// Reply with the price of `pair` as returned by a provider, written with
+/// `price_format`, followed by the trend against the previous quote when
+/// there is one
+pub fn format_quote(
+    language: Language,
+    format: MessageFormat,
+    price_format: &PriceFormat,
+    pair: &CurrencyPair,
+    price_data: &PriceData,
+    change: Option<PriceChange>,
+) -> String {
+    let trend = change
+        .map(|change| format!(" {}", format.code(&change.to_string())))
+        .unwrap_or_default();
+    format.render(
+        tr(language, "price.quote"),
+        &[
+            ("pair", format.bold(&pair.to_string())),
+            ("source", format.escape(&price_data.pair.to_string())),
+            ("price", format.code(&price_format.format(price_data.price))),
+            ("trend", trend),
+        ],
+    )
+}
+
+/// 30-day and all-time ranges of a pair, shown below its quote so the current
+/// rate can be put into context
+pub fn format_watermarks(
+    language: Language,
+    format: MessageFormat,
+    price_format: &PriceFormat,
+    watermarks: &Watermarks,
+) -> String {
+    let line = |key, range: PriceRange| {
+        format.render(
+            tr(language, key),
+            &[
+                ("min", format.code(&price_format.format(range.min))),
+                ("max", format.code(&price_format.format(range.max))),
+            ],
+        )
+    };
+    format!(
+        "{}\n{}",
+        line("price.range_rolling", watermarks.rolling),
+        line("price.range_all_time", watermarks.all_time)
+    )
+}
+
+/// Sparkline of the prices of `pair` over `window`, shown below its quote
+fn format_sparkline(
+    language: Language,
+    format: MessageFormat,
+    history: &PriceHistory,
+    pair: &CurrencyPair,
+    window: chrono::Duration,
+) -> Option<String> {
+    let sparkline = history.sparkline(pair, chrono::Local::now() - window)?;
+    Some(format.render(
+        tr(language, "price.sparkline"),
+        &[
+            ("period", format.escape(&format_period(window))),
+            ("sparkline", format.escape(&sparkline)),
+        ],
+    ))
+}
+
+/// `text` followed by the footer configured in `display.footer`, with the
+/// sources of the quotes of `pairs` and the time in the chat's timezone
+pub fn append_footer(ctx: &ModuleContext, text: String, pairs: &[CurrencyPair]) -> String {
+    match &ctx.config.display.footer {
+        Some(template) => {
+            let footer = render_footer(
+                template,
+                &ctx.price_service,
+                pairs,
+                ctx.format,
+                ctx.profile.timezone,
+            );
+            format!("{}\n\n{}", text, footer)
+        }
+        None => text,
+    }
+}
+
+/// Reply explaining why a price couldn't be fetched
+pub fn format_price_error(
+    language: Language,
+    format: MessageFormat,
+    error: &PriceProviderError,
+) -> String {
+    let (key, error) = match error {
+        PriceProviderError::Network(error) => ("price.error.network", error),
+        PriceProviderError::Api(error) => ("price.error.api", error),
+        PriceProviderError::Parsing(error) => ("price.error.parsing", error),
+        PriceProviderError::Provider(error) => ("price.error.provider", error),
+    };
+    format.render(tr(language, key), &[("error", format.escape(error))])
 }
 
-impl PriceModule {
-    /// Create a new PriceModule instance
-    pub fn new(price_service: Arc<PriceService>) -> Self {
-        Self { price_service }
+/// Quote of `pair` with its ranges and the quick actions, or the
+/// reason the price couldn't be fetched
+async fn quote(
+    ctx: &ModuleContext,
+    language: Language,
+    pair: &CurrencyPair,
+) -> Result<(String, InlineKeyboardMarkup), String> {
+    let format = ctx.format;
+    let price_data = ctx
+        .price_service
+        .get_price(pair)
+        .await
+        .map_err(|e| format_price_error(language, format, &e))?;
+    let change = ctx.price_service.last_change(pair);
+    let price_format = ctx.config.display.price_format(pair);
+    let mut text = format_quote(language, format, &price_format, pair, &price_data, change);
+    let history = ctx.price_service.history();
+    let sparkline = ctx
+        .config
+        .display
+        .sparkline_window()
+        .and_then(|window| format_sparkline(language, format, history, pair, window));
+    if let Some(sparkline) = sparkline {
+        text = format!("{}\n{}", text, sparkline);
+    }
+    if let Some(watermarks) = history.analytics().watermarks(pair) {
+        text = format!(
+            "{}\n\n{}",
+            text,
+            format_watermarks(language, format, &price_format, &watermarks)
+        );
     }
+    let text = append_footer(ctx, text, std::slice::from_ref(pair));
+    Ok((
+        text,
+        quick_actions(language, &price_format, pair, price_data.price),
+    ))
 }
 
+/// Quotes of several pairs fetched in one batch, one line per pair as in the
+/// periodic messages with the pairs that failed listed below, or the reason
+/// the prices couldn't be fetched when none was
+async fn quote_table(ctx: &ModuleContext, language: Language, pairs: &[CurrencyPair]) -> String {
+    let format = ctx.format;
+    let mut quotes = Quotes::new();
+    let mut failed = Vec::new();
+    let mut error = None;
+    for (pair, result) in pairs.iter().zip(ctx.price_service.get_prices(pairs).await) {
+        match result {
+            Ok(price_data) => {
+                quotes.insert(pair.clone(), price_data.price);
+            }
+            Err(e) => {
+                failed.push(pair.to_string());
+                error.get_or_insert(e);
+            }
+        }
+    }
+    if let (true, Some(error)) = (quotes.is_empty(), &error) {
+        return format_price_error(language, format, error);
+    }
+
+    let history = ctx.price_service.history();
+    let previous: Quotes = pairs
+        .iter()
+        .filter_map(|pair| Some((pair.clone(), history.previous(pair)?.price)))
+        .collect();
+    let sparklines: Sparklines = match ctx.config.display.sparkline_window() {
+        Some(window) => {
+            let since = chrono::Local::now() - window;
+            pairs
+                .iter()
+                .filter_map(|pair| Some((pair.clone(), history.sparkline(pair, since)?)))
+                .collect()
+        }
+        None => Sparklines::new(),
+    };
+    let trends = Trends {
+        previous: Some(&previous),
+        day_ago: &day_ago_quotes(history, pairs),
+        sparklines: &sparklines,
+    };
+    let mut table = render_quotes(
+        DEFAULT_PRICE_LINE,
+        pairs.to_vec(),
+        &quotes,
+        trends,
+        format,
+        &ctx.config.display.price_formats(),
+    );
+    if !failed.is_empty() {
+        let note = format.render(
+            tr(language, "price.partial"),
+            &[("pairs", format.escape(&failed.join(", ")))],
+        );
+        table = format!("{}\n\n{}", table, note);
+    }
+    append_footer(ctx, table, pairs)
+}
+
+/// Add a one-shot alert for `pair` crossing `level`: below it when the
+/// latest price is at or above the level, above it otherwise
+fn add_alert(
+    ctx: &ModuleContext,
+    language: Language,
+    chat_id: ChatId,
+    pair: &CurrencyPair,
+    level: f64,
+) -> String {
+    let latest = ctx.price_service.history().latest(pair);
+    let condition = match latest {
+        Some(point) if point.price < level => AlertCondition::Above { price: level },
+        _ => AlertCondition::Below { price: level },
+    };
+    let rule = AlertRule::new(pair.clone(), condition, false);
+    let description = rule.describe(language, ctx.manager.price_formats());
+    match ctx.manager.alerts().add(chat_id, rule) {
+        Some(number) => {
+            ctx.manager.save_state();
+            tr_args(
+                language,
+                "alerts.created",
+                &[("number", &number), ("rule", &description)],
+            )
+        }
+        None => tr_args(language, "alerts.limit", &[("max", &MAX_ALERTS_PER_CHAT)]),
+    }
+}
+
+/// Price module for handling price-related commands
+pub struct PriceModule;
+
 #[async_trait]
 impl super::Module for PriceModule {
     fn name(&self) -> &str {
@@ -26,67 +341,306 @@ impl super::Module for PriceModule {
         vec!["/price"]
     }
 
-    async fn handle(&self, bot: Bot, msg: Message) -> Result<(), Box<dyn Error + Send + Sync>> {
-        if let Some(text) = msg.text() {
-            let parts: Vec<&str> = text.split_whitespace().collect();
-
-            if parts.len() == 2 && parts[0] == "/price" {
-                let pair_input = parts[1];
-
-                // Try to parse as domain currency pair
-                if let Some(pair) = CurrencyPair::from_str(pair_input) {
-                    // Use the new interface that works directly with currency pairs
-                    match self.price_service.get_price(&pair).await {
-                        Ok(price_data) => {
-                            let response = format!(
-                                "💰 {} Price\n\nCurrency Pair: {}\nPrice: {:.2}",
-                                pair, price_data.pair, price_data.price
-                            );
-                            bot.send_message(msg.chat.id, response).await?;
-                        }
-                        Err(e) => {
-                            let error_msg = match e {
-                                PriceProviderError::Network(msg) => {
-                                    format!("🌐 Network error: {}", msg)
-                                }
-                                PriceProviderError::Api(msg) => {
-                                    format!("🔌 API error: {}", msg)
-                                }
-                                PriceProviderError::Parsing(msg) => {
-                                    format!("📜 Parsing error: {}", msg)
-                                }
-                                PriceProviderError::Provider(msg) => {
-                                    format!("❌ Provider error: {}", msg)
-                                }
-                            };
-                            bot.send_message(msg.chat.id, error_msg).await?;
-                        }
-                    }
-                } else {
-                    bot.send_message(
-                                msg.chat.id,
-                                "❌ Invalid currency pair format. Available pairs: USD/RUB, USDCe/RUB, USDTe/RUB",
-                            )
+    fn command_descriptions(&self, language: Language) -> Vec<(&str, &str)> {
+        vec![("/price", tr(language, "price.menu"))]
+    }
+
+    fn description(&self, language: Language) -> Option<&str> {
+        Some(tr(language, "price.section"))
+    }
+
+    fn usage(&self, language: Language) -> Vec<String> {
+        vec![tr(language, "price.usage").to_string()]
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if let Some(command) = ParsedCommand::from_message(&msg) {
+            let language = ctx.languages.get(msg.chat.id);
+            let format = ctx.format;
+            let pairs_list = get_all_currency_pairs()
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let pairs_arg = [("pairs", format.escape(&pairs_list))];
+
+            let pairs = match parse_pairs(command.args) {
+                // Most chats only ever ask for their default pair
+                Ok(pairs) if pairs.is_empty() => {
+                    Ok(ctx.profile.default_pair.iter().cloned().collect())
+                }
+                pairs => pairs,
+            };
+            let response = match pairs.as_deref() {
+                Ok([]) => {
+                    ctx.send_reply(
+                        &bot,
+                        &msg,
+                        format
+                            .reply(&bot, &msg, format.escape(tr(language, "price.choose")))
+                            .reply_markup(pair_picker()),
+                    )
                     .await?;
+                    return Ok(());
                 }
-            } else {
-                let available_pairs = crate::domain::get_all_currency_pairs();
-                let pairs_list = available_pairs
-                    .iter()
-                    .map(|p| format!("{}", p))
-                    .collect::<Vec<_>>()
-                    .join(", ");
-                bot.send_message(
-                    msg.chat.id,
-                    format!(
-                        "Usage: /price CURRENCY_PAIR\nAvailable pairs: {}\nExample: /price USD/RUB",
-                        pairs_list
-                    ),
+                Ok([pair]) => match quote(ctx, language, pair).await {
+                    Ok((text, keyboard)) => {
+                        let request = format.reply(&bot, &msg, text).reply_markup(keyboard);
+                        ctx.send_reply(&bot, &msg, request).await?;
+                        return Ok(());
+                    }
+                    Err(error) => error,
+                },
+                Ok(pairs) => quote_table(ctx, language, pairs).await,
+                Err(_) => format.render(tr(language, "price.invalid_pair"), &pairs_arg),
+            };
+            ctx.send_reply(&bot, &msg, format.reply(&bot, &msg, response))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    fn callback_prefix(&self) -> Option<&str> {
+        Some(CALLBACK_PREFIX)
+    }
+
+    async fn handle_callback(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        query: CallbackQuery,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (Some(message), Some(action)) = (
+            query.message.as_ref(),
+            query.data.as_deref().and_then(QuickAction::parse),
+        ) else {
+            bot.answer_callback_query(query.id).await?;
+            return Ok(());
+        };
+        let chat_id = message.chat().id;
+        let language = ctx.languages.get(chat_id);
+
+        let notice = match action {
+            QuickAction::Refresh(pair) => match quote(ctx, language, &pair).await {
+                Ok((text, keyboard)) => {
+                    let edit = ctx
+                        .outbound
+                        .send(Priority::Reply, chat_id, || {
+                            let mut request = bot
+                                .edit_message_text(chat_id, message.id(), &text)
+                                .reply_markup(keyboard.clone());
+                            request.payload_mut().parse_mode = ctx.format.parse_mode();
+                            request.send()
+                        })
+                        .await;
+                    match edit {
+                        Ok(_) | Err(RequestError::Api(ApiError::MessageNotModified)) => {}
+                        Err(e) => tracing::warn!("Failed to refresh price in {}: {}", chat_id, e),
+                    }
+                    None
+                }
+                // Keep the last quote, the error is only shown to whoever pressed
+                Err(error) => Some(error),
+            },
+            QuickAction::Alert(pair, level) => {
+                Some(add_alert(ctx, language, chat_id, &pair, level))
+            }
+            QuickAction::Chart(pair) => {
+                // Drawing can take a while or fail, the button stops spinning first
+                bot.answer_callback_query(query.id).await?;
+                let thread_id = message.regular_message().and_then(topic_of);
+                Chart::draw(
+                    ctx.price_service.history(),
+                    language,
+                    &pair,
+                    DEFAULT_CHART_PERIOD,
                 )
+                .send(&bot, &ctx.outbound, chat_id, thread_id)
                 .await?;
+                return Ok(());
             }
-        }
+        };
 
+        let answer = bot.answer_callback_query(query.id);
+        match notice {
+            Some(text) => answer.text(text).await?,
+            None => answer.await?,
+        };
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_service::PriceService;
+    use chrono::Local;
+
+    #[test]
+    fn test_quick_actions() {
+        let keyboard = quick_actions(
+            Language::En,
+            &PriceFormat::default(),
+            &CurrencyPair::USD2RUB,
+            92.346,
+        );
+        let data: Vec<_> = keyboard.inline_keyboard[0]
+            .iter()
+            .map(|button| match &button.kind {
+                teloxide::types::InlineKeyboardButtonKind::CallbackData(data) => data.clone(),
+                kind => panic!("Unexpected button {:?}", kind),
+            })
+            .collect();
+        assert_eq!(
+            data,
+            [
+                "price:refresh:USD/RUB",
+                "price:alert:USD/RUB:92.35",
+                "price:chart:USD/RUB"
+            ]
+        );
+        assert_eq!(
+            QuickAction::parse(&data[1]),
+            Some(QuickAction::Alert(CurrencyPair::USD2RUB, 92.35))
+        );
+        assert_eq!(
+            QuickAction::parse(&data[2]),
+            Some(QuickAction::Chart(CurrencyPair::USD2RUB))
+        );
+        let precise = PriceFormat {
+            decimals: 4,
+            ..PriceFormat::default()
+        };
+        let keyboard = quick_actions(Language::En, &precise, &CurrencyPair::USD2RUB, 92.34567);
+        assert_eq!(
+            keyboard.inline_keyboard[0][1].kind,
+            teloxide::types::InlineKeyboardButtonKind::CallbackData(
+                "price:alert:USD/RUB:92.3457".to_string()
+            )
+        );
+        assert_eq!(QuickAction::parse("price:alert:USD/RUB:-1"), None);
+        assert_eq!(QuickAction::parse("price:refresh:EUR/RUB"), None);
+        assert_eq!(QuickAction::parse("settings:refresh:USD/RUB"), None);
+    }
+
+    /// Provider quoting USD/RUB only
+    struct UsdProvider;
+
+    #[async_trait]
+    impl crate::price_service::provider::PriceProvider for UsdProvider {
+        fn name(&self) -> &str {
+            "Usd"
+        }
+
+        async fn fetch_price(&self, pair: &CurrencyPair) -> Result<PriceData, PriceProviderError> {
+            Ok(PriceData {
+                pair: pair.clone(),
+                price: 92.0,
+            })
+        }
+
+        fn supports_currency_pair(&self, pair: &CurrencyPair) -> bool {
+            *pair == CurrencyPair::USD2RUB
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quote_table() {
+        let mut service = PriceService::new();
+        service.add_provider(std::sync::Arc::new(UsdProvider));
+        let defaults = ModuleContext::default();
+        let ctx = ModuleContext::new(
+            defaults.config.clone(),
+            std::sync::Arc::new(service),
+            defaults.manager.clone(),
+        );
+        let table = quote_table(
+            &ctx,
+            Language::En,
+            &[CurrencyPair::USD2RUB, CurrencyPair::USDTe2RUB],
+        )
+        .await;
+        assert_eq!(
+            table,
+            "💰 USD/RUB: 92.00\n💰 USDTe/RUB: —\n\n⚠️ Failed to fetch: USDTe/RUB"
+        );
+
+        // The second quote gets a delta and a flat sparkline
+        let table = quote_table(&ctx, Language::En, &[CurrencyPair::USD2RUB]).await;
+        assert_eq!(table, "💰 USD/RUB: 92.00 → +0.00 (+0.00%) ▄▄");
+
+        let table = quote_table(&ctx, Language::En, &[CurrencyPair::USDTe2RUB]).await;
+        assert!(table.starts_with("❌"), "{}", table);
+
+        // Without any price there's no table, only the reason
+        let table = quote_table(
+            &ctx,
+            Language::En,
+            &[CurrencyPair::USDCe2RUB, CurrencyPair::USDTe2RUB],
+        )
+        .await;
+        let error = ctx
+            .price_service
+            .get_price(&CurrencyPair::USDCe2RUB)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            table,
+            format_price_error(Language::En, MessageFormat::default(), &error)
+        );
+        assert!(!table.contains("Failed to fetch"), "{}", table);
+    }
+
+    #[test]
+    fn test_sparkline_below_quote() {
+        let history = PriceHistory::default();
+        let pair = CurrencyPair::USD2RUB;
+        let window = chrono::Duration::hours(24);
+        let sparkline =
+            || format_sparkline(Language::En, MessageFormat::Plain, &history, &pair, window);
+        assert_eq!(sparkline(), None);
+        let now = Local::now();
+        for (hours, price) in [(3, 90.0), (2, 92.0), (1, 91.0)] {
+            history.record(&pair, price, now - chrono::Duration::hours(hours));
+        }
+        assert_eq!(sparkline().as_deref(), Some("📊 Last 1d: ▁█▅"));
+    }
+
+    #[test]
+    fn test_alert_at_level() {
+        let ctx = ModuleContext::default();
+        let chat_id = ChatId(1);
+        let pair = CurrencyPair::USD2RUB;
+        ctx.price_service
+            .history()
+            .record(&pair, 92.0, Local::now());
+
+        let text = add_alert(&ctx, Language::En, chat_id, &pair, 92.0);
+        assert!(text.starts_with("✅ Alert #1 created"));
+        add_alert(&ctx, Language::En, chat_id, &pair, 95.0);
+        let conditions: Vec<_> = ctx
+            .manager
+            .alerts()
+            .list(chat_id)
+            .iter()
+            .map(|rule| rule.condition)
+            .collect();
+        assert_eq!(
+            conditions,
+            [
+                AlertCondition::Below { price: 92.0 },
+                AlertCondition::Above { price: 95.0 }
+            ]
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use super::subscribers::{DeliveryMode, QuietHours};
+use crate::domain::CurrencyPair;
+use crate::i18n::Language;
+use chrono::FixedOffset;
+use serde::{Deserialize, Serialize};
+
+/// Preferences of a chat the commands default to, stored with the
+/// subscriptions for every chat that set one, subscribed or not
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Preferences {
+    /// Offset of the chat's local time from UTC, written like `+03:00`
+    #[serde(default, with = "utc_offset", skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<FixedOffset>,
+    /// Pair quoted when a command names none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_pair: Option<CurrencyPair>,
+    /// Amount converted when a command names none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_amount: Option<f64>,
+}
+
+impl Preferences {
+    /// Whether nothing is set, such preferences aren't stored
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Everything the bot knows about how a chat wants to be answered, assembled
+/// by [`super::SubscriberManager::profile`] from the language, the delivery
+/// settings and the [`Preferences`] of the chat, and handed to the modules
+/// as [`super::ModuleContext::profile`] for the update being handled
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserProfile {
+    pub language: Language,
+    pub timezone: Option<FixedOffset>,
+    pub default_pair: Option<CurrencyPair>,
+    pub default_amount: Option<f64>,
+    pub delivery: DeliveryMode,
+    pub quiet_hours: Option<QuietHours>,
+}
+
+/// (De)serialization of an optional UTC offset as its `+03:00` form
+mod utc_offset {
+    use chrono::FixedOffset;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        offset: &Option<FixedOffset>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match offset {
+            Some(offset) => serializer.serialize_str(&offset.to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<FixedOffset>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|offset| offset.parse().map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use teloxide::types::{ChatId, Message, MessageId};
+
+/// Replies remembered at most, older commands get a new reply when edited
+const MAX_REPLIES: usize = 1000;
+
+#[derive(Default)]
+struct ReplyState {
+    replies: HashMap<(ChatId, MessageId), MessageId>,
+    /// Commands in the order they were answered, to forget the oldest
+    order: VecDeque<(ChatId, MessageId)>,
+}
+
+/// Reply the bot sent to each recent command, so an edited command updates
+/// its earlier answer instead of adding another one. Shared by the clones.
+#[derive(Clone, Default)]
+pub struct ReplyLog {
+    state: Arc<Mutex<ReplyState>>,
+}
+
+impl ReplyLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember `reply` as the answer to the command `msg`
+    pub fn remember(&self, msg: &Message, reply: &Message) {
+        let key = (msg.chat.id, msg.id);
+        let mut state = self.state.lock().unwrap();
+        if state.replies.insert(key, reply.id).is_none() {
+            state.order.push_back(key);
+        }
+        while state.order.len() > MAX_REPLIES {
+            if let Some(oldest) = state.order.pop_front() {
+                state.replies.remove(&oldest);
+            }
+        }
+    }
+
+    /// Reply sent to the command `msg` of `chat_id`
+    pub fn get(&self, chat_id: ChatId, msg: MessageId) -> Option<MessageId> {
+        self.state
+            .lock()
+            .unwrap()
+            .replies
+            .get(&(chat_id, msg))
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot_modules::testing::MessageBuilder;
+
+    #[test]
+    fn test_oldest_replies_are_forgotten() {
+        let log = ReplyLog::new();
+        let command = |id| {
+            let mut msg = MessageBuilder::new("/price").build();
+            msg.id = MessageId(id);
+            msg
+        };
+        for id in 0..=MAX_REPLIES as i32 {
+            log.remember(&command(id), &command(id + 10_000));
+        }
+        assert_eq!(log.get(ChatId(42), MessageId(0)), None);
+        assert_eq!(log.get(ChatId(42), MessageId(1)), Some(MessageId(10_001)));
+    }
+}
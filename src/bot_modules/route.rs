@@ -0,0 +1,293 @@
+use super::subscribers::{fetch_quotes, Quotes};
+use super::{reply, ArgError, Args, Module, ModuleContext, ParsedCommand, TelegramBot};
+use crate::config::RouteConfig;
+use crate::domain::{get_all_currency_pairs, get_reference_pairs, CurrencyPair};
+use crate::i18n::{tr, Language};
+use async_trait::async_trait;
+use std::error::Error;
+use teloxide::prelude::*;
+
+/// One exchange of a [`Route`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub from: &'static str,
+    pub to: &'static str,
+    pub pair: CurrencyPair,
+    /// Units of `to` received per unit of `from`, after the fee
+    pub rate: f64,
+    pub fee_bps: u32,
+}
+
+/// Chain of exchanges from one currency to another
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub steps: Vec<Step>,
+    /// Amount of the target currency received
+    pub output: f64,
+}
+
+/// Every exchange the quoted pairs allow: buying and selling along each pair
+fn steps(quotes: &Quotes, config: &RouteConfig) -> Vec<Step> {
+    let mut steps = Vec::new();
+    for (pair, price) in quotes {
+        if !price.is_finite() || *price <= 0.0 {
+            continue;
+        }
+        let fee_bps = config.fee_bps(pair);
+        let keep = 1.0 - f64::from(fee_bps) / 10_000.0;
+        let (base, quote) = pair.currencies();
+        for (from, to, rate) in [(base, quote, *price), (quote, base, 1.0 / *price)] {
+            steps.push(Step {
+                from,
+                to,
+                pair: pair.clone(),
+                rate: rate * keep,
+                fee_bps,
+            });
+        }
+    }
+    steps
+}
+
+/// Route of at most `config.max_hops` exchanges turning `amount` of `from`
+/// into the most `to`, every currency visited once
+pub fn best_route(
+    amount: f64,
+    from: &str,
+    to: &str,
+    quotes: &Quotes,
+    config: &RouteConfig,
+) -> Option<Route> {
+    fn search<'a>(
+        steps: &'a [Step],
+        at: &str,
+        to: &str,
+        hops_left: usize,
+        path: &mut Vec<&'a Step>,
+        best: &mut Option<(f64, Vec<&'a Step>)>,
+    ) {
+        if at == to && !path.is_empty() {
+            let factor = path.iter().map(|step| step.rate).product::<f64>();
+            if best.as_ref().is_none_or(|(best, _)| factor > *best) {
+                *best = Some((factor, path.clone()));
+            }
+            return;
+        }
+        if hops_left == 0 {
+            return;
+        }
+        for step in steps.iter().filter(|step| step.from == at) {
+            let visited = path.iter().any(|taken| taken.from == step.to);
+            if !visited {
+                path.push(step);
+                search(steps, step.to, to, hops_left - 1, path, best);
+                path.pop();
+            }
+        }
+    }
+
+    let steps = steps(quotes, config);
+    let mut best = None;
+    search(
+        &steps,
+        from,
+        to,
+        config.max_hops,
+        &mut Vec::new(),
+        &mut best,
+    );
+    best.map(|(factor, path)| Route {
+        steps: path.into_iter().cloned().collect(),
+        output: amount * factor,
+    })
+}
+
+/// Currency code of a pair written in any case, e.g. `usdt` or `RUB`
+fn currency(word: &str) -> Option<&'static str> {
+    get_all_currency_pairs()
+        .iter()
+        .chain(&get_reference_pairs())
+        .flat_map(|pair| {
+            let (base, quote) = pair.currencies();
+            [base, quote]
+        })
+        .find(|code| code.eq_ignore_ascii_case(word))
+}
+
+/// `/route <amount> <from> <to>`, e.g. `/route 100000 RUB USDT`, finds the
+/// chain of exchanges over the quoted pairs giving the most of the target
+/// currency once the configured fees (`[route]`) are paid
+pub struct RouteModule;
+
+impl RouteModule {
+    fn parse_args(args: &str) -> Result<(f64, &'static str, &'static str), ArgError> {
+        let mut args = Args::new(args);
+        let amount = args.amount()?;
+        let mut currency_arg = || {
+            let word = args.word()?;
+            currency(word).ok_or_else(|| ArgError::UnknownCurrency(word.to_string()))
+        };
+        let (from, to) = (currency_arg()?, currency_arg()?);
+        args.finish()?;
+        Ok((amount, from, to))
+    }
+
+    fn render(ctx: &ModuleContext, language: Language, amount: f64, route: &Route) -> String {
+        let format = ctx.format;
+        let (Some(first), Some(last)) = (route.steps.first(), route.steps.last()) else {
+            return String::new();
+        };
+        let lines: Vec<String> = route
+            .steps
+            .iter()
+            .map(|step| {
+                format.render(
+                    tr(language, "route.step"),
+                    &[
+                        ("from", format.escape(step.from)),
+                        ("to", format.escape(step.to)),
+                        ("pair", format.bold(&step.pair.to_string())),
+                        ("rate", format.code(&format!("{:.6}", step.rate))),
+                        (
+                            "fee",
+                            format.escape(&format!("{:.2}%", f64::from(step.fee_bps) / 100.0)),
+                        ),
+                    ],
+                )
+            })
+            .collect();
+        let title = format.render(
+            tr(language, "route.result"),
+            &[
+                ("amount", format.code(&format!("{:.2}", amount))),
+                ("from", format.escape(first.from)),
+                ("output", format.code(&format!("{:.2}", route.output))),
+                ("to", format.escape(last.to)),
+            ],
+        );
+        format!("{}\n\n{}", title, lines.join("\n"))
+    }
+}
+
+#[async_trait]
+impl Module for RouteModule {
+    fn name(&self) -> &str {
+        "Route"
+    }
+
+    fn commands(&self) -> Vec<&str> {
+        vec!["/route"]
+    }
+
+    fn command_descriptions(&self, language: Language) -> Vec<(&str, &str)> {
+        vec![("/route", tr(language, "route.menu"))]
+    }
+
+    fn usage(&self, language: Language) -> Vec<String> {
+        vec![tr(language, "route.usage").to_string()]
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let Some(command) = ParsedCommand::from_message(&msg) else {
+            return Ok(());
+        };
+        let language = ctx.languages.get(msg.chat.id);
+        let (amount, from, to) = match Self::parse_args(command.args) {
+            Ok(args) => args,
+            Err(ArgError::Missing) => {
+                ctx.send_reply(&bot, &msg, reply(&bot, &msg, tr(language, "route.help")))
+                    .await?;
+                return Ok(());
+            }
+            Err(error) => {
+                let response = format!(
+                    "{}\n\n{}",
+                    error.message(language),
+                    tr(language, "route.help")
+                );
+                ctx.send_reply(&bot, &msg, reply(&bot, &msg, response))
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        let pairs: Vec<CurrencyPair> = get_all_currency_pairs()
+            .into_iter()
+            .chain(get_reference_pairs())
+            .filter(|pair| ctx.price_service.supports(pair))
+            .collect();
+        let quotes = fetch_quotes(&ctx.price_service, &pairs).await;
+        let response = match best_route(amount, from, to, &quotes, &ctx.config.route) {
+            Some(route) => Self::render(ctx, language, amount, &route),
+            None => ctx.format.render(
+                tr(language, "route.not_found"),
+                &[
+                    ("from", ctx.format.escape(from)),
+                    ("to", ctx.format.escape(to)),
+                ],
+            ),
+        };
+        ctx.send_reply(&bot, &msg, ctx.format.reply(&bot, &msg, response))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quotes() -> Quotes {
+        Quotes::from([
+            (CurrencyPair::USDTe2RUB, 100.0),
+            (CurrencyPair::USD2RUB, 90.0),
+            (CurrencyPair::USDT2USD, 1.0),
+        ])
+    }
+
+    #[test]
+    fn test_best_route_pays_fees() {
+        let config = RouteConfig::default();
+        // RUB -> USD at 90 and USD -> USDT at 1 beat the straight RUB -> USDT
+        // at 100 despite paying the fee (0.5% by default) twice
+        let route = best_route(100_000.0, "RUB", "USDT", &quotes(), &config).unwrap();
+        assert_eq!(route.steps.len(), 2);
+        assert_eq!(route.steps[0].pair, CurrencyPair::USD2RUB);
+        assert!((route.output - 100_000.0 / 90.0 * 0.995 * 0.995).abs() < 1e-6);
+
+        // A pricier second hop makes the direct exchange the better one
+        let config = RouteConfig {
+            fees: [("USDT/USD".to_string(), 2_000)].into(),
+            ..RouteConfig::default()
+        };
+        let route = best_route(100_000.0, "RUB", "USDT", &quotes(), &config).unwrap();
+        assert_eq!(route.steps.len(), 1);
+        assert_eq!(route.steps[0].pair, CurrencyPair::USDTe2RUB);
+        assert!((route.output - 995.0).abs() < 1e-6);
+
+        let config = RouteConfig {
+            max_hops: 1,
+            ..RouteConfig::default()
+        };
+        assert!(best_route(1.0, "EUR", "RUB", &quotes(), &config).is_none());
+        assert!(best_route(1.0, "RUB", "RUB", &quotes(), &config).is_none());
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            RouteModule::parse_args("100000 rub usdt"),
+            Ok((100_000.0, "RUB", "USDT"))
+        );
+        assert_eq!(RouteModule::parse_args("100 RUB"), Err(ArgError::Missing));
+        assert_eq!(
+            RouteModule::parse_args("100 RUB BTC"),
+            Err(ArgError::UnknownCurrency("BTC".to_string()))
+        );
+    }
+}
@@ -1,88 +1,517 @@
-use super::subscribers::SubscriberManager;
-use std::sync::Arc;
+use crate::reporting::ErrorReporter;
+use chrono::Local;
+use futures::FutureExt;
+use std::any::Any;
+use std::fmt;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use teloxide::prelude::*;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
 
-pub struct Scheduler {
-    subscribers: Arc<SubscriberManager>,
-    interval: Duration,
+/// When a periodic job runs
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobSchedule {
+    /// Fixed interval between runs
+    Interval(Duration),
+    /// Cron expression with seconds, e.g. `0 */10 9-22 * * *`, in local time
+    Cron(Box<cron::Schedule>),
 }
 
-impl Scheduler {
-    pub fn new(subscribers: Arc<SubscriberManager>, interval_minutes: u64) -> Self {
-        let interval = Duration::from_secs(interval_minutes * 60);
-        log::info!(
-            "Scheduler initialized with interval: {} minutes",
-            interval.as_secs() / 60
-        );
+impl JobSchedule {
+    /// Fixed interval shorthand
+    pub fn from_minutes(minutes: u64) -> Self {
+        JobSchedule::Interval(Duration::from_secs(minutes * 60))
+    }
+
+    /// Parse a schedule: a plain number is an interval in minutes, anything
+    /// else is a cron expression
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        if let Ok(minutes) = spec.parse::<u64>() {
+            if minutes == 0 {
+                return Err("Interval must be at least 1 minute".to_string());
+            }
+            return Ok(Self::from_minutes(minutes));
+        }
+
+        cron::Schedule::from_str(spec)
+            .map(|schedule| JobSchedule::Cron(Box::new(schedule)))
+            .map_err(|e| format!("Invalid cron expression '{}': {}", spec, e))
+    }
+}
+
+impl fmt::Display for JobSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobSchedule::Interval(interval) => {
+                write!(f, "every {} minutes", interval.as_secs() / 60)
+            }
+            JobSchedule::Cron(schedule) => write!(f, "cron '{}'", schedule),
+        }
+    }
+}
+
+/// Parse what an interval job does with ticks missed while the host was
+/// suspended or a run took longer than the interval: `burst` catches up with
+/// every missed run, `skip` runs once and realigns to the original schedule,
+/// `delay` runs once and restarts the interval from that run
+pub fn parse_missed_tick_behavior(spec: &str) -> Result<MissedTickBehavior, String> {
+    match spec.trim().to_lowercase().as_str() {
+        "burst" => Ok(MissedTickBehavior::Burst),
+        "skip" => Ok(MissedTickBehavior::Skip),
+        "delay" => Ok(MissedTickBehavior::Delay),
+        other => Err(format!(
+            "Unknown missed tick behavior '{}', expected burst, skip or delay",
+            other
+        )),
+    }
+}
+
+/// When the tick after one scheduled at `tick` and observed at `now` fires,
+/// mirroring how tokio's interval reschedules for `behavior`
+fn next_tick(
+    behavior: MissedTickBehavior,
+    tick: tokio::time::Instant,
+    now: tokio::time::Instant,
+    period: Duration,
+) -> tokio::time::Instant {
+    let on_time = tick + period;
+    if on_time > now {
+        return on_time;
+    }
+    match behavior {
+        MissedTickBehavior::Burst => on_time,
+        MissedTickBehavior::Delay => now + period,
+        MissedTickBehavior::Skip => {
+            let late = (now - tick).as_nanos() % period.as_nanos();
+            now + period - Duration::from_nanos(late as u64)
+        }
+    }
+}
+
+type JobFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+type JobTask = Box<dyn Fn() -> JobFuture + Send + Sync>;
+type NextRunListener = Box<dyn Fn(Instant) + Send + Sync>;
+
+/// Text of a panic payload, which is a `&str` or a `String` for `panic!`
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// A named periodic job: an async closure executed on its own schedule
+pub struct Job {
+    name: String,
+    schedule: Mutex<JobSchedule>,
+    task: JobTask,
+    next_run_listener: Option<NextRunListener>,
+    first_run: Mutex<Option<Instant>>,
+    /// Last announced next run, kept after the job stops
+    next_run: Mutex<Option<Instant>>,
+    /// Time between the last announcement and the announced run, what a
+    /// cycle is expected to take
+    period: Mutex<Duration>,
+    missed_tick_behavior: MissedTickBehavior,
+    /// Keeps scheduled and manually triggered runs from overlapping
+    run_lock: tokio::sync::Mutex<()>,
+    reporter: ErrorReporter,
+}
+
+impl Job {
+    /// Create a job running `task` according to `schedule`
+    pub fn new<F, Fut>(name: &str, schedule: JobSchedule, task: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
         Self {
-            subscribers,
-            interval,
+            name: name.to_string(),
+            schedule: Mutex::new(schedule),
+            task: Box::new(move || Box::pin(task())),
+            next_run_listener: None,
+            first_run: Mutex::new(None),
+            next_run: Mutex::new(None),
+            period: Mutex::new(Duration::ZERO),
+            missed_tick_behavior: MissedTickBehavior::Burst,
+            run_lock: tokio::sync::Mutex::new(()),
+            reporter: ErrorReporter::default(),
         }
     }
 
-    pub async fn start(&self, bot: Bot) {
-        let mut interval_timer = tokio::time::interval(self.interval);
+    /// Choose how an interval job handles missed ticks, `Burst` by default
+    pub fn missed_ticks(mut self, behavior: MissedTickBehavior) -> Self {
+        self.missed_tick_behavior = behavior;
+        self
+    }
 
-        loop {
-            let next_send = Instant::now() + self.interval;
-            self.subscribers.set_next_send_time(next_send);
+    /// Delay the first run of an interval job until `at` instead of running
+    /// right away, e.g. to resume a countdown persisted before a restart.
+    /// Only the first start is delayed, cron jobs always follow their
+    /// expression and ignore this.
+    pub fn first_run_at(self, at: Instant) -> Self {
+        *self.first_run.lock().unwrap() = Some(at);
+        self
+    }
+
+    /// Get notified about the next run time, e.g. to show a countdown
+    pub fn on_next_run(mut self, listener: impl Fn(Instant) + Send + Sync + 'static) -> Self {
+        self.next_run_listener = Some(Box::new(listener));
+        self
+    }
 
-            interval_timer.tick().await;
-            self.send_periodic_message(&bot).await;
+    pub fn schedule(&self) -> JobSchedule {
+        self.schedule.lock().unwrap().clone()
+    }
+
+    fn notify_next_run(&self, next_run: Instant) {
+        *self.next_run.lock().unwrap() = Some(next_run);
+        *self.period.lock().unwrap() = match self.schedule() {
+            JobSchedule::Interval(interval) => interval,
+            JobSchedule::Cron(_) => next_run.saturating_duration_since(Instant::now()),
+        };
+        if let Some(listener) = &self.next_run_listener {
+            listener(next_run);
+        }
+    }
+
+    async fn execute(&self) {
+        let _running = self.run_lock.lock().await;
+        tracing::debug!("Running job '{}'", self.name);
+        // A panicking run is reported, the job keeps its schedule
+        if let Err(panic) = AssertUnwindSafe((self.task)()).catch_unwind().await {
+            let message = panic_message(panic.as_ref());
+            tracing::error!("Job '{}' panicked: {}", self.name, message);
+            self.reporter
+                .report(&format!("job {}", self.name), &message);
         }
     }
 
-    async fn send_periodic_message(&self, bot: &Bot) {
-        let subscribers = self.subscribers.get_subscribers();
-        let count = subscribers.len();
+    /// Run the job until `token` is cancelled. Cancellation only interrupts
+    /// the wait between runs, a run in progress is always completed.
+    async fn run(&self, token: CancellationToken) {
+        match &self.schedule() {
+            JobSchedule::Interval(interval) => {
+                let start = self
+                    .first_run
+                    .lock()
+                    .unwrap()
+                    .take()
+                    .map(tokio::time::Instant::from_std)
+                    .unwrap_or_else(tokio::time::Instant::now);
+                let mut interval_timer = tokio::time::interval_at(start, *interval);
+                interval_timer.set_missed_tick_behavior(self.missed_tick_behavior);
+                let mut next_run = start;
+
+                loop {
+                    self.notify_next_run(next_run.into_std());
+
+                    tokio::select! {
+                        _ = token.cancelled() => return,
+                        tick = interval_timer.tick() => {
+                            next_run = next_tick(
+                                self.missed_tick_behavior,
+                                tick,
+                                tokio::time::Instant::now(),
+                                *interval,
+                            );
+                        }
+                    }
+                    self.execute().await;
+                }
+            }
+            JobSchedule::Cron(schedule) => loop {
+                let Some(next) = schedule.upcoming(Local).next() else {
+                    tracing::warn!(
+                        "Cron schedule of job '{}' has no upcoming fire times, job stopped",
+                        self.name
+                    );
+                    self.reporter.report(
+                        &format!("job {}", self.name),
+                        &"cron schedule has no upcoming fire times, job stopped",
+                    );
+                    return;
+                };
+                let delay = (next - Local::now()).to_std().unwrap_or_default();
+                self.notify_next_run(Instant::now() + delay);
+
+                tokio::select! {
+                    _ = token.cancelled() => return,
+                    _ = tokio::time::sleep(delay) => {}
+                }
+                self.execute().await;
+            },
+        }
+    }
+}
+
+/// State of a started job as seen by the [`Watchdog`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobHealth {
+    /// Running on schedule, or not started
+    Healthy,
+    /// No cycle completed within twice the job's period: a run hangs
+    Stalled,
+    /// The job loop panicked with this message
+    Died(String),
+}
+
+/// Minimum time a cycle may take before it counts as stalled, so cron jobs
+/// announced right before they fire aren't restarted while they run
+const MIN_CYCLE: Duration = Duration::from_secs(60);
+
+/// Start/stop handle of a registered job
+pub struct JobHandle {
+    job: Arc<Job>,
+    shutdown: CancellationToken,
+    task: Mutex<Option<(CancellationToken, JoinHandle<()>)>>,
+}
 
-        if count == 0 {
-            log::debug!("No subscribers to send message to");
+impl JobHandle {
+    fn new(job: Job, shutdown: CancellationToken) -> Self {
+        Self {
+            job: Arc::new(job),
+            shutdown,
+            task: Mutex::new(None),
+        }
+    }
+
+    /// Spawn the job loop unless it is already running
+    pub fn start(&self) {
+        if self.is_running() {
             return;
         }
+        let mut task = self.task.lock().unwrap();
+
+        let job = Arc::clone(&self.job);
+        let token = self.shutdown.child_token();
+        let job_token = token.clone();
+        *task = Some((token, tokio::spawn(async move { job.run(job_token).await })));
+        tracing::info!(
+            "Job '{}' started with schedule: {}",
+            self.job.name,
+            self.job.schedule()
+        );
+    }
+
+    pub fn schedule(&self) -> JobSchedule {
+        self.job.schedule()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.job.name
+    }
+
+    /// When the job runs next, `None` before it was first started
+    pub fn next_run(&self) -> Option<Instant> {
+        *self.job.next_run.lock().unwrap()
+    }
+
+    /// Switch the job to `schedule`, restarting a running loop. An interval
+    /// job runs next one interval from now rather than right away.
+    pub async fn reschedule(&self, schedule: JobSchedule) {
+        // Stopped first so the old loop can't pick up the new first run
+        let running = self.is_running();
+        if running {
+            self.stop().await;
+        }
+        if let JobSchedule::Interval(interval) = &schedule {
+            *self.job.first_run.lock().unwrap() = Some(Instant::now() + *interval);
+        }
+        tracing::info!("Job '{}' rescheduled: {}", self.job.name, schedule);
+        *self.job.schedule.lock().unwrap() = schedule;
+        if running {
+            self.start();
+        }
+    }
 
-        log::info!("Sending periodic message to {} subscribers", count);
+    /// Whether the job loop is currently scheduled
+    pub fn is_running(&self) -> bool {
+        self.task
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|(_, task)| !task.is_finished())
+    }
 
-        let mut success_count = 0;
-        let mut error_count = 0;
+    /// Run the job once right away, independent of its schedule. Waits for a
+    /// scheduled run in progress to finish first.
+    pub async fn run_now(&self) {
+        tracing::info!("Job '{}' triggered manually", self.job.name);
+        self.job.execute().await;
+    }
 
-        for chat_id in subscribers {
-            match self
-                .subscribers
-                .send_periodic_message_to_chat(bot, chat_id)
-                .await
-            {
-                Ok(true) => success_count += 1,
-                Ok(false) => error_count += 1,
-                Err(e) => {
-                    log::error!("Unexpected error for {}: {}", chat_id, e);
-                    error_count += 1;
+    /// Whether the loop started last is still making progress at `now`. A
+    /// loop that ended on its own, like a cron schedule without upcoming
+    /// fire times, is cleared and counts as healthy.
+    pub fn health(&self, now: Instant) -> JobHealth {
+        let mut task = self.task.lock().unwrap();
+        let Some((_, handle)) = task.as_ref() else {
+            return JobHealth::Healthy;
+        };
+        if handle.is_finished() {
+            let finished = task.take().and_then(|(_, handle)| handle.now_or_never());
+            return match finished {
+                Some(Err(e)) if e.is_panic() => {
+                    JobHealth::Died(panic_message(e.into_panic().as_ref()))
                 }
+                _ => JobHealth::Healthy,
+            };
+        }
+        let period = (*self.job.period.lock().unwrap()).max(MIN_CYCLE);
+        match self.next_run() {
+            Some(next_run) if now > next_run + period => JobHealth::Stalled,
+            _ => JobHealth::Healthy,
+        }
+    }
+
+    /// Abort the job loop, including a hanging run, and start it again
+    pub fn restart(&self) {
+        if let Some((token, task)) = self.task.lock().unwrap().take() {
+            token.cancel();
+            task.abort();
+        }
+        tracing::warn!("Restarting job '{}'", self.job.name);
+        self.start();
+    }
+
+    /// Stop the job loop and wait until a run in progress has completed
+    pub async fn stop(&self) {
+        let running = self.task.lock().unwrap().take();
+        if let Some((token, task)) = running {
+            token.cancel();
+            if let Err(e) = task.await {
+                tracing::error!("Job '{}' terminated abnormally: {}", self.job.name, e);
             }
+            tracing::info!("Job '{}' stopped", self.job.name);
+        }
+    }
+}
 
-            tokio::time::sleep(Duration::from_millis(50)).await;
+/// Registry of periodic jobs
+pub struct Scheduler {
+    jobs: Vec<Arc<JobHandle>>,
+    shutdown: CancellationToken,
+    reporter: ErrorReporter,
+}
+
+impl Scheduler {
+    /// Create a scheduler whose jobs stop once `shutdown` is cancelled
+    pub fn new(shutdown: CancellationToken) -> Self {
+        Self {
+            jobs: Vec::new(),
+            shutdown,
+            reporter: ErrorReporter::default(),
         }
+    }
 
-        log::info!(
-            "Periodic message sent: {} success, {} errors",
-            success_count,
-            error_count
-        );
+    /// Where failures of jobs registered afterwards are reported
+    pub fn set_error_reporter(&mut self, reporter: ErrorReporter) {
+        self.reporter = reporter;
+    }
+
+    /// Register a job without starting it
+    pub fn register(&mut self, mut job: Job) -> Arc<JobHandle> {
+        job.reporter = self.reporter.clone();
+        tracing::info!("Registered job: {}", job.name);
+        let handle = Arc::new(JobHandle::new(job, self.shutdown.clone()));
+        self.jobs.push(Arc::clone(&handle));
+        handle
+    }
+
+    /// Delay the first run of the job `name` until `at`, like
+    /// [`Job::first_run_at`], unless it already has a first run
+    pub fn resume(&self, name: &str, at: Instant) {
+        let Some(job) = self.jobs.iter().find(|job| job.name() == name) else {
+            return;
+        };
+        let mut first_run = job.job.first_run.lock().unwrap();
+        if first_run.is_none() {
+            *first_run = Some(at);
+        }
+    }
+
+    /// Next run of every job that was started
+    pub fn next_runs(&self) -> Vec<(String, Instant)> {
+        self.jobs
+            .iter()
+            .filter_map(|job| Some((job.name().to_string(), job.next_run()?)))
+            .collect()
+    }
+
+    pub fn start_all(&self) {
+        for job in &self.jobs {
+            job.start();
+        }
+    }
+
+    /// Stop every job, waiting for runs in progress to complete
+    pub async fn stop_all(&self) {
+        for job in &self.jobs {
+            job.stop().await;
+        }
+    }
+
+    /// Watchdog over the jobs registered so far
+    pub fn watchdog(&self) -> Watchdog {
+        Watchdog {
+            jobs: self.jobs.clone(),
+        }
+    }
+}
+
+/// Restarts jobs whose loop panicked or hasn't completed a cycle within
+/// twice its period, e.g. because a send hangs
+#[derive(Clone)]
+pub struct Watchdog {
+    jobs: Vec<Arc<JobHandle>>,
+}
+
+impl Watchdog {
+    /// Restart the unhealthy jobs, returning their names and what was wrong
+    pub fn check(&self, now: Instant) -> Vec<(String, JobHealth)> {
+        let mut restarted = Vec::new();
+        for job in &self.jobs {
+            let health = job.health(now);
+            if health == JobHealth::Healthy {
+                continue;
+            }
+            tracing::error!("Job '{}' is unhealthy: {:?}", job.name(), health);
+            job.job
+                .reporter
+                .report(&format!("job {}", job.name()), &format!("{:?}", health));
+            job.restart();
+            restarted.push((job.name().to_string(), health));
+        }
+        restarted
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bot_modules::subscribers::SubscriberManager;
+    use crate::price_service::PriceService;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use teloxide::adaptors::throttle::Limits;
+    use teloxide::prelude::*;
     use tokio::time;
 
     #[tokio::test]
     async fn test_scheduler_creation() {
-        let manager = Arc::new(SubscriberManager::new("Test message".to_string()));
-        let scheduler = Scheduler::new(Arc::clone(&manager), 10);
-        let expected_interval = scheduler.interval;
-        assert_eq!(expected_interval, Duration::from_secs(10 * 60));
+        let job = Job::new("broadcast", JobSchedule::from_minutes(10), || async {});
+        let expected_interval = job.schedule();
+        assert_eq!(
+            expected_interval,
+            JobSchedule::Interval(Duration::from_secs(10 * 60))
+        );
     }
 
     #[tokio::test]
@@ -93,18 +522,31 @@ mod tests {
         );
 
         let manager = Arc::new(SubscriberManager::new("Test message".to_string()));
-        let scheduler = Scheduler::new(manager.clone(), 1);
+        let price_service = Arc::new(PriceService::new());
+        let bot = Bot::from_env().throttle(Limits::default());
 
-        let bot = Bot::from_env();
-        let mut interval = time::interval(Duration::from_millis(100));
-
-        tokio::spawn(async move {
-            scheduler.start(bot).await;
-        });
+        let mut scheduler = Scheduler::new(CancellationToken::new());
+        scheduler.register(Job::new(
+            "broadcast",
+            JobSchedule::from_minutes(1),
+            move || {
+                let manager = Arc::clone(&manager);
+                let price_service = Arc::clone(&price_service);
+                let bot = bot.clone();
+                async move {
+                    manager
+                        .broadcast_periodic_message(&bot, &price_service)
+                        .await
+                }
+            },
+        ));
+        scheduler.start_all();
 
+        let mut interval = time::interval(Duration::from_millis(100));
         interval.tick().await;
         interval.tick().await;
 
+        scheduler.stop_all().await;
         std::env::remove_var("TELOXIDE_TOKEN");
     }
 
@@ -123,22 +565,353 @@ mod tests {
         let manager = Arc::new(SubscriberManager::new(
             "Периодическое сообщение от бота".to_string(),
         ));
-        let scheduler = Scheduler::new(Arc::clone(&manager), 10);
-        let expected_interval = scheduler.interval;
+        let job = Job::new("broadcast", JobSchedule::from_minutes(10), || async {});
+        let expected_interval = job.schedule();
         let expected_message = manager.get_periodic_message_text();
 
-        assert_eq!(expected_interval, Duration::from_secs(10 * 60));
+        assert_eq!(
+            expected_interval,
+            JobSchedule::Interval(Duration::from_secs(10 * 60))
+        );
         assert_eq!(expected_message, "Периодическое сообщение от бота");
     }
 
     #[test]
     fn test_scheduler_custom_values() {
         let manager = Arc::new(SubscriberManager::new("Custom message".to_string()));
-        let scheduler = Scheduler::new(Arc::clone(&manager), 5);
-        let interval = scheduler.interval;
+        let job = Job::new("broadcast", JobSchedule::from_minutes(5), || async {});
+        let interval = job.schedule();
         let message_text = manager.get_periodic_message_text();
 
-        assert_eq!(interval, Duration::from_secs(5 * 60));
+        assert_eq!(interval, JobSchedule::Interval(Duration::from_secs(5 * 60)));
         assert_eq!(message_text, "Custom message");
     }
+
+    #[test]
+    fn test_schedule_parse_minutes_shorthand() {
+        assert_eq!(
+            JobSchedule::parse("15"),
+            Ok(JobSchedule::Interval(Duration::from_secs(15 * 60)))
+        );
+        assert!(JobSchedule::parse("0").is_err());
+    }
+
+    #[test]
+    fn test_schedule_parse_cron() {
+        let schedule = JobSchedule::parse("0 */10 9-22 * * *").unwrap();
+        assert!(matches!(schedule, JobSchedule::Cron(_)));
+        assert!(JobSchedule::parse("every now and then").is_err());
+    }
+
+    #[test]
+    fn test_parse_missed_tick_behavior() {
+        assert_eq!(
+            parse_missed_tick_behavior("Skip"),
+            Ok(MissedTickBehavior::Skip)
+        );
+        assert_eq!(
+            parse_missed_tick_behavior("burst"),
+            Ok(MissedTickBehavior::Burst)
+        );
+        assert!(parse_missed_tick_behavior("later").is_err());
+    }
+
+    #[test]
+    fn test_next_tick_after_missed_ticks() {
+        let period = Duration::from_secs(60);
+        let tick = tokio::time::Instant::now();
+        // Observed 2.5 periods late, e.g. after the host woke up
+        let now = tick + Duration::from_secs(150);
+
+        assert_eq!(
+            next_tick(MissedTickBehavior::Burst, tick, now, period),
+            tick + period
+        );
+        assert_eq!(
+            next_tick(MissedTickBehavior::Delay, tick, now, period),
+            now + period
+        );
+        assert_eq!(
+            next_tick(MissedTickBehavior::Skip, tick, now, period),
+            tick + Duration::from_secs(180)
+        );
+        // On-time ticks are unaffected by the behavior
+        assert_eq!(
+            next_tick(MissedTickBehavior::Skip, tick, tick, period),
+            tick + period
+        );
+    }
+
+    #[tokio::test]
+    async fn test_job_start_stop() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let job_runs = Arc::clone(&runs);
+        let job = Job::new(
+            "counter",
+            JobSchedule::Interval(Duration::from_millis(20)),
+            move || {
+                let runs = Arc::clone(&job_runs);
+                async move {
+                    runs.fetch_add(1, Ordering::SeqCst);
+                }
+            },
+        );
+
+        let mut scheduler = Scheduler::new(CancellationToken::new());
+        let handle = scheduler.register(job);
+        handle.start();
+        // Starting twice must not spawn a second loop
+        handle.start();
+        time::sleep(Duration::from_millis(70)).await;
+        handle.stop().await;
+
+        let after_stop = runs.load(Ordering::SeqCst);
+        assert!(after_stop >= 2);
+        time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), after_stop);
+    }
+
+    #[tokio::test]
+    async fn test_job_first_run_at_delays_first_run() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let job_runs = Arc::clone(&runs);
+        let next_runs = Arc::new(Mutex::new(Vec::new()));
+        let listener_runs = Arc::clone(&next_runs);
+        let first_run = Instant::now() + Duration::from_millis(80);
+        let job = Job::new(
+            "delayed",
+            JobSchedule::Interval(Duration::from_secs(60)),
+            move || {
+                let runs = Arc::clone(&job_runs);
+                async move {
+                    runs.fetch_add(1, Ordering::SeqCst);
+                }
+            },
+        )
+        .first_run_at(first_run)
+        .on_next_run(move |next_run| listener_runs.lock().unwrap().push(next_run));
+
+        let mut scheduler = Scheduler::new(CancellationToken::new());
+        scheduler.register(job);
+        scheduler.start_all();
+
+        time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        scheduler.stop_all().await;
+
+        let next_runs = next_runs.lock().unwrap();
+        assert_eq!(next_runs[0], first_run);
+        assert!(next_runs[1] >= first_run + Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn test_resume_and_next_runs() {
+        let interval = JobSchedule::Interval(Duration::from_secs(60));
+        let persisted = Instant::now() + Duration::from_secs(30);
+        let mut scheduler = Scheduler::new(CancellationToken::new());
+        scheduler.register(Job::new("resumed", interval.clone(), || async {}));
+        scheduler.register(
+            Job::new("delayed", interval, || async {})
+                .first_run_at(persisted + Duration::from_secs(5)),
+        );
+        assert!(scheduler.next_runs().is_empty());
+
+        scheduler.resume("resumed", persisted);
+        scheduler.resume("delayed", persisted);
+        scheduler.resume("unknown", persisted);
+        scheduler.start_all();
+        time::sleep(Duration::from_millis(20)).await;
+        scheduler.stop_all().await;
+
+        // A first run the job already had wins
+        let next_runs = scheduler.next_runs();
+        assert_eq!(
+            next_runs,
+            vec![
+                ("resumed".to_string(), persisted),
+                ("delayed".to_string(), persisted + Duration::from_secs(5)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_job_handle_pause_resume_and_run_now() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let job_runs = Arc::clone(&runs);
+        let job = Job::new(
+            "manual",
+            JobSchedule::Interval(Duration::from_secs(60)),
+            move || {
+                let runs = Arc::clone(&job_runs);
+                async move {
+                    runs.fetch_add(1, Ordering::SeqCst);
+                }
+            },
+        )
+        .first_run_at(Instant::now() + Duration::from_secs(60));
+
+        let mut scheduler = Scheduler::new(CancellationToken::new());
+        let handle = scheduler.register(job);
+        assert!(!handle.is_running());
+
+        handle.run_now().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        handle.start();
+        assert!(handle.is_running());
+        handle.stop().await;
+        assert!(!handle.is_running());
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        // The delayed first run only applies to the first start
+        handle.start();
+        time::sleep(Duration::from_millis(20)).await;
+        handle.stop().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_restarts_stalled_job() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let job_runs = Arc::clone(&runs);
+        let mut scheduler = Scheduler::new(CancellationToken::new());
+        let hanging = scheduler.register(Job::new(
+            "hanging",
+            JobSchedule::Interval(Duration::from_millis(20)),
+            move || {
+                let runs = Arc::clone(&job_runs);
+                async move {
+                    // Only the first run hangs, like a send that never returns
+                    if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                        std::future::pending::<()>().await;
+                    }
+                }
+            },
+        ));
+        let watchdog = scheduler.watchdog();
+        scheduler.start_all();
+        time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(hanging.health(Instant::now()), JobHealth::Healthy);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        let later = Instant::now() + 2 * MIN_CYCLE;
+        assert_eq!(
+            watchdog.check(later),
+            vec![("hanging".to_string(), JobHealth::Stalled)]
+        );
+        time::sleep(Duration::from_millis(50)).await;
+        assert!(runs.load(Ordering::SeqCst) >= 2);
+        assert_eq!(hanging.health(Instant::now()), JobHealth::Healthy);
+        hanging.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_detects_dead_job_loop() {
+        let mut scheduler = Scheduler::new(CancellationToken::new());
+        let dying = scheduler.register(
+            Job::new(
+                "dying",
+                JobSchedule::Interval(Duration::from_secs(60)),
+                || async {},
+            )
+            .on_next_run(|_| panic!("listener failed")),
+        );
+        dying.start();
+        time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(
+            dying.health(Instant::now()),
+            JobHealth::Died("listener failed".to_string())
+        );
+        // Taken out by the check, a loop that ended is not reported twice
+        assert_eq!(dying.health(Instant::now()), JobHealth::Healthy);
+        assert!(!dying.is_running());
+    }
+
+    #[tokio::test]
+    async fn test_reschedule_restarts_running_job() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let job_runs = Arc::clone(&runs);
+        let job = Job::new(
+            "rescheduled",
+            JobSchedule::Interval(Duration::from_secs(60)),
+            move || {
+                let runs = Arc::clone(&job_runs);
+                async move {
+                    runs.fetch_add(1, Ordering::SeqCst);
+                }
+            },
+        )
+        .first_run_at(Instant::now() + Duration::from_secs(60));
+
+        let mut scheduler = Scheduler::new(CancellationToken::new());
+        let handle = scheduler.register(job);
+        handle.start();
+
+        handle
+            .reschedule(JobSchedule::Interval(Duration::from_millis(30)))
+            .await;
+        assert!(handle.is_running());
+        assert_eq!(
+            handle.schedule(),
+            JobSchedule::Interval(Duration::from_millis(30))
+        );
+        // The new interval starts counting from the change
+        time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        time::sleep(Duration::from_millis(60)).await;
+        assert!(runs.load(Ordering::SeqCst) >= 1);
+        scheduler.stop_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_token_completes_running_job() {
+        let finished = Arc::new(AtomicUsize::new(0));
+        let job_finished = Arc::clone(&finished);
+        let job = Job::new(
+            "slow",
+            JobSchedule::Interval(Duration::from_secs(60)),
+            move || {
+                let finished = Arc::clone(&job_finished);
+                async move {
+                    time::sleep(Duration::from_millis(50)).await;
+                    finished.fetch_add(1, Ordering::SeqCst);
+                }
+            },
+        );
+
+        let shutdown = CancellationToken::new();
+        let mut scheduler = Scheduler::new(shutdown.clone());
+        scheduler.register(job);
+        scheduler.start_all();
+
+        // The first interval tick fires immediately, cancel while it runs
+        time::sleep(Duration::from_millis(10)).await;
+        shutdown.cancel();
+        scheduler.stop_all().await;
+
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_panicking_job_is_reported() {
+        let (reporter, mut reports) = ErrorReporter::new(Duration::from_secs(60));
+        let mut scheduler = Scheduler::new(CancellationToken::new());
+        scheduler.set_error_reporter(reporter);
+        let handle = scheduler.register(Job::new(
+            "broken",
+            JobSchedule::from_minutes(10),
+            || async { panic!("provider returned garbage") },
+        ));
+
+        handle.run_now().await;
+        // The job survives and can run again
+        handle.run_now().await;
+        let report = reports.try_recv().unwrap();
+        assert_eq!(report.source, "job broken");
+        assert_eq!(report.message, "provider returned garbage");
+        assert!(reports.try_recv().is_err());
+    }
 }
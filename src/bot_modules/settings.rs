@@ -0,0 +1,762 @@
+use super::outbound::Priority;
+use super::scheduler::{JobHandle, JobSchedule};
+use super::subscribers::{can_manage_chat, is_chat_admin, DeliveryMode, QuietHours};
+use super::{reply, Args, Module, ModuleContext, ParsedCommand, SubscriberManager, TelegramBot};
+use crate::domain::{get_all_currency_pairs, CurrencyPair};
+use crate::i18n::{tr, tr_args, Language};
+use async_trait::async_trait;
+use chrono::FixedOffset;
+use std::error::Error;
+use std::str::FromStr;
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+use teloxide::{ApiError, RequestError};
+
+/// Prefix of the settings menu's callback data, e.g. `settings:quiet:22-8`
+const CALLBACK_PREFIX: &str = "settings:";
+
+/// Quiet hours offered as buttons, `None` turns them off
+const QUIET_HOURS_PRESETS: [Option<QuietHours>; 4] = [
+    None,
+    Some(QuietHours { start: 22, end: 8 }),
+    Some(QuietHours { start: 23, end: 7 }),
+    Some(QuietHours { start: 0, end: 8 }),
+];
+
+/// Page of the settings menu
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Screen {
+    Main,
+    Language,
+    Pairs,
+    Quiet,
+    Delivery,
+}
+
+fn button(text: impl Into<String>, action: &str) -> InlineKeyboardButton {
+    InlineKeyboardButton::callback(text, format!("{}{}", CALLBACK_PREFIX, action))
+}
+
+/// Mark the currently selected option of a sub-menu
+fn option_label(selected: bool, label: &str) -> String {
+    format!("{} {}", if selected { "✅" } else { "▫️" }, label)
+}
+
+/// Callback data of a quiet hours preset
+fn quiet_action(quiet_hours: Option<QuietHours>) -> String {
+    match quiet_hours {
+        Some(quiet_hours) => format!("quiet:{}-{}", quiet_hours.start, quiet_hours.end),
+        None => "quiet:off".to_string(),
+    }
+}
+
+/// `/settings` shows and changes the chat's configuration through an inline
+/// keyboard, changes are stored with the subscriptions
+pub struct SettingsModule {
+    broadcast_job: Arc<JobHandle>,
+}
+
+impl SettingsModule {
+    /// Create a settings menu showing the current schedule of `broadcast_job`
+    pub fn new(broadcast_job: Arc<JobHandle>) -> Self {
+        Self { broadcast_job }
+    }
+
+    fn format_interval(&self, language: Language) -> String {
+        match &self.broadcast_job.schedule() {
+            JobSchedule::Interval(interval) => tr_args(
+                language,
+                "settings.interval_minutes",
+                &[("minutes", &(interval.as_secs() / 60))],
+            ),
+            JobSchedule::Cron(schedule) => tr_args(
+                language,
+                "settings.interval_cron",
+                &[("schedule", schedule)],
+            ),
+        }
+    }
+
+    fn format_switch(language: Language, enabled: bool) -> &'static str {
+        match enabled {
+            true => tr(language, "settings.on"),
+            false => tr(language, "settings.off"),
+        }
+    }
+
+    fn format_quiet(language: Language, quiet_hours: Option<QuietHours>) -> String {
+        match quiet_hours {
+            Some(quiet_hours) => quiet_hours.to_string(),
+            None => tr(language, "settings.quiet_off").to_string(),
+        }
+    }
+
+    fn format_default_pair(
+        manager: &SubscriberManager,
+        language: Language,
+        chat_id: ChatId,
+    ) -> String {
+        match manager.get_preferences(chat_id).default_pair {
+            Some(pair) => pair.to_string(),
+            None => tr(language, "settings.off").to_string(),
+        }
+    }
+
+    fn format_timezone(language: Language, timezone: Option<FixedOffset>) -> String {
+        match timezone {
+            Some(timezone) => format!("UTC{}", timezone),
+            None => tr(language, "settings.off").to_string(),
+        }
+    }
+
+    fn format_default_amount(language: Language, amount: Option<f64>) -> String {
+        match amount {
+            Some(amount) => amount.to_string(),
+            None => tr(language, "settings.off").to_string(),
+        }
+    }
+
+    /// Overview of all settings of the chat
+    fn format_settings(
+        &self,
+        manager: &SubscriberManager,
+        language: Language,
+        chat_id: ChatId,
+    ) -> String {
+        let preferences = manager.get_preferences(chat_id);
+        let pairs = manager
+            .get_pairs(chat_id)
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        tr_args(
+            language,
+            "settings.title",
+            &[
+                ("language", &tr(language, "language.name")),
+                ("pairs", &pairs),
+                (
+                    "default_pair",
+                    &Self::format_default_pair(manager, language, chat_id),
+                ),
+                (
+                    "timezone",
+                    &Self::format_timezone(language, preferences.timezone),
+                ),
+                (
+                    "amount",
+                    &Self::format_default_amount(language, preferences.default_amount),
+                ),
+                ("interval", &self.format_interval(language)),
+                (
+                    "quiet",
+                    &Self::format_quiet(language, manager.get_quiet_hours(chat_id)),
+                ),
+                ("delivery", &manager.get_delivery_mode(chat_id)),
+                (
+                    "summary",
+                    &Self::format_switch(language, manager.get_daily_summary(chat_id)),
+                ),
+                (
+                    "report",
+                    &Self::format_switch(language, manager.get_weekly_report(chat_id)),
+                ),
+            ],
+        )
+    }
+
+    fn screen_text(
+        &self,
+        manager: &SubscriberManager,
+        language: Language,
+        chat_id: ChatId,
+        screen: Screen,
+    ) -> String {
+        let key = match screen {
+            Screen::Main => return self.format_settings(manager, language, chat_id),
+            Screen::Language => "settings.choose.language",
+            Screen::Pairs => "settings.choose.pairs",
+            Screen::Quiet => "settings.choose.quiet",
+            Screen::Delivery => "settings.choose.delivery",
+        };
+        tr(language, key).to_string()
+    }
+
+    fn keyboard(
+        manager: &SubscriberManager,
+        language: Language,
+        chat_id: ChatId,
+        screen: Screen,
+    ) -> InlineKeyboardMarkup {
+        let mut rows: Vec<Vec<InlineKeyboardButton>> = match screen {
+            Screen::Main => {
+                return InlineKeyboardMarkup::new(vec![
+                    vec![
+                        button(tr(language, "settings.button.language"), "language"),
+                        button(tr(language, "settings.button.pairs"), "pairs"),
+                    ],
+                    vec![
+                        button(tr(language, "settings.button.quiet"), "quiet"),
+                        button(tr(language, "settings.button.delivery"), "delivery"),
+                    ],
+                    vec![
+                        button(
+                            option_label(
+                                manager.get_daily_summary(chat_id),
+                                tr(language, "settings.button.summary"),
+                            ),
+                            "summary",
+                        ),
+                        button(
+                            option_label(
+                                manager.get_weekly_report(chat_id),
+                                tr(language, "settings.button.report"),
+                            ),
+                            "report",
+                        ),
+                    ],
+                ]);
+            }
+            Screen::Language => Language::ALL
+                .into_iter()
+                .map(|option| {
+                    let label = option_label(option == language, tr(option, "language.name"));
+                    vec![button(label, &format!("language:{}", option.code()))]
+                })
+                .collect(),
+            Screen::Pairs => {
+                let pairs = manager.get_pairs(chat_id);
+                get_all_currency_pairs()
+                    .into_iter()
+                    .map(|pair| {
+                        let label = option_label(pairs.contains(&pair), &pair.to_string());
+                        vec![button(label, &format!("pair:{}", pair))]
+                    })
+                    .collect()
+            }
+            Screen::Quiet => {
+                let current = manager.get_quiet_hours(chat_id);
+                QUIET_HOURS_PRESETS
+                    .into_iter()
+                    .map(|preset| {
+                        let label =
+                            option_label(preset == current, &Self::format_quiet(language, preset));
+                        vec![button(label, &quiet_action(preset))]
+                    })
+                    .collect()
+            }
+            Screen::Delivery => {
+                let current = manager.get_delivery_mode(chat_id);
+                [DeliveryMode::Edit, DeliveryMode::New, DeliveryMode::Replace]
+                    .into_iter()
+                    .map(|mode| {
+                        let label = option_label(mode == current, &mode.to_string());
+                        vec![button(label, &format!("delivery:{}", mode))]
+                    })
+                    .collect()
+            }
+        };
+        rows.push(vec![button(tr(language, "settings.button.back"), "menu")]);
+        InlineKeyboardMarkup::new(rows)
+    }
+
+    /// Apply the menu `action` (callback data without the prefix), returning
+    /// the screen to show next and a notice key for the callback answer
+    fn apply_callback(
+        manager: &SubscriberManager,
+        chat_id: ChatId,
+        action: &str,
+    ) -> (Screen, Option<&'static str>) {
+        let (name, value) = match action.split_once(':') {
+            Some((name, value)) => (name, Some(value)),
+            None => (action, None),
+        };
+        match (name, value) {
+            ("language", None) => (Screen::Language, None),
+            ("language", Some(code)) => {
+                if let Some(language) = Language::from_code(code) {
+                    manager.languages().set(chat_id, language);
+                }
+                (Screen::Main, None)
+            }
+            ("pairs", None) => (Screen::Pairs, None),
+            ("pair", Some(pair)) => match CurrencyPair::from_str(pair) {
+                Ok(pair) => (Screen::Pairs, Self::toggle_pair(manager, chat_id, pair)),
+                Err(_) => (Screen::Pairs, None),
+            },
+            ("quiet", None) => (Screen::Quiet, None),
+            ("quiet", Some(quiet)) => {
+                manager.set_quiet_hours(chat_id, QuietHours::from_str(quiet).ok());
+                (Screen::Main, None)
+            }
+            ("delivery", None) => (Screen::Delivery, None),
+            ("delivery", Some(mode)) => {
+                if let Ok(mode) = DeliveryMode::from_str(mode) {
+                    manager.set_delivery_mode(chat_id, mode);
+                }
+                (Screen::Main, None)
+            }
+            ("summary", None) => {
+                let enabled = manager.get_daily_summary(chat_id);
+                manager.set_daily_summary(chat_id, !enabled);
+                (Screen::Main, None)
+            }
+            ("report", None) => {
+                let enabled = manager.get_weekly_report(chat_id);
+                manager.set_weekly_report(chat_id, !enabled);
+                (Screen::Main, None)
+            }
+            _ => (Screen::Main, None),
+        }
+    }
+
+    /// Include or exclude `pair` from the chat's periodic message, keeping at
+    /// least one pair
+    fn toggle_pair(
+        manager: &SubscriberManager,
+        chat_id: ChatId,
+        pair: CurrencyPair,
+    ) -> Option<&'static str> {
+        let mut pairs = manager.get_pairs(chat_id);
+        if pairs.contains(&pair) {
+            if pairs.len() == 1 {
+                return Some("settings.last_pair");
+            }
+            pairs.retain(|p| *p != pair);
+        } else {
+            pairs.push(pair);
+            // Keep the same order as the full list of pairs
+            let all_pairs = get_all_currency_pairs();
+            pairs.sort_by_key(|p| all_pairs.iter().position(|candidate| candidate == p));
+        }
+        manager.set_pairs(chat_id, pairs);
+        None
+    }
+
+    /// Handle `/settings delivery edit|new|replace`, `/settings quiet 22-8|off`,
+    /// `/settings summary on|off`, `/settings report on|off`,
+    /// `/settings default_pair <pair>|off`, `/settings timezone +03:00|off` and
+    /// `/settings default_amount <amount>|off`, `None` when the menu should be
+    /// shown instead
+    fn apply_text(
+        manager: &SubscriberManager,
+        language: Language,
+        chat_id: ChatId,
+        args: &str,
+    ) -> Option<String> {
+        let mut tokens = args.split_whitespace();
+        let response = match (tokens.next(), tokens.next()) {
+            (Some("delivery"), Some(mode)) => match DeliveryMode::from_str(mode) {
+                Ok(mode) => {
+                    manager.set_delivery_mode(chat_id, mode);
+                    tr_args(language, "settings.delivery_changed", &[("mode", &mode)])
+                }
+                Err(_) => tr_args(language, "settings.unknown_delivery", &[("mode", &mode)]),
+            },
+            (Some("quiet"), Some(quiet)) => {
+                let quiet_hours = QuietHours::from_str(quiet).ok();
+                if quiet_hours.is_none() && quiet != "off" {
+                    tr_args(language, "settings.unknown_quiet", &[("quiet", &quiet)])
+                } else {
+                    manager.set_quiet_hours(chat_id, quiet_hours);
+                    tr_args(
+                        language,
+                        "settings.quiet_changed",
+                        &[("quiet", &Self::format_quiet(language, quiet_hours))],
+                    )
+                }
+            }
+            (Some("default_pair"), Some(value)) => {
+                let default_pair = CurrencyPair::from_str(value).ok();
+                if default_pair.is_none() && value != "off" {
+                    return Some(tr_args(language, "unknown_pair", &[("pair", &value)]));
+                }
+                let mut preferences = manager.get_preferences(chat_id);
+                preferences.default_pair = default_pair;
+                manager.set_preferences(chat_id, preferences);
+                tr_args(
+                    language,
+                    "settings.default_pair_changed",
+                    &[(
+                        "pair",
+                        &Self::format_default_pair(manager, language, chat_id),
+                    )],
+                )
+            }
+            (Some("timezone"), Some(value)) => {
+                let timezone = value.parse::<FixedOffset>().ok();
+                if timezone.is_none() && value != "off" {
+                    return Some(tr_args(
+                        language,
+                        "settings.unknown_timezone",
+                        &[("timezone", &value)],
+                    ));
+                }
+                let mut preferences = manager.get_preferences(chat_id);
+                preferences.timezone = timezone;
+                manager.set_preferences(chat_id, preferences);
+                tr_args(
+                    language,
+                    "settings.timezone_changed",
+                    &[("timezone", &Self::format_timezone(language, timezone))],
+                )
+            }
+            (Some("default_amount"), Some(value)) => {
+                let amount = match value {
+                    "off" => None,
+                    _ => match Args::new(value).amount() {
+                        Ok(amount) => Some(amount),
+                        Err(error) => return Some(error.message(language)),
+                    },
+                };
+                let mut preferences = manager.get_preferences(chat_id);
+                preferences.default_amount = amount;
+                manager.set_preferences(chat_id, preferences);
+                tr_args(
+                    language,
+                    "settings.default_amount_changed",
+                    &[("amount", &Self::format_default_amount(language, amount))],
+                )
+            }
+            (Some(setting @ ("summary" | "report")), Some(value)) => {
+                let enabled = match value {
+                    "on" => true,
+                    "off" => false,
+                    _ => {
+                        return Some(tr_args(
+                            language,
+                            "settings.unknown_switch",
+                            &[("setting", &setting), ("value", &value)],
+                        ))
+                    }
+                };
+                let key = match setting {
+                    "summary" => {
+                        manager.set_daily_summary(chat_id, enabled);
+                        "settings.summary_changed"
+                    }
+                    _ => {
+                        manager.set_weekly_report(chat_id, enabled);
+                        "settings.report_changed"
+                    }
+                };
+                tr_args(
+                    language,
+                    key,
+                    &[("value", &Self::format_switch(language, enabled))],
+                )
+            }
+            _ => return None,
+        };
+        Some(response)
+    }
+}
+
+#[async_trait]
+impl Module for SettingsModule {
+    fn name(&self) -> &str {
+        "Settings"
+    }
+
+    fn commands(&self) -> Vec<&str> {
+        vec!["/settings"]
+    }
+
+    fn command_descriptions(&self, language: Language) -> Vec<(&str, &str)> {
+        vec![("/settings", tr(language, "settings.menu"))]
+    }
+
+    fn usage(&self, language: Language) -> Vec<String> {
+        vec![tr(language, "settings.usage").to_string()]
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let Some(command) = ParsedCommand::from_message(&msg) else {
+            return Ok(());
+        };
+        let chat_id = msg.chat.id;
+        let language = ctx.languages.get(chat_id);
+
+        if !can_manage_chat(&bot, &msg).await? {
+            ctx.send_reply(
+                &bot,
+                &msg,
+                reply(&bot, &msg, tr(language, "subs.group_admins_only")),
+            )
+            .await?;
+            return Ok(());
+        }
+        match Self::apply_text(&ctx.manager, language, chat_id, command.args) {
+            Some(response) => {
+                ctx.send_reply(&bot, &msg, reply(&bot, &msg, response))
+                    .await?;
+            }
+            None => {
+                ctx.send_reply(
+                    &bot,
+                    &msg,
+                    reply(
+                        &bot,
+                        &msg,
+                        self.screen_text(&ctx.manager, language, chat_id, Screen::Main),
+                    )
+                    .reply_markup(Self::keyboard(
+                        &ctx.manager,
+                        language,
+                        chat_id,
+                        Screen::Main,
+                    )),
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn callback_prefix(&self) -> Option<&str> {
+        Some(CALLBACK_PREFIX)
+    }
+
+    async fn handle_callback(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        query: CallbackQuery,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let Some(message) = query.message.as_ref() else {
+            bot.answer_callback_query(query.id).await?;
+            return Ok(());
+        };
+        let chat_id = message.chat().id;
+
+        if !is_chat_admin(&bot, message.chat(), query.from.id).await? {
+            let language = ctx.languages.get(chat_id);
+            bot.answer_callback_query(query.id)
+                .text(tr(language, "subs.group_admins_only"))
+                .await?;
+            return Ok(());
+        }
+
+        let action = query
+            .data
+            .as_deref()
+            .and_then(|data| data.strip_prefix(CALLBACK_PREFIX))
+            .unwrap_or_default();
+        let (screen, notice) = Self::apply_callback(&ctx.manager, chat_id, action);
+        // Read after applying so a language change redraws the menu in it
+        let language = ctx.languages.get(chat_id);
+
+        let text = self.screen_text(&ctx.manager, language, chat_id, screen);
+        let keyboard = Self::keyboard(&ctx.manager, language, chat_id, screen);
+        let edit = ctx
+            .outbound
+            .send(Priority::Reply, chat_id, || {
+                bot.edit_message_text(chat_id, message.id(), &text)
+                    .reply_markup(keyboard.clone())
+                    .send()
+            })
+            .await;
+        match edit {
+            Ok(_) | Err(RequestError::Api(ApiError::MessageNotModified)) => {}
+            Err(e) => tracing::warn!("Failed to update settings menu in {}: {}", chat_id, e),
+        }
+
+        let answer = bot.answer_callback_query(query.id);
+        match notice {
+            Some(key) => answer.text(tr(language, key)).await?,
+            None => answer.await?,
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot_modules::scheduler::{Job, Scheduler};
+    use tokio_util::sync::CancellationToken;
+
+    fn settings_module() -> (SettingsModule, Arc<SubscriberManager>) {
+        let manager = Arc::new(SubscriberManager::new("Test message".to_string()));
+        let broadcast_job = Scheduler::new(CancellationToken::new()).register(Job::new(
+            "broadcast",
+            JobSchedule::from_minutes(10),
+            || async {},
+        ));
+        let module = SettingsModule::new(broadcast_job);
+        (module, manager)
+    }
+
+    #[test]
+    fn test_text_settings() {
+        let (module, manager) = settings_module();
+        let chat_id = ChatId(12345);
+        assert_eq!(manager.get_delivery_mode(chat_id), DeliveryMode::Edit);
+
+        let response = SettingsModule::apply_text(&manager, Language::Ru, chat_id, "delivery new");
+        assert!(response.unwrap().contains("изменён"));
+        assert_eq!(manager.get_delivery_mode(chat_id), DeliveryMode::New);
+        let response =
+            SettingsModule::apply_text(&manager, Language::Ru, chat_id, "delivery sometimes");
+        assert!(response.unwrap().starts_with("❌"));
+        assert_eq!(manager.get_delivery_mode(chat_id), DeliveryMode::New);
+        let response =
+            SettingsModule::apply_text(&manager, Language::En, chat_id, "delivery replace");
+        assert_eq!(response.unwrap(), "Delivery mode changed: replace");
+        assert_eq!(manager.get_delivery_mode(chat_id), DeliveryMode::Replace);
+        SettingsModule::apply_text(&manager, Language::Ru, chat_id, "delivery new");
+
+        SettingsModule::apply_text(&manager, Language::Ru, chat_id, "quiet 22-8");
+        assert_eq!(
+            manager.get_quiet_hours(chat_id),
+            QuietHours::from_str("22-8").ok()
+        );
+        let response = SettingsModule::apply_text(&manager, Language::Ru, chat_id, "quiet night");
+        assert!(response.unwrap().starts_with("❌"));
+        SettingsModule::apply_text(&manager, Language::Ru, chat_id, "quiet off");
+        assert_eq!(manager.get_quiet_hours(chat_id), None);
+
+        let response = SettingsModule::apply_text(&manager, Language::En, chat_id, "summary on");
+        assert_eq!(response.unwrap(), "Daily summary: on");
+        assert!(manager.get_daily_summary(chat_id));
+        let response = SettingsModule::apply_text(&manager, Language::En, chat_id, "summary daily");
+        assert!(response.unwrap().starts_with("❌"));
+        assert!(manager.get_daily_summary(chat_id));
+        let response = SettingsModule::apply_text(&manager, Language::En, chat_id, "report on");
+        assert_eq!(response.unwrap(), "Weekly report: on");
+        assert!(manager.get_weekly_report(chat_id));
+        let response = SettingsModule::apply_text(&manager, Language::En, chat_id, "report weekly");
+        assert_eq!(
+            response.unwrap(),
+            "❌ Unknown value: weekly\nExample: /settings report on or /settings report off"
+        );
+
+        let response =
+            SettingsModule::apply_text(&manager, Language::En, chat_id, "default_pair USDTe/RUB");
+        assert_eq!(response.unwrap(), "Default pair: USDTe/RUB");
+        assert_eq!(
+            manager.profile(chat_id).default_pair,
+            Some(CurrencyPair::USDTe2RUB)
+        );
+        let response =
+            SettingsModule::apply_text(&manager, Language::En, chat_id, "default_pair EUR/RUB");
+        assert_eq!(response.unwrap(), "❌ Unknown pair: EUR/RUB");
+        assert!(module
+            .format_settings(&manager, Language::Ru, chat_id)
+            .contains("Пара по умолчанию: USDTe/RUB"));
+        let response =
+            SettingsModule::apply_text(&manager, Language::En, chat_id, "default_pair off");
+        assert_eq!(response.unwrap(), "Default pair: off");
+        assert!(manager.get_preferences(chat_id).is_empty());
+
+        let response =
+            SettingsModule::apply_text(&manager, Language::En, chat_id, "timezone +05:00");
+        assert_eq!(response.unwrap(), "Time zone: UTC+05:00");
+        assert_eq!(manager.profile(chat_id).timezone, "+05:00".parse().ok());
+        let response = SettingsModule::apply_text(&manager, Language::En, chat_id, "timezone MSK");
+        assert!(response.unwrap().starts_with("❌ Unknown time zone: MSK"));
+        let response =
+            SettingsModule::apply_text(&manager, Language::En, chat_id, "default_amount 1000,5");
+        assert_eq!(response.unwrap(), "Default amount: 1000.5");
+        assert_eq!(manager.profile(chat_id).default_amount, Some(1000.5));
+        let response =
+            SettingsModule::apply_text(&manager, Language::En, chat_id, "default_amount -5");
+        assert!(response.unwrap().contains("-5"));
+        let overview = module.format_settings(&manager, Language::Ru, chat_id);
+        assert!(overview.contains("Часовой пояс: UTC+05:00"));
+        assert!(overview.contains("Сумма по умолчанию: 1000.5"));
+        SettingsModule::apply_text(&manager, Language::En, chat_id, "timezone off");
+        SettingsModule::apply_text(&manager, Language::En, chat_id, "default_amount off");
+        assert!(manager.get_preferences(chat_id).is_empty());
+
+        assert!(SettingsModule::apply_text(&manager, Language::Ru, chat_id, "").is_none());
+        let overview = module.format_settings(&manager, Language::Ru, chat_id);
+        assert!(overview.contains("Доставка: new"));
+        assert!(overview.contains("Итоги дня: вкл"));
+        assert!(overview.contains("Недельный отчёт: вкл"));
+        assert!(overview.contains("Тихие часы: выкл"));
+        assert!(overview.contains("каждые 10 мин"));
+    }
+
+    #[test]
+    fn test_menu_navigation() {
+        let (_, manager) = settings_module();
+        let chat_id = ChatId(12345);
+
+        assert_eq!(
+            SettingsModule::apply_callback(&manager, chat_id, "quiet"),
+            (Screen::Quiet, None)
+        );
+        assert_eq!(
+            SettingsModule::apply_callback(&manager, chat_id, "quiet:23-7"),
+            (Screen::Main, None)
+        );
+        assert_eq!(
+            manager.get_quiet_hours(chat_id),
+            QuietHours::from_str("23-7").ok()
+        );
+
+        SettingsModule::apply_callback(&manager, chat_id, "language:en");
+        assert_eq!(manager.languages().get(chat_id), Language::En);
+        SettingsModule::apply_callback(&manager, chat_id, "delivery:new");
+        assert_eq!(manager.get_delivery_mode(chat_id), DeliveryMode::New);
+        SettingsModule::apply_callback(&manager, chat_id, "summary");
+        assert!(manager.get_daily_summary(chat_id));
+        SettingsModule::apply_callback(&manager, chat_id, "summary");
+        assert!(!manager.get_daily_summary(chat_id));
+        SettingsModule::apply_callback(&manager, chat_id, "report");
+        assert!(manager.get_weekly_report(chat_id));
+        assert_eq!(
+            SettingsModule::apply_callback(&manager, chat_id, "unknown"),
+            (Screen::Main, None)
+        );
+    }
+
+    #[test]
+    fn test_toggle_pairs_keeps_one() {
+        let (_, manager) = settings_module();
+        let chat_id = ChatId(12345);
+        manager.set_pairs(chat_id, vec![CurrencyPair::USD2RUB]);
+
+        assert_eq!(
+            SettingsModule::apply_callback(&manager, chat_id, "pair:USD/RUB"),
+            (Screen::Pairs, Some("settings.last_pair"))
+        );
+        SettingsModule::apply_callback(&manager, chat_id, "pair:USDCe/RUB");
+        assert_eq!(
+            manager.get_pairs(chat_id),
+            vec![CurrencyPair::USDCe2RUB, CurrencyPair::USD2RUB]
+        );
+        SettingsModule::apply_callback(&manager, chat_id, "pair:USD/RUB");
+        assert_eq!(manager.get_pairs(chat_id), vec![CurrencyPair::USDCe2RUB]);
+    }
+
+    #[test]
+    fn test_keyboard_callback_data() {
+        let (_, manager) = settings_module();
+        for screen in [
+            Screen::Main,
+            Screen::Language,
+            Screen::Pairs,
+            Screen::Quiet,
+            Screen::Delivery,
+        ] {
+            let keyboard = SettingsModule::keyboard(&manager, Language::Ru, ChatId(1), screen);
+            for button in keyboard.inline_keyboard.iter().flatten() {
+                let teloxide::types::InlineKeyboardButtonKind::CallbackData(data) = &button.kind
+                else {
+                    panic!("Settings buttons only carry callback data");
+                };
+                // Telegram limits callback data to 64 bytes
+                assert!(data.starts_with(CALLBACK_PREFIX) && data.len() <= 64);
+            }
+        }
+    }
+}
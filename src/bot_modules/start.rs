@@ -1,20 +1,104 @@
-use super::Module;
+use super::subscribers::{can_manage_chat, send_first_message};
+use super::{reply, topic_of, Module, ModuleContext, ParsedCommand, TelegramBot};
+use crate::domain::{get_all_currency_pairs, CurrencyPair};
+use crate::i18n::{tr, tr_args, Language};
 use async_trait::async_trait;
 use std::error::Error;
 use teloxide::prelude::*;
 
-const GREETING: &str =
-    "Добро пожаловать в Currency Bot!\nИспользуйте /echo <текст> для эхо-ответа.";
+/// Longest deep link payload Telegram passes to `/start`
+const MAX_PAYLOAD_LEN: usize = 64;
 
+/// What a `t.me/<bot>?start=<payload>` link asks for, applied by `/start`
+#[derive(Debug, Clone, PartialEq)]
+enum DeepLink {
+    /// `sub_USDRUB` or `sub_USDRUB_USDTeRUB`: subscribe to the periodic
+    /// message of these pairs
+    Subscribe(Vec<CurrencyPair>),
+    /// `pair_USDRUB`: make the pair the one a bare `/price` quotes
+    DefaultPair(CurrencyPair),
+    /// `lang_en`: answer in this language
+    Language(Language),
+}
+
+impl DeepLink {
+    /// Parse a payload, `None` for a malformed or unknown one. Telegram only
+    /// allows `A-Z`, `a-z`, `0-9`, `_` and `-` in payloads, so pairs are
+    /// written without the slash.
+    fn parse(payload: &str) -> Option<Self> {
+        let valid = payload.len() <= MAX_PAYLOAD_LEN
+            && payload
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return None;
+        }
+        let (kind, value) = payload.split_once('_')?;
+        match kind {
+            "sub" => {
+                let pairs = value
+                    .split('_')
+                    .map(link_pair)
+                    .collect::<Option<Vec<_>>>()?;
+                Some(DeepLink::Subscribe(pairs))
+            }
+            "pair" => link_pair(value).map(DeepLink::DefaultPair),
+            "lang" => Language::from_code(value).map(DeepLink::Language),
+            _ => None,
+        }
+    }
+}
+
+/// Pair written without its slash and in any case, e.g. `USDRUB` or `usdterub`
+fn link_pair(code: &str) -> Option<CurrencyPair> {
+    get_all_currency_pairs()
+        .into_iter()
+        .find(|pair| pair.to_string().replace('/', "").eq_ignore_ascii_case(code))
+}
+
+/// `/start` greets the user and applies the deep link it was opened with,
+/// so channels can share one-tap subscribe links
 pub struct StartModule;
 
 impl StartModule {
-    pub fn new() -> Self {
-        Self
+    pub fn greeting(language: Language) -> &'static str {
+        tr(language, "start.greeting")
     }
 
-    pub fn greeting() -> &'static str {
-        GREETING
+    /// Apply `link` to the chat, returning the language to answer in and the
+    /// confirmation shown below the greeting
+    fn apply(ctx: &ModuleContext, chat_id: ChatId, link: &DeepLink) -> (Language, String) {
+        let manager = &ctx.manager;
+        let language = ctx.languages.get(chat_id);
+        match link {
+            DeepLink::Subscribe(pairs) => {
+                manager.set_pairs(chat_id, pairs.clone());
+                manager.subscribe(chat_id);
+                let pairs = pairs
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let text = tr_args(language, "start.subscribed", &[("pairs", &pairs)]);
+                (language, text)
+            }
+            DeepLink::DefaultPair(pair) => {
+                let mut preferences = manager.get_preferences(chat_id);
+                preferences.default_pair = Some(pair.clone());
+                manager.set_preferences(chat_id, preferences);
+                let text = tr_args(language, "settings.default_pair_changed", &[("pair", pair)]);
+                (language, text)
+            }
+            DeepLink::Language(language) => {
+                ctx.languages.set(chat_id, *language);
+                let text = tr_args(
+                    *language,
+                    "language.changed",
+                    &[("language", &tr(*language, "language.name"))],
+                );
+                (*language, text)
+            }
+        }
     }
 }
 
@@ -28,8 +112,61 @@ impl Module for StartModule {
         vec!["/start"]
     }
 
-    async fn handle(&self, bot: Bot, msg: Message) -> Result<(), Box<dyn Error + Send + Sync>> {
-        bot.send_message(msg.chat.id, Self::greeting()).await?;
+    fn command_descriptions(&self, language: Language) -> Vec<(&str, &str)> {
+        vec![("/start", tr(language, "start.menu"))]
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let chat_id = msg.chat.id;
+        let language = ctx.languages.get(chat_id);
+        let payload = ParsedCommand::from_message(&msg)
+            .map(|command| command.args)
+            .unwrap_or_default();
+        if payload.is_empty() {
+            ctx.send_reply(&bot, &msg, reply(&bot, &msg, Self::greeting(language)))
+                .await?;
+            return Ok(());
+        }
+        let Some(link) = DeepLink::parse(payload) else {
+            tracing::debug!(
+                "Ignoring unknown /start payload {:?} in {}",
+                payload,
+                chat_id
+            );
+            let response = format!(
+                "{}\n\n{}",
+                Self::greeting(language),
+                tr(language, "start.unknown_link")
+            );
+            ctx.send_reply(&bot, &msg, reply(&bot, &msg, response))
+                .await?;
+            return Ok(());
+        };
+        if !can_manage_chat(&bot, &msg).await? {
+            ctx.send_reply(
+                &bot,
+                &msg,
+                reply(&bot, &msg, tr(language, "subs.group_admins_only")),
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let newly_subscribed =
+            matches!(link, DeepLink::Subscribe(_)) && !ctx.manager.is_subscribed(chat_id);
+        let (language, confirmation) = Self::apply(ctx, chat_id, &link);
+        let response = format!("{}\n\n{}", Self::greeting(language), confirmation);
+        ctx.send_reply(&bot, &msg, reply(&bot, &msg, response))
+            .await?;
+        if newly_subscribed {
+            ctx.manager.set_thread_id(chat_id, topic_of(&msg));
+            send_first_message(ctx, &bot, chat_id).await?;
+        }
         Ok(())
     }
 }
@@ -40,21 +177,88 @@ mod tests {
 
     #[test]
     fn test_module_name() {
-        let module = StartModule::new();
+        let module = StartModule;
         assert_eq!(module.name(), "Start");
     }
 
     #[test]
     fn test_module_commands() {
-        let module = StartModule::new();
+        let module = StartModule;
         assert_eq!(module.commands(), vec!["/start"]);
     }
 
     #[test]
     fn test_greeting() {
         assert_eq!(
-            StartModule::greeting(),
+            StartModule::greeting(Language::Ru),
             "Добро пожаловать в Currency Bot!\nИспользуйте /echo <текст> для эхо-ответа."
         );
+        assert!(StartModule::greeting(Language::En).starts_with("Welcome"));
+    }
+
+    #[test]
+    fn test_parse_deep_link() {
+        assert_eq!(
+            DeepLink::parse("sub_USDRUB"),
+            Some(DeepLink::Subscribe(vec![CurrencyPair::USD2RUB]))
+        );
+        assert_eq!(
+            DeepLink::parse("sub_usdrub_USDTeRUB"),
+            Some(DeepLink::Subscribe(vec![
+                CurrencyPair::USD2RUB,
+                CurrencyPair::USDTe2RUB
+            ]))
+        );
+        assert_eq!(
+            DeepLink::parse("pair_USDCeRUB"),
+            Some(DeepLink::DefaultPair(CurrencyPair::USDCe2RUB))
+        );
+        assert_eq!(
+            DeepLink::parse("lang_en"),
+            Some(DeepLink::Language(Language::En))
+        );
+        for payload in [
+            "",
+            "sub",
+            "sub_",
+            "sub_EURRUB",
+            "sub_USD/RUB",
+            "lang_de",
+            "ref_42",
+        ] {
+            assert_eq!(DeepLink::parse(payload), None, "{}", payload);
+        }
+        assert_eq!(
+            DeepLink::parse(&format!("sub_{}", "USDRUB_".repeat(10))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_apply_deep_link() {
+        let ctx = ModuleContext::default();
+        let chat_id = ChatId(42);
+        let (language, text) = StartModule::apply(
+            &ctx,
+            chat_id,
+            &DeepLink::Subscribe(vec![CurrencyPair::USD2RUB]),
+        );
+        assert_eq!(language, Language::Ru);
+        assert_eq!(text, "✅ Вы подписаны на рассылку курсов: USD/RUB");
+        assert!(ctx.manager.is_subscribed(chat_id));
+        assert_eq!(ctx.manager.get_pairs(chat_id), [CurrencyPair::USD2RUB]);
+
+        let (language, text) = StartModule::apply(&ctx, chat_id, &DeepLink::Language(Language::En));
+        assert_eq!(language, Language::En);
+        assert_eq!(text, "Language changed: English");
+        StartModule::apply(
+            &ctx,
+            chat_id,
+            &DeepLink::DefaultPair(CurrencyPair::USDTe2RUB),
+        );
+        assert_eq!(
+            ctx.manager.profile(chat_id).default_pair,
+            Some(CurrencyPair::USDTe2RUB)
+        );
     }
 }
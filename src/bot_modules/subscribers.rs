@@ -1,11 +1,29 @@
-use super::Module;
+use super::alerts::{AlertEngine, AlertRecord};
+use super::command::parse_pairs;
+use super::format::{MessageFormat, PriceFormat, PriceFormats};
+use super::outbound::{OutboundQueue, Priority};
+use super::profile::{Preferences, UserProfile};
+use super::template::{self, TemplateValues};
+use super::{reply, topic_of, Module, ModuleContext, ModuleSwitches, ParsedCommand, TelegramBot};
+use crate::domain::{get_all_currency_pairs, CurrencyPair};
+use crate::i18n::{tr, tr_args, Language, Languages};
+use crate::price_service::history::{PriceChange, PriceHistory};
+use crate::price_service::PriceService;
 use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset, Local, Timelike, Utc};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use teloxide::prelude::*;
-use teloxide::types::MessageId;
+use teloxide::requests::HasPayload;
+use teloxide::types::{InputFile, MessageId, ThreadId};
+use teloxide::{ApiError, RequestError};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum SubscriptionAction {
@@ -15,13 +33,484 @@ pub enum SubscriptionAction {
     NotSubscribed,
 }
 
+/// How the periodic message reaches a chat
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryMode {
+    /// Edit one anchor message in place, re-creating it if it was deleted
+    #[default]
+    Edit,
+    /// Send a new message every time
+    New,
+    /// Send a new message every time and delete the previous one
+    Replace,
+}
+
+impl FromStr for DeliveryMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.trim().to_lowercase().as_str() {
+            "edit" => Ok(DeliveryMode::Edit),
+            "new" => Ok(DeliveryMode::New),
+            "replace" => Ok(DeliveryMode::Replace),
+            _ => Err(format!("'{}' is not edit, new or replace", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for DeliveryMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeliveryMode::Edit => write!(f, "edit"),
+            DeliveryMode::New => write!(f, "new"),
+            DeliveryMode::Replace => write!(f, "replace"),
+        }
+    }
+}
+
+/// Hours of local time during which a chat gets no periodic messages, e.g.
+/// `22-8` from 22:00 until 08:00
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl FromStr for QuietHours {
+    type Err = String;
+
+    /// Parse `22-8`, hours must be different and below 24
+    fn from_str(s: &str) -> Result<Self, String> {
+        let hours = || {
+            let (start, end) = s.trim().split_once('-')?;
+            let start: u32 = start.trim().parse().ok()?;
+            let end: u32 = end.trim().parse().ok()?;
+            (start < 24 && end < 24 && start != end).then_some(Self { start, end })
+        };
+        hours().ok_or_else(|| format!("'{}' is not quiet hours like 22-8", s))
+    }
+}
+
+impl QuietHours {
+    /// Whether `hour` falls into the quiet hours, which may wrap midnight
+    pub fn contains(&self, hour: u32) -> bool {
+        if self.start < self.end {
+            (self.start..self.end).contains(&hour)
+        } else {
+            hour >= self.start || hour < self.end
+        }
+    }
+}
+
+impl std::fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02}:00–{:02}:00", self.start, self.end)
+    }
+}
+
+/// The anchor message is gone or can no longer be edited, so a new one has
+/// to be sent instead
+pub fn is_edit_target_lost(error: &RequestError) -> bool {
+    matches!(
+        error,
+        RequestError::Api(
+            ApiError::MessageToEditNotFound
+                | ApiError::MessageCantBeEdited
+                | ApiError::MessageIdInvalid
+        )
+    )
+}
+
+/// Latest known price per currency pair
+pub type Quotes = HashMap<CurrencyPair, f64>;
+
+/// Sparklines of the recent prices by pair, see [`PriceHistory::sparkline`]
+pub type Sparklines = HashMap<CurrencyPair, String>;
+
+/// Period `{change_24h}` covers
+const CHANGE_PERIOD: chrono::Duration = chrono::Duration::hours(24);
+
+/// What the quotes of a message are shown against, by pair
+#[derive(Debug, Clone, Copy)]
+pub struct Trends<'a> {
+    /// Quotes of the previous message, for `{delta}`
+    pub previous: Option<&'a Quotes>,
+    /// Quotes of [`CHANGE_PERIOD`] before, for `{change_24h}`, see [`day_ago_quotes`]
+    pub day_ago: &'a Quotes,
+    pub sparklines: &'a Sparklines,
+}
+
+/// Oldest recorded quote of each of `pairs` within the last 24 hours, the
+/// reference of `{change_24h}`
+pub fn day_ago_quotes(history: &PriceHistory, pairs: &[CurrencyPair]) -> Quotes {
+    let since = Local::now() - CHANGE_PERIOD;
+    pairs
+        .iter()
+        .filter_map(|pair| Some((pair.clone(), history.ohlc(pair, since)?.open)))
+        .collect()
+}
+
+/// Line rendered for every subscribed pair when the chat has no custom template
+pub const DEFAULT_PRICE_LINE: &str = "💰 {pair}: {price} {delta} {sparkline}";
+
+/// Periodic messages waiting for the outbound queue at once during a broadcast
+const BROADCAST_CONCURRENCY: usize = 20;
+
+/// How often a broadcast in progress writes its outbox entry to the state file
+const OUTBOX_SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Run `send` for every item with at most `concurrency` calls in flight, the
+/// pacing is left to the [`OutboundQueue`] the calls send through
+async fn send_concurrently<T, F, Fut>(
+    items: Vec<T>,
+    concurrency: usize,
+    send: F,
+) -> Vec<Fut::Output>
+where
+    F: Fn(T) -> Fut,
+    Fut: std::future::Future,
+{
+    stream::iter(items)
+        .map(send)
+        .buffer_unordered(concurrency)
+        .collect()
+        .await
+}
+
+/// Stable offset of the chat's periodic send within the jitter `window`, so
+/// a chat keeps receiving its message at the same point of every broadcast
+fn jitter_offset(chat_id: ChatId, window: Duration) -> Duration {
+    if window.is_zero() {
+        return Duration::ZERO;
+    }
+    let mut hasher = std::hash::DefaultHasher::new();
+    chat_id.hash(&mut hasher);
+    Duration::from_millis(hasher.finish() % window.as_millis().max(1) as u64)
+}
+
+/// Unguessable token of a calendar feed link, 16 bytes of the OS random source
+fn new_calendar_token() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("the OS random source is unavailable");
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Delivery attempts per chat within one broadcast before it is dead-lettered
+const MAX_DELIVERY_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry pass of a broadcast, doubled for every further pass
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+
+/// Backoff before retry pass number `attempt` (starting at 1)
+fn retry_delay(attempt: u32) -> Duration {
+    RETRY_BASE_DELAY * 2u32.pow(attempt.saturating_sub(1))
+}
+
+/// Network-level failures are worth retrying, API errors (bot blocked,
+/// message deleted, ...) will fail the same way again
+fn is_transient(error: &RequestError) -> bool {
+    matches!(
+        error,
+        RequestError::Network(_) | RequestError::RetryAfter(_) | RequestError::Io(_)
+    )
+}
+
+/// Chat whose periodic message could not be delivered
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub chat_id: ChatId,
+    pub attempts: u32,
+    pub error: String,
+    pub failed_at: DateTime<Local>,
+}
+
+/// One-off broadcast in the outbox of the state file: written before the
+/// first send and trimmed as its chats are delivered, so a restart in the
+/// middle resumes with the chats still pending
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub id: u64,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+    /// Chats the text wasn't sent to yet
+    pub pending: Vec<ChatId>,
+}
+
+/// Render `template` once per pair, or once if it has no per-pair
+/// placeholders, with deltas and changes against the quotes of `trends` and
+/// the prices written in the pair's format
+pub fn render_quotes(
+    template: &str,
+    pairs: Vec<CurrencyPair>,
+    quotes: &Quotes,
+    trends: Trends,
+    format: MessageFormat,
+    price_formats: &PriceFormats,
+) -> String {
+    let time = Local::now();
+    if !template::has_pair_placeholders(template) {
+        let values = TemplateValues {
+            pair: None,
+            price: None,
+            delta: None,
+            change_24h: None,
+            sparkline: None,
+            time,
+        };
+        // There is no price to write
+        return template::render(template, &values, format, &PriceFormat::default());
+    }
+
+    pairs
+        .into_iter()
+        .map(|pair| {
+            let price = quotes.get(&pair).copied();
+            let delta = price
+                .zip(trends.previous.and_then(|prev| prev.get(&pair)).copied())
+                .and_then(|(current, prev)| PriceChange::between(prev, current));
+            let change_24h = price
+                .zip(trends.day_ago.get(&pair).copied())
+                .and_then(|(current, day_ago)| PriceChange::between(day_ago, current))
+                .map(|change| change.percent);
+            let price_format = price_formats.get(&pair);
+            let values = TemplateValues {
+                sparkline: trends.sparklines.get(&pair).cloned(),
+                pair: Some(pair),
+                price,
+                delta,
+                change_24h,
+                time,
+            };
+            template::render(template, &values, format, price_format)
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Footer of a price message rendered from `template` (`display.footer`):
+/// `{source}` the providers of the latest quotes of `pairs`, `{time}` the
+/// time of the latest of them at the UTC offset `timezone`, the server's one
+/// when `None`
+pub fn render_footer(
+    template: &str,
+    price_service: &PriceService,
+    pairs: &[CurrencyPair],
+    format: MessageFormat,
+    timezone: Option<FixedOffset>,
+) -> String {
+    let mut sources: Vec<String> = Vec::new();
+    for source in pairs.iter().filter_map(|pair| price_service.source(pair)) {
+        if !sources.contains(&source) {
+            sources.push(source);
+        }
+    }
+    let time = pairs
+        .iter()
+        .filter_map(|pair| price_service.history().latest(pair))
+        .map(|point| point.time)
+        .max()
+        .unwrap_or_else(Local::now);
+    let time = match timezone {
+        Some(timezone) => time.with_timezone(&timezone).format("%d.%m.%Y %H:%M"),
+        None => time.format("%d.%m.%Y %H:%M"),
+    };
+    let sources = match sources.is_empty() {
+        true => "—".to_string(),
+        false => sources.join(", "),
+    };
+    format.render(
+        template,
+        &[
+            ("source", format.escape(&sources)),
+            ("time", format.escape(&time.to_string())),
+        ],
+    )
+}
+
+/// Fetch current prices for `pairs`, skipping the ones no provider could serve
+pub async fn fetch_quotes(price_service: &PriceService, pairs: &[CurrencyPair]) -> Quotes {
+    let mut quotes = Quotes::new();
+    for (pair, result) in pairs.iter().zip(price_service.get_prices(pairs).await) {
+        match result {
+            Ok(price_data) => {
+                quotes.insert(pair.clone(), price_data.price);
+            }
+            Err(e) => tracing::warn!("Failed to fetch {} for periodic message: {}", pair, e),
+        }
+    }
+    quotes
+}
+
+/// Version of the subscriptions snapshot format
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// Upgrades of older snapshots, the entry `n - 1` turns version `n` into `n + 1`
+const SNAPSHOT_MIGRATIONS: [fn(&mut serde_json::Value); SNAPSHOT_VERSION as usize - 1] =
+    [migrate_alert_state];
+
+/// Version 2 replaced the `triggered` flag of alert rules with their `state`
+fn migrate_alert_state(snapshot: &mut serde_json::Value) {
+    let records = snapshot
+        .get_mut("alerts")
+        .and_then(serde_json::Value::as_array_mut);
+    for record in records.into_iter().flatten() {
+        let rules = record
+            .get_mut("rules")
+            .and_then(serde_json::Value::as_array_mut);
+        for rule in rules
+            .into_iter()
+            .flatten()
+            .filter_map(|rule| rule.as_object_mut())
+        {
+            let triggered = rule
+                .remove("triggered")
+                .and_then(|triggered| triggered.as_bool());
+            let state = match triggered {
+                Some(true) => "triggered",
+                _ => "armed",
+            };
+            rule.insert("state".to_string(), state.into());
+        }
+    }
+}
+
+/// Serializable snapshot of all subscriptions, used to migrate state
+/// between deployments
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionsSnapshot {
+    pub version: u32,
+    pub subscriptions: Vec<SubscriptionRecord>,
+    /// Wall-clock time of the next periodic broadcast
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_send_at: Option<DateTime<Utc>>,
+    /// Price alert rules of every chat, subscribed or not
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<AlertRecord>,
+    /// Chats that chose a language other than the default one
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub languages: HashMap<ChatId, Language>,
+    /// Modules switched off with `/admin module disable`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disabled_modules: Vec<String>,
+    /// Chats that set any [`Preferences`]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub preferences: HashMap<ChatId, Preferences>,
+    /// Broadcasts a restart interrupted, resumed by
+    /// [`SubscriberManager::resume_broadcasts`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outbox: Vec<OutboxEntry>,
+    /// Tokens of the `/calendar` feed links of every chat, subscribed or not
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub calendar_tokens: HashMap<ChatId, String>,
+}
+
+impl SubscriptionsSnapshot {
+    /// Parse a snapshot of the current or an earlier version, upgrading the
+    /// latter through [`SNAPSHOT_MIGRATIONS`]
+    pub fn parse(json: &[u8]) -> Result<Self, String> {
+        let mut snapshot: serde_json::Value =
+            serde_json::from_slice(json).map_err(|e| e.to_string())?;
+        let version = snapshot
+            .get("version")
+            .and_then(serde_json::Value::as_u64)
+            .ok_or("Missing snapshot version")?;
+        let version = u32::try_from(version)
+            .ok()
+            .filter(|version| (1..=SNAPSHOT_VERSION).contains(version))
+            .ok_or_else(|| {
+                format!(
+                    "Unsupported snapshot version {} (expected up to {})",
+                    version, SNAPSHOT_VERSION
+                )
+            })?;
+        for migrate in &SNAPSHOT_MIGRATIONS[version as usize - 1..] {
+            migrate(&mut snapshot);
+        }
+        snapshot["version"] = SNAPSHOT_VERSION.into();
+        serde_json::from_value(snapshot).map_err(|e| e.to_string())
+    }
+}
+
+/// Single chat subscription inside a [`SubscriptionsSnapshot`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionRecord {
+    pub chat_id: ChatId,
+    pub message_count: u64,
+    /// When the chat subscribed, unknown for subscriptions older than the field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subscribed_at: Option<DateTime<Utc>>,
+    pub message_id: Option<i32>,
+    pub template: Option<String>,
+    #[serde(default)]
+    pub pairs: Option<Vec<CurrencyPair>>,
+    #[serde(default)]
+    pub delivery: DeliveryMode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quiet_hours: Option<QuietHours>,
+    /// Forum topic the chat subscribed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<ThreadId>,
+    /// Whether the chat gets the daily summary
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub daily_summary: bool,
+    /// Whether the chat gets the weekly CSV report
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub weekly_report: bool,
+}
+
+/// What the deliveries left behind that isn't part of the subscriptions,
+/// kept across restarts by the [runtime snapshot](crate::snapshot)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeliveryState {
+    /// Quotes of each chat's last message, the deltas of its next one
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub last_quotes: HashMap<ChatId, Quotes>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dead_letters: Vec<DeadLetter>,
+}
+
 #[derive(Debug, Clone)]
 pub struct SubscriberManager {
     subscribers: Arc<std::sync::Mutex<HashSet<ChatId>>>,
     next_send_time: Arc<std::sync::Mutex<Option<Instant>>>,
     message_counters: Arc<std::sync::Mutex<HashMap<ChatId, u64>>>,
+    subscribed_at: Arc<std::sync::Mutex<HashMap<ChatId, DateTime<Utc>>>>,
     message_ids: Arc<std::sync::Mutex<HashMap<ChatId, MessageId>>>,
-    message_text: String,
+    templates: Arc<std::sync::Mutex<HashMap<ChatId, String>>>,
+    pairs: Arc<std::sync::Mutex<HashMap<ChatId, Vec<CurrencyPair>>>>,
+    last_quotes: Arc<std::sync::Mutex<HashMap<ChatId, Quotes>>>,
+    sparklines: Arc<std::sync::Mutex<Sparklines>>,
+    day_ago: Arc<std::sync::Mutex<Quotes>>,
+    footer: Arc<std::sync::Mutex<Option<String>>>,
+    dead_letters: Arc<std::sync::Mutex<HashMap<ChatId, DeadLetter>>>,
+    delivery_modes: Arc<std::sync::Mutex<HashMap<ChatId, DeliveryMode>>>,
+    quiet_hours: Arc<std::sync::Mutex<HashMap<ChatId, QuietHours>>>,
+    thread_ids: Arc<std::sync::Mutex<HashMap<ChatId, ThreadId>>>,
+    daily_summaries: Arc<std::sync::Mutex<HashSet<ChatId>>>,
+    weekly_reports: Arc<std::sync::Mutex<HashSet<ChatId>>>,
+    calendar_tokens: Arc<std::sync::Mutex<HashMap<ChatId, String>>>,
+    preferences: Arc<std::sync::Mutex<HashMap<ChatId, Preferences>>>,
+    outbox: Arc<std::sync::Mutex<Vec<OutboxEntry>>>,
+    alerts: Arc<AlertEngine>,
+    languages: Languages,
+    module_switches: ModuleSwitches,
+    message_format: MessageFormat,
+    outbound: OutboundQueue,
+    message_text: Arc<std::sync::Mutex<String>>,
+    jitter_window: Duration,
+    /// History the sparklines of the periodic messages cover
+    sparkline_window: Option<chrono::Duration>,
+    /// Template of the footer of the periodic messages (`display.footer`)
+    footer_template: Option<String>,
+    /// How the prices of the periodic messages, summaries and alerts are written
+    price_formats: PriceFormats,
+    pin_messages: bool,
+    state_file: Option<PathBuf>,
+    /// Held while the state file is written, saves come from several tasks
+    save_lock: Arc<std::sync::Mutex<()>>,
 }
 
 impl SubscriberManager {
@@ -30,20 +519,175 @@ impl SubscriberManager {
             subscribers: Arc::new(std::sync::Mutex::new(HashSet::new())),
             next_send_time: Arc::new(std::sync::Mutex::new(None)),
             message_counters: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            subscribed_at: Arc::new(std::sync::Mutex::new(HashMap::new())),
             message_ids: Arc::new(std::sync::Mutex::new(HashMap::new())),
-            message_text,
+            templates: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            pairs: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            last_quotes: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            sparklines: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            day_ago: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            footer: Arc::new(std::sync::Mutex::new(None)),
+            dead_letters: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            delivery_modes: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            quiet_hours: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            thread_ids: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            daily_summaries: Arc::new(std::sync::Mutex::new(HashSet::new())),
+            weekly_reports: Arc::new(std::sync::Mutex::new(HashSet::new())),
+            calendar_tokens: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            preferences: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            outbox: Arc::new(std::sync::Mutex::new(Vec::new())),
+            alerts: Arc::new(AlertEngine::new()),
+            languages: Languages::new(),
+            module_switches: ModuleSwitches::new(),
+            message_format: MessageFormat::default(),
+            outbound: OutboundQueue::default(),
+            message_text: Arc::new(std::sync::Mutex::new(message_text)),
+            jitter_window: Duration::ZERO,
+            sparkline_window: None,
+            footer_template: None,
+            price_formats: PriceFormats::default(),
+            pin_messages: false,
+            state_file: None,
+            save_lock: Arc::new(std::sync::Mutex::new(())),
+        }
+    }
+
+    /// Markup of the periodic messages
+    pub fn with_message_format(mut self, format: MessageFormat) -> Self {
+        self.message_format = format;
+        self
+    }
+
+    /// Queue the messages to the chats are sent through, shared by the modules
+    /// and jobs of the bot
+    pub fn with_outbound(mut self, outbound: OutboundQueue) -> Self {
+        self.outbound = outbound;
+        self
+    }
+
+    /// Pin every new periodic (anchor) message in its chat
+    pub fn with_pinned_messages(mut self, pin_messages: bool) -> Self {
+        self.pin_messages = pin_messages;
+        self
+    }
+
+    /// State file [`Self::save_state`] writes to
+    pub fn with_state_file(mut self, path: PathBuf) -> Self {
+        self.state_file = Some(path);
+        self
+    }
+
+    /// Write the state file right away, for changes that shouldn't wait for
+    /// the shutdown, like alert rules. Failures are logged.
+    pub fn save_state(&self) {
+        if let Some(path) = &self.state_file {
+            if let Err(e) = self.save_to_file(path) {
+                tracing::error!("Failed to save subscriptions to {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// Markup of the messages sent to the chats
+    pub fn message_format(&self) -> MessageFormat {
+        self.message_format
+    }
+
+    pub fn outbound(&self) -> &OutboundQueue {
+        &self.outbound
+    }
+
+    pub fn pins_messages(&self) -> bool {
+        self.pin_messages
+    }
+
+    /// Price alert rules, stored and persisted together with the subscriptions
+    pub fn alerts(&self) -> &AlertEngine {
+        &self.alerts
+    }
+
+    /// Reply language of every chat, stored and persisted together with the
+    /// subscriptions
+    pub fn languages(&self) -> &Languages {
+        &self.languages
+    }
+
+    /// Modules switched on or off at runtime, persisted together with the
+    /// subscriptions
+    pub fn module_switches(&self) -> &ModuleSwitches {
+        &self.module_switches
+    }
+
+    /// Spread the periodic sends of a broadcast over `window` instead of
+    /// delivering to every chat at once
+    pub fn with_jitter_window(mut self, window: Duration) -> Self {
+        self.jitter_window = window;
+        self
+    }
+
+    /// Show a sparkline of the prices over `window` in the periodic
+    /// messages, none without a window
+    pub fn with_sparkline_window(mut self, window: Option<chrono::Duration>) -> Self {
+        self.sparkline_window = window;
+        self
+    }
+
+    /// End the periodic messages with a footer rendered from `template`
+    pub fn with_footer(mut self, template: Option<String>) -> Self {
+        self.footer_template = template;
+        self
+    }
+
+    /// Write the prices of the messages to the chats in `formats`
+    pub fn with_price_formats(mut self, formats: PriceFormats) -> Self {
+        self.price_formats = formats;
+        self
+    }
+
+    pub fn price_formats(&self) -> &PriceFormats {
+        &self.price_formats
+    }
+
+    /// Render the footer of the next periodic messages, with the sources of
+    /// the quotes of `pairs` just fetched
+    pub fn refresh_footer(&self, price_service: &PriceService, pairs: &[CurrencyPair]) {
+        if let Some(template) = &self.footer_template {
+            // Shared by the chats, in the server's time
+            let footer = render_footer(template, price_service, pairs, self.message_format, None);
+            *self.footer.lock().unwrap() = Some(footer);
+        }
+    }
+
+    /// Look up the 24 hour changes and compute the sparklines of `pairs`
+    /// from `history` for the next periodic messages
+    pub fn refresh_trends(&self, history: &PriceHistory, pairs: &[CurrencyPair]) {
+        self.day_ago
+            .lock()
+            .unwrap()
+            .extend(day_ago_quotes(history, pairs));
+        let Some(window) = self.sparkline_window else {
+            return;
+        };
+        let since = Local::now() - window;
+        let mut sparklines = self.sparklines.lock().unwrap();
+        for pair in pairs {
+            match history.sparkline(pair, since) {
+                Some(sparkline) => sparklines.insert(pair.clone(), sparkline),
+                None => sparklines.remove(pair),
+            };
         }
     }
 
     pub fn subscribe(&self, chat_id: ChatId) -> SubscriptionAction {
         let mut subscribers = self.subscribers.lock().unwrap();
         if subscribers.insert(chat_id) {
-            log::info!("User {} subscribed to periodic messages", chat_id);
+            tracing::info!("User {} subscribed to periodic messages", chat_id);
             let mut counters = self.message_counters.lock().unwrap();
             counters.insert(chat_id, 0);
+            let mut subscribed_at = self.subscribed_at.lock().unwrap();
+            subscribed_at.insert(chat_id, Utc::now());
             SubscriptionAction::Subscribed
         } else {
-            log::debug!("User {} already subscribed", chat_id);
+            tracing::debug!("User {} already subscribed", chat_id);
             SubscriptionAction::AlreadySubscribed
         }
     }
@@ -51,12 +695,16 @@ impl SubscriberManager {
     pub fn unsubscribe(&self, chat_id: ChatId) -> SubscriptionAction {
         let mut subscribers = self.subscribers.lock().unwrap();
         if subscribers.remove(&chat_id) {
-            log::info!("User {} unsubscribed from periodic messages", chat_id);
+            tracing::info!("User {} unsubscribed from periodic messages", chat_id);
             let mut counters = self.message_counters.lock().unwrap();
             counters.remove(&chat_id);
+            self.subscribed_at.lock().unwrap().remove(&chat_id);
+            self.last_quotes.lock().unwrap().remove(&chat_id);
+            self.dead_letters.lock().unwrap().remove(&chat_id);
+            self.thread_ids.lock().unwrap().remove(&chat_id);
             SubscriptionAction::Unsubscribed
         } else {
-            log::debug!("User {} was not subscribed", chat_id);
+            tracing::debug!("User {} was not subscribed", chat_id);
             SubscriptionAction::NotSubscribed
         }
     }
@@ -76,126 +724,1093 @@ impl SubscriberManager {
         subscribers.len()
     }
 
+    /// When the chat subscribed, `None` when it isn't subscribed or did
+    /// before the time was recorded
+    pub fn get_subscribed_at(&self, chat_id: ChatId) -> Option<DateTime<Utc>> {
+        let subscribed_at = self.subscribed_at.lock().unwrap();
+        subscribed_at.get(&chat_id).copied()
+    }
+
     pub fn get_message_count(&self, chat_id: ChatId) -> u64 {
         let counters = self.message_counters.lock().unwrap();
         *counters.get(&chat_id).unwrap_or(&0)
     }
 
-    pub fn increment_message_counter(&self, chat_id: ChatId) {
-        let mut counters = self.message_counters.lock().unwrap();
-        if let Some(counter) = counters.get_mut(&chat_id) {
-            *counter += 1;
+    /// Periodic messages sent to all current subscribers
+    pub fn total_message_count(&self) -> u64 {
+        self.message_counters.lock().unwrap().values().sum()
+    }
+
+    /// Send `text` to `chat_ids` through the outbox, returning how many
+    /// chats got it and how many failed
+    pub async fn broadcast_text(
+        &self,
+        bot: &TelegramBot,
+        chat_ids: Vec<ChatId>,
+        text: &str,
+    ) -> (usize, usize) {
+        let id = self.enqueue_broadcast(text, chat_ids);
+        self.deliver_broadcast(bot, id).await
+    }
+
+    /// Add a broadcast to the outbox and write it to the state file before
+    /// anything is sent
+    fn enqueue_broadcast(&self, text: &str, chat_ids: Vec<ChatId>) -> u64 {
+        let id = {
+            let mut outbox = self.outbox.lock().unwrap();
+            let id = outbox.iter().map(|entry| entry.id).max().unwrap_or(0) + 1;
+            outbox.push(OutboxEntry {
+                id,
+                text: text.to_string(),
+                created_at: Utc::now(),
+                pending: chat_ids,
+            });
+            id
+        };
+        self.save_state();
+        id
+    }
+
+    /// Broadcasts waiting in the outbox, oldest first
+    pub fn pending_broadcasts(&self) -> Vec<OutboxEntry> {
+        self.outbox.lock().unwrap().clone()
+    }
+
+    /// Send the broadcasts a restart interrupted to the chats still pending
+    pub async fn resume_broadcasts(&self, bot: &TelegramBot) {
+        for entry in self.pending_broadcasts() {
+            tracing::info!(
+                "Resuming broadcast {} to {} chats",
+                entry.id,
+                entry.pending.len()
+            );
+            let (delivered, failed) = self.deliver_broadcast(bot, entry.id).await;
+            tracing::info!(
+                "Resumed broadcast {} delivered to {} chats, {} failed",
+                entry.id,
+                delivered,
+                failed
+            );
+        }
+    }
+
+    /// Send the outbox entry `id` to its pending chats, removing every chat
+    /// once its send is done, delivered or not, and the entry at the end
+    async fn deliver_broadcast(&self, bot: &TelegramBot, id: u64) -> (usize, usize) {
+        let entry = {
+            let outbox = self.outbox.lock().unwrap();
+            outbox.iter().find(|entry| entry.id == id).cloned()
+        };
+        let Some(entry) = entry else {
+            return (0, 0);
+        };
+        let text = entry.text.as_str();
+        // Writing the state file after every chat would rewrite it thousands
+        // of times, a restart resends at most the chats of the last interval
+        let last_save = std::sync::Mutex::new(Instant::now());
+        let last_save = &last_save;
+        let results =
+            send_concurrently(entry.pending, BROADCAST_CONCURRENCY, |chat_id| async move {
+                let result = self
+                    .send_with_migration(chat_id, |chat_id| {
+                        let mut request = bot.send_message(chat_id, text);
+                        request.payload_mut().message_thread_id = self.get_thread_id(chat_id);
+                        request.send()
+                    })
+                    .await;
+                if let Err(e) = &result {
+                    tracing::warn!("Failed to deliver broadcast to {}: {}", chat_id, e);
+                }
+                self.mark_broadcast_sent(id, chat_id);
+                let save = {
+                    let mut last_save = last_save.lock().unwrap();
+                    let due = last_save.elapsed() >= OUTBOX_SAVE_INTERVAL;
+                    if due {
+                        *last_save = Instant::now();
+                    }
+                    due
+                };
+                if save {
+                    self.save_state();
+                }
+                result.is_ok()
+            })
+            .await;
+        self.outbox.lock().unwrap().retain(|entry| entry.id != id);
+        self.save_state();
+        let delivered = results.iter().filter(|delivered| **delivered).count();
+        (delivered, results.len() - delivered)
+    }
+
+    fn mark_broadcast_sent(&self, id: u64, chat_id: ChatId) {
+        let mut outbox = self.outbox.lock().unwrap();
+        if let Some(entry) = outbox.iter_mut().find(|entry| entry.id == id) {
+            entry.pending.retain(|pending| *pending != chat_id);
+        }
+    }
+
+    pub fn increment_message_counter(&self, chat_id: ChatId) {
+        let mut counters = self.message_counters.lock().unwrap();
+        if let Some(counter) = counters.get_mut(&chat_id) {
+            *counter += 1;
+        }
+    }
+
+    /// Deliver the chat's periodic message according to its delivery mode.
+    /// In edit mode a missing or deleted anchor message is sent anew.
+    pub async fn send_periodic_message_to_chat(
+        &self,
+        bot: &TelegramBot,
+        chat_id: ChatId,
+        quotes: &Quotes,
+    ) -> Result<(), RequestError> {
+        let anchor = match self.get_delivery_mode(chat_id) {
+            DeliveryMode::Edit => self.get_message_id(chat_id),
+            DeliveryMode::New | DeliveryMode::Replace => None,
+        };
+        let Some(message_id) = anchor else {
+            return self.send_new_periodic_message(bot, chat_id, quotes).await;
+        };
+
+        let message_with_counter = self.format_periodic_message(chat_id, quotes);
+        let edit = self
+            .outbound
+            .send(Priority::Broadcast, chat_id, || {
+                let mut request = bot.edit_message_text(chat_id, message_id, &message_with_counter);
+                request.payload_mut().parse_mode = self.message_format.parse_mode();
+                request.send()
+            })
+            .await;
+        match edit {
+            Ok(_) => {
+                self.increment_message_counter(chat_id);
+                self.remember_quotes(chat_id, quotes);
+                Ok(())
+            }
+            Err(e) if is_edit_target_lost(&e) => {
+                tracing::info!(
+                    "Periodic message in {} can't be edited ({}), sending a new one",
+                    chat_id,
+                    e
+                );
+                self.remove_message_id(chat_id);
+                self.send_new_periodic_message(bot, chat_id, quotes).await
+            }
+            Err(RequestError::MigrateToChatId(new_id)) => {
+                self.migrate_chat(chat_id, new_id);
+                self.send_new_periodic_message(bot, new_id, quotes).await
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Send the periodic message as a new message and make it the chat's
+    /// anchor, moving the pin to it when pinning is enabled and deleting the
+    /// previous one in replace mode
+    pub async fn send_new_periodic_message(
+        &self,
+        bot: &TelegramBot,
+        chat_id: ChatId,
+        quotes: &Quotes,
+    ) -> Result<(), RequestError> {
+        let text = self.format_periodic_message(chat_id, quotes);
+        let message = self.send_to_chat(bot, chat_id, text).await?;
+        // A group migrated to a supergroup on the way is continued there
+        let chat_id = message.chat.id;
+        let previous = self.get_message_id(chat_id);
+        self.set_message_id(chat_id, message.id);
+        self.increment_message_counter(chat_id);
+        self.remember_quotes(chat_id, quotes);
+
+        if self.pin_messages {
+            if let Some(previous) = previous {
+                if let Err(e) = bot.unpin_chat_message(chat_id).message_id(previous).await {
+                    tracing::warn!("Failed to unpin periodic message in {}: {}", chat_id, e);
+                }
+            }
+            if let Err(e) = bot
+                .pin_chat_message(chat_id, message.id)
+                .disable_notification(true)
+                .await
+            {
+                tracing::warn!("Failed to pin periodic message in {}: {}", chat_id, e);
+            }
+        }
+
+        let replace = self.get_delivery_mode(chat_id) == DeliveryMode::Replace;
+        if let Some(previous) = previous.filter(|_| replace) {
+            // Bots can't delete messages older than 48 hours, those stay in the chat
+            if let Err(e) = bot.delete_message(chat_id, previous).await {
+                tracing::warn!(
+                    "Failed to delete previous periodic message in {}: {}",
+                    chat_id,
+                    e
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_delivery_mode(&self, chat_id: ChatId, mode: DeliveryMode) {
+        let mut modes = self.delivery_modes.lock().unwrap();
+        modes.insert(chat_id, mode);
+    }
+
+    /// Get the chat's delivery mode, editing in place by default
+    pub fn get_delivery_mode(&self, chat_id: ChatId) -> DeliveryMode {
+        let modes = self.delivery_modes.lock().unwrap();
+        modes.get(&chat_id).copied().unwrap_or_default()
+    }
+
+    /// Set or clear (`None`) the chat's quiet hours
+    pub fn set_quiet_hours(&self, chat_id: ChatId, quiet_hours: Option<QuietHours>) {
+        let mut all_quiet_hours = self.quiet_hours.lock().unwrap();
+        match quiet_hours {
+            Some(quiet_hours) => all_quiet_hours.insert(chat_id, quiet_hours),
+            None => all_quiet_hours.remove(&chat_id),
+        };
+    }
+
+    pub fn get_quiet_hours(&self, chat_id: ChatId) -> Option<QuietHours> {
+        let quiet_hours = self.quiet_hours.lock().unwrap();
+        quiet_hours.get(&chat_id).copied()
+    }
+
+    /// Deliver the chat's messages to a forum topic, or to General (`None`).
+    /// Returns whether the topic changed.
+    pub fn set_thread_id(&self, chat_id: ChatId, thread_id: Option<ThreadId>) -> bool {
+        let mut thread_ids = self.thread_ids.lock().unwrap();
+        let previous = match thread_id {
+            Some(thread_id) => thread_ids.insert(chat_id, thread_id),
+            None => thread_ids.remove(&chat_id),
+        };
+        previous != thread_id
+    }
+
+    pub fn get_thread_id(&self, chat_id: ChatId) -> Option<ThreadId> {
+        let thread_ids = self.thread_ids.lock().unwrap();
+        thread_ids.get(&chat_id).copied()
+    }
+
+    /// Opt the chat in or out of the daily summary
+    pub fn set_daily_summary(&self, chat_id: ChatId, enabled: bool) {
+        let mut daily_summaries = self.daily_summaries.lock().unwrap();
+        match enabled {
+            true => daily_summaries.insert(chat_id),
+            false => daily_summaries.remove(&chat_id),
+        };
+    }
+
+    pub fn get_daily_summary(&self, chat_id: ChatId) -> bool {
+        self.daily_summaries.lock().unwrap().contains(&chat_id)
+    }
+
+    /// Subscribed chats that opted in to the daily summary
+    pub fn get_daily_summary_chats(&self) -> Vec<ChatId> {
+        let daily_summaries = self.daily_summaries.lock().unwrap();
+        self.get_subscribers()
+            .into_iter()
+            .filter(|chat_id| daily_summaries.contains(chat_id))
+            .collect()
+    }
+
+    /// Opt the chat in or out of the weekly CSV report
+    pub fn set_weekly_report(&self, chat_id: ChatId, enabled: bool) {
+        let mut weekly_reports = self.weekly_reports.lock().unwrap();
+        match enabled {
+            true => weekly_reports.insert(chat_id),
+            false => weekly_reports.remove(&chat_id),
+        };
+    }
+
+    pub fn get_weekly_report(&self, chat_id: ChatId) -> bool {
+        self.weekly_reports.lock().unwrap().contains(&chat_id)
+    }
+
+    /// Store the chat's preferences, forgetting them once nothing is set
+    pub fn set_preferences(&self, chat_id: ChatId, preferences: Preferences) {
+        let mut all_preferences = self.preferences.lock().unwrap();
+        match preferences.is_empty() {
+            true => all_preferences.remove(&chat_id),
+            false => all_preferences.insert(chat_id, preferences),
+        };
+    }
+
+    pub fn get_preferences(&self, chat_id: ChatId) -> Preferences {
+        let preferences = self.preferences.lock().unwrap();
+        preferences.get(&chat_id).cloned().unwrap_or_default()
+    }
+
+    /// Language, preferences and delivery settings of the chat in one record
+    pub fn profile(&self, chat_id: ChatId) -> UserProfile {
+        let preferences = self.get_preferences(chat_id);
+        UserProfile {
+            language: self.languages.get(chat_id),
+            timezone: preferences.timezone,
+            default_pair: preferences.default_pair,
+            default_amount: preferences.default_amount,
+            delivery: self.get_delivery_mode(chat_id),
+            quiet_hours: self.get_quiet_hours(chat_id),
+        }
+    }
+
+    /// Subscribed chats that opted in to the weekly CSV report
+    pub fn get_weekly_report_chats(&self) -> Vec<ChatId> {
+        let weekly_reports = self.weekly_reports.lock().unwrap();
+        self.get_subscribers()
+            .into_iter()
+            .filter(|chat_id| weekly_reports.contains(chat_id))
+            .collect()
+    }
+
+    /// Token of the chat's calendar feed, issued on first use
+    pub fn get_calendar_token(&self, chat_id: ChatId) -> String {
+        let mut calendar_tokens = self.calendar_tokens.lock().unwrap();
+        calendar_tokens
+            .entry(chat_id)
+            .or_insert_with(new_calendar_token)
+            .clone()
+    }
+
+    /// Issue the chat a new calendar token, the old feed link stops working
+    pub fn reset_calendar_token(&self, chat_id: ChatId) -> String {
+        let token = new_calendar_token();
+        let mut calendar_tokens = self.calendar_tokens.lock().unwrap();
+        calendar_tokens.insert(chat_id, token.clone());
+        token
+    }
+
+    /// Chat the calendar feed `token` was issued to
+    pub fn get_calendar_chat(&self, token: &str) -> Option<ChatId> {
+        let calendar_tokens = self.calendar_tokens.lock().unwrap();
+        calendar_tokens
+            .iter()
+            .find(|(_, issued)| issued.as_str() == token)
+            .map(|(chat_id, _)| *chat_id)
+    }
+
+    /// How long after a broadcast starts the chat gets its periodic message
+    pub fn get_jitter_offset(&self, chat_id: ChatId) -> Duration {
+        jitter_offset(chat_id, self.jitter_window)
+    }
+
+    /// Send `text` to the chat, into its forum topic
+    pub async fn send_to_chat(
+        &self,
+        bot: &TelegramBot,
+        chat_id: ChatId,
+        text: String,
+    ) -> Result<Message, RequestError> {
+        self.send_with_migration(chat_id, |chat_id| {
+            let mut request = self.message_format.send_message(bot, chat_id, text.clone());
+            request.payload_mut().message_thread_id = self.get_thread_id(chat_id);
+            request.send()
+        })
+        .await
+    }
+
+    /// Send `document` with `caption` to the chat, into its forum topic
+    pub async fn send_document_to_chat(
+        &self,
+        bot: &TelegramBot,
+        chat_id: ChatId,
+        document: InputFile,
+        caption: String,
+    ) -> Result<Message, RequestError> {
+        self.send_with_migration(chat_id, |chat_id| {
+            let mut request = bot
+                .send_document(chat_id, document.clone())
+                .caption(caption.clone());
+            request.payload_mut().message_thread_id = self.get_thread_id(chat_id);
+            request.send()
+        })
+        .await
+    }
+
+    /// Send `request` to the chat through the outbound queue. When the group
+    /// was upgraded to a supergroup its settings move to the new chat ID and
+    /// the request is sent there instead of failing for good.
+    pub async fn send_with_migration<T, F, Fut>(
+        &self,
+        chat_id: ChatId,
+        request: F,
+    ) -> Result<T, RequestError>
+    where
+        F: Fn(ChatId) -> Fut,
+        Fut: std::future::Future<Output = Result<T, RequestError>>,
+    {
+        match self
+            .outbound
+            .send(Priority::Broadcast, chat_id, || request(chat_id))
+            .await
+        {
+            Err(RequestError::MigrateToChatId(new_id)) => {
+                self.migrate_chat(chat_id, new_id);
+                self.outbound
+                    .send(Priority::Broadcast, new_id, || request(new_id))
+                    .await
+            }
+            result => result,
+        }
+    }
+
+    /// Move everything stored for the group `from` to `to`, the ID Telegram
+    /// gave it as a supergroup. Messages stay behind in the old chat, so the
+    /// periodic message is sent anew and forum topics are forgotten.
+    pub fn migrate_chat(&self, from: ChatId, to: ChatId) {
+        fn move_entry<V>(map: &std::sync::Mutex<HashMap<ChatId, V>>, from: ChatId, to: ChatId) {
+            let mut map = map.lock().unwrap();
+            if let Some(value) = map.remove(&from) {
+                map.insert(to, value);
+            }
+        }
+        fn move_member(set: &std::sync::Mutex<HashSet<ChatId>>, from: ChatId, to: ChatId) {
+            let mut set = set.lock().unwrap();
+            if set.remove(&from) {
+                set.insert(to);
+            }
+        }
+
+        tracing::info!("Chat {} migrated to supergroup {}", from, to);
+        move_member(&self.subscribers, from, to);
+        move_member(&self.daily_summaries, from, to);
+        move_member(&self.weekly_reports, from, to);
+        move_entry(&self.message_counters, from, to);
+        move_entry(&self.subscribed_at, from, to);
+        move_entry(&self.templates, from, to);
+        move_entry(&self.pairs, from, to);
+        move_entry(&self.last_quotes, from, to);
+        move_entry(&self.delivery_modes, from, to);
+        move_entry(&self.quiet_hours, from, to);
+        move_entry(&self.preferences, from, to);
+        move_entry(&self.calendar_tokens, from, to);
+        self.message_ids.lock().unwrap().remove(&from);
+        self.thread_ids.lock().unwrap().remove(&from);
+        self.dead_letters.lock().unwrap().remove(&from);
+        self.alerts.migrate(from, to);
+        let language = self.languages.get(from);
+        self.languages.set(from, Language::default());
+        self.languages.set(to, language);
+        self.save_state();
+    }
+
+    /// Whether `now` falls into the chat's quiet hours, read in its timezone
+    /// when one is set and in the server's otherwise
+    fn in_quiet_hours(&self, chat_id: ChatId, now: DateTime<Utc>) -> bool {
+        let Some(quiet_hours) = self.get_quiet_hours(chat_id) else {
+            return false;
+        };
+        let hour = match self.get_preferences(chat_id).timezone {
+            Some(timezone) => now.with_timezone(&timezone).hour(),
+            None => now.with_timezone(&Local).hour(),
+        };
+        quiet_hours.contains(hour)
+    }
+
+    /// Deliver the periodic message to every subscribed chat outside of its
+    /// quiet hours
+    pub async fn broadcast_periodic_message(
+        &self,
+        bot: &TelegramBot,
+        price_service: &PriceService,
+    ) {
+        let now = Utc::now();
+        let mut subscribers = self.get_subscribers();
+        subscribers.retain(|chat_id| {
+            let quiet = self.in_quiet_hours(*chat_id, now);
+            if quiet {
+                tracing::debug!("Skipping periodic message to {} in quiet hours", chat_id);
+            }
+            !quiet
+        });
+        let count = subscribers.len();
+
+        if count == 0 {
+            tracing::debug!("No subscribers to send message to");
+            return;
+        }
+
+        tracing::info!("Sending periodic message to {} subscribers", count);
+
+        // Fetch every pair once for the whole broadcast instead of per chat
+        let mut pairs = Vec::new();
+        for chat_id in &subscribers {
+            for pair in self.get_pairs(*chat_id) {
+                if !pairs.contains(&pair) {
+                    pairs.push(pair);
+                }
+            }
+        }
+        let quotes = fetch_quotes(price_service, &pairs).await;
+        self.refresh_trends(price_service.history(), &pairs);
+        self.refresh_footer(price_service, &pairs);
+
+        let quotes = &quotes;
+        let mut pending = subscribers;
+        let mut attempt = 1;
+
+        // Chats are sent in order of their jitter offset, so waiting for the
+        // offset inside a send only holds slots the next due chats need
+        let jitter_window = self.jitter_window;
+        let started = tokio::time::Instant::now();
+        pending.sort_by_key(|chat_id| jitter_offset(*chat_id, jitter_window));
+
+        let mut success_count = 0;
+        let mut error_count = 0;
+
+        loop {
+            let results = send_concurrently(pending, BROADCAST_CONCURRENCY, |chat_id| async move {
+                // Retry passes are delivered as fast as pacing allows
+                if attempt == 1 {
+                    tokio::time::sleep_until(started + jitter_offset(chat_id, jitter_window)).await;
+                }
+                let result = self
+                    .send_periodic_message_to_chat(bot, chat_id, quotes)
+                    .await;
+                (chat_id, result)
+            })
+            .await;
+
+            let mut retry = Vec::new();
+            for (chat_id, result) in results {
+                match result {
+                    Ok(()) => {
+                        success_count += 1;
+                        self.dead_letters.lock().unwrap().remove(&chat_id);
+                    }
+                    Err(e) if is_transient(&e) && attempt < MAX_DELIVERY_ATTEMPTS => {
+                        tracing::warn!(
+                            "Failed to deliver periodic message to {} (attempt {}): {}",
+                            chat_id,
+                            attempt,
+                            e
+                        );
+                        retry.push(chat_id);
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to deliver periodic message to {}: {}", chat_id, e);
+                        self.record_dead_letter(chat_id, attempt, e.to_string());
+                        error_count += 1;
+                    }
+                }
+            }
+
+            if retry.is_empty() {
+                break;
+            }
+
+            let delay = retry_delay(attempt);
+            tracing::info!("Retrying {} chats in {:?}", retry.len(), delay);
+            tokio::time::sleep(delay).await;
+            pending = retry;
+            attempt += 1;
+        }
+
+        tracing::info!(
+            "Periodic message sent: {} success, {} errors",
+            success_count,
+            error_count
+        );
+    }
+
+    fn record_dead_letter(&self, chat_id: ChatId, attempts: u32, error: String) {
+        let mut dead_letters = self.dead_letters.lock().unwrap();
+        dead_letters.insert(
+            chat_id,
+            DeadLetter {
+                chat_id,
+                attempts,
+                error,
+                failed_at: Local::now(),
+            },
+        );
+    }
+
+    /// Chats whose last periodic message failed permanently, latest first
+    pub fn get_dead_letters(&self) -> Vec<DeadLetter> {
+        let dead_letters = self.dead_letters.lock().unwrap();
+        let mut list: Vec<_> = dead_letters.values().cloned().collect();
+        list.sort_by_key(|letter| std::cmp::Reverse(letter.failed_at));
+        list
+    }
+
+    pub fn clear_dead_letters(&self) {
+        self.dead_letters.lock().unwrap().clear();
+    }
+
+    pub fn set_message_id(&self, chat_id: ChatId, message_id: MessageId) {
+        let mut ids = self.message_ids.lock().unwrap();
+        ids.insert(chat_id, message_id);
+    }
+
+    pub fn get_message_id(&self, chat_id: ChatId) -> Option<MessageId> {
+        let ids = self.message_ids.lock().unwrap();
+        ids.get(&chat_id).copied()
+    }
+
+    pub fn remove_message_id(&self, chat_id: ChatId) {
+        let mut ids = self.message_ids.lock().unwrap();
+        ids.remove(&chat_id);
+    }
+
+    pub fn set_next_send_time(&self, time: Instant) {
+        let mut next = self.next_send_time.lock().unwrap();
+        *next = Some(time);
+    }
+
+    /// Forget the next send time, e.g. while the schedule is paused
+    pub fn clear_next_send_time(&self) {
+        let mut next = self.next_send_time.lock().unwrap();
+        *next = None;
+    }
+
+    pub fn get_next_send_time(&self) -> Option<Instant> {
+        *self.next_send_time.lock().unwrap()
+    }
+
+    pub fn get_time_until_next(&self) -> Option<Duration> {
+        let next = self.next_send_time.lock().unwrap();
+        next.map(|t| {
+            let remaining = t.duration_since(Instant::now());
+            if remaining.is_zero() {
+                Duration::from_secs(0)
+            } else {
+                remaining
+            }
+        })
+    }
+
+    pub fn get_periodic_message_text(&self) -> String {
+        self.message_text.lock().unwrap().clone()
+    }
+
+    /// Replace the default text of the periodic message, used by chats
+    /// without a template of their own
+    pub fn set_periodic_message_text(&self, text: String) {
+        *self.message_text.lock().unwrap() = text;
+    }
+
+    /// Set a custom periodic message template for the chat
+    pub fn set_template(&self, chat_id: ChatId, template: String) {
+        let mut templates = self.templates.lock().unwrap();
+        templates.insert(chat_id, template);
+    }
+
+    /// Drop the chat's custom template and fall back to the default text
+    pub fn reset_template(&self, chat_id: ChatId) {
+        let mut templates = self.templates.lock().unwrap();
+        templates.remove(&chat_id);
+    }
+
+    /// Get the chat's template, or the default periodic message text
+    pub fn get_template(&self, chat_id: ChatId) -> String {
+        let templates = self.templates.lock().unwrap();
+        templates
+            .get(&chat_id)
+            .cloned()
+            .unwrap_or_else(|| self.get_periodic_message_text())
+    }
+
+    /// Set the currency pairs included in the chat's periodic message
+    pub fn set_pairs(&self, chat_id: ChatId, pairs: Vec<CurrencyPair>) {
+        let mut all_pairs = self.pairs.lock().unwrap();
+        all_pairs.insert(chat_id, pairs);
+    }
+
+    /// Get the chat's currency pairs, all available pairs by default
+    pub fn get_pairs(&self, chat_id: ChatId) -> Vec<CurrencyPair> {
+        let all_pairs = self.pairs.lock().unwrap();
+        all_pairs
+            .get(&chat_id)
+            .cloned()
+            .unwrap_or_else(get_all_currency_pairs)
+    }
+
+    /// Remember the prices sent to the chat to show deltas in the next message
+    pub fn remember_quotes(&self, chat_id: ChatId, quotes: &Quotes) {
+        let mut last_quotes = self.last_quotes.lock().unwrap();
+        last_quotes
+            .entry(chat_id)
+            .or_default()
+            .extend(quotes.iter().map(|(pair, price)| (pair.clone(), *price)));
+    }
+
+    /// Render `template` for the chat's pairs with deltas against the
+    /// quotes of its previous message
+    fn render_for_pairs(&self, chat_id: ChatId, template: &str, quotes: &Quotes) -> String {
+        let last_quotes = self.last_quotes.lock().unwrap();
+        let trends = Trends {
+            previous: last_quotes.get(&chat_id),
+            day_ago: &self.day_ago.lock().unwrap(),
+            sparklines: &self.sparklines.lock().unwrap(),
+        };
+        render_quotes(
+            template,
+            self.get_pairs(chat_id),
+            quotes,
+            trends,
+            self.message_format,
+            &self.price_formats,
+        )
+    }
+
+    /// Collect all subscriptions into a serializable snapshot
+    pub fn export_subscriptions(&self) -> SubscriptionsSnapshot {
+        let mut chat_ids = self.get_subscribers();
+        chat_ids.sort();
+
+        let templates = self.templates.lock().unwrap().clone();
+        let pairs = self.pairs.lock().unwrap().clone();
+        let subscriptions = chat_ids
+            .into_iter()
+            .map(|chat_id| SubscriptionRecord {
+                chat_id,
+                message_count: self.get_message_count(chat_id),
+                subscribed_at: self.get_subscribed_at(chat_id),
+                message_id: self.get_message_id(chat_id).map(|id| id.0),
+                template: templates.get(&chat_id).cloned(),
+                pairs: pairs.get(&chat_id).cloned(),
+                delivery: self.get_delivery_mode(chat_id),
+                quiet_hours: self.get_quiet_hours(chat_id),
+                thread_id: self.get_thread_id(chat_id),
+                daily_summary: self.get_daily_summary(chat_id),
+                weekly_report: self.get_weekly_report(chat_id),
+            })
+            .collect();
+
+        // Instants are process-local, convert to wall-clock time to survive a restart
+        let next_send_at = self.get_time_until_next().and_then(|remaining| {
+            chrono::Duration::from_std(remaining)
+                .ok()
+                .map(|remaining| Utc::now() + remaining)
+        });
+
+        SubscriptionsSnapshot {
+            version: SNAPSHOT_VERSION,
+            subscriptions,
+            next_send_at,
+            alerts: self.alerts.export(),
+            languages: self.languages.export(),
+            disabled_modules: self.module_switches.export(),
+            preferences: self.preferences.lock().unwrap().clone(),
+            outbox: self.pending_broadcasts(),
+            calendar_tokens: self.calendar_tokens.lock().unwrap().clone(),
         }
     }
 
-    pub async fn send_periodic_message_to_chat(
-        &self,
-        bot: &Bot,
-        chat_id: ChatId,
-    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
-        let message_with_counter = self.format_periodic_message(chat_id);
+    /// Merge a snapshot into the current subscriptions, overwriting chats
+    /// that are already present. Returns the number of imported chats.
+    pub fn import_subscriptions(&self, snapshot: SubscriptionsSnapshot) -> Result<usize, String> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(format!(
+                "Unsupported snapshot version {} (expected {})",
+                snapshot.version, SNAPSHOT_VERSION
+            ));
+        }
 
-        match self.get_message_id(chat_id) {
-            Some(message_id) => {
-                match bot
-                    .edit_message_text(chat_id, message_id, &message_with_counter)
-                    .await
-                {
-                    Ok(_) => {
-                        self.increment_message_counter(chat_id);
-                        Ok(true)
-                    }
-                    Err(e) => {
-                        log::error!("Failed to edit message for {}: {}", chat_id, e);
-                        Ok(false)
-                    }
-                }
+        let count = snapshot.subscriptions.len();
+        self.alerts.import(snapshot.alerts);
+        self.languages.import(snapshot.languages);
+        self.module_switches.import(snapshot.disabled_modules);
+        for (chat_id, preferences) in snapshot.preferences {
+            self.set_preferences(chat_id, preferences);
+        }
+        self.calendar_tokens
+            .lock()
+            .unwrap()
+            .extend(snapshot.calendar_tokens);
+        for record in snapshot.subscriptions {
+            self.subscribers.lock().unwrap().insert(record.chat_id);
+            self.message_counters
+                .lock()
+                .unwrap()
+                .insert(record.chat_id, record.message_count);
+            if let Some(time) = record.subscribed_at {
+                self.subscribed_at
+                    .lock()
+                    .unwrap()
+                    .insert(record.chat_id, time);
             }
-            None => {
-                log::debug!(
-                    "No message ID found for chat {}, skipping periodic message",
-                    chat_id
-                );
-                Ok(false)
+
+            match record.message_id {
+                Some(id) => self.set_message_id(record.chat_id, MessageId(id)),
+                None => self.remove_message_id(record.chat_id),
+            }
+            match record.template {
+                Some(template) => self.set_template(record.chat_id, template),
+                None => self.reset_template(record.chat_id),
+            }
+            if let Some(pairs) = record.pairs {
+                self.set_pairs(record.chat_id, pairs);
             }
+            self.set_delivery_mode(record.chat_id, record.delivery);
+            self.set_quiet_hours(record.chat_id, record.quiet_hours);
+            self.set_thread_id(record.chat_id, record.thread_id);
+            self.set_daily_summary(record.chat_id, record.daily_summary);
+            self.set_weekly_report(record.chat_id, record.weekly_report);
         }
-    }
 
-    pub fn set_message_id(&self, chat_id: ChatId, message_id: MessageId) {
-        let mut ids = self.message_ids.lock().unwrap();
-        ids.insert(chat_id, message_id);
+        tracing::info!("Imported {} subscriptions", count);
+        Ok(count)
     }
 
-    pub fn get_message_id(&self, chat_id: ChatId) -> Option<MessageId> {
-        let ids = self.message_ids.lock().unwrap();
-        ids.get(&chat_id).copied()
+    /// Collect the delivery state of all chats
+    pub fn export_delivery_state(&self) -> DeliveryState {
+        DeliveryState {
+            last_quotes: self.last_quotes.lock().unwrap().clone(),
+            dead_letters: self.get_dead_letters(),
+        }
     }
 
-    pub fn remove_message_id(&self, chat_id: ChatId) {
-        let mut ids = self.message_ids.lock().unwrap();
-        ids.remove(&chat_id);
+    /// Restore the delivery state of the chats that are still subscribed
+    pub fn import_delivery_state(&self, state: DeliveryState) {
+        let subscribers = self.subscribers.lock().unwrap().clone();
+        self.last_quotes.lock().unwrap().extend(
+            state
+                .last_quotes
+                .into_iter()
+                .filter(|(chat_id, _)| subscribers.contains(chat_id)),
+        );
+        self.dead_letters.lock().unwrap().extend(
+            state
+                .dead_letters
+                .into_iter()
+                .filter(|letter| subscribers.contains(&letter.chat_id))
+                .map(|letter| (letter.chat_id, letter)),
+        );
     }
 
-    pub fn set_next_send_time(&self, time: Instant) {
-        let mut next = self.next_send_time.lock().unwrap();
-        *next = Some(time);
+    /// Write all subscriptions to `path`, replacing the previous state file
+    pub fn save_to_file(&self, path: &Path) -> std::io::Result<()> {
+        let _guard = self.save_lock.lock().unwrap();
+        let json = serde_json::to_vec_pretty(&self.export_subscriptions())?;
+        // Write to a temporary file first so a crash never leaves a truncated state file
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, json)?;
+        std::fs::rename(&tmp_path, path)?;
+        tracing::info!(
+            "Saved {} subscriptions to {}",
+            self.subscriber_count(),
+            path.display()
+        );
+        Ok(())
     }
 
-    pub fn get_time_until_next(&self) -> Option<Duration> {
-        let next = self.next_send_time.lock().unwrap();
-        next.map(|t| {
-            let remaining = t.duration_since(Instant::now());
-            if remaining.is_zero() {
-                Duration::from_secs(0)
-            } else {
-                remaining
-            }
-        })
-    }
+    /// Restore subscriptions, the next send time and the outbox from a state
+    /// file written by [`Self::save_to_file`]. A missing file is not an error
+    /// and restores nothing. A send time that already passed is restored as
+    /// due now.
+    pub fn load_from_file(&self, path: &Path) -> Result<usize, String> {
+        if !path.exists() {
+            return Ok(0);
+        }
+        let contents =
+            std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut snapshot = SubscriptionsSnapshot::parse(&contents)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+        let next_send_at = snapshot.next_send_at;
+        // Only the state file resumes broadcasts, not `/admin import_subs`
+        let outbox = std::mem::take(&mut snapshot.outbox);
+        let count = self.import_subscriptions(snapshot)?;
+        self.outbox.lock().unwrap().extend(outbox);
 
-    pub fn get_periodic_message_text(&self) -> String {
-        self.message_text.clone()
+        if let Some(next_send_at) = next_send_at {
+            let remaining = (next_send_at - Utc::now()).to_std().unwrap_or_default();
+            self.set_next_send_time(Instant::now() + remaining);
+        }
+        Ok(count)
     }
 
-    pub fn format_periodic_message(&self, chat_id: ChatId) -> String {
+    pub fn format_periodic_message(&self, chat_id: ChatId, quotes: &Quotes) -> String {
         let current_count = self.get_message_count(chat_id);
-        let message_text = self.get_periodic_message_text();
-        format!(
-            "Периодическое сообщение #{}:\n{}",
-            current_count + 1,
-            message_text
+        let custom_template = self.templates.lock().unwrap().get(&chat_id).cloned();
+        let mut message_text = match custom_template {
+            Some(template) => self.render_for_pairs(chat_id, &template, quotes),
+            None => format!(
+                "{}\n\n{}",
+                self.message_format
+                    .escape(&self.get_periodic_message_text()),
+                self.render_for_pairs(chat_id, DEFAULT_PRICE_LINE, quotes)
+            ),
+        };
+        if let Some(footer) = self.footer.lock().unwrap().as_deref() {
+            message_text = format!("{}\n\n{}", message_text, footer);
+        }
+        let format = self.message_format;
+        format.render(
+            tr(self.languages.get(chat_id), "subs.header"),
+            &[
+                ("number", format.escape(&(current_count + 1).to_string())),
+                ("text", message_text),
+            ],
         )
     }
 }
 
-pub struct SubscriberModule {
-    manager: Arc<SubscriberManager>,
+/// Send a newly subscribed chat its periodic message right away instead of
+/// at the next broadcast
+pub async fn send_first_message(
+    ctx: &ModuleContext,
+    bot: &TelegramBot,
+    chat_id: ChatId,
+) -> Result<(), RequestError> {
+    let manager = &ctx.manager;
+    let pairs = manager.get_pairs(chat_id);
+    let quotes = fetch_quotes(&ctx.price_service, &pairs).await;
+    manager.refresh_trends(ctx.price_service.history(), &pairs);
+    manager.refresh_footer(&ctx.price_service, &pairs);
+    manager
+        .send_new_periodic_message(bot, chat_id, &quotes)
+        .await
+}
+
+/// In private chats anyone manages their own subscription and settings, in
+/// groups only chat administrators (including anonymous admins) may do it
+pub async fn can_manage_chat(
+    bot: &TelegramBot,
+    msg: &Message,
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    // Anonymous administrators send messages on behalf of the group itself
+    if msg.sender_chat.as_ref().map(|chat| chat.id) == Some(msg.chat.id) {
+        return Ok(true);
+    }
+
+    match msg.from.as_ref() {
+        Some(user) => is_chat_admin(bot, &msg.chat, user.id).await,
+        None => Ok(!msg.chat.is_group() && !msg.chat.is_supergroup()),
+    }
+}
+
+/// Whether `user_id` administers the group `chat`, always true in private chats
+pub async fn is_chat_admin(
+    bot: &TelegramBot,
+    chat: &teloxide::types::Chat,
+    user_id: UserId,
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    if !chat.is_group() && !chat.is_supergroup() {
+        return Ok(true);
+    }
+    let admins = bot.get_chat_administrators(chat.id).await?;
+    Ok(admins.iter().any(|member| member.user.id == user_id))
 }
 
+pub struct SubscriberModule;
+
 impl SubscriberModule {
-    pub fn new(manager: Arc<SubscriberManager>) -> Self {
-        Self { manager }
+    fn format_pairs_help(
+        manager: &SubscriberManager,
+        language: Language,
+        chat_id: ChatId,
+    ) -> String {
+        let format_list = |pairs: Vec<CurrencyPair>| {
+            pairs
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        tr_args(
+            language,
+            "subs.pairs_help",
+            &[
+                ("pairs", &format_list(manager.get_pairs(chat_id))),
+                ("available", &format_list(get_all_currency_pairs())),
+            ],
+        )
     }
 
-    fn format_status(&self, chat_id: ChatId) -> String {
-        if self.manager.is_subscribed(chat_id) {
-            let time_left = self.manager.get_time_until_next();
+    fn format_template_help(
+        manager: &SubscriberManager,
+        language: Language,
+        chat_id: ChatId,
+    ) -> String {
+        tr_args(
+            language,
+            "subs.template_help",
+            &[
+                ("template", &manager.get_template(chat_id)),
+                ("placeholders", &template::PLACEHOLDERS.join(", ")),
+            ],
+        )
+    }
+
+    /// Handle `/calendar [reset]`: the link of the chat's iCalendar feed,
+    /// served by the HTTP API at `http.public_url`
+    fn apply_calendar(
+        ctx: &ModuleContext,
+        language: Language,
+        chat_id: ChatId,
+        args: &str,
+    ) -> String {
+        let http = &ctx.config.http;
+        let base = match (&http.public_url, http.addr) {
+            (Some(base), Some(_)) if cfg!(feature = "http-api") => base.trim_end_matches('/'),
+            _ => return tr(language, "subs.calendar_unavailable").to_string(),
+        };
+        let (key, token) = match args {
+            "reset" => (
+                "subs.calendar_reset",
+                ctx.manager.reset_calendar_token(chat_id),
+            ),
+            _ => ("subs.calendar", ctx.manager.get_calendar_token(chat_id)),
+        };
+        let url = format!("{}/calendar/{}.ics", base, token);
+        tr_args(language, key, &[("url", &url)])
+    }
+
+    /// Handle `/language [ru|en]`, replying in the newly chosen language
+    fn apply_language(manager: &SubscriberManager, chat_id: ChatId, args: &str) -> String {
+        let languages = manager.languages();
+        let current = languages.get(chat_id);
+        let format_current = |language: Language| {
+            tr_args(
+                language,
+                "language.current",
+                &[("language", &tr(language, "language.name"))],
+            )
+        };
+        if args.is_empty() {
+            return format_current(current);
+        }
+        match Language::from_code(args) {
+            Some(language) => {
+                languages.set(chat_id, language);
+                tr_args(
+                    language,
+                    "language.changed",
+                    &[("language", &tr(language, "language.name"))],
+                )
+            }
+            None => format!(
+                "{}\n\n{}",
+                tr_args(current, "language.unknown", &[("language", &args)]),
+                format_current(current)
+            ),
+        }
+    }
+
+    fn format_status(manager: &SubscriberManager, language: Language, chat_id: ChatId) -> String {
+        if manager.is_subscribed(chat_id) {
+            let time_left = manager.get_time_until_next();
             let time_text = match time_left {
-                Some(d) if d.as_secs() > 0 => {
-                    let minutes = d.as_secs() / 60;
-                    let seconds = d.as_secs() % 60;
-                    format!("Следующее сообщение через {} мин {} сек", minutes, seconds)
-                }
-                Some(_) => "Сообщение будет отправлено скоро...".to_string(),
-                None => "Информация о времени рассылки недоступна".to_string(),
+                Some(d) if d.as_secs() > 0 => tr_args(
+                    language,
+                    "subs.next_in",
+                    &[
+                        ("minutes", &(d.as_secs() / 60)),
+                        ("seconds", &(d.as_secs() % 60)),
+                    ],
+                ),
+                Some(_) => tr(language, "subs.next_soon").to_string(),
+                None => tr(language, "subs.next_unknown").to_string(),
             };
-            format!(
-                "Вы подписаны на рассылку.\n{}\nВсего подписчиков: {}",
-                time_text,
-                self.manager.subscriber_count()
+            tr_args(
+                language,
+                "subs.status_subscribed",
+                &[("next", &time_text), ("count", &manager.subscriber_count())],
             )
         } else {
-            "Вы не подписаны на рассылку.".to_string()
+            tr(language, "subs.status_not_subscribed").to_string()
         }
     }
 }
@@ -207,43 +1822,174 @@ impl Module for SubscriberModule {
     }
 
     fn commands(&self) -> Vec<&str> {
-        vec!["/subscribe", "/unsubscribe", "/status"]
+        vec![
+            "/subscribe",
+            "/unsubscribe",
+            "/status",
+            "/template",
+            "/pairs",
+            "/language",
+            "/calendar",
+        ]
+    }
+
+    fn command_descriptions(&self, language: Language) -> Vec<(&str, &str)> {
+        vec![
+            ("/subscribe", tr(language, "subs.menu.subscribe")),
+            ("/unsubscribe", tr(language, "subs.menu.unsubscribe")),
+            ("/status", tr(language, "subs.menu.status")),
+            ("/template", tr(language, "subs.menu.template")),
+            ("/pairs", tr(language, "subs.menu.pairs")),
+            ("/language", tr(language, "subs.menu.language")),
+            ("/calendar", tr(language, "subs.menu.calendar")),
+        ]
     }
 
-    async fn handle(&self, bot: Bot, msg: Message) -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn description(&self, language: Language) -> Option<&str> {
+        Some(tr(language, "subs.section"))
+    }
+
+    fn usage(&self, language: Language) -> Vec<String> {
+        [
+            "subs.usage.subscribe",
+            "subs.usage.unsubscribe",
+            "subs.usage.status",
+            "subs.usage.template",
+            "subs.usage.pairs",
+            "subs.usage.language",
+            "subs.usage.calendar",
+        ]
+        .into_iter()
+        .map(|key| tr(language, key).to_string())
+        .collect()
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let manager = &ctx.manager;
         let chat_id = msg.chat.id;
+        let language = ctx.languages.get(chat_id);
+
+        if let Some(command) = msg.text().and_then(ParsedCommand::parse) {
+            let args = command.args;
 
-        if let Some(text) = msg.text() {
-            match text {
+            match command.name {
+                "/subscribe" | "/unsubscribe" | "/template" | "/pairs" | "/language"
+                    if !can_manage_chat(&bot, &msg).await? =>
+                {
+                    ctx.send_reply(
+                        &bot,
+                        &msg,
+                        reply(&bot, &msg, tr(language, "subs.group_admins_only")),
+                    )
+                    .await?;
+                }
+                "/calendar" if args == "reset" && !can_manage_chat(&bot, &msg).await? => {
+                    ctx.send_reply(
+                        &bot,
+                        &msg,
+                        reply(&bot, &msg, tr(language, "subs.group_admins_only")),
+                    )
+                    .await?;
+                }
                 "/subscribe" => {
-                    let action = self.manager.subscribe(chat_id);
+                    let action = manager.subscribe(chat_id);
+                    let topic_changed = manager.set_thread_id(chat_id, topic_of(&msg));
                     let response = match action {
-                        SubscriptionAction::Subscribed => "Вы успешно подписались на рассылку!",
-                        SubscriptionAction::AlreadySubscribed => "Вы уже подписаны на рассылку.",
+                        SubscriptionAction::Subscribed => tr(language, "subs.subscribed"),
+                        SubscriptionAction::AlreadySubscribed if topic_changed => {
+                            tr(language, "subs.topic_changed")
+                        }
+                        SubscriptionAction::AlreadySubscribed => {
+                            tr(language, "subs.already_subscribed")
+                        }
                         _ => unreachable!(),
                     };
-                    bot.send_message(chat_id, response).await?;
+                    ctx.send_reply(&bot, &msg, reply(&bot, &msg, response))
+                        .await?;
 
-                    if let SubscriptionAction::Subscribed = action {
-                        let initial_message = self.manager.format_periodic_message(chat_id);
-                        let message = bot.send_message(chat_id, &initial_message).await?;
-                        self.manager.set_message_id(chat_id, message.id);
-                        self.manager.increment_message_counter(chat_id);
+                    if action == SubscriptionAction::Subscribed || topic_changed {
+                        send_first_message(ctx, &bot, chat_id).await?;
                     }
                 }
                 "/unsubscribe" => {
-                    let action = self.manager.unsubscribe(chat_id);
+                    let action = manager.unsubscribe(chat_id);
                     let response = match action {
-                        SubscriptionAction::Unsubscribed => "Вы успешно отписались от рассылки.",
-                        SubscriptionAction::NotSubscribed => "Вы не были подписаны на рассылку.",
+                        SubscriptionAction::Unsubscribed => tr(language, "subs.unsubscribed"),
+                        SubscriptionAction::NotSubscribed => tr(language, "subs.not_subscribed"),
                         _ => unreachable!(),
                     };
-                    bot.send_message(chat_id, response).await?;
-                    self.manager.remove_message_id(chat_id);
+                    ctx.send_reply(&bot, &msg, reply(&bot, &msg, response))
+                        .await?;
+
+                    if let Some(message_id) = manager.get_message_id(chat_id) {
+                        if manager.pins_messages() {
+                            if let Err(e) =
+                                bot.unpin_chat_message(chat_id).message_id(message_id).await
+                            {
+                                tracing::warn!(
+                                    "Failed to unpin periodic message in {}: {}",
+                                    chat_id,
+                                    e
+                                );
+                            }
+                        }
+                    }
+                    manager.remove_message_id(chat_id);
                 }
                 "/status" => {
-                    let status = self.format_status(chat_id);
-                    bot.send_message(chat_id, status).await?;
+                    let status = Self::format_status(manager, language, chat_id);
+                    ctx.send_reply(&bot, &msg, reply(&bot, &msg, status))
+                        .await?;
+                }
+                "/template" => {
+                    let response = match args {
+                        "" => Self::format_template_help(manager, language, chat_id),
+                        "reset" => {
+                            manager.reset_template(chat_id);
+                            tr(language, "subs.template_reset").to_string()
+                        }
+                        template => {
+                            manager.set_template(chat_id, template.to_string());
+                            tr(language, "subs.template_updated").to_string()
+                        }
+                    };
+                    ctx.send_reply(&bot, &msg, reply(&bot, &msg, response))
+                        .await?;
+                }
+                "/pairs" => {
+                    let response = if args.is_empty() {
+                        Self::format_pairs_help(manager, language, chat_id)
+                    } else {
+                        match parse_pairs(args) {
+                            Ok(pairs) if !pairs.is_empty() => {
+                                manager.set_pairs(chat_id, pairs);
+                                tr(language, "subs.pairs_updated").to_string()
+                            }
+                            Ok(_) => Self::format_pairs_help(manager, language, chat_id),
+                            Err(token) => format!(
+                                "{}\n\n{}",
+                                tr_args(language, "unknown_pair", &[("pair", &token)]),
+                                Self::format_pairs_help(manager, language, chat_id)
+                            ),
+                        }
+                    };
+                    ctx.send_reply(&bot, &msg, reply(&bot, &msg, response))
+                        .await?;
+                }
+                "/language" => {
+                    let response = Self::apply_language(manager, chat_id, args);
+                    ctx.send_reply(&bot, &msg, reply(&bot, &msg, response))
+                        .await?;
+                }
+                "/calendar" => {
+                    let response = Self::apply_calendar(ctx, language, chat_id, args);
+                    ctx.send_reply(&bot, &msg, reply(&bot, &msg, response))
+                        .await?;
                 }
                 _ => {}
             }
@@ -255,6 +2001,8 @@ impl Module for SubscriberModule {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bot_modules::alerts::{AlertCondition, AlertRule, AlertState};
+    use crate::bot_modules::testing::MockBot;
     use teloxide::types::ChatId;
 
     #[test]
@@ -384,29 +2132,649 @@ mod tests {
         let chat_id = ChatId(12345);
 
         manager.subscribe(chat_id);
-        let message = manager.format_periodic_message(chat_id);
+        let message = manager.format_periodic_message(chat_id, &Quotes::new());
         assert!(message.contains("Периодическое сообщение #1:"));
         assert!(message.contains("Периодическое сообщение от бота"));
 
         manager.increment_message_counter(chat_id);
-        let message = manager.format_periodic_message(chat_id);
+        let message = manager.format_periodic_message(chat_id, &Quotes::new());
         assert!(message.contains("Периодическое сообщение #2:"));
     }
 
     #[test]
     fn test_module_name() {
-        let manager = Arc::new(SubscriberManager::new("Test message".to_string()));
-        let module = SubscriberModule::new(manager);
-        assert_eq!(module.name(), "Subscriber");
+        assert_eq!(SubscriberModule.name(), "Subscriber");
     }
 
     #[test]
     fn test_module_commands() {
-        let manager = Arc::new(SubscriberManager::new("Test message".to_string()));
-        let module = SubscriberModule::new(manager);
         assert_eq!(
-            module.commands(),
-            vec!["/subscribe", "/unsubscribe", "/status"]
+            SubscriberModule.commands(),
+            vec![
+                "/subscribe",
+                "/unsubscribe",
+                "/status",
+                "/template",
+                "/pairs",
+                "/language",
+                "/calendar"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_template() {
+        let manager = SubscriberManager::new("Default text".to_string());
+        let chat_id = ChatId(12345);
+        let other_chat_id = ChatId(54321);
+
+        manager.set_template(chat_id, "Custom {pair}".to_string());
+        assert_eq!(manager.get_template(chat_id), "Custom {pair}");
+        assert_eq!(manager.get_template(other_chat_id), "Default text");
+        assert!(manager
+            .format_periodic_message(chat_id, &Quotes::new())
+            .contains("Custom USD/RUB"));
+
+        manager.reset_template(chat_id);
+        assert_eq!(manager.get_template(chat_id), "Default text");
+    }
+
+    #[test]
+    fn test_export_import_roundtrip() {
+        let source = SubscriberManager::new("Test message".to_string());
+        let chat_id = ChatId(111);
+        source.subscribe(chat_id);
+        source.increment_message_counter(chat_id);
+        source.set_message_id(chat_id, MessageId(42));
+        source.set_template(chat_id, "{pair}: {price}".to_string());
+        source.subscribe(ChatId(222));
+
+        let json = serde_json::to_string(&source.export_subscriptions()).unwrap();
+        let snapshot: SubscriptionsSnapshot = serde_json::from_str(&json).unwrap();
+
+        let target = SubscriberManager::new("Test message".to_string());
+        assert_eq!(target.import_subscriptions(snapshot), Ok(2));
+        assert_eq!(target.subscriber_count(), 2);
+        assert_eq!(target.get_message_count(chat_id), 1);
+        assert_eq!(target.get_message_id(chat_id), Some(MessageId(42)));
+        assert_eq!(target.get_template(chat_id), "{pair}: {price}");
+        assert_eq!(target.get_message_id(ChatId(222)), None);
+        assert_eq!(
+            target.get_subscribed_at(chat_id),
+            source.get_subscribed_at(chat_id)
+        );
+        assert!(target.get_subscribed_at(chat_id).is_some());
+    }
+
+    #[test]
+    fn test_state_file_restores_next_send_time() {
+        let path = std::env::temp_dir().join(format!(
+            "currency-bot-next-send-{}.json",
+            std::process::id()
+        ));
+        let source = SubscriberManager::new("Test message".to_string());
+        source.subscribe(ChatId(111));
+        source.set_next_send_time(Instant::now() + Duration::from_secs(300));
+        source.save_to_file(&path).unwrap();
+
+        let target = SubscriberManager::new("Test message".to_string());
+        assert_eq!(target.load_from_file(&path), Ok(1));
+        std::fs::remove_file(&path).unwrap();
+
+        let remaining = target.get_time_until_next().unwrap();
+        assert!(remaining > Duration::from_secs(290));
+        assert!(remaining <= Duration::from_secs(300));
+    }
+
+    #[test]
+    fn test_jitter_offset_is_stable_and_within_window() {
+        let window = Duration::from_secs(60);
+        for id in 0..100 {
+            let offset = jitter_offset(ChatId(id), window);
+            assert!(offset < window);
+            assert_eq!(offset, jitter_offset(ChatId(id), window));
+        }
+        assert_eq!(jitter_offset(ChatId(1), Duration::ZERO), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_parse_migrates_version_1() {
+        let v1 = r#"{
+            "version": 1,
+            "subscriptions": [{"chat_id": 1, "message_count": 3, "message_id": null, "template": null}],
+            "alerts": [{"chat_id": 1, "rules": [
+                {"pair": "USD2RUB", "condition": {"type": "above", "price": 100.0}, "repeat": true, "triggered": true},
+                {"pair": "USD2RUB", "condition": {"type": "below", "price": 90.0}, "repeat": false}
+            ]}]
+        }"#;
+        let snapshot = SubscriptionsSnapshot::parse(v1.as_bytes()).unwrap();
+        assert_eq!(snapshot.version, SNAPSHOT_VERSION);
+        let states: Vec<AlertState> = snapshot.alerts[0]
+            .rules
+            .iter()
+            .map(|rule| rule.state)
+            .collect();
+        assert_eq!(states, vec![AlertState::Triggered, AlertState::Armed]);
+
+        let current = serde_json::to_vec(&snapshot).unwrap();
+        let reparsed = SubscriptionsSnapshot::parse(&current).unwrap();
+        assert_eq!(reparsed.alerts[0].rules, snapshot.alerts[0].rules);
+        assert!(SubscriptionsSnapshot::parse(br#"{"version": 3, "subscriptions": []}"#).is_err());
+        assert!(SubscriptionsSnapshot::parse(br#"{"subscriptions": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_import_rejects_unknown_version() {
+        let manager = SubscriberManager::new("Test message".to_string());
+        let snapshot = SubscriptionsSnapshot {
+            version: SNAPSHOT_VERSION + 1,
+            subscriptions: Vec::new(),
+            next_send_at: None,
+            alerts: Vec::new(),
+            languages: HashMap::new(),
+            disabled_modules: Vec::new(),
+            preferences: HashMap::new(),
+            outbox: Vec::new(),
+            calendar_tokens: HashMap::new(),
+        };
+        assert!(manager.import_subscriptions(snapshot).is_err());
+    }
+
+    #[test]
+    fn test_pairs_default_and_custom() {
+        let manager = SubscriberManager::new("Test message".to_string());
+        let chat_id = ChatId(12345);
+        assert_eq!(manager.get_pairs(chat_id), get_all_currency_pairs());
+
+        manager.set_pairs(chat_id, vec![CurrencyPair::USD2RUB]);
+        assert_eq!(manager.get_pairs(chat_id), vec![CurrencyPair::USD2RUB]);
+    }
+
+    #[test]
+    fn test_quiet_hours() {
+        let night = QuietHours::from_str("22-8").unwrap();
+        assert!(night.contains(23));
+        assert!(night.contains(0));
+        assert!(!night.contains(8));
+        assert!(!night.contains(12));
+        let lunch = QuietHours::from_str("13-14").unwrap();
+        assert!(lunch.contains(13));
+        assert!(!lunch.contains(14));
+        assert_eq!(night.to_string(), "22:00–08:00");
+        assert!(QuietHours::from_str("8-8").is_err());
+        assert!(QuietHours::from_str("22-24").is_err());
+        assert!(QuietHours::from_str("night").is_err());
+    }
+
+    #[test]
+    fn test_quiet_hours_follow_the_chat_timezone() {
+        let manager = SubscriberManager::new("Test message".to_string());
+        let chat_id = ChatId(111);
+        manager.set_quiet_hours(chat_id, QuietHours::from_str("22-8").ok());
+        let now = "2026-01-15T20:30:00Z".parse::<DateTime<Utc>>().unwrap();
+
+        // 23:30 in Moscow, 15:30 in New York
+        let in_timezone = |offset: &str| {
+            let preferences = Preferences {
+                timezone: offset.parse().ok(),
+                ..Preferences::default()
+            };
+            manager.set_preferences(chat_id, preferences);
+            manager.in_quiet_hours(chat_id, now)
+        };
+        assert!(in_timezone("+03:00"));
+        assert!(!in_timezone("-05:00"));
+
+        manager.set_quiet_hours(chat_id, None);
+        assert!(!in_timezone("+03:00"));
+    }
+
+    #[test]
+    fn test_settings_and_alerts_survive_export_import() {
+        let source = SubscriberManager::new("Test message".to_string());
+        let chat_id = ChatId(111);
+        source.subscribe(chat_id);
+        source.set_delivery_mode(chat_id, DeliveryMode::New);
+        source.set_quiet_hours(chat_id, QuietHours::from_str("23-7").ok());
+        source.set_thread_id(chat_id, Some(ThreadId(MessageId(5))));
+        source.set_daily_summary(chat_id, true);
+        // Opting in doesn't deliver the summary to chats without a subscription
+        source.set_daily_summary(ChatId(222), true);
+        assert_eq!(source.get_daily_summary_chats(), vec![chat_id]);
+        source.set_weekly_report(chat_id, true);
+        // Alerts are kept for chats without a subscription too
+        let alert = AlertRule {
+            state: AlertState::Triggered,
+            cooldown_minutes: Some(30),
+            ..AlertRule::new(
+                CurrencyPair::USD2RUB,
+                AlertCondition::Above { price: 100.0 },
+                true,
+            )
+        };
+        source.alerts().add(ChatId(333), alert.clone()).unwrap();
+
+        let target = SubscriberManager::new("Test message".to_string());
+        target
+            .import_subscriptions(source.export_subscriptions())
+            .unwrap();
+        assert_eq!(target.get_delivery_mode(chat_id), DeliveryMode::New);
+        assert_eq!(
+            target.get_quiet_hours(chat_id),
+            Some(QuietHours { start: 23, end: 7 })
+        );
+        assert_eq!(target.get_thread_id(chat_id), Some(ThreadId(MessageId(5))));
+        assert!(target.get_daily_summary(chat_id));
+        assert!(target.get_weekly_report(chat_id));
+        assert_eq!(target.alerts().list(ChatId(333)), vec![alert]);
+
+        // Snapshots written before delivery modes existed default to editing
+        let legacy: SubscriptionRecord = serde_json::from_str(
+            r#"{"chat_id":1,"message_count":0,"message_id":null,"template":null}"#,
+        )
+        .unwrap();
+        assert_eq!(legacy.delivery, DeliveryMode::Edit);
+    }
+
+    #[test]
+    fn test_language_command_and_persistence() {
+        let manager = SubscriberManager::new("Test message".to_string());
+        let chat_id = ChatId(-100);
+        let apply_language = |args| SubscriberModule::apply_language(&manager, chat_id, args);
+
+        assert!(apply_language("").starts_with("Язык: русский"));
+        assert!(apply_language("de").starts_with("❌"));
+        assert_eq!(apply_language("en"), "Language changed: English");
+        manager.subscribe(chat_id);
+        assert!(manager
+            .format_periodic_message(chat_id, &Quotes::new())
+            .starts_with("Periodic message #1:"));
+
+        let json = serde_json::to_string(&manager.export_subscriptions()).unwrap();
+        let target = SubscriberManager::new("Test message".to_string());
+        target
+            .import_subscriptions(serde_json::from_str(&json).unwrap())
+            .unwrap();
+        assert_eq!(target.languages().get(chat_id), Language::En);
+    }
+
+    #[test]
+    fn test_calendar_tokens() {
+        let ctx = ModuleContext::default();
+        let chat_id = ChatId(-100);
+        let apply_calendar = |ctx: &ModuleContext, args| {
+            SubscriberModule::apply_calendar(ctx, Language::En, chat_id, args)
+        };
+        assert_eq!(
+            apply_calendar(&ctx, ""),
+            "The message calendar is not enabled on this bot."
+        );
+        assert!(ctx.manager.get_calendar_chat("").is_none());
+
+        let mut config = (*ctx.config).clone();
+        config.http.addr = Some("127.0.0.1:8080".parse().unwrap());
+        config.http.public_url = Some("https://bot.example.com/".to_string());
+        let ctx = ModuleContext::new(
+            Arc::new(config),
+            ctx.price_service.clone(),
+            ctx.manager.clone(),
+        );
+        let link = apply_calendar(&ctx, "");
+        // Only served with the HTTP API built in
+        if !cfg!(feature = "http-api") {
+            assert_eq!(link, "The message calendar is not enabled on this bot.");
+            return;
+        }
+        let token = ctx.manager.get_calendar_token(chat_id);
+        assert_eq!(token.len(), 32);
+        assert!(link.contains(&format!(
+            "\nhttps://bot.example.com/calendar/{}.ics\n",
+            token
+        )));
+        assert_eq!(ctx.manager.get_calendar_chat(&token), Some(chat_id));
+
+        // A new link replaces the old one
+        assert!(apply_calendar(&ctx, "reset").starts_with("📅 New calendar link"));
+        assert!(ctx.manager.get_calendar_chat(&token).is_none());
+        let token = ctx.manager.get_calendar_token(chat_id);
+
+        // The link survives a restart before the chat subscribes
+        let json = serde_json::to_string(&ctx.manager.export_subscriptions()).unwrap();
+        let target = SubscriberManager::new("Test message".to_string());
+        target
+            .import_subscriptions(serde_json::from_str(&json).unwrap())
+            .unwrap();
+        assert_eq!(target.get_calendar_chat(&token), Some(chat_id));
+    }
+
+    #[test]
+    fn test_profile_survives_export_and_import() {
+        let manager = SubscriberManager::new("Test message".to_string());
+        let chat_id = ChatId(12345);
+        assert_eq!(manager.profile(chat_id), UserProfile::default());
+
+        manager.languages().set(chat_id, Language::En);
+        manager.set_delivery_mode(chat_id, DeliveryMode::Replace);
+        let preferences = Preferences {
+            timezone: "+03:00".parse().ok(),
+            default_pair: Some(CurrencyPair::USDTe2RUB),
+            default_amount: Some(1000.0),
+        };
+        manager.set_preferences(chat_id, preferences.clone());
+        let profile = manager.profile(chat_id);
+        assert_eq!(profile.language, Language::En);
+        assert_eq!(profile.default_pair, Some(CurrencyPair::USDTe2RUB));
+        assert_eq!(profile.delivery, DeliveryMode::Replace);
+
+        // Preferences are kept for chats that aren't subscribed too
+        let json = serde_json::to_string(&manager.export_subscriptions()).unwrap();
+        assert!(json.contains(r#""timezone":"+03:00""#));
+        let target = SubscriberManager::new("Test message".to_string());
+        target
+            .import_subscriptions(SubscriptionsSnapshot::parse(json.as_bytes()).unwrap())
+            .unwrap();
+        assert_eq!(target.get_preferences(chat_id), preferences);
+
+        target.set_preferences(chat_id, Preferences::default());
+        assert!(target.export_subscriptions().preferences.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_broadcast_resumes_after_restart() {
+        let path =
+            std::env::temp_dir().join(format!("currency-bot-outbox-{}.json", std::process::id()));
+        let source =
+            SubscriberManager::new("Test message".to_string()).with_state_file(path.clone());
+        let chats = vec![ChatId(1), ChatId(2), ChatId(3)];
+        for chat_id in &chats {
+            source.subscribe(*chat_id);
+        }
+        // Stopped after the first chat got the text
+        let id = source.enqueue_broadcast("Maintenance tonight", chats);
+        source.mark_broadcast_sent(id, ChatId(1));
+        source.save_state();
+
+        let target =
+            SubscriberManager::new("Test message".to_string()).with_state_file(path.clone());
+        assert_eq!(target.load_from_file(&path), Ok(3));
+        let pending = target.pending_broadcasts();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].text, "Maintenance tonight");
+        assert_eq!(pending[0].pending, [ChatId(2), ChatId(3)]);
+
+        let mock = MockBot::start().await;
+        target.resume_broadcasts(&mock.bot()).await;
+        let mut sent: Vec<_> = mock
+            .requests()
+            .iter()
+            .map(|request| request.body["chat_id"].as_i64().unwrap())
+            .collect();
+        sent.sort();
+        assert_eq!(sent, [2, 3]);
+        assert!(target.pending_broadcasts().is_empty());
+
+        // Finished broadcasts leave the state file
+        let restarted = SubscriberManager::new("Test message".to_string());
+        restarted.load_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(restarted.pending_broadcasts().is_empty());
+    }
+
+    #[test]
+    fn test_periodic_message_includes_prices_and_deltas() {
+        let manager = SubscriberManager::new("Курсы".to_string());
+        let chat_id = ChatId(12345);
+        manager.subscribe(chat_id);
+        manager.set_pairs(chat_id, vec![CurrencyPair::USD2RUB]);
+
+        let first = Quotes::from([(CurrencyPair::USD2RUB, 90.0)]);
+        let message = manager.format_periodic_message(chat_id, &first);
+        assert!(message.ends_with("💰 USD/RUB: 90.00"));
+
+        manager.remember_quotes(chat_id, &first);
+        let second = Quotes::from([(CurrencyPair::USD2RUB, 90.5)]);
+        let message = manager.format_periodic_message(chat_id, &second);
+        assert!(message.ends_with("💰 USD/RUB: 90.50 ▲ +0.50 (+0.56%)"));
+    }
+
+    #[test]
+    fn test_periodic_message_sparkline() {
+        let manager = SubscriberManager::new("Курсы".to_string())
+            .with_sparkline_window(Some(chrono::Duration::hours(24)));
+        let chat_id = ChatId(12345);
+        manager.set_pairs(chat_id, vec![CurrencyPair::USD2RUB]);
+        let history = PriceHistory::default();
+        let now = Local::now();
+        history.record(
+            &CurrencyPair::USD2RUB,
+            90.0,
+            now - chrono::Duration::hours(1),
+        );
+        history.record(&CurrencyPair::USD2RUB, 91.0, now);
+
+        let quotes = Quotes::from([(CurrencyPair::USD2RUB, 91.0)]);
+        manager.refresh_trends(&history, &[CurrencyPair::USD2RUB]);
+        let message = manager.format_periodic_message(chat_id, &quotes);
+        assert!(message.ends_with("💰 USD/RUB: 91.00 ▁█"));
+
+        // Without a window there is nothing to show
+        let manager = SubscriberManager::new("Курсы".to_string());
+        manager.set_pairs(chat_id, vec![CurrencyPair::USD2RUB]);
+        manager.refresh_trends(&history, &[CurrencyPair::USD2RUB]);
+        let message = manager.format_periodic_message(chat_id, &quotes);
+        assert!(message.ends_with("💰 USD/RUB: 91.00"));
+    }
+
+    #[test]
+    fn test_periodic_message_change_24h() {
+        let manager = SubscriberManager::new("Курсы".to_string());
+        let chat_id = ChatId(12345);
+        manager.set_pairs(chat_id, vec![CurrencyPair::USD2RUB]);
+        manager.set_template(chat_id, "{pair} {change_24h}".to_string());
+        let history = PriceHistory::default();
+        let now = Local::now();
+        history.record(
+            &CurrencyPair::USD2RUB,
+            90.0,
+            now - chrono::Duration::hours(12),
+        );
+        history.record(&CurrencyPair::USD2RUB, 91.0, now);
+
+        let quotes = Quotes::from([(CurrencyPair::USD2RUB, 91.0)]);
+        let message = manager.format_periodic_message(chat_id, &quotes);
+        assert!(!message.contains('%'));
+
+        manager.refresh_trends(&history, &[CurrencyPair::USD2RUB]);
+        let message = manager.format_periodic_message(chat_id, &quotes);
+        assert!(message.ends_with("USD/RUB +1.11%"));
+    }
+
+    #[tokio::test]
+    async fn test_periodic_message_footer() {
+        use crate::price_service::providers::fixed::FixedProvider;
+
+        let mut service = PriceService::new();
+        let pairs = [CurrencyPair::USD2RUB, CurrencyPair::USDTe2RUB];
+        assert_eq!(
+            render_footer(
+                "Source: {source}",
+                &service,
+                &pairs,
+                MessageFormat::Plain,
+                None
+            ),
+            "Source: —"
+        );
+        service.add_provider(Arc::new(FixedProvider::new(90.0)));
+        for pair in &pairs {
+            service.get_price(pair).await.unwrap();
+        }
+        let time = service.history().latest(&pairs[1]).unwrap().time;
+        assert_eq!(
+            render_footer(
+                "Source: {source}, {time}",
+                &service,
+                &pairs,
+                MessageFormat::Plain,
+                None
+            ),
+            format!("Source: Fixed, {}", time.format("%d.%m.%Y %H:%M"))
+        );
+        // A chat's timezone moves the time, not the sources
+        let timezone = "+05:00".parse::<FixedOffset>().unwrap();
+        assert_eq!(
+            render_footer(
+                "{time}",
+                &service,
+                &pairs,
+                MessageFormat::Plain,
+                Some(timezone)
+            ),
+            time.with_timezone(&timezone)
+                .format("%d.%m.%Y %H:%M")
+                .to_string()
+        );
+
+        let manager = SubscriberManager::new("Курсы".to_string())
+            .with_footer(Some("Source: {source}".to_string()));
+        let chat_id = ChatId(12345);
+        manager.set_pairs(chat_id, vec![CurrencyPair::USD2RUB]);
+        manager.refresh_footer(&service, &[CurrencyPair::USD2RUB]);
+        let quotes = Quotes::from([(CurrencyPair::USD2RUB, 90.0)]);
+        let message = manager.format_periodic_message(chat_id, &quotes);
+        assert!(message.ends_with("💰 USD/RUB: 90.00\n\nSource: Fixed"));
+    }
+
+    #[test]
+    fn test_save_and_load_file() {
+        let path = std::env::temp_dir().join(format!(
+            "currency-bot-subscribers-{}.json",
+            std::process::id()
+        ));
+        let source = SubscriberManager::new("Test message".to_string());
+        source.subscribe(ChatId(111));
+        source.set_pairs(ChatId(111), vec![CurrencyPair::USD2RUB]);
+        source.save_to_file(&path).unwrap();
+
+        let target = SubscriberManager::new("Test message".to_string());
+        assert_eq!(target.load_from_file(&path), Ok(1));
+        assert!(target.is_subscribed(ChatId(111)));
+        assert_eq!(target.get_pairs(ChatId(111)), vec![CurrencyPair::USD2RUB]);
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(target.load_from_file(&path), Ok(0));
+    }
+
+    #[tokio::test]
+    async fn test_send_concurrently_bounds_concurrency() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let in_flight = AtomicUsize::new(0);
+        let max_in_flight = AtomicUsize::new(0);
+
+        let results = send_concurrently((0..10).collect(), 3, |item: u32| {
+            let in_flight = &in_flight;
+            let max_in_flight = &max_in_flight;
+            async move {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                item * 2
+            }
+        })
+        .await;
+
+        let mut results = results;
+        results.sort();
+        assert_eq!(results, (0..10).map(|i| i * 2).collect::<Vec<_>>());
+        assert!(max_in_flight.load(Ordering::SeqCst) <= 3);
+    }
+
+    #[test]
+    fn test_retry_delay_backoff() {
+        assert_eq!(retry_delay(1), RETRY_BASE_DELAY);
+        assert_eq!(retry_delay(2), RETRY_BASE_DELAY * 2);
+        assert_eq!(retry_delay(3), RETRY_BASE_DELAY * 4);
+    }
+
+    #[test]
+    fn test_migrate_chat() {
+        let manager = SubscriberManager::new("Test message".to_string());
+        let (group, supergroup) = (ChatId(-111), ChatId(-100111));
+        manager.subscribe(group);
+        manager.increment_message_counter(group);
+        manager.set_pairs(group, vec![CurrencyPair::USD2RUB]);
+        manager.set_message_id(group, MessageId(7));
+        manager.set_weekly_report(group, true);
+        manager.languages().set(group, Language::En);
+        let preferences = Preferences {
+            default_pair: Some(CurrencyPair::USD2RUB),
+            ..Preferences::default()
+        };
+        manager.set_preferences(group, preferences.clone());
+        let rule = AlertRule::new(
+            CurrencyPair::USD2RUB,
+            AlertCondition::Above { price: 100.0 },
+            false,
         );
+        manager.alerts().add(group, rule.clone()).unwrap();
+
+        manager.migrate_chat(group, supergroup);
+        assert_eq!(manager.get_subscribers(), vec![supergroup]);
+        assert_eq!(manager.get_message_count(supergroup), 1);
+        assert_eq!(manager.get_pairs(supergroup), vec![CurrencyPair::USD2RUB]);
+        assert_eq!(manager.get_message_id(supergroup), None);
+        assert_eq!(manager.get_message_id(group), None);
+        assert!(manager.get_weekly_report(supergroup));
+        assert_eq!(manager.languages().get(supergroup), Language::En);
+        assert_eq!(manager.languages().get(group), Language::default());
+        assert_eq!(manager.get_preferences(supergroup), preferences);
+        assert!(manager.get_preferences(group).is_empty());
+        assert_eq!(manager.alerts().list(supergroup), vec![rule]);
+        assert!(manager.alerts().list(group).is_empty());
+    }
+
+    #[test]
+    fn test_dead_letters() {
+        let manager = SubscriberManager::new("Test message".to_string());
+        let chat_id = ChatId(12345);
+        manager.subscribe(chat_id);
+
+        manager.record_dead_letter(chat_id, MAX_DELIVERY_ATTEMPTS, "timeout".to_string());
+        let dead_letters = manager.get_dead_letters();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].chat_id, chat_id);
+        assert_eq!(dead_letters[0].error, "timeout");
+
+        manager.clear_dead_letters();
+        assert!(manager.get_dead_letters().is_empty());
+
+        manager.record_dead_letter(chat_id, 1, "blocked".to_string());
+        manager.unsubscribe(chat_id);
+        assert!(manager.get_dead_letters().is_empty());
+    }
+
+    #[test]
+    fn test_delivery_state_round_trip() {
+        let source = SubscriberManager::new("Test message".to_string());
+        let quotes = Quotes::from([(CurrencyPair::USD2RUB, 90.0)]);
+        for chat_id in [ChatId(111), ChatId(222)] {
+            source.subscribe(chat_id);
+            source.remember_quotes(chat_id, &quotes);
+        }
+        source.record_dead_letter(ChatId(222), 1, "blocked".to_string());
+        let json = serde_json::to_string(&source.export_delivery_state()).unwrap();
+
+        // Chats that unsubscribed in the meantime are left out
+        let target = SubscriberManager::new("Test message".to_string());
+        target.subscribe(ChatId(111));
+        target.import_delivery_state(serde_json::from_str(&json).unwrap());
+        let second = Quotes::from([(CurrencyPair::USD2RUB, 90.5)]);
+        assert!(target
+            .format_periodic_message(ChatId(111), &second)
+            .ends_with("▲ +0.50 (+0.56%)"));
+        assert_eq!(target.export_delivery_state().last_quotes.len(), 1);
+        assert!(target.get_dead_letters().is_empty());
     }
 }
@@ -0,0 +1,248 @@
+use super::channels::ChannelPublisher;
+use super::format::{MessageFormat, PriceFormats};
+use super::{SubscriberManager, TelegramBot};
+use crate::domain::{get_all_currency_pairs, CurrencyPair};
+use crate::i18n::{tr, Language};
+use crate::price_service::analytics::rub_strength;
+use crate::price_service::history::PriceHistory;
+use chrono::{Duration, Local};
+
+/// Period the daily summary covers, up to the time it is sent
+const SUMMARY_PERIOD: Duration = Duration::hours(24);
+
+/// Open, high, low, close and change of every pair of `pairs` over the
+/// last 24 hours, `None` when there are no samples of any of them
+pub fn render_daily_summary(
+    language: Language,
+    format: MessageFormat,
+    price_formats: &PriceFormats,
+    history: &PriceHistory,
+    pairs: &[CurrencyPair],
+) -> Option<String> {
+    let since = Local::now() - SUMMARY_PERIOD;
+    let lines: Vec<String> = pairs
+        .iter()
+        .filter_map(|pair| {
+            let ohlc = history.ohlc(pair, since)?;
+            let price = |price: f64| format.code(&price_formats.get(pair).format(price));
+            let change = ohlc
+                .change()
+                .map(|change| format.code(&change.to_string()))
+                .unwrap_or_default();
+            let line = format.render(
+                tr(language, "summary.line"),
+                &[
+                    ("pair", format.bold(&pair.to_string())),
+                    ("open", price(ohlc.open)),
+                    ("high", price(ohlc.high)),
+                    ("low", price(ohlc.low)),
+                    ("close", price(ohlc.close)),
+                    ("change", change),
+                ],
+            );
+            Some(line.trim_end().to_string())
+        })
+        .collect();
+    if lines.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "{}\n\n{}",
+        format.bold(tr(language, "summary.title")),
+        lines.join("\n")
+    ))
+}
+
+/// Line of the ruble strength index over the summary period, `None`
+/// without history of any basket currency
+pub fn render_strength_index(
+    language: Language,
+    format: MessageFormat,
+    history: &PriceHistory,
+) -> Option<String> {
+    let index = rub_strength(history, Local::now() - SUMMARY_PERIOD)?;
+    Some(format.render(
+        tr(language, "summary.strength"),
+        &[
+            ("index", format.code(&format!("{:.2}", index.value))),
+            ("basket", format.escape(&index.currencies.join(", "))),
+        ],
+    ))
+}
+
+/// Daily summary followed by the strength index when `strength_index` is on
+fn render_digest(
+    language: Language,
+    format: MessageFormat,
+    price_formats: &PriceFormats,
+    history: &PriceHistory,
+    pairs: &[CurrencyPair],
+    strength_index: bool,
+) -> Option<String> {
+    let summary = render_daily_summary(language, format, price_formats, history, pairs)?;
+    match strength_index
+        .then(|| render_strength_index(language, format, history))
+        .flatten()
+    {
+        Some(index) => Some(format!("{}\n\n{}", summary, index)),
+        None => Some(summary),
+    }
+}
+
+/// Send the daily summary to the subscribed chats of `manager` that opted
+/// in, each in its language and with its pairs
+pub async fn send_daily_summary(
+    bot: &TelegramBot,
+    manager: &SubscriberManager,
+    history: &PriceHistory,
+    strength_index: bool,
+) {
+    for chat_id in manager.get_daily_summary_chats() {
+        let language = manager.languages().get(chat_id);
+        let pairs = manager.get_pairs(chat_id);
+        let format = manager.message_format();
+        let price_formats = manager.price_formats();
+        let Some(text) = render_digest(
+            language,
+            format,
+            price_formats,
+            history,
+            &pairs,
+            strength_index,
+        ) else {
+            tracing::info!("No prices for the daily summary of {}", chat_id);
+            continue;
+        };
+        if let Err(e) = manager.send_to_chat(bot, chat_id, text).await {
+            tracing::warn!("Failed to deliver daily summary to {}: {}", chat_id, e);
+        }
+    }
+}
+
+/// Post the daily summary of every pair to the channels of `publisher`
+pub async fn publish_daily_summary(
+    bot: &TelegramBot,
+    publisher: &ChannelPublisher,
+    history: &PriceHistory,
+    strength_index: bool,
+) {
+    let text = render_digest(
+        Language::default(),
+        publisher.format(),
+        publisher.price_formats(),
+        history,
+        &get_all_currency_pairs(),
+        strength_index,
+    );
+    match text {
+        Some(text) => publisher.post(bot, &text).await,
+        None => tracing::info!("No prices for the daily summary of the channels"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot_modules::format::PriceFormat;
+
+    #[test]
+    fn test_render_daily_summary() {
+        let history = PriceHistory::default();
+        let now = Local::now();
+        for (hours, price) in [(30, 80.0), (20, 90.0), (12, 95.0), (6, 88.0), (0, 92.0)] {
+            history.record(&CurrencyPair::USD2RUB, price, now - Duration::hours(hours));
+        }
+        let pairs = [CurrencyPair::USD2RUB, CurrencyPair::USDTe2RUB];
+
+        let formats = PriceFormats::default();
+        let summary = render_daily_summary(
+            Language::En,
+            MessageFormat::Plain,
+            &formats,
+            &history,
+            &pairs,
+        )
+        .unwrap();
+        assert_eq!(
+            summary,
+            "📊 Daily summary\n\nUSD/RUB: open 90.00, high 95.00, low 88.00, close 92.00 ▲ +2.00 (+2.22%)"
+        );
+        let html = render_daily_summary(
+            Language::Ru,
+            MessageFormat::Html,
+            &formats,
+            &history,
+            &pairs,
+        )
+        .unwrap();
+        assert!(html.contains("<b>USD/RUB</b>"));
+        assert!(html.contains("<code>95.00</code>"));
+
+        // In the pair's own format
+        let mut formats = PriceFormats::default();
+        formats.pairs.insert(
+            "USD/RUB".to_string(),
+            PriceFormat {
+                decimals: 0,
+                ..PriceFormat::default()
+            },
+        );
+        let summary = render_daily_summary(
+            Language::En,
+            MessageFormat::Plain,
+            &formats,
+            &history,
+            &pairs,
+        )
+        .unwrap();
+        assert!(
+            summary.contains("open 90, high 95, low 88, close 92"),
+            "{}",
+            summary
+        );
+
+        assert!(render_daily_summary(
+            Language::En,
+            MessageFormat::Plain,
+            &PriceFormats::default(),
+            &history,
+            &[CurrencyPair::USDTe2RUB]
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_digest_with_strength_index() {
+        let history = PriceHistory::default();
+        let now = Local::now();
+        history.record(&CurrencyPair::USD2RUB, 100.0, now - Duration::hours(12));
+        history.record(&CurrencyPair::USD2RUB, 98.0, now);
+        let pairs = [CurrencyPair::USD2RUB];
+        let formats = PriceFormats::default();
+
+        let digest = render_digest(
+            Language::En,
+            MessageFormat::Plain,
+            &formats,
+            &history,
+            &pairs,
+            true,
+        )
+        .unwrap();
+        assert!(
+            digest.ends_with("\n\nRUB strength index: 102.04 (basket: USD; 100 = a day ago)"),
+            "{}",
+            digest
+        );
+        let digest = render_digest(
+            Language::En,
+            MessageFormat::Plain,
+            &formats,
+            &history,
+            &pairs,
+            false,
+        )
+        .unwrap();
+        assert!(!digest.contains("strength"));
+    }
+}
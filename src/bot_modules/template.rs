@@ -0,0 +1,222 @@
+use super::format::{MessageFormat, PriceFormat};
+use crate::domain::CurrencyPair;
+use crate::price_service::history::PriceChange;
+use chrono::{DateTime, Local};
+
+/// Placeholders supported in periodic message templates
+pub const PLACEHOLDERS: [&str; 6] = [
+    "{pair}",
+    "{price}",
+    "{delta}",
+    "{change_24h}",
+    "{sparkline}",
+    "{time}",
+];
+
+/// Placeholders that make a template render once per subscribed pair
+const PAIR_PLACEHOLDERS: [&str; 5] = [
+    "{pair}",
+    "{price}",
+    "{delta}",
+    "{change_24h}",
+    "{sparkline}",
+];
+
+/// Rendered in place of a value that is not available yet
+const MISSING_VALUE: &str = "—";
+
+/// Values substituted into a periodic message template
+#[derive(Debug, Clone)]
+pub struct TemplateValues {
+    pub pair: Option<CurrencyPair>,
+    pub price: Option<f64>,
+    /// Price change since the previous periodic message
+    pub delta: Option<PriceChange>,
+    pub change_24h: Option<f64>,
+    /// Recent prices as bars like `▁▂▄▆█`
+    pub sparkline: Option<String>,
+    pub time: DateTime<Local>,
+}
+
+/// Check whether the template refers to per-pair values
+pub fn has_pair_placeholders(template: &str) -> bool {
+    PAIR_PLACEHOLDERS
+        .iter()
+        .any(|placeholder| template.contains(placeholder))
+}
+
+/// Replace the supported placeholders in `template` with `values`, pair
+/// names in bold and numbers in monospace when `format` has markup, the
+/// price written with `price_format`. The optional values take the space
+/// before them along when they are omitted.
+pub fn render(
+    template: &str,
+    values: &TemplateValues,
+    format: MessageFormat,
+    price_format: &PriceFormat,
+) -> String {
+    let mut template = template.to_string();
+    for (placeholder, omitted) in [
+        (" {delta}", values.delta.is_none()),
+        (" {sparkline}", values.sparkline.is_none()),
+    ] {
+        if omitted {
+            template = template.replace(placeholder, &placeholder[1..]);
+        }
+    }
+    let missing = || format.escape(MISSING_VALUE);
+    let pair = values
+        .pair
+        .as_ref()
+        .map(|p| format.bold(&p.to_string()))
+        .unwrap_or_else(missing);
+    let price = values
+        .price
+        .map(|p| format.code(&price_format.format(p)))
+        .unwrap_or_else(missing);
+    // An unknown delta (first message) is simply omitted
+    let delta = values
+        .delta
+        .map(|d| format.code(&d.to_string()))
+        .unwrap_or_default();
+    let change_24h = values
+        .change_24h
+        .map(|c| format.code(&format!("{:+.2}%", c)))
+        .unwrap_or_else(missing);
+    let sparkline = values
+        .sparkline
+        .as_deref()
+        .map(|sparkline| format.escape(sparkline))
+        .unwrap_or_default();
+    let time = format.escape(&values.time.format("%d.%m.%Y %H:%M").to_string());
+
+    format.render(
+        &template,
+        &[
+            ("pair", pair),
+            ("price", price),
+            ("delta", delta),
+            ("change_24h", change_24h),
+            ("sparkline", sparkline),
+            ("time", time),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn fixed_time() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 5, 1, 9, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn test_render_all_placeholders() {
+        let values = TemplateValues {
+            pair: Some(CurrencyPair::USD2RUB),
+            price: Some(92.456),
+            delta: PriceChange::between(92.756, 92.456),
+            change_24h: Some(0.5),
+            sparkline: Some("▁▄█".to_string()),
+            time: fixed_time(),
+        };
+        assert_eq!(
+            render(
+                "{pair}: {price} {delta} ({change_24h}) {sparkline} at {time}",
+                &values,
+                MessageFormat::Plain,
+                &PriceFormat::default()
+            ),
+            "USD/RUB: 92.46 ▼ -0.30 (-0.32%) (+0.50%) ▁▄█ at 01.05.2024 09:30"
+        );
+        let precise = PriceFormat {
+            decimals: 4,
+            ..PriceFormat::default()
+        };
+        assert_eq!(
+            render("{price}", &values, MessageFormat::Plain, &precise),
+            "92.4560"
+        );
+    }
+
+    #[test]
+    fn test_render_missing_values() {
+        let values = TemplateValues {
+            pair: None,
+            price: None,
+            delta: None,
+            change_24h: None,
+            sparkline: None,
+            time: fixed_time(),
+        };
+        assert_eq!(
+            render(
+                "{pair} {price} {change_24h}{delta}",
+                &values,
+                MessageFormat::Plain,
+                &PriceFormat::default()
+            ),
+            "— — —"
+        );
+        assert_eq!(
+            render(
+                "{pair}: {price} {delta} {sparkline}!",
+                &values,
+                MessageFormat::Plain,
+                &PriceFormat::default()
+            ),
+            "—: —!"
+        );
+    }
+
+    #[test]
+    fn test_render_markdown_v2() {
+        let values = TemplateValues {
+            pair: Some(CurrencyPair::USD2RUB),
+            price: Some(92.456),
+            delta: PriceChange::between(92.356, 92.456),
+            change_24h: None,
+            sparkline: None,
+            time: fixed_time(),
+        };
+        assert_eq!(
+            render(
+                "💰 {pair}: {price} {delta}!",
+                &values,
+                MessageFormat::MarkdownV2,
+                &PriceFormat::default()
+            ),
+            "💰 *USD/RUB*: `92.46` `▲ +0.10 (+0.11%)`\\!"
+        );
+    }
+
+    #[test]
+    fn test_render_plain_text() {
+        let values = TemplateValues {
+            pair: None,
+            price: None,
+            delta: None,
+            change_24h: None,
+            sparkline: None,
+            time: fixed_time(),
+        };
+        assert_eq!(
+            render(
+                "Просто текст",
+                &values,
+                MessageFormat::Plain,
+                &PriceFormat::default()
+            ),
+            "Просто текст"
+        );
+    }
+
+    #[test]
+    fn test_has_pair_placeholders() {
+        assert!(has_pair_placeholders("{pair}: {price}"));
+        assert!(has_pair_placeholders("Δ {delta}"));
+        assert!(!has_pair_placeholders("Отправлено в {time}"));
+    }
+}
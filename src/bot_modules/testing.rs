@@ -0,0 +1,523 @@
+//! Test doubles for end-to-end module tests: [`MockBot`] serves the Bot API
+//! locally for a real [`TelegramBot`] and records every call, the builders
+//! create the updates handlers receive. A test sends a command through the
+//! registry and asserts on the replies:
+//!
+//! ```ignore
+//! let mock = MockBot::start().await;
+//! registry.handle_message(mock.bot(), MessageBuilder::new("/echo hi").build()).await?;
+//! assert_eq!(mock.sent_texts(), ["hi"]);
+//! ```
+
+use super::TelegramBot;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use teloxide::adaptors::throttle::Limits;
+use teloxide::prelude::*;
+use teloxide::types::{CallbackQuery, Message};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Bot API call made by the bot under test
+#[derive(Debug, Clone)]
+pub struct SentRequest {
+    /// API method, e.g. `SendMessage`
+    pub method: String,
+    /// JSON parameters, `Null` for multipart uploads like documents
+    pub body: Value,
+}
+
+impl SentRequest {
+    pub fn text(&self) -> Option<&str> {
+        self.body.get("text").and_then(Value::as_str)
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    requests: Vec<SentRequest>,
+    /// Canned answers by method, used once each before the default ones
+    responses: HashMap<String, VecDeque<Value>>,
+    next_message_id: i32,
+}
+
+impl MockState {
+    /// Answer to `request`: the next canned one, a message for sends and
+    /// edits, `true` for everything else
+    fn respond(&mut self, request: &SentRequest) -> Value {
+        let method = request.method.to_ascii_lowercase();
+        if let Some(response) = self
+            .responses
+            .get_mut(&method)
+            .and_then(VecDeque::pop_front)
+        {
+            return response;
+        }
+        let result = if method == "getme" {
+            json!({
+                "id": 1,
+                "is_bot": true,
+                "first_name": "Test bot",
+                "username": "test_bot",
+                "can_join_groups": true,
+                "can_read_all_group_messages": false,
+                "supports_inline_queries": false,
+                "can_connect_to_business": false,
+                "has_main_web_app": false,
+            })
+        } else if method.starts_with("send") || method.starts_with("edit") {
+            self.next_message_id += 1;
+            let chat_id = request.body.get("chat_id").and_then(Value::as_i64);
+            let text = request
+                .text()
+                .or_else(|| request.body.get("caption").and_then(Value::as_str))
+                .unwrap_or_default();
+            json!({
+                "message_id": self.next_message_id,
+                "date": 0,
+                "chat": {"id": chat_id.unwrap_or(-1), "type": "private", "first_name": "Test"},
+                "text": text,
+            })
+        } else {
+            json!(true)
+        };
+        json!({"ok": true, "result": result})
+    }
+}
+
+/// Local Bot API server for the [`TelegramBot`] returned by [`Self::bot`].
+/// Sends and edits succeed with a message echoing the text, other calls with
+/// `true`; [`Self::respond_with`] queues other answers, e.g. errors.
+pub struct MockBot {
+    bot: TelegramBot,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockBot {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let server_state = Arc::clone(&state);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, Arc::clone(&server_state)));
+            }
+        });
+        // Telegram's per-chat limits would only slow the tests down
+        let limits = Limits {
+            messages_per_sec_chat: 1000,
+            messages_per_min_chat: 1000,
+            messages_per_min_channel_or_supergroup: 1000,
+            messages_per_sec_overall: 1000,
+        };
+        let bot = Bot::new("123:test")
+            .set_api_url(url.parse().unwrap())
+            .throttle(limits);
+        Self { bot, state }
+    }
+
+    pub fn bot(&self) -> TelegramBot {
+        self.bot.clone()
+    }
+
+    /// Answer the next call of `method` (e.g. `SendMessage`) with the full
+    /// API `response`, like `{"ok": false, "error_code": 403, ...}`
+    pub fn respond_with(&self, method: &str, response: Value) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .responses
+            .entry(method.to_ascii_lowercase())
+            .or_default()
+            .push_back(response);
+    }
+
+    /// Calls made so far, oldest first
+    pub fn requests(&self) -> Vec<SentRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Texts of the messages sent or edited so far
+    pub fn sent_texts(&self) -> Vec<String> {
+        self.requests()
+            .iter()
+            .filter_map(|request| request.text().map(str::to_string))
+            .collect()
+    }
+}
+
+/// Answer the HTTP/1.1 requests of one keep-alive connection
+async fn serve(stream: TcpStream, state: Arc<Mutex<MockState>>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).await.unwrap_or(0) == 0 {
+            return;
+        }
+        let mut content_length = 0;
+        let mut chunked = false;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await.unwrap_or(0) == 0 {
+                return;
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                let value = value.trim();
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.parse().unwrap_or(0);
+                } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                    chunked = value.eq_ignore_ascii_case("chunked");
+                }
+            }
+        }
+        let body = match chunked {
+            true => read_chunked(&mut reader).await,
+            false => {
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).await.map(|_| body).ok()
+            }
+        };
+        let Some(body) = body else {
+            return;
+        };
+
+        // `POST /bot<token>/<Method> HTTP/1.1`
+        let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+        let request = SentRequest {
+            method: path.rsplit('/').next().unwrap_or_default().to_string(),
+            body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+        };
+        let response = {
+            let mut state = state.lock().unwrap();
+            let response = state.respond(&request);
+            state.requests.push(request);
+            response.to_string()
+        };
+        let head = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n",
+            response.len()
+        );
+        let written = writer.write_all(head.as_bytes()).await;
+        if written.is_err() || writer.write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn read_chunked<R: AsyncBufReadExt + Unpin>(reader: &mut R) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let mut size = String::new();
+        reader.read_line(&mut size).await.ok()?;
+        let size = usize::from_str_radix(size.trim(), 16).ok()?;
+        let mut chunk = vec![0; size + 2];
+        reader.read_exact(&mut chunk).await.ok()?;
+        if size == 0 {
+            return Some(body);
+        }
+        body.extend_from_slice(&chunk[..size]);
+    }
+}
+
+/// Incoming message, a private chat with user 42 unless changed
+pub struct MessageBuilder {
+    text: String,
+    chat: Value,
+    user_id: u64,
+    language_code: Option<String>,
+    edited: bool,
+}
+
+impl MessageBuilder {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            chat: json!({"id": 42, "type": "private", "first_name": "Test"}),
+            user_id: 42,
+            language_code: None,
+            edited: false,
+        }
+    }
+
+    pub fn from(mut self, user_id: u64) -> Self {
+        self.user_id = user_id;
+        self
+    }
+
+    /// Post the message in the supergroup `chat_id`
+    pub fn group(mut self, chat_id: i64) -> Self {
+        self.chat = json!({"id": chat_id, "type": "supergroup", "title": "Test group"});
+        self
+    }
+
+    /// Language of the sender's Telegram client, e.g. `en`
+    pub fn language_code(mut self, code: &str) -> Self {
+        self.language_code = Some(code.to_string());
+        self
+    }
+
+    /// Deliver the message as edited by the sender
+    pub fn edited(mut self) -> Self {
+        self.edited = true;
+        self
+    }
+
+    fn user(&self) -> Value {
+        json!({
+            "id": self.user_id,
+            "is_bot": false,
+            "first_name": "Test",
+            "language_code": self.language_code,
+        })
+    }
+
+    pub fn build(self) -> Message {
+        let mut message = json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": self.chat,
+            "from": self.user(),
+            "text": self.text,
+        });
+        if self.edited {
+            message["edit_date"] = json!(60);
+        }
+        serde_json::from_value(message).unwrap()
+    }
+}
+
+/// Press of an inline keyboard button under a message of the bot
+pub struct CallbackQueryBuilder {
+    data: String,
+    message: MessageBuilder,
+}
+
+impl CallbackQueryBuilder {
+    pub fn new(data: &str) -> Self {
+        Self {
+            data: data.to_string(),
+            message: MessageBuilder::new(""),
+        }
+    }
+
+    /// The message carrying the button, its sender is the one pressing it
+    pub fn message(mut self, message: MessageBuilder) -> Self {
+        self.message = message;
+        self
+    }
+
+    pub fn build(self) -> CallbackQuery {
+        let from = self.message.user();
+        let message = serde_json::to_value(self.message.build()).unwrap();
+        serde_json::from_value(json!({
+            "id": "1",
+            "from": from,
+            "chat_instance": "1",
+            "message": message,
+            "data": self.data,
+        }))
+        .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot_modules::alerts::{check_alerts, AlertCondition, AlertRule};
+    use crate::bot_modules::scheduler::{Job, JobSchedule, Scheduler};
+    use crate::bot_modules::subscribers::Quotes;
+    use crate::bot_modules::{
+        AccessControl, AdminModule, BotCommandsModule, EchoModule, ModuleContext, ModuleRegistry,
+        PriceModule, SettingsModule,
+    };
+    use crate::domain::CurrencyPair;
+    use crate::i18n::Language;
+    use crate::price_service::history::PriceHistory;
+    use std::collections::HashSet;
+    use teloxide::types::{MessageId, ThreadId};
+    use tokio_util::sync::CancellationToken;
+
+    fn registry(context: &ModuleContext) -> ModuleRegistry {
+        let broadcast_job = Scheduler::new(CancellationToken::new()).register(Job::new(
+            "broadcast",
+            JobSchedule::from_minutes(10),
+            || async {},
+        ));
+        let mut registry = ModuleRegistry::new();
+        registry.set_context(context.clone());
+        registry.set_access_control(AccessControl::new(HashSet::from([UserId(1)])));
+        registry.register(Box::new(BotCommandsModule::new(EchoModule)));
+        registry.register(Box::new(SettingsModule::new(broadcast_job.clone())));
+        registry.register(Box::new(AdminModule::new(broadcast_job)));
+        registry.register(Box::new(PriceModule));
+        registry
+    }
+
+    #[tokio::test]
+    async fn test_commands_end_to_end() {
+        let mock = MockBot::start().await;
+        let context = ModuleContext::default();
+        let registry = registry(&context);
+        for text in ["/echo Привет, мир", "/echo", "/ehco hi", "/stats"] {
+            let msg = MessageBuilder::new(text).build();
+            registry.handle_message(mock.bot(), msg).await.unwrap();
+        }
+        assert_eq!(
+            mock.sent_texts(),
+            [
+                "Привет, мир",
+                "Использование: /echo <текст>",
+                "Неизвестная команда. Возможно, вы имели в виду /echo? Используйте /help",
+                "Команда доступна только администраторам.",
+            ]
+        );
+
+        // Admins can, and the reply goes to the chat the command came from
+        let msg = MessageBuilder::new("/stats").from(1).group(-100).build();
+        registry.handle_message(mock.bot(), msg).await.unwrap();
+        let stats = mock.requests().pop().unwrap();
+        assert_eq!(stats.body["chat_id"], -100);
+        assert!(stats.text().unwrap().starts_with("📊 Статистика"));
+    }
+
+    #[tokio::test]
+    async fn test_bare_price_uses_default_pair() {
+        let mock = MockBot::start().await;
+        let context = ModuleContext::default();
+        let registry = registry(&context);
+        let price = MessageBuilder::new("/price").build();
+
+        // Without a default pair the chat picks one
+        registry
+            .handle_message(mock.bot(), price.clone())
+            .await
+            .unwrap();
+        let picker = mock.requests().pop().unwrap();
+        assert!(picker.text().unwrap().starts_with("Выберите пару"));
+        assert_eq!(
+            picker.body["reply_markup"]["inline_keyboard"][0][0]["callback_data"],
+            "price:refresh:USDCe/RUB"
+        );
+
+        let settings = MessageBuilder::new("/settings default_pair USD/RUB").build();
+        registry.handle_message(mock.bot(), settings).await.unwrap();
+        assert_eq!(
+            mock.sent_texts().last().unwrap(),
+            "Пара по умолчанию: USD/RUB"
+        );
+        registry.handle_message(mock.bot(), price).await.unwrap();
+        // No provider quotes it here, the error names the pair asked for
+        let quote = mock.sent_texts().pop().unwrap();
+        assert!(quote.contains("USD/RUB"), "{}", quote);
+    }
+
+    #[tokio::test]
+    async fn test_edited_price_updates_the_reply() {
+        let mock = MockBot::start().await;
+        let context = ModuleContext::default();
+        let registry = registry(&context);
+        registry
+            .handle_message(mock.bot(), MessageBuilder::new("/price").build())
+            .await
+            .unwrap();
+        let picker = mock.requests().pop().unwrap();
+        assert_eq!(picker.method, "SendMessage");
+
+        let edited = MessageBuilder::new("/price USD/RUB").edited().build();
+        registry
+            .handle_edited_message(mock.bot(), edited)
+            .await
+            .unwrap();
+        let quote = mock.requests().pop().unwrap();
+        assert_eq!(quote.method, "EditMessageText");
+        // The ids of the mock start at 1
+        assert_eq!(quote.body["message_id"], 1);
+        assert!(quote.text().unwrap().contains("USD/RUB"));
+
+        // Repeating a command that changes something is left to the user
+        let echo = MessageBuilder::new("/echo hi").edited().build();
+        registry
+            .handle_edited_message(mock.bot(), echo)
+            .await
+            .unwrap();
+        assert_eq!(mock.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_callback_query_end_to_end() {
+        let mock = MockBot::start().await;
+        let context = ModuleContext::default();
+        let registry = registry(&context);
+
+        let query = CallbackQueryBuilder::new("settings:language:en").build();
+        registry
+            .handle_callback_query(mock.bot(), query)
+            .await
+            .unwrap();
+        assert_eq!(context.languages.get(ChatId(42)), Language::En);
+        let methods: Vec<_> = mock
+            .requests()
+            .into_iter()
+            .map(|request| request.method)
+            .collect();
+        assert_eq!(methods, ["EditMessageText", "AnswerCallbackQuery"]);
+    }
+
+    #[tokio::test]
+    async fn test_send_follows_chat_migration() {
+        let mock = MockBot::start().await;
+        let context = ModuleContext::default();
+        let manager = &context.manager;
+        manager.subscribe(ChatId(-5));
+        mock.respond_with(
+            "SendMessage",
+            json!({
+                "ok": false,
+                "error_code": 400,
+                "description": "Bad Request: group chat was upgraded to a supergroup chat",
+                "parameters": {"migrate_to_chat_id": -1005},
+            }),
+        );
+
+        let message = manager
+            .send_to_chat(&mock.bot(), ChatId(-5), "USD/RUB 90".to_string())
+            .await
+            .unwrap();
+        assert_eq!(message.chat.id, ChatId(-1005));
+        assert_eq!(manager.get_subscribers(), [ChatId(-1005)]);
+        let chats: Vec<_> = mock
+            .requests()
+            .into_iter()
+            .map(|request| request.body["chat_id"].clone())
+            .collect();
+        assert_eq!(chats, [json!(-5), json!(-1005)]);
+    }
+
+    #[tokio::test]
+    async fn test_alerts_are_sent_into_the_chats_topic() {
+        let mock = MockBot::start().await;
+        let manager = ModuleContext::default().manager.clone();
+        manager.subscribe(ChatId(-5));
+        manager.set_thread_id(ChatId(-5), Some(ThreadId(MessageId(7))));
+        let rule = AlertRule::new(
+            CurrencyPair::USD2RUB,
+            AlertCondition::Above { price: 95.0 },
+            false,
+        );
+        manager.alerts().add(ChatId(-5), rule);
+
+        let quotes = Quotes::from([(CurrencyPair::USD2RUB, 95.5)]);
+        let fired = check_alerts(&manager, &PriceHistory::default(), &mock.bot(), &quotes).await;
+        assert_eq!(fired.len(), 1);
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].body["chat_id"], json!(-5));
+        assert_eq!(requests[0].body["message_thread_id"], json!(7));
+    }
+}
@@ -0,0 +1,232 @@
+use super::format::MessageFormat;
+use super::{ArgError, Args, Module, ModuleContext, ParsedCommand, TelegramBot};
+use crate::domain::{get_all_currency_pairs, CurrencyPair};
+use crate::i18n::{tr, Language};
+use crate::price_service::history::Candle;
+use async_trait::async_trait;
+use chrono::{Duration, Local, NaiveDate};
+use std::error::Error;
+use teloxide::prelude::*;
+
+/// Days shown by `/week`, today included
+const WEEK_DAYS: i64 = 7;
+
+/// Percent change of a day of the week
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DayChange {
+    pub date: NaiveDate,
+    /// Change of the day's close from the close of the last day recorded
+    /// before, or from its own open without one, `None` without samples that day
+    pub percent: Option<f64>,
+}
+
+impl DayChange {
+    /// Cell of the heatmap: green up, red down, white unchanged at the
+    /// displayed precision and black without data
+    fn square(&self) -> &'static str {
+        match self.percent {
+            None => "⬛",
+            Some(percent) if format!("{:.2}", percent.abs()) == "0.00" => "⬜",
+            Some(percent) if percent > 0.0 => "🟩",
+            Some(_) => "🟥",
+        }
+    }
+}
+
+/// Changes of the [`WEEK_DAYS`] days up to `today` from the daily candles of
+/// a pair, oldest first
+pub fn week_changes(days: &[Candle], today: NaiveDate) -> Vec<DayChange> {
+    (0..WEEK_DAYS)
+        .rev()
+        .map(|ago| {
+            let date = today - Duration::days(ago);
+            let candle = days.iter().find(|day| day.time.date_naive() == date);
+            let previous_close = days
+                .iter()
+                .rev()
+                .find(|day| day.time.date_naive() < date)
+                .map(|day| day.ohlc.close);
+            let percent = candle.and_then(|candle| {
+                let reference = previous_close.unwrap_or(candle.ohlc.open);
+                (reference > 0.0).then(|| (candle.ohlc.close - reference) / reference * 100.0)
+            });
+            DayChange { date, percent }
+        })
+        .collect()
+}
+
+/// `/week <pair>` shows the daily changes of a pair over the last week as
+/// a row of colored squares, for context without a full chart
+pub struct WeekModule;
+
+impl WeekModule {
+    fn render(
+        language: Language,
+        format: MessageFormat,
+        pair: &CurrencyPair,
+        changes: &[DayChange],
+    ) -> String {
+        let title = format.render(
+            tr(language, "week.title"),
+            &[("pair", format.bold(&pair.to_string()))],
+        );
+        let grid: String = changes.iter().map(DayChange::square).collect();
+        let lines: Vec<String> = changes
+            .iter()
+            .map(|change| {
+                let percent = match change.percent {
+                    Some(percent) => format.code(&format!("{:+.2}%", percent)),
+                    None => format.escape("—"),
+                };
+                format!(
+                    "{} {} {}",
+                    change.square(),
+                    format.escape(&change.date.format("%d.%m").to_string()),
+                    percent
+                )
+            })
+            .collect();
+        format!("{}\n\n{}\n\n{}", title, grid, lines.join("\n"))
+    }
+
+    /// Parse `<pair>`
+    fn parse_args(args: &str) -> Result<CurrencyPair, ArgError> {
+        let mut args = Args::new(args);
+        let pair = args.pair()?;
+        args.finish()?;
+        Ok(pair)
+    }
+
+    /// Reply to `/week <pair>`
+    fn respond(ctx: &ModuleContext, language: Language, args: &str) -> String {
+        let format = ctx.format;
+        let pairs = get_all_currency_pairs()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let help = format.render(
+            tr(language, "week.help"),
+            &[("pairs", format.escape(&pairs))],
+        );
+        let pair = match Self::parse_args(args) {
+            Ok(pair) => pair,
+            Err(ArgError::Missing) => return help,
+            Err(error) => {
+                return format!("{}\n\n{}", format.escape(&error.message(language)), help)
+            }
+        };
+        // One more day for the close the first day is compared with
+        let now = Local::now();
+        let days = ctx
+            .price_service
+            .history()
+            .daily(&pair, now - Duration::days(WEEK_DAYS));
+        if days.is_empty() {
+            return format.render(
+                tr(language, "ta.no_data"),
+                &[("pair", format.escape(&pair.to_string()))],
+            );
+        }
+        let changes = week_changes(&days, now.date_naive());
+        Self::render(language, format, &pair, &changes)
+    }
+}
+
+#[async_trait]
+impl Module for WeekModule {
+    fn name(&self) -> &str {
+        "Week"
+    }
+
+    fn commands(&self) -> Vec<&str> {
+        vec!["/week"]
+    }
+
+    fn command_descriptions(&self, language: Language) -> Vec<(&str, &str)> {
+        vec![("/week", tr(language, "week.menu"))]
+    }
+
+    fn usage(&self, language: Language) -> Vec<String> {
+        vec![tr(language, "week.usage").to_string()]
+    }
+
+    async fn handle(
+        &self,
+        ctx: &ModuleContext,
+        bot: TelegramBot,
+        msg: Message,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let Some(command) = ParsedCommand::from_message(&msg) else {
+            return Ok(());
+        };
+        let language = ctx.languages.get(msg.chat.id);
+        let response = Self::respond(ctx, language, command.args);
+        ctx.send_reply(&bot, &msg, ctx.format.reply(&bot, &msg, response))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_service::history::PriceHistory;
+    use chrono::{NaiveTime, TimeZone};
+
+    #[test]
+    fn test_week_changes() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let history = PriceHistory::default();
+        let at = |days_ago: i64, hour: u32, price: f64| {
+            let date = today - Duration::days(days_ago);
+            let time = NaiveTime::from_hms_opt(hour, 0, 0).unwrap();
+            let time = Local.from_local_datetime(&date.and_time(time)).unwrap();
+            history.record(&CurrencyPair::USD2RUB, price, time);
+        };
+        at(8, 10, 90.0);
+        at(8, 18, 99.0);
+        at(6, 12, 101.0);
+        at(4, 10, 101.0);
+        at(4, 18, 99.99);
+        at(3, 12, 99.99);
+        at(0, 12, 102.0);
+        let days = history.daily(&CurrencyPair::USD2RUB, Local::now() - Duration::days(3650));
+        let changes = week_changes(&days, today);
+
+        assert_eq!(changes.len(), 7);
+        assert_eq!(changes[0].date, today - Duration::days(6));
+        let squares: String = changes.iter().map(DayChange::square).collect();
+        assert_eq!(squares, "🟩⬛🟥⬜⬛⬛🟩");
+        assert_eq!(changes[1].percent, None);
+        // Days without samples are skipped for the reference close
+        assert!((changes[2].percent.unwrap() + 1.0).abs() < 1e-9);
+        // The first day recorded is compared with its own open
+        let earlier = week_changes(&days, today - Duration::days(2));
+        assert_eq!(earlier[0].date, today - Duration::days(8));
+        assert!((earlier[0].percent.unwrap() - 10.0).abs() < 1e-9);
+
+        let response = WeekModule::render(
+            Language::En,
+            MessageFormat::Plain,
+            &CurrencyPair::USD2RUB,
+            &changes,
+        );
+        assert!(response.starts_with("🗓 USD/RUB over the last 7 days\n\n🟩⬛🟥⬜⬛⬛🟩\n\n"));
+        assert!(response.ends_with("🟩 10.05 +2.01%"));
+    }
+
+    #[test]
+    fn test_week_command() {
+        let ctx = ModuleContext::default();
+        let respond = |args: &str| WeekModule::respond(&ctx, Language::En, args);
+        assert!(respond("").starts_with("Usage: /week"));
+        assert!(respond("EUR/RUB").starts_with("❌ Unknown pair: EUR/RUB\n\nUsage: /week"));
+        assert!(respond("USD/RUB 7d").starts_with("❌ Unexpected argument: 7d\n\nUsage: /week"));
+        assert_eq!(respond("USD/RUB"), "No prices of USD/RUB recorded yet.");
+
+        let history = ctx.price_service.history();
+        history.record(&CurrencyPair::USD2RUB, 92.0, Local::now());
+        assert_eq!(respond(" USD/RUB ").lines().count(), 11);
+    }
+}
@@ -0,0 +1,122 @@
+use super::{SubscriberManager, TelegramBot};
+use crate::domain::{get_all_currency_pairs, CurrencyPair};
+use crate::i18n::tr_args;
+use crate::price_service::history::{Ohlc, PriceHistory, PricePoint};
+use chrono::{DateTime, Duration, DurationRound, Local, SecondsFormat};
+use std::collections::BTreeMap;
+use teloxide::types::{ChatId, InputFile};
+
+/// Period the weekly report covers, up to the time it is sent
+const REPORT_PERIOD: Duration = Duration::days(7);
+
+const CSV_HEADER: &str = "pair,hour,open,high,low,close,samples";
+
+/// Open, high, low and close of every pair of `pairs` for each hour since
+/// `since` as CSV, one row per pair and hour with samples, `None` when
+/// there are no samples at all
+pub fn render_weekly_csv(
+    history: &PriceHistory,
+    pairs: &[CurrencyPair],
+    since: DateTime<Local>,
+) -> Option<String> {
+    let mut rows = Vec::new();
+    for pair in pairs {
+        let mut hours: BTreeMap<DateTime<Local>, Vec<PricePoint>> = BTreeMap::new();
+        for point in history.range(pair, since) {
+            // Truncation fails only for local times skipped or repeated by a DST change
+            if let Ok(hour) = point.time.duration_trunc(Duration::hours(1)) {
+                hours.entry(hour).or_default().push(point);
+            }
+        }
+        for (hour, points) in hours {
+            let Some(ohlc) = Ohlc::from_points(&points) else {
+                continue;
+            };
+            rows.push(format!(
+                "{},{},{},{},{},{},{}",
+                pair,
+                hour.to_rfc3339_opts(SecondsFormat::Secs, false),
+                ohlc.open,
+                ohlc.high,
+                ohlc.low,
+                ohlc.close,
+                points.len()
+            ));
+        }
+    }
+    if rows.is_empty() {
+        return None;
+    }
+    Some(format!("{}\n{}\n", CSV_HEADER, rows.join("\n")))
+}
+
+/// Send the CSV of the last week to `admins` and to the subscribed chats of
+/// `manager` that opted in, each chat once
+pub async fn send_weekly_report(
+    bot: &TelegramBot,
+    manager: &SubscriberManager,
+    admins: &[ChatId],
+    history: &PriceHistory,
+) {
+    let now = Local::now();
+    let since = now - REPORT_PERIOD;
+    let Some(csv) = render_weekly_csv(history, &get_all_currency_pairs(), since) else {
+        tracing::info!("No prices for the weekly report");
+        return;
+    };
+    let file_name = format!("rates-{}.csv", now.format("%Y-%m-%d"));
+
+    let mut chats = admins.to_vec();
+    for chat_id in manager.get_weekly_report_chats() {
+        if !chats.contains(&chat_id) {
+            chats.push(chat_id);
+        }
+    }
+    for chat_id in chats {
+        let caption = tr_args(
+            manager.languages().get(chat_id),
+            "weekly_report.caption",
+            &[
+                ("from", &since.format("%d.%m.%Y")),
+                ("to", &now.format("%d.%m.%Y")),
+            ],
+        );
+        let document = InputFile::memory(csv.clone()).file_name(file_name.clone());
+        if let Err(e) = manager
+            .send_document_to_chat(bot, chat_id, document, caption)
+            .await
+        {
+            tracing::warn!("Failed to deliver weekly report to {}: {}", chat_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_weekly_csv() {
+        let history = PriceHistory::default();
+        let hour = Local::now().duration_trunc(Duration::hours(1)).unwrap() - Duration::hours(2);
+        for (minutes, price) in [(-30, 70.0), (0, 90.0), (20, 95.0), (40, 88.0), (70, 92.0)] {
+            history.record(
+                &CurrencyPair::USD2RUB,
+                price,
+                hour + Duration::minutes(minutes),
+            );
+        }
+        let pairs = [CurrencyPair::USD2RUB, CurrencyPair::USDTe2RUB];
+
+        let csv = render_weekly_csv(&history, &pairs, hour).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_HEADER);
+        let first = hour.to_rfc3339_opts(SecondsFormat::Secs, false);
+        assert_eq!(lines[1], format!("USD/RUB,{},90,95,88,88,3", first));
+        let second = (hour + Duration::hours(1)).to_rfc3339_opts(SecondsFormat::Secs, false);
+        assert_eq!(lines[2], format!("USD/RUB,{},92,92,92,92,1", second));
+
+        assert!(render_weekly_csv(&history, &[CurrencyPair::USDTe2RUB], hour).is_none());
+    }
+}
@@ -0,0 +1,223 @@
+//! iCalendar feeds of the messages the bot sends a chat on schedule: the
+//! periodic message, the daily summary and the weekly report. Served by the
+//! HTTP API at `/calendar/<token>.ics` with the token `/calendar` gave the
+//! chat. The schedules are the bot's, a feed lists their coming runs the way
+//! the chat gets them: with its jitter offset, without its quiet hours and
+//! only the digests it opted in to. Calendar apps refresh the feed to follow
+//! changes.
+
+use crate::bot_modules::scheduler::{JobHandle, JobSchedule};
+use crate::bot_modules::subscribers::SubscriberManager;
+use crate::i18n::tr;
+use chrono::{DateTime, Local, Timelike, Utc};
+use std::sync::Arc;
+use std::time::Instant;
+use teloxide::types::ChatId;
+
+/// How far ahead a feed lists the runs
+const FEED_DAYS: i64 = 7;
+
+/// Events of one feed at most, for schedules running every few minutes
+const MAX_EVENTS: usize = 500;
+
+/// Length of an event, enough to show up in the day view of calendar apps
+const EVENT_DURATION: &str = "PT5M";
+
+/// Schedules of the messages a feed lists
+#[derive(Debug, Clone)]
+pub struct Digests {
+    pub broadcast: JobSchedule,
+    /// Next periodic message, interval schedules are counted from it
+    pub next_broadcast: Option<DateTime<Local>>,
+    pub summary: Option<JobSchedule>,
+    pub weekly_report: Option<JobSchedule>,
+}
+
+/// Feeds of the chats of every bot the process serves
+pub struct CalendarFeeds {
+    /// Subscriptions and broadcast job of each bot
+    pub bots: Vec<(Arc<SubscriberManager>, Arc<JobHandle>)>,
+    pub summary: Option<JobSchedule>,
+    pub weekly_report: Option<JobSchedule>,
+}
+
+impl CalendarFeeds {
+    /// Feed of the chat `token` was issued to, `None` for an unknown token
+    pub fn render(&self, token: &str, now: DateTime<Local>) -> Option<String> {
+        self.bots.iter().find_map(|(manager, broadcast)| {
+            let chat_id = manager.get_calendar_chat(token)?;
+            let next_broadcast = broadcast.next_run().map(|next| {
+                let remaining = next.saturating_duration_since(Instant::now());
+                now + chrono::Duration::from_std(remaining).unwrap_or_default()
+            });
+            let digests = Digests {
+                broadcast: broadcast.schedule(),
+                next_broadcast,
+                summary: self.summary.clone(),
+                weekly_report: self.weekly_report.clone(),
+            };
+            Some(feed(manager, chat_id, &digests, now))
+        })
+    }
+}
+
+/// Runs of `schedule` after `from` and before `until`, an interval starting
+/// at `next` or one interval from `from` when the next run isn't known
+fn runs(
+    schedule: &JobSchedule,
+    next: Option<DateTime<Local>>,
+    from: DateTime<Local>,
+    until: DateTime<Local>,
+) -> Vec<DateTime<Local>> {
+    match schedule {
+        JobSchedule::Interval(interval) => {
+            let step = chrono::Duration::from_std(*interval).unwrap_or_default();
+            let first = next.unwrap_or(from + step);
+            std::iter::successors(Some(first), |run| Some(*run + step))
+                .take_while(|run| *run < until)
+                .take(MAX_EVENTS)
+                .collect()
+        }
+        JobSchedule::Cron(schedule) => schedule
+            .after(&from)
+            .take_while(|run| *run < until)
+            .take(MAX_EVENTS)
+            .collect(),
+    }
+}
+
+/// Time in the UTC form of iCalendar, e.g. `20261014T090000Z`
+fn ics_time(time: DateTime<Local>) -> String {
+    time.with_timezone(&Utc)
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+/// iCalendar feed of the chat's messages of the next [`FEED_DAYS`] days
+pub fn feed(
+    manager: &SubscriberManager,
+    chat_id: ChatId,
+    digests: &Digests,
+    now: DateTime<Local>,
+) -> String {
+    let until = now + chrono::Duration::days(FEED_DAYS);
+    let mut events = Vec::new();
+    if manager.is_subscribed(chat_id) {
+        let quiet_hours = manager.get_quiet_hours(chat_id);
+        let offset =
+            chrono::Duration::from_std(manager.get_jitter_offset(chat_id)).unwrap_or_default();
+        // The quiet hours are checked when the broadcast starts, before the offset
+        let periodic = runs(&digests.broadcast, digests.next_broadcast, now, until)
+            .into_iter()
+            .filter(|run| !quiet_hours.is_some_and(|quiet_hours| quiet_hours.contains(run.hour())))
+            .map(|run| ("periodic", "calendar.periodic", run + offset));
+        events.extend(periodic);
+        let opted_in = [
+            (
+                "summary",
+                "calendar.summary",
+                &digests.summary,
+                manager.get_daily_summary(chat_id),
+            ),
+            (
+                "weekly_report",
+                "calendar.weekly_report",
+                &digests.weekly_report,
+                manager.get_weekly_report(chat_id),
+            ),
+        ];
+        for (kind, title, schedule, enabled) in opted_in {
+            if let (Some(schedule), true) = (schedule, enabled) {
+                events.extend(
+                    runs(schedule, None, now, until)
+                        .into_iter()
+                        .map(|run| (kind, title, run)),
+                );
+            }
+        }
+    }
+    events.sort_by_key(|(_, _, start)| *start);
+    events.truncate(MAX_EVENTS);
+
+    let language = manager.languages().get(chat_id);
+    let stamp = ics_time(now);
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//currency-bot//digests//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", tr(language, "calendar.name")),
+        "REFRESH-INTERVAL;VALUE=DURATION:PT1H".to_string(),
+        "X-PUBLISHED-TTL:PT1H".to_string(),
+    ];
+    for (kind, title, start) in events {
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!(
+                "UID:{}-{}-{}@currency-bot",
+                kind,
+                chat_id,
+                start.timestamp()
+            ),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART:{}", ics_time(start)),
+            format!("DURATION:{}", EVENT_DURATION),
+            format!("SUMMARY:{}", tr(language, title)),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.join("\r\n") + "\r\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot_modules::subscribers::QuietHours;
+    use chrono::TimeZone;
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    #[test]
+    fn test_feed_lists_the_chats_digests() {
+        let manager = SubscriberManager::new("Test message".to_string())
+            .with_jitter_window(Duration::from_secs(60));
+        let chat_id = ChatId(42);
+        let now = Local.with_ymd_and_hms(2026, 10, 14, 12, 0, 0).unwrap();
+        let digests = Digests {
+            broadcast: JobSchedule::from_minutes(6 * 60),
+            next_broadcast: Some(Local.with_ymd_and_hms(2026, 10, 14, 14, 0, 0).unwrap()),
+            summary: Some(JobSchedule::parse("0 0 21 * * *").unwrap()),
+            weekly_report: Some(JobSchedule::parse("0 0 9 * * Mon").unwrap()),
+        };
+        let events = |feed: &str| feed.matches("BEGIN:VEVENT").count();
+
+        // Nothing is sent to a chat that isn't subscribed
+        let unsubscribed = feed(&manager, chat_id, &digests, now);
+        assert!(unsubscribed.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(unsubscribed.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(events(&unsubscribed), 0);
+
+        // 14:00, 20:00, 02:00 and 08:00 for a week, without 02:00 in the quiet hours
+        manager.subscribe(chat_id);
+        manager.set_quiet_hours(chat_id, QuietHours::from_str("1-7").ok());
+        let subscribed = feed(&manager, chat_id, &digests, now);
+        assert_eq!(events(&subscribed), 21);
+        let offset = chrono::Duration::from_std(manager.get_jitter_offset(chat_id)).unwrap();
+        let first = ics_time(Local.with_ymd_and_hms(2026, 10, 14, 14, 0, 0).unwrap() + offset);
+        assert!(subscribed.contains(&format!("DTSTART:{}\r\n", first)));
+        assert!(subscribed.contains("SUMMARY:Периодическое сообщение с курсами\r\n"));
+
+        // Every evening of the week and the Monday morning
+        manager.set_daily_summary(chat_id, true);
+        manager.set_weekly_report(chat_id, true);
+        let opted_in = feed(&manager, chat_id, &digests, now);
+        assert_eq!(events(&opted_in), 21 + 7 + 1);
+        let monday = ics_time(Local.with_ymd_and_hms(2026, 10, 19, 9, 0, 0).unwrap());
+        assert!(opted_in.contains(&format!(
+            "DTSTART:{}\r\nDURATION:PT5M\r\nSUMMARY:Недельный отчёт\r\n",
+            monday
+        )));
+    }
+}
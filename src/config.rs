@@ -0,0 +1,1770 @@
+// Configuration module
+// Typed settings loaded from `config.toml`, overridden by environment variables,
+// and reloaded when the file changes
+
+use crate::bot_modules::auth::parse_id_list;
+use crate::bot_modules::channels::{parse_channels, ChannelConfig};
+use crate::bot_modules::chart::DEFAULT_CHART_FONT_PATH;
+use crate::bot_modules::format::{PriceFormat, PriceFormats, Rounding};
+use crate::bot_modules::scheduler::{parse_missed_tick_behavior, JobSchedule};
+use crate::bot_modules::MessageFormat;
+use crate::domain::CurrencyPair;
+use crate::price_service::analytics::default_windows;
+use crate::price_service::history::{
+    parse_period, RetentionPolicy, DEFAULT_HOURLY_RETENTION_DAYS, DEFAULT_RETENTION_DAYS,
+};
+use crate::price_service::providers::Faults;
+use crate::reporting::DEFAULT_REPORT_COOLDOWN;
+use chrono::{NaiveDate, NaiveTime, Timelike};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{de, Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::watch;
+use tokio::time::MissedTickBehavior;
+
+/// Config file read when `CONFIG_FILE` is not set, optional
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Id of the bot configured in `[telegram]`
+pub const MAIN_BOT_ID: &str = "main";
+
+/// Most decimals a price is displayed with
+const MAX_PRICE_DECIMALS: u8 = 8;
+
+/// How an environment variable is turned into a TOML value
+#[derive(Debug, Clone, Copy)]
+enum EnvKind {
+    String,
+    Integer,
+    Bool,
+    IdList,
+    /// Comma-separated strings
+    List,
+}
+
+/// Environment variables overriding the config file, with the key they set
+const ENV_OVERRIDES: &[(&str, &str, EnvKind)] = &[
+    ("TELOXIDE_TOKEN", "telegram.token", EnvKind::String),
+    ("ADMIN_USER_IDS", "telegram.admin_user_ids", EnvKind::IdList),
+    ("ALLOWED_IDS", "telegram.allowed_ids", EnvKind::IdList),
+    ("BLOCKED_IDS", "telegram.blocked_ids", EnvKind::IdList),
+    (
+        "COMMANDS_PER_MINUTE",
+        "telegram.commands_per_minute",
+        EnvKind::Integer,
+    ),
+    (
+        "DUPLICATE_WINDOW_SECONDS",
+        "telegram.duplicate_window_seconds",
+        EnvKind::Integer,
+    ),
+    (
+        "MESSAGES_PER_SECOND",
+        "telegram.messages_per_second",
+        EnvKind::Integer,
+    ),
+    ("PARSE_MODE", "telegram.parse_mode", EnvKind::String),
+    ("BOT_MODULES", "telegram.modules", EnvKind::List),
+    (
+        "FEEDBACK_CHAT_ID",
+        "telegram.feedback_chat_id",
+        EnvKind::Integer,
+    ),
+    (
+        "SUBSCRIPTION_INTERVAL_MINUTES",
+        "subscriptions.interval_minutes",
+        EnvKind::Integer,
+    ),
+    (
+        "SUBSCRIPTION_SCHEDULE",
+        "subscriptions.schedule",
+        EnvKind::String,
+    ),
+    (
+        "SUBSCRIPTION_MISSED_TICKS",
+        "subscriptions.missed_ticks",
+        EnvKind::String,
+    ),
+    (
+        "SUBSCRIPTION_JITTER_SECONDS",
+        "subscriptions.jitter_seconds",
+        EnvKind::Integer,
+    ),
+    (
+        "PERIODIC_MESSAGE_TEXT",
+        "subscriptions.message_text",
+        EnvKind::String,
+    ),
+    (
+        "PIN_PERIODIC_MESSAGE",
+        "subscriptions.pin_messages",
+        EnvKind::Bool,
+    ),
+    ("SUBSCRIBERS_FILE", "subscriptions.file", EnvKind::String),
+    ("CHANNELS", "channels.list", EnvKind::String),
+    ("CHANNEL_SCHEDULE", "channels.schedule", EnvKind::String),
+    ("SUMMARY_TIME", "summary.time", EnvKind::String),
+    ("SUMMARY_CHANNELS", "summary.channels", EnvKind::Bool),
+    (
+        "SUMMARY_STRENGTH_INDEX",
+        "summary.strength_index",
+        EnvKind::Bool,
+    ),
+    (
+        "WEEKLY_REPORT_SCHEDULE",
+        "weekly_report.schedule",
+        EnvKind::String,
+    ),
+    ("NEWLINE_ENABLED", "newline.enabled", EnvKind::Bool),
+    ("NEWLINE_API_BASE_URL", "newline.base_url", EnvKind::String),
+    ("NEWLINE_COOKIE", "newline.cookie", EnvKind::String),
+    (
+        "NEWLINE_PREFERRED_CITY",
+        "newline.preferred_city",
+        EnvKind::String,
+    ),
+    (
+        "NEWLINE_COOKIE_EXPIRES",
+        "newline.cookie_expires",
+        EnvKind::String,
+    ),
+    (
+        "NEWLINE_RATE_LIMIT_PER_HOUR",
+        "newline.rate_limit_per_hour",
+        EnvKind::Integer,
+    ),
+    ("KRAKEN_ENABLED", "kraken.enabled", EnvKind::Bool),
+    ("KRAKEN_API_BASE_URL", "kraken.base_url", EnvKind::String),
+    (
+        "KRAKEN_RATE_LIMIT_PER_HOUR",
+        "kraken.rate_limit_per_hour",
+        EnvKind::Integer,
+    ),
+    ("STREET_RATE_ENABLED", "street_rate.enabled", EnvKind::Bool),
+    (
+        "STREET_RATE_TRIM_PERCENT",
+        "street_rate.trim_percent",
+        EnvKind::Integer,
+    ),
+    ("ROUTE_FEE_BPS", "route.fee_bps", EnvKind::Integer),
+    ("ROUTE_MAX_HOPS", "route.max_hops", EnvKind::Integer),
+    ("PRICE_DECIMALS", "display.decimals", EnvKind::Integer),
+    (
+        "PRICE_THOUSANDS_SEPARATOR",
+        "display.thousands_separator",
+        EnvKind::String,
+    ),
+    ("PRICE_ROUNDING", "display.rounding", EnvKind::String),
+    (
+        "PRICE_SPARKLINE_HOURS",
+        "display.sparkline_hours",
+        EnvKind::Integer,
+    ),
+    ("PRICE_FOOTER", "display.footer", EnvKind::String),
+    (
+        "PRICE_POLL_INTERVAL_MINUTES",
+        "prices.poll_interval_minutes",
+        EnvKind::Integer,
+    ),
+    (
+        "PRICE_CONDITIONAL_REQUESTS",
+        "prices.conditional_requests",
+        EnvKind::Bool,
+    ),
+    ("HISTORY_RAW_DAYS", "history.raw_days", EnvKind::Integer),
+    (
+        "HISTORY_HOURLY_DAYS",
+        "history.hourly_days",
+        EnvKind::Integer,
+    ),
+    (
+        "HISTORY_COMPACTION_INTERVAL_MINUTES",
+        "history.compaction_interval_minutes",
+        EnvKind::Integer,
+    ),
+    (
+        "AUDIT_MAX_ENTRIES_PER_CHAT",
+        "audit.max_entries_per_chat",
+        EnvKind::Integer,
+    ),
+    (
+        "AUDIT_RETENTION_DAYS",
+        "audit.retention_days",
+        EnvKind::Integer,
+    ),
+    ("CHART_FONT_PATH", "chart.font_path", EnvKind::String),
+    ("ANALYTICS_WINDOWS", "analytics.windows", EnvKind::List),
+    (
+        "ERROR_REPORT_CHAT_ID",
+        "reporting.chat_id",
+        EnvKind::Integer,
+    ),
+    (
+        "ERROR_REPORT_COOLDOWN_MINUTES",
+        "reporting.cooldown_minutes",
+        EnvKind::Integer,
+    ),
+    ("SENTRY_DSN", "reporting.sentry_dsn", EnvKind::String),
+    (
+        "CREDENTIAL_REMINDER_DAYS",
+        "reporting.credential_reminder_days",
+        EnvKind::Integer,
+    ),
+    ("SNAPSHOT_FILE", "snapshot.file", EnvKind::String),
+    ("HTTP_API_ADDR", "http.addr", EnvKind::String),
+    ("HTTP_PUBLIC_URL", "http.public_url", EnvKind::String),
+    (
+        "PROVIDER_TIMEOUT_SECONDS",
+        "http_client.timeout_seconds",
+        EnvKind::Integer,
+    ),
+    (
+        "PROVIDER_CONNECT_TIMEOUT_SECONDS",
+        "http_client.connect_timeout_seconds",
+        EnvKind::Integer,
+    ),
+    (
+        "PROVIDER_POOL_MAX_IDLE",
+        "http_client.pool_max_idle_per_host",
+        EnvKind::Integer,
+    ),
+    (
+        "PROVIDER_POOL_IDLE_TIMEOUT_SECONDS",
+        "http_client.pool_idle_timeout_seconds",
+        EnvKind::Integer,
+    ),
+    (
+        "PROVIDER_ROOT_CA_FILE",
+        "http_client.root_ca_file",
+        EnvKind::String,
+    ),
+    ("PROVIDER_PROXY", "http_client.proxy", EnvKind::String),
+    (
+        "ALERT_WEBHOOK_SECRET",
+        "alert_webhooks.secret",
+        EnvKind::String,
+    ),
+    (
+        "FAULT_ERROR_PERCENT",
+        "fault_injection.error_percent",
+        EnvKind::Integer,
+    ),
+    (
+        "FAULT_STALE_PERCENT",
+        "fault_injection.stale_percent",
+        EnvKind::Integer,
+    ),
+    (
+        "FAULT_LATENCY_MS",
+        "fault_injection.latency_ms",
+        EnvKind::Integer,
+    ),
+    ("LOG_FORMAT", "logging.format", EnvKind::String),
+];
+
+/// Error type for loading the configuration
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to read {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid config file {path}: {message}")]
+    Parse { path: PathBuf, message: String },
+
+    #[error("Invalid {variable}: {message}")]
+    Env { variable: String, message: String },
+
+    #[error("Invalid configuration:\n  - {}", .0.join("\n  - "))]
+    Invalid(Vec<String>),
+}
+
+/// Bot account, access control and command handling
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelegramConfig {
+    pub token: Option<String>,
+    pub admin_user_ids: Vec<u64>,
+    /// Turns on whitelist mode when set
+    pub allowed_ids: Option<Vec<i64>>,
+    pub blocked_ids: Vec<i64>,
+    /// Per sender and minute, `0` disables the limit
+    pub commands_per_minute: usize,
+    /// Identical commands of a sender in a chat within this many seconds are
+    /// answered once, `0` answers every one
+    pub duplicate_window_seconds: u64,
+    /// Messages the bot sends on its own per second, across all chats
+    pub messages_per_second: u32,
+    #[serde(deserialize_with = "message_format")]
+    pub parse_mode: MessageFormat,
+    /// Chat receiving /feedback, the command is only offered when set
+    pub feedback_chat_id: Option<i64>,
+    /// Modules the bot serves by name, all when unset
+    pub modules: Option<Vec<String>>,
+}
+
+impl Default for TelegramConfig {
+    fn default() -> Self {
+        Self {
+            token: None,
+            admin_user_ids: Vec::new(),
+            allowed_ids: None,
+            blocked_ids: Vec::new(),
+            commands_per_minute: 10,
+            duplicate_window_seconds: 3,
+            messages_per_second: 25,
+            parse_mode: MessageFormat::default(),
+            feedback_chat_id: None,
+            modules: None,
+        }
+    }
+}
+
+/// Periodic messages to subscribed chats
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SubscriptionConfig {
+    pub interval_minutes: u64,
+    /// Takes precedence over `interval_minutes`
+    #[serde(deserialize_with = "job_schedule")]
+    pub schedule: Option<JobSchedule>,
+    #[serde(deserialize_with = "missed_ticks")]
+    pub missed_ticks: MissedTickBehavior,
+    pub jitter_seconds: u64,
+    pub message_text: String,
+    pub pin_messages: bool,
+    /// State file the subscriptions are persisted to
+    pub file: Option<PathBuf>,
+}
+
+impl SubscriptionConfig {
+    /// Schedule of the broadcast job
+    pub fn broadcast_schedule(&self) -> JobSchedule {
+        self.schedule
+            .clone()
+            .unwrap_or_else(|| JobSchedule::from_minutes(self.interval_minutes))
+    }
+}
+
+impl Default for SubscriptionConfig {
+    fn default() -> Self {
+        Self {
+            interval_minutes: 10,
+            schedule: None,
+            // Burst-catching-up after the host slept would deliver several broadcasts at once
+            missed_ticks: MissedTickBehavior::Skip,
+            jitter_seconds: 0,
+            message_text: "Периодическое сообщение от бота".to_string(),
+            pin_messages: false,
+            file: None,
+        }
+    }
+}
+
+/// Channels the rates are published to
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChannelsConfig {
+    /// In the `CHANNELS` format, e.g. `-1001234567890|{pair}: {price};@rates`
+    #[serde(deserialize_with = "channel_list")]
+    pub list: Vec<ChannelConfig>,
+    /// The subscription schedule when not set
+    #[serde(deserialize_with = "job_schedule")]
+    pub schedule: Option<JobSchedule>,
+}
+
+/// Daily summary of the last 24 hours for the chats that opted in
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SummaryConfig {
+    /// Local time of day the summary is sent at, e.g. `21:00`, not sent when unset
+    #[serde(deserialize_with = "time_of_day")]
+    pub time: Option<NaiveTime>,
+    /// Post the summary to the channels as well
+    pub channels: bool,
+    /// Add the ruble strength index against the USD/EUR/CNY basket
+    pub strength_index: bool,
+}
+
+impl SummaryConfig {
+    /// Schedule of the summary job, `None` when the summary is off
+    pub fn schedule(&self) -> Option<JobSchedule> {
+        let time = self.time?;
+        let spec = format!("0 {} {} * * *", time.minute(), time.hour());
+        JobSchedule::parse(&spec).ok()
+    }
+}
+
+/// Weekly CSV of the hourly rates for the admins and the chats that opted in
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WeeklyReportConfig {
+    /// When the report is sent, e.g. `0 0 9 * * Mon`, not sent when unset
+    #[serde(deserialize_with = "job_schedule")]
+    pub schedule: Option<JobSchedule>,
+}
+
+/// NewLine price provider
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NewLineSettings {
+    /// Whether prices are requested from NewLine at all
+    pub enabled: bool,
+    pub base_url: String,
+    pub cookie: Option<String>,
+    pub preferred_city: String,
+    /// Last day the cookie is valid, e.g. `2026-12-31`, for the reminders
+    pub cookie_expires: Option<NaiveDate>,
+    /// Requests NewLine allows per hour, the admins are warned near it
+    pub rate_limit_per_hour: Option<u32>,
+}
+
+impl Default for NewLineSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            base_url: "https://newline.online".to_string(),
+            cookie: None,
+            preferred_city: "spb".to_string(),
+            cookie_expires: None,
+            rate_limit_per_hour: None,
+        }
+    }
+}
+
+/// Kraken price provider for the USDT/USD and EUR/USD legs
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KrakenSettings {
+    /// Whether prices are requested from Kraken at all
+    pub enabled: bool,
+    pub base_url: String,
+    /// Requests Kraken allows per hour, the admins are warned near it
+    pub rate_limit_per_hour: Option<u32>,
+}
+
+impl Default for KrakenSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            base_url: "https://api.kraken.com".to_string(),
+            rate_limit_per_hour: None,
+        }
+    }
+}
+
+/// "Street rate" of the ruble pairs blended from the cash exchangers' quotes,
+/// asked before the exchangers themselves when enabled
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StreetRateConfig {
+    pub enabled: bool,
+    /// Quotes dropped at each end before averaging, in percent
+    pub trim_percent: u8,
+}
+
+impl Default for StreetRateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            trim_percent: 20,
+        }
+    }
+}
+
+/// Fees and length of the exchange routes `/route` looks for
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouteConfig {
+    /// Fee of an exchange, in basis points (50 = 0.5%)
+    pub fee_bps: u32,
+    /// Fees of the exchanges along a pair like `USDT/USD`, in basis points
+    pub fees: BTreeMap<String, u32>,
+    pub max_hops: usize,
+}
+
+impl RouteConfig {
+    /// Fee of an exchange along `pair`
+    pub fn fee_bps(&self, pair: &CurrencyPair) -> u32 {
+        self.fees
+            .get(&pair.to_string())
+            .copied()
+            .unwrap_or(self.fee_bps)
+    }
+}
+
+impl Default for RouteConfig {
+    fn default() -> Self {
+        Self {
+            fee_bps: 50,
+            fees: BTreeMap::new(),
+            max_hops: 3,
+        }
+    }
+}
+
+/// How prices are displayed in quotes
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    /// Decimals of the pairs without a format in `pairs`
+    pub decimals: u8,
+    pub thousands_separator: String,
+    pub rounding: Rounding,
+    /// Formats of pairs like `USDT/USD`
+    pub pairs: BTreeMap<String, PriceFormat>,
+    /// Hours of history the sparkline of quotes and periodic messages
+    /// covers, 0 without one
+    pub sparkline_hours: u32,
+    /// Appended to the price messages, with the placeholders `{source}`
+    /// and `{time}` of the quotes, e.g. for attribution and a disclaimer
+    pub footer: Option<String>,
+}
+
+impl DisplayConfig {
+    /// Format of the prices of `pair`
+    pub fn price_format(&self, pair: &CurrencyPair) -> PriceFormat {
+        self.price_formats().get(pair).clone()
+    }
+
+    /// Formats of the prices of every pair, for the messages built away
+    /// from the config
+    pub fn price_formats(&self) -> PriceFormats {
+        PriceFormats {
+            default: PriceFormat {
+                decimals: self.decimals,
+                thousands_separator: self.thousands_separator.clone(),
+                rounding: self.rounding,
+            },
+            pairs: self.pairs.clone(),
+        }
+    }
+
+    /// Period of the sparklines, `None` when they are off
+    pub fn sparkline_window(&self) -> Option<chrono::Duration> {
+        (self.sparkline_hours > 0).then(|| chrono::Duration::hours(self.sparkline_hours.into()))
+    }
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        let format = PriceFormat::default();
+        Self {
+            decimals: format.decimals,
+            thousands_separator: format.thousands_separator,
+            rounding: format.rounding,
+            pairs: BTreeMap::new(),
+            sparkline_hours: 24,
+            footer: None,
+        }
+    }
+}
+
+/// Background price sampling
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PricesConfig {
+    pub poll_interval_minutes: u64,
+    /// Whether providers answering with an `ETag` or `Last-Modified` are
+    /// asked with conditional requests, reusing the last body on `304`
+    pub conditional_requests: bool,
+}
+
+impl Default for PricesConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_minutes: 5,
+            conditional_requests: true,
+        }
+    }
+}
+
+/// Longest retention of the history tiers and the audit log, a hundred years
+const MAX_RETENTION_DAYS: u64 = 36_500;
+
+/// Tiered retention of the price history: samples, then hourly candles,
+/// then daily candles kept forever
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// Days samples are kept before they become hourly candles
+    pub raw_days: u64,
+    /// Days hourly candles are kept before they become daily candles
+    pub hourly_days: u64,
+    pub compaction_interval_minutes: u64,
+}
+
+impl HistoryConfig {
+    pub fn retention(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            raw: chrono::Duration::days(self.raw_days as i64),
+            hourly: chrono::Duration::days(self.hourly_days as i64),
+        }
+    }
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            raw_days: DEFAULT_RETENTION_DAYS as u64,
+            hourly_days: DEFAULT_HOURLY_RETENTION_DAYS as u64,
+            compaction_interval_minutes: 60,
+        }
+    }
+}
+
+/// Limits of the per-chat command audit log shown by `/audit`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditConfig {
+    /// Commands kept per chat, the log is off when 0
+    pub max_entries_per_chat: usize,
+    pub retention_days: u64,
+}
+
+impl AuditConfig {
+    pub fn retention(&self) -> chrono::Duration {
+        chrono::Duration::days(self.retention_days as i64)
+    }
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            max_entries_per_chat: 200,
+            retention_days: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChartConfig {
+    pub font_path: PathBuf,
+}
+
+impl Default for ChartConfig {
+    fn default() -> Self {
+        Self {
+            font_path: PathBuf::from(DEFAULT_CHART_FONT_PATH),
+        }
+    }
+}
+
+/// Windows of the moving averages and volatility of `/ta`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnalyticsConfig {
+    /// Periods like `1h` or `7d`
+    #[serde(deserialize_with = "periods")]
+    pub windows: Vec<chrono::Duration>,
+}
+
+impl Default for AnalyticsConfig {
+    fn default() -> Self {
+        Self {
+            windows: default_windows(),
+        }
+    }
+}
+
+/// Where failures are reported
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReportingConfig {
+    pub chat_id: Option<i64>,
+    pub cooldown_minutes: u64,
+    pub sentry_dsn: Option<String>,
+    /// Days before a credential expires the admins are reminded from
+    pub credential_reminder_days: u32,
+}
+
+impl ReportingConfig {
+    /// Period the reports of one source are counted instead of sent
+    pub fn cooldown(&self) -> Duration {
+        Duration::from_secs(self.cooldown_minutes.saturating_mul(60))
+    }
+}
+
+impl Default for ReportingConfig {
+    fn default() -> Self {
+        Self {
+            chat_id: None,
+            cooldown_minutes: DEFAULT_REPORT_COOLDOWN.as_secs() / 60,
+            sentry_dsn: None,
+            credential_reminder_days: 7,
+        }
+    }
+}
+
+/// Runtime state saved on shutdown, see [`crate::snapshot`]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnapshotConfig {
+    pub file: Option<PathBuf>,
+}
+
+/// Embedded HTTP API, served with the `http-api` feature
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    pub addr: Option<SocketAddr>,
+    /// Address the server is reached at from outside, e.g.
+    /// `https://bot.example.com`, for the calendar links of `/calendar`
+    pub public_url: Option<String>,
+}
+
+/// The `reqwest::Client` shared by every price provider
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpClientConfig {
+    /// Whole request, from connecting to the end of the body
+    pub timeout_seconds: u64,
+    pub connect_timeout_seconds: u64,
+    /// Idle connections kept open to each host
+    pub pool_max_idle_per_host: usize,
+    pub pool_idle_timeout_seconds: u64,
+    /// PEM certificate trusted next to the system roots, e.g. of a TLS
+    /// inspecting proxy
+    pub root_ca_file: Option<PathBuf>,
+    /// Proxy of every provider request, e.g. `http://proxy:3128`; without it
+    /// the `HTTPS_PROXY`/`HTTP_PROXY` variables are used
+    pub proxy: Option<String>,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            timeout_seconds: 30,
+            connect_timeout_seconds: 10,
+            pool_max_idle_per_host: 8,
+            pool_idle_timeout_seconds: 90,
+            root_ca_file: None,
+            proxy: None,
+        }
+    }
+}
+
+/// Callback URLs the triggered alerts of some chats are POSTed to, for
+/// scripts reacting to the same thresholds; set up by the operator
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertWebhooksConfig {
+    /// Key of the HMAC-SHA256 signature of every payload
+    pub secret: Option<String>,
+    /// Callback URL by chat id, like `"123456789" = "https://example.com/alerts"`
+    pub urls: BTreeMap<String, String>,
+}
+
+impl AlertWebhooksConfig {
+    /// Callback URL by chat id, ids that aren't numbers are left out
+    pub fn callbacks(&self) -> BTreeMap<i64, String> {
+        self.urls
+            .iter()
+            .filter_map(|(chat_id, url)| Some((chat_id.parse().ok()?, url.clone())))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Misbehavior injected into every price provider for chaos testing on
+/// staging, off by default
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FaultInjectionConfig {
+    /// Requests failing with a network error, in percent
+    pub error_percent: u8,
+    /// Requests answered with the previous price, in percent
+    pub stale_percent: u8,
+    pub latency_ms: u64,
+}
+
+impl FaultInjectionConfig {
+    pub fn faults(&self) -> Faults {
+        Faults {
+            error_percent: self.error_percent,
+            stale_percent: self.stale_percent,
+            latency: Duration::from_millis(self.latency_ms),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub format: LogFormat,
+}
+
+/// A bot run by the process: the `[telegram]` one or an entry of `[[bots]]`
+/// sharing its price service and scheduler. Everything but the token, the
+/// modules and the subscriptions comes from the other sections.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BotConfig {
+    /// Names the bot in logs and jobs
+    pub id: String,
+    pub token: String,
+    /// Modules the bot serves by name (e.g. `["price", "admin"]`), all when unset
+    #[serde(default)]
+    pub modules: Option<Vec<String>>,
+    /// State file of the bot's subscriptions, `subscriptions.file` with the
+    /// id added (`subscribers.ops.json`) when unset
+    #[serde(default)]
+    pub subscriptions_file: Option<PathBuf>,
+}
+
+/// All settings of the bot
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub telegram: TelegramConfig,
+    pub subscriptions: SubscriptionConfig,
+    pub channels: ChannelsConfig,
+    pub summary: SummaryConfig,
+    pub weekly_report: WeeklyReportConfig,
+    pub newline: NewLineSettings,
+    pub kraken: KrakenSettings,
+    pub street_rate: StreetRateConfig,
+    pub route: RouteConfig,
+    pub display: DisplayConfig,
+    pub prices: PricesConfig,
+    pub history: HistoryConfig,
+    pub audit: AuditConfig,
+    pub chart: ChartConfig,
+    pub analytics: AnalyticsConfig,
+    pub reporting: ReportingConfig,
+    pub snapshot: SnapshotConfig,
+    pub http: HttpConfig,
+    pub http_client: HttpClientConfig,
+    pub alert_webhooks: AlertWebhooksConfig,
+    pub fault_injection: FaultInjectionConfig,
+    pub logging: LoggingConfig,
+    /// Bots run next to the `[telegram]` one
+    pub bots: Vec<BotConfig>,
+}
+
+impl Config {
+    /// Load the config file and apply the environment overrides read with
+    /// `env`. The file is `CONFIG_FILE`, or `config.toml` when it exists.
+    pub fn load(env: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let (path, required) = config_file(&env);
+        Self::load_file(&path, required, env)
+    }
+
+    fn load_file(
+        path: &Path,
+        required: bool,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(source) => {
+                return Err(ConfigError::Read {
+                    path: path.to_path_buf(),
+                    source,
+                })
+            }
+        };
+        Self::from_toml(path, &text, env)
+    }
+
+    /// Every bot to run, the `[telegram]` one first with the id [`MAIN_BOT_ID`]
+    pub fn bots(&self) -> Vec<BotConfig> {
+        let main = BotConfig {
+            id: MAIN_BOT_ID.to_string(),
+            token: self.telegram.token.clone().unwrap_or_default(),
+            modules: self.telegram.modules.clone(),
+            subscriptions_file: self.subscriptions.file.clone(),
+        };
+        let others = self.bots.iter().map(|bot| BotConfig {
+            subscriptions_file: bot.subscriptions_file.clone().or_else(|| {
+                let file = self.subscriptions.file.as_deref()?;
+                Some(bot_state_file(file, &bot.id))
+            }),
+            ..bot.clone()
+        });
+        std::iter::once(main).chain(others).collect()
+    }
+
+    /// Provider credentials with a configured expiry date
+    pub fn credential_expiries(&self) -> Vec<(&'static str, NaiveDate)> {
+        [("NewLine cookie", self.newline.cookie_expires)]
+            .into_iter()
+            .filter_map(|(credential, expires)| Some((credential, expires?)))
+            .collect()
+    }
+
+    /// Schedule of the channel publishing job, the subscription schedule
+    /// unless the channels have their own
+    pub fn channel_schedule(&self) -> JobSchedule {
+        self.channels
+            .schedule
+            .clone()
+            .unwrap_or_else(|| self.subscriptions.broadcast_schedule())
+    }
+
+    /// Sections of `new` that differ from this config in settings only read
+    /// at startup, everything [`watch`] consumers apply at runtime aside
+    pub fn restart_required(&self, new: &Config) -> Vec<&'static str> {
+        let mut new = new.clone();
+        new.telegram.admin_user_ids = self.telegram.admin_user_ids.clone();
+        new.telegram.allowed_ids = self.telegram.allowed_ids.clone();
+        new.telegram.blocked_ids = self.telegram.blocked_ids.clone();
+        new.subscriptions.interval_minutes = self.subscriptions.interval_minutes;
+        new.subscriptions.schedule = self.subscriptions.schedule.clone();
+        new.subscriptions.message_text = self.subscriptions.message_text.clone();
+        new.channels.schedule = self.channels.schedule.clone();
+        new.newline.enabled = self.newline.enabled;
+        new.kraken.enabled = self.kraken.enabled;
+        new.prices.poll_interval_minutes = self.prices.poll_interval_minutes;
+
+        [
+            ("telegram", self.telegram != new.telegram),
+            ("subscriptions", self.subscriptions != new.subscriptions),
+            ("channels", self.channels != new.channels),
+            ("summary", self.summary != new.summary),
+            ("weekly_report", self.weekly_report != new.weekly_report),
+            ("newline", self.newline != new.newline),
+            ("kraken", self.kraken != new.kraken),
+            ("street_rate", self.street_rate != new.street_rate),
+            ("route", self.route != new.route),
+            ("display", self.display != new.display),
+            ("prices", self.prices != new.prices),
+            ("history", self.history != new.history),
+            ("audit", self.audit != new.audit),
+            ("chart", self.chart != new.chart),
+            ("analytics", self.analytics != new.analytics),
+            ("reporting", self.reporting != new.reporting),
+            ("snapshot", self.snapshot != new.snapshot),
+            ("http", self.http != new.http),
+            ("http_client", self.http_client != new.http_client),
+            ("alert_webhooks", self.alert_webhooks != new.alert_webhooks),
+            (
+                "fault_injection",
+                self.fault_injection != new.fault_injection,
+            ),
+            ("logging", self.logging != new.logging),
+            ("bots", self.bots != new.bots),
+        ]
+        .into_iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(section, _)| section)
+        .collect()
+    }
+
+    fn from_toml(
+        path: &Path,
+        text: &str,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let parse_error = |message: String| ConfigError::Parse {
+            path: path.to_path_buf(),
+            message,
+        };
+        let mut table: toml::Table = text.parse().map_err(|e| parse_error(format!("{}", e)))?;
+        for (variable, key, kind) in ENV_OVERRIDES {
+            if let Some(value) = env(variable) {
+                let value = env_value(&value, *kind).map_err(|message| ConfigError::Env {
+                    variable: variable.to_string(),
+                    message,
+                })?;
+                set_key(&mut table, key, value);
+            }
+        }
+        let config: Config = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| parse_error(e.message().to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check what the types alone can't, listing every problem at once
+    fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        if self.telegram.token.as_deref().is_none_or(str::is_empty) {
+            problems.push("telegram.token (TELOXIDE_TOKEN) is required".to_string());
+        }
+        let newline = cfg!(feature = "newline") && self.newline.enabled;
+        if newline && self.newline.cookie.as_deref().is_none_or(str::is_empty) {
+            problems.push("newline.cookie (NEWLINE_COOKIE) is required".to_string());
+        }
+        if self.telegram.messages_per_second == 0 {
+            problems.push(
+                "telegram.messages_per_second (MESSAGES_PER_SECOND) must be at least 1".to_string(),
+            );
+        }
+        if self.subscriptions.interval_minutes == 0 {
+            problems.push(
+                "subscriptions.interval_minutes (SUBSCRIPTION_INTERVAL_MINUTES) must be at least 1"
+                    .to_string(),
+            );
+        }
+        if self.prices.poll_interval_minutes == 0 {
+            problems.push(
+                "prices.poll_interval_minutes (PRICE_POLL_INTERVAL_MINUTES) must be at least 1"
+                    .to_string(),
+            );
+        }
+        if self.history.raw_days == 0 || self.history.hourly_days < self.history.raw_days {
+            problems.push(
+                "history.raw_days (HISTORY_RAW_DAYS) must be at least 1 and at most history.hourly_days (HISTORY_HOURLY_DAYS)"
+                    .to_string(),
+            );
+        }
+        if u64::from(self.display.sparkline_hours) > self.history.raw_days.saturating_mul(24) {
+            problems.push(
+                "display.sparkline_hours (PRICE_SPARKLINE_HOURS) must be at most the hours of history.raw_days (HISTORY_RAW_DAYS)"
+                    .to_string(),
+            );
+        }
+        if self.history.hourly_days > MAX_RETENTION_DAYS {
+            problems.push(format!(
+                "history.hourly_days (HISTORY_HOURLY_DAYS) must be at most {}",
+                MAX_RETENTION_DAYS
+            ));
+        }
+        let rate_limits = [
+            (
+                "newline.rate_limit_per_hour (NEWLINE_RATE_LIMIT_PER_HOUR)",
+                self.newline.rate_limit_per_hour,
+            ),
+            (
+                "kraken.rate_limit_per_hour (KRAKEN_RATE_LIMIT_PER_HOUR)",
+                self.kraken.rate_limit_per_hour,
+            ),
+        ];
+        for (setting, _) in rate_limits.iter().filter(|(_, limit)| *limit == Some(0)) {
+            problems.push(format!("{} must be at least 1", setting));
+        }
+        let http_client = &self.http_client;
+        if http_client.timeout_seconds == 0 || http_client.connect_timeout_seconds == 0 {
+            problems.push(
+                "http_client.timeout_seconds (PROVIDER_TIMEOUT_SECONDS) and connect_timeout_seconds (PROVIDER_CONNECT_TIMEOUT_SECONDS) must be at least 1"
+                    .to_string(),
+            );
+        }
+        let url_schemes = ["http://", "https://"];
+        if let Some(proxy) = &http_client.proxy {
+            if !url_schemes.iter().any(|scheme| proxy.starts_with(scheme)) {
+                problems.push(format!(
+                    "http_client.proxy (PROVIDER_PROXY) must be an http:// or https:// URL, got {}",
+                    proxy
+                ));
+            }
+        }
+        if let Some(url) = &self.http.public_url {
+            if !url_schemes.iter().any(|scheme| url.starts_with(scheme)) {
+                problems.push(format!(
+                    "http.public_url (HTTP_PUBLIC_URL) must be an http:// or https:// URL, got {}",
+                    url
+                ));
+            }
+        }
+        let webhooks = &self.alert_webhooks;
+        for (chat_id, url) in &webhooks.urls {
+            if chat_id.parse::<i64>().is_err() {
+                problems.push(format!("alert_webhooks.urls: {} is not a chat id", chat_id));
+            }
+            if !url_schemes.iter().any(|scheme| url.starts_with(scheme)) {
+                problems.push(format!(
+                    "alert_webhooks.urls: the URL of {} must be an http:// or https:// URL",
+                    chat_id
+                ));
+            }
+        }
+        if !webhooks.urls.is_empty() && webhooks.secret.as_deref().is_none_or(str::is_empty) {
+            problems.push(
+                "alert_webhooks.secret (ALERT_WEBHOOK_SECRET) is required with alert_webhooks.urls"
+                    .to_string(),
+            );
+        }
+        let faults = &self.fault_injection;
+        if faults.error_percent > 100 || faults.stale_percent > 100 {
+            problems.push(
+                "fault_injection.error_percent (FAULT_ERROR_PERCENT) and stale_percent (FAULT_STALE_PERCENT) must be at most 100"
+                    .to_string(),
+            );
+        }
+        if self.street_rate.trim_percent >= 50 {
+            problems.push(
+                "street_rate.trim_percent (STREET_RATE_TRIM_PERCENT) must be below 50".to_string(),
+            );
+        }
+        let route = &self.route;
+        if route.fee_bps >= 10_000 || route.fees.values().any(|fee| *fee >= 10_000) {
+            problems
+                .push("route fees (ROUTE_FEE_BPS) must be below 10000 basis points".to_string());
+        }
+        for pair in route.fees.keys() {
+            if CurrencyPair::from_str(pair).is_err() {
+                problems.push(format!("route.fees: unknown pair {}", pair));
+            }
+        }
+        if route.max_hops == 0 {
+            problems.push("route.max_hops (ROUTE_MAX_HOPS) must be at least 1".to_string());
+        }
+        let display = &self.display;
+        if display.decimals > MAX_PRICE_DECIMALS
+            || display
+                .pairs
+                .values()
+                .any(|format| format.decimals > MAX_PRICE_DECIMALS)
+        {
+            problems.push(format!(
+                "display decimals (PRICE_DECIMALS) must be at most {}",
+                MAX_PRICE_DECIMALS
+            ));
+        }
+        for pair in display.pairs.keys() {
+            if CurrencyPair::from_str(pair).is_err() {
+                problems.push(format!("display.pairs: unknown pair {}", pair));
+            }
+        }
+        if self.audit.retention_days == 0 || self.audit.retention_days > MAX_RETENTION_DAYS {
+            problems.push(format!(
+                "audit.retention_days (AUDIT_RETENTION_DAYS) must be between 1 and {}",
+                MAX_RETENTION_DAYS
+            ));
+        }
+        if self.history.compaction_interval_minutes == 0 {
+            problems.push(
+                "history.compaction_interval_minutes (HISTORY_COMPACTION_INTERVAL_MINUTES) must be at least 1"
+                    .to_string(),
+            );
+        }
+        let mut ids = vec![MAIN_BOT_ID];
+        for bot in &self.bots {
+            if bot.id.is_empty() || ids.contains(&bot.id.as_str()) {
+                problems.push(format!("bots: id '{}' is empty or used twice", bot.id));
+            }
+            ids.push(&bot.id);
+            if bot.token.is_empty() {
+                problems.push(format!("bots: token of '{}' is empty", bot.id));
+            }
+        }
+        let mut files = Vec::new();
+        for file in self
+            .bots()
+            .into_iter()
+            .filter_map(|bot| bot.subscriptions_file)
+        {
+            if files.contains(&file) {
+                problems.push(format!(
+                    "bots: {} is the subscriptions file of two bots",
+                    file.display()
+                ));
+            }
+            files.push(file);
+        }
+        if self
+            .snapshot
+            .file
+            .as_ref()
+            .is_some_and(|file| files.contains(file))
+        {
+            problems.push(
+                "snapshot.file (SNAPSHOT_FILE) must differ from the subscriptions files"
+                    .to_string(),
+            );
+        }
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(ConfigError::Invalid(problems)),
+        }
+    }
+}
+
+/// `file` with `id` added before the extension, `subscribers.json` becomes
+/// `subscribers.<id>.json`
+fn bot_state_file(file: &Path, id: &str) -> PathBuf {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match file.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, id, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, id),
+    };
+    file.with_file_name(name)
+}
+
+/// The config file and whether it has to exist
+fn config_file(env: &impl Fn(&str) -> Option<String>) -> (PathBuf, bool) {
+    match env("CONFIG_FILE") {
+        Some(path) => (PathBuf::from(path), true),
+        None => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+    }
+}
+
+/// Reload the config file whenever it changes and publish the result through
+/// `sender` if it differs from the current config. Edits that don't load keep
+/// the previous config and are logged. Runs until the watcher is dropped.
+pub fn watch(
+    env: impl Fn(&str) -> Option<String> + Send + 'static,
+    sender: watch::Sender<Config>,
+) -> notify::Result<RecommendedWatcher> {
+    let (path, _) = config_file(&env);
+    let file_name = path.file_name().map(|name| name.to_os_string());
+    // Editors save by replacing the file, which ends a watch on the file itself
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                tracing::warn!("Config watch error: {}", e);
+                return;
+            }
+        };
+        let touches_config = event
+            .paths
+            .iter()
+            .any(|changed| changed.file_name() == file_name.as_deref());
+        if matches!(event.kind, EventKind::Access(_)) || !touches_config {
+            return;
+        }
+        // A file deleted halfway through a save isn't the same as no file
+        match Config::load_file(&path, true, &env) {
+            Ok(config) => {
+                sender.send_if_modified(|current| {
+                    let changed = *current != config;
+                    *current = config;
+                    changed
+                });
+            }
+            Err(e) => tracing::warn!("Keeping the previous configuration: {}", e),
+        }
+    })?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+    Ok(watcher)
+}
+
+fn env_value(value: &str, kind: EnvKind) -> Result<toml::Value, String> {
+    match kind {
+        EnvKind::String => Ok(toml::Value::String(value.to_string())),
+        EnvKind::Integer => value
+            .trim()
+            .parse()
+            .map(toml::Value::Integer)
+            .map_err(|_| format!("'{}' is not a number", value)),
+        EnvKind::Bool => match value.trim() {
+            "true" | "1" => Ok(toml::Value::Boolean(true)),
+            "false" | "0" | "" => Ok(toml::Value::Boolean(false)),
+            other => Err(format!("'{}' is not true or false", other)),
+        },
+        EnvKind::IdList => Ok(toml::Value::Array(
+            parse_id_list(value)?
+                .into_iter()
+                .map(toml::Value::Integer)
+                .collect(),
+        )),
+        EnvKind::List => Ok(toml::Value::Array(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| toml::Value::String(item.to_string()))
+                .collect(),
+        )),
+    }
+}
+
+/// Set a dotted `section.key` in `table`, creating the section
+fn set_key(table: &mut toml::Table, key: &str, value: toml::Value) {
+    let (section, key) = key.split_once('.').expect("override keys have a section");
+    let section = table
+        .entry(section)
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    if let toml::Value::Table(section) = section {
+        section.insert(key.to_string(), value);
+    }
+}
+
+fn parsed<'de, D, T>(
+    deserializer: D,
+    parse: impl FnOnce(&str) -> Result<T, String>,
+) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let spec = String::deserialize(deserializer)?;
+    parse(&spec).map_err(de::Error::custom)
+}
+
+fn message_format<'de, D: Deserializer<'de>>(deserializer: D) -> Result<MessageFormat, D::Error> {
+    parsed(deserializer, MessageFormat::from_str)
+}
+
+fn job_schedule<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<JobSchedule>, D::Error> {
+    parsed(deserializer, JobSchedule::parse).map(Some)
+}
+
+fn time_of_day<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<NaiveTime>, D::Error> {
+    parsed(deserializer, |spec| {
+        NaiveTime::parse_from_str(spec.trim(), "%H:%M")
+            .map(Some)
+            .map_err(|_| format!("'{}' is not a time like 21:00", spec))
+    })
+}
+
+fn periods<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<chrono::Duration>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|period| {
+            let max = chrono::Duration::days(MAX_RETENTION_DAYS as i64);
+            parse_period(period, max).ok_or_else(|| {
+                de::Error::custom(format!("'{}' is not a period like 1h or 7d", period))
+            })
+        })
+        .collect()
+}
+
+fn missed_ticks<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<MissedTickBehavior, D::Error> {
+    parsed(deserializer, parse_missed_tick_behavior)
+}
+
+fn channel_list<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<ChannelConfig>, D::Error> {
+    parsed(deserializer, parse_channels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    const REQUIRED: &str = r#"
+        [telegram]
+        token = "123:abc"
+
+        [newline]
+        cookie = "session=1"
+    "#;
+
+    fn load(text: &str, env: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Config::from_toml(Path::new("config.toml"), text, |name| {
+            env.get(name).cloned()
+        })
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = load(REQUIRED, &[]).unwrap();
+        assert_eq!(
+            config.subscriptions.broadcast_schedule(),
+            JobSchedule::from_minutes(10)
+        );
+        assert_eq!(config.telegram.commands_per_minute, 10);
+        assert_eq!(config.telegram.duplicate_window_seconds, 3);
+        assert_eq!(config.telegram.messages_per_second, 25);
+        assert!(!config.fault_injection.faults().is_active());
+        assert_eq!(config.newline.preferred_city, "spb");
+        assert_eq!(config.kraken.base_url, "https://api.kraken.com");
+        assert_eq!(config.newline.rate_limit_per_hour, None);
+        assert!(!config.street_rate.enabled);
+        assert!(config.credential_expiries().is_empty());
+        assert_eq!(config.route.fee_bps(&CurrencyPair::USD2RUB), 50);
+        assert_eq!(
+            config.display.price_format(&CurrencyPair::USD2RUB),
+            PriceFormat::default()
+        );
+        assert_eq!(
+            config.display.sparkline_window(),
+            Some(chrono::Duration::hours(24))
+        );
+        assert_eq!(config.prices.poll_interval_minutes, 5);
+        assert!(config.prices.conditional_requests);
+        assert_eq!(config.logging.format, LogFormat::Text);
+        assert_eq!(config.analytics.windows, default_windows());
+        assert!(config.channels.list.is_empty());
+        assert!(config.http.addr.is_none());
+        assert!(config.http.public_url.is_none());
+        assert_eq!(config.http_client, HttpClientConfig::default());
+        assert_eq!(config.http_client.timeout_seconds, 30);
+    }
+
+    #[test]
+    fn test_example_config_is_valid() {
+        let config = load(
+            include_str!("../config.example.toml"),
+            &[
+                ("TELOXIDE_TOKEN", "123:abc"),
+                ("NEWLINE_COOKIE", "session=1"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            config.subscriptions.message_text,
+            "Периодическое сообщение от бота"
+        );
+        assert_eq!(
+            config.chart.font_path,
+            PathBuf::from(DEFAULT_CHART_FONT_PATH)
+        );
+    }
+
+    #[test]
+    fn test_file_values() {
+        let text = format!(
+            "{}{}",
+            REQUIRED,
+            r#"
+            [subscriptions]
+            schedule = "0 0 9 * * *"
+            missed_ticks = "burst"
+            jitter_seconds = 30
+
+            [telegram.extra]
+            "#
+        );
+        // Unknown keys are rejected instead of silently ignored
+        assert!(matches!(load(&text, &[]), Err(ConfigError::Parse { .. })));
+
+        let text = text.replace("[telegram.extra]", "[channels]\nlist = \"@rates\"");
+        let config = load(&text, &[]).unwrap();
+        assert!(matches!(
+            config.subscriptions.broadcast_schedule(),
+            JobSchedule::Cron(_)
+        ));
+        assert_eq!(config.subscriptions.missed_ticks, MissedTickBehavior::Burst);
+        assert_eq!(config.subscriptions.jitter_seconds, 30);
+        assert_eq!(config.channels.list.len(), 1);
+        assert!(config.summary.schedule().is_none());
+
+        let config = load(
+            REQUIRED,
+            &[("SUMMARY_TIME", "21:30"), ("SUMMARY_CHANNELS", "true")],
+        )
+        .unwrap();
+        assert_eq!(config.summary.time, NaiveTime::from_hms_opt(21, 30, 0));
+        assert!(config.summary.channels);
+        let Some(JobSchedule::Cron(schedule)) = config.summary.schedule() else {
+            panic!("the summary runs on a daily cron schedule");
+        };
+        assert_eq!(schedule.to_string(), "0 30 21 * * *");
+
+        assert!(config.weekly_report.schedule.is_none());
+        let config = load(REQUIRED, &[("WEEKLY_REPORT_SCHEDULE", "0 0 9 * * Mon")]).unwrap();
+        assert!(matches!(
+            config.weekly_report.schedule,
+            Some(JobSchedule::Cron(_))
+        ));
+
+        let text = format!(
+            "{}{}",
+            REQUIRED,
+            r#"
+            [display]
+            thousands_separator = " "
+            pairs = { "USDT/USD" = { decimals = 4, rounding = "down" } }
+            "#
+        );
+        let config = load(
+            &text,
+            &[("PRICE_DECIMALS", "1"), ("PRICE_SPARKLINE_HOURS", "0")],
+        )
+        .unwrap();
+        assert!(config.display.sparkline_window().is_none());
+        assert!(config.display.footer.is_none());
+        let usd = config.display.price_format(&CurrencyPair::USD2RUB);
+        assert_eq!(usd.decimals, 1);
+        assert_eq!(usd.thousands_separator, " ");
+        let usdt = config.display.price_format(&CurrencyPair::USDT2USD);
+        assert_eq!(usdt.decimals, 4);
+        assert_eq!(usdt.rounding, Rounding::Down);
+        assert_eq!(usdt.thousands_separator, "");
+        let text = text.replace("USDT/USD", "BTC/RUB");
+        assert!(load(&text, &[]).is_err());
+
+        // The sparklines are drawn from the raw samples
+        let error = load(
+            REQUIRED,
+            &[
+                ("PRICE_SPARKLINE_HOURS", "4294967295"),
+                ("HISTORY_RAW_DAYS", "7"),
+            ],
+        )
+        .unwrap_err();
+        assert!(
+            error.to_string().contains("PRICE_SPARKLINE_HOURS"),
+            "{}",
+            error
+        );
+        assert!(load(
+            REQUIRED,
+            &[("PRICE_SPARKLINE_HOURS", "168"), ("HISTORY_RAW_DAYS", "7")]
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let text = format!("{}\n[prices]\npoll_interval_minutes = 15\n", REQUIRED);
+        let config = load(
+            &text,
+            &[
+                ("PRICE_POLL_INTERVAL_MINUTES", "2"),
+                ("ADMIN_USER_IDS", "1, 2"),
+                ("PIN_PERIODIC_MESSAGE", "1"),
+                ("HTTP_API_ADDR", "127.0.0.1:8080"),
+                ("HTTP_PUBLIC_URL", "https://bot.example.com"),
+                ("ERROR_REPORT_COOLDOWN_MINUTES", "1"),
+                ("NEWLINE_COOKIE_EXPIRES", "2026-12-31"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            config.credential_expiries(),
+            vec![(
+                "NewLine cookie",
+                NaiveDate::from_ymd_opt(2026, 12, 31).unwrap()
+            )]
+        );
+        assert_eq!(config.prices.poll_interval_minutes, 2);
+        assert_eq!(config.telegram.admin_user_ids, vec![1, 2]);
+        assert!(config.subscriptions.pin_messages);
+        assert_eq!(config.http.addr, Some("127.0.0.1:8080".parse().unwrap()));
+        assert_eq!(
+            config.http.public_url.as_deref(),
+            Some("https://bot.example.com")
+        );
+        assert_eq!(config.reporting.cooldown(), Duration::from_secs(60));
+        let config = load(
+            REQUIRED,
+            &[("ERROR_REPORT_COOLDOWN_MINUTES", "9223372036854775807")],
+        )
+        .unwrap();
+        assert_eq!(config.reporting.cooldown(), Duration::from_secs(u64::MAX));
+
+        // Required settings can come from the environment alone
+        let config = load(
+            "",
+            &[
+                ("TELOXIDE_TOKEN", "123:abc"),
+                ("NEWLINE_COOKIE", "session=1"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.newline.cookie.as_deref(), Some("session=1"));
+    }
+
+    #[test]
+    fn test_invalid_values_name_the_setting() {
+        let error = load(REQUIRED, &[("SUBSCRIPTION_INTERVAL_MINUTES", "ten")]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid SUBSCRIPTION_INTERVAL_MINUTES: 'ten' is not a number"
+        );
+        let error = load(REQUIRED, &[("SUBSCRIPTION_SCHEDULE", "soon")]).unwrap_err();
+        assert!(error.to_string().contains("Invalid cron expression 'soon'"));
+        let error = load(REQUIRED, &[("PARSE_MODE", "bbcode")]).unwrap_err();
+        assert!(error.to_string().contains("'bbcode' is not plain"));
+        let error = load(REQUIRED, &[("ANALYTICS_WINDOWS", "1h, 1y")]).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("'1y' is not a period like 1h or 7d"));
+        let error = load(REQUIRED, &[("SUMMARY_TIME", "9pm")]).unwrap_err();
+        assert!(error.to_string().contains("'9pm' is not a time like 21:00"));
+        let error = load(REQUIRED, &[("PROVIDER_PROXY", "proxy:3128")]).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("http_client.proxy (PROVIDER_PROXY) must be an http:// or https:// URL"));
+    }
+
+    #[test]
+    fn test_alert_webhooks() {
+        let webhooks = r#"
+            [alert_webhooks.urls]
+            "42" = "https://example.com/alerts"
+            "-100" = "http://localhost:9000/hook"
+        "#;
+        let text = format!("{}{}", REQUIRED, webhooks);
+        let error = load(&text, &[]).unwrap_err();
+        assert!(error.to_string().contains(
+            "alert_webhooks.secret (ALERT_WEBHOOK_SECRET) is required with alert_webhooks.urls"
+        ));
+        let config = load(&text, &[("ALERT_WEBHOOK_SECRET", "s3cret")]).unwrap();
+        let callbacks = config.alert_webhooks.callbacks();
+        assert_eq!(callbacks[&42], "https://example.com/alerts");
+        assert_eq!(callbacks[&-100], "http://localhost:9000/hook");
+
+        let text = format!("{}{}\"me\" = \"ftp://example.com\"\n", REQUIRED, webhooks);
+        let error = load(&text, &[("ALERT_WEBHOOK_SECRET", "s3cret")]).unwrap_err();
+        assert!(error.to_string().contains("me is not a chat id"));
+        assert!(error
+            .to_string()
+            .contains("the URL of me must be an http:// or https:// URL"));
+    }
+
+    #[test]
+    #[cfg(feature = "newline")]
+    fn test_validation_lists_every_problem() {
+        let error = load("[prices]\npoll_interval_minutes = 0\n", &[]).unwrap_err();
+        let ConfigError::Invalid(problems) = error else {
+            panic!("expected a validation error, got {}", error);
+        };
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].contains("TELOXIDE_TOKEN"));
+        assert!(problems[1].contains("NEWLINE_COOKIE"));
+        assert!(problems[2].contains("PRICE_POLL_INTERVAL_MINUTES"));
+    }
+
+    #[test]
+    fn test_history_retention() {
+        let config = load(REQUIRED, &[]).unwrap();
+        assert_eq!(config.history.retention(), RetentionPolicy::default());
+
+        let config = load(
+            REQUIRED,
+            &[("HISTORY_RAW_DAYS", "7"), ("HISTORY_HOURLY_DAYS", "90")],
+        )
+        .unwrap();
+        assert_eq!(config.history.retention().raw, chrono::Duration::days(7));
+        assert_eq!(
+            config.history.retention().hourly,
+            chrono::Duration::days(90)
+        );
+
+        let error = load(REQUIRED, &[("HISTORY_HOURLY_DAYS", "7")]).unwrap_err();
+        assert!(error.to_string().contains("HISTORY_RAW_DAYS"), "{}", error);
+
+        let huge = [
+            ("HISTORY_RAW_DAYS", "99999999999999"),
+            ("HISTORY_HOURLY_DAYS", "99999999999999"),
+        ];
+        let error = load(REQUIRED, &huge).unwrap_err();
+        assert!(
+            error.to_string().contains("HISTORY_HOURLY_DAYS"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_audit_limits() {
+        let config = load(REQUIRED, &[("AUDIT_MAX_ENTRIES_PER_CHAT", "0")]).unwrap();
+        assert_eq!(config.audit.max_entries_per_chat, 0);
+        assert_eq!(config.audit.retention(), chrono::Duration::days(30));
+
+        for days in ["0", "99999999999999"] {
+            let error = load(REQUIRED, &[("AUDIT_RETENTION_DAYS", days)]).unwrap_err();
+            assert!(
+                error.to_string().contains("AUDIT_RETENTION_DAYS"),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn test_restart_required_ignores_reloadable_settings() {
+        let config = load(REQUIRED, &[]).unwrap();
+        let mut new = config.clone();
+        new.telegram.admin_user_ids = vec![1];
+        new.subscriptions.message_text = "Новый текст".to_string();
+        new.subscriptions.schedule = Some(JobSchedule::from_minutes(30));
+        new.newline.enabled = false;
+        new.kraken.enabled = false;
+        new.prices.poll_interval_minutes = 1;
+        assert!(config.restart_required(&new).is_empty());
+
+        new.telegram.commands_per_minute = 0;
+        new.logging.format = LogFormat::Json;
+        assert_eq!(config.restart_required(&new), vec!["telegram", "logging"]);
+    }
+
+    #[tokio::test]
+    async fn test_watch_publishes_changed_config() {
+        let dir = std::env::temp_dir().join(format!("currency-bot-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(&path, REQUIRED).unwrap();
+        let env_path = path.clone();
+        let env = move |name: &str| (name == "CONFIG_FILE").then(|| env_path.display().to_string());
+
+        let config = Config::load(&env).unwrap();
+        let (sender, mut receiver) = watch::channel(config);
+        let _watcher = watch(env, sender).unwrap();
+
+        // An invalid edit is not published
+        std::fs::write(&path, "[prices]\npoll_interval_minutes = 0\n").unwrap();
+        std::fs::write(
+            &path,
+            format!("{}\n[prices]\npoll_interval_minutes = 1\n", REQUIRED),
+        )
+        .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), receiver.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(receiver.borrow().prices.poll_interval_minutes, 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_additional_bots() {
+        let text = format!(
+            "{}{}",
+            REQUIRED,
+            r#"
+            [subscriptions]
+            file = "state/subscribers.json"
+
+            [[bots]]
+            id = "ops"
+            token = "456:def"
+            modules = ["price", "admin"]
+            "#
+        );
+        let config = load(&text, &[("BOT_MODULES", "price, subscriber")]).unwrap();
+        let bots = config.bots();
+        assert_eq!(bots.len(), 2);
+        assert_eq!(bots[0].id, MAIN_BOT_ID);
+        assert_eq!(bots[0].token, "123:abc");
+        assert_eq!(
+            bots[0].modules,
+            Some(vec!["price".to_string(), "subscriber".to_string()])
+        );
+        assert_eq!(bots[1].id, "ops");
+        assert_eq!(
+            bots[1].subscriptions_file,
+            Some(PathBuf::from("state/subscribers.ops.json"))
+        );
+        let Err(ConfigError::Invalid(problems)) =
+            load(&text, &[("SNAPSHOT_FILE", "state/subscribers.ops.json")])
+        else {
+            panic!("expected the snapshot file to be rejected");
+        };
+        assert!(problems[0].contains("SNAPSHOT_FILE"));
+
+        let text = text.replace("id = \"ops\"", "id = \"main\"");
+        let Err(ConfigError::Invalid(problems)) = load(&text, &[]) else {
+            panic!("expected the duplicate id to be rejected");
+        };
+        assert!(problems[0].contains("'main'"));
+    }
+}
@@ -3,6 +3,7 @@
 
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::str::FromStr;
 use thiserror::Error;
 
 /// Price data structure
@@ -13,7 +14,7 @@ pub struct PriceData {
 }
 
 /// Error type for price providers
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
 pub enum PriceProviderError {
     #[error("Network error: {0}")]
     Network(String),
@@ -28,6 +29,15 @@ pub enum PriceProviderError {
     Provider(String),
 }
 
+impl PriceProviderError {
+    /// Whether the provider rejected its credentials (401 or 403), e.g. an
+    /// expired NewLine cookie
+    pub fn is_auth_failure(&self) -> bool {
+        matches!(self, PriceProviderError::Api(message)
+            if message.contains("401") || message.contains("403"))
+    }
+}
+
 /// Domain currency pairs used in the application
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum CurrencyPair {
@@ -37,6 +47,10 @@ pub enum CurrencyPair {
     USDTe2RUB,
     /// USD to RUB
     USD2RUB,
+    /// USDT to USD, a stablecoin leg without a ruble side
+    USDT2USD,
+    /// EUR to USD, a fiat leg without a ruble side
+    EUR2USD,
 }
 
 impl fmt::Display for CurrencyPair {
@@ -45,19 +59,39 @@ impl fmt::Display for CurrencyPair {
             CurrencyPair::USDCe2RUB => "USDCe/RUB",
             CurrencyPair::USDTe2RUB => "USDTe/RUB",
             CurrencyPair::USD2RUB => "USD/RUB",
+            CurrencyPair::USDT2USD => "USDT/USD",
+            CurrencyPair::EUR2USD => "EUR/USD",
         };
         write!(f, "{}", s)
     }
 }
 
-impl CurrencyPair {
+impl FromStr for CurrencyPair {
+    type Err = String;
+
     /// Parse string to CurrencyPair
-    pub fn from_str(s: &str) -> Option<Self> {
+    fn from_str(s: &str) -> Result<Self, String> {
         match s {
-            "USDCe/RUB" => Some(CurrencyPair::USDCe2RUB),
-            "USDTe/RUB" => Some(CurrencyPair::USDTe2RUB),
-            "USD/RUB" => Some(CurrencyPair::USD2RUB),
-            _ => None,
+            "USDCe/RUB" => Ok(CurrencyPair::USDCe2RUB),
+            "USDTe/RUB" => Ok(CurrencyPair::USDTe2RUB),
+            "USD/RUB" => Ok(CurrencyPair::USD2RUB),
+            "USDT/USD" => Ok(CurrencyPair::USDT2USD),
+            "EUR/USD" => Ok(CurrencyPair::EUR2USD),
+            _ => Err(format!("unknown currency pair {}", s)),
+        }
+    }
+}
+
+impl CurrencyPair {
+    /// Currency bought and currency paid, a quote being the price of one
+    /// unit of the first in the second
+    pub fn currencies(&self) -> (&'static str, &'static str) {
+        match self {
+            CurrencyPair::USDCe2RUB => ("USDC", "RUB"),
+            CurrencyPair::USDTe2RUB => ("USDT", "RUB"),
+            CurrencyPair::USD2RUB => ("USD", "RUB"),
+            CurrencyPair::USDT2USD => ("USDT", "USD"),
+            CurrencyPair::EUR2USD => ("EUR", "USD"),
         }
     }
 }
@@ -71,58 +105,39 @@ pub fn get_all_currency_pairs() -> Vec<CurrencyPair> {
     ]
 }
 
+/// Fiat and stablecoin legs quoted by international providers: they can be
+/// asked for (`/price USDT/USD`), but aren't part of the broadcasts
+pub fn get_reference_pairs() -> Vec<CurrencyPair> {
+    vec![CurrencyPair::USDT2USD, CurrencyPair::EUR2USD]
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{get_all_currency_pairs, CurrencyPair};
-    use crate::price_service::provider::PriceProvider;
-    use crate::price_service::providers::newline_provider::{NewLineConfig, NewLineProvider};
+    use super::{get_all_currency_pairs, get_reference_pairs, CurrencyPair};
+    use std::str::FromStr;
 
     #[test]
     fn test_currency_pair_parsing() {
         assert_eq!(
-            CurrencyPair::from_str("USD/RUB"),
+            CurrencyPair::from_str("USD/RUB").ok(),
             Some(CurrencyPair::USD2RUB)
         );
         assert_eq!(
-            CurrencyPair::from_str("USDCe/RUB"),
+            CurrencyPair::from_str("USDCe/RUB").ok(),
             Some(CurrencyPair::USDCe2RUB)
         );
         assert_eq!(
-            CurrencyPair::from_str("USDTe/RUB"),
+            CurrencyPair::from_str("USDTe/RUB").ok(),
             Some(CurrencyPair::USDTe2RUB)
         );
-        assert_eq!(CurrencyPair::from_str("INVALID"), None);
-    }
-
-    #[test]
-    fn test_currency_pair_mapping() {
-        let config = NewLineConfig {
-            base_url: "https://test.com".to_string(),
-            cookie: "test_cookie".to_string(),
-            preferred_city: "spb".to_string(),
-        };
-
-        let provider = NewLineProvider::new(config);
-
-        // Test mapping indirectly through supports_currency_pair
-        assert!(provider.supports_currency_pair(&CurrencyPair::USDCe2RUB));
-        assert!(provider.supports_currency_pair(&CurrencyPair::USDTe2RUB));
-        assert!(provider.supports_currency_pair(&CurrencyPair::USD2RUB));
-    }
-
-    #[test]
-    fn test_currency_pair_support() {
-        let config = NewLineConfig {
-            base_url: "https://test.com".to_string(),
-            cookie: "test_cookie".to_string(),
-            preferred_city: "spb".to_string(),
-        };
-
-        let provider = NewLineProvider::new(config);
-
-        assert!(provider.supports_currency_pair(&CurrencyPair::USDCe2RUB));
-        assert!(provider.supports_currency_pair(&CurrencyPair::USDTe2RUB));
-        assert!(provider.supports_currency_pair(&CurrencyPair::USD2RUB));
+        assert_eq!(
+            CurrencyPair::from_str("EUR/USD").ok(),
+            Some(CurrencyPair::EUR2USD)
+        );
+        assert_eq!(CurrencyPair::from_str("INVALID").ok(), None);
+        for pair in get_reference_pairs() {
+            assert_eq!(CurrencyPair::from_str(&pair.to_string()).ok(), Some(pair));
+        }
     }
 
     #[test]
@@ -0,0 +1,1155 @@
+// Localization of user-facing replies
+// Simple key -> string bundles per language with `{name}` placeholders
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use teloxide::types::ChatId;
+
+/// Language of the bot's replies in a chat
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    Ru,
+    En,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::Ru, Language::En];
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code.to_lowercase().as_str() {
+            "ru" => Some(Language::Ru),
+            "en" => Some(Language::En),
+            _ => None,
+        }
+    }
+
+    /// IETF code used by Telegram, e.g. for `set_my_commands`
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::Ru => "ru",
+            Language::En => "en",
+        }
+    }
+
+    fn bundle(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Language::Ru => RU,
+            Language::En => EN,
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// Text of `key` in `language`, falling back to Russian, `None` for keys
+/// that are not translated at all
+pub fn lookup(language: Language, key: &str) -> Option<&'static str> {
+    let find = |bundle: &'static [(&'static str, &'static str)]| {
+        bundle
+            .iter()
+            .find(|(candidate, _)| *candidate == key)
+            .map(|(_, text)| *text)
+    };
+    find(language.bundle()).or_else(|| find(RU))
+}
+
+/// Text of `key` in `language`, falling back to Russian and then to the
+/// key itself so a missing translation is visible but never fatal
+pub fn tr(language: Language, key: &'static str) -> &'static str {
+    lookup(language, key).unwrap_or_else(|| {
+        tracing::warn!("Missing translation for {}", key);
+        key
+    })
+}
+
+/// [`tr`] with every `{name}` placeholder replaced by its value in `args`
+pub fn tr_args(
+    language: Language,
+    key: &'static str,
+    args: &[(&str, &dyn fmt::Display)],
+) -> String {
+    let mut text = tr(language, key).to_string();
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+    text
+}
+
+/// Language chosen by every chat, shared by the modules
+#[derive(Debug, Clone, Default)]
+pub struct Languages {
+    chats: Arc<Mutex<HashMap<ChatId, Language>>>,
+}
+
+impl Languages {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, chat_id: ChatId) -> Language {
+        let chats = self.chats.lock().unwrap();
+        chats.get(&chat_id).copied().unwrap_or_default()
+    }
+
+    pub fn set(&self, chat_id: ChatId, language: Language) {
+        let mut chats = self.chats.lock().unwrap();
+        if language == Language::default() {
+            chats.remove(&chat_id);
+        } else {
+            chats.insert(chat_id, language);
+        }
+    }
+
+    /// Chats with a non-default language
+    pub fn export(&self) -> HashMap<ChatId, Language> {
+        self.chats.lock().unwrap().clone()
+    }
+
+    pub fn import(&self, languages: HashMap<ChatId, Language>) {
+        for (chat_id, language) in languages {
+            self.set(chat_id, language);
+        }
+    }
+}
+
+const RU: &[(&str, &str)] = &[
+    // Registry and middlewares
+    ("admin_only", "Команда доступна только администраторам."),
+    ("unknown_command", "Неизвестная команда. Используйте /help"),
+    (
+        "unknown_command_suggestion",
+        "Неизвестная команда. Возможно, вы имели в виду {command}? Используйте /help",
+    ),
+    (
+        "rate_limited",
+        "⏳ Слишком много команд. Попробуйте через {seconds} сек.",
+    ),
+    ("unknown_pair", "❌ Неизвестная пара: {pair}"),
+    ("args.missing", "❌ Не хватает аргументов"),
+    ("args.unknown_currency", "❌ Неизвестная валюта: {value}"),
+    ("args.bad_amount", "❌ Неверное число: {value}"),
+    (
+        "args.bad_period",
+        "❌ Неверный период: {value}, например 30m, 2h или 7d",
+    ),
+    ("args.unexpected", "❌ Лишний аргумент: {value}"),
+    ("args.not_a_number", "❌ Аргумент должен быть числом"),
+    ("args.invalid", "❌ Неверные аргументы"),
+    // Help and start
+    ("help.title", "Доступные команды:"),
+    ("help.menu", "Показать справку"),
+    ("help.usage", "/help - Показать эту справку"),
+    ("start.menu", "Начать работу с ботом"),
+    (
+        "start.greeting",
+        "Добро пожаловать в Currency Bot!\nИспользуйте /echo <текст> для эхо-ответа.",
+    ),
+    ("start.subscribed", "✅ Вы подписаны на рассылку курсов: {pairs}"),
+    (
+        "start.unknown_link",
+        "Ссылка, по которой вы пришли, не распознана. Команды бота: /help",
+    ),
+    ("echo.menu", "Отправить эхо-ответ"),
+    ("echo.usage", "/echo <текст> - Отправить эхо-ответ"),
+    ("echo.help", "Использование: /echo <текст>"),
+    // Prices
+    ("price.section", "Курсы валют:"),
+    ("price.menu", "Курс валютной пары"),
+    (
+        "price.usage",
+        "/price [пара ...] - Курс валютных пар, например /price USD/RUB USDTe/RUB, без пары - курс пары по умолчанию",
+    ),
+    (
+        "price.choose",
+        "Выберите пару. Чтобы /price сразу показывал нужную, задайте её: /settings default_pair USD/RUB",
+    ),
+    ("price.newline_usage", "/newLine - Курс USDTe/RUB от NewLine"),
+    (
+        "price.quote",
+        "💰 Курс {pair}\n\nВалютная пара: {source}\nЦена: {price}{trend}",
+    ),
+    ("price.sparkline", "📊 За {period}: {sparkline}"),
+    ("price.range_rolling", "📏 Диапазон за 30 дней: {min}–{max}"),
+    ("price.range_all_time", "📏 За всё время: {min}–{max}"),
+    ("price.action.refresh", "🔄 Обновить"),
+    ("price.action.alert", "🔔 Оповестить на этом уровне"),
+    ("price.action.chart", "📈 График"),
+    (
+        "price.invalid_pair",
+        "❌ Неверный формат валютной пары. Доступные пары: {pairs}",
+    ),
+    ("price.error.network", "🌐 Ошибка сети: {error}"),
+    ("price.error.api", "🔌 Ошибка API: {error}"),
+    ("price.error.parsing", "📜 Ошибка разбора ответа: {error}"),
+    ("price.error.provider", "❌ Ошибка провайдера: {error}"),
+    ("price.partial", "⚠️ Не удалось получить курс: {pairs}"),
+    ("inline.rate_at", "Курс на {time}"),
+    (
+        "convert.usage",
+        "Конвертация: напишите, например, «100 usd в рубли» или «сколько стоит 50 usdt»",
+    ),
+    (
+        "convert.result",
+        "💱 {amount} {from} = {result} {to}\nКурс {pair}: {price}",
+    ),
+    // Charts
+    ("chart.menu", "График курса за период"),
+    (
+        "chart.usage",
+        "/chart <пара> [период] - График курса, например /chart USD/RUB 7d",
+    ),
+    (
+        "chart.help",
+        "Использование: /chart <пара> [период]\nПериод: 30m, 12h, 1d, 7d (до {max}d, по умолчанию 1d)\nДоступные пары: {pairs}\nПример: /chart USD/RUB 7d",
+    ),
+    (
+        "chart.not_enough_data",
+        "Недостаточно данных для графика {pair} за {period}.",
+    ),
+    ("chart.caption", "{pair} за {period}: {price}"),
+    ("chart.render_failed", "❌ Не удалось построить график."),
+    ("chart.min", "мин"),
+    ("chart.max", "макс"),
+    ("chart.now", "сейчас"),
+    // Technical analysis
+    ("ta.menu", "Скользящие средние и волатильность"),
+    (
+        "ta.usage",
+        "/ta <пара> - Скользящие средние (SMA, EMA) и волатильность, например /ta USD/RUB",
+    ),
+    (
+        "ta.help",
+        "Использование: /ta <пара>\nДоступные пары: {pairs}\nПример: /ta USD/RUB",
+    ),
+    ("ta.title", "📈 {pair}: скользящие средние и волатильность"),
+    (
+        "ta.line",
+        "{window}: SMA {sma}, EMA {ema}, волатильность {volatility} ({samples} замеров)",
+    ),
+    ("ta.no_volatility", "н/д"),
+    ("ta.no_data", "Курсы {pair} ещё не записывались."),
+    // Chat stats
+    ("me.menu", "Статистика чата"),
+    (
+        "me.usage",
+        "/me - Статистика чата: команды, сообщения от бота, подписка и оповещения",
+    ),
+    (
+        "me.title",
+        "👤 Статистика чата\n\nКоманд за {days} дн.: {commands}\nСообщений от бота: {messages}\nПодписка: {subscription}\nОповещения: {alerts} активных, сработало {triggered}",
+    ),
+    ("me.not_subscribed", "нет, см. /subscribe"),
+    ("me.subscribed", "есть"),
+    ("me.subscribed_since", "с {date} ({days} дн.)"),
+    ("me.favorite", "Чаще всего: {command} ({count})"),
+    ("me.quiet", "🌙 В тихие часы {quiet} рассылка не приходит"),
+    (
+        "me.delivery_failed",
+        "⚠️ Последнее сообщение рассылки не доставлено ({time}): {error}",
+    ),
+    // Week heatmap
+    ("week.menu", "Изменения курса по дням за неделю"),
+    (
+        "week.usage",
+        "/week <пара> - Изменения курса по дням за 7 дней, например /week USD/RUB",
+    ),
+    (
+        "week.help",
+        "Использование: /week <пара>\nДоступные пары: {pairs}\nПример: /week USD/RUB",
+    ),
+    ("week.title", "🗓 {pair} за последние 7 дней"),
+    // Conversion routes
+    ("route.menu", "Выгодный путь обмена с учётом комиссий"),
+    (
+        "route.usage",
+        "/route <сумма> <из> <в> - Выгодный путь обмена, например /route 100000 RUB USDT",
+    ),
+    (
+        "route.help",
+        "Использование: /route <сумма> <из> <в>\nВалюты: RUB, USD, USDT, USDC, EUR\nПример: /route 100000 RUB USDT",
+    ),
+    ("route.result", "🔀 {amount} {from} → {output} {to}"),
+    ("route.step", "{from} → {to} по {pair}: {rate} (комиссия {fee})"),
+    ("route.not_found", "Нет котировок для обмена {from} на {to}."),
+    // Alerts
+    ("alerts.section", "Оповещения:"),
+    ("alerts.menu.alert", "Создать оповещение о курсе"),
+    ("alerts.menu.alerts", "Список оповещений"),
+    ("alerts.usage.threshold", "/alert <пара> > <цена> - Оповещение о курсе"),
+    (
+        "alerts.usage.moves",
+        "/alert <пара> moves <процент>% in <период> - Оповещение о резком движении",
+    ),
+    ("alerts.usage.list", "/alerts - Список оповещений"),
+    (
+        "alerts.help",
+        "Использование:\n/alert <пара> > <цена> [repeat] - Оповестить, когда курс поднимется выше цены\n/alert <пара> < <цена> [repeat] - Оповестить, когда курс опустится ниже цены\n/alert <пара> moves <N>% in <период> [repeat] - Оповестить о резком движении курса, например moves 2% in 1h\n/alert delete N - Удалить оповещение номер N\n/alerts - Список оповещений\n\nБез repeat оповещение срабатывает один раз и удаляется. Повторяющемуся можно добавить cooldown <период> (не чаще раза в период) и rearm <N>% (снова сработает, только когда курс откатится на N% от порога).\nПример: /alert USD/RUB > 100 repeat cooldown 1h rearm 0.5%",
+    ),
+    ("alerts.moves", "±{percent}% за {period}"),
+    ("alerts.repeating", " (повторяющееся)"),
+    ("alerts.repeating_with", " (повторяющееся, {options})"),
+    ("alerts.cooldown", "не чаще раза в {period}"),
+    ("alerts.rearm", "снова после отката на {percent}%"),
+    (
+        "alerts.bad_rearm",
+        "❌ Укажите откат в процентах меньше 100, например rearm 0.5%",
+    ),
+    (
+        "alerts.options_need_repeat",
+        "❌ cooldown и rearm работают только вместе с repeat",
+    ),
+    ("alerts.limit", "❌ Не больше {max} оповещений на чат"),
+    ("alerts.fired", "🔔 {pair} {condition}: сейчас {price}"),
+    ("alerts.fired_removed", "Оповещение выполнено и удалено."),
+    (
+        "alerts.bad_percent",
+        "❌ Укажите изменение в процентах, например moves 2% in 1h",
+    ),
+    ("alerts.bad_period", "❌ Укажите период, например 30m, 1h или 1d"),
+    ("alerts.bad_price", "❌ Укажите цену, например /alert USD/RUB > 100"),
+    ("alerts.none", "Оповещений нет.\nСоздать: /alert USD/RUB > 100"),
+    (
+        "alerts.list",
+        "Ваши оповещения:\n{alerts}\n\nУдалить: /alert delete N",
+    ),
+    ("alerts.deleted", "Оповещение удалено: {rule}"),
+    ("alerts.not_found", "❌ Нет оповещения номер {number}"),
+    ("alerts.created", "✅ Оповещение #{number} создано: {rule}"),
+    // Subscriptions
+    ("subs.section", "Рассылка:"),
+    ("subs.menu.subscribe", "Подписаться на периодические сообщения"),
+    ("subs.menu.unsubscribe", "Отписаться от периодических сообщений"),
+    ("subs.menu.status", "Статус подписки"),
+    ("subs.menu.template", "Шаблон периодического сообщения"),
+    ("subs.menu.pairs", "Валютные пары в рассылке"),
+    ("subs.menu.language", "Язык бота"),
+    ("subs.menu.calendar", "Расписание сообщений в календаре"),
+    (
+        "subs.usage.subscribe",
+        "/subscribe - Подписаться на периодические сообщения",
+    ),
+    (
+        "subs.usage.unsubscribe",
+        "/unsubscribe - Отписаться от периодических сообщений",
+    ),
+    ("subs.usage.status", "/status - Проверить статус подписки"),
+    (
+        "subs.usage.template",
+        "/template <текст> - Задать шаблон периодического сообщения",
+    ),
+    (
+        "subs.usage.pairs",
+        "/pairs <пары> - Выбрать валютные пары для рассылки",
+    ),
+    ("subs.usage.language", "/language ru|en - Язык бота"),
+    (
+        "subs.usage.calendar",
+        "/calendar [reset] - Ссылка на календарь сообщений бота",
+    ),
+    (
+        "subs.calendar",
+        "📅 Когда бот пишет в этот чат, ссылка для приложения календаря:\n{url}\n\n/calendar reset - новая ссылка вместо этой",
+    ),
+    (
+        "subs.calendar_reset",
+        "📅 Новая ссылка на календарь, старая больше не работает:\n{url}",
+    ),
+    (
+        "subs.calendar_unavailable",
+        "Календарь сообщений не включён в этом боте.",
+    ),
+    ("calendar.name", "Currency Bot: сообщения"),
+    ("calendar.periodic", "Периодическое сообщение с курсами"),
+    ("calendar.summary", "Итоги дня"),
+    ("calendar.weekly_report", "Недельный отчёт"),
+    ("subs.header", "Периодическое сообщение #{number}:\n{text}"),
+    (
+        "subs.pairs_help",
+        "Пары в рассылке: {pairs}\n\nИзменить: /pairs USD/RUB USDTe/RUB\nДоступные пары: {available}",
+    ),
+    (
+        "subs.template_help",
+        "Текущий шаблон сообщения:\n{template}\n\nИзменить: /template <текст>\nСбросить: /template reset\nДоступные подстановки: {placeholders}",
+    ),
+    (
+        "subs.next_in",
+        "Следующее сообщение через {minutes} мин {seconds} сек",
+    ),
+    ("subs.next_soon", "Сообщение будет отправлено скоро..."),
+    ("subs.next_unknown", "Информация о времени рассылки недоступна"),
+    (
+        "subs.status_subscribed",
+        "Вы подписаны на рассылку.\n{next}\nВсего подписчиков: {count}",
+    ),
+    ("subs.status_not_subscribed", "Вы не подписаны на рассылку."),
+    (
+        "subs.group_admins_only",
+        "Управлять подпиской группы могут только администраторы.",
+    ),
+    ("subs.subscribed", "Вы успешно подписались на рассылку!"),
+    ("subs.already_subscribed", "Вы уже подписаны на рассылку."),
+    (
+        "subs.topic_changed",
+        "Рассылка теперь будет приходить в эту тему.",
+    ),
+    ("subs.unsubscribed", "Вы успешно отписались от рассылки."),
+    ("subs.not_subscribed", "Вы не были подписаны на рассылку."),
+    ("subs.template_reset", "Шаблон сброшен на стандартный."),
+    ("subs.template_updated", "Шаблон сообщения обновлён."),
+    ("subs.pairs_updated", "Список пар обновлён."),
+    // Settings
+    ("settings.menu", "Настройки чата"),
+    (
+        "settings.usage",
+        "/settings - Настройки чата: язык, пары, тихие часы, доставка\n/settings delivery edit|new|replace - Обновлять одно сообщение, присылать новое или заменять старое новым\n/settings quiet 22-8|off - Не присылать рассылку ночью\n/settings summary on|off - Итоги дня: открытие, максимум, минимум и закрытие за сутки\n/settings report on|off - Недельный отчёт: CSV с почасовыми курсами за неделю\n/settings default_pair <пара>|off - Пара, которую показывает /price без аргументов\n/settings timezone +03:00|off - Часовой пояс времени котировок и тихих часов\n/settings default_amount <сумма>|off - Сумма конвертации, когда вопрос её не называет",
+    ),
+    (
+        "settings.title",
+        "⚙️ Настройки чата\n\nЯзык: {language}\nПары: {pairs}\nПара по умолчанию: {default_pair}\nЧасовой пояс: {timezone}\nСумма по умолчанию: {amount}\nИнтервал: {interval}\nТихие часы: {quiet}\nДоставка: {delivery}\nИтоги дня: {summary}\nНедельный отчёт: {report}",
+    ),
+    ("settings.interval_minutes", "каждые {minutes} мин"),
+    ("settings.interval_cron", "по расписанию {schedule}"),
+    ("settings.quiet_off", "выкл"),
+    ("settings.on", "вкл"),
+    ("settings.off", "выкл"),
+    ("settings.button.language", "🌐 Язык"),
+    ("settings.button.pairs", "💱 Пары"),
+    ("settings.button.quiet", "🌙 Тихие часы"),
+    ("settings.button.delivery", "📨 Доставка"),
+    ("settings.button.summary", "Итоги дня"),
+    ("settings.button.report", "Недельный отчёт"),
+    ("settings.button.back", "⬅️ Назад"),
+    ("settings.choose.language", "Выберите язык бота:"),
+    (
+        "settings.choose.pairs",
+        "Нажмите на пару, чтобы включить или выключить её в рассылке:",
+    ),
+    ("settings.choose.quiet", "В эти часы рассылка не приходит:"),
+    (
+        "settings.choose.delivery",
+        "edit - обновлять одно сообщение, new - присылать новое сообщение, replace - присылать новое и удалять предыдущее:",
+    ),
+    ("settings.last_pair", "В рассылке должна остаться хотя бы одна пара"),
+    ("settings.delivery_changed", "Способ доставки изменён: {mode}"),
+    ("settings.unknown_delivery", "❌ Неизвестный способ доставки: {mode}"),
+    ("settings.quiet_changed", "Тихие часы: {quiet}"),
+    ("settings.summary_changed", "Итоги дня: {value}"),
+    ("settings.report_changed", "Недельный отчёт: {value}"),
+    ("settings.default_pair_changed", "Пара по умолчанию: {pair}"),
+    ("settings.timezone_changed", "Часовой пояс: {timezone}"),
+    (
+        "settings.unknown_timezone",
+        "❌ Неизвестный часовой пояс: {timezone}\nПример: /settings timezone +03:00",
+    ),
+    ("settings.default_amount_changed", "Сумма по умолчанию: {amount}"),
+    (
+        "settings.unknown_switch",
+        "❌ Неизвестное значение: {value}\nПример: /settings {setting} on или /settings {setting} off",
+    ),
+    (
+        "settings.unknown_quiet",
+        "❌ Неверные тихие часы: {quiet}\nПример: /settings quiet 22-8 или /settings quiet off",
+    ),
+    // Daily summary
+    ("summary.title", "📊 Итоги за сутки"),
+    (
+        "summary.strength",
+        "Индекс силы рубля: {index} (корзина: {basket}; 100 = сутки назад)",
+    ),
+    (
+        "summary.line",
+        "{pair}: откр. {open}, макс. {high}, мин. {low}, закр. {close} {change}",
+    ),
+    // Weekly report
+    (
+        "weekly_report.caption",
+        "📎 Курсы с {from} по {to} по часам: открытие, максимум, минимум и закрытие",
+    ),
+    // Feedback
+    ("feedback.menu", "Написать разработчикам"),
+    (
+        "feedback.usage",
+        "/feedback <текст> - Сообщить об ошибке или предложить идею",
+    ),
+    (
+        "feedback.help",
+        "Напишите сообщение после команды, например:\n/feedback Не приходит рассылка",
+    ),
+    ("feedback.sent", "✅ Спасибо! Сообщение передано разработчикам."),
+    (
+        "feedback.failed",
+        "❌ Не удалось отправить сообщение, попробуйте позже.",
+    ),
+    ("feedback.reply", "💬 Ответ на ваше сообщение:\n{text}"),
+    (
+        "feedback.forwarded",
+        "📝 Отзыв от {sender}\nПользователь: {user}, чат: {chat}\n\n{text}\n\nОтветить: /reply {user} <текст>",
+    ),
+    ("feedback.reply_usage", "Использование: /reply <user_id> <текст>"),
+    ("feedback.reply_sent", "✅ Ответ отправлен в {chat}"),
+    ("feedback.reply_failed", "❌ Не удалось отправить ответ в {chat}: {error}"),
+    // Language
+    ("language.name", "русский"),
+    (
+        "language.current",
+        "Язык: {language}\n\nИзменить: /language ru|en",
+    ),
+    ("language.changed", "Язык изменён: {language}"),
+    ("language.unknown", "❌ Неизвестный язык: {language}"),
+    // Admin
+    (
+        "admin.usage",
+        "Использование:\n/admin export_subs - Выгрузить подписки в JSON\n/admin import_subs - Загрузить подписки из JSON (файл с этой подписью или ответом на файл)\n/admin dead_letters [clear] - Чаты, которым не удалось доставить рассылку\n/admin broadcast_now - Отправить периодическую рассылку немедленно\n/admin scheduler pause|resume - Приостановить или возобновить рассылку по расписанию\n/admin module [enable|disable <модуль>] - Включить или выключить модуль\n/admin set_newline_cookie <cookie> - Заменить cookie NewLine без перезапуска\n/broadcast [--pair <пара>] [--active-within <период>] [--lang <язык>] <текст> - Отправить сообщение подписчикам\n/stats - Статистика бота\n/users - Недавно активные чаты\n/audit <chat_id> - Последние команды чата",
+    ),
+    (
+        "admin.broadcast_usage",
+        "Использование: /broadcast [--pair <пара>] [--active-within <период>] [--lang ru|en] <текст>\nБез фильтров сообщение получат все подписчики, например:\n/broadcast --pair USD/RUB --active-within 7d Плановые работы у провайдера USD/RUB",
+    ),
+    ("admin.filter_missing", "❌ Не указано значение --{filter}"),
+    ("admin.filter_unknown", "❌ Неизвестный фильтр: --{filter}"),
+    ("admin.no_audience", "Нет подписчиков, подходящих под фильтры."),
+    ("admin.broadcast_done", "✅ Доставлено: {delivered}, ошибок: {failed}"),
+    ("admin.uptime", "{days}д {hours}ч {minutes}м"),
+    ("admin.stats", "📊 Статистика\n{lines}"),
+    ("admin.stats_uptime", "Аптайм: {uptime}"),
+    ("admin.stats_subscribers", "Подписчиков: {count}"),
+    ("admin.stats_periodic", "Отправлено периодических сообщений: {count}"),
+    ("admin.stats_active", "Активных чатов: {count}"),
+    ("admin.stats_dead_letters", "Недоставленных сообщений: {count}"),
+    ("admin.stats_outbound", "Исходящих сообщений: {sent}, ошибок: {failed}"),
+    ("admin.stats_providers", "Провайдеры:"),
+    (
+        "admin.stats_provider",
+        "{provider} — запросов: {requests}, ошибок: {errors} ({rate}%)",
+    ),
+    ("admin.stats_schema_drift", ", ⚠️ ответов в изменённом формате: {count}"),
+    ("admin.users", "Недавно активные чаты:\n{chats}"),
+    ("admin.users_none", "Активных чатов пока нет."),
+    ("admin.users_line", "{chat}{title} — {time}, команд: {commands}"),
+    ("admin.audit", "Журнал команд чата {chat}:\n{entries}"),
+    ("admin.audit_usage", "Использование: /audit <chat_id>"),
+    ("admin.audit_none", "Команд чата {chat} в журнале нет."),
+    ("admin.audit_earlier", "…и ещё {count} раньше"),
+    (
+        "admin.audit_line",
+        "{time} {command} ({module}) — {latency} мс{user}{error}",
+    ),
+    ("admin.audit_user", ", пользователь {user}"),
+    ("admin.cookie_usage", "Использование: /admin set_newline_cookie <cookie>"),
+    ("admin.cookie_no_provider", "❌ Провайдер NewLine не подключён"),
+    ("admin.cookie_checked", "✅ Cookie NewLine заменена, провайдер отвечает"),
+    (
+        "admin.cookie_check_failed",
+        "⚠️ Cookie NewLine заменена, но проверка не прошла: {error}",
+    ),
+    ("admin.cookie_replaced", "✅ Cookie NewLine заменена"),
+    ("admin.export_caption", "Подписок: {count}"),
+    (
+        "admin.import_help",
+        "Прикрепите JSON-файл с подписью /admin import_subs или ответьте этой командой на файл.",
+    ),
+    ("admin.import_done", "Импортировано подписок: {count}"),
+    ("admin.import_failed", "❌ Ошибка импорта: {error}"),
+    ("admin.import_invalid", "❌ Некорректный файл: {error}"),
+    ("admin.scheduler_paused", "⏸ Рассылка по расписанию приостановлена."),
+    ("admin.scheduler_already_paused", "Рассылка по расписанию уже приостановлена."),
+    ("admin.scheduler_already_running", "Рассылка по расписанию уже работает."),
+    ("admin.scheduler_resumed", "▶️ Рассылка по расписанию возобновлена."),
+    (
+        "admin.scheduler_running_state",
+        "Рассылка по расписанию работает.\nИспользование: /admin scheduler pause|resume",
+    ),
+    (
+        "admin.scheduler_paused_state",
+        "Рассылка по расписанию приостановлена.\nИспользование: /admin scheduler pause|resume",
+    ),
+    (
+        "admin.modules",
+        "Модули:\n{modules}\n\nИспользование: /admin module enable|disable <модуль>",
+    ),
+    ("admin.module_locked", "❌ Модуль admin нельзя выключить"),
+    ("admin.module_unknown", "❌ Неизвестный модуль: {module}"),
+    ("admin.module_enabled", "✅ Модуль {module} включён"),
+    ("admin.module_disabled", "✅ Модуль {module} выключен"),
+    ("admin.dead_letters", "Недоставленные сообщения:\n{letters}"),
+    ("admin.dead_letters_none", "Недоставленных сообщений нет."),
+    (
+        "admin.dead_letters_line",
+        "{chat} — попыток: {attempts}, {error} ({time})",
+    ),
+    ("admin.dead_letters_more", "…и ещё {count}"),
+    ("admin.dead_letters_cleared", "Список недоставленных сообщений очищен."),
+    ("admin.broadcast_now", "Отправляю рассылку..."),
+    ("admin.broadcast_now_done", "✅ Рассылка завершена, подписчиков: {count}"),
+    // Credential reminders
+    (
+        "credentials.expires",
+        "🔑 {credential} истекает {expires} (дней осталось: {days}), обновите его",
+    ),
+    (
+        "credentials.expired",
+        "🔑 {credential} истёк {expires}, запросы провайдера будут отклоняться",
+    ),
+    // Error reports
+    ("reports.error", "⚠️ Ошибка: {source}\n{message}"),
+    (
+        "reports.suppressed",
+        "\n(ещё {count} похожих за последнее время пропущено)",
+    ),
+    // Watchdog
+    ("watchdog.stalled", "⚠️ Задача {job} зависла и перезапущена"),
+    ("watchdog.died", "⚠️ Задача {job} упала и перезапущена: {error}"),
+    // Rate limits
+    (
+        "rate_limit.warning",
+        "📈 {provider}: {requests} запросов за час из {limit} допустимых ({percent}%). Увеличьте PRICE_POLL_INTERVAL_MINUTES{interval}, пока провайдер не заблокировал бота",
+    ),
+    ("rate_limit.interval", " (сейчас {minutes} мин)"),
+];
+
+const EN: &[(&str, &str)] = &[
+    // Registry and middlewares
+    ("admin_only", "This command is only available to administrators."),
+    ("unknown_command", "Unknown command. Use /help"),
+    (
+        "unknown_command_suggestion",
+        "Unknown command. Did you mean {command}? Use /help",
+    ),
+    (
+        "rate_limited",
+        "⏳ Too many commands. Try again in {seconds} s.",
+    ),
+    ("unknown_pair", "❌ Unknown pair: {pair}"),
+    ("args.missing", "❌ Missing arguments"),
+    ("args.unknown_currency", "❌ Unknown currency: {value}"),
+    ("args.bad_amount", "❌ Invalid number: {value}"),
+    (
+        "args.bad_period",
+        "❌ Invalid period: {value}, e.g. 30m, 2h or 7d",
+    ),
+    ("args.unexpected", "❌ Unexpected argument: {value}"),
+    ("args.not_a_number", "❌ The argument has to be a number"),
+    ("args.invalid", "❌ Invalid arguments"),
+    // Help and start
+    ("help.title", "Available commands:"),
+    ("help.menu", "Show help"),
+    ("help.usage", "/help - Show this help"),
+    ("start.menu", "Start using the bot"),
+    (
+        "start.greeting",
+        "Welcome to Currency Bot!\nUse /echo <text> to get an echo reply.",
+    ),
+    ("start.subscribed", "✅ You are subscribed to the rates of: {pairs}"),
+    (
+        "start.unknown_link",
+        "The link you came from wasn't recognized. Bot commands: /help",
+    ),
+    ("echo.menu", "Send an echo reply"),
+    ("echo.usage", "/echo <text> - Send an echo reply"),
+    ("echo.help", "Usage: /echo <text>"),
+    // Prices
+    ("price.section", "Exchange rates:"),
+    ("price.menu", "Currency pair rate"),
+    (
+        "price.usage",
+        "/price [pair ...] - Currency pair rates, e.g. /price USD/RUB USDTe/RUB, without one - the default pair",
+    ),
+    (
+        "price.choose",
+        "Choose a pair. To have /price show it right away, set it: /settings default_pair USD/RUB",
+    ),
+    ("price.newline_usage", "/newLine - USDTe/RUB rate from NewLine"),
+    (
+        "price.quote",
+        "💰 {pair} Price\n\nCurrency Pair: {source}\nPrice: {price}{trend}",
+    ),
+    ("price.sparkline", "📊 Last {period}: {sparkline}"),
+    ("price.range_rolling", "📏 30d range: {min}–{max}"),
+    ("price.range_all_time", "📏 All-time range: {min}–{max}"),
+    ("price.action.refresh", "🔄 Refresh"),
+    ("price.action.alert", "🔔 Alert at this level"),
+    ("price.action.chart", "📈 Chart"),
+    (
+        "price.invalid_pair",
+        "❌ Invalid currency pair format. Available pairs: {pairs}",
+    ),
+    ("price.error.network", "🌐 Network error: {error}"),
+    ("price.error.api", "🔌 API error: {error}"),
+    ("price.error.parsing", "📜 Parsing error: {error}"),
+    ("price.error.provider", "❌ Provider error: {error}"),
+    ("price.partial", "⚠️ Failed to fetch: {pairs}"),
+    ("inline.rate_at", "Rate as of {time}"),
+    (
+        "convert.usage",
+        "Conversion: just type e.g. \"100 usd в рубли\" or \"сколько стоит 50 usdt\"",
+    ),
+    (
+        "convert.result",
+        "💱 {amount} {from} = {result} {to}\n{pair} rate: {price}",
+    ),
+    // Charts
+    ("chart.menu", "Rate chart for a period"),
+    (
+        "chart.usage",
+        "/chart <pair> [period] - Rate chart, e.g. /chart USD/RUB 7d",
+    ),
+    (
+        "chart.help",
+        "Usage: /chart <pair> [period]\nPeriod: 30m, 12h, 1d, 7d (up to {max}d, 1d by default)\nAvailable pairs: {pairs}\nExample: /chart USD/RUB 7d",
+    ),
+    (
+        "chart.not_enough_data",
+        "Not enough data to chart {pair} over {period}.",
+    ),
+    ("chart.caption", "{pair} over {period}: {price}"),
+    ("chart.render_failed", "❌ Failed to draw the chart."),
+    ("chart.min", "min"),
+    ("chart.max", "max"),
+    ("chart.now", "now"),
+    // Technical analysis
+    ("ta.menu", "Moving averages and volatility"),
+    (
+        "ta.usage",
+        "/ta <pair> - Moving averages (SMA, EMA) and volatility, e.g. /ta USD/RUB",
+    ),
+    (
+        "ta.help",
+        "Usage: /ta <pair>\nAvailable pairs: {pairs}\nExample: /ta USD/RUB",
+    ),
+    ("ta.title", "📈 {pair}: moving averages and volatility"),
+    (
+        "ta.line",
+        "{window}: SMA {sma}, EMA {ema}, volatility {volatility} ({samples} samples)",
+    ),
+    ("ta.no_volatility", "n/a"),
+    ("ta.no_data", "No prices of {pair} recorded yet."),
+    // Chat stats
+    ("me.menu", "Chat stats"),
+    (
+        "me.usage",
+        "/me - Chat stats: commands, messages from the bot, subscription and alerts",
+    ),
+    (
+        "me.title",
+        "👤 Chat stats\n\nCommands in the last {days} days: {commands}\nMessages from the bot: {messages}\nSubscription: {subscription}\nAlerts: {alerts} active, {triggered} triggered",
+    ),
+    ("me.not_subscribed", "none, see /subscribe"),
+    ("me.subscribed", "active"),
+    ("me.subscribed_since", "since {date} ({days} days)"),
+    ("me.favorite", "Most used: {command} ({count})"),
+    ("me.quiet", "🌙 No periodic messages during the quiet hours {quiet}"),
+    (
+        "me.delivery_failed",
+        "⚠️ The last periodic message wasn't delivered ({time}): {error}",
+    ),
+    // Week heatmap
+    ("week.menu", "Daily changes over the week"),
+    (
+        "week.usage",
+        "/week <pair> - Daily changes over 7 days, e.g. /week USD/RUB",
+    ),
+    (
+        "week.help",
+        "Usage: /week <pair>\nAvailable pairs: {pairs}\nExample: /week USD/RUB",
+    ),
+    ("week.title", "🗓 {pair} over the last 7 days"),
+    // Conversion routes
+    ("route.menu", "Best exchange route after fees"),
+    (
+        "route.usage",
+        "/route <amount> <from> <to> - Best exchange route, e.g. /route 100000 RUB USDT",
+    ),
+    (
+        "route.help",
+        "Usage: /route <amount> <from> <to>\nCurrencies: RUB, USD, USDT, USDC, EUR\nExample: /route 100000 RUB USDT",
+    ),
+    ("route.result", "🔀 {amount} {from} → {output} {to}"),
+    ("route.step", "{from} → {to} via {pair}: {rate} (fee {fee})"),
+    ("route.not_found", "No quotes to exchange {from} for {to}."),
+    // Alerts
+    ("alerts.section", "Alerts:"),
+    ("alerts.menu.alert", "Create a price alert"),
+    ("alerts.menu.alerts", "List alerts"),
+    ("alerts.usage.threshold", "/alert <pair> > <price> - Price alert"),
+    (
+        "alerts.usage.moves",
+        "/alert <pair> moves <percent>% in <period> - Sudden move alert",
+    ),
+    ("alerts.usage.list", "/alerts - List alerts"),
+    (
+        "alerts.help",
+        "Usage:\n/alert <pair> > <price> [repeat] - Notify when the rate rises above the price\n/alert <pair> < <price> [repeat] - Notify when the rate falls below the price\n/alert <pair> moves <N>% in <period> [repeat] - Notify about a sudden move, e.g. moves 2% in 1h\n/alert delete N - Delete alert number N\n/alerts - List alerts\n\nWithout repeat an alert fires once and is removed. A repeating one takes cooldown <period> (at most once per period) and rearm <N>% (fires again only after the rate moved N% back from the threshold).\nExample: /alert USD/RUB > 100 repeat cooldown 1h rearm 0.5%",
+    ),
+    ("alerts.moves", "±{percent}% over {period}"),
+    ("alerts.repeating", " (repeating)"),
+    ("alerts.repeating_with", " (repeating, {options})"),
+    ("alerts.cooldown", "at most every {period}"),
+    ("alerts.rearm", "re-arms {percent}% back"),
+    (
+        "alerts.bad_rearm",
+        "❌ Specify the move back in percent below 100, e.g. rearm 0.5%",
+    ),
+    (
+        "alerts.options_need_repeat",
+        "❌ cooldown and rearm only work together with repeat",
+    ),
+    ("alerts.limit", "❌ At most {max} alerts per chat"),
+    ("alerts.fired", "🔔 {pair} {condition}: now {price}"),
+    ("alerts.fired_removed", "The alert fired and was removed."),
+    (
+        "alerts.bad_percent",
+        "❌ Specify the change in percent, e.g. moves 2% in 1h",
+    ),
+    ("alerts.bad_period", "❌ Specify a period, e.g. 30m, 1h or 1d"),
+    ("alerts.bad_price", "❌ Specify a price, e.g. /alert USD/RUB > 100"),
+    ("alerts.none", "No alerts.\nCreate one: /alert USD/RUB > 100"),
+    ("alerts.list", "Your alerts:\n{alerts}\n\nDelete: /alert delete N"),
+    ("alerts.deleted", "Alert deleted: {rule}"),
+    ("alerts.not_found", "❌ No alert number {number}"),
+    ("alerts.created", "✅ Alert #{number} created: {rule}"),
+    // Subscriptions
+    ("subs.section", "Periodic messages:"),
+    ("subs.menu.subscribe", "Subscribe to periodic messages"),
+    ("subs.menu.unsubscribe", "Unsubscribe from periodic messages"),
+    ("subs.menu.status", "Subscription status"),
+    ("subs.menu.template", "Periodic message template"),
+    ("subs.menu.pairs", "Currency pairs in periodic messages"),
+    ("subs.menu.language", "Bot language"),
+    ("subs.menu.calendar", "Message schedule in your calendar"),
+    (
+        "subs.usage.subscribe",
+        "/subscribe - Subscribe to periodic messages",
+    ),
+    (
+        "subs.usage.unsubscribe",
+        "/unsubscribe - Unsubscribe from periodic messages",
+    ),
+    ("subs.usage.status", "/status - Check the subscription status"),
+    (
+        "subs.usage.template",
+        "/template <text> - Set the periodic message template",
+    ),
+    (
+        "subs.usage.pairs",
+        "/pairs <pairs> - Choose the pairs of periodic messages",
+    ),
+    ("subs.usage.language", "/language ru|en - Bot language"),
+    (
+        "subs.usage.calendar",
+        "/calendar [reset] - Calendar link of the bot's messages",
+    ),
+    (
+        "subs.calendar",
+        "📅 When the bot writes to this chat, a link for your calendar app:\n{url}\n\n/calendar reset - a new link instead of this one",
+    ),
+    (
+        "subs.calendar_reset",
+        "📅 New calendar link, the old one no longer works:\n{url}",
+    ),
+    (
+        "subs.calendar_unavailable",
+        "The message calendar is not enabled on this bot.",
+    ),
+    ("calendar.name", "Currency Bot: messages"),
+    ("calendar.periodic", "Periodic rates message"),
+    ("calendar.summary", "Daily summary"),
+    ("calendar.weekly_report", "Weekly report"),
+    ("subs.header", "Periodic message #{number}:\n{text}"),
+    (
+        "subs.pairs_help",
+        "Pairs in periodic messages: {pairs}\n\nChange: /pairs USD/RUB USDTe/RUB\nAvailable pairs: {available}",
+    ),
+    (
+        "subs.template_help",
+        "Current message template:\n{template}\n\nChange: /template <text>\nReset: /template reset\nPlaceholders: {placeholders}",
+    ),
+    ("subs.next_in", "Next message in {minutes} min {seconds} s"),
+    ("subs.next_soon", "The message will be sent soon..."),
+    ("subs.next_unknown", "The next send time is unknown"),
+    (
+        "subs.status_subscribed",
+        "You are subscribed to periodic messages.\n{next}\nSubscribers: {count}",
+    ),
+    (
+        "subs.status_not_subscribed",
+        "You are not subscribed to periodic messages.",
+    ),
+    (
+        "subs.group_admins_only",
+        "Only chat administrators can manage the group's subscription.",
+    ),
+    ("subs.subscribed", "You have subscribed to periodic messages!"),
+    (
+        "subs.already_subscribed",
+        "You are already subscribed to periodic messages.",
+    ),
+    (
+        "subs.topic_changed",
+        "Periodic messages will now be delivered to this topic.",
+    ),
+    ("subs.unsubscribed", "You have unsubscribed from periodic messages."),
+    ("subs.not_subscribed", "You were not subscribed to periodic messages."),
+    ("subs.template_reset", "The template was reset to the default."),
+    ("subs.template_updated", "The message template was updated."),
+    ("subs.pairs_updated", "The pair list was updated."),
+    // Settings
+    ("settings.menu", "Chat settings"),
+    (
+        "settings.usage",
+        "/settings - Chat settings: language, pairs, quiet hours, delivery\n/settings delivery edit|new|replace - Update one message, send a new one or replace the old one\n/settings quiet 22-8|off - No periodic messages at night\n/settings summary on|off - Daily summary: open, high, low and close over the day\n/settings report on|off - Weekly report: CSV with the hourly rates of the week\n/settings default_pair <pair>|off - Pair shown by /price without arguments\n/settings timezone +03:00|off - Time zone of the quote times and quiet hours\n/settings default_amount <amount>|off - Amount converted when a question names none",
+    ),
+    (
+        "settings.title",
+        "⚙️ Chat settings\n\nLanguage: {language}\nPairs: {pairs}\nDefault pair: {default_pair}\nTime zone: {timezone}\nDefault amount: {amount}\nInterval: {interval}\nQuiet hours: {quiet}\nDelivery: {delivery}\nDaily summary: {summary}\nWeekly report: {report}",
+    ),
+    ("settings.interval_minutes", "every {minutes} min"),
+    ("settings.interval_cron", "on schedule {schedule}"),
+    ("settings.quiet_off", "off"),
+    ("settings.on", "on"),
+    ("settings.off", "off"),
+    ("settings.button.language", "🌐 Language"),
+    ("settings.button.pairs", "💱 Pairs"),
+    ("settings.button.quiet", "🌙 Quiet hours"),
+    ("settings.button.delivery", "📨 Delivery"),
+    ("settings.button.summary", "Daily summary"),
+    ("settings.button.report", "Weekly report"),
+    ("settings.button.back", "⬅️ Back"),
+    ("settings.choose.language", "Choose the bot language:"),
+    (
+        "settings.choose.pairs",
+        "Tap a pair to include or exclude it from the periodic message:",
+    ),
+    ("settings.choose.quiet", "No periodic messages during these hours:"),
+    (
+        "settings.choose.delivery",
+        "edit - update one message, new - send a new message every time, replace - send a new one and delete the previous one:",
+    ),
+    ("settings.last_pair", "At least one pair has to stay in the periodic message"),
+    ("settings.delivery_changed", "Delivery mode changed: {mode}"),
+    ("settings.unknown_delivery", "❌ Unknown delivery mode: {mode}"),
+    ("settings.quiet_changed", "Quiet hours: {quiet}"),
+    ("settings.summary_changed", "Daily summary: {value}"),
+    ("settings.report_changed", "Weekly report: {value}"),
+    ("settings.default_pair_changed", "Default pair: {pair}"),
+    ("settings.timezone_changed", "Time zone: {timezone}"),
+    (
+        "settings.unknown_timezone",
+        "❌ Unknown time zone: {timezone}\nExample: /settings timezone +03:00",
+    ),
+    ("settings.default_amount_changed", "Default amount: {amount}"),
+    (
+        "settings.unknown_switch",
+        "❌ Unknown value: {value}\nExample: /settings {setting} on or /settings {setting} off",
+    ),
+    (
+        "settings.unknown_quiet",
+        "❌ Invalid quiet hours: {quiet}\nExample: /settings quiet 22-8 or /settings quiet off",
+    ),
+    // Daily summary
+    ("summary.title", "📊 Daily summary"),
+    (
+        "summary.strength",
+        "RUB strength index: {index} (basket: {basket}; 100 = a day ago)",
+    ),
+    (
+        "summary.line",
+        "{pair}: open {open}, high {high}, low {low}, close {close} {change}",
+    ),
+    // Weekly report
+    (
+        "weekly_report.caption",
+        "📎 Hourly rates from {from} to {to}: open, high, low and close",
+    ),
+    // Feedback
+    ("feedback.menu", "Contact the developers"),
+    (
+        "feedback.usage",
+        "/feedback <text> - Report a bug or suggest an idea",
+    ),
+    (
+        "feedback.help",
+        "Write your message after the command, e.g.:\n/feedback Periodic messages stopped",
+    ),
+    ("feedback.sent", "✅ Thank you! Your message was passed to the developers."),
+    (
+        "feedback.failed",
+        "❌ Failed to send the message, please try again later.",
+    ),
+    ("feedback.reply", "💬 Reply to your message:\n{text}"),
+    (
+        "feedback.forwarded",
+        "📝 Feedback from {sender}\nUser: {user}, chat: {chat}\n\n{text}\n\nAnswer: /reply {user} <text>",
+    ),
+    ("feedback.reply_usage", "Usage: /reply <user_id> <text>"),
+    ("feedback.reply_sent", "✅ Answer sent to {chat}"),
+    ("feedback.reply_failed", "❌ Failed to send the answer to {chat}: {error}"),
+    // Language
+    ("language.name", "English"),
+    (
+        "language.current",
+        "Language: {language}\n\nChange: /language ru|en",
+    ),
+    ("language.changed", "Language changed: {language}"),
+    ("language.unknown", "❌ Unknown language: {language}"),
+    // Admin
+    (
+        "admin.usage",
+        "Usage:\n/admin export_subs - Export the subscriptions as JSON\n/admin import_subs - Import the subscriptions from JSON (a file with this caption or a reply to one)\n/admin dead_letters [clear] - Chats the periodic message couldn't be delivered to\n/admin broadcast_now - Send the periodic message right away\n/admin scheduler pause|resume - Pause or resume the scheduled periodic message\n/admin module [enable|disable <module>] - Switch a module on or off\n/admin set_newline_cookie <cookie> - Replace the NewLine cookie without a restart\n/broadcast [--pair <pair>] [--active-within <period>] [--lang <language>] <text> - Send a message to the subscribers\n/stats - Bot statistics\n/users - Recently active chats\n/audit <chat_id> - Latest commands of a chat",
+    ),
+    (
+        "admin.broadcast_usage",
+        "Usage: /broadcast [--pair <pair>] [--active-within <period>] [--lang ru|en] <text>\nWithout filters every subscriber gets the message, e.g.:\n/broadcast --pair USD/RUB --active-within 7d Scheduled maintenance of the USD/RUB provider",
+    ),
+    ("admin.filter_missing", "❌ No value given for --{filter}"),
+    ("admin.filter_unknown", "❌ Unknown filter: --{filter}"),
+    ("admin.no_audience", "No subscribers match the filters."),
+    ("admin.broadcast_done", "✅ Delivered: {delivered}, failed: {failed}"),
+    ("admin.uptime", "{days}d {hours}h {minutes}m"),
+    ("admin.stats", "📊 Statistics\n{lines}"),
+    ("admin.stats_uptime", "Uptime: {uptime}"),
+    ("admin.stats_subscribers", "Subscribers: {count}"),
+    ("admin.stats_periodic", "Periodic messages sent: {count}"),
+    ("admin.stats_active", "Active chats: {count}"),
+    ("admin.stats_dead_letters", "Undelivered messages: {count}"),
+    ("admin.stats_outbound", "Outgoing messages: {sent}, failed: {failed}"),
+    ("admin.stats_providers", "Providers:"),
+    (
+        "admin.stats_provider",
+        "{provider} — requests: {requests}, errors: {errors} ({rate}%)",
+    ),
+    ("admin.stats_schema_drift", ", ⚠️ responses in a changed format: {count}"),
+    ("admin.users", "Recently active chats:\n{chats}"),
+    ("admin.users_none", "No active chats yet."),
+    ("admin.users_line", "{chat}{title} — {time}, commands: {commands}"),
+    ("admin.audit", "Command log of chat {chat}:\n{entries}"),
+    ("admin.audit_usage", "Usage: /audit <chat_id>"),
+    ("admin.audit_none", "No commands of chat {chat} in the log."),
+    ("admin.audit_earlier", "…and {count} earlier"),
+    (
+        "admin.audit_line",
+        "{time} {command} ({module}) — {latency} ms{user}{error}",
+    ),
+    ("admin.audit_user", ", user {user}"),
+    ("admin.cookie_usage", "Usage: /admin set_newline_cookie <cookie>"),
+    ("admin.cookie_no_provider", "❌ The NewLine provider is not enabled"),
+    ("admin.cookie_checked", "✅ NewLine cookie replaced, the provider answers"),
+    (
+        "admin.cookie_check_failed",
+        "⚠️ NewLine cookie replaced, but the check failed: {error}",
+    ),
+    ("admin.cookie_replaced", "✅ NewLine cookie replaced"),
+    ("admin.export_caption", "Subscriptions: {count}"),
+    (
+        "admin.import_help",
+        "Attach a JSON file with the caption /admin import_subs or reply to a file with this command.",
+    ),
+    ("admin.import_done", "Subscriptions imported: {count}"),
+    ("admin.import_failed", "❌ Import failed: {error}"),
+    ("admin.import_invalid", "❌ Invalid file: {error}"),
+    ("admin.scheduler_paused", "⏸ Scheduled periodic message paused."),
+    ("admin.scheduler_already_paused", "The scheduled periodic message is already paused."),
+    ("admin.scheduler_already_running", "The scheduled periodic message is already running."),
+    ("admin.scheduler_resumed", "▶️ Scheduled periodic message resumed."),
+    (
+        "admin.scheduler_running_state",
+        "The scheduled periodic message is running.\nUsage: /admin scheduler pause|resume",
+    ),
+    (
+        "admin.scheduler_paused_state",
+        "The scheduled periodic message is paused.\nUsage: /admin scheduler pause|resume",
+    ),
+    (
+        "admin.modules",
+        "Modules:\n{modules}\n\nUsage: /admin module enable|disable <module>",
+    ),
+    ("admin.module_locked", "❌ The admin module can't be switched off"),
+    ("admin.module_unknown", "❌ Unknown module: {module}"),
+    ("admin.module_enabled", "✅ Module {module} switched on"),
+    ("admin.module_disabled", "✅ Module {module} switched off"),
+    ("admin.dead_letters", "Undelivered messages:\n{letters}"),
+    ("admin.dead_letters_none", "No undelivered messages."),
+    (
+        "admin.dead_letters_line",
+        "{chat} — attempts: {attempts}, {error} ({time})",
+    ),
+    ("admin.dead_letters_more", "…and {count} more"),
+    ("admin.dead_letters_cleared", "The list of undelivered messages was cleared."),
+    ("admin.broadcast_now", "Sending the periodic message..."),
+    ("admin.broadcast_now_done", "✅ Periodic message sent, subscribers: {count}"),
+    // Credential reminders
+    (
+        "credentials.expires",
+        "🔑 {credential} expires on {expires} ({days} days left), renew it",
+    ),
+    (
+        "credentials.expired",
+        "🔑 {credential} expired on {expires}, the provider will reject its requests",
+    ),
+    // Error reports
+    ("reports.error", "⚠️ Error: {source}\n{message}"),
+    ("reports.suppressed", "\n({count} similar ones skipped recently)"),
+    // Watchdog
+    ("watchdog.stalled", "⚠️ Job {job} hung and was restarted"),
+    ("watchdog.died", "⚠️ Job {job} died and was restarted: {error}"),
+    // Rate limits
+    (
+        "rate_limit.warning",
+        "📈 {provider}: {requests} requests in the last hour of {limit} allowed ({percent}%). Raise PRICE_POLL_INTERVAL_MINUTES{interval} before the provider blocks the bot",
+    ),
+    ("rate_limit.interval", " (now {minutes} min)"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_bundles_have_the_same_keys() {
+        let keys = |bundle: &[(&'static str, &str)]| -> Vec<&'static str> {
+            bundle.iter().map(|(key, _)| *key).collect()
+        };
+        let ru = keys(RU);
+        assert_eq!(ru.iter().collect::<HashSet<_>>().len(), ru.len());
+        assert_eq!(ru, keys(EN));
+    }
+
+    #[test]
+    fn test_tr_args() {
+        assert_eq!(
+            tr_args(Language::En, "unknown_pair", &[("pair", &"EUR/RUB")]),
+            "❌ Unknown pair: EUR/RUB"
+        );
+        assert_eq!(
+            tr_args(
+                Language::Ru,
+                "subs.next_in",
+                &[("minutes", &5), ("seconds", &7)]
+            ),
+            "Следующее сообщение через 5 мин 7 сек"
+        );
+        assert_eq!(tr(Language::En, "missing.key"), "missing.key");
+    }
+
+    #[test]
+    fn test_languages_store() {
+        let languages = Languages::new();
+        assert_eq!(languages.get(ChatId(1)), Language::Ru);
+        languages.set(ChatId(1), Language::En);
+        assert_eq!(languages.get(ChatId(1)), Language::En);
+        assert_eq!(languages.export().len(), 1);
+        languages.set(ChatId(1), Language::Ru);
+        assert!(languages.export().is_empty());
+
+        assert_eq!(Language::from_code("EN"), Some(Language::En));
+        assert_eq!(Language::from_code("de"), None);
+    }
+}
@@ -0,0 +1,34 @@
+//! Telegram bot posting currency rates, usable as a library to embed the bot
+//! into a bigger service and add modules without forking:
+//!
+//! ```no_run
+//! # async fn example(my_module: Box<dyn currency_bot::Module>) {
+//! let config = currency_bot::Config::load(|name| std::env::var(name).ok()).unwrap();
+//! if let Err(e) = currency_bot::run_bot_with_modules(config, vec![my_module]).await {
+//!     eprintln!("{}", e);
+//! }
+//! # }
+//! ```
+
+#[cfg(feature = "http-api")]
+mod api;
+mod bot;
+pub mod bot_modules;
+#[cfg(feature = "http-api")]
+mod calendar;
+pub mod config;
+pub mod domain;
+pub mod i18n;
+pub mod price_service;
+pub mod reporting;
+pub mod snapshot;
+pub mod startup;
+#[cfg(feature = "webhooks")]
+pub mod webhooks;
+
+pub use bot::{price_service, run_bot, run_bot_with_modules};
+pub use bot_modules::subscribers::{SubscriberManager, SubscriberModule};
+pub use bot_modules::{Module, ModuleRegistry, TelegramBot};
+pub use config::Config;
+pub use price_service::provider::PriceProvider;
+pub use price_service::PriceService;
@@ -1,94 +1,203 @@
-use std::sync::Arc;
-use teloxide::prelude::*;
-
-mod bot_modules;
-mod domain;
-mod price_service;
-use bot_modules::scheduler::Scheduler;
-use bot_modules::{
-    EchoModule, HelpModule, ModuleRegistry, NewLineModule, PriceModule, StartModule,
-    SubscriberManager, SubscriberModule,
-};
-use price_service::{
-    providers::{NewLineConfig, NewLineProvider},
-    PriceService,
-};
-
-#[tokio::main]
-async fn main() {
-    // Try to load .env file, but don't fail if it's not present
-    let _ = dotenvy::dotenv();
-
-    pretty_env_logger::init();
-    log::info!("Starting currency bot...");
-
-    let subscription_interval_minutes = std::env::var("SUBSCRIPTION_INTERVAL_MINUTES")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(10);
+use chrono::Local;
+use clap::{Parser, Subcommand};
+use currency_bot::config::{Config, LogFormat};
+use currency_bot::domain::{get_all_currency_pairs, CurrencyPair};
+use currency_bot::price_service::backfill::{merge_history, parse_history, HistoryFormat};
+use currency_bot::price_service::history::PriceHistory;
+use currency_bot::snapshot::RuntimeSnapshot;
+use currency_bot::startup;
+use std::path::PathBuf;
+use std::str::FromStr;
+use tracing_subscriber::EnvFilter;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Telegram bot posting currency rates")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-    let periodic_message_text = std::env::var("PERIODIC_MESSAGE_TEXT")
-        .ok()
-        .unwrap_or_else(|| "Периодическое сообщение от бота".to_string());
+#[derive(Debug, PartialEq, Subcommand)]
+enum Command {
+    /// Run the bot, the default without a command
+    Run,
+    /// Validate the configuration and run the startup checks, then exit
+    CheckConfig {
+        /// Skip the startup checks (Telegram tokens, provider credentials, state files)
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Fetch the current price of a pair from the providers and print it as JSON, without Telegram
+    Fetch {
+        /// Currency pair, e.g. USD/RUB
+        pair: String,
+    },
+    /// Import historical rates into the price history of the runtime snapshot
+    /// (`SNAPSHOT_FILE`); run it while the bot is stopped, it overwrites the
+    /// snapshot on shutdown
+    ImportHistory {
+        /// CSV (`pair,time,price`) or CBR XML files
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// csv or cbr, by default cbr for .xml files and csv otherwise
+        #[arg(long, value_parser = history_format)]
+        format: Option<HistoryFormat>,
+    },
+}
 
-    let subscriber_manager = Arc::new(SubscriberManager::new(periodic_message_text.clone()));
+fn history_format(format: &str) -> Result<HistoryFormat, String> {
+    HistoryFormat::parse(format)
+        .ok_or_else(|| format!("unknown format {}, expected csv or cbr", format))
+}
 
-    // Initialize price service
-    let mut price_service = PriceService::new();
+/// Log to stderr filtered by `RUST_LOG` (errors only by default), as JSON
+/// lines with `logging.format = "json"`. Records of the `log` crate (teloxide,
+/// reqwest) are forwarded as well.
+fn init_tracing(format: LogFormat) {
+    let builder = tracing_subscriber::fmt().with_env_filter(EnvFilter::from_default_env());
+    match format {
+        LogFormat::Json => builder.json().init(),
+        LogFormat::Text => builder.init(),
+    }
+}
 
-    // Configure NewLine provider from environment
-    let newline_base_url = std::env::var("NEWLINE_API_BASE_URL")
-        .unwrap_or_else(|_| "https://newline.online".to_string());
-    let newline_cookie = std::env::var("NEWLINE_COOKIE")
-        .unwrap_or_else(|_| panic!("NEWLINE_COOKIE environment variable is required but not set"));
-    let newline_preferred_city =
-        std::env::var("NEWLINE_PREFERRED_CITY").unwrap_or_else(|_| "spb".to_string());
+/// Print `message` to stderr and exit with a failure status
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1)
+}
 
-    let newline_config = NewLineConfig {
-        base_url: newline_base_url,
-        cookie: newline_cookie,
-        preferred_city: newline_preferred_city,
+async fn fetch(config: &Config, pair: &str) {
+    let Ok(pair) = CurrencyPair::from_str(pair) else {
+        let supported: Vec<String> = get_all_currency_pairs()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        fail(format!(
+            "Unknown currency pair {}, supported: {}",
+            pair,
+            supported.join(", ")
+        ));
     };
+    let price_service = currency_bot::price_service(config).unwrap_or_else(|e| fail(e));
+    match price_service.get_price(&pair).await {
+        Ok(price) => println!(
+            "{}",
+            serde_json::json!({ "pair": price.pair.to_string(), "price": price.price })
+        ),
+        Err(e) => fail(e),
+    }
+}
 
-    let newline_provider = Arc::new(NewLineProvider::new(newline_config));
-    price_service.add_provider(newline_provider);
-    let price_service = Arc::new(price_service);
-
-    let mut registry = ModuleRegistry::new();
-    registry.register(Box::new(StartModule::new()));
-    registry.register(Box::new(EchoModule::new()));
-    registry.register(Box::new(PriceModule::new(Arc::clone(&price_service))));
-    registry.register(Box::new(NewLineModule::new(Arc::clone(&price_service))));
-    registry.register(Box::new(SubscriberModule::new(Arc::clone(
-        &subscriber_manager,
-    ))));
-    registry.register(Box::new(HelpModule::new()));
-    let registry = Arc::new(registry);
-
-    let bot = Bot::from_env();
-
-    let scheduler = Scheduler::new(
-        Arc::clone(&subscriber_manager),
-        subscription_interval_minutes,
+fn import_history(config: &Config, files: &[PathBuf], format: Option<HistoryFormat>) {
+    let Some(path) = &config.snapshot.file else {
+        fail("SNAPSHOT_FILE is not set, there is no history to import into");
+    };
+    let mut snapshot = RuntimeSnapshot::load(path)
+        .unwrap_or_else(|e| fail(e))
+        .unwrap_or_default();
+
+    let mut imported = Vec::new();
+    for file in files {
+        let contents = std::fs::read(file)
+            .unwrap_or_else(|e| fail(format!("Failed to read {}: {}", file.display(), e)));
+        let format = format.unwrap_or_else(|| HistoryFormat::detect(file));
+        let samples = parse_history(format, &contents)
+            .unwrap_or_else(|e| fail(format!("{}: {}", file.display(), e)));
+        imported.extend(samples);
+    }
+    let before: usize = snapshot.prices.values().map(Vec::len).sum();
+    let merged = merge_history(std::mem::take(&mut snapshot.prices), imported);
+    let after: usize = merged.values().map(Vec::len).sum();
+
+    // Compact right away, years of an archive would stay raw until the bot's first compaction
+    let history = PriceHistory::new(config.history.retention());
+    history.import(merged);
+    history.import_candles(std::mem::take(&mut snapshot.candles));
+    history.compact(Local::now());
+    snapshot.prices = history.export();
+    snapshot.candles = history.export_candles();
+    if let Err(e) = snapshot.save(path) {
+        fail(format!("Failed to save {}: {}", path.display(), e));
+    }
+    println!(
+        "Imported {} samples into {}",
+        after.saturating_sub(before),
+        path.display()
     );
-    let scheduler_bot = bot.clone();
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
 
-    tokio::spawn(async move {
-        scheduler.start(scheduler_bot).await;
-    });
+    // Try to load .env file, but don't fail if it's not present
+    let _ = dotenvy::dotenv();
 
-    log::info!("Scheduler started in background");
+    // Settings come from config.toml, environment variables (and .env) override them
+    let config = Config::load(|name| std::env::var(name).ok()).unwrap_or_else(|e| fail(e));
 
-    teloxide::repl(bot, move |bot: Bot, msg: Message| {
-        let registry = Arc::clone(&registry);
-        async move {
-            if let Err(e) = registry.handle_message(bot, msg).await {
-                log::error!("Error handling message: {}", e);
-                Ok(())
-            } else {
-                Ok(())
+    init_tracing(config.logging.format);
+    match cli.command.unwrap_or(Command::Run) {
+        Command::Run => {
+            if let Err(e) = currency_bot::run_bot(config).await {
+                fail(e);
             }
         }
-    })
-    .await;
+        Command::CheckConfig { offline } => {
+            if !offline {
+                let price_service =
+                    currency_bot::price_service(&config).unwrap_or_else(|e| fail(e));
+                if let Err(e) = startup::check(&config, &price_service).await {
+                    fail(e);
+                }
+            }
+            println!("Configuration is valid");
+        }
+        Command::Fetch { pair } => fetch(&config, &pair).await,
+        Command::ImportHistory { files, format } => import_history(&config, &files, format),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+        assert_eq!(Cli::parse_from(["currency-bot"]).command, None);
+        assert_eq!(
+            Cli::parse_from(["currency-bot", "check-config", "--offline"]).command,
+            Some(Command::CheckConfig { offline: true })
+        );
+        assert_eq!(
+            Cli::parse_from(["currency-bot", "fetch", "USD/RUB"]).command,
+            Some(Command::Fetch {
+                pair: "USD/RUB".to_string()
+            })
+        );
+        assert!(Cli::try_parse_from(["currency-bot", "fetch"]).is_err());
+        assert_eq!(
+            Cli::parse_from([
+                "currency-bot",
+                "import-history",
+                "--format",
+                "cbr",
+                "2023.xml",
+                "2024.xml"
+            ])
+            .command,
+            Some(Command::ImportHistory {
+                files: vec![PathBuf::from("2023.xml"), PathBuf::from("2024.xml")],
+                format: Some(HistoryFormat::Cbr),
+            })
+        );
+        assert!(Cli::try_parse_from(["currency-bot", "import-history"]).is_err());
+        assert!(
+            Cli::try_parse_from(["currency-bot", "import-history", "--format", "xls", "a"])
+                .is_err()
+        );
+    }
 }
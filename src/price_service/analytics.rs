@@ -0,0 +1,513 @@
+// Analytics Module
+// Moving averages, realized volatility and min/max watermarks of every pair,
+// updated with each recorded sample instead of being recomputed from the history
+
+use crate::domain::CurrencyPair;
+use crate::price_service::history::{PriceHistory, PricePoint};
+use chrono::{DateTime, Duration, Local};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Window of the rolling min/max watermarks
+pub const RANGE_WINDOW_DAYS: i64 = 30;
+
+/// Windows the indicators are computed over by default: an hour, a day and a week
+pub fn default_windows() -> Vec<Duration> {
+    vec![Duration::hours(1), Duration::days(1), Duration::days(7)]
+}
+
+/// Indicators of a pair over one window
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Indicators {
+    pub window_minutes: i64,
+    /// Samples within the window
+    pub samples: usize,
+    /// Simple moving average of the samples within the window
+    pub sma: f64,
+    /// Exponential moving average with the window as its time constant
+    pub ema: f64,
+    /// Realized volatility, the square root of the summed squared log returns
+    /// within the window in percent, `None` before the second sample
+    pub volatility: Option<f64>,
+}
+
+/// Lowest and highest price over a period
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PriceRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl PriceRange {
+    fn include(range: &mut Option<Self>, low: f64, high: f64) {
+        *range = Some(match *range {
+            Some(range) => Self {
+                min: range.min.min(low),
+                max: range.max.max(high),
+            },
+            None => Self {
+                min: low,
+                max: high,
+            },
+        });
+    }
+}
+
+/// Min/max watermarks of a pair
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Watermarks {
+    /// Over the last [`RANGE_WINDOW_DAYS`] days
+    pub rolling: PriceRange,
+    /// Over every sample and candle the history has seen
+    pub all_time: PriceRange,
+}
+
+/// Rolling min and max, each a monotonic queue of the samples that can still
+/// become the extreme of the window
+#[derive(Debug, Default)]
+struct RollingRange {
+    minima: VecDeque<PricePoint>,
+    maxima: VecDeque<PricePoint>,
+}
+
+impl RollingRange {
+    fn push(&mut self, point: PricePoint, period: Duration) {
+        while self
+            .minima
+            .back()
+            .is_some_and(|back| back.price >= point.price)
+        {
+            self.minima.pop_back();
+        }
+        self.minima.push_back(point);
+        while self
+            .maxima
+            .back()
+            .is_some_and(|back| back.price <= point.price)
+        {
+            self.maxima.pop_back();
+        }
+        self.maxima.push_back(point);
+
+        let cutoff = point.time - period;
+        for queue in [&mut self.minima, &mut self.maxima] {
+            while queue.front().is_some_and(|front| front.time < cutoff) {
+                queue.pop_front();
+            }
+        }
+    }
+
+    fn range(&self) -> Option<PriceRange> {
+        Some(PriceRange {
+            min: self.minima.front()?.price,
+            max: self.maxima.front()?.price,
+        })
+    }
+}
+
+/// Sample within a window with the squared log return against the sample before it
+#[derive(Debug, Clone, Copy)]
+struct WindowSample {
+    time: DateTime<Local>,
+    price: f64,
+    squared_return: Option<f64>,
+}
+
+/// Running sums of one window, samples leaving it are subtracted again
+#[derive(Debug)]
+struct WindowState {
+    period: Duration,
+    samples: VecDeque<WindowSample>,
+    price_sum: f64,
+    squared_return_sum: f64,
+    returns: usize,
+    ema: Option<f64>,
+}
+
+impl WindowState {
+    fn new(period: Duration) -> Self {
+        Self {
+            period,
+            samples: VecDeque::new(),
+            price_sum: 0.0,
+            squared_return_sum: 0.0,
+            returns: 0,
+            ema: None,
+        }
+    }
+
+    fn push(&mut self, point: PricePoint, previous: Option<PricePoint>) {
+        let squared_return = previous
+            .filter(|previous| previous.price > 0.0 && point.price > 0.0)
+            .map(|previous| (point.price / previous.price).ln().powi(2));
+        self.ema = Some(match (self.ema, previous) {
+            (Some(ema), Some(previous)) => {
+                // Time-weighted, so irregular polling doesn't skew the average
+                let elapsed = (point.time - previous.time).num_milliseconds() as f64;
+                let alpha = 1.0 - (-elapsed / self.period.num_milliseconds() as f64).exp();
+                ema + alpha * (point.price - ema)
+            }
+            _ => point.price,
+        });
+
+        self.samples.push_back(WindowSample {
+            time: point.time,
+            price: point.price,
+            squared_return,
+        });
+        self.price_sum += point.price;
+        if let Some(squared_return) = squared_return {
+            self.squared_return_sum += squared_return;
+            self.returns += 1;
+        }
+
+        let cutoff = point.time - self.period;
+        while let Some(first) = self.samples.front().copied() {
+            if first.time >= cutoff {
+                break;
+            }
+            self.samples.pop_front();
+            self.price_sum -= first.price;
+            if let Some(squared_return) = first.squared_return {
+                self.squared_return_sum -= squared_return;
+                self.returns -= 1;
+            }
+        }
+    }
+
+    fn indicators(&self) -> Option<Indicators> {
+        let samples = self.samples.len();
+        if samples == 0 {
+            return None;
+        }
+        Some(Indicators {
+            window_minutes: self.period.num_minutes(),
+            samples,
+            sma: self.price_sum / samples as f64,
+            ema: self.ema?,
+            // Subtracting leaves rounding errors, which must not go below zero
+            volatility: (self.returns > 0).then(|| self.squared_return_sum.max(0.0).sqrt() * 100.0),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct PairState {
+    last: Option<PricePoint>,
+    windows: Vec<WindowState>,
+    rolling: RollingRange,
+    all_time: Option<PriceRange>,
+}
+
+/// Indicators of every pair over the configured windows, fed by
+/// [`PriceHistory::record`](crate::price_service::history::PriceHistory::record)
+pub struct Analytics {
+    windows: Vec<Duration>,
+    pairs: Mutex<HashMap<CurrencyPair, PairState>>,
+}
+
+impl Analytics {
+    /// Compute the indicators over `windows`
+    pub fn new(windows: Vec<Duration>) -> Self {
+        let mut windows: Vec<Duration> = windows
+            .into_iter()
+            .filter(|window| *window > Duration::zero())
+            .collect();
+        windows.sort();
+        windows.dedup();
+        Self {
+            windows,
+            pairs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn with_state(&self, pair: &CurrencyPair, update: impl FnOnce(&mut PairState)) {
+        let mut pairs = self.pairs.lock().unwrap();
+        let state = pairs.entry(pair.clone()).or_insert_with(|| PairState {
+            last: None,
+            windows: self.windows.iter().copied().map(WindowState::new).collect(),
+            rolling: RollingRange::default(),
+            all_time: None,
+        });
+        update(state);
+    }
+
+    /// Add a sample, samples older than the last one are ignored
+    pub fn record(&self, pair: &CurrencyPair, point: PricePoint) {
+        self.with_state(pair, |state| {
+            if state.last.is_some_and(|last| last.time > point.time) {
+                return;
+            }
+            for window in &mut state.windows {
+                window.push(point, state.last);
+            }
+            state.rolling.push(point, Duration::days(RANGE_WINDOW_DAYS));
+            PriceRange::include(&mut state.all_time, point.price, point.price);
+            state.last = Some(point);
+        });
+    }
+
+    /// Extend the all-time watermarks with the extremes of samples that are
+    /// only kept as candles
+    pub fn record_extremes(&self, pair: &CurrencyPair, low: f64, high: f64) {
+        self.with_state(pair, |state| {
+            PriceRange::include(&mut state.all_time, low, high);
+        });
+    }
+
+    /// Min/max watermarks of `pair`, `None` without samples
+    pub fn watermarks(&self, pair: &CurrencyPair) -> Option<Watermarks> {
+        let pairs = self.pairs.lock().unwrap();
+        let state = pairs.get(pair)?;
+        let rolling = state.rolling.range()?;
+        Some(Watermarks {
+            rolling,
+            all_time: state.all_time.unwrap_or(rolling),
+        })
+    }
+
+    /// Indicators of `pair` over every window, shortest first, empty without samples
+    pub fn indicators(&self, pair: &CurrencyPair) -> Vec<Indicators> {
+        let pairs = self.pairs.lock().unwrap();
+        pairs
+            .get(pair)
+            .map(|state| {
+                state
+                    .windows
+                    .iter()
+                    .filter_map(WindowState::indicators)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl Default for Analytics {
+    fn default() -> Self {
+        Self::new(default_windows())
+    }
+}
+
+/// Currencies the ruble is measured against and their weights. CNY has no
+/// pair yet and is left out until one is quoted, the weights of the others
+/// are scaled up instead.
+pub const RUB_BASKET: [(&str, f64); 3] = [("USD", 0.5), ("EUR", 0.3), ("CNY", 0.2)];
+
+/// Value of the ruble against [`RUB_BASKET`] now compared to `since`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StrengthIndex {
+    /// 100 when unchanged, above when the ruble got stronger
+    pub value: f64,
+    /// Basket currencies with prices at both ends
+    pub currencies: Vec<&'static str>,
+}
+
+/// Rubles per unit of `currency` at the first and the last sample since
+/// `since`, EUR through EUR/USD and USD/RUB
+fn rub_prices(
+    history: &PriceHistory,
+    currency: &str,
+    since: DateTime<Local>,
+) -> Option<(f64, f64)> {
+    let ends = |pair: &CurrencyPair| {
+        let points = history.range(pair, since);
+        Some((points.first()?.price, points.last()?.price))
+    };
+    match currency {
+        "USD" => ends(&CurrencyPair::USD2RUB),
+        "EUR" => {
+            let (eur_start, eur_end) = ends(&CurrencyPair::EUR2USD)?;
+            let (usd_start, usd_end) = ends(&CurrencyPair::USD2RUB)?;
+            Some((eur_start * usd_start, eur_end * usd_end))
+        }
+        _ => None,
+    }
+}
+
+/// Weighted geometric mean of the ruble's change against every basket
+/// currency with history since `since`, `None` without any
+pub fn rub_strength(history: &PriceHistory, since: DateTime<Local>) -> Option<StrengthIndex> {
+    let changes: Vec<(&'static str, f64, f64)> = RUB_BASKET
+        .iter()
+        .filter_map(|(currency, weight)| {
+            let (start, end) = rub_prices(history, currency, since)?;
+            (start > 0.0 && end > 0.0).then_some((*currency, *weight, start / end))
+        })
+        .collect();
+    let total_weight: f64 = changes.iter().map(|(_, weight, _)| weight).sum();
+    if changes.is_empty() || total_weight <= 0.0 {
+        return None;
+    }
+    let log_change: f64 = changes
+        .iter()
+        .map(|(_, weight, change)| weight / total_weight * change.ln())
+        .sum();
+    Some(StrengthIndex {
+        value: 100.0 * log_change.exp(),
+        currencies: changes
+            .into_iter()
+            .map(|(currency, _, _)| currency)
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(analytics: &Analytics, prices: &[(i64, f64)]) {
+        let start = Local::now() - Duration::hours(10);
+        for (minutes, price) in prices {
+            let point = PricePoint {
+                time: start + Duration::minutes(*minutes),
+                price: *price,
+            };
+            analytics.record(&CurrencyPair::USD2RUB, point);
+        }
+    }
+
+    #[test]
+    fn test_sma_over_window() {
+        let analytics = Analytics::new(vec![Duration::hours(1), Duration::minutes(30)]);
+        record(
+            &analytics,
+            &[(0, 80.0), (40, 90.0), (60, 100.0), (80, 110.0)],
+        );
+
+        let indicators = analytics.indicators(&CurrencyPair::USD2RUB);
+        assert_eq!(indicators.len(), 2);
+        // The 80.0 sample left both windows
+        assert_eq!(indicators[0].window_minutes, 30);
+        assert_eq!(indicators[0].samples, 2);
+        assert_eq!(indicators[0].sma, 105.0);
+        assert_eq!(indicators[1].window_minutes, 60);
+        assert_eq!(indicators[1].samples, 3);
+        assert_eq!(indicators[1].sma, 100.0);
+        assert!(analytics.indicators(&CurrencyPair::USDTe2RUB).is_empty());
+    }
+
+    #[test]
+    fn test_ema_follows_price() {
+        let analytics = Analytics::new(vec![Duration::hours(1)]);
+        record(&analytics, &[(0, 100.0)]);
+        assert_eq!(analytics.indicators(&CurrencyPair::USD2RUB)[0].ema, 100.0);
+
+        // One time constant later the average covered 1 - 1/e of the jump
+        record(&analytics, &[(60, 200.0)]);
+        let ema = analytics.indicators(&CurrencyPair::USD2RUB)[0].ema;
+        let expected = 100.0 + 100.0 * (1.0 - (-1.0f64).exp());
+        assert!((ema - expected).abs() < 1e-9, "{}", ema);
+    }
+
+    #[test]
+    fn test_volatility() {
+        let analytics = Analytics::new(vec![Duration::hours(1)]);
+        record(&analytics, &[(0, 100.0)]);
+        assert_eq!(
+            analytics.indicators(&CurrencyPair::USD2RUB)[0].volatility,
+            None
+        );
+
+        record(&analytics, &[(10, 101.0), (20, 100.0)]);
+        let volatility = analytics.indicators(&CurrencyPair::USD2RUB)[0]
+            .volatility
+            .unwrap();
+        let expected = (2.0 * (1.01f64).ln().powi(2)).sqrt() * 100.0;
+        assert!((volatility - expected).abs() < 1e-9, "{}", volatility);
+
+        // A flat hour later the returns left the window
+        record(&analytics, &[(90, 100.0), (100, 100.0)]);
+        let volatility = analytics.indicators(&CurrencyPair::USD2RUB)[0]
+            .volatility
+            .unwrap();
+        assert!(volatility < 1e-6, "{}", volatility);
+    }
+
+    #[test]
+    fn test_watermarks() {
+        let analytics = Analytics::default();
+        assert!(analytics.watermarks(&CurrencyPair::USD2RUB).is_none());
+
+        let days = |days: i64| days * 24 * 60;
+        record(
+            &analytics,
+            &[
+                (-days(40), 70.0),
+                (-days(20), 99.0),
+                (-days(10), 88.2),
+                (0, 97.4),
+                (60, 90.0),
+            ],
+        );
+        let watermarks = analytics.watermarks(&CurrencyPair::USD2RUB).unwrap();
+        assert_eq!(
+            watermarks.rolling,
+            PriceRange {
+                min: 88.2,
+                max: 99.0
+            }
+        );
+        assert_eq!(
+            watermarks.all_time,
+            PriceRange {
+                min: 70.0,
+                max: 99.0
+            }
+        );
+
+        // Twenty days later the maximum left the window
+        record(&analytics, &[(days(11), 91.0)]);
+        let watermarks = analytics.watermarks(&CurrencyPair::USD2RUB).unwrap();
+        assert_eq!(
+            watermarks.rolling,
+            PriceRange {
+                min: 88.2,
+                max: 97.4
+            }
+        );
+        assert_eq!(watermarks.all_time.max, 99.0);
+
+        analytics.record_extremes(&CurrencyPair::USD2RUB, 60.0, 120.0);
+        let watermarks = analytics.watermarks(&CurrencyPair::USD2RUB).unwrap();
+        assert_eq!(
+            watermarks.all_time,
+            PriceRange {
+                min: 60.0,
+                max: 120.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_rub_strength() {
+        let history = PriceHistory::default();
+        let now = Local::now();
+        let since = now - Duration::days(1);
+        assert_eq!(rub_strength(&history, since), None);
+
+        // The ruble loses 10% against the dollar
+        history.record(&CurrencyPair::USD2RUB, 90.0, now - Duration::hours(20));
+        history.record(&CurrencyPair::USD2RUB, 100.0, now);
+        let index = rub_strength(&history, since).unwrap();
+        assert_eq!(index.currencies, vec!["USD"]);
+        assert!((index.value - 90.0).abs() < 1e-9);
+
+        // The euro falls as much against the dollar: unchanged against the ruble
+        history.record(&CurrencyPair::EUR2USD, 1.1, now - Duration::hours(20));
+        history.record(&CurrencyPair::EUR2USD, 0.99, now);
+        let index = rub_strength(&history, since).unwrap();
+        assert_eq!(index.currencies, vec!["USD", "EUR"]);
+        let expected = 100.0 * (0.9f64.ln() * 0.625).exp();
+        assert!((index.value - expected).abs() < 1e-9, "{}", index.value);
+    }
+
+    #[test]
+    fn test_out_of_order_samples_are_ignored() {
+        let analytics = Analytics::new(vec![Duration::hours(1)]);
+        record(&analytics, &[(30, 100.0), (10, 50.0)]);
+        let indicators = analytics.indicators(&CurrencyPair::USD2RUB);
+        assert_eq!(indicators[0].samples, 1);
+        assert_eq!(indicators[0].sma, 100.0);
+    }
+}
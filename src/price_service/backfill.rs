@@ -0,0 +1,378 @@
+// Backfill Module
+// Historical rates imported into the price history from CSV files and the
+// CBR (Bank of Russia) XML archive, so charts have data from the first day
+
+use crate::domain::CurrencyPair;
+use crate::price_service::history::PricePoint;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Code of the US dollar in the CBR archive
+const CBR_USD_ID: &str = "R01235";
+
+/// Format of a history file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryFormat {
+    /// `pair,time,price` with a header, the columns in any order; `hour` and
+    /// `close` of the weekly report are accepted as well
+    Csv,
+    /// CBR `XML_daily.asp` (one day, every currency) or `XML_dynamic.asp`
+    /// (one currency over a range of days) files, USD only
+    Cbr,
+}
+
+impl HistoryFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format {
+            "csv" => Some(HistoryFormat::Csv),
+            "cbr" => Some(HistoryFormat::Cbr),
+            _ => None,
+        }
+    }
+
+    /// CBR for `.xml` files, CSV otherwise
+    pub fn detect(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("xml") => HistoryFormat::Cbr,
+            _ => HistoryFormat::Csv,
+        }
+    }
+}
+
+/// Samples of a history file in `format`
+pub fn parse_history(
+    format: HistoryFormat,
+    contents: &[u8],
+) -> Result<Vec<(CurrencyPair, PricePoint)>, String> {
+    // CBR files are windows-1251, the names aren't needed and the rest is ASCII
+    let text = String::from_utf8_lossy(contents);
+    match format {
+        HistoryFormat::Csv => parse_csv(&text),
+        HistoryFormat::Cbr => Ok(parse_cbr(&text)?
+            .into_iter()
+            .map(|point| (CurrencyPair::USD2RUB, point))
+            .collect()),
+    }
+}
+
+/// Add `imported` to `series`, a
+/// [`PriceHistory::export`](crate::price_service::history::PriceHistory::export),
+/// keeping the stored sample where both have one at the same time
+pub fn merge_history(
+    mut series: HashMap<CurrencyPair, Vec<PricePoint>>,
+    imported: Vec<(CurrencyPair, PricePoint)>,
+) -> HashMap<CurrencyPair, Vec<PricePoint>> {
+    for (pair, point) in imported {
+        series.entry(pair).or_default().push(point);
+    }
+    for points in series.values_mut() {
+        // Stable, so the stored samples stay in front of imported ones at the same time
+        points.sort_by_key(|point| point.time);
+        points.dedup_by_key(|point| point.time);
+    }
+    series
+}
+
+fn parse_csv(text: &str) -> Result<Vec<(CurrencyPair, PricePoint)>, String> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let Some((_, header)) = lines.next() else {
+        return Ok(Vec::new());
+    };
+    let columns: Vec<&str> = header.split(',').map(str::trim).collect();
+    let column = |names: &[&str]| {
+        columns
+            .iter()
+            .position(|column| names.contains(column))
+            .ok_or_else(|| format!("The header has no {} column", names.join(" or ")))
+    };
+    let pair_column = column(&["pair"])?;
+    let time_column = column(&["time", "hour", "date"])?;
+    let price_column = column(&["price", "close"])?;
+
+    lines
+        .map(|(index, line)| {
+            let line_number = index + 1;
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let field = |column: usize| {
+                fields
+                    .get(column)
+                    .copied()
+                    .ok_or_else(|| format!("Line {}: missing column {}", line_number, column + 1))
+            };
+            let pair = field(pair_column)?;
+            let pair = CurrencyPair::from_str(pair)
+                .map_err(|_| format!("Line {}: unknown pair {}", line_number, pair))?;
+            let time = field(time_column)?;
+            let time = parse_time(time)
+                .ok_or_else(|| format!("Line {}: '{}' is not a time", line_number, time))?;
+            let price = field(price_column)?;
+            let price = parse_price(price)
+                .ok_or_else(|| format!("Line {}: '{}' is not a price", line_number, price))?;
+            Ok((pair, PricePoint { time, price }))
+        })
+        .collect()
+}
+
+/// RFC 3339 or local `2024-01-31 12:00[:00]`, `2024-01-31` and `31.01.2024`
+fn parse_time(time: &str) -> Option<DateTime<Local>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(time) {
+        return Some(time.with_timezone(&Local));
+    }
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(time, format).ok())
+        .or_else(|| {
+            ["%Y-%m-%d", "%d.%m.%Y"]
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(time, format).ok())
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+fn parse_price(price: &str) -> Option<f64> {
+    price
+        .parse::<f64>()
+        .ok()
+        .filter(|price| price.is_finite() && *price > 0.0)
+}
+
+/// USD rates of the `<ValCurs>` documents in `text`, at the start of their day
+fn parse_cbr(text: &str) -> Result<Vec<PricePoint>, String> {
+    let mut points = Vec::new();
+    for document in elements(text, "ValCurs") {
+        match attribute(document, "Date") {
+            // XML_daily.asp: the date of the document, a <Valute> per currency
+            Some(date) => {
+                let valute = elements(document, "Valute")
+                    .find(|valute| attribute(valute, "ID") == Some(CBR_USD_ID));
+                if let Some(valute) = valute {
+                    points.push(cbr_point(date, valute)?);
+                }
+            }
+            // XML_dynamic.asp: a <Record> per day of one currency
+            None => {
+                if let Some(id) = attribute(document, "ID").filter(|id| *id != CBR_USD_ID) {
+                    return Err(format!(
+                        "Only USD rates ({}) can be imported, the file has {}",
+                        CBR_USD_ID, id
+                    ));
+                }
+                for record in elements(document, "Record") {
+                    let date = attribute(record, "Date")
+                        .ok_or_else(|| "A <Record> has no Date".to_string())?;
+                    points.push(cbr_point(date, record)?);
+                }
+            }
+        }
+    }
+    if points.is_empty() {
+        return Err("No USD rates in the CBR file".to_string());
+    }
+    Ok(points)
+}
+
+/// Rate per unit of an element with `<Nominal>` and `<Value>` (decimal comma)
+fn cbr_point(date: &str, element: &str) -> Result<PricePoint, String> {
+    let time = NaiveDate::parse_from_str(date, "%d.%m.%Y")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .and_then(|time| Local.from_local_datetime(&time).earliest())
+        .ok_or_else(|| format!("'{}' is not a date", date))?;
+    let number = |name: &str| {
+        child_text(element, name)
+            .and_then(|value| parse_price(&value.replace(',', ".")))
+            .ok_or_else(|| format!("No valid <{}> on {}", name, date))
+    };
+    let nominal = number("Nominal")?;
+    let value = number("Value")?;
+    Ok(PricePoint {
+        time,
+        price: value / nominal,
+    })
+}
+
+/// Every `<name ...>...</name>` element in `text`, opening tag included
+fn elements<'a>(text: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut rest = text;
+    std::iter::from_fn(move || loop {
+        let start = rest.find(&open)?;
+        let after = &rest[start + open.len()..];
+        // <Valute> but not <ValuteFoo>
+        if !after.starts_with(|c: char| c == '>' || c.is_whitespace()) {
+            rest = after;
+            continue;
+        }
+        let end = after
+            .find(&close)
+            .map_or(rest.len(), |end| start + open.len() + end + close.len());
+        let element = &rest[start..end];
+        rest = &rest[end..];
+        return Some(element);
+    })
+}
+
+/// Value of the attribute `name` of the opening tag of `element`
+fn attribute<'a>(element: &'a str, name: &str) -> Option<&'a str> {
+    let tag = &element[..element.find('>')?];
+    let pattern = format!(" {}=\"", name);
+    let start = tag.find(&pattern)? + pattern.len();
+    let length = tag[start..].find('"')?;
+    Some(&tag[start..start + length])
+}
+
+/// Text of the first `<name>` child of `element`
+fn child_text<'a>(element: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<{}>", name);
+    let start = element.find(&open)? + open.len();
+    let length = element[start..].find('<')?;
+    Some(element[start..start + length].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn local(date: &str) -> DateTime<Local> {
+        parse_time(date).unwrap()
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let csv = "pair,time,price\n\
+                   USD/RUB,2024-01-31 12:00,90.5\n\
+                   \n\
+                   USDTe/RUB,2024-01-31T09:00:00+00:00,91\n";
+        let samples = parse_history(HistoryFormat::Csv, csv.as_bytes()).unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].0, CurrencyPair::USD2RUB);
+        assert_eq!(samples[0].1.price, 90.5);
+        assert_eq!(
+            samples[0].1.time.naive_local().to_string(),
+            "2024-01-31 12:00:00"
+        );
+        assert_eq!(
+            samples[1].1.time.with_timezone(&chrono::Utc).to_rfc3339(),
+            "2024-01-31T09:00:00+00:00"
+        );
+
+        // The weekly report can be imported back
+        let report = "pair,hour,open,high,low,close,samples\nUSD/RUB,31.01.2024,90,95,88,92,3\n";
+        let samples = parse_history(HistoryFormat::Csv, report.as_bytes()).unwrap();
+        assert_eq!(samples[0].1.price, 92.0);
+
+        let error = parse_history(HistoryFormat::Csv, b"pair,price\n").unwrap_err();
+        assert_eq!(error, "The header has no time or hour or date column");
+        let error = parse_history(
+            HistoryFormat::Csv,
+            b"pair,time,price\nEUR/RUB,2024-01-31,1\n",
+        )
+        .unwrap_err();
+        assert_eq!(error, "Line 2: unknown pair EUR/RUB");
+        let error = parse_history(
+            HistoryFormat::Csv,
+            b"pair,time,price\nUSD/RUB,2024-01-31,-1\n",
+        )
+        .unwrap_err();
+        assert_eq!(error, "Line 2: '-1' is not a price");
+    }
+
+    #[test]
+    fn test_parse_cbr_daily() {
+        let xml = r#"<?xml version="1.0" encoding="windows-1251"?>
+<ValCurs Date="02.03.2002" name="Foreign Currency Market">
+<Valute ID="R01010"><NumCode>036</NumCode><CharCode>AUD</CharCode><Nominal>1</Nominal><Value>16,0102</Value></Valute>
+<Valute ID="R01235"><NumCode>840</NumCode><CharCode>USD</CharCode><Nominal>1</Nominal><Value>30,9436</Value></Valute>
+</ValCurs>"#;
+        let samples = parse_history(HistoryFormat::Cbr, xml.as_bytes()).unwrap();
+        assert_eq!(
+            samples,
+            vec![(
+                CurrencyPair::USD2RUB,
+                PricePoint {
+                    time: local("2002-03-02"),
+                    price: 30.9436
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parse_cbr_dynamic() {
+        let xml = r#"<ValCurs ID="R01235" DateRange1="01.03.2001" DateRange2="03.03.2001" name="Foreign Currency Market Dynamic">
+<Record Date="02.03.2001" Id="R01235"><Nominal>1</Nominal><Value>28,6200</Value></Record>
+<Record Date="03.03.2001" Id="R01235"><Nominal>10</Nominal><Value>286,6000</Value></Record>
+</ValCurs>"#;
+        let samples = parse_history(HistoryFormat::Cbr, xml.as_bytes()).unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].1.time, local("2001-03-02"));
+        assert!((samples[1].1.price - 28.66).abs() < 1e-9);
+
+        let euro = xml.replace("ValCurs ID=\"R01235\"", "ValCurs ID=\"R01239\"");
+        let error = parse_history(HistoryFormat::Cbr, euro.as_bytes()).unwrap_err();
+        assert!(error.contains("the file has R01239"), "{}", error);
+        assert!(parse_history(HistoryFormat::Cbr, b"<html></html>").is_err());
+    }
+
+    #[test]
+    fn test_merge_history() {
+        let now = Local::now();
+        let stored = PricePoint {
+            time: now - Duration::hours(1),
+            price: 90.0,
+        };
+        let series = HashMap::from([(CurrencyPair::USD2RUB, vec![stored])]);
+        let imported = vec![
+            (
+                CurrencyPair::USD2RUB,
+                PricePoint {
+                    time: now - Duration::days(40),
+                    price: 70.0,
+                },
+            ),
+            (
+                CurrencyPair::USD2RUB,
+                PricePoint {
+                    time: now - Duration::days(2),
+                    price: 85.0,
+                },
+            ),
+            (
+                CurrencyPair::USD2RUB,
+                PricePoint {
+                    time: stored.time,
+                    price: 1.0,
+                },
+            ),
+        ];
+
+        let merged = merge_history(series, imported);
+        let prices: Vec<f64> = merged[&CurrencyPair::USD2RUB]
+            .iter()
+            .map(|point| point.price)
+            .collect();
+        assert_eq!(prices, vec![70.0, 85.0, 90.0]);
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(
+            HistoryFormat::detect(Path::new("XML_dynamic.XML")),
+            HistoryFormat::Cbr
+        );
+        assert_eq!(
+            HistoryFormat::detect(Path::new("rates.csv")),
+            HistoryFormat::Csv
+        );
+        assert_eq!(HistoryFormat::parse("cbr"), Some(HistoryFormat::Cbr));
+        assert_eq!(HistoryFormat::parse("json"), None);
+    }
+}
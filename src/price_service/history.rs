@@ -0,0 +1,669 @@
+use crate::domain::CurrencyPair;
+use crate::price_service::analytics::Analytics;
+use chrono::{DateTime, Duration, DurationRound, Local, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+
+/// How long price samples are kept by default
+pub const DEFAULT_RETENTION_DAYS: i64 = 30;
+
+/// How long hourly candles are kept by default, daily ones are kept forever
+pub const DEFAULT_HOURLY_RETENTION_DAYS: i64 = 180;
+
+/// How long each tier of the history is kept before [`PriceHistory::compact`]
+/// folds it into the next one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetentionPolicy {
+    /// Samples older than this become hourly candles
+    pub raw: Duration,
+    /// Hourly candles older than this become daily candles
+    pub hourly: Duration,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            raw: Duration::days(DEFAULT_RETENTION_DAYS),
+            hourly: Duration::days(DEFAULT_HOURLY_RETENTION_DAYS),
+        }
+    }
+}
+
+/// Parse a period like `30m`, `12h` or `7d` of at most `max`, like the raw
+/// retention of the history the period is looked up in
+pub fn parse_period(period: &str, max: Duration) -> Option<Duration> {
+    let unit = period.chars().last()?;
+    let amount = &period[..period.len() - unit.len_utf8()];
+    let amount: i64 = amount.parse().ok().filter(|amount| *amount > 0)?;
+    let duration = match unit {
+        'm' => Duration::try_minutes(amount),
+        'h' => Duration::try_hours(amount),
+        'd' => Duration::try_days(amount),
+        _ => None,
+    }?;
+    (duration <= max).then_some(duration)
+}
+
+/// Format a period back into the shortest form accepted by [`parse_period`]
+pub fn format_period(period: Duration) -> String {
+    let minutes = period.num_minutes();
+    if minutes % (24 * 60) == 0 {
+        format!("{}d", minutes / (24 * 60))
+    } else if minutes % 60 == 0 {
+        format!("{}h", minutes / 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// Price of a pair at a point in time
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PricePoint {
+    pub time: DateTime<Local>,
+    pub price: f64,
+}
+
+/// Change of a price relative to an earlier quote, shown as a trend arrow
+/// like `▲ +0.45 (+0.50%)`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PriceChange {
+    pub delta: f64,
+    pub percent: f64,
+}
+
+impl PriceChange {
+    /// Change from `previous` to `current`, `None` without a usable previous price
+    pub fn between(previous: f64, current: f64) -> Option<Self> {
+        (previous > 0.0).then(|| Self {
+            delta: current - previous,
+            percent: (current - previous) / previous * 100.0,
+        })
+    }
+}
+
+impl fmt::Display for PriceChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Compare the rounded value so a change shown as 0.00 gets no arrow
+        let arrow = match format!("{:.2}", self.delta.abs()).as_str() {
+            "0.00" => "→",
+            _ if self.delta > 0.0 => "▲",
+            _ => "▼",
+        };
+        write!(f, "{} {:+.2} ({:+.2}%)", arrow, self.delta, self.percent)
+    }
+}
+
+/// Bars of a sparkline, lowest first
+const SPARKLINE_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Bars of a sparkline at most, each the last price of its share of the samples
+pub const SPARKLINE_WIDTH: usize = 12;
+
+/// Sparkline like `▁▂▄▆█` of `prices` in chronological order, scaled from
+/// their minimum to their maximum, `None` with fewer than two prices
+pub fn sparkline(prices: &[f64], width: usize) -> Option<String> {
+    if prices.len() < 2 || width == 0 {
+        return None;
+    }
+    let bars = prices.len().min(width);
+    let sampled: Vec<f64> = (1..=bars)
+        .map(|bar| prices[bar * prices.len() / bars - 1])
+        .collect();
+    let min = sampled.iter().copied().fold(f64::MAX, f64::min);
+    let max = sampled.iter().copied().fold(f64::MIN, f64::max);
+    let top = SPARKLINE_LEVELS.len() - 1;
+    Some(
+        sampled
+            .iter()
+            .map(|price| {
+                let level = if max > min {
+                    ((price - min) / (max - min) * top as f64).round() as usize
+                } else {
+                    top / 2
+                };
+                SPARKLINE_LEVELS[level.min(top)]
+            })
+            .collect(),
+    )
+}
+
+/// Open, high, low and close of a pair over a period
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Ohlc {
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+}
+
+impl Ohlc {
+    /// Aggregate samples in chronological order, `None` without any
+    pub fn from_points(points: &[PricePoint]) -> Option<Self> {
+        let (first, last) = (points.first()?, points.last()?);
+        let prices = points.iter().map(|point| point.price);
+        Some(Self {
+            open: first.price,
+            high: prices.clone().fold(f64::MIN, f64::max),
+            low: prices.fold(f64::MAX, f64::min),
+            close: last.price,
+        })
+    }
+
+    /// Change from the open to the close
+    pub fn change(&self) -> Option<PriceChange> {
+        PriceChange::between(self.open, self.close)
+    }
+}
+
+/// Period a [`Candle`] covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Hour,
+    Day,
+}
+
+impl Resolution {
+    /// Start of the local hour or day `time` falls into
+    fn start(self, time: DateTime<Local>) -> DateTime<Local> {
+        // Both fail only for local times skipped or repeated by a DST change
+        match self {
+            Resolution::Hour => time.duration_trunc(Duration::hours(1)).ok(),
+            Resolution::Day => Local
+                .from_local_datetime(&time.date_naive().and_time(NaiveTime::MIN))
+                .earliest(),
+        }
+        .unwrap_or(time)
+    }
+}
+
+/// Open, high, low and close of an hour or a day, kept once its samples are dropped
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Candle {
+    /// Start of the hour or day
+    pub time: DateTime<Local>,
+    #[serde(flatten)]
+    pub ohlc: Ohlc,
+    pub samples: usize,
+}
+
+impl Candle {
+    fn from_point(point: PricePoint) -> Self {
+        Self {
+            time: point.time,
+            ohlc: Ohlc {
+                open: point.price,
+                high: point.price,
+                low: point.price,
+                close: point.price,
+            },
+            samples: 1,
+        }
+    }
+
+    /// Extend with `later`, which follows every sample of this candle
+    fn extend(&mut self, later: &Candle) {
+        self.ohlc.high = self.ohlc.high.max(later.ohlc.high);
+        self.ohlc.low = self.ohlc.low.min(later.ohlc.low);
+        self.ohlc.close = later.ohlc.close;
+        self.samples += later.samples;
+    }
+}
+
+/// Hourly and daily candles of a pair, oldest first
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CandleSeries {
+    #[serde(default)]
+    pub hourly: Vec<Candle>,
+    #[serde(default)]
+    pub daily: Vec<Candle>,
+}
+
+/// Candles of a pair by the start of their period
+#[derive(Debug, Default)]
+struct Tiers {
+    hourly: BTreeMap<DateTime<Local>, Candle>,
+    daily: BTreeMap<DateTime<Local>, Candle>,
+}
+
+/// Merge `candle` into the candle of its period at `resolution`
+fn add_candle(
+    candles: &mut BTreeMap<DateTime<Local>, Candle>,
+    resolution: Resolution,
+    candle: Candle,
+) {
+    let time = resolution.start(candle.time);
+    candles
+        .entry(time)
+        .and_modify(|existing| existing.extend(&candle))
+        .or_insert(Candle { time, ..candle });
+}
+
+/// Prices per currency pair: recent samples, then hourly and daily candles
+/// of older ones, see [`Self::compact`]
+pub struct PriceHistory {
+    points: Mutex<HashMap<CurrencyPair, VecDeque<PricePoint>>>,
+    candles: Mutex<HashMap<CurrencyPair, Tiers>>,
+    retention: RetentionPolicy,
+    analytics: Analytics,
+}
+
+impl PriceHistory {
+    /// Create a history compacting its tiers according to `retention`
+    pub fn new(retention: RetentionPolicy) -> Self {
+        Self {
+            points: Mutex::new(HashMap::new()),
+            candles: Mutex::new(HashMap::new()),
+            retention,
+            analytics: Analytics::default(),
+        }
+    }
+
+    /// Compute the [`Analytics`] indicators over `windows` instead of the
+    /// default ones, each at most the raw retention
+    pub fn with_analytics_windows(mut self, windows: Vec<Duration>) -> Self {
+        let raw = self.retention.raw;
+        self.analytics =
+            Analytics::new(windows.into_iter().map(|window| window.min(raw)).collect());
+        self
+    }
+
+    /// How long the tiers are kept, samples for the raw retention only
+    pub fn retention(&self) -> RetentionPolicy {
+        self.retention
+    }
+
+    /// Indicators kept up to date with every recorded sample
+    pub fn analytics(&self) -> &Analytics {
+        &self.analytics
+    }
+
+    /// Store a sample, samples arriving out of order are ignored
+    pub fn record(&self, pair: &CurrencyPair, price: f64, time: DateTime<Local>) {
+        let mut points = self.points.lock().unwrap();
+        let series = points.entry(pair.clone()).or_default();
+        if series.back().is_some_and(|last| last.time > time) {
+            return;
+        }
+        series.push_back(PricePoint { time, price });
+        drop(points);
+        self.analytics.record(pair, PricePoint { time, price });
+    }
+
+    /// Fold the samples older than the raw retention into hourly candles and
+    /// the hourly candles older than the hourly retention into daily ones,
+    /// returning the number of samples folded
+    pub fn compact(&self, now: DateTime<Local>) -> usize {
+        let raw_cutoff = now - self.retention.raw;
+        let hourly_cutoff = now - self.retention.hourly;
+        let mut points = self.points.lock().unwrap();
+        let mut candles = self.candles.lock().unwrap();
+        let mut compacted = 0;
+        for (pair, series) in points.iter_mut() {
+            let tiers = candles.entry(pair.clone()).or_default();
+            while let Some(point) = series.front().copied().filter(|p| p.time < raw_cutoff) {
+                series.pop_front();
+                add_candle(
+                    &mut tiers.hourly,
+                    Resolution::Hour,
+                    Candle::from_point(point),
+                );
+                compacted += 1;
+            }
+        }
+        for tiers in candles.values_mut() {
+            let recent = tiers.hourly.split_off(&hourly_cutoff);
+            for candle in std::mem::replace(&mut tiers.hourly, recent).into_values() {
+                add_candle(&mut tiers.daily, Resolution::Day, candle);
+            }
+        }
+        compacted
+    }
+
+    /// Candles of `pair` at `resolution`, oldest first
+    pub fn candles(&self, pair: &CurrencyPair, resolution: Resolution) -> Vec<Candle> {
+        let candles = self.candles.lock().unwrap();
+        let Some(tiers) = candles.get(pair) else {
+            return Vec::new();
+        };
+        let candles = match resolution {
+            Resolution::Hour => &tiers.hourly,
+            Resolution::Day => &tiers.daily,
+        };
+        candles.values().copied().collect()
+    }
+
+    /// Daily candles of `pair` from the day of `since` on, oldest first,
+    /// aggregated over every tier: daily and hourly candles and samples
+    pub fn daily(&self, pair: &CurrencyPair, since: DateTime<Local>) -> Vec<Candle> {
+        let start = Resolution::Day.start(since);
+        let mut days = BTreeMap::new();
+        {
+            let candles = self.candles.lock().unwrap();
+            if let Some(tiers) = candles.get(pair) {
+                // The tiers don't overlap, older ones first
+                for candle in tiers.daily.values().chain(tiers.hourly.values()) {
+                    if candle.time >= start {
+                        add_candle(&mut days, Resolution::Day, *candle);
+                    }
+                }
+            }
+        }
+        for point in self.range(pair, start) {
+            add_candle(&mut days, Resolution::Day, Candle::from_point(point));
+        }
+        days.into_values().collect()
+    }
+
+    /// Every candle by pair
+    pub fn export_candles(&self) -> HashMap<CurrencyPair, CandleSeries> {
+        let candles = self.candles.lock().unwrap();
+        candles
+            .iter()
+            .filter(|(_, tiers)| !tiers.hourly.is_empty() || !tiers.daily.is_empty())
+            .map(|(pair, tiers)| {
+                let series = CandleSeries {
+                    hourly: tiers.hourly.values().copied().collect(),
+                    daily: tiers.daily.values().copied().collect(),
+                };
+                (pair.clone(), series)
+            })
+            .collect()
+    }
+
+    /// Add the candles of an [`Self::export_candles`], e.g. from before a restart
+    pub fn import_candles(&self, series: HashMap<CurrencyPair, CandleSeries>) {
+        let mut candles = self.candles.lock().unwrap();
+        for (pair, series) in series {
+            for candle in series.hourly.iter().chain(&series.daily) {
+                self.analytics
+                    .record_extremes(&pair, candle.ohlc.low, candle.ohlc.high);
+            }
+            let tiers = candles.entry(pair).or_default();
+            for candle in series.hourly {
+                add_candle(&mut tiers.hourly, Resolution::Hour, candle);
+            }
+            for candle in series.daily {
+                add_candle(&mut tiers.daily, Resolution::Day, candle);
+            }
+        }
+    }
+
+    /// Most recent sample of `pair`
+    pub fn latest(&self, pair: &CurrencyPair) -> Option<PricePoint> {
+        let points = self.points.lock().unwrap();
+        points.get(pair).and_then(|series| series.back()).copied()
+    }
+
+    /// Sample of `pair` recorded before the most recent one, the quote a
+    /// fresh price is compared with
+    pub fn previous(&self, pair: &CurrencyPair) -> Option<PricePoint> {
+        let points = self.points.lock().unwrap();
+        let series = points.get(pair)?;
+        series.iter().rev().nth(1).copied()
+    }
+
+    /// Samples of `pair` recorded at or after `since`, oldest first
+    pub fn range(&self, pair: &CurrencyPair, since: DateTime<Local>) -> Vec<PricePoint> {
+        let points = self.points.lock().unwrap();
+        points
+            .get(pair)
+            .map(|series| {
+                series
+                    .iter()
+                    .filter(|point| point.time >= since)
+                    .copied()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Open, high, low and close of `pair` since `since`, `None` without samples
+    pub fn ohlc(&self, pair: &CurrencyPair, since: DateTime<Local>) -> Option<Ohlc> {
+        Ohlc::from_points(&self.range(pair, since))
+    }
+
+    /// [`sparkline`] of the samples of `pair` since `since`
+    pub fn sparkline(&self, pair: &CurrencyPair, since: DateTime<Local>) -> Option<String> {
+        let prices: Vec<f64> = self
+            .range(pair, since)
+            .iter()
+            .map(|point| point.price)
+            .collect();
+        sparkline(&prices, SPARKLINE_WIDTH)
+    }
+
+    /// Every stored sample by pair, oldest first
+    pub fn export(&self) -> HashMap<CurrencyPair, Vec<PricePoint>> {
+        let points = self.points.lock().unwrap();
+        points
+            .iter()
+            .map(|(pair, series)| (pair.clone(), series.iter().copied().collect()))
+            .collect()
+    }
+
+    /// Record the samples of an [`Self::export`], e.g. from before a restart
+    pub fn import(&self, series: HashMap<CurrencyPair, Vec<PricePoint>>) {
+        for (pair, points) in series {
+            for point in points {
+                self.record(&pair, point.price, point.time);
+            }
+        }
+    }
+}
+
+impl Default for PriceHistory {
+    fn default() -> Self {
+        Self::new(RetentionPolicy::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_returns_points_since() {
+        let history = PriceHistory::default();
+        let now = Local::now();
+        history.record(&CurrencyPair::USD2RUB, 90.0, now - Duration::hours(3));
+        history.record(&CurrencyPair::USD2RUB, 91.0, now - Duration::hours(1));
+        history.record(&CurrencyPair::USD2RUB, 92.0, now);
+
+        let points = history.range(&CurrencyPair::USD2RUB, now - Duration::hours(2));
+        let prices: Vec<f64> = points.iter().map(|p| p.price).collect();
+        assert_eq!(prices, vec![91.0, 92.0]);
+        assert!(history
+            .range(&CurrencyPair::USDTe2RUB, now - Duration::days(1))
+            .is_empty());
+        assert_eq!(
+            history.latest(&CurrencyPair::USD2RUB).map(|p| p.price),
+            Some(92.0)
+        );
+        assert_eq!(
+            history.previous(&CurrencyPair::USD2RUB).map(|p| p.price),
+            Some(91.0)
+        );
+        assert!(history.latest(&CurrencyPair::USDTe2RUB).is_none());
+    }
+
+    #[test]
+    fn test_ohlc() {
+        let history = PriceHistory::default();
+        let now = Local::now();
+        for (hours, price) in [(30, 80.0), (20, 90.0), (12, 95.0), (6, 88.0), (0, 92.0)] {
+            history.record(&CurrencyPair::USD2RUB, price, now - Duration::hours(hours));
+        }
+
+        let ohlc = history
+            .ohlc(&CurrencyPair::USD2RUB, now - Duration::days(1))
+            .unwrap();
+        assert_eq!(
+            ohlc,
+            Ohlc {
+                open: 90.0,
+                high: 95.0,
+                low: 88.0,
+                close: 92.0,
+            }
+        );
+        assert_eq!(ohlc.change().unwrap().to_string(), "▲ +2.00 (+2.22%)");
+        assert!(history
+            .ohlc(&CurrencyPair::USDTe2RUB, now - Duration::days(1))
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse_and_format_period() {
+        let max = RetentionPolicy::default().raw;
+        assert_eq!(parse_period("30m", max), Some(Duration::minutes(30)));
+        assert_eq!(parse_period("12h", max), Some(Duration::hours(12)));
+        assert_eq!(parse_period("7d", max), Some(Duration::days(7)));
+        assert!(parse_period("0d", max).is_none());
+        assert!(parse_period("365d", max).is_none());
+        assert!(parse_period("7w", max).is_none());
+        assert!(parse_period("", max).is_none());
+        // Neither a multibyte unit nor an amount out of range panics
+        assert!(parse_period("7д", max).is_none());
+        assert!(parse_period("д", max).is_none());
+        assert!(parse_period("99999999999999d", max).is_none());
+        assert!(parse_period("9223372036854775807m", max).is_none());
+
+        assert_eq!(format_period(Duration::minutes(90)), "90m");
+        assert_eq!(format_period(Duration::hours(2)), "2h");
+        assert_eq!(format_period(Duration::days(7)), "7d");
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[92.0], SPARKLINE_WIDTH), None);
+        assert_eq!(
+            sparkline(&[90.0, 91.0, 93.0, 95.0, 97.0], SPARKLINE_WIDTH).as_deref(),
+            Some("▁▂▄▆█")
+        );
+        assert_eq!(
+            sparkline(&[92.0, 92.0], SPARKLINE_WIDTH).as_deref(),
+            Some("▄▄")
+        );
+        // Every bar shows the last of its samples
+        let prices: Vec<f64> = (1..=24).map(f64::from).collect();
+        let line = sparkline(&prices, 12).unwrap();
+        assert_eq!(line.chars().count(), 12);
+        assert!(line.starts_with('▁') && line.ends_with('█'));
+
+        let history = PriceHistory::default();
+        let now = Local::now();
+        history.record(&CurrencyPair::USD2RUB, 92.0, now - Duration::hours(30));
+        history.record(&CurrencyPair::USD2RUB, 90.0, now - Duration::hours(2));
+        history.record(&CurrencyPair::USD2RUB, 91.0, now);
+        let since = now - Duration::hours(24);
+        assert_eq!(
+            history.sparkline(&CurrencyPair::USD2RUB, since).as_deref(),
+            Some("▁█")
+        );
+    }
+
+    #[test]
+    fn test_price_change() {
+        let up = PriceChange::between(90.0, 90.45).unwrap();
+        assert_eq!(up.to_string(), "▲ +0.45 (+0.50%)");
+        let down = PriceChange::between(100.0, 99.0).unwrap();
+        assert_eq!(down.to_string(), "▼ -1.00 (-1.00%)");
+        let flat = PriceChange::between(100.0, 100.001).unwrap();
+        assert_eq!(flat.to_string(), "→ +0.00 (+0.00%)");
+        assert!(PriceChange::between(0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn test_out_of_order_points_are_ignored() {
+        let history = PriceHistory::default();
+        let now = Local::now();
+        history.record(&CurrencyPair::USD2RUB, 92.0, now);
+        history.record(&CurrencyPair::USD2RUB, 91.0, now - Duration::hours(1));
+
+        let points = history.range(&CurrencyPair::USD2RUB, now - Duration::days(1));
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].price, 92.0);
+    }
+
+    #[test]
+    fn test_compaction() {
+        let history = PriceHistory::new(RetentionPolicy {
+            raw: Duration::days(1),
+            hourly: Duration::days(3),
+        });
+        // Noon `days` ago, so the samples of a day stay within it
+        let noon = |days: i64| {
+            Local
+                .from_local_datetime(
+                    &(Local::now() - Duration::days(days))
+                        .date_naive()
+                        .and_hms_opt(12, 0, 0)
+                        .unwrap(),
+                )
+                .earliest()
+                .unwrap()
+        };
+        let start = noon(5);
+        for (minutes, price) in [(0, 90.0), (20, 95.0), (40, 88.0), (70, 91.0)] {
+            history.record(
+                &CurrencyPair::USD2RUB,
+                price,
+                start + Duration::minutes(minutes),
+            );
+        }
+        history.record(&CurrencyPair::USD2RUB, 92.0, noon(2));
+        history.record(&CurrencyPair::USD2RUB, 93.0, Local::now());
+
+        // Two days later the samples of the first day became hourly candles
+        assert_eq!(history.compact(start + Duration::days(2)), 4);
+        let hourly = history.candles(&CurrencyPair::USD2RUB, Resolution::Hour);
+        assert_eq!(hourly.len(), 2);
+        assert_eq!(hourly[0].time, start);
+        assert_eq!(
+            hourly[0].ohlc,
+            Ohlc {
+                open: 90.0,
+                high: 95.0,
+                low: 88.0,
+                close: 88.0,
+            }
+        );
+        assert_eq!(hourly[0].samples, 3);
+        assert!(history
+            .candles(&CurrencyPair::USD2RUB, Resolution::Day)
+            .is_empty());
+
+        // Now the first day is past the hourly retention as well
+        assert_eq!(history.compact(Local::now()), 1);
+        let daily = history.candles(&CurrencyPair::USD2RUB, Resolution::Day);
+        assert_eq!(daily.len(), 1);
+        assert_eq!(daily[0].time, noon(5) - Duration::hours(12));
+        assert_eq!(daily[0].ohlc.close, 91.0);
+        assert_eq!(daily[0].samples, 4);
+        assert_eq!(
+            history.candles(&CurrencyPair::USD2RUB, Resolution::Hour)[0]
+                .ohlc
+                .close,
+            92.0
+        );
+        let points = history.range(&CurrencyPair::USD2RUB, start);
+        assert_eq!(points.len(), 1);
+
+        // Days aggregate whichever tier holds them
+        let days = history.daily(&CurrencyPair::USD2RUB, noon(6));
+        let closes: Vec<f64> = days.iter().map(|day| day.ohlc.close).collect();
+        assert_eq!(closes, [91.0, 92.0, 93.0]);
+        assert_eq!(days[0].samples, 4);
+        assert_eq!(history.daily(&CurrencyPair::USD2RUB, noon(2)).len(), 2);
+
+        // Candles survive a restart
+        let restored = PriceHistory::default();
+        restored.import_candles(history.export_candles());
+        assert_eq!(
+            restored.candles(&CurrencyPair::USD2RUB, Resolution::Day),
+            daily
+        );
+    }
+}
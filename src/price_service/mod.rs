@@ -1,8 +1,12 @@
 // Price Service Module
 // Main module for price service functionality
 
+pub mod analytics;
+pub mod backfill;
+pub mod history;
 pub mod provider;
 pub mod providers;
+pub mod schema;
 pub mod service;
 
 pub use service::PriceService;
@@ -1,5 +1,7 @@
 use crate::domain::{CurrencyPair, PriceData, PriceProviderError};
 use async_trait::async_trait;
+use futures::future::join_all;
+use std::sync::Arc;
 
 /// Trait for price providers
 #[async_trait]
@@ -10,6 +12,61 @@ pub trait PriceProvider: Send + Sync {
     /// Fetch price data for a domain currency pair
     async fn fetch_price(&self, pair: &CurrencyPair) -> Result<PriceData, PriceProviderError>;
 
+    /// Fetch several pairs, the results in the order of `pairs`. By default
+    /// a [`Self::fetch_price`] of each pair at the same time, providers
+    /// answering many pairs in one response override it to send a single
+    /// request.
+    async fn fetch_prices(
+        &self,
+        pairs: &[CurrencyPair],
+    ) -> Vec<Result<PriceData, PriceProviderError>> {
+        join_all(pairs.iter().map(|pair| self.fetch_price(pair))).await
+    }
+
     /// Check if this provider supports the given currency pair
     fn supports_currency_pair(&self, pair: &CurrencyPair) -> bool;
+
+    /// Responses that deviated from the format the provider expects but
+    /// were still read, see [`super::schema`]
+    fn schema_drift(&self) -> u64 {
+        0
+    }
+
+    /// Replace the credential the provider authenticates with, e.g. a
+    /// rotated cookie, returning false for providers without one
+    fn set_credential(&self, _credential: &str) -> bool {
+        false
+    }
+}
+
+/// Shared providers, e.g. the ones of a `PriceService` wrapped by a
+/// [`super::providers::FaultInjectingProvider`]
+#[async_trait]
+impl<P: PriceProvider + ?Sized> PriceProvider for Arc<P> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    async fn fetch_price(&self, pair: &CurrencyPair) -> Result<PriceData, PriceProviderError> {
+        (**self).fetch_price(pair).await
+    }
+
+    async fn fetch_prices(
+        &self,
+        pairs: &[CurrencyPair],
+    ) -> Vec<Result<PriceData, PriceProviderError>> {
+        (**self).fetch_prices(pairs).await
+    }
+
+    fn supports_currency_pair(&self, pair: &CurrencyPair) -> bool {
+        (**self).supports_currency_pair(pair)
+    }
+
+    fn schema_drift(&self) -> u64 {
+        (**self).schema_drift()
+    }
+
+    fn set_credential(&self, credential: &str) -> bool {
+        (**self).set_credential(credential)
+    }
 }
@@ -0,0 +1,239 @@
+use crate::domain::{CurrencyPair, PriceData, PriceProviderError};
+use crate::price_service::provider::PriceProvider;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Misbehavior injected by a [`FaultInjectingProvider`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Faults {
+    /// Share of requests failing with a network error, `0..=100`
+    pub error_percent: u8,
+    /// Share of requests answered with the pair's previous price instead of
+    /// asking the provider, `0..=100`
+    pub stale_percent: u8,
+    /// Delay added to every request
+    pub latency: Duration,
+}
+
+impl Faults {
+    pub fn is_active(&self) -> bool {
+        self.error_percent > 0 || self.stale_percent > 0 || !self.latency.is_zero()
+    }
+}
+
+/// Provider wrapper for chaos testing: delays requests, fails some of them
+/// and serves stale prices, so the fallback to other providers, the
+/// down/up reports and the alerts can be checked against a misbehaving
+/// provider. It keeps the name of the wrapped provider.
+pub struct FaultInjectingProvider<P> {
+    inner: P,
+    faults: Faults,
+    /// xorshift state, seeded for reproducible tests
+    rng: Mutex<u64>,
+    last_prices: Mutex<HashMap<CurrencyPair, PriceData>>,
+}
+
+impl<P: PriceProvider> FaultInjectingProvider<P> {
+    pub fn new(inner: P, faults: Faults) -> Self {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(1, |time| time.as_nanos() as u64);
+        Self::with_seed(inner, faults, seed)
+    }
+
+    pub fn with_seed(inner: P, faults: Faults, seed: u64) -> Self {
+        Self {
+            inner,
+            faults,
+            // xorshift never leaves zero
+            rng: Mutex::new(seed.max(1)),
+            last_prices: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether an event of `percent` happens this time
+    fn roll(&self, percent: u8) -> bool {
+        if percent == 0 {
+            return false;
+        }
+        let mut state = self.rng.lock().unwrap();
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state % 100 < u64::from(percent)
+    }
+
+    /// Injected failure or stale price of `pair`, `None` to ask the provider
+    fn injected(&self, pair: &CurrencyPair) -> Option<Result<PriceData, PriceProviderError>> {
+        if self.roll(self.faults.error_percent) {
+            return Some(Err(PriceProviderError::Network(format!(
+                "Injected failure of {} for {}",
+                self.name(),
+                pair
+            ))));
+        }
+        if self.roll(self.faults.stale_percent) {
+            if let Some(stale) = self.last_prices.lock().unwrap().get(pair) {
+                return Some(Ok(stale.clone()));
+            }
+        }
+        None
+    }
+
+    /// Keep the price the provider answered with to serve it stale later
+    fn remember(
+        &self,
+        result: Result<PriceData, PriceProviderError>,
+    ) -> Result<PriceData, PriceProviderError> {
+        if let Ok(price) = &result {
+            self.last_prices
+                .lock()
+                .unwrap()
+                .insert(price.pair.clone(), price.clone());
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl<P: PriceProvider> PriceProvider for FaultInjectingProvider<P> {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn fetch_price(&self, pair: &CurrencyPair) -> Result<PriceData, PriceProviderError> {
+        if !self.faults.latency.is_zero() {
+            tokio::time::sleep(self.faults.latency).await;
+        }
+        if let Some(injected) = self.injected(pair) {
+            return injected;
+        }
+        self.remember(self.inner.fetch_price(pair).await)
+    }
+
+    /// Faults are rolled for every pair, the rest is left to one batch of
+    /// the wrapped provider
+    async fn fetch_prices(
+        &self,
+        pairs: &[CurrencyPair],
+    ) -> Vec<Result<PriceData, PriceProviderError>> {
+        if !self.faults.latency.is_zero() {
+            tokio::time::sleep(self.faults.latency).await;
+        }
+        let injected: Vec<_> = pairs.iter().map(|pair| self.injected(pair)).collect();
+        let asked: Vec<CurrencyPair> = pairs
+            .iter()
+            .zip(&injected)
+            .filter(|(_, injected)| injected.is_none())
+            .map(|(pair, _)| pair.clone())
+            .collect();
+        let mut fetched = self.inner.fetch_prices(&asked).await.into_iter();
+        injected
+            .into_iter()
+            .map(|injected| {
+                injected.unwrap_or_else(|| match fetched.next() {
+                    Some(result) => self.remember(result),
+                    None => Err(PriceProviderError::Provider(format!(
+                        "{} answered fewer pairs than asked",
+                        self.name()
+                    ))),
+                })
+            })
+            .collect()
+    }
+
+    fn supports_currency_pair(&self, pair: &CurrencyPair) -> bool {
+        self.inner.supports_currency_pair(pair)
+    }
+
+    fn schema_drift(&self) -> u64 {
+        self.inner.schema_drift()
+    }
+
+    fn set_credential(&self, credential: &str) -> bool {
+        self.inner.set_credential(credential)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Provider quoting 1.0, 2.0, ... on its successive requests
+    struct CountingProvider {
+        requests: AtomicU32,
+    }
+
+    #[async_trait]
+    impl PriceProvider for CountingProvider {
+        fn name(&self) -> &str {
+            "Counting"
+        }
+
+        async fn fetch_price(&self, pair: &CurrencyPair) -> Result<PriceData, PriceProviderError> {
+            let request = self.requests.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(PriceData {
+                pair: pair.clone(),
+                price: f64::from(request),
+            })
+        }
+
+        fn supports_currency_pair(&self, _pair: &CurrencyPair) -> bool {
+            true
+        }
+    }
+
+    fn provider(faults: Faults) -> FaultInjectingProvider<CountingProvider> {
+        let inner = CountingProvider {
+            requests: AtomicU32::new(0),
+        };
+        FaultInjectingProvider::with_seed(inner, faults, 42)
+    }
+
+    #[tokio::test]
+    async fn test_error_rate() {
+        let provider = provider(Faults {
+            error_percent: 30,
+            ..Faults::default()
+        });
+        assert_eq!(provider.name(), "Counting");
+        let mut errors = 0;
+        for _ in 0..1000 {
+            if let Err(e) = provider.fetch_price(&CurrencyPair::USD2RUB).await {
+                assert!(matches!(e, PriceProviderError::Network(_)));
+                errors += 1;
+            }
+        }
+        assert!((200..400).contains(&errors), "{} errors", errors);
+
+        let reliable = self::provider(Faults::default());
+        assert!(!Faults::default().is_active());
+        for _ in 0..100 {
+            assert!(reliable.fetch_price(&CurrencyPair::USD2RUB).await.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stale_prices_and_latency() {
+        let provider = provider(Faults {
+            stale_percent: 100,
+            latency: Duration::from_millis(20),
+            ..Faults::default()
+        });
+        let started = std::time::Instant::now();
+        // Nothing to repeat yet, then the first price forever
+        for _ in 0..3 {
+            let price = provider.fetch_price(&CurrencyPair::USD2RUB).await.unwrap();
+            assert_eq!(price.price, 1.0);
+        }
+        let other = provider
+            .fetch_price(&CurrencyPair::USDTe2RUB)
+            .await
+            .unwrap();
+        assert_eq!(other.price, 2.0);
+        assert!(started.elapsed() >= Duration::from_millis(80));
+    }
+}
@@ -0,0 +1,49 @@
+use crate::domain::{CurrencyPair, PriceData, PriceProviderError};
+use crate::price_service::provider::PriceProvider;
+use async_trait::async_trait;
+
+/// Provider for tests quoting every pair at one price, or failing every
+/// request without a price
+pub struct FixedProvider {
+    name: &'static str,
+    price: Option<f64>,
+}
+
+impl FixedProvider {
+    /// "Fixed" quoting every pair at `price`
+    pub fn new(price: f64) -> Self {
+        Self::named("Fixed", price)
+    }
+
+    pub fn named(name: &'static str, price: f64) -> Self {
+        Self {
+            name,
+            price: Some(price),
+        }
+    }
+
+    /// Provider whose every request times out
+    pub fn failing(name: &'static str) -> Self {
+        Self { name, price: None }
+    }
+}
+
+#[async_trait]
+impl PriceProvider for FixedProvider {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    async fn fetch_price(&self, pair: &CurrencyPair) -> Result<PriceData, PriceProviderError> {
+        self.price
+            .map(|price| PriceData {
+                pair: pair.clone(),
+                price,
+            })
+            .ok_or_else(|| PriceProviderError::Network("timeout".to_string()))
+    }
+
+    fn supports_currency_pair(&self, _pair: &CurrencyPair) -> bool {
+        true
+    }
+}
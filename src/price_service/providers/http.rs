@@ -0,0 +1,176 @@
+use crate::config::HttpClientConfig;
+use crate::domain::PriceProviderError;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{Certificate, Client, Proxy, RequestBuilder, StatusCode};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Build the client every provider is handed, so they share one connection
+/// pool, the timeouts and the proxy and TLS settings of `[http_client]`
+pub fn shared_client(settings: &HttpClientConfig) -> Result<Client, String> {
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(settings.timeout_seconds))
+        .connect_timeout(Duration::from_secs(settings.connect_timeout_seconds))
+        .pool_max_idle_per_host(settings.pool_max_idle_per_host)
+        .pool_idle_timeout(Duration::from_secs(settings.pool_idle_timeout_seconds));
+    if let Some(path) = &settings.root_ca_file {
+        let pem = std::fs::read(path).map_err(|e| {
+            format!(
+                "http_client.root_ca_file (PROVIDER_ROOT_CA_FILE) {}: {}",
+                path.display(),
+                e
+            )
+        })?;
+        let certificate = Certificate::from_pem(&pem).map_err(|e| {
+            format!(
+                "http_client.root_ca_file (PROVIDER_ROOT_CA_FILE) {} is not a PEM certificate: {}",
+                path.display(),
+                e
+            )
+        })?;
+        builder = builder.add_root_certificate(certificate);
+    }
+    if let Some(proxy) = &settings.proxy {
+        let proxy = Proxy::all(proxy)
+            .map_err(|e| format!("http_client.proxy (PROVIDER_PROXY) {}: {}", proxy, e))?;
+        builder = builder.proxy(proxy);
+    }
+    builder.build().map_err(|e| format!("HTTP client: {}", e))
+}
+
+/// Last body of a URL with the validators the upstream sent for it
+#[derive(Debug, Clone)]
+struct CachedBody {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    body: String,
+}
+
+/// GET requests of a provider, made conditional with `If-None-Match` and
+/// `If-Modified-Since` once the upstream sent an `ETag` or `Last-Modified`
+/// for the URL, the cached body being reused on `304 Not Modified`.
+/// Upstreams without validators are requested as usual.
+#[derive(Debug)]
+pub struct ConditionalClient {
+    client: Client,
+    enabled: bool,
+    cache: Mutex<HashMap<String, CachedBody>>,
+}
+
+impl ConditionalClient {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            enabled: true,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Send every request unconditionally (`prices.conditional_requests`)
+    pub fn with_conditional_requests(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Send `request` and read its body: a `304` answers the cached body
+    /// with `200 OK`, other statuses are returned with their own body
+    pub async fn get_text(
+        &self,
+        request: RequestBuilder,
+    ) -> Result<(StatusCode, String), PriceProviderError> {
+        let mut request = request
+            .build()
+            .map_err(|e| PriceProviderError::Network(e.to_string()))?;
+        let key = request.url().to_string();
+        let cached = match self.enabled {
+            true => self.cache.lock().unwrap().get(&key).cloned(),
+            false => None,
+        };
+        if let Some(cached) = &cached {
+            let headers = request.headers_mut();
+            if let Some(etag) = cached.etag.as_ref().and_then(|etag| etag.parse().ok()) {
+                headers.insert(IF_NONE_MATCH, etag);
+            }
+            let last_modified = cached
+                .last_modified
+                .as_ref()
+                .and_then(|time| time.parse().ok());
+            if let Some(last_modified) = last_modified {
+                headers.insert(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = self
+            .client
+            .execute(request)
+            .await
+            .map_err(|e| PriceProviderError::Network(e.to_string()))?;
+        let status = response.status();
+        if let (StatusCode::NOT_MODIFIED, Some(cached)) = (status, cached) {
+            tracing::debug!("{} not modified, reusing the cached body", key);
+            return Ok((StatusCode::OK, cached.body));
+        }
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        let body = response
+            .text()
+            .await
+            .map_err(|e| PriceProviderError::Network(e.to_string()))?;
+
+        if self.enabled && status.is_success() {
+            let mut cache = self.cache.lock().unwrap();
+            match etag.is_some() || last_modified.is_some() {
+                true => {
+                    cache.insert(
+                        key,
+                        CachedBody {
+                            etag,
+                            last_modified,
+                            body: body.clone(),
+                        },
+                    );
+                }
+                false => {
+                    cache.remove(&key);
+                }
+            }
+        }
+        Ok((status, body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_client_reports_bad_root_ca() {
+        assert!(shared_client(&HttpClientConfig::default()).is_ok());
+        let settings = HttpClientConfig {
+            root_ca_file: Some("missing-ca.pem".into()),
+            ..Default::default()
+        };
+        let error = shared_client(&settings).unwrap_err();
+        assert!(
+            error.starts_with("http_client.root_ca_file (PROVIDER_ROOT_CA_FILE) missing-ca.pem: ")
+        );
+        let settings = HttpClientConfig {
+            root_ca_file: Some("Cargo.toml".into()),
+            ..Default::default()
+        };
+        let error = shared_client(&settings).unwrap_err();
+        assert!(error.contains("is not a PEM certificate"), "{}", error);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::domain::{CurrencyPair, PriceData, PriceProviderError};
+use crate::price_service::provider::PriceProvider;
+use crate::price_service::providers::http::ConditionalClient;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Configuration for the Kraken provider
+#[derive(Debug, Clone)]
+pub struct KrakenConfig {
+    pub base_url: String,
+}
+
+/// Kraken public API envelope: `error` lists the failures, `result` holds the
+/// tickers by Kraken's own pair name (e.g. `USDTZUSD` for `USDTUSD`)
+#[derive(Debug, Deserialize)]
+pub struct KrakenResponse {
+    pub error: Vec<String>,
+    #[serde(default)]
+    pub result: HashMap<String, KrakenTicker>,
+}
+
+/// Ticker of a pair, only the last trade is read
+#[derive(Debug, Deserialize)]
+pub struct KrakenTicker {
+    /// Last trade closed: price and lot volume
+    pub c: Vec<String>,
+}
+
+/// Price provider for the fiat and stablecoin legs quoted on Kraken's public
+/// ticker, a source independent of the ruble venues
+pub struct KrakenProvider {
+    config: KrakenConfig,
+    http: ConditionalClient,
+}
+
+impl KrakenProvider {
+    /// Name the provider is registered and toggled by
+    pub const NAME: &'static str = "KrakenProvider";
+
+    /// Provider sending its requests through `client`, the one
+    /// [`super::http::shared_client`] built for all providers
+    pub fn new(config: KrakenConfig, client: Client) -> Self {
+        Self {
+            config,
+            http: ConditionalClient::new(client),
+        }
+    }
+
+    /// Request the tickers unconditionally even when Kraken sends an `ETag`
+    /// or `Last-Modified`, see [`ConditionalClient`]
+    pub fn with_conditional_requests(mut self, enabled: bool) -> Self {
+        self.http = self.http.with_conditional_requests(enabled);
+        self
+    }
+
+    /// Kraken pair requested for `pair`, `None` for pairs with a ruble side
+    /// Kraken doesn't list
+    fn map_currency_pair(pair: &CurrencyPair) -> Option<&'static str> {
+        match pair {
+            CurrencyPair::USDT2USD => Some("USDTUSD"),
+            CurrencyPair::EUR2USD => Some("EURUSD"),
+            CurrencyPair::USDCe2RUB | CurrencyPair::USDTe2RUB | CurrencyPair::USD2RUB => None,
+        }
+    }
+
+    /// Last trade price of the only ticker in `response`
+    fn parse_price(response: KrakenResponse, symbol: &str) -> Result<f64, PriceProviderError> {
+        if !response.error.is_empty() {
+            return Err(PriceProviderError::Api(response.error.join(", ")));
+        }
+        let ticker = response.result.into_values().next().ok_or_else(|| {
+            PriceProviderError::Provider(format!("No ticker for {} in API response", symbol))
+        })?;
+        ticker
+            .c
+            .first()
+            .and_then(|price| price.parse::<f64>().ok())
+            .filter(|price| price.is_finite() && *price > 0.0)
+            .ok_or_else(|| {
+                PriceProviderError::Parsing(format!(
+                    "Invalid last trade of {}: {:?}",
+                    symbol, ticker.c
+                ))
+            })
+    }
+}
+
+#[async_trait]
+impl PriceProvider for KrakenProvider {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    async fn fetch_price(&self, pair: &CurrencyPair) -> Result<PriceData, PriceProviderError> {
+        let symbol = Self::map_currency_pair(pair).ok_or_else(|| {
+            PriceProviderError::Provider(format!(
+                "Currency pair {} not supported by this provider",
+                pair
+            ))
+        })?;
+
+        let url = format!("{}/0/public/Ticker", self.config.base_url);
+        tracing::debug!("KrakenProvider: Fetching {} for pair {}", symbol, pair);
+
+        let request = self
+            .http
+            .client()
+            .get(&url)
+            .query(&[("pair", symbol)])
+            .header("Accept", "application/json");
+        let (status, body) = self.http.get_text(request).await?;
+        if !status.is_success() {
+            return Err(PriceProviderError::Api(format!(
+                "API request failed with status: {}",
+                status
+            )));
+        }
+
+        let response: KrakenResponse =
+            serde_json::from_str(&body).map_err(|e| PriceProviderError::Parsing(e.to_string()))?;
+        Ok(PriceData {
+            pair: pair.clone(),
+            price: Self::parse_price(response, symbol)?,
+        })
+    }
+
+    fn supports_currency_pair(&self, pair: &CurrencyPair) -> bool {
+        Self::map_currency_pair(pair).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_currency_pair_support() {
+        let provider = KrakenProvider::new(
+            KrakenConfig {
+                base_url: "https://test.com".to_string(),
+            },
+            Client::new(),
+        );
+
+        assert!(provider.supports_currency_pair(&CurrencyPair::USDT2USD));
+        assert!(provider.supports_currency_pair(&CurrencyPair::EUR2USD));
+        assert!(!provider.supports_currency_pair(&CurrencyPair::USD2RUB));
+        assert!(!provider.supports_currency_pair(&CurrencyPair::USDTe2RUB));
+    }
+
+    #[test]
+    fn test_parse_price() {
+        let response = |json: &str| serde_json::from_str::<KrakenResponse>(json).unwrap();
+
+        let ok = response(r#"{"error":[],"result":{"ZEURZUSD":{"c":["1.08340","12.5"]}}}"#);
+        assert_eq!(KrakenProvider::parse_price(ok, "EURUSD").unwrap(), 1.0834);
+
+        let unknown = response(r#"{"error":["EQuery:Unknown asset pair"]}"#);
+        let error = KrakenProvider::parse_price(unknown, "EURUSD").unwrap_err();
+        assert!(matches!(error, PriceProviderError::Api(message) if message.contains("Unknown")));
+
+        let invalid = response(r#"{"error":[],"result":{"USDTZUSD":{"c":["n/a"]}}}"#);
+        assert!(matches!(
+            KrakenProvider::parse_price(invalid, "USDTUSD"),
+            Err(PriceProviderError::Parsing(_))
+        ));
+    }
+}
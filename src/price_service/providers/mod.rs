@@ -1,6 +1,22 @@
 // Price providers module
-// Contains concrete implementations of price providers
+// Contains concrete implementations of price providers, each behind its own
+// cargo feature
 
+pub mod fault_injection;
+#[cfg(test)]
+pub mod fixed;
+#[cfg(any(feature = "newline", feature = "kraken", feature = "webhooks"))]
+pub mod http;
+#[cfg(feature = "kraken")]
+pub mod kraken_provider;
+#[cfg(feature = "newline")]
 pub mod newline_provider;
+pub mod street_rate;
 
+pub use fault_injection::{FaultInjectingProvider, Faults};
+#[cfg(feature = "kraken")]
+pub use kraken_provider::{KrakenConfig, KrakenProvider};
+
+#[cfg(feature = "newline")]
 pub use newline_provider::{NewLineConfig, NewLineProvider};
+pub use street_rate::StreetRate;
@@ -1,9 +1,12 @@
 use crate::domain::{CurrencyPair, PriceData, PriceProviderError};
 use crate::price_service::provider::PriceProvider;
+use crate::price_service::providers::http::ConditionalClient;
+use crate::price_service::schema::{Extractor, SchemaDrift};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use serde_json;
+use serde_json::Value;
+use std::sync::RwLock;
 
 /// Configuration for the NewLine provider
 #[derive(Debug, Clone)]
@@ -35,16 +38,30 @@ pub struct NewLineCityData {
     pub data: Vec<NewLineExchange>,
 }
 
+/// Fields of a city the parser reads
+const CITY_FIELDS: [&str; 2] = ["city_code", "data"];
+/// Fields of an exchange direction the parser reads
+const EXCHANGE_FIELDS: [&str; 2] = ["from_", "to_data"];
+/// Fields of a direction's rate the parser reads
+const TO_DATA_FIELDS: [&str; 3] = ["to", "course_from", "course_to"];
+
 /// Price provider implementation for NewLine API
 pub struct NewLineProvider {
     config: NewLineConfig,
-    client: Client,
+    /// `config.cookie` until an admin swaps it with `set_credential`
+    cookie: RwLock<String>,
+    http: ConditionalClient,
     supported_symbols: Vec<String>,
+    drift: SchemaDrift,
 }
 
 impl NewLineProvider {
-    /// Create a new NewLineProvider instance
-    pub fn new(config: NewLineConfig) -> Self {
+    /// Name the provider is registered and toggled by
+    pub const NAME: &'static str = "NewLineProvider";
+
+    /// Create a new NewLineProvider instance sending its requests through
+    /// `client`, the one [`super::http::shared_client`] built for all providers
+    pub fn new(config: NewLineConfig, client: Client) -> Self {
         let supported_symbols = vec![
             "BTC_TO_CASHRUB".to_string(),
             "USDTERC_TO_CASHRUB".to_string(),
@@ -65,12 +82,83 @@ impl NewLineProvider {
         ];
 
         Self {
+            cookie: RwLock::new(config.cookie.clone()),
             config,
-            client: Client::new(),
+            http: ConditionalClient::new(client),
             supported_symbols,
+            drift: SchemaDrift::new(Self::NAME),
         }
     }
 
+    /// Request the direction endpoint unconditionally even when NewLine sends
+    /// an `ETag` or `Last-Modified`, see [`ConditionalClient`]
+    pub fn with_conditional_requests(mut self, enabled: bool) -> Self {
+        self.http = self.http.with_conditional_requests(enabled);
+        self
+    }
+
+    /// Read the cities out of a response body through a tolerant
+    /// [`Extractor`]: renamed fields, numbers sent as strings and entries
+    /// missing what a price needs (which are skipped) count as schema drift
+    /// instead of failing the request. Only a body that isn't JSON or has no
+    /// list of cities is an error.
+    pub fn parse_city_data(&self, body: &str) -> Result<Vec<NewLineCityData>, PriceProviderError> {
+        let body: Value =
+            serde_json::from_str(body).map_err(|e| PriceProviderError::Parsing(e.to_string()))?;
+        let mut extract = Extractor::new();
+        let cities = match &body {
+            Value::Array(cities) => Some(cities.as_slice()),
+            Value::Object(object) => {
+                extract.deviation("cities wrapped in an object".to_string());
+                extract.array("response", object, &["data", "results"])
+            }
+            _ => None,
+        }
+        .ok_or_else(|| {
+            PriceProviderError::Parsing("Expected a list of cities in API response".to_string())
+        })?;
+
+        let mut city_data_list = Vec::new();
+        for city in extract.objects("city", cities) {
+            extract.unknown_fields("city", city, &CITY_FIELDS);
+            let code = extract.string("city", city, &["city_code", "city"]);
+            let exchanges = extract.array("city", city, &["data", "directions"]);
+            let (Some(code), Some(exchanges)) = (code, exchanges) else {
+                continue;
+            };
+            let mut data = Vec::new();
+            for exchange in extract.objects("exchange", exchanges) {
+                extract.unknown_fields("exchange", exchange, &EXCHANGE_FIELDS);
+                let from = extract.string("exchange", exchange, &["from_", "from"]);
+                let rates = extract.array("exchange", exchange, &["to_data", "rates"]);
+                let (Some(from), Some(rates)) = (from, rates) else {
+                    continue;
+                };
+                let to_data = extract
+                    .objects("to_data", rates)
+                    .filter_map(|rate| {
+                        extract.unknown_fields("to_data", rate, &TO_DATA_FIELDS);
+                        Some(NewLineToData {
+                            to: extract.string("to_data", rate, &["to"])?.to_string(),
+                            course_from: extract.number("to_data", rate, &["course_from"])?,
+                            course_to: extract.number("to_data", rate, &["course_to"])?,
+                        })
+                    })
+                    .collect();
+                data.push(NewLineExchange {
+                    from_: from.to_string(),
+                    to_data,
+                });
+            }
+            city_data_list.push(NewLineCityData {
+                city_code: code.to_string(),
+                data,
+            });
+        }
+        extract.finish(&self.drift);
+        Ok(city_data_list)
+    }
+
     /// Extract price data from NewLine exchange data
     ///
     /// # Arguments
@@ -98,7 +186,7 @@ impl NewLineProvider {
     /// # Returns
     ///
     /// Option<NewLineToData> - Some(to_data) if found in preferred city, None otherwise
-    fn find_price_in_city_data(
+    pub fn find_price_in_city_data(
         &self,
         city_data_list: &[NewLineCityData],
         symbol: &str,
@@ -118,60 +206,34 @@ impl NewLineProvider {
         None
     }
 
-    /// Map domain currency pair to NewLine provider symbol (private method)
-    ///
-    /// Note: Both USDCe2RUB and USDTe2RUB map to the same USDTERC_TO_CASHRUB symbol
-    /// since the NewLine API doesn't distinguish between different ERC20 stablecoins.
-    /// This is a provider limitation, not a bug in the mapping logic.
-    fn map_currency_pair(&self, pair: &CurrencyPair) -> Option<String> {
-        match pair {
-            CurrencyPair::USDCe2RUB => Some("USDTERC_TO_CASHRUB".to_string()),
-            CurrencyPair::USDTe2RUB => Some("USDTERC_TO_CASHRUB".to_string()),
-            CurrencyPair::USD2RUB => Some("CASHUSD_TO_USDTERC".to_string()),
-        }
-    }
-}
-
-#[async_trait]
-impl PriceProvider for NewLineProvider {
-    fn name(&self) -> &str {
-        "NewLineProvider"
-    }
-
-    async fn fetch_price(&self, pair: &CurrencyPair) -> Result<PriceData, PriceProviderError> {
-        let symbol = self.map_currency_pair(pair).ok_or_else(|| {
+    fn symbol(&self, pair: &CurrencyPair) -> Result<String, PriceProviderError> {
+        self.map_currency_pair(pair).ok_or_else(|| {
             PriceProviderError::Provider(format!(
                 "Currency pair {} not supported by this provider",
                 pair
             ))
-        })?;
+        })
+    }
 
+    /// Request the direction list with the prices of every city
+    async fn fetch_city_data(&self) -> Result<Vec<NewLineCityData>, PriceProviderError> {
         let url = format!("{}/api/direction/", self.config.base_url);
-        log::debug!("NewLineProvider: Fetching price for pair: {}", pair);
-        log::debug!("NewLineProvider: Mapped to symbol: {}", symbol);
-        log::debug!("NewLineProvider: Request URL: {}", url);
+        tracing::debug!("NewLineProvider: Request URL: {}", url);
+        let cookie = self.cookie.read().unwrap().clone();
 
-        let response = self
-            .client
+        let request = self
+            .http
+            .client()
             .get(&url)
             .header("Accept", "application/json")
-            .header("Cookie", &self.config.cookie)
-            .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
-            .send()
-            .await
-            .map_err(|e| PriceProviderError::Network(e.to_string()))?;
-
-        let status = response.status();
-        log::debug!("NewLineProvider: Response status: {}", status);
-
-        let response_text = response
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unable to read response body".to_string());
-        log::debug!("NewLineProvider: Response body: {}", response_text);
+            .header("Cookie", cookie)
+            .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36");
+        let (status, response_text) = self.http.get_text(request).await?;
+        tracing::debug!("NewLineProvider: Response status: {}", status);
+        tracing::debug!("NewLineProvider: Response body: {}", response_text);
 
         if !status.is_success() {
-            log::error!(
+            tracing::error!(
                 "NewLineProvider: API request failed with status: {}, response: {}",
                 status,
                 response_text
@@ -182,19 +244,70 @@ impl PriceProvider for NewLineProvider {
             )));
         }
 
-        let city_data_list: Vec<NewLineCityData> = serde_json::from_str(&response_text)
-            .map_err(|e| PriceProviderError::Parsing(e.to_string()))?;
+        self.parse_city_data(&response_text)
+    }
+
+    /// Price of `pair` in the preferred city of `city_data_list`
+    fn price_in(
+        &self,
+        city_data_list: &[NewLineCityData],
+        pair: &CurrencyPair,
+    ) -> Result<PriceData, PriceProviderError> {
+        let symbol = self.symbol(pair)?;
+        match self.find_price_in_city_data(city_data_list, &symbol) {
+            Some(to_data) => Ok(self.extract_price_data(&to_data, pair)),
+            None => Err(PriceProviderError::Provider(format!(
+                "Symbol {} not found in API response for pair {}",
+                symbol, pair
+            ))),
+        }
+    }
 
-        // Find the requested symbol in the preferred city
-        if let Some(to_data) = self.find_price_in_city_data(&city_data_list, &symbol) {
-            let price_data = self.extract_price_data(&to_data, pair);
-            return Ok(price_data);
+    /// Map domain currency pair to NewLine provider symbol (private method)
+    ///
+    /// Note: Both USDCe2RUB and USDTe2RUB map to the same USDTERC_TO_CASHRUB symbol
+    /// since the NewLine API doesn't distinguish between different ERC20 stablecoins.
+    /// This is a provider limitation, not a bug in the mapping logic.
+    fn map_currency_pair(&self, pair: &CurrencyPair) -> Option<String> {
+        match pair {
+            CurrencyPair::USDCe2RUB => Some("USDTERC_TO_CASHRUB".to_string()),
+            CurrencyPair::USDTe2RUB => Some("USDTERC_TO_CASHRUB".to_string()),
+            CurrencyPair::USD2RUB => Some("CASHUSD_TO_USDTERC".to_string()),
+            CurrencyPair::USDT2USD | CurrencyPair::EUR2USD => None,
         }
+    }
+}
+
+#[async_trait]
+impl PriceProvider for NewLineProvider {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
 
-        Err(PriceProviderError::Provider(format!(
-            "Symbol {} not found in API response for pair {}",
-            symbol, pair
-        )))
+    async fn fetch_price(&self, pair: &CurrencyPair) -> Result<PriceData, PriceProviderError> {
+        let symbol = self.symbol(pair)?;
+        tracing::debug!("NewLineProvider: Fetching price for pair: {}", pair);
+        tracing::debug!("NewLineProvider: Mapped to symbol: {}", symbol);
+        let city_data_list = self.fetch_city_data().await?;
+        self.price_in(&city_data_list, pair)
+    }
+
+    /// Every pair is read from the one direction list NewLine answers with
+    async fn fetch_prices(
+        &self,
+        pairs: &[CurrencyPair],
+    ) -> Vec<Result<PriceData, PriceProviderError>> {
+        if pairs.is_empty() {
+            return Vec::new();
+        }
+        tracing::debug!("NewLineProvider: Fetching prices for {} pairs", pairs.len());
+        match self.fetch_city_data().await {
+            Ok(city_data_list) => pairs
+                .iter()
+                .map(|pair| self.price_in(&city_data_list, pair))
+                .collect(),
+            Err(e) => pairs.iter().map(|_| Err(e.clone())).collect(),
+        }
     }
 
     /// Check if this provider supports the given currency pair
@@ -205,4 +318,98 @@ impl PriceProvider for NewLineProvider {
             false
         }
     }
+
+    fn schema_drift(&self) -> u64 {
+        self.drift.count()
+    }
+
+    fn set_credential(&self, credential: &str) -> bool {
+        *self.cookie.write().unwrap() = credential.to_string();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_currency_pair_mapping() {
+        let config = NewLineConfig {
+            base_url: "https://test.com".to_string(),
+            cookie: "test_cookie".to_string(),
+            preferred_city: "spb".to_string(),
+        };
+
+        let provider = NewLineProvider::new(config, Client::new());
+
+        // Test mapping indirectly through supports_currency_pair
+        assert!(provider.supports_currency_pair(&CurrencyPair::USDCe2RUB));
+        assert!(provider.supports_currency_pair(&CurrencyPair::USDTe2RUB));
+        assert!(provider.supports_currency_pair(&CurrencyPair::USD2RUB));
+    }
+
+    #[test]
+    fn test_currency_pair_support() {
+        let config = NewLineConfig {
+            base_url: "https://test.com".to_string(),
+            cookie: "test_cookie".to_string(),
+            preferred_city: "spb".to_string(),
+        };
+
+        let provider = NewLineProvider::new(config, Client::new());
+
+        assert!(provider.supports_currency_pair(&CurrencyPair::USDCe2RUB));
+        assert!(provider.supports_currency_pair(&CurrencyPair::USDTe2RUB));
+        assert!(provider.supports_currency_pair(&CurrencyPair::USD2RUB));
+        assert!(!provider.supports_currency_pair(&CurrencyPair::EUR2USD));
+    }
+
+    #[test]
+    fn test_parse_city_data_tolerates_schema_drift() {
+        let provider = NewLineProvider::new(
+            NewLineConfig {
+                base_url: "https://test.com".to_string(),
+                cookie: "test_cookie".to_string(),
+                preferred_city: "spb".to_string(),
+            },
+            Client::new(),
+        );
+        let price = |cities: &[NewLineCityData]| {
+            provider
+                .find_price_in_city_data(cities, "USDTERC_TO_CASHRUB")
+                .map(|to_data| to_data.course_to / to_data.course_from)
+        };
+
+        // Extra fields are read past without drift
+        let cities = provider
+            .parse_city_data(
+                r#"[{"city_code": "spb", "region": "nw", "data": [{"from_": "USDTERC",
+                    "to_data": [{"to": "CASHRUB", "course_from": 1, "course_to": 92.5, "min": 100}]}]}]"#,
+            )
+            .unwrap();
+        assert_eq!(price(&cities), Some(92.5));
+        assert_eq!(provider.schema_drift(), 0);
+
+        // A renamed field, a number as a string and an incomplete rate
+        let cities = provider
+            .parse_city_data(
+                r#"{"data": [{"city": "spb", "data": [{"from_": "USDTERC", "to_data": [
+                    {"to": "CASHUSD", "course_from": 1},
+                    {"to": "CASHRUB", "course_from": "1", "course_to": "93,1"}]}]}]}"#,
+            )
+            .unwrap();
+        assert_eq!(price(&cities), Some(93.1));
+        assert_eq!(cities[0].data[0].to_data.len(), 1);
+        assert_eq!(provider.schema_drift(), 1);
+
+        assert!(matches!(
+            provider.parse_city_data(r#"{"error": "maintenance"}"#),
+            Err(PriceProviderError::Parsing(_))
+        ));
+        assert!(matches!(
+            provider.parse_city_data("<html>"),
+            Err(PriceProviderError::Parsing(_))
+        ));
+    }
 }
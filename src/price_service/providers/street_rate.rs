@@ -0,0 +1,93 @@
+use crate::domain::{CurrencyPair, PriceData, PriceProviderError};
+
+/// Mean of `prices` without the `trim_percent` lowest and highest ones,
+/// `None` without any price
+pub fn trimmed_mean(prices: &mut [f64], trim_percent: u8) -> Option<f64> {
+    if prices.is_empty() {
+        return None;
+    }
+    prices.sort_by(f64::total_cmp);
+    let trim = prices.len() * usize::from(trim_percent.min(49)) / 100;
+    let kept = &prices[trim..prices.len() - trim];
+    Some(kept.iter().sum::<f64>() / kept.len() as f64)
+}
+
+/// Virtual provider blending the cash exchangers' quotes of a ruble pair
+/// into one "street rate", the trimmed mean of the quotes of every source
+/// that answers, so a single exchanger's outlier doesn't move it. Set with
+/// `PriceService::set_street_rate`, which asks the enabled providers of a
+/// pair for the blend before any of them alone, as long as there are two.
+#[derive(Debug, Clone, Copy)]
+pub struct StreetRate {
+    trim_percent: u8,
+}
+
+impl StreetRate {
+    /// Name the blend is recorded as the source of a quote and toggled by
+    pub const NAME: &'static str = "StreetRate";
+
+    pub fn new(trim_percent: u8) -> Self {
+        Self { trim_percent }
+    }
+
+    /// Street rate of `pair` from the quotes of the sources that answered,
+    /// `errors` naming the ones that didn't
+    pub fn blend(
+        &self,
+        pair: &CurrencyPair,
+        mut prices: Vec<f64>,
+        errors: Vec<String>,
+    ) -> Result<PriceData, PriceProviderError> {
+        tracing::debug!(
+            "Street rate of {} from {} quotes, {} failed",
+            pair,
+            prices.len(),
+            errors.len()
+        );
+        let price = trimmed_mean(&mut prices, self.trim_percent).ok_or_else(|| {
+            PriceProviderError::Provider(format!(
+                "No cash quote of {} for the street rate: {}",
+                pair,
+                errors.join("; ")
+            ))
+        })?;
+        Ok(PriceData {
+            pair: pair.clone(),
+            price,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trimmed_mean() {
+        assert_eq!(trimmed_mean(&mut [], 20), None);
+        assert_eq!(trimmed_mean(&mut [92.0], 20), Some(92.0));
+        // One of five is dropped at each end
+        assert_eq!(
+            trimmed_mean(&mut [95.0, 91.0, 80.0, 92.0, 93.0], 20),
+            Some(92.0)
+        );
+        assert_eq!(trimmed_mean(&mut [90.0, 94.0], 0), Some(92.0));
+    }
+
+    #[test]
+    fn test_blend_names_the_failed_sources() {
+        let street_rate = StreetRate::new(20);
+        let price = street_rate
+            .blend(&CurrencyPair::USD2RUB, vec![93.0, 91.0], Vec::new())
+            .unwrap();
+        assert_eq!(price.price, 92.0);
+        let error = street_rate
+            .blend(
+                &CurrencyPair::USD2RUB,
+                Vec::new(),
+                vec!["C: Network error: timeout".to_string()],
+            )
+            .unwrap_err();
+        assert!(error.to_string().contains("C: Network error: timeout"));
+    }
+}
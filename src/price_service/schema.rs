@@ -0,0 +1,223 @@
+//! Tolerant reading of provider responses: a response is parsed into a
+//! [`serde_json::Value`] and the fields are picked out one by one, accepting
+//! renamed fields, numbers sent as strings and fields nobody asked for.
+//! Every deviation from the format the parser was written for is counted as
+//! schema drift and logged once, so an upstream change shows up in `/stats`
+//! while the prices keep coming. Extra fields never break parsing and aren't
+//! drift, providers add them all the time; they are logged once at debug
+//! level to tell what a new field might replace.
+
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Schema drift seen in the responses of one provider
+#[derive(Debug)]
+pub struct SchemaDrift {
+    provider: &'static str,
+    /// Responses with at least one deviation
+    responses: AtomicU64,
+    /// Deviations and unknown fields already logged
+    logged: Mutex<HashSet<String>>,
+}
+
+impl SchemaDrift {
+    pub fn new(provider: &'static str) -> Self {
+        Self {
+            provider,
+            responses: AtomicU64::new(0),
+            logged: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Responses that deviated from the expected format since startup
+    pub fn count(&self) -> u64 {
+        self.responses.load(Ordering::Relaxed)
+    }
+
+    /// Count a response with the deviations an [`Extractor`] collected,
+    /// logging each deviation and unknown field the first time it is seen
+    fn record(&self, extractor: Extractor) {
+        if !extractor.deviations.is_empty() {
+            self.responses.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut logged = self.logged.lock().unwrap();
+        for deviation in extractor.deviations {
+            if !logged.contains(&deviation) {
+                tracing::warn!("{} schema drift: {}", self.provider, deviation);
+                logged.insert(deviation);
+            }
+        }
+        for field in extractor.unknown {
+            if !logged.contains(&field) {
+                tracing::debug!("{} response has an unknown field {}", self.provider, field);
+                logged.insert(field);
+            }
+        }
+    }
+}
+
+/// Reads the fields of one response, collecting the deviations for
+/// [`Self::finish`]. `what` names the object in the deviations, like
+/// `city` or `to_data`.
+#[derive(Debug, Default)]
+pub struct Extractor {
+    deviations: Vec<String>,
+    /// Fields the parser doesn't read, like `city.region`
+    unknown: Vec<String>,
+}
+
+impl Extractor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note a deviation the caller found itself
+    pub fn deviation(&mut self, deviation: String) {
+        if !self.deviations.contains(&deviation) {
+            self.deviations.push(deviation);
+        }
+    }
+
+    /// Value of the first of `names` present in `object`: the field the
+    /// parser was written for, then the names it might be renamed to
+    pub fn field<'a>(
+        &mut self,
+        what: &str,
+        object: &'a Map<String, Value>,
+        names: &[&str],
+    ) -> Option<&'a Value> {
+        let (index, value) = names
+            .iter()
+            .enumerate()
+            .find_map(|(index, name)| object.get(*name).map(|value| (index, value)))
+            .or_else(|| {
+                self.deviation(format!("{} without {}", what, names[0]));
+                None
+            })?;
+        if index > 0 {
+            self.deviation(format!("{}.{} renamed to {}", what, names[0], names[index]));
+        }
+        Some(value)
+    }
+
+    /// String field, see [`Self::field`]
+    pub fn string<'a>(
+        &mut self,
+        what: &str,
+        object: &'a Map<String, Value>,
+        names: &[&str],
+    ) -> Option<&'a str> {
+        let value = self.field(what, object, names)?;
+        let string = value.as_str();
+        if string.is_none() {
+            self.deviation(format!("{}.{} is not a string", what, names[0]));
+        }
+        string
+    }
+
+    /// Number field, also accepted as a numeric string, see [`Self::field`]
+    pub fn number(
+        &mut self,
+        what: &str,
+        object: &Map<String, Value>,
+        names: &[&str],
+    ) -> Option<f64> {
+        let value = self.field(what, object, names)?;
+        if let Some(number) = value.as_f64() {
+            return Some(number);
+        }
+        let number = value
+            .as_str()
+            .and_then(|string| string.trim().replace(',', ".").parse().ok());
+        match number {
+            Some(_) => self.deviation(format!("{}.{} sent as a string", what, names[0])),
+            None => self.deviation(format!("{}.{} is not a number", what, names[0])),
+        }
+        number
+    }
+
+    /// Array field, see [`Self::field`]
+    pub fn array<'a>(
+        &mut self,
+        what: &str,
+        object: &'a Map<String, Value>,
+        names: &[&str],
+    ) -> Option<&'a [Value]> {
+        let value = self.field(what, object, names)?;
+        let array = value.as_array().map(Vec::as_slice);
+        if array.is_none() {
+            self.deviation(format!("{}.{} is not an array", what, names[0]));
+        }
+        array
+    }
+
+    /// Objects of `values`, noting the ones that aren't
+    pub fn objects<'a>(
+        &mut self,
+        what: &str,
+        values: &'a [Value],
+    ) -> impl Iterator<Item = &'a Map<String, Value>> {
+        if values.iter().any(|value| !value.is_object()) {
+            self.deviation(format!("{} is not an object", what));
+        }
+        values.iter().filter_map(Value::as_object)
+    }
+
+    /// Note the fields of `object` the parser doesn't read
+    pub fn unknown_fields(&mut self, what: &str, object: &Map<String, Value>, known: &[&str]) {
+        for name in object.keys() {
+            let field = format!("{}.{}", what, name);
+            if !known.contains(&name.as_str()) && !self.unknown.contains(&field) {
+                self.unknown.push(field);
+            }
+        }
+    }
+
+    /// Record the deviations of the response in `drift`
+    pub fn finish(self, drift: &SchemaDrift) {
+        drift.record(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_extractor_tolerates_drift() {
+        let drift = SchemaDrift::new("Test");
+        let object = json!({ "price": 92.5, "amount": "1,5", "code": "spb" });
+        let object = object.as_object().unwrap();
+
+        // Extra fields are no drift
+        let mut extractor = Extractor::new();
+        assert_eq!(extractor.number("rate", object, &["price"]), Some(92.5));
+        assert_eq!(extractor.string("rate", object, &["code"]), Some("spb"));
+        extractor.unknown_fields("rate", object, &["price", "code"]);
+        assert_eq!(extractor.unknown, ["rate.amount"]);
+        extractor.finish(&drift);
+        assert_eq!(drift.count(), 0);
+
+        let mut extractor = Extractor::new();
+        assert_eq!(
+            extractor.number("rate", object, &["volume", "amount"]),
+            Some(1.5)
+        );
+        assert_eq!(extractor.number("rate", object, &["code"]), None);
+        assert_eq!(extractor.array("rate", object, &["items"]), None);
+        assert_eq!(
+            extractor.deviations,
+            [
+                "rate.volume renamed to amount",
+                "rate.volume sent as a string",
+                "rate.code is not a number",
+                "rate without items",
+            ]
+        );
+        extractor.finish(&drift);
+        assert_eq!(drift.count(), 1);
+    }
+}
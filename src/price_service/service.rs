@@ -1,10 +1,101 @@
-use crate::domain::{CurrencyPair, PriceData, PriceProviderError};
+use crate::domain::{
+    get_all_currency_pairs, get_reference_pairs, CurrencyPair, PriceData, PriceProviderError,
+};
+use crate::price_service::history::{PriceChange, PriceHistory};
 use crate::price_service::provider::PriceProvider;
-use std::sync::Arc;
+use crate::price_service::providers::StreetRate;
+use crate::reporting::ErrorReporter;
+use chrono::Local;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tracing::Instrument;
+
+/// A provider failing this many requests in a row is considered down
+pub const PROVIDER_DOWN_AFTER_ERRORS: u64 = 3;
+
+/// Fresh quotes buffered for a slow [`PriceService::subscribe`] receiver
+/// before it starts missing them
+const UPDATES_CAPACITY: usize = 64;
+
+/// Window the requests are counted in against a provider's rate limit
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Requests sent to a provider within the last [`RATE_LIMIT_WINDOW`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RequestVolume {
+    pub last_hour: u32,
+    pub limit_per_hour: u32,
+}
+
+/// Times of the latest requests of a provider with a rate limit
+#[derive(Debug)]
+struct RequestWindow {
+    limit_per_hour: u32,
+    requests: VecDeque<Instant>,
+}
+
+impl RequestWindow {
+    fn prune(&mut self, now: Instant) {
+        while self
+            .requests
+            .front()
+            .is_some_and(|&time| now.duration_since(time) >= RATE_LIMIT_WINDOW)
+        {
+            self.requests.pop_front();
+        }
+    }
+}
+
+/// Result of a [`PriceService::get_price`] in flight, handed to the callers
+/// that asked for the same pair while it was requested
+type Flight = broadcast::Sender<Result<PriceData, PriceProviderError>>;
+
+/// Ends the flight of a pair when its request is done or cancelled, a
+/// cancelled one closing the flight without a result
+struct FlightGuard<'a> {
+    inflight: &'a Mutex<HashMap<CurrencyPair, Flight>>,
+    pair: &'a CurrencyPair,
+}
+
+impl Drop for FlightGuard<'_> {
+    fn drop(&mut self) {
+        self.inflight.lock().unwrap().remove(self.pair);
+    }
+}
+
+/// Requests made to a provider since startup and how many of them failed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ProviderStats {
+    pub requests: u64,
+    pub errors: u64,
+    /// Failed requests since the last successful one
+    pub consecutive_errors: u64,
+    /// Of those, requests whose credentials were rejected
+    pub consecutive_auth_failures: u64,
+    /// Responses read despite deviating from the expected format
+    pub schema_drift: u64,
+}
 
 /// Main price service that manages multiple providers
 pub struct PriceService {
     providers: Vec<Arc<dyn PriceProvider>>,
+    history: PriceHistory,
+    stats: Mutex<HashMap<String, ProviderStats>>,
+    /// Providers skipped by `get_price`, by name
+    disabled: Mutex<HashSet<String>>,
+    /// Provider of the latest quote of each pair
+    sources: Mutex<HashMap<CurrencyPair, String>>,
+    reporter: ErrorReporter,
+    updates: broadcast::Sender<CurrencyPair>,
+    /// Pairs being requested from the providers right now
+    inflight: Mutex<HashMap<CurrencyPair, Flight>>,
+    /// Recent requests of the providers with a rate limit, by name
+    windows: Mutex<HashMap<String, RequestWindow>>,
+    /// Blend of the providers' quotes asked before any of them alone
+    street_rate: Option<StreetRate>,
 }
 
 impl PriceService {
@@ -12,34 +103,988 @@ impl PriceService {
     pub fn new() -> Self {
         Self {
             providers: Vec::new(),
+            history: PriceHistory::default(),
+            stats: Mutex::new(HashMap::new()),
+            disabled: Mutex::new(HashSet::new()),
+            sources: Mutex::new(HashMap::new()),
+            reporter: ErrorReporter::default(),
+            updates: broadcast::channel(UPDATES_CAPACITY).0,
+            inflight: Mutex::new(HashMap::new()),
+            windows: Mutex::new(HashMap::new()),
+            street_rate: None,
         }
     }
 
+    /// Request and error counters of every provider used so far, by name
+    pub fn provider_stats(&self) -> Vec<(String, ProviderStats)> {
+        let mut stats: Vec<_> = self
+            .stats
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, stats)| {
+                let schema_drift = self
+                    .providers
+                    .iter()
+                    .filter(|provider| provider.name() == name)
+                    .map(|provider| provider.schema_drift())
+                    .sum();
+                let stats = ProviderStats {
+                    schema_drift,
+                    ..*stats
+                };
+                (name.clone(), stats)
+            })
+            .collect();
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+        stats
+    }
+
+    /// Report providers going down to `reporter`
+    pub fn set_error_reporter(&mut self, reporter: ErrorReporter) {
+        self.reporter = reporter;
+    }
+
+    /// Count the requests of the provider named `name` against the
+    /// `limit_per_hour` its API allows, see [`Self::request_volume`]
+    pub fn set_rate_limit(&self, name: &str, limit_per_hour: u32) {
+        self.windows.lock().unwrap().insert(
+            name.to_string(),
+            RequestWindow {
+                limit_per_hour,
+                requests: VecDeque::new(),
+            },
+        );
+    }
+
+    /// Requests of the last hour of every provider with a rate limit, by name
+    pub fn request_volume(&self) -> Vec<(String, RequestVolume)> {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+        let mut volume: Vec<_> = windows
+            .iter_mut()
+            .map(|(name, window)| {
+                window.prune(now);
+                let volume = RequestVolume {
+                    last_hour: window.requests.len() as u32,
+                    limit_per_hour: window.limit_per_hour,
+                };
+                (name.clone(), volume)
+            })
+            .collect();
+        volume.sort_by(|a, b| a.0.cmp(&b.0));
+        volume
+    }
+
+    /// Count a request of `provider` against its rate limit
+    fn count_request(&self, provider: &str) {
+        if let Some(window) = self.windows.lock().unwrap().get_mut(provider) {
+            let now = Instant::now();
+            window.prune(now);
+            window.requests.push_back(now);
+        }
+    }
+
+    fn record_request(&self, provider: &str, error: Option<&PriceProviderError>) {
+        self.count_request(provider);
+        let mut stats = self.stats.lock().unwrap();
+        let stats = stats.entry(provider.to_string()).or_default();
+        stats.requests += 1;
+        let Some(error) = error else {
+            stats.consecutive_errors = 0;
+            stats.consecutive_auth_failures = 0;
+            return;
+        };
+        stats.errors += 1;
+        stats.consecutive_errors += 1;
+        // Rejected credentials don't get better by retrying, reported at the
+        // first rejection even when the provider was already failing
+        if error.is_auth_failure() {
+            stats.consecutive_auth_failures += 1;
+            if stats.consecutive_auth_failures == 1 {
+                self.reporter.report(
+                    &format!("credentials {}", provider),
+                    &format!("Credentials rejected, renew them: {}", error),
+                );
+            }
+        }
+        // Reported once when the provider goes down, not for every failure after
+        if stats.consecutive_errors == PROVIDER_DOWN_AFTER_ERRORS {
+            let message = format!(
+                "{} failed requests in a row, last error: {}",
+                PROVIDER_DOWN_AFTER_ERRORS, error
+            );
+            self.reporter
+                .report(&format!("provider {}", provider), &message);
+        }
+    }
+
+    /// Prices fetched so far, used for charts
+    pub fn history(&self) -> &PriceHistory {
+        &self.history
+    }
+
+    /// Replace the history, e.g. with other retention or analytics windows,
+    /// before any price is recorded
+    pub fn set_history(&mut self, history: PriceHistory) {
+        self.history = history;
+    }
+
     /// Add a price provider to the service
     pub fn add_provider(&mut self, provider: Arc<dyn PriceProvider>) {
-        log::info!("Added price provider: {}", provider.name());
+        tracing::info!("Added price provider: {}", provider.name());
         self.providers.push(provider);
     }
 
-    /// Get price from the first available provider that supports the currency pair
+    /// Answer the ruble pairs with the street rate of the providers, see
+    /// [`Self::street_rate_sources`]
+    pub fn set_street_rate(&mut self, street_rate: StreetRate) {
+        tracing::info!("Added price provider: {}", StreetRate::NAME);
+        self.street_rate = Some(street_rate);
+    }
+
+    /// Turn the provider named `name` off or back on without restarting
+    pub fn set_provider_enabled(&self, name: &str, enabled: bool) {
+        let mut disabled = self.disabled.lock().unwrap();
+        let changed = match enabled {
+            true => disabled.remove(name),
+            false => disabled.insert(name.to_string()),
+        };
+        if changed {
+            tracing::info!(
+                "Price provider {} {}",
+                name,
+                if enabled { "enabled" } else { "disabled" }
+            );
+        }
+    }
+
+    fn is_enabled(&self, provider: &dyn PriceProvider) -> bool {
+        !self.disabled.lock().unwrap().contains(provider.name())
+    }
+
+    /// Indexes of the enabled providers blended into the street rate of
+    /// `pair`, none when it isn't set or switched off, for pairs without a
+    /// ruble side and for a single source, whose quote the blend would only
+    /// repeat
+    fn street_rate_sources(&self, pair: &CurrencyPair) -> Vec<usize> {
+        let blends = self.street_rate.is_some()
+            && !self.disabled.lock().unwrap().contains(StreetRate::NAME)
+            && !get_reference_pairs().contains(pair);
+        if !blends {
+            return Vec::new();
+        }
+        let sources: Vec<usize> = (0..self.providers.len())
+            .filter(|&index| {
+                let provider = self.providers[index].as_ref();
+                provider.supports_currency_pair(pair) && self.is_enabled(provider)
+            })
+            .collect();
+        match sources.len() {
+            0 | 1 => Vec::new(),
+            _ => sources,
+        }
+    }
+
+    /// Whether an enabled provider quotes `pair`
+    pub fn supports(&self, pair: &CurrencyPair) -> bool {
+        self.providers.iter().any(|provider| {
+            provider.supports_currency_pair(pair) && self.is_enabled(provider.as_ref())
+        })
+    }
+
+    /// Request a supported pair from every enabled provider, outside of the
+    /// history and stats but counted against the rate limits, and return the
+    /// providers that failed
+    pub async fn check_providers(&self) -> Vec<(String, PriceProviderError)> {
+        let mut failed = Vec::new();
+        for provider in &self.providers {
+            if !self.is_enabled(provider.as_ref()) {
+                continue;
+            }
+            if let Some(Err(e)) = self.check(provider.as_ref()).await {
+                failed.push((provider.name().to_string(), e));
+            }
+        }
+        failed
+    }
+
+    /// Check the provider named `name` like [`Self::check_providers`], even
+    /// if it is disabled. `None` without such a provider or a pair it quotes.
+    pub async fn check_provider(&self, name: &str) -> Option<Result<(), PriceProviderError>> {
+        let provider = self
+            .providers
+            .iter()
+            .find(|provider| provider.name() == name)?;
+        self.check(provider.as_ref()).await
+    }
+
+    async fn check(&self, provider: &dyn PriceProvider) -> Option<Result<(), PriceProviderError>> {
+        let pairs = get_all_currency_pairs();
+        let pair = pairs
+            .iter()
+            .find(|pair| provider.supports_currency_pair(pair))?;
+        self.count_request(provider.name());
+        Some(provider.fetch_price(pair).await.map(|_| ()))
+    }
+
+    /// Hand the provider named `name` a new credential without a restart,
+    /// false if there is no such provider or it doesn't take one
+    pub fn set_credential(&self, name: &str, credential: &str) -> bool {
+        // Every provider of the name, not just the first taking it
+        let updated = self
+            .providers
+            .iter()
+            .filter(|provider| provider.name() == name)
+            .filter(|provider| provider.set_credential(credential))
+            .count()
+            > 0;
+        if updated {
+            tracing::info!("Replaced the credential of price provider {}", name);
+        }
+        updated
+    }
+
+    /// Change of the latest recorded price of `pair` against the quote
+    /// recorded before it
+    pub fn last_change(&self, pair: &CurrencyPair) -> Option<PriceChange> {
+        let previous = self.history.previous(pair)?;
+        let latest = self.history.latest(pair)?;
+        PriceChange::between(previous.price, latest.price)
+    }
+
+    /// Name of the provider that served the latest quote of `pair`
+    pub fn source(&self, pair: &CurrencyPair) -> Option<String> {
+        self.sources.lock().unwrap().get(pair).cloned()
+    }
+
+    /// Pairs with a fresh quote in the history, as soon as it is recorded
+    pub fn subscribe(&self) -> broadcast::Receiver<CurrencyPair> {
+        self.updates.subscribe()
+    }
+
+    /// Get price from the first available provider that supports the currency
+    /// pair and record it in the history. Callers asking for a pair while it
+    /// is being requested wait for that request instead of sending their own.
     pub async fn get_price(&self, pair: &CurrencyPair) -> Result<PriceData, PriceProviderError> {
+        let follower = {
+            let mut inflight = self.inflight.lock().unwrap();
+            match inflight.get(pair) {
+                Some(flight) => Some(flight.subscribe()),
+                None => {
+                    inflight.insert(pair.clone(), broadcast::channel(1).0);
+                    None
+                }
+            }
+        };
+        if let Some(mut flight) = follower {
+            return match flight.recv().await {
+                Ok(result) => result,
+                // The request was cancelled, nobody else is sending one
+                Err(_) => Box::pin(self.get_price(pair)).await,
+            };
+        }
+
+        let guard = FlightGuard {
+            inflight: &self.inflight,
+            pair,
+        };
+        let result = self.fetch_price(pair).await;
+        let flight = self.inflight.lock().unwrap().get(pair).cloned();
+        drop(guard);
+        if let Some(flight) = flight {
+            // Nobody waiting is not an error
+            let _ = flight.send(result.clone());
+        }
+        result
+    }
+
+    async fn fetch_price(&self, pair: &CurrencyPair) -> Result<PriceData, PriceProviderError> {
+        // Every source was just asked, a failed blend isn't asked again alone
+        if let Some(result) = self.fetch_street_rate(pair).await {
+            return result;
+        }
         let mut errors = Vec::new();
 
         for provider in &self.providers {
-            if provider.supports_currency_pair(pair) {
-                match provider.fetch_price(pair).await {
-                    Ok(price) => return Ok(price),
+            if provider.supports_currency_pair(pair) && self.is_enabled(provider.as_ref()) {
+                let span = tracing::info_span!("provider_request", provider = provider.name(), pair = %pair);
+                let result = provider.fetch_price(pair).instrument(span).await;
+                self.record_request(provider.name(), result.as_ref().err());
+                match result {
+                    Ok(price) => {
+                        self.record_price(provider.name(), pair, &price);
+                        return Ok(price);
+                    }
                     Err(e) => {
-                        log::warn!("Provider {} failed for {}: {}", provider.name(), pair, e);
+                        tracing::warn!("Provider {} failed for {}: {}", provider.name(), pair, e);
                         errors.push(e);
                     }
                 }
             }
         }
 
-        Err(PriceProviderError::Provider(format!(
+        Err(Self::all_failed(pair, errors))
+    }
+
+    /// Street rate of `pair` from its [`Self::street_rate_sources`] asked at
+    /// the same time, `None` when it isn't blended
+    async fn fetch_street_rate(
+        &self,
+        pair: &CurrencyPair,
+    ) -> Option<Result<PriceData, PriceProviderError>> {
+        let street_rate = self.street_rate.as_ref()?;
+        let sources = self.street_rate_sources(pair);
+        if sources.is_empty() {
+            return None;
+        }
+        let requests = sources.iter().map(|&index| {
+            let provider = self.providers[index].as_ref();
+            let span =
+                tracing::info_span!("provider_request", provider = provider.name(), pair = %pair);
+            async move { (provider, provider.fetch_price(pair).instrument(span).await) }
+        });
+        let mut prices = Vec::new();
+        let mut errors = Vec::new();
+        for (provider, result) in futures::future::join_all(requests).await {
+            self.record_request(provider.name(), result.as_ref().err());
+            match result {
+                Ok(price) => prices.push(price.price),
+                Err(e) => errors.push(format!("{}: {}", provider.name(), e)),
+            }
+        }
+        let result = street_rate.blend(pair, prices, errors);
+        self.record_request(StreetRate::NAME, result.as_ref().err());
+        match &result {
+            Ok(price) => self.record_price(StreetRate::NAME, pair, price),
+            Err(e) => tracing::warn!("Provider {} failed for {}: {}", StreetRate::NAME, pair, e),
+        }
+        Some(result)
+    }
+
+    /// Street rates of `pairs` in their order like [`Self::fetch_street_rate`],
+    /// every source asked once for all the pairs it is blended into
+    async fn fetch_street_rates(
+        &self,
+        pairs: &[CurrencyPair],
+    ) -> Vec<Option<Result<PriceData, PriceProviderError>>> {
+        let Some(street_rate) = self.street_rate.as_ref() else {
+            return vec![None; pairs.len()];
+        };
+        let sources: Vec<Vec<usize>> = pairs
+            .iter()
+            .map(|pair| self.street_rate_sources(pair))
+            .collect();
+        let requests = (0..self.providers.len()).filter_map(|index| {
+            let batch: Vec<CurrencyPair> = pairs
+                .iter()
+                .zip(&sources)
+                .filter(|(_, sources)| sources.contains(&index))
+                .map(|(pair, _)| pair.clone())
+                .collect();
+            if batch.is_empty() {
+                return None;
+            }
+            let provider = self.providers[index].as_ref();
+            let span = tracing::info_span!(
+                "provider_request",
+                provider = provider.name(),
+                pairs = batch.len()
+            );
+            Some(async move {
+                let results = provider.fetch_prices(&batch).instrument(span).await;
+                (provider, batch, results)
+            })
+        });
+        let answers = futures::future::join_all(requests).await;
+        for (provider, _, results) in &answers {
+            // One request as far as the stats go, failed when no pair came back
+            let failed = match results.iter().any(Result::is_ok) {
+                true => None,
+                false => results.iter().find_map(|result| result.as_ref().err()),
+            };
+            self.record_request(provider.name(), failed);
+        }
+        let blends: Vec<_> = pairs
+            .iter()
+            .zip(&sources)
+            .map(|(pair, sources)| {
+                if sources.is_empty() {
+                    return None;
+                }
+                let mut prices = Vec::new();
+                let mut errors = Vec::new();
+                for (provider, batch, results) in &answers {
+                    let index = batch.iter().position(|asked| asked == pair);
+                    let Some(result) = index.and_then(|index| results.get(index)) else {
+                        continue;
+                    };
+                    match result {
+                        Ok(price) => prices.push(price.price),
+                        Err(e) => errors.push(format!("{}: {}", provider.name(), e)),
+                    }
+                }
+                let result = street_rate.blend(pair, prices, errors);
+                match &result {
+                    Ok(price) => self.record_price(StreetRate::NAME, pair, price),
+                    Err(e) => {
+                        tracing::warn!("Provider {} failed for {}: {}", StreetRate::NAME, pair, e)
+                    }
+                }
+                Some(result)
+            })
+            .collect();
+        if blends.iter().any(Option::is_some) {
+            let failed = match blends.iter().flatten().any(Result::is_ok) {
+                true => None,
+                false => blends
+                    .iter()
+                    .flatten()
+                    .find_map(|result| result.as_ref().err()),
+            };
+            self.record_request(StreetRate::NAME, failed);
+        }
+        blends
+    }
+
+    /// Prices of `pairs` in their order, like [`Self::get_price`] for each
+    /// of them, but every provider is asked once for all the pairs it quotes
+    /// that no provider before it answered, in one request where its API
+    /// allows. Pairs being requested are waited for like in
+    /// [`Self::get_price`], and callers asking for a pair of the batch wait
+    /// for the batch.
+    pub async fn get_prices(
+        &self,
+        pairs: &[CurrencyPair],
+    ) -> Vec<Result<PriceData, PriceProviderError>> {
+        let mut followers = Vec::new();
+        let mut leading = Vec::new();
+        {
+            let mut inflight = self.inflight.lock().unwrap();
+            for (index, pair) in pairs.iter().enumerate() {
+                match inflight.get(pair) {
+                    Some(flight) => followers.push((index, flight.subscribe())),
+                    None => {
+                        inflight.insert(pair.clone(), broadcast::channel(1).0);
+                        leading.push(index);
+                    }
+                }
+            }
+        }
+
+        let mut results: Vec<Option<Result<PriceData, PriceProviderError>>> =
+            vec![None; pairs.len()];
+        let guards: Vec<FlightGuard> = leading
+            .iter()
+            .map(|&index| FlightGuard {
+                inflight: &self.inflight,
+                pair: &pairs[index],
+            })
+            .collect();
+        let batch: Vec<CurrencyPair> = leading.iter().map(|&index| pairs[index].clone()).collect();
+        let fetched = self.fetch_prices(&batch).await;
+        for ((guard, index), result) in guards.into_iter().zip(leading).zip(fetched) {
+            let flight = self.inflight.lock().unwrap().get(guard.pair).cloned();
+            drop(guard);
+            if let Some(flight) = flight {
+                // Nobody waiting is not an error
+                let _ = flight.send(result.clone());
+            }
+            results[index] = Some(result);
+        }
+        for (index, mut flight) in followers {
+            let result = match flight.recv().await {
+                Ok(result) => result,
+                // The request was cancelled, nobody else is sending one
+                Err(_) => self.get_price(&pairs[index]).await,
+            };
+            results[index] = Some(result);
+        }
+        results.into_iter().flatten().collect()
+    }
+
+    /// Prices of `pairs` in their order, every provider asked once for the
+    /// pairs no provider before it answered, after the street rates
+    async fn fetch_prices(
+        &self,
+        pairs: &[CurrencyPair],
+    ) -> Vec<Result<PriceData, PriceProviderError>> {
+        let blends = self.fetch_street_rates(pairs).await;
+        let mut prices: Vec<Option<PriceData>> = vec![None; pairs.len()];
+        let mut errors: Vec<Vec<PriceProviderError>> = vec![Vec::new(); pairs.len()];
+
+        for provider in &self.providers {
+            if !self.is_enabled(provider.as_ref()) {
+                continue;
+            }
+            let indexes: Vec<usize> = (0..pairs.len())
+                .filter(|&index| {
+                    blends[index].is_none()
+                        && prices[index].is_none()
+                        && provider.supports_currency_pair(&pairs[index])
+                })
+                .collect();
+            if indexes.is_empty() {
+                continue;
+            }
+            let batch: Vec<CurrencyPair> =
+                indexes.iter().map(|&index| pairs[index].clone()).collect();
+            let span = tracing::info_span!(
+                "provider_request",
+                provider = provider.name(),
+                pairs = batch.len()
+            );
+            let results = provider.fetch_prices(&batch).instrument(span).await;
+            // One request as far as the stats go, failed when no pair came back
+            let failed = match results.iter().any(Result::is_ok) {
+                true => None,
+                false => results.iter().find_map(|result| result.as_ref().err()),
+            };
+            self.record_request(provider.name(), failed);
+            for (index, result) in indexes.into_iter().zip(results) {
+                let pair = &pairs[index];
+                match result {
+                    Ok(price) => {
+                        self.record_price(provider.name(), pair, &price);
+                        prices[index] = Some(price);
+                    }
+                    Err(e) => {
+                        tracing::warn!("Provider {} failed for {}: {}", provider.name(), pair, e);
+                        errors[index].push(e);
+                    }
+                }
+            }
+        }
+
+        pairs
+            .iter()
+            .zip(blends)
+            .zip(prices.into_iter().zip(errors))
+            .map(|((pair, blend), (price, errors))| {
+                blend.unwrap_or_else(|| price.ok_or_else(|| Self::all_failed(pair, errors)))
+            })
+            .collect()
+    }
+
+    /// Record a fresh quote of `pair` served by `provider`
+    fn record_price(&self, provider: &str, pair: &CurrencyPair, price: &PriceData) {
+        self.history.record(pair, price.price, Local::now());
+        self.sources
+            .lock()
+            .unwrap()
+            .insert(pair.clone(), provider.to_string());
+        // Nobody listening is not an error
+        let _ = self.updates.send(pair.clone());
+    }
+
+    fn all_failed(pair: &CurrencyPair, errors: Vec<PriceProviderError>) -> PriceProviderError {
+        PriceProviderError::Provider(format!(
             "All providers failed to fetch price for {}: {:?}",
             pair, errors
-        )))
+        ))
+    }
+}
+
+impl Default for PriceService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_service::providers::fixed::FixedProvider;
+    use async_trait::async_trait;
+
+    #[tokio::test]
+    async fn test_disabled_providers_are_skipped() {
+        let mut service = PriceService::new();
+        service.add_provider(Arc::new(FixedProvider::new(90.0)));
+        assert_eq!(service.source(&CurrencyPair::USD2RUB), None);
+        assert!(service.get_price(&CurrencyPair::USD2RUB).await.is_ok());
+        assert_eq!(
+            service.source(&CurrencyPair::USD2RUB).as_deref(),
+            Some("Fixed")
+        );
+
+        service.set_provider_enabled("Fixed", false);
+        assert!(!service.supports(&CurrencyPair::USD2RUB));
+        assert!(service.get_price(&CurrencyPair::USD2RUB).await.is_err());
+        service.set_provider_enabled("Fixed", true);
+        assert!(service.get_price(&CurrencyPair::USD2RUB).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_get_fresh_quotes() {
+        let mut service = PriceService::new();
+        service.add_provider(Arc::new(FixedProvider::new(90.0)));
+        let mut updates = service.subscribe();
+        service.get_price(&CurrencyPair::USDTe2RUB).await.unwrap();
+        assert_eq!(updates.recv().await.unwrap(), CurrencyPair::USDTe2RUB);
+
+        service.set_provider_enabled("Fixed", false);
+        assert!(service.get_price(&CurrencyPair::USD2RUB).await.is_err());
+        assert!(updates.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_from_failing_provider() {
+        use crate::price_service::providers::{FaultInjectingProvider, Faults};
+
+        let faults = Faults {
+            error_percent: 100,
+            ..Faults::default()
+        };
+        let mut service = PriceService::new();
+        service.add_provider(Arc::new(FaultInjectingProvider::new(
+            FixedProvider::new(90.0),
+            faults,
+        )));
+        service.add_provider(Arc::new(FixedProvider::named("Backup", 91.0)));
+        for _ in 0..PROVIDER_DOWN_AFTER_ERRORS {
+            let price = service.get_price(&CurrencyPair::USD2RUB).await.unwrap();
+            assert_eq!(price.price, 91.0);
+        }
+        let stats = service.provider_stats();
+        assert_eq!(stats[0].0, "Backup");
+        assert_eq!(stats[1].0, "Fixed");
+        assert_eq!(stats[1].1.consecutive_errors, PROVIDER_DOWN_AFTER_ERRORS);
+    }
+
+    /// Answers after 50ms, counting its requests
+    #[derive(Default)]
+    struct SlowProvider {
+        requests: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl PriceProvider for SlowProvider {
+        fn name(&self) -> &str {
+            "Slow"
+        }
+
+        async fn fetch_price(&self, pair: &CurrencyPair) -> Result<PriceData, PriceProviderError> {
+            self.requests
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            Ok(PriceData {
+                pair: pair.clone(),
+                price: 90.0,
+            })
+        }
+
+        fn supports_currency_pair(&self, _pair: &CurrencyPair) -> bool {
+            true
+        }
+    }
+
+    // The clock only moves once every task waits, so the sleeps order the
+    // requests the same way on every run
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrent_requests_share_one_fetch() {
+        use std::sync::atomic::Ordering;
+        use std::time::Duration;
+
+        let provider = Arc::new(SlowProvider::default());
+        let mut service = PriceService::new();
+        service.add_provider(provider.clone());
+        let service = Arc::new(service);
+        let requests = (0..10).map(|_| service.get_price(&CurrencyPair::USD2RUB));
+        let prices = futures::future::join_all(requests).await;
+        assert!(prices
+            .iter()
+            .all(|price| price.as_ref().unwrap().price == 90.0));
+        assert_eq!(provider.requests.load(Ordering::SeqCst), 1);
+        assert_eq!(service.provider_stats()[0].1.requests, 1);
+
+        // A cancelled request hands the flight over to the callers waiting
+        let leader = tokio::spawn({
+            let service = service.clone();
+            async move { service.get_price(&CurrencyPair::USD2RUB).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let follower = tokio::spawn({
+            let service = service.clone();
+            async move { service.get_price(&CurrencyPair::USD2RUB).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        leader.abort();
+        assert_eq!(follower.await.unwrap().unwrap().price, 90.0);
+        assert_eq!(provider.requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_batches_share_requests_in_flight() {
+        use std::sync::atomic::Ordering;
+        use tokio::time::{Duration, Instant};
+
+        let provider = Arc::new(SlowProvider::default());
+        let mut service = PriceService::new();
+        service.add_provider(provider.clone());
+        let pairs = get_all_currency_pairs();
+
+        // The pairs of a batch are fetched at the same time by default
+        let started = Instant::now();
+        let prices = service.get_prices(&pairs).await;
+        assert!(prices.iter().all(Result::is_ok));
+        assert_eq!(started.elapsed(), Duration::from_millis(50));
+        assert_eq!(provider.requests.load(Ordering::SeqCst), pairs.len());
+
+        // A batch waits for the pair already requested, the request for a
+        // pair of the batch waits for the batch
+        let (single, batch, late) = tokio::join!(
+            service.get_price(&CurrencyPair::USD2RUB),
+            service.get_prices(&pairs),
+            async {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                service.get_price(&CurrencyPair::USDTe2RUB).await
+            },
+        );
+        assert_eq!(single.unwrap().price, 90.0);
+        assert!(batch.iter().all(Result::is_ok));
+        assert_eq!(late.unwrap().price, 90.0);
+        assert_eq!(provider.requests.load(Ordering::SeqCst), 2 * pairs.len());
+    }
+
+    #[tokio::test]
+    async fn test_batches_go_to_one_provider_each() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Quotes the ruble pairs, counting its batches
+        #[derive(Default)]
+        struct BatchProvider {
+            batches: AtomicUsize,
+        }
+
+        #[async_trait]
+        impl PriceProvider for BatchProvider {
+            fn name(&self) -> &str {
+                "Batch"
+            }
+
+            async fn fetch_price(
+                &self,
+                pair: &CurrencyPair,
+            ) -> Result<PriceData, PriceProviderError> {
+                Ok(PriceData {
+                    pair: pair.clone(),
+                    price: 92.0,
+                })
+            }
+
+            async fn fetch_prices(
+                &self,
+                pairs: &[CurrencyPair],
+            ) -> Vec<Result<PriceData, PriceProviderError>> {
+                self.batches.fetch_add(1, Ordering::SeqCst);
+                let mut prices = Vec::new();
+                for pair in pairs {
+                    prices.push(self.fetch_price(pair).await);
+                }
+                prices
+            }
+
+            fn supports_currency_pair(&self, pair: &CurrencyPair) -> bool {
+                pair.to_string().ends_with("/RUB")
+            }
+        }
+
+        let batch = Arc::new(BatchProvider::default());
+        let mut service = PriceService::new();
+        service.add_provider(batch.clone());
+        service.add_provider(Arc::new(FixedProvider::new(90.0)));
+        let prices = service
+            .get_prices(&[
+                CurrencyPair::USD2RUB,
+                CurrencyPair::EUR2USD,
+                CurrencyPair::USDTe2RUB,
+            ])
+            .await;
+        let prices: Vec<f64> = prices
+            .into_iter()
+            .map(|price| price.unwrap().price)
+            .collect();
+        assert_eq!(prices, [92.0, 90.0, 92.0]);
+        assert_eq!(batch.batches.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            service.source(&CurrencyPair::EUR2USD).as_deref(),
+            Some("Fixed")
+        );
+        assert_eq!(
+            service.source(&CurrencyPair::USD2RUB).as_deref(),
+            Some("Batch")
+        );
+        let stats = service.provider_stats();
+        assert_eq!((stats[0].1.requests, stats[1].1.requests), (1, 1));
+
+        service.set_provider_enabled("Batch", false);
+        service.set_provider_enabled("Fixed", false);
+        let prices = service.get_prices(&[CurrencyPair::USD2RUB]).await;
+        assert!(prices[0].is_err());
+    }
+
+    #[tokio::test]
+    async fn test_street_rate_blends_enabled_sources() {
+        let mut service = PriceService::new();
+        service.add_provider(Arc::new(FixedProvider::named("A", 91.0)));
+        service.add_provider(Arc::new(FixedProvider::named("B", 93.0)));
+        service.add_provider(Arc::new(FixedProvider::failing("C")));
+        service.set_street_rate(StreetRate::new(20));
+        service.set_rate_limit("A", 600);
+        let requests = |service: &PriceService| -> Vec<(String, u64)> {
+            let stats = service.provider_stats().into_iter();
+            stats.map(|(name, stats)| (name, stats.requests)).collect()
+        };
+
+        let price = service.get_price(&CurrencyPair::USD2RUB).await.unwrap();
+        assert_eq!(price.price, 92.0);
+        assert_eq!(
+            service.source(&CurrencyPair::USD2RUB).as_deref(),
+            Some(StreetRate::NAME)
+        );
+        // Every source is asked once and counted
+        let once = |name: &str| (name.to_string(), 1);
+        assert_eq!(
+            requests(&service),
+            [once("A"), once("B"), once("C"), once(StreetRate::NAME)]
+        );
+
+        // Pairs without a ruble side aren't blended
+        service.get_price(&CurrencyPair::EUR2USD).await.unwrap();
+        assert_eq!(service.source(&CurrencyPair::EUR2USD).as_deref(), Some("A"));
+
+        // A disabled source is left out of the blend
+        service.set_provider_enabled("B", false);
+        let price = service.get_price(&CurrencyPair::USD2RUB).await.unwrap();
+        assert_eq!(price.price, 91.0);
+
+        // Nor is there a blend of one source, it answers alone
+        service.set_provider_enabled("C", false);
+        service.get_price(&CurrencyPair::USD2RUB).await.unwrap();
+        assert_eq!(service.source(&CurrencyPair::USD2RUB).as_deref(), Some("A"));
+        assert_eq!(requests(&service)[0], ("A".to_string(), 4));
+        assert_eq!(service.request_volume()[0].1.last_hour, 4);
+    }
+
+    #[tokio::test]
+    async fn test_failed_street_rate_isnt_asked_again() {
+        let mut service = PriceService::new();
+        service.add_provider(Arc::new(FixedProvider::failing("A")));
+        service.add_provider(Arc::new(FixedProvider::failing("B")));
+        service.set_street_rate(StreetRate::new(20));
+
+        let error = service.get_price(&CurrencyPair::USD2RUB).await.unwrap_err();
+        assert!(error.to_string().contains("A: Network error: timeout"));
+        let prices = service
+            .get_prices(&[CurrencyPair::USD2RUB, CurrencyPair::USDTe2RUB])
+            .await;
+        assert!(prices.iter().all(Result::is_err));
+        // One request for the price and one batch for the prices
+        let stats = service.provider_stats();
+        assert_eq!((stats[0].1.requests, stats[1].1.requests), (2, 2));
+    }
+
+    #[tokio::test]
+    async fn test_street_rate_blends_batches() {
+        let mut service = PriceService::new();
+        service.add_provider(Arc::new(FixedProvider::named("A", 91.0)));
+        service.add_provider(Arc::new(FixedProvider::named("B", 93.0)));
+        service.set_street_rate(StreetRate::new(0));
+
+        let prices = service
+            .get_prices(&[
+                CurrencyPair::USD2RUB,
+                CurrencyPair::EUR2USD,
+                CurrencyPair::USDTe2RUB,
+            ])
+            .await;
+        let prices: Vec<f64> = prices
+            .into_iter()
+            .map(|price| price.unwrap().price)
+            .collect();
+        assert_eq!(prices, [92.0, 91.0, 92.0]);
+        // A is asked for the blended pairs and then for EUR/USD on its own
+        let stats = service.provider_stats();
+        assert_eq!((stats[0].1.requests, stats[1].1.requests), (2, 1));
+    }
+
+    #[tokio::test]
+    async fn test_request_volume_of_rate_limited_providers() {
+        let mut service = PriceService::new();
+        service.add_provider(Arc::new(FixedProvider::new(90.0)));
+        assert!(service.request_volume().is_empty());
+        service.set_rate_limit("Fixed", 600);
+        for _ in 0..3 {
+            service.get_price(&CurrencyPair::USD2RUB).await.unwrap();
+        }
+        service
+            .get_prices(&[CurrencyPair::USD2RUB, CurrencyPair::EUR2USD])
+            .await;
+        // The probes count too, they are requests all the same
+        assert!(service.check_providers().await.is_empty());
+        assert!(matches!(
+            service.check_provider("Fixed").await,
+            Some(Ok(()))
+        ));
+        assert_eq!(
+            service.request_volume(),
+            [(
+                "Fixed".to_string(),
+                RequestVolume {
+                    last_hour: 6,
+                    limit_per_hour: 600,
+                }
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejected_credentials_are_reported_at_once() {
+        struct ExpiredProvider;
+
+        #[async_trait]
+        impl PriceProvider for ExpiredProvider {
+            fn name(&self) -> &str {
+                "Expired"
+            }
+
+            async fn fetch_price(
+                &self,
+                _pair: &CurrencyPair,
+            ) -> Result<PriceData, PriceProviderError> {
+                Err(PriceProviderError::Api(
+                    "API request failed with status: 403 Forbidden".to_string(),
+                ))
+            }
+
+            fn supports_currency_pair(&self, _pair: &CurrencyPair) -> bool {
+                true
+            }
+        }
+
+        let (reporter, mut reports) = ErrorReporter::new(std::time::Duration::from_secs(60));
+        let mut service = PriceService::new();
+        service.set_error_reporter(reporter);
+        service.add_provider(Arc::new(ExpiredProvider));
+        assert!(service.get_price(&CurrencyPair::USD2RUB).await.is_err());
+        let report = reports.try_recv().unwrap();
+        assert_eq!(report.source, "credentials Expired");
+        assert!(report.message.contains("403"));
+        assert!(reports.try_recv().is_err());
+
+        // Credentials expiring while the provider times out
+        let (reporter, mut reports) = ErrorReporter::new(std::time::Duration::from_secs(60));
+        let mut service = PriceService::new();
+        service.set_error_reporter(reporter);
+        let timeout = PriceProviderError::Network("timed out".to_string());
+        let rejected = PriceProviderError::Api("status: 401 Unauthorized".to_string());
+        service.record_request("Flaky", Some(&timeout));
+        service.record_request("Flaky", Some(&rejected));
+        assert_eq!(reports.try_recv().unwrap().source, "credentials Flaky");
+        // Down now, the credentials aren't reported again
+        service.record_request("Flaky", Some(&rejected));
+        assert_eq!(reports.try_recv().unwrap().source, "provider Flaky");
+        assert!(reports.try_recv().is_err());
     }
 }
@@ -0,0 +1,343 @@
+// Error reporting module
+// Forwards failures nobody sees in the logs to the operator's chat and,
+// with the `sentry` feature, to Sentry
+
+use crate::bot_modules::outbound::{OutboundQueue, Priority};
+use crate::bot_modules::scheduler::JobHealth;
+use crate::bot_modules::TelegramBot;
+use crate::i18n::{tr_args, Language, Languages};
+use crate::price_service::service::RequestVolume;
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use teloxide::prelude::*;
+use tokio::sync::mpsc;
+
+/// Reports of the same source within this period are counted instead of sent
+pub const DEFAULT_REPORT_COOLDOWN: Duration = Duration::from_secs(10 * 60);
+
+/// Failure forwarded to the operator
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorReport {
+    /// What failed, e.g. `module Price` or `job price_poller`
+    pub source: String,
+    pub message: String,
+    /// Reports of the same source dropped by the rate limit since the last one
+    pub suppressed: u64,
+}
+
+impl ErrorReport {
+    fn text(&self, language: Language) -> String {
+        let mut text = tr_args(
+            language,
+            "reports.error",
+            &[("source", &self.source), ("message", &self.message)],
+        );
+        if self.suppressed > 0 {
+            text.push_str(&tr_args(
+                language,
+                "reports.suppressed",
+                &[("count", &self.suppressed)],
+            ));
+        }
+        text
+    }
+}
+
+#[derive(Debug)]
+struct SourceWindow {
+    last_sent: Instant,
+    suppressed: u64,
+}
+
+/// Handle for reporting errors from anywhere in the bot, cheap to clone.
+/// Reports are queued for [`deliver_reports`] and rate limited per source,
+/// so a failing provider doesn't flood the admin chat.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorReporter {
+    sender: Option<mpsc::UnboundedSender<ErrorReport>>,
+    windows: Arc<Mutex<HashMap<String, SourceWindow>>>,
+    cooldown: Duration,
+}
+
+impl ErrorReporter {
+    /// Create a reporter and the receiving end to pass to [`deliver_reports`]
+    pub fn new(cooldown: Duration) -> (Self, mpsc::UnboundedReceiver<ErrorReport>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let reporter = Self {
+            sender: Some(sender),
+            windows: Arc::new(Mutex::new(HashMap::new())),
+            cooldown,
+        };
+        (reporter, receiver)
+    }
+
+    /// Queue a report unless `source` was reported within the cooldown.
+    /// Does nothing for a reporter created with `default()`.
+    pub fn report(&self, source: &str, error: &dyn Display) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let now = Instant::now();
+        let suppressed = {
+            let mut windows = self.windows.lock().unwrap();
+            match windows.get_mut(source) {
+                Some(window) if now.duration_since(window.last_sent) < self.cooldown => {
+                    window.suppressed += 1;
+                    return;
+                }
+                Some(window) => {
+                    window.last_sent = now;
+                    std::mem::take(&mut window.suppressed)
+                }
+                None => {
+                    windows.insert(
+                        source.to_string(),
+                        SourceWindow {
+                            last_sent: now,
+                            suppressed: 0,
+                        },
+                    );
+                    0
+                }
+            }
+        };
+        let report = ErrorReport {
+            source: source.to_string(),
+            message: error.to_string(),
+            suppressed,
+        };
+        if sender.send(report).is_err() {
+            tracing::warn!("Error report from {} dropped, delivery stopped", source);
+        }
+    }
+}
+
+/// Reminder for the admins that `credential` expires on `expires`, sent
+/// every day from `remind_days` before it on; `None` before that
+pub fn credential_reminder(
+    language: Language,
+    credential: &str,
+    expires: NaiveDate,
+    today: NaiveDate,
+    remind_days: u32,
+) -> Option<String> {
+    let days_left = (expires - today).num_days();
+    match days_left {
+        days if days > i64::from(remind_days) => None,
+        0.. => Some(tr_args(
+            language,
+            "credentials.expires",
+            &[
+                ("credential", &credential),
+                ("expires", &expires),
+                ("days", &days_left),
+            ],
+        )),
+        _ => Some(tr_args(
+            language,
+            "credentials.expired",
+            &[("credential", &credential), ("expires", &expires)],
+        )),
+    }
+}
+
+/// Notice for the admins that the watchdog restarted `job`, `None` for a
+/// healthy one
+pub fn watchdog_notice(language: Language, job: &str, health: &JobHealth) -> Option<String> {
+    match health {
+        JobHealth::Stalled => Some(tr_args(language, "watchdog.stalled", &[("job", &job)])),
+        JobHealth::Died(message) => Some(tr_args(
+            language,
+            "watchdog.died",
+            &[("job", &job), ("error", message)],
+        )),
+        JobHealth::Healthy => None,
+    }
+}
+
+/// Share of a provider's hourly rate limit at which the admins are warned
+pub const RATE_LIMIT_WARN_PERCENT: u32 = 80;
+
+/// Warning for the admins in `language` that `provider` sent
+/// `volume.last_hour` requests, at least [`RATE_LIMIT_WARN_PERCENT`] of what
+/// it allows per hour, with the setting that lowers it and its current value
+/// (`None` for a poller on cron); `None` below that
+pub fn rate_limit_warning(
+    language: Language,
+    provider: &str,
+    volume: RequestVolume,
+    poll_interval_minutes: Option<u64>,
+) -> Option<String> {
+    let percent = u64::from(volume.last_hour) * 100 / u64::from(volume.limit_per_hour.max(1));
+    if percent < u64::from(RATE_LIMIT_WARN_PERCENT) {
+        return None;
+    }
+    let interval = poll_interval_minutes
+        .map(|minutes| tr_args(language, "rate_limit.interval", &[("minutes", &minutes)]))
+        .unwrap_or_default();
+    Some(tr_args(
+        language,
+        "rate_limit.warning",
+        &[
+            ("provider", &provider),
+            ("requests", &volume.last_hour),
+            ("limit", &volume.limit_per_hour),
+            ("percent", &percent),
+            ("interval", &interval),
+        ],
+    ))
+}
+
+/// Send queued reports to `chat` through `outbound`, in its language of
+/// `languages` (and to Sentry when enabled) until every reporter is dropped
+pub async fn deliver_reports(
+    mut receiver: mpsc::UnboundedReceiver<ErrorReport>,
+    bot: TelegramBot,
+    outbound: OutboundQueue,
+    chat: Option<ChatId>,
+    languages: Languages,
+) {
+    while let Some(report) = receiver.recv().await {
+        #[cfg(feature = "sentry")]
+        sentry::capture_message(
+            &format!("{}: {}", report.source, report.message),
+            sentry::Level::Error,
+        );
+        if let Some(chat) = chat {
+            let text = report.text(languages.get(chat));
+            let sent = outbound
+                .send(Priority::Broadcast, chat, || {
+                    bot.send_message(chat, &text).send()
+                })
+                .await;
+            if let Err(e) = sent {
+                tracing::warn!("Failed to deliver error report to {}: {}", chat, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credential_reminder() {
+        let expires = NaiveDate::from_ymd_opt(2026, 12, 31).unwrap();
+        let day = |day| NaiveDate::from_ymd_opt(2026, 12, day).unwrap();
+        assert_eq!(
+            credential_reminder(Language::Ru, "NewLine cookie", expires, day(23), 7),
+            None
+        );
+        assert_eq!(
+            credential_reminder(Language::Ru, "NewLine cookie", expires, day(24), 7).unwrap(),
+            "🔑 NewLine cookie истекает 2026-12-31 (дней осталось: 7), обновите его"
+        );
+        assert!(
+            credential_reminder(Language::Ru, "NewLine cookie", expires, day(31), 7)
+                .unwrap()
+                .contains("дней осталось: 0")
+        );
+        let expired =
+            credential_reminder(Language::Ru, "NewLine cookie", day(30), day(31), 7).unwrap();
+        assert!(expired.contains("истёк 2026-12-30"));
+        assert_eq!(
+            credential_reminder(Language::En, "NewLine cookie", day(30), day(31), 7).unwrap(),
+            "🔑 NewLine cookie expired on 2026-12-30, the provider will reject its requests"
+        );
+    }
+
+    #[test]
+    fn test_watchdog_notice() {
+        assert_eq!(
+            watchdog_notice(Language::Ru, "price_poller", &JobHealth::Healthy),
+            None
+        );
+        assert_eq!(
+            watchdog_notice(Language::Ru, "price_poller", &JobHealth::Stalled).unwrap(),
+            "⚠️ Задача price_poller зависла и перезапущена"
+        );
+        assert_eq!(
+            watchdog_notice(
+                Language::En,
+                "price_poller",
+                &JobHealth::Died("overflow".to_string())
+            )
+            .unwrap(),
+            "⚠️ Job price_poller died and was restarted: overflow"
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_warning() {
+        let volume = |last_hour| RequestVolume {
+            last_hour,
+            limit_per_hour: 600,
+        };
+        let warning = |language, last_hour, interval| {
+            rate_limit_warning(language, "NewLineProvider", volume(last_hour), interval)
+        };
+        assert_eq!(warning(Language::Ru, 479, Some(5)), None);
+        assert_eq!(
+            warning(Language::Ru, 480, Some(5)).unwrap(),
+            "📈 NewLineProvider: 480 запросов за час из 600 допустимых (80%). Увеличьте PRICE_POLL_INTERVAL_MINUTES (сейчас 5 мин), пока провайдер не заблокировал бота"
+        );
+        assert_eq!(
+            warning(Language::En, 540, Some(5)).unwrap(),
+            "📈 NewLineProvider: 540 requests in the last hour of 600 allowed (90%). Raise PRICE_POLL_INTERVAL_MINUTES (now 5 min) before the provider blocks the bot"
+        );
+        // A poller on cron has no interval to show
+        assert_eq!(
+            warning(Language::En, 480, None).unwrap(),
+            "📈 NewLineProvider: 480 requests in the last hour of 600 allowed (80%). Raise PRICE_POLL_INTERVAL_MINUTES before the provider blocks the bot"
+        );
+    }
+
+    #[test]
+    fn test_reports_are_rate_limited_per_source() {
+        let (reporter, mut receiver) = ErrorReporter::new(Duration::from_secs(60));
+        reporter.report("provider NewLine", &"timeout");
+        reporter.report("provider NewLine", &"timeout");
+        reporter.report("job price_poller", &"panicked");
+
+        let first = receiver.try_recv().unwrap();
+        assert_eq!(first.source, "provider NewLine");
+        assert_eq!(first.suppressed, 0);
+        assert_eq!(receiver.try_recv().unwrap().source, "job price_poller");
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_suppressed_reports_are_counted() {
+        let (reporter, mut receiver) = ErrorReporter::new(Duration::from_secs(60));
+        reporter.report("module Price", &"first");
+        reporter.report("module Price", &"second");
+        reporter.report("module Price", &"third");
+        receiver.try_recv().unwrap();
+        // Let the next report through as if the cooldown had passed
+        reporter
+            .windows
+            .lock()
+            .unwrap()
+            .get_mut("module Price")
+            .unwrap()
+            .last_sent -= Duration::from_secs(61);
+        reporter.report("module Price", &"fourth");
+        let report = receiver.try_recv().unwrap();
+        assert_eq!(report.message, "fourth");
+        assert_eq!(report.suppressed, 2);
+        assert!(report.text(Language::Ru).contains("ещё 2 похожих"));
+        assert_eq!(
+            report.text(Language::En),
+            "⚠️ Error: module Price\nfourth\n(2 similar ones skipped recently)"
+        );
+    }
+
+    #[test]
+    fn test_default_reporter_is_disabled() {
+        ErrorReporter::default().report("module Price", &"ignored");
+    }
+}
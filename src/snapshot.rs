@@ -0,0 +1,173 @@
+// Runtime state snapshot
+// Keeps what the bot only holds in memory across a graceful restart: the
+// price history, the quotes behind the next deltas, dead letters, the channel
+// posts to edit, the command audit log and the countdowns of the jobs.
+// Subscriptions with their message counters and anchor messages have their
+// own state file.
+
+use crate::bot_modules::audit::AuditEntry;
+use crate::bot_modules::channels::ChannelPostRecord;
+use crate::bot_modules::subscribers::DeliveryState;
+use crate::domain::CurrencyPair;
+use crate::price_service::history::{CandleSeries, PricePoint};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Instant;
+
+/// Version of the runtime snapshot format
+pub const RUNTIME_SNAPSHOT_VERSION: u32 = 1;
+
+/// Runtime state written on shutdown and restored on start (`SNAPSHOT_FILE`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuntimeSnapshot {
+    pub version: u32,
+    pub saved_at: DateTime<Utc>,
+    /// Price samples by pair, oldest first
+    #[serde(default)]
+    pub prices: HashMap<CurrencyPair, Vec<PricePoint>>,
+    /// Hourly and daily candles by pair, the samples compacted so far
+    #[serde(default)]
+    pub candles: HashMap<CurrencyPair, CandleSeries>,
+    /// Delivery state of every bot by its id
+    #[serde(default)]
+    pub bots: HashMap<String, DeliveryState>,
+    #[serde(default)]
+    pub channel_posts: Vec<ChannelPostRecord>,
+    /// Commands of the audit log of every bot by its id
+    #[serde(default)]
+    pub audit: HashMap<String, Vec<AuditEntry>>,
+    /// Next run of every job by its name
+    #[serde(default)]
+    pub next_runs: HashMap<String, DateTime<Utc>>,
+}
+
+impl RuntimeSnapshot {
+    /// Read a snapshot written by [`Self::save`], `None` when there is none yet
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents =
+            std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let snapshot: Self = serde_json::from_slice(&contents)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+        if snapshot.version != RUNTIME_SNAPSHOT_VERSION {
+            return Err(format!(
+                "Unsupported runtime snapshot version {} (expected {})",
+                snapshot.version, RUNTIME_SNAPSHOT_VERSION
+            ));
+        }
+        Ok(Some(snapshot))
+    }
+
+    /// Write the snapshot to `path`, replacing the previous one
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_vec(self)?;
+        // Write to a temporary file first so a crash never leaves a truncated snapshot
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, json)?;
+        std::fs::rename(&tmp_path, path)
+    }
+}
+
+/// Snapshot without any state, saved now
+impl Default for RuntimeSnapshot {
+    fn default() -> Self {
+        Self {
+            version: RUNTIME_SNAPSHOT_VERSION,
+            saved_at: Utc::now(),
+            prices: HashMap::new(),
+            candles: HashMap::new(),
+            bots: HashMap::new(),
+            channel_posts: Vec::new(),
+            audit: HashMap::new(),
+            next_runs: HashMap::new(),
+        }
+    }
+}
+
+/// Instants are process-local, the snapshot stores them as wall-clock time
+pub fn wall_clock(instant: Instant) -> DateTime<Utc> {
+    let remaining = instant.saturating_duration_since(Instant::now());
+    Utc::now() + chrono::Duration::from_std(remaining).unwrap_or_default()
+}
+
+/// Instant of a wall-clock time, a time that already passed is now
+pub fn instant(time: DateTime<Utc>) -> Instant {
+    Instant::now() + (time - Utc::now()).to_std().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_service::history::{Candle, Ohlc};
+    use chrono::Local;
+    use std::time::Duration;
+
+    #[test]
+    fn test_save_and_load() {
+        let path =
+            std::env::temp_dir().join(format!("currency-bot-snapshot-{}.json", std::process::id()));
+        assert!(matches!(RuntimeSnapshot::load(&path), Ok(None)));
+
+        let point = PricePoint {
+            time: Local::now(),
+            price: 90.0,
+        };
+        let candle = Candle {
+            time: point.time,
+            ohlc: Ohlc {
+                open: 90.0,
+                high: 92.0,
+                low: 89.0,
+                close: 91.0,
+            },
+            samples: 12,
+        };
+        let candles = CandleSeries {
+            hourly: Vec::new(),
+            daily: vec![candle],
+        };
+        let next_run = Utc::now() + chrono::Duration::minutes(5);
+        let snapshot = RuntimeSnapshot {
+            version: RUNTIME_SNAPSHOT_VERSION,
+            saved_at: Utc::now(),
+            prices: HashMap::from([(CurrencyPair::USD2RUB, vec![point])]),
+            candles: HashMap::from([(CurrencyPair::USD2RUB, candles.clone())]),
+            bots: HashMap::from([("main".to_string(), DeliveryState::default())]),
+            channel_posts: Vec::new(),
+            audit: HashMap::new(),
+            next_runs: HashMap::from([("channel_publish".to_string(), next_run)]),
+        };
+        snapshot.save(&path).unwrap();
+
+        let loaded = RuntimeSnapshot::load(&path).unwrap().unwrap();
+        assert_eq!(loaded.prices[&CurrencyPair::USD2RUB], vec![point]);
+        assert_eq!(loaded.candles[&CurrencyPair::USD2RUB], candles);
+        assert!(loaded.bots.contains_key("main"));
+        assert_eq!(loaded.next_runs["channel_publish"], next_run);
+
+        std::fs::write(
+            &path,
+            r#"{"version": 2, "saved_at": "2024-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+        assert!(RuntimeSnapshot::load(&path)
+            .unwrap_err()
+            .contains("Unsupported runtime snapshot version 2"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_wall_clock_round_trip() {
+        let later = Instant::now() + Duration::from_secs(300);
+        let restored = instant(wall_clock(later));
+        assert!(restored.duration_since(later) < Duration::from_secs(1));
+        assert!(later.duration_since(restored) < Duration::from_secs(1));
+
+        let passed = Utc::now() - chrono::Duration::minutes(1);
+        assert!(instant(passed) <= Instant::now());
+    }
+}
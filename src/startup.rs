@@ -0,0 +1,95 @@
+// Startup checks
+// Verifies what the config alone can't before the bot starts serving: the
+// tokens are accepted, the providers accept their credentials and the state
+// files can be written
+
+use crate::config::{Config, MAIN_BOT_ID};
+use crate::domain::PriceProviderError;
+use crate::price_service::PriceService;
+use std::path::Path;
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::RequestError;
+use thiserror::Error;
+
+const TELEGRAM_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Problems found by [`check`], all listed at once
+#[derive(Debug, Error)]
+#[error("Startup checks failed:\n  - {}", .0.join("\n  - "))]
+pub struct StartupError(pub Vec<String>);
+
+/// Check the Telegram tokens, provider credentials and state files of
+/// `config`. Only rejections fail the check: Telegram or a provider being
+/// unreachable is logged, the bot retries once it is running.
+pub async fn check(config: &Config, price_service: &PriceService) -> Result<(), StartupError> {
+    let mut problems = Vec::new();
+    for bot in config.bots() {
+        let setting = match bot.id.as_str() {
+            MAIN_BOT_ID => "telegram.token (TELOXIDE_TOKEN)".to_string(),
+            id => format!("token of bot '{}'", id),
+        };
+        let request = Bot::new(bot.token).get_me();
+        match tokio::time::timeout(TELEGRAM_CHECK_TIMEOUT, request).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(RequestError::Api(e))) => {
+                problems.push(format!("{} was rejected by Telegram: {}", setting, e))
+            }
+            Ok(Err(e)) => tracing::warn!("Could not check {}: {}", setting, e),
+            Err(_) => tracing::warn!("Could not check {}: Telegram timed out", setting),
+        }
+        if let Some(path) = &bot.subscriptions_file {
+            if let Err(e) = check_store(path) {
+                problems.push(format!("subscriptions file {}: {}", path.display(), e));
+            }
+        }
+    }
+    if let Some(path) = &config.snapshot.file {
+        if let Err(e) = check_store(path) {
+            problems.push(format!("snapshot file {}: {}", path.display(), e));
+        }
+    }
+    for (provider, error) in price_service.check_providers().await {
+        match error {
+            PriceProviderError::Network(e) => {
+                tracing::warn!("Could not check provider {}: {}", provider, e)
+            }
+            e => problems.push(format!("provider {} failed: {}", provider, e)),
+        }
+    }
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(StartupError(problems)),
+    }
+}
+
+/// The state file can be written when its directory exists and is writable
+pub(crate) fn check_store(path: &Path) -> Result<(), String> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let metadata =
+        std::fs::metadata(dir).map_err(|e| format!("{} is not reachable: {}", dir.display(), e))?;
+    if !metadata.is_dir() || metadata.permissions().readonly() {
+        return Err(format!("{} is not a writable directory", dir.display()));
+    }
+    if path.is_dir() {
+        return Err(format!("{} is a directory", path.display()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_store() {
+        let dir = std::env::temp_dir();
+        assert!(check_store(&dir.join("subscribers.json")).is_ok());
+        assert!(check_store(Path::new("subscribers.json")).is_ok());
+        assert!(check_store(&dir.join("missing-dir/subscribers.json")).is_err());
+        assert!(check_store(&dir).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+//! Alert webhooks: the triggered alerts of the chats in `[alert_webhooks]`
+//! are POSTed to their callback URL as JSON, signed with HMAC-SHA256 of the
+//! body in the `X-Signature-256: sha256=<hex>` header, so scripts can check
+//! the payload came from the bot. A failed delivery is logged, not retried.
+
+use crate::bot_modules::alerts::{AlertCondition, TriggeredAlert};
+use crate::config::AlertWebhooksConfig;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde::Serialize;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Header carrying the signature of the payload
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+/// Callbacks taking longer than this are given up on, unless the shared
+/// client gives up sooner
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Body POSTed for a triggered alert
+#[derive(Debug, Serialize)]
+struct AlertPayload<'a> {
+    chat_id: i64,
+    pair: String,
+    condition: &'a AlertCondition,
+    price: f64,
+    /// Whether the rule stays after firing
+    repeat: bool,
+    fired_at: DateTime<Utc>,
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `body` keyed with `secret`
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+/// Callback URLs of the chats that registered one
+pub struct AlertWebhooks {
+    client: Client,
+    secret: String,
+    urls: BTreeMap<i64, String>,
+}
+
+impl AlertWebhooks {
+    /// Webhooks of `config` POSTed with `client`, the `[http_client]` one
+    /// with its proxy and CA; `None` without callbacks
+    pub fn new(config: &AlertWebhooksConfig, client: Client) -> Option<Self> {
+        let urls = config.callbacks();
+        if urls.is_empty() {
+            return None;
+        }
+        Some(Self {
+            client,
+            secret: config.secret.clone().unwrap_or_default(),
+            urls,
+        })
+    }
+
+    /// POST the alerts of the chats with a callback, concurrently
+    pub async fn deliver(&self, alerts: &[TriggeredAlert]) {
+        let fired_at = Utc::now();
+        let deliveries = alerts.iter().filter_map(|alert| {
+            let url = self.urls.get(&alert.chat_id.0)?;
+            Some(self.post(url, alert, fired_at))
+        });
+        futures::future::join_all(deliveries).await;
+    }
+
+    async fn post(&self, url: &str, alert: &TriggeredAlert, fired_at: DateTime<Utc>) {
+        let payload = AlertPayload {
+            chat_id: alert.chat_id.0,
+            pair: alert.rule.pair.to_string(),
+            condition: &alert.rule.condition,
+            price: alert.price,
+            repeat: alert.rule.repeat,
+            fired_at,
+        };
+        let body = serde_json::to_vec(&payload).expect("alert payload serializes");
+        let sent = self
+            .client
+            .post(url)
+            .timeout(WEBHOOK_TIMEOUT)
+            .header("Content-Type", "application/json")
+            .header(SIGNATURE_HEADER, sign(&self.secret, &body))
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        match sent {
+            Ok(_) => tracing::debug!("Alert webhook of {} delivered", alert.chat_id),
+            Err(e) => tracing::warn!("Alert webhook of {} failed: {}", alert.chat_id, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot_modules::alerts::AlertRule;
+    use crate::domain::CurrencyPair;
+    use teloxide::types::ChatId;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deliver_posts_signed_payload() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/alerts"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let config = AlertWebhooksConfig {
+            secret: Some("s3cret".to_string()),
+            urls: BTreeMap::from([("42".to_string(), format!("{}/alerts", server.uri()))]),
+        };
+        let webhooks = AlertWebhooks::new(&config, Client::new()).unwrap();
+        let alert = |chat_id| TriggeredAlert {
+            chat_id: ChatId(chat_id),
+            rule: AlertRule::new(
+                CurrencyPair::USD2RUB,
+                AlertCondition::Above { price: 95.0 },
+                false,
+            ),
+            price: 95.5,
+        };
+        // Chats without a callback are skipped
+        webhooks.deliver(&[alert(42), alert(7)]).await;
+
+        let requests = server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["chat_id"], 42);
+        assert_eq!(body["pair"], "USD/RUB");
+        assert_eq!(body["condition"]["type"], "above");
+        assert_eq!(body["price"], 95.5);
+        let signature = requests[0].headers.get(SIGNATURE_HEADER).unwrap();
+        assert_eq!(
+            signature.to_str().unwrap(),
+            sign("s3cret", &requests[0].body)
+        );
+        assert!(AlertWebhooks::new(&AlertWebhooksConfig::default(), Client::new()).is_none());
+    }
+}
@@ -0,0 +1,36 @@
+//! Helpers shared by the provider contract tests
+
+use wiremock::matchers::{method, path};
+use wiremock::{Match, Mock, MockServer, ResponseTemplate};
+
+/// Recorded payload `name` of the provider fixtures in `tests/fixtures/<provider>`
+pub fn fixture(provider: &str, name: &str) -> String {
+    let path = format!(
+        "{}/tests/fixtures/{}/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        provider,
+        name
+    );
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e))
+}
+
+pub fn json(body: String) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_raw(body, "application/json")
+}
+
+/// Answer the GETs of `url_path` matching `matcher` with `response`,
+/// expecting at least one
+pub async fn mount_get(
+    server: &MockServer,
+    url_path: &str,
+    matcher: impl Match + 'static,
+    response: ResponseTemplate,
+) {
+    Mock::given(method("GET"))
+        .and(path(url_path))
+        .and(matcher)
+        .respond_with(response)
+        .expect(1..)
+        .mount(server)
+        .await;
+}
@@ -0,0 +1,99 @@
+//! Contract tests of `KrakenProvider` against recorded `/0/public/Ticker`
+//! payloads in `tests/fixtures/kraken`, served by a local mock server
+#![cfg(feature = "kraken")]
+
+mod common;
+
+use common::{json, mount_get};
+use currency_bot::domain::{CurrencyPair, PriceProviderError};
+use currency_bot::price_service::providers::{KrakenConfig, KrakenProvider};
+use currency_bot::PriceProvider;
+use wiremock::matchers::query_param;
+use wiremock::{MockServer, ResponseTemplate};
+
+/// Recorded payload `name` of `tests/fixtures/kraken`
+fn fixture(name: &str) -> String {
+    common::fixture("kraken", name)
+}
+
+/// Provider of `server`, answering the ticker request of `pair` with `response`
+async fn provider(server: &MockServer, pair: &str, response: ResponseTemplate) -> KrakenProvider {
+    mount_get(
+        server,
+        "/0/public/Ticker",
+        query_param("pair", pair),
+        response,
+    )
+    .await;
+    KrakenProvider::new(
+        KrakenConfig {
+            base_url: server.uri(),
+        },
+        reqwest::Client::new(),
+    )
+}
+
+#[tokio::test]
+async fn test_success_reads_last_trade() {
+    let server = MockServer::start().await;
+    let provider = provider(&server, "USDTUSD", json(fixture("ticker_usdt.json"))).await;
+
+    let price = provider.fetch_price(&CurrencyPair::USDT2USD).await.unwrap();
+    assert_eq!(price.pair, CurrencyPair::USDT2USD);
+    assert_eq!(price.price, 0.99985);
+}
+
+#[tokio::test]
+async fn test_unknown_pair_is_an_api_error() {
+    let server = MockServer::start().await;
+    let provider = provider(&server, "EURUSD", json(fixture("unknown_pair.json"))).await;
+
+    let error = provider
+        .fetch_price(&CurrencyPair::EUR2USD)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&error, PriceProviderError::Api(message) if message.contains("Unknown asset pair")),
+        "{:?}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn test_empty_result_is_not_found() {
+    let server = MockServer::start().await;
+    let provider = provider(&server, "EURUSD", json(fixture("empty_result.json"))).await;
+
+    let error = provider
+        .fetch_price(&CurrencyPair::EUR2USD)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&error, PriceProviderError::Provider(message) if message.contains("EURUSD")),
+        "{:?}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn test_ruble_pairs_are_not_requested() {
+    let server = MockServer::start().await;
+    let provider = KrakenProvider::new(
+        KrakenConfig {
+            base_url: server.uri(),
+        },
+        reqwest::Client::new(),
+    );
+
+    assert!(!provider.supports_currency_pair(&CurrencyPair::USD2RUB));
+    let error = provider
+        .fetch_price(&CurrencyPair::USD2RUB)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(error, PriceProviderError::Provider(_)),
+        "{:?}",
+        error
+    );
+    assert!(server.received_requests().await.unwrap().is_empty());
+}
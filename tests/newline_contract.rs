@@ -0,0 +1,190 @@
+//! Contract tests of `NewLineProvider` against recorded `/api/direction/`
+//! payloads in `tests/fixtures/newline`, served by a local mock server
+#![cfg(feature = "newline")]
+
+mod common;
+
+use common::{json, mount_get};
+use currency_bot::domain::{CurrencyPair, PriceProviderError};
+use currency_bot::price_service::providers::{NewLineConfig, NewLineProvider};
+use currency_bot::PriceProvider;
+use currency_bot::PriceService;
+use std::sync::Arc;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Recorded payload `name` of `tests/fixtures/newline`
+fn fixture(name: &str) -> String {
+    common::fixture("newline", name)
+}
+
+/// Provider for the spb city of `server`, answering the direction request with `response`
+async fn provider(server: &MockServer, response: ResponseTemplate) -> NewLineProvider {
+    mount_get(
+        server,
+        "/api/direction/",
+        header("Cookie", "session=test"),
+        response,
+    )
+    .await;
+    NewLineProvider::new(
+        NewLineConfig {
+            base_url: server.uri(),
+            cookie: "session=test".to_string(),
+            preferred_city: "spb".to_string(),
+        },
+        reqwest::Client::new(),
+    )
+}
+
+#[tokio::test]
+async fn test_success_uses_preferred_city() {
+    let server = MockServer::start().await;
+    let provider = provider(&server, json(fixture("success.json"))).await;
+
+    let price = provider
+        .fetch_price(&CurrencyPair::USDTe2RUB)
+        .await
+        .unwrap();
+    assert_eq!(price.pair, CurrencyPair::USDTe2RUB);
+    assert_eq!(price.price, 92.5);
+
+    // course_to / course_from of CASHUSD_TO_USDTERC, the Moscow rate is ignored
+    let price = provider.fetch_price(&CurrencyPair::USD2RUB).await.unwrap();
+    assert!((price.price - 1.0 / 1.02).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn test_batch_is_one_request() {
+    let server = MockServer::start().await;
+    let provider = provider(&server, json(fixture("success.json"))).await;
+    let mut price_service = PriceService::new();
+    price_service.add_provider(Arc::new(provider));
+
+    let prices = price_service
+        .get_prices(&[
+            CurrencyPair::USDTe2RUB,
+            CurrencyPair::EUR2USD,
+            CurrencyPair::USD2RUB,
+        ])
+        .await;
+    assert_eq!(prices[0].as_ref().unwrap().price, 92.5);
+    assert!(prices[1].is_err());
+    assert!((prices[2].as_ref().unwrap().price - 1.0 / 1.02).abs() < 1e-9);
+    assert_eq!(server.received_requests().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_empty_city_is_not_found() {
+    let server = MockServer::start().await;
+    let provider = provider(&server, json(fixture("empty_city.json"))).await;
+
+    let error = provider
+        .fetch_price(&CurrencyPair::USDTe2RUB)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&error, PriceProviderError::Provider(message) if message.contains("USDTERC_TO_CASHRUB")),
+        "{:?}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn test_malformed_entry_is_schema_drift() {
+    let server = MockServer::start().await;
+    let provider = provider(&server, json(fixture("malformed_entry.json"))).await;
+
+    // The bad entry is skipped, the other pairs of the payload are still quoted
+    let price = provider
+        .fetch_price(&CurrencyPair::USDTe2RUB)
+        .await
+        .unwrap();
+    assert_eq!(price.price, 92.5);
+    assert_eq!(provider.schema_drift(), 1);
+}
+
+#[tokio::test]
+async fn test_non_json_fails_parsing() {
+    let server = MockServer::start().await;
+    let provider = provider(&server, json("<html>Maintenance</html>".to_string())).await;
+
+    let error = provider
+        .fetch_price(&CurrencyPair::USDTe2RUB)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(error, PriceProviderError::Parsing(_)),
+        "{:?}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn test_swapped_cookie_is_used_right_away() {
+    let server = MockServer::start().await;
+    let provider = provider(&server, json(fixture("success.json"))).await;
+    assert!(provider.set_credential("session=expired"));
+    let mut price_service = PriceService::new();
+    price_service.add_provider(Arc::new(provider));
+
+    // Requests with another cookie don't match the mock
+    let check = price_service.check_provider(NewLineProvider::NAME).await;
+    assert!(
+        matches!(check, Some(Err(PriceProviderError::Api(_)))),
+        "{:?}",
+        check
+    );
+
+    assert!(price_service.set_credential(NewLineProvider::NAME, "session=test"));
+    let check = price_service.check_provider(NewLineProvider::NAME).await;
+    assert!(matches!(check, Some(Ok(()))), "{:?}", check);
+    assert!(!price_service.set_credential("KrakenProvider", "key"));
+}
+
+#[tokio::test]
+async fn test_not_modified_reuses_cached_body() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/direction/"))
+        .and(header("If-None-Match", "\"v1\""))
+        .respond_with(ResponseTemplate::new(304))
+        .with_priority(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    let provider = provider(
+        &server,
+        json(fixture("success.json")).insert_header("ETag", "\"v1\""),
+    )
+    .await;
+
+    // The second request is answered with 304, the price comes from the first body
+    for _ in 0..2 {
+        let price = provider
+            .fetch_price(&CurrencyPair::USDTe2RUB)
+            .await
+            .unwrap();
+        assert_eq!(price.price, 92.5);
+    }
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2);
+    assert!(!requests[0].headers.contains_key("if-none-match"));
+}
+
+#[tokio::test]
+async fn test_forbidden_is_an_api_error() {
+    let server = MockServer::start().await;
+    let forbidden = ResponseTemplate::new(403).set_body_string("<html>Access denied</html>");
+    let provider = provider(&server, forbidden).await;
+
+    let error = provider
+        .fetch_price(&CurrencyPair::USDTe2RUB)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&error, PriceProviderError::Api(message) if message.contains("403")),
+        "{:?}",
+        error
+    );
+}